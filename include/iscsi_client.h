@@ -0,0 +1,76 @@
+/*
+ * C interface of iscsi-client-rs (cargo feature `ffi`).
+ *
+ * Build the library with
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ * and link against target/release/libiscsi_client_rs.so.
+ *
+ * All calls block. They return ISCSI_OK or a negative ISCSI_ERR_* code;
+ * iscsi_last_error() describes the last failure on the calling thread.
+ *
+ * SPDX-License-Identifier: AGPL-3.0-or-later
+ * Copyright (C) 2012-2025 Andrei Maltsev
+ */
+
+#ifndef ISCSI_CLIENT_H
+#define ISCSI_CLIENT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+enum {
+    ISCSI_OK = 0,
+    ISCSI_ERR_INVALID_ARG = -1,
+    ISCSI_ERR_IO = -2,
+    ISCSI_ERR_LOGIN = -3,
+    ISCSI_ERR_TIMEOUT = -4,
+    ISCSI_ERR_DISCONNECTED = -5,
+    ISCSI_ERR_SCSI_STATUS = -6,
+    ISCSI_ERR_PANIC = -7,
+};
+
+typedef struct IscsiSession IscsiSession;
+typedef struct IscsiLun IscsiLun;
+
+typedef struct IscsiInquiry {
+    uint8_t peripheral_qualifier;
+    uint8_t device_type;
+    uint8_t removable;
+    uint8_t version;
+    char vendor[9];
+    char product[17];
+    char revision[5];
+} IscsiInquiry;
+
+/* Message of the last failed call on this thread, or NULL. Valid until the
+ * next failing call on the same thread. */
+const char *iscsi_last_error(void);
+
+/* Connect and log in as described by a YAML config file. */
+int iscsi_session_open(const char *config_path, IscsiSession **out);
+/* Log out (waiting up to timeout_ms per connection) and free the session. */
+int iscsi_session_close(IscsiSession *session, uint32_t timeout_ms);
+
+/* lun is the 64-bit LUN field, e.g. 0x0001000000000000 for LUN 1. */
+int iscsi_lun_open(const IscsiSession *session, uint64_t lun, IscsiLun **out);
+void iscsi_lun_free(IscsiLun *lun);
+
+int iscsi_lun_test_unit_ready(const IscsiLun *lun);
+int iscsi_lun_capacity(const IscsiLun *lun, uint64_t *blocks, uint32_t *block_size);
+int iscsi_lun_inquiry(const IscsiLun *lun, IscsiInquiry *out);
+/* buf_len must equal blocks * block_size. */
+int iscsi_lun_read(const IscsiLun *lun, uint64_t lba, uint32_t blocks, uint8_t *buf,
+                   size_t buf_len);
+/* len must be a multiple of the block size. */
+int iscsi_lun_write(const IscsiLun *lun, uint64_t lba, const uint8_t *buf, size_t len);
+int iscsi_lun_flush(const IscsiLun *lun);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ISCSI_CLIENT_H */
@@ -0,0 +1,42 @@
+//! Setup commands for the iscsi-client-rs tools.
+//!
+//! ```text
+//! iscsi-client init [path] [--force]
+//! ```
+//!
+//! `init` writes a commented default configuration, including the logger
+//! section, to `path` (default `config.yaml`). Edit InitiatorName,
+//! TargetName and TargetAddress, then pass the file to `iscsi-dd`,
+//! `iscsi-nbd` or `iscsi-ublk`. An existing file is kept unless `--force`
+//! is given.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use iscsi_client_rs::cfg::cli::write_default_config;
+
+const USAGE: &str = "usage: iscsi-client init [path] [--force]";
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("init") => {},
+        _ => bail!(USAGE),
+    }
+    let mut path = None;
+    let mut force = false;
+    for arg in args {
+        match arg.as_str() {
+            "--force" | "-f" => force = true,
+            _ if arg.starts_with('-') || path.is_some() => bail!(USAGE),
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    let path = path.unwrap_or_else(|| PathBuf::from("config.yaml"));
+    write_default_config(&path, force)?;
+    eprintln!("wrote {}", path.display());
+    Ok(())
+}
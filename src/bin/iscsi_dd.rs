@@ -0,0 +1,106 @@
+//! Copy an iSCSI LUN to or from a local file or raw device.
+//!
+//! ```text
+//! iscsi-dd <config.yaml> <read|write> <lun> <path> [key=value ...]
+//! ```
+//!
+//! `read` copies the LUN into `path`, `write` copies `path` onto the LUN.
+//! `lun` is a LUN number (decimal) or the 64-bit LUN field (`0x...`).
+//! Options, sizes taking a `K`, `M` or `G` suffix:
+//!
+//! - `bs=SIZE`     bytes per READ / WRITE (default 1M)
+//! - `jobs=N`      commands in flight (default two per connection)
+//! - `lba=N`       first LBA on the LUN
+//! - `offset=SIZE` byte offset in the file
+//! - `count=SIZE`  bytes to copy (default: to the end of the source)
+//! - `resume=SIZE` bytes already copied, as printed by an interrupted run
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use iscsi_client_rs::{
+    cfg::{config::Config, logger::init_logger},
+    client::{
+        dd::{DdOptions, DdProgress, copy_file_to_lun, copy_lun_to_file},
+        device::IscsiDevice,
+    },
+    models::identifiers::Lun,
+};
+
+const USAGE: &str = "usage: iscsi-dd <config.yaml> <read|write> <lun> <path> [bs=SIZE] \
+                     [jobs=N] [lba=N] [offset=SIZE] [count=SIZE] [resume=SIZE]";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let config_path = args.next().context(USAGE)?;
+    let to_file = match args.next().as_deref() {
+        Some("read") => true,
+        Some("write") => false,
+        _ => bail!(USAGE),
+    };
+    let lun: Lun = args.next().context(USAGE)?.parse()?;
+    let path = args.next().context(USAGE)?;
+    let mut opts = DdOptions::default();
+    for arg in args {
+        let (key, value) = arg.split_once('=').context(USAGE)?;
+        match key {
+            "bs" => opts.chunk_bytes = u32::try_from(parse_size(value)?)?,
+            "jobs" => opts.parallelism = Some(value.parse()?),
+            "lba" => opts.lba = value.parse()?,
+            "offset" => opts.file_offset = parse_size(value)?,
+            "count" => opts.len = Some(parse_size(value)?),
+            "resume" => opts.resume_from = parse_size(value)?,
+            _ => bail!("unknown option {key}\n{USAGE}"),
+        }
+    }
+
+    let _log = init_logger(&config_path)?;
+    let cfg = Config::load_from_file(&config_path)?;
+    let device = IscsiDevice::open(&cfg, lun).await?;
+    let pool = Arc::clone(device.lun().session().pool());
+
+    let mut last_percent = None;
+    let mut resume_offset = opts.resume_from;
+    let progress = |p: DdProgress| {
+        resume_offset = p.resume_offset;
+        let percent = (p.bytes_done * 100)
+            .checked_div(p.bytes_total)
+            .unwrap_or(100);
+        if last_percent != Some(percent) {
+            last_percent = Some(percent);
+            eprint!("\r{percent:3}% {} / {} bytes", p.bytes_done, p.bytes_total);
+        }
+    };
+    let copied = if to_file {
+        copy_lun_to_file(device.lun(), &path, &opts, progress).await
+    } else {
+        copy_file_to_lun(&path, device.lun(), &opts, progress).await
+    };
+    eprintln!();
+    match &copied {
+        Ok(bytes) => eprintln!("{bytes} bytes copied"),
+        Err(_) => eprintln!("copy stopped; continue with resume={resume_offset}"),
+    }
+    pool.shutdown_gracefully(Duration::from_secs(10)).await?;
+    copied?;
+    Ok(())
+}
+
+/// Parse a byte count with an optional `K`, `M` or `G` (binary) suffix.
+fn parse_size(s: &str) -> Result<u64> {
+    let (digits, shift) = match s.as_bytes().last() {
+        Some(b'K' | b'k') => (&s[..s.len() - 1], 10),
+        Some(b'M' | b'm') => (&s[..s.len() - 1], 20),
+        Some(b'G' | b'g') => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let n: u64 = digits
+        .parse()
+        .with_context(|| format!("invalid size {s}"))?;
+    n.checked_mul(1 << shift)
+        .with_context(|| format!("size {s} overflows"))
+}
@@ -0,0 +1,60 @@
+//! Serve an iSCSI LUN over NBD.
+//!
+//! ```text
+//! iscsi-nbd <config.yaml> [lun] [listen-addr]
+//! ```
+//!
+//! `lun` is a LUN number (decimal) or the 64-bit LUN field (`0x...`),
+//! default `0`; `listen-addr` defaults to `127.0.0.1:10809`. Stop with Ctrl-C;
+//! the sessions are logged out before exiting.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use iscsi_client_rs::{
+    cfg::{config::Config, logger::init_logger},
+    client::device::IscsiDevice,
+    export::nbd::{NBD_DEFAULT_PORT, NbdServer},
+    models::identifiers::Lun,
+};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let config_path = args
+        .next()
+        .context("usage: iscsi-nbd <config.yaml> [lun] [listen-addr]")?;
+    let lun = args.next().as_deref().map_or(Ok(Lun::ZERO), str::parse)?;
+    let listen = args
+        .next()
+        .unwrap_or_else(|| format!("127.0.0.1:{NBD_DEFAULT_PORT}"));
+
+    let _log = init_logger(&config_path)?;
+    let cfg = Config::load_from_file(&config_path)?;
+    let device = IscsiDevice::open(&cfg, lun).await?;
+    let pool = Arc::clone(device.lun().session().pool());
+    eprintln!(
+        "serving {lun} ({} bytes, {}-byte blocks) on nbd://{listen}",
+        device.size(),
+        device.block_size()
+    );
+
+    let listener = TcpListener::bind(&listen)
+        .await
+        .with_context(|| format!("cannot listen on {listen}"))?;
+    let cancel = CancellationToken::new();
+    let server = Arc::new(NbdServer::new(device));
+    let serving = tokio::spawn(server.serve(listener, cancel.clone()));
+
+    tokio::signal::ctrl_c().await?;
+    cancel.cancel();
+    serving.await??;
+    pool.shutdown_gracefully(Duration::from_secs(10))
+        .await
+        .map_err(Into::into)
+}
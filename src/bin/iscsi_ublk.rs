@@ -0,0 +1,50 @@
+//! Expose an iSCSI LUN as a local ublk block device.
+//!
+//! ```text
+//! iscsi-ublk <config.yaml> [lun] [queues]
+//! ```
+//!
+//! `lun` is a LUN number (decimal) or the 64-bit LUN field (`0x...`),
+//! default `0`. The
+//! device path is printed once it is live. Needs root and the `ublk_drv`
+//! module; stop with Ctrl-C.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use iscsi_client_rs::{
+    cfg::{config::Config, logger::init_logger},
+    client::device::IscsiDevice,
+    export::ublk::UblkServer,
+    models::identifiers::Lun,
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let config_path = args
+        .next()
+        .context("usage: iscsi-ublk <config.yaml> [lun] [queues]")?;
+    let lun = args.next().as_deref().map_or(Ok(Lun::ZERO), str::parse)?;
+    let queues: u16 = args
+        .next()
+        .map_or(Ok(1), |q| q.parse())
+        .context("invalid queue count")?;
+
+    let _log = init_logger(&config_path)?;
+    let cfg = Config::load_from_file(&config_path)?;
+    let device = IscsiDevice::open(&cfg, lun).await?;
+    let pool = Arc::clone(device.lun().session().pool());
+
+    let ublk = UblkServer::new(device).with_queues(queues).start().await?;
+    eprintln!("{lun} is available as {}", ublk.path().display());
+
+    tokio::signal::ctrl_c().await?;
+    ublk.stop().await?;
+    pool.shutdown_gracefully(Duration::from_secs(10))
+        .await
+        .map_err(Into::into)
+}
@@ -0,0 +1,57 @@
+//! Plumbing shared by the blocking bindings (features `ffi` and `python`):
+//! the tokio runtime their calls run on and the coarse error classes they
+//! report to the caller.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use tokio::runtime::Runtime;
+
+use crate::error::IscsiError;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// The runtime blocking calls run on, started on first use.
+pub(crate) fn runtime() -> Result<&'static Runtime> {
+    if let Some(rt) = RUNTIME.get() {
+        return Ok(rt);
+    }
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("iscsi-client")
+        .build()
+        .context("cannot start the tokio runtime")?;
+    Ok(RUNTIME.get_or_init(|| rt))
+}
+
+/// What a binding tells its caller about a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ErrorClass {
+    /// The target rejected the login.
+    Login,
+    /// A command or login timed out.
+    Timeout,
+    /// The connection was lost, shut down or cancelled.
+    Disconnected,
+    /// A command completed with a SCSI status other than GOOD.
+    Scsi,
+    /// Anything else.
+    Other,
+}
+
+impl ErrorClass {
+    pub(crate) fn of(error: &IscsiError) -> Self {
+        match error {
+            IscsiError::LoginRejected(_)
+            | IscsiError::SessionLogin(_)
+            | IscsiError::TargetLogin(_) => Self::Login,
+            IscsiError::Timeout(_) => Self::Timeout,
+            IscsiError::Disconnected(_) | IscsiError::Cancelled(_) => Self::Disconnected,
+            IscsiError::Scsi(_) | IscsiError::ReservationConflict(_) => Self::Scsi,
+            _ => Self::Other,
+        }
+    }
+}
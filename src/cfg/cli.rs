@@ -1,9 +1,15 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::path::{Path, PathBuf};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+
+use crate::cfg::{config::Config, logger};
 
 pub fn resolve_config_path(rel: &str) -> Result<PathBuf> {
     let p = Path::new(rel);
@@ -22,3 +28,41 @@ pub fn resolve_config_path(rel: &str) -> Result<PathBuf> {
 
     Ok(canon)
 }
+
+/// The default configuration followed by the default logger section, as
+/// one file the command-line tools read both from.
+pub fn default_config_file() -> String {
+    format!(
+        "{}\n{}",
+        Config::default_template(),
+        logger::default_template()
+    )
+}
+
+/// Write [`default_config_file`] to `path`. An existing file is only
+/// replaced with `force`.
+pub fn write_default_config(path: &Path, force: bool) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("cannot create {}", dir.display()))?;
+    }
+    // create_new checks and creates in one step, so a file appearing
+    // meanwhile is not overwritten.
+    let mut file = match OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .create_new(!force)
+        .open(path)
+    {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            bail!(
+                "{} already exists (use --force to overwrite it)",
+                path.display()
+            )
+        },
+        file => file.with_context(|| format!("cannot create {}", path.display()))?,
+    };
+    file.write_all(default_config_file().as_bytes())
+        .with_context(|| format!("cannot write {}", path.display()))
+}
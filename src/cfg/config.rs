@@ -1,12 +1,31 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::{collections::HashMap, fs, path::Path, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::{Context, Result, ensure};
-use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-use crate::cfg::enums::{Digest, SessionType, YesNo};
+use crate::{
+    cfg::{
+        enums::{ChapAlgorithm, Digest, IoBackend, SessionType, YesNo},
+        overrides::apply_overrides,
+        secrets::resolve_secret,
+        validate::ConfigValidationError,
+    },
+    client::{
+        load_balance::LoadBalancePolicy, reconnect::ReconnectPolicy,
+        remap::MediumErrorPolicy, retry::RetryPolicy, socks5::Socks5Proxy,
+        throttle::ThrottlePolicy, tls::TlsSettings,
+    },
+    models::identifiers::Isid,
+};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
@@ -15,6 +34,109 @@ pub struct Config {
     pub login: LoginConfig,
     /// Implementation/runtime parameters that live outside the iSCSI protocol.
     pub runtime: RuntimeConfig,
+    /// Targets of a multi-target configuration, each inheriting `login`.
+    /// When empty, `login.identity.TargetName` is the only target.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<TargetConfig>,
+}
+
+/// One target of a multi-target configuration. Sections left out inherit
+/// the top-level `login`; a section given replaces it as a whole.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TargetConfig {
+    /// TargetName of this target, also the name the pool addresses it by.
+    #[serde(rename = "TargetName")]
+    pub name: String,
+    /// Preferred address of this target; with `Portals` it replaces the
+    /// inherited portals.
+    #[serde(
+        default,
+        rename = "TargetAddress",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub target_address: Option<String>,
+    /// Further portals of this target.
+    #[serde(default, rename = "Portals", skip_serializing_if = "Vec::is_empty")]
+    pub portals: Vec<String>,
+    /// Authentication for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthConfig>,
+    /// Digest preferences for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<Integrity>,
+    /// Read-side flow limits for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow: Option<Flow>,
+    /// Write-side flow control for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_flow: Option<WriteFlow>,
+    /// Ordering preferences for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ordering: Option<Ordering>,
+    /// Error recovery level for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery: Option<Recovery>,
+    /// Timers for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timers: Option<Timers>,
+    /// MaxConnections cap for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<Limits>,
+}
+
+impl TargetConfig {
+    /// Target `name`, inheriting everything else.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            target_address: None,
+            portals: Vec::new(),
+            auth: None,
+            integrity: None,
+            flow: None,
+            write_flow: None,
+            ordering: None,
+            recovery: None,
+            timers: None,
+            limits: None,
+        }
+    }
+
+    /// `login` with this target's name and the sections it sets.
+    pub fn apply_to(&self, login: &LoginConfig) -> LoginConfig {
+        let mut login = login.clone();
+        login.identity.target_name = self.name.clone();
+        if self.target_address.is_some() || !self.portals.is_empty() {
+            login.transport.target_address =
+                self.target_address.clone().unwrap_or_default();
+            login.transport.portals = self.portals.clone();
+        }
+        if let Some(auth) = &self.auth {
+            login.auth = auth.clone();
+        }
+        if let Some(integrity) = &self.integrity {
+            login.integrity = integrity.clone();
+        }
+        if let Some(flow) = &self.flow {
+            login.flow = flow.clone();
+        }
+        if let Some(write_flow) = &self.write_flow {
+            login.write_flow = write_flow.clone();
+        }
+        if let Some(ordering) = &self.ordering {
+            login.ordering = ordering.clone();
+        }
+        if let Some(recovery) = &self.recovery {
+            login.recovery = recovery.clone();
+        }
+        if let Some(timers) = &self.timers {
+            login.timers = timers.clone();
+        }
+        if let Some(limits) = &self.limits {
+            login.limits = limits.clone();
+        }
+        login
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -66,17 +188,67 @@ pub struct Identity {
     #[serde(default, rename = "TargetName")]
     /// Required for Normal sessions; ignored during Discovery.
     pub target_name: String,
+
+    #[serde(default, rename = "Isid", skip_serializing_if = "Option::is_none")]
+    /// Fixed ISID (12 hex digits) for the sessions logged in from this
+    /// config. Keeping it stable across restarts lets a new login reinstate
+    /// a session left behind on the target; random when unset.
+    pub isid: Option<String>,
 }
 
 /// Transport hints that are stored locally but never sent over the wire.
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct TransportHints {
     #[serde(default, rename = "TargetAddress")]
-    /// Preferred target address.
+    /// Preferred target address: `host[:port]`, `[ipv6]:port` or
+    /// `unix:///path/to/socket`.
     pub target_address: String,
     #[serde(default, rename = "TargetPortalGroupTag")]
     /// Target portal group tag to probe first.
     pub portal_group_tag: u16,
+    #[serde(default, rename = "Portals", skip_serializing_if = "Vec::is_empty")]
+    /// Further portals of the same target, used after `TargetAddress`.
+    pub portals: Vec<String>,
+    #[serde(
+        default,
+        rename = "LocalAddress",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// Local IP the outgoing TCP connections are bound to (any port). Only
+    /// target addresses of the same family are tried.
+    pub local_address: Option<IpAddr>,
+    #[serde(
+        default,
+        rename = "BindInterface",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// Network interface the connections are bound to (`SO_BINDTODEVICE`,
+    /// Linux only).
+    pub bind_interface: Option<String>,
+    #[serde(
+        default,
+        rename = "Socks5Proxy",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// SOCKS5 proxy (e.g. a jump host) through which the target is reached.
+    pub socks5_proxy: Option<Socks5Proxy>,
+    #[serde(default, rename = "Tls", skip_serializing_if = "Option::is_none")]
+    /// Wrap the connection in TLS (requires the `tls` feature).
+    pub tls: Option<TlsSettings>,
+}
+
+impl TransportHints {
+    /// All configured portals in preference order: `TargetAddress` first,
+    /// then `Portals`, without duplicates or empty entries.
+    pub fn all_portals(&self) -> Vec<String> {
+        let mut out: Vec<String> = Vec::with_capacity(1 + self.portals.len());
+        for portal in std::iter::once(&self.target_address).chain(&self.portals) {
+            if !portal.is_empty() && !out.contains(portal) {
+                out.push(portal.clone());
+            }
+        }
+        out
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -87,15 +259,196 @@ pub enum AuthConfig {
     None,
     #[serde(rename = "CHAP")]
     Chap(ChapConfig),
+    #[serde(rename = "SRP")]
+    Srp(SrpConfig),
+}
+
+impl AuthConfig {
+    /// Read the secrets the credentials reference through files or
+    /// environment variables.
+    pub fn resolve_secrets(&mut self, base_dir: &Path) -> Result<()> {
+        match self {
+            Self::None => Ok(()),
+            Self::Chap(chap) => chap.resolve_secrets(base_dir),
+            Self::Srp(srp) => srp.resolve_secrets(base_dir),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 /// CHAP credentials used during challenge-response authentication.
 pub struct ChapConfig {
-    /// Username advertised via CHAP_N.
+    /// Username advertised via CHAP_N; may be left to a secret provider.
+    #[serde(default)]
     pub username: String,
-    /// Shared secret used to generate CHAP_R.
+    /// Shared secret used to generate CHAP_R. Leave it out and set
+    /// `secret_file` or `secret_env` instead to keep it out of the file.
+    #[serde(default)]
     pub secret: String,
+    /// File holding `secret`, relative to the config file. On Unix it must
+    /// not be accessible by group or others.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_file: Option<PathBuf>,
+    /// Environment variable holding `secret`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_env: Option<String>,
+    /// Name the target must answer with (CHAP_N) in mutual CHAP; any name
+    /// is accepted when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_username: Option<String>,
+    /// Secret the target computes its CHAP_R from. Setting it enables
+    /// mutual CHAP: the initiator challenges the target and fails the login
+    /// unless the answer matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_secret: Option<String>,
+    /// File holding `target_secret`, like `secret_file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_secret_file: Option<PathBuf>,
+    /// Environment variable holding `target_secret`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_secret_env: Option<String>,
+    /// Offer MD5 after SHA3-256 and SHA-256 in CHAP_A, for targets that
+    /// know nothing stronger.
+    #[serde(default = "default_md5_fallback")]
+    pub md5_fallback: bool,
+    /// Shortest CHAP_C accepted from the target in mutual CHAP, in bytes.
+    #[serde(default = "default_min_challenge_len")]
+    pub min_challenge_len: usize,
+    /// Refuse a mutual CHAP config whose target secret equals `secret`
+    /// (RFC 7143 § 12.1.3), as a target could then reflect the initiator's
+    /// own challenge.
+    #[serde(default)]
+    pub distinct_secrets: bool,
+}
+
+fn default_md5_fallback() -> bool {
+    true
+}
+
+fn default_min_challenge_len() -> usize {
+    16
+}
+
+impl ChapConfig {
+    /// One-way CHAP credentials.
+    pub fn new(username: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            secret: secret.into(),
+            secret_file: None,
+            secret_env: None,
+            target_username: None,
+            target_secret: None,
+            target_secret_file: None,
+            target_secret_env: None,
+            md5_fallback: default_md5_fallback(),
+            min_challenge_len: default_min_challenge_len(),
+            distinct_secrets: false,
+        }
+    }
+
+    /// Algorithms offered in CHAP_A, strongest first.
+    pub fn algorithms(&self) -> Vec<ChapAlgorithm> {
+        ChapAlgorithm::BY_STRENGTH
+            .into_iter()
+            .filter(|&a| a != ChapAlgorithm::Md5 || self.md5_fallback)
+            .collect()
+    }
+
+    /// Also authenticate the target (mutual CHAP) as `username` with
+    /// `secret`.
+    pub fn with_target(
+        mut self,
+        username: impl Into<String>,
+        secret: impl Into<String>,
+    ) -> Self {
+        self.target_username = Some(username.into());
+        self.target_secret = Some(secret.into());
+        self
+    }
+
+    /// Whether the target is authenticated too.
+    #[inline]
+    pub fn is_mutual(&self) -> bool {
+        self.target_secret.is_some()
+    }
+
+    /// Read the secrets referenced through `*_file` / `*_env`; relative
+    /// files are looked up in `base_dir`.
+    pub fn resolve_secrets(&mut self, base_dir: &Path) -> Result<()> {
+        let inline = (!self.secret.is_empty()).then_some(self.secret.as_str());
+        if let Some(secret) = resolve_secret(
+            "CHAP secret",
+            inline,
+            self.secret_file.as_deref(),
+            self.secret_env.as_deref(),
+            base_dir,
+        )? {
+            self.secret = secret;
+        }
+        if let Some(secret) = resolve_secret(
+            "CHAP target_secret",
+            self.target_secret.as_deref(),
+            self.target_secret_file.as_deref(),
+            self.target_secret_env.as_deref(),
+            base_dir,
+        )? {
+            self.target_secret = Some(secret);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// SRP credentials (RFC 2945, SHA-1) for targets that require SRP instead of
+/// CHAP. The target keeps only the salt and the verifier derived from them,
+/// see [`srp_verifier`](crate::state_machine::login::login_srp::srp_verifier).
+pub struct SrpConfig {
+    /// Username advertised via SRP_U.
+    pub username: String,
+    /// Password the verifier was derived from. Leave it out and set
+    /// `password_file` or `password_env` instead to keep it out of the file.
+    #[serde(default)]
+    pub password: String,
+    /// File holding `password`, like [`ChapConfig::secret_file`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<PathBuf>,
+    /// Environment variable holding `password`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_env: Option<String>,
+    /// Ask the target to prove it knows the verifier too (TargetAuth=Yes,
+    /// checked through SRP_HM).
+    #[serde(default)]
+    pub target_auth: bool,
+}
+
+impl SrpConfig {
+    /// SRP credentials without target authentication.
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+            password_file: None,
+            password_env: None,
+            target_auth: false,
+        }
+    }
+
+    /// Read the password referenced through `password_file` /
+    /// `password_env`; a relative file is looked up in `base_dir`.
+    pub fn resolve_secrets(&mut self, base_dir: &Path) -> Result<()> {
+        let inline = (!self.password.is_empty()).then_some(self.password.as_str());
+        if let Some(password) = resolve_secret(
+            "SRP password",
+            inline,
+            self.password_file.as_deref(),
+            self.password_env.as_deref(),
+            base_dir,
+        )? {
+            self.password = password;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -194,10 +547,11 @@ pub struct Extensions {
     pub custom: HashMap<String, String>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[serde(rename_all = "PascalCase")]
 /// RFC7143 TaskReporting values.
 pub enum TaskReporting {
+    #[default]
     RFC3720,
     ResponseFence,
     FastAbort,
@@ -211,9 +565,13 @@ pub struct RuntimeConfig {
     pub max_sessions: u32,
 
     #[serde(rename = "TimeoutConnection", with = "serde_secs")]
-    /// Timeout for TCP connect and per-I/O read/write operations.
+    /// Fallback for every entry of `Timeouts` that is left unset.
     pub timeout_connection: Duration,
 
+    #[serde(rename = "Timeouts", default)]
+    /// Per-phase timeouts (TCP connect, login, data-phase I/O, TMF).
+    pub timeouts: Timeouts,
+
     #[serde(rename = "ResponseQueueCapacity")]
     /// Buffered response PDUs allowed per in-flight command.
     pub response_queue_capacity: usize,
@@ -221,58 +579,352 @@ pub struct RuntimeConfig {
     #[serde(rename = "MaxConnectionRecoveryAttempts")]
     /// Number of retries after a poisoned connection's initial failure.
     pub max_connection_recovery_attempts: usize,
+
+    #[serde(rename = "LoginBusyRetries", default = "default_login_busy_retries")]
+    /// Login retries after the target reports busy / resource unavailable,
+    /// each one after `DefaultTime2Wait`. `0` fails on the first rejection.
+    pub login_busy_retries: u32,
+
+    #[serde(rename = "LoginParallelism", default = "default_login_parallelism")]
+    /// Sessions `login_sessions_from_cfg` logs in at the same time.
+    pub login_parallelism: usize,
+
+    #[serde(rename = "CommandTimeout", default, with = "serde_secs")]
+    /// Per-command timeout in seconds; on expiry the task is aborted with
+    /// ABORT TASK and the call fails. `0` (default) waits forever.
+    pub command_timeout: Duration,
+
+    #[serde(rename = "LunQueueDepth", default)]
+    /// Maximum outstanding commands per (session, LUN); excess submissions
+    /// wait in FIFO order. `0` (default) means unlimited.
+    pub lun_queue_depth: usize,
+
+    #[serde(rename = "StatSnAckThreshold", default)]
+    /// Send an immediate NOP-Out acknowledging ExpStatSN once this many
+    /// received StatSNs have not been reported back to the target. `0`
+    /// (default) disables the automatic acknowledgment.
+    pub stat_sn_ack_threshold: u32,
+
+    #[serde(rename = "Retry", default)]
+    /// Pool-wide retry policy for transient SCSI statuses (BUSY, TASK SET
+    /// FULL, selected Unit Attentions). Disabled unless `MaxAttempts > 1`.
+    pub retry: RetryPolicy,
+
+    #[serde(rename = "FailbackInterval", default, with = "serde_secs")]
+    /// How often `Multipath` probes a more preferred portal while running on
+    /// a fallback one. `0` (default) disables failback.
+    pub failback_interval: Duration,
+
+    #[serde(rename = "Reconnect", default)]
+    /// Background re-login of connections whose transport died. Disabled
+    /// unless `MaxAttempts > 0`.
+    pub reconnect: ReconnectPolicy,
+
+    #[serde(rename = "IoBackend", default)]
+    /// Socket I/O backend for TCP connections: `Tokio` (default) or `IoUring`
+    /// (Linux, feature `io-uring`).
+    pub io_backend: IoBackend,
+
+    #[serde(rename = "Tcp", default)]
+    /// Socket options applied to every TCP connection before it connects.
+    pub tcp: TcpTuning,
+
+    #[serde(rename = "Throttle", default)]
+    /// Per-connection bandwidth caps for Data-Out and Data-In payload.
+    pub throttle: ThrottlePolicy,
+
+    #[serde(rename = "LoadBalance", default)]
+    /// How commands not pinned to a CID are spread over the connections of
+    /// a session.
+    pub load_balance: LoadBalancePolicy,
+
+    #[serde(rename = "MediumError", default)]
+    /// What LUN handle reads and writes do on a MEDIUM ERROR: `Off`
+    /// (default), `Record` the bad LBA, or `Reassign` it and retry once.
+    pub medium_error: MediumErrorPolicy,
+}
+
+fn default_login_busy_retries() -> u32 {
+    3
+}
+
+fn default_login_parallelism() -> usize {
+    8
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+/// Per-phase timeouts in seconds. Unset entries fall back to
+/// `TimeoutConnection`.
+pub struct Timeouts {
+    #[serde(
+        rename = "Connect",
+        default,
+        with = "serde_secs_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// TCP connect to the target portal.
+    pub connect: Option<Duration>,
+
+    #[serde(
+        rename = "Login",
+        default,
+        with = "serde_secs_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// Whole login phase, from the first Login Request to Full Feature.
+    pub login: Option<Duration>,
+
+    #[serde(
+        rename = "Io",
+        default,
+        with = "serde_secs_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// Inactivity limit for a single socket read/write once connected.
+    pub io: Option<Duration>,
+
+    #[serde(
+        rename = "Tmf",
+        default,
+        with = "serde_secs_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// Wait for a Task Management Function response.
+    pub tmf: Option<Duration>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+/// TCP socket options. Zero sizes and durations keep the OS defaults.
+pub struct TcpTuning {
+    #[serde(rename = "NoDelay")]
+    /// `TCP_NODELAY` (default on): send PDUs without Nagle delay.
+    pub nodelay: bool,
+
+    #[serde(rename = "RecvBufferSize")]
+    /// `SO_RCVBUF` in bytes. Set before connecting so that the TCP window
+    /// scale is negotiated for it.
+    pub recv_buffer_size: usize,
+
+    #[serde(rename = "SendBufferSize")]
+    /// `SO_SNDBUF` in bytes.
+    pub send_buffer_size: usize,
+
+    #[serde(rename = "KeepaliveIdle", with = "serde_secs")]
+    /// Idle time before TCP keep-alive probes start; `0` leaves
+    /// `SO_KEEPALIVE` off.
+    pub keepalive_idle: Duration,
+
+    #[serde(rename = "KeepaliveInterval", with = "serde_secs")]
+    /// Time between keep-alive probes.
+    pub keepalive_interval: Duration,
+
+    #[serde(rename = "KeepaliveRetries")]
+    /// Unanswered probes before the connection is dropped.
+    pub keepalive_retries: u32,
+
+    #[serde(rename = "Dscp", skip_serializing_if = "Option::is_none")]
+    /// DiffServ code point (0-63) written into the IPv4 TOS / IPv6 traffic
+    /// class of outgoing packets.
+    pub dscp: Option<u8>,
+}
+
+impl Default for TcpTuning {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            recv_buffer_size: 0,
+            send_buffer_size: 0,
+            keepalive_idle: Duration::ZERO,
+            keepalive_interval: Duration::ZERO,
+            keepalive_retries: 0,
+            dscp: None,
+        }
+    }
+}
+
+impl Identity {
+    /// ISID for the `index`-th session: the configured `Isid` with its
+    /// qualifier advanced by `index`, or a random one when unset.
+    pub fn session_isid(&self, index: u16) -> Result<Isid> {
+        match &self.isid {
+            Some(isid) => Ok(isid.parse::<Isid>()?.with_qualifier_offset(index)),
+            None => Ok(Isid::generate().0),
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Timeout for the TCP connect.
+    #[inline]
+    pub fn connect_timeout(&self) -> Duration {
+        self.timeouts.connect.unwrap_or(self.timeout_connection)
+    }
+
+    /// Timeout for the whole login phase.
+    #[inline]
+    pub fn login_timeout(&self) -> Duration {
+        self.timeouts.login.unwrap_or(self.timeout_connection)
+    }
+
+    /// Timeout for a single socket read/write.
+    #[inline]
+    pub fn io_timeout(&self) -> Duration {
+        self.timeouts.io.unwrap_or(self.timeout_connection)
+    }
+
+    /// Timeout for a Task Management Function response.
+    #[inline]
+    pub fn tmf_timeout(&self) -> Duration {
+        self.timeouts.tmf.unwrap_or(self.timeout_connection)
+    }
+}
+
+/// File format of a configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Format named by the extension of `path`: `.toml`, `.json`, else
+    /// YAML.
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("toml") => Self::Toml,
+            Some("json") => Self::Json,
+            _ => Self::Yaml,
+        }
+    }
+
+    /// Parses `s` in this format.
+    pub fn parse<T: DeserializeOwned>(self, s: &str) -> Result<T> {
+        match self {
+            Self::Yaml => serde_yaml::from_str(s).context("failed to parse config YAML"),
+            Self::Toml => toml::from_str(s).context("failed to parse config TOML"),
+            Self::Json => serde_json::from_str(s).context("failed to parse config JSON"),
+        }
+    }
+}
+
+impl fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+        })
+    }
 }
 
 impl Config {
-    /// Loads the configuration from YAML, validates it, and returns the
+    /// A commented YAML configuration holding every key with its default
+    /// value and the optional ones commented out; it loads as is against a
+    /// local target. `iscsi-client init` writes it together with
+    /// [`logger::default_template`](crate::cfg::logger::default_template).
+    pub fn default_template() -> &'static str {
+        include_str!("default_config.yaml")
+    }
+
+    /// Loads the configuration in the format its extension names (see
+    /// [`ConfigFormat::from_path`]), applies the `ISCSI__*` environment
+    /// overrides, resolves referenced secrets, validates it, and returns the
     /// ready-to-use value.
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::load_from_file_as(path, ConfigFormat::from_path(path))
+    }
+
+    /// Same as [`Config::load_from_file`] with an explicit format.
+    pub fn load_from_file_as<P: AsRef<Path>>(
+        path: P,
+        format: ConfigFormat,
+    ) -> Result<Self> {
+        let path = path.as_ref();
         let s = fs::read_to_string(path)?;
-        let mut cfg: Config =
-            serde_yaml::from_str(&s).context("failed to parse config YAML")?;
+        let mut cfg: Config = format.parse(&s)?;
+        cfg.apply_env_overrides()?;
+        cfg.resolve_secrets(path.parent().unwrap_or(Path::new(".")))?;
         cfg.validate_and_normalize()?;
         Ok(cfg)
     }
 
-    /// Validates invariants and normalizes derived fields.
-    pub fn validate_and_normalize(&mut self) -> Result<()> {
-        // Discovery sessions always use MaxConnections=1 and ERL=0.
-        if self.login.identity.session_type.is_discovery() {
-            if self.login.limits.max_connections != 1 {
-                self.login.limits.max_connections = 1;
-            }
-            if self.login.recovery.error_recovery_level != 0 {
-                self.login.recovery.error_recovery_level = 0;
+    /// Applies the `ISCSI__*` environment variables, see
+    /// [`crate::cfg::overrides`].
+    pub fn apply_env_overrides(&mut self) -> Result<()> {
+        self.apply_overrides(
+            std::env::vars_os().filter_map(|(k, v)| {
+                Some((k.into_string().ok()?, v.into_string().ok()?))
+            }),
+        )
+    }
+
+    /// Applies `ISCSI__*` overrides given as `(name, value)` pairs; other
+    /// names are ignored.
+    pub fn apply_overrides<I>(&mut self, vars: I) -> Result<()>
+    where I: IntoIterator<Item = (String, String)> {
+        apply_overrides(self, vars)
+    }
+
+    /// Resolves the secrets the auth config references through files or
+    /// environment variables, see [`ChapConfig::resolve_secrets`] and
+    /// [`SrpConfig::resolve_secrets`].
+    pub fn resolve_secrets(&mut self, base_dir: &Path) -> Result<()> {
+        self.login.auth.resolve_secrets(base_dir)?;
+        for target in &mut self.targets {
+            if let Some(auth) = &mut target.auth {
+                auth.resolve_secrets(base_dir)
+                    .with_context(|| format!("target {}", target.name))?;
             }
         }
+        Ok(())
+    }
 
-        // Ensure the declared iSCSIProtocolLevel is valid if provided.
-        if let Some(lv) = self.login.extensions.iscsi_protocol_level {
-            ensure!(lv >= 1, "iSCSIProtocolLevel must be >= 1");
+    /// One single-target config per configured target, in configuration
+    /// order, or `self` alone when `targets` is empty.
+    pub fn target_configs(&self) -> Vec<Config> {
+        if self.targets.is_empty() {
+            return vec![self.clone()];
         }
+        self.targets
+            .iter()
+            .map(|target| Config {
+                login: target.apply_to(&self.login),
+                runtime: self.runtime.clone(),
+                targets: Vec::new(),
+            })
+            .collect()
+    }
 
-        // Mandatory base fields.
-        ensure!(
-            !self.login.identity.initiator_name.is_empty(),
-            "InitiatorName must not be empty"
-        );
-        if self.login.identity.session_type.is_normal() {
-            ensure!(
-                !self.login.identity.target_name.is_empty(),
-                "TargetName is required for Normal session"
-            );
-        }
+    /// Single-target config of the target named `target_name`.
+    pub fn target_config(&self, target_name: &str) -> Option<Config> {
+        self.target_configs()
+            .into_iter()
+            .find(|cfg| cfg.login.identity.target_name == target_name)
+    }
 
-        ensure!(
-            self.login.limits.max_connections >= 1,
-            "MaxConnections must be >= 1"
-        );
-        ensure!(self.runtime.max_sessions >= 1, "MaxSessions must be >= 1");
-        ensure!(
-            self.runtime.response_queue_capacity >= 1,
-            "ResponseQueueCapacity must be >= 1"
-        );
+    /// Normalizes derived fields, then validates the config. Fails with a
+    /// [`ConfigValidationError`] listing every violation, see
+    /// [`Config::violations`].
+    pub fn validate_and_normalize(&mut self) -> Result<()> {
+        // Discovery sessions always use MaxConnections=1 and ERL=0.
+        if self.login.identity.session_type.is_discovery() {
+            self.login.limits.max_connections = 1;
+            self.login.recovery.error_recovery_level = 0;
+        }
 
+        let violations = self.violations();
+        if !violations.is_empty() {
+            return Err(ConfigValidationError { violations }.into());
+        }
         Ok(())
     }
 }
@@ -335,14 +987,28 @@ pub fn login_keys_security(cfg: &Config) -> Vec<u8> {
         ),
         (
             "AuthMethod",
-            Some(match cfg.login.auth {
+            Some(match &cfg.login.auth {
                 AuthConfig::None => "None".to_string(),
+                // Mutual CHAP must not let the target fall back to None.
+                AuthConfig::Chap(chap) if chap.is_mutual() => "CHAP".to_string(),
                 AuthConfig::Chap(_) => "CHAP,None".to_string(),
+                AuthConfig::Srp(srp) if srp.target_auth => "SRP".to_string(),
+                AuthConfig::Srp(_) => "SRP,None".to_string(),
             }),
         ),
     ])
 }
 
+/// Builds the CHAP_A offer: the algorithms of `chap`, strongest first.
+pub fn login_keys_chap_algorithms(chap: &ChapConfig) -> Vec<u8> {
+    let codes = chap
+        .algorithms()
+        .iter()
+        .map(|a| a.code().to_string())
+        .collect::<Vec<_>>();
+    build_kv_sorted([("CHAP_A", Some(codes.join(",")))])
+}
+
 /// Builds the initiator response for a CHAP challenge (CHAP_N / CHAP_R only).
 pub fn login_keys_chap_response(user: &str, chap_r_upper_hex_with_0x: &str) -> Vec<u8> {
     build_kv_sorted([
@@ -351,6 +1017,49 @@ pub fn login_keys_chap_response(user: &str, chap_r_upper_hex_with_0x: &str) -> V
     ])
 }
 
+/// Builds the initiator response for a CHAP challenge together with the
+/// initiator's own challenge to the target (mutual CHAP): CHAP_N / CHAP_R
+/// plus CHAP_I / CHAP_C.
+pub fn login_keys_chap_mutual_response(
+    user: &str,
+    chap_r_upper_hex_with_0x: &str,
+    chap_i: u8,
+    chap_c_upper_hex_with_0x: &str,
+) -> Vec<u8> {
+    build_kv_sorted([
+        ("CHAP_N", Some(user.to_string())),
+        ("CHAP_R", Some(chap_r_upper_hex_with_0x.to_string())),
+        ("CHAP_I", Some(chap_i.to_string())),
+        ("CHAP_C", Some(chap_c_upper_hex_with_0x.to_string())),
+    ])
+}
+
+/// Builds the first SRP request: the username and whether the target must
+/// authenticate itself.
+pub fn login_keys_srp_user(srp: &SrpConfig) -> Vec<u8> {
+    build_kv_sorted([
+        ("SRP_U", Some(srp.username.clone())),
+        (
+            "TargetAuth",
+            Some(if srp.target_auth { "Yes" } else { "No" }.to_string()),
+        ),
+    ])
+}
+
+/// Builds the SRP request carrying the initiator's public value and the
+/// group it chose from the target's list.
+pub fn login_keys_srp_public(group: &str, a_hex_with_0x: &str) -> Vec<u8> {
+    build_kv_sorted([
+        ("SRP_A", Some(a_hex_with_0x.to_string())),
+        ("SRP_GROUP", Some(group.to_string())),
+    ])
+}
+
+/// Builds the SRP request carrying the initiator's evidence SRP_M.
+pub fn login_keys_srp_evidence(m_hex_with_0x: &str) -> Vec<u8> {
+    build_kv_sorted([("SRP_M", Some(m_hex_with_0x.to_string()))])
+}
+
 /// Builds the Operational Negotiation payload (only operational keys). Ordering
 /// is canonical and unset/empty values are skipped.
 pub fn login_keys_operational(cfg: &Config) -> Vec<u8> {
@@ -445,3 +1154,25 @@ mod serde_secs {
         Ok(Duration::from_secs(secs))
     }
 }
+
+/// Same as [`serde_secs`] for optional durations.
+mod serde_secs_opt {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        d: &Option<Duration>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        match d {
+            Some(d) => s.serialize_some(&d.as_secs()),
+            None => s.serialize_none(),
+        }
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u64>::deserialize(d)?.map(Duration::from_secs))
+    }
+}
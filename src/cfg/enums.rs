@@ -90,3 +90,77 @@ impl fmt::Display for Digest {
         })
     }
 }
+
+/// Socket I/O backend of a connection.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoBackend {
+    /// Tokio's readiness-based socket I/O.
+    #[default]
+    #[serde(rename = "Tokio", alias = "tokio")]
+    Tokio,
+    /// io_uring with registered buffers (Linux, feature `io-uring`).
+    #[serde(rename = "IoUring", alias = "io_uring", alias = "iouring")]
+    IoUring,
+}
+
+/// CHAP hash algorithm, as numbered in CHAP_A (RFC 7143bis, IANA PPP
+/// authentication algorithms).
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChapAlgorithm {
+    #[serde(rename = "MD5", alias = "md5")]
+    Md5,
+    #[serde(
+        rename = "SHA-256",
+        alias = "sha-256",
+        alias = "SHA256",
+        alias = "sha256"
+    )]
+    Sha256,
+    #[serde(
+        rename = "SHA3-256",
+        alias = "sha3-256",
+        alias = "SHA3_256",
+        alias = "sha3_256"
+    )]
+    Sha3_256,
+}
+impl ChapAlgorithm {
+    /// Algorithms from the strongest down, the order CHAP_A offers them in.
+    pub const BY_STRENGTH: [Self; 3] = [Self::Sha3_256, Self::Sha256, Self::Md5];
+
+    /// Value of the algorithm in CHAP_A.
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::Md5 => 5,
+            Self::Sha256 => 7,
+            Self::Sha3_256 => 8,
+        }
+    }
+
+    /// Algorithm of a CHAP_A value, `None` for unsupported ones.
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            5 => Some(Self::Md5),
+            7 => Some(Self::Sha256),
+            8 => Some(Self::Sha3_256),
+            _ => None,
+        }
+    }
+
+    /// Length of the digest, and of the CHAP_C sent with it.
+    pub const fn digest_len(self) -> usize {
+        match self {
+            Self::Md5 => 16,
+            Self::Sha256 | Self::Sha3_256 => 32,
+        }
+    }
+}
+impl fmt::Display for ChapAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChapAlgorithm::Md5 => "MD5",
+            ChapAlgorithm::Sha256 => "SHA-256",
+            ChapAlgorithm::Sha3_256 => "SHA3-256",
+        })
+    }
+}
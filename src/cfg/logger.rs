@@ -304,6 +304,12 @@ impl tracing::field::Visit for JsonVisitor {
     }
 }
 
+/// A commented `logger` section as read by [`init_logger`], to be appended
+/// to [`Config::default_template`](crate::cfg::config::Config::default_template).
+pub fn default_template() -> &'static str {
+    include_str!("default_logger.yaml")
+}
+
 pub fn init_logger(config_path: &str) -> anyhow::Result<WorkerGuard> {
     let config_content = fs::read_to_string(config_path)
         .with_context(|| format!("Failed to read config file: {config_path}"))?;
@@ -410,3 +416,19 @@ pub async fn perform_save_to_file<P: AsRef<Path>, C: AsRef<[u8]>>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_template_parses() {
+        let config: LoggerConfig =
+            serde_yaml::from_str(default_template()).expect("valid logger template");
+        assert_eq!(config.logger.level, "info");
+        assert!(matches!(config.logger.output, Output::Stdout));
+        // The template spells out the default rotation.
+        let file = config.logger.file.expect("template has a file section");
+        assert!(matches!(file.rotation_frequency, Some(RotationFreq::Never)));
+    }
+}
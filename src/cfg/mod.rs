@@ -11,3 +11,9 @@ pub mod config;
 pub mod enums;
 /// Logger initialization.
 pub mod logger;
+/// Configuration overrides from environment variables.
+pub mod overrides;
+/// CHAP secrets read from files or the environment.
+pub mod secrets;
+/// Config validation reporting every violation with its field path.
+pub mod validate;
@@ -0,0 +1,286 @@
+//! Configuration overrides from environment variables.
+//!
+//! A variable such as `ISCSI__LOGIN__FLOW__MAX_BURST_LENGTH=1048576` sets
+//! `login.flow.MaxBurstLength` after the file is parsed, so CI matrices and
+//! containers can change single parameters without templating the file.
+//! Each `__`-separated part names a key; case, `_` and `-` are ignored when
+//! matching, so `MAX_BURST_LENGTH` finds `MaxBurstLength`. The value is read
+//! as a YAML scalar or flow collection (`1048576`, `true`, `[a, b]`); it
+//! stays a string where the key currently holds one, and an empty value is
+//! null, which clears an optional key. An override naming no config field,
+//! or with a value the field does not accept, fails the load.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use anyhow::{Context, Result, bail};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_yaml::{Mapping, Value};
+
+/// Prefix of the variables read by
+/// [`Config::apply_env_overrides`](crate::cfg::config::Config::apply_env_overrides).
+pub const ENV_OVERRIDE_PREFIX: &str = "ISCSI__";
+
+/// How a key missing from the serialized tree is spelled when added.
+#[derive(Debug, Clone, Copy)]
+enum KeyStyle {
+    /// `MAX_BURST_LENGTH` -> `MaxBurstLength`, as most renamed fields.
+    Pascal,
+    /// `MAX_BURST_LENGTH` -> `max_burst_length`, as plain fields.
+    Snake,
+}
+
+/// Apply to `value` the `(name, value)` pairs whose name starts with
+/// [`ENV_OVERRIDE_PREFIX`], in name order. A key absent from the serialized
+/// `value` (an unset optional field) is tried in both spellings; an override
+/// that names no field fails. Without such pairs `value` is left untouched.
+pub fn apply_overrides<T, I>(value: &mut T, vars: I) -> Result<()>
+where
+    T: Serialize + DeserializeOwned,
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut vars = vars
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(ENV_OVERRIDE_PREFIX))
+        .collect::<Vec<_>>();
+    if vars.is_empty() {
+        return Ok(());
+    }
+    vars.sort();
+    let mut tree = serde_yaml::to_value(&*value).context("failed to serialize config")?;
+    for (name, raw) in vars {
+        let path = name[ENV_OVERRIDE_PREFIX.len()..]
+            .split("__")
+            .collect::<Vec<_>>();
+        if path.iter().any(|p| p.is_empty()) {
+            bail!("{name}: empty key in override path");
+        }
+        tree = apply_one::<T>(&tree, &path, &raw).with_context(|| name.clone())?;
+    }
+    *value = serde_yaml::from_value(tree)
+        .context("environment overrides do not fit the config")?;
+    Ok(())
+}
+
+/// Values set in place of a null override to find out whether the key it
+/// clears is known: a cleared optional field is not serialized, so the
+/// null alone cannot tell it from a key the config type drops.
+const PROBES: [&str; 5] = ["0", "''", "false", "[]", "{}"];
+
+/// `tree` after a round trip through `T` with the key at `path` set to
+/// `raw`.
+fn apply_one<T>(tree: &Value, path: &[&str], raw: &str) -> Result<Value>
+where T: Serialize + DeserializeOwned {
+    let mut invalid = None;
+    for style in [KeyStyle::Pascal, KeyStyle::Snake] {
+        let mut candidate = tree.clone();
+        let set = set_path(&mut candidate, path, raw, style)?;
+        // Round trip through the config type: a key it does not know is
+        // dropped, a value it does not accept fails.
+        match round_trip::<T>(candidate) {
+            Ok(back) => {
+                if find_path(&back, path).is_some()
+                    || (set.is_null() && is_known::<T>(tree, path, style))
+                {
+                    return Ok(back);
+                }
+            },
+            Err(e) => invalid = Some(e),
+        }
+    }
+    match invalid {
+        Some(e) => Err(anyhow::Error::new(e).context(format!("invalid value {raw:?}"))),
+        None => bail!("no such config key"),
+    }
+}
+
+/// `tree` parsed as `T` and serialized again.
+fn round_trip<T>(tree: Value) -> serde_yaml::Result<Value>
+where T: Serialize + DeserializeOwned {
+    serde_yaml::to_value(serde_yaml::from_value::<T>(tree)?)
+}
+
+/// Whether `T` keeps the key at `path`, spelled in `style`, for one of the
+/// [`PROBES`].
+fn is_known<T>(tree: &Value, path: &[&str], style: KeyStyle) -> bool
+where T: Serialize + DeserializeOwned {
+    PROBES.iter().any(|probe| {
+        let mut candidate = tree.clone();
+        set_path(&mut candidate, path, probe, style).is_ok()
+            && round_trip::<T>(candidate)
+                .is_ok_and(|back| find_path(&back, path).is_some())
+    })
+}
+
+/// Set the key at `path` below `node` to `raw`, creating missing mappings
+/// and spelling missing keys in `style`. Returns the value set.
+fn set_path(
+    node: &mut Value,
+    path: &[&str],
+    raw: &str,
+    style: KeyStyle,
+) -> Result<Value> {
+    if node.is_null() {
+        *node = Value::Mapping(Mapping::new());
+    }
+    let Value::Mapping(map) = node else {
+        bail!("cannot set {} inside a non-mapping value", path.join("__"));
+    };
+    let (segment, rest) = path.split_first().expect("path is not empty");
+    let key = match find_key(map, segment) {
+        Some(key) => key,
+        None => Value::String(spell(segment, style)),
+    };
+    let slot = map.entry(key).or_insert(Value::Null);
+    if rest.is_empty() {
+        *slot = parse_value(raw, slot);
+        Ok(slot.clone())
+    } else {
+        set_path(slot, rest, raw, style)
+    }
+}
+
+/// The value at `path`, matching keys loosely.
+fn find_path<'a>(node: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(node, |node, segment| {
+        let map = node.as_mapping()?;
+        map.get(find_key(map, segment)?)
+    })
+}
+
+/// Key of `map` matching `segment`, ignoring case, `_` and `-`.
+fn find_key(map: &Mapping, segment: &str) -> Option<Value> {
+    let norm = |s: &str| {
+        s.chars()
+            .filter(|c| !matches!(c, '_' | '-'))
+            .map(|c| c.to_ascii_lowercase())
+            .collect::<String>()
+    };
+    let wanted = norm(segment);
+    map.keys()
+        .find(|k| k.as_str().is_some_and(|k| norm(k) == wanted))
+        .cloned()
+}
+
+fn spell(segment: &str, style: KeyStyle) -> String {
+    match style {
+        KeyStyle::Snake => segment.to_ascii_lowercase(),
+        KeyStyle::Pascal => segment
+            .split('_')
+            .flat_map(|word| {
+                let mut chars = word.chars();
+                chars.next().map(|first| {
+                    first.to_ascii_uppercase().to_string()
+                        + &chars.as_str().to_ascii_lowercase()
+                })
+            })
+            .collect(),
+    }
+}
+
+/// `raw` as YAML, or as a string when `current` is one or it does not
+/// parse.
+fn parse_value(raw: &str, current: &Value) -> Value {
+    if current.is_string() {
+        return Value::String(raw.to_string());
+    }
+    serde_yaml::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Flow {
+        #[serde(rename = "MaxBurstLength")]
+        max_burst_length: u32,
+        #[serde(rename = "Alias", default)]
+        alias: String,
+        #[serde(rename = "Io", default, skip_serializing_if = "Option::is_none")]
+        io: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retries: Option<u8>,
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn overrides_find_renamed_and_unset_fields() -> Result<()> {
+        let flow = Flow {
+            max_burst_length: 262_144,
+            alias: "host".into(),
+            io: None,
+            retries: None,
+        };
+        let mut flow = flow;
+        apply_overrides(
+            &mut flow,
+            vars(&[
+                ("ISCSI__MAX_BURST_LENGTH", "1048576"),
+                ("ISCSI__ALIAS", "42"),
+                ("ISCSI__IO", "30"),
+                ("ISCSI__RETRIES", "3"),
+                ("PATH", "/usr/bin"),
+            ]),
+        )?;
+        assert_eq!(
+            flow,
+            Flow {
+                max_burst_length: 1 << 20,
+                alias: "42".into(),
+                io: Some(30),
+                retries: Some(3),
+            }
+        );
+
+        assert!(
+            apply_overrides(&mut flow, vars(&[("ISCSI__NO_SUCH_KEY", "1")])).is_err()
+        );
+        assert!(
+            apply_overrides(&mut flow, vars(&[("ISCSI__MAX_BURST_LENGTH", "lots")]))
+                .is_err()
+        );
+        assert!(apply_overrides(&mut flow, vars(&[("ISCSI____IO", "1")])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn null_overrides_clear_known_keys_only() -> Result<()> {
+        let mut flow = Flow {
+            max_burst_length: 262_144,
+            alias: "host".into(),
+            io: Some(30),
+            retries: None,
+        };
+        apply_overrides(
+            &mut flow,
+            vars(&[("ISCSI__IO", "null"), ("ISCSI__RETRIES", "")]),
+        )?;
+        assert_eq!((flow.io, flow.retries), (None, None));
+
+        for unknown in ["null", "~", ""] {
+            assert!(
+                apply_overrides(&mut flow, vars(&[("ISCSI__NO_SUCH_KEY", unknown)]))
+                    .is_err(),
+                "{unknown:?} hid an unknown key"
+            );
+        }
+        assert!(
+            apply_overrides(&mut flow, vars(&[("ISCSI__MAX_BURST_LENGTH", "null")]))
+                .is_err()
+        );
+        assert_eq!(
+            spell("MAX_BURST_LENGTH", KeyStyle::Pascal),
+            "MaxBurstLength"
+        );
+        Ok(())
+    }
+}
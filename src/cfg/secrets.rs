@@ -0,0 +1,383 @@
+//! Secrets kept out of the configuration file.
+//!
+//! A CHAP secret is given inline, or referenced through a file
+//! (`secret_file`) or an environment variable (`secret_env`).
+//! [`Config::load_from_file`](crate::cfg::config::Config::load_from_file)
+//! resolves the references, so YAML files checked into a repository need
+//! not hold credentials.
+//!
+//! Embedders that keep credentials elsewhere (Vault, a KMS) install a
+//! [`SecretProvider`] with
+//! [`Pool::set_secret_provider`](crate::client::pool_sessions::Pool::set_secret_provider).
+//! The pool asks it for the CHAP credentials of each target it logs in to,
+//! and of the discovery login of
+//! [`Pool::discover`](crate::client::pool_sessions::Pool::discover); its
+//! answer replaces the configured username and secrets and is checked like
+//! them.
+//! [`FileSecretProvider`] and [`EnvSecretProvider`] are built in.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    fmt::{self, Debug},
+    fs,
+    path::{Path, PathBuf},
+    pin::Pin,
+};
+
+use anyhow::{Context, Result, bail, ensure};
+
+use crate::cfg::config::ChapConfig;
+
+/// CHAP credentials for one target, as handed out by a [`SecretProvider`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct ChapCredentials {
+    /// Username sent in CHAP_N.
+    pub username: String,
+    /// Secret the initiator computes its CHAP_R from.
+    pub secret: String,
+    /// Name the target must answer with in mutual CHAP.
+    pub target_username: Option<String>,
+    /// Target secret; setting it enables mutual CHAP.
+    pub target_secret: Option<String>,
+}
+
+impl Debug for ChapCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChapCredentials")
+            .field("username", &self.username)
+            .field("target_username", &self.target_username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChapCredentials {
+    /// `chap` with these credentials in place of its own; the algorithm and
+    /// challenge settings are kept.
+    pub fn apply_to(self, chap: &ChapConfig) -> ChapConfig {
+        let mut chap = chap.clone();
+        chap.username = self.username;
+        chap.secret = self.secret;
+        chap.target_username = self.target_username;
+        chap.target_secret = self.target_secret;
+        chap
+    }
+}
+
+/// The checks [`Config::validate_and_normalize`] runs on configured CHAP
+/// credentials, for `chap` once a [`SecretProvider`] filled it in.
+///
+/// [`Config::validate_and_normalize`]: crate::cfg::config::Config::validate_and_normalize
+pub(crate) fn check_provided_chap(chap: &ChapConfig) -> Result<()> {
+    ensure!(!chap.secret.is_empty(), "empty CHAP secret");
+    ensure!(
+        chap.target_username.is_none() || chap.is_mutual(),
+        "target_username requires target_secret"
+    );
+    ensure!(
+        !chap.distinct_secrets || chap.target_secret.as_deref() != Some(&chap.secret),
+        "target_secret must differ from secret"
+    );
+    Ok(())
+}
+
+/// Future returned by [`SecretProvider::chap_credentials`].
+pub type SecretFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<ChapCredentials>>> + Send + 'a>>;
+
+/// Source of CHAP credentials, queried by target name before each login.
+pub trait SecretProvider: Send + Sync + Debug {
+    /// Credentials for `target_name`; `None` keeps the configured ones.
+    /// An error fails the login.
+    fn chap_credentials<'a>(&'a self, target_name: &'a str) -> SecretFuture<'a>;
+}
+
+/// A [`SecretProvider`] that answers without awaiting anything.
+pub trait SyncSecretProvider: Send + Sync + Debug {
+    /// Credentials for `target_name`; `None` keeps the configured ones.
+    fn chap_credentials_sync(&self, target_name: &str)
+    -> Result<Option<ChapCredentials>>;
+}
+
+impl<P: SyncSecretProvider> SecretProvider for P {
+    fn chap_credentials<'a>(&'a self, target_name: &'a str) -> SecretFuture<'a> {
+        Box::pin(std::future::ready(self.chap_credentials_sync(target_name)))
+    }
+}
+
+/// Reads credentials from one directory per target, named after the target,
+/// holding one file per field: `username`, `secret` and optionally
+/// `target_username` / `target_secret` (the layout of a mounted Kubernetes
+/// secret). The secret files are checked like `secret_file`. Targets
+/// without a directory keep the configured credentials.
+#[derive(Debug, Clone)]
+pub struct FileSecretProvider {
+    dir: PathBuf,
+}
+
+impl FileSecretProvider {
+    /// Provider reading the target directories under `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl SyncSecretProvider for FileSecretProvider {
+    fn chap_credentials_sync(
+        &self,
+        target_name: &str,
+    ) -> Result<Option<ChapCredentials>> {
+        ensure!(
+            !matches!(target_name, "" | "." | "..") && !target_name.contains(['/', '\\']),
+            "target name {target_name:?} cannot name a secret directory"
+        );
+        let dir = self.dir.join(target_name);
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        let read_plain = |name: &str| -> Result<Option<String>> {
+            let path = dir.join(name);
+            if !path.exists() {
+                return Ok(None);
+            }
+            let v = fs::read_to_string(&path)
+                .with_context(|| format!("cannot read {}", path.display()))?;
+            Ok(Some(v.trim_end_matches(['\r', '\n']).to_string()))
+        };
+        let read_secret = |name: &str| -> Result<Option<String>> {
+            let path = dir.join(name);
+            path.exists().then(|| read_secret_file(&path)).transpose()
+        };
+        Ok(Some(ChapCredentials {
+            username: read_plain("username")?
+                .with_context(|| format!("{} has no username", dir.display()))?,
+            secret: read_secret("secret")?
+                .with_context(|| format!("{} has no secret", dir.display()))?,
+            target_username: read_plain("target_username")?,
+            target_secret: read_secret("target_secret")?,
+        }))
+    }
+}
+
+/// Reads credentials from `<PREFIX>_<TARGET>_USERNAME`, `..._SECRET`,
+/// `..._TARGET_USERNAME` and `..._TARGET_SECRET`, where `<TARGET>` is the
+/// target name in upper case with every other character than a letter or
+/// digit replaced by `_`. Targets without a `..._SECRET` variable keep the
+/// configured credentials.
+#[derive(Debug, Clone)]
+pub struct EnvSecretProvider {
+    prefix: String,
+}
+
+impl EnvSecretProvider {
+    /// Provider reading variables that start with `prefix`, e.g.
+    /// `ISCSI_CHAP`.
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Name of the variable holding `field` of `target_name`.
+    pub fn var_name(&self, target_name: &str, field: &str) -> String {
+        let target = target_name
+            .chars()
+            .map(|c| match c {
+                c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+                _ => '_',
+            })
+            .collect::<String>();
+        format!("{}_{target}_{field}", self.prefix)
+    }
+}
+
+impl SyncSecretProvider for EnvSecretProvider {
+    fn chap_credentials_sync(
+        &self,
+        target_name: &str,
+    ) -> Result<Option<ChapCredentials>> {
+        let var = |field: &str| std::env::var(self.var_name(target_name, field)).ok();
+        let Some(secret) = var("SECRET") else {
+            return Ok(None);
+        };
+        let username = var("USERNAME").with_context(|| {
+            format!("{} is not set", self.var_name(target_name, "USERNAME"))
+        })?;
+        Ok(Some(ChapCredentials {
+            username,
+            secret,
+            target_username: var("TARGET_USERNAME"),
+            target_secret: var("TARGET_SECRET"),
+        }))
+    }
+}
+
+/// Read a secret from `path`: the whole file without its trailing line
+/// break. On Unix the file must not be accessible by group or others.
+pub fn read_secret_file(path: &Path) -> Result<String> {
+    let meta = fs::metadata(path)
+        .with_context(|| format!("cannot stat secret file {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = meta.permissions().mode() & 0o777;
+        ensure!(
+            mode & 0o077 == 0,
+            "secret file {} is accessible by group or others (mode {mode:03o}), \
+             expected 0600",
+            path.display()
+        );
+    }
+    ensure!(
+        meta.is_file(),
+        "secret file {} is not a file",
+        path.display()
+    );
+    let secret = fs::read_to_string(path)
+        .with_context(|| format!("cannot read secret file {}", path.display()))?;
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    ensure!(
+        !secret.is_empty(),
+        "secret file {} is empty",
+        path.display()
+    );
+    Ok(secret.to_string())
+}
+
+/// Read a secret from the environment variable `var`.
+pub fn read_secret_env(var: &str) -> Result<String> {
+    let secret = std::env::var(var)
+        .with_context(|| format!("secret environment variable {var} is not set"))?;
+    ensure!(
+        !secret.is_empty(),
+        "secret environment variable {var} is empty"
+    );
+    Ok(secret)
+}
+
+/// Resolve the `what` secret given by at most one of `inline`, `file`
+/// (relative to `base_dir`) or `env`. Returns the secret read from a file
+/// or the environment, `None` when it is inline or not set.
+pub(crate) fn resolve_secret(
+    what: &str,
+    inline: Option<&str>,
+    file: Option<&Path>,
+    env: Option<&str>,
+    base_dir: &Path,
+) -> Result<Option<String>> {
+    let sources = [inline.is_some(), file.is_some(), env.is_some()];
+    if sources.iter().filter(|&&s| s).count() > 1 {
+        bail!("{what} is given more than once (inline, file, environment)");
+    }
+    if let Some(file) = file {
+        let path = base_dir.join(file);
+        return read_secret_file(&path)
+            .with_context(|| format!("cannot resolve {what}"))
+            .map(Some);
+    }
+    if let Some(var) = env {
+        return read_secret_env(var)
+            .with_context(|| format!("cannot resolve {what}"))
+            .map(Some);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_private_secret_files_only() -> Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("iscsi-secret-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("chap");
+        fs::write(&path, "s3cret\n")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644))?;
+            assert!(read_secret_file(&path).is_err());
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+        assert_eq!(read_secret_file(&path)?, "s3cret");
+        assert_eq!(
+            resolve_secret("CHAP secret", None, Some(Path::new("chap")), None, &dir)?,
+            Some("s3cret".to_string())
+        );
+        assert_eq!(
+            resolve_secret("CHAP secret", Some("inline"), None, None, &dir)?,
+            None
+        );
+        assert!(
+            resolve_secret("CHAP secret", Some("x"), Some(&path), None, &dir).is_err()
+        );
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn checks_provided_credentials_like_configured_ones() {
+        let creds = |secret: &str, target_secret: Option<&str>| ChapCredentials {
+            username: "user".into(),
+            secret: secret.into(),
+            target_username: Some("target".into()),
+            target_secret: target_secret.map(String::from),
+        };
+        let mut chap = ChapConfig::new("configured", "configured");
+        assert!(check_provided_chap(&creds("s", Some("t")).apply_to(&chap)).is_ok());
+        assert!(check_provided_chap(&creds("", Some("t")).apply_to(&chap)).is_err());
+        assert!(check_provided_chap(&creds("s", None).apply_to(&chap)).is_err());
+        assert!(check_provided_chap(&creds("s", Some("s")).apply_to(&chap)).is_ok());
+        chap.distinct_secrets = true;
+        assert!(check_provided_chap(&creds("s", Some("s")).apply_to(&chap)).is_err());
+    }
+
+    #[test]
+    fn file_provider_reads_one_directory_per_target() -> Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("iscsi-provider-{}", std::process::id()));
+        let target = "iqn.2004-10.com.example:disk";
+        fs::create_dir_all(dir.join(target))?;
+        fs::write(dir.join(target).join("username"), "user\n")?;
+        let secret = dir.join(target).join("secret");
+        fs::write(&secret, "s3cret")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&secret, fs::Permissions::from_mode(0o600))?;
+        }
+
+        let provider = FileSecretProvider::new(&dir);
+        let creds = provider
+            .chap_credentials_sync(target)?
+            .context("credentials for the target")?;
+        assert_eq!(
+            (creds.username.as_str(), creds.secret.as_str()),
+            ("user", "s3cret")
+        );
+        assert_eq!(creds.target_secret, None);
+        assert!(!format!("{creds:?}").contains("s3cret"));
+        assert!(provider.chap_credentials_sync("iqn.other")?.is_none());
+        assert!(provider.chap_credentials_sync("../etc").is_err());
+
+        let chap = creds.apply_to(&ChapConfig::new("configured", "old"));
+        assert_eq!(
+            (chap.username.as_str(), chap.secret.as_str()),
+            ("user", "s3cret")
+        );
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn env_provider_maps_target_names_to_variables() {
+        let provider = EnvSecretProvider::new("ISCSI_CHAP");
+        assert_eq!(
+            provider.var_name("iqn.2004-10.com.example:disk", "SECRET"),
+            "ISCSI_CHAP_IQN_2004_10_COM_EXAMPLE_DISK_SECRET"
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Validation of a [`Config`] reporting every violation at once.
+//!
+//! [`Config::violations`] checks the whole config and returns one
+//! [`ConfigViolation`] per broken rule, named by the path of the offending
+//! key (`login.flow.FirstBurstLength`).
+//! [`Config::validate_and_normalize`] fails with a
+//! [`ConfigValidationError`] listing all of them, so a config is fixed in
+//! one round instead of one error at a time. The checks of a target of a
+//! multi-target config run on its effective config; a violation in a key
+//! the target sets is reported under `targets[<index>].`, one in a key it
+//! inherits once under its `login` or `runtime` path.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::{
+    cfg::{
+        config::{AuthConfig, Config, TargetConfig},
+        enums::IoBackend,
+    },
+    client::{load_balance::BalanceStrategy, portal::PortalTarget},
+    models::identifiers::Isid,
+};
+
+/// Smallest MaxRecvDataSegmentLength, MaxBurstLength and FirstBurstLength
+/// (RFC 7143 § 13); MaxRecvDataSegmentLength must also be a multiple of it.
+const MIN_DATA_LENGTH: u32 = 512;
+/// Largest MaxRecvDataSegmentLength, MaxBurstLength and FirstBurstLength.
+const MAX_DATA_LENGTH: u32 = (1 << 24) - 1;
+
+/// One broken rule of a config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigViolation {
+    /// Path of the offending key, e.g. `login.flow.MaxBurstLength`.
+    pub path: String,
+    /// What is wrong with it.
+    pub message: String,
+}
+
+impl fmt::Display for ConfigViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// A config broke one or more rules, see [`Config::violations`].
+#[derive(Debug, Error)]
+#[error(
+    "invalid config, {} problem(s): {}",
+    violations.len(),
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+)]
+pub struct ConfigValidationError {
+    /// Every violation, in check order.
+    pub violations: Vec<ConfigViolation>,
+}
+
+/// Collects the violations of one config.
+#[derive(Default)]
+struct Checker {
+    violations: Vec<ConfigViolation>,
+}
+
+impl Checker {
+    /// Record a violation at `path` unless `ok`.
+    fn check(&mut self, ok: bool, path: &str, message: impl FnOnce() -> String) {
+        if !ok {
+            self.fail(path, message());
+        }
+    }
+
+    fn fail(&mut self, path: &str, message: impl Into<String>) {
+        self.violations.push(ConfigViolation {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+}
+
+impl Config {
+    /// Every rule the config breaks, empty when it is valid.
+    pub fn violations(&self) -> Vec<ConfigViolation> {
+        let mut c = Checker::default();
+        if self.targets.is_empty() {
+            check_login(&mut c, self);
+            check_runtime(&mut c, self);
+            return c.violations;
+        }
+
+        c.check(
+            self.login.identity.session_type.is_normal(),
+            "login.identity.SessionType",
+            || "targets require a Normal session".into(),
+        );
+        for (i, target) in self.targets.iter().enumerate() {
+            if self.targets[..i].iter().any(|t| t.name == target.name) {
+                c.fail(
+                    &format!("targets[{i}].TargetName"),
+                    format!("target {:?} is configured twice", target.name),
+                );
+            }
+        }
+        let targets = self.targets.iter().zip(self.target_configs());
+        for (i, (target, cfg)) in targets.enumerate() {
+            let mut t = Checker::default();
+            check_login(&mut t, &cfg);
+            for mut v in t.violations {
+                if let Some(path) = target_path(target, &v.path) {
+                    v.path = format!("targets[{i}].{path}");
+                }
+                // Inherited keys are reported once, not once per target.
+                if !c.violations.contains(&v) {
+                    c.violations.push(v);
+                }
+            }
+        }
+        check_runtime(&mut c, self);
+        c.violations
+    }
+}
+
+/// Path of the `login` key `path` inside `target`, if `target` sets it.
+fn target_path(target: &TargetConfig, path: &str) -> Option<String> {
+    let rest = path.strip_prefix("login.")?;
+    let (section, key) = rest.split_once('.').unwrap_or((rest, ""));
+    let sets_portals = target.target_address.is_some() || !target.portals.is_empty();
+    let set = match section {
+        "identity" => key == "TargetName",
+        "transport" => sets_portals && matches!(key, "TargetAddress" | "Portals"),
+        "auth" => target.auth.is_some(),
+        "integrity" => target.integrity.is_some(),
+        "flow" => target.flow.is_some(),
+        "write_flow" => target.write_flow.is_some(),
+        "ordering" => target.ordering.is_some(),
+        "recovery" => target.recovery.is_some(),
+        "timers" => target.timers.is_some(),
+        "limits" => target.limits.is_some(),
+        _ => false,
+    };
+    set.then(|| match section {
+        "identity" | "transport" => key.to_string(),
+        _ => rest.to_string(),
+    })
+}
+
+fn check_login(c: &mut Checker, cfg: &Config) {
+    check_identity(c, cfg);
+    check_auth(c, &cfg.login.auth);
+    check_negotiation(c, cfg);
+    check_transport(c, cfg);
+}
+
+fn check_identity(c: &mut Checker, cfg: &Config) {
+    let identity = &cfg.login.identity;
+    check_name(
+        c,
+        "login.identity.InitiatorName",
+        &identity.initiator_name,
+        true,
+    );
+    check_name(
+        c,
+        "login.identity.TargetName",
+        &identity.target_name,
+        identity.session_type.is_normal(),
+    );
+    if let Some(isid) = &identity.isid
+        && let Err(e) = isid.parse::<Isid>()
+    {
+        c.fail("login.identity.Isid", format!("{e:#}"));
+    }
+}
+
+/// An iSCSI name must use the `iqn.`, `eui.` or `naa.` format (RFC 7143
+/// § 4.2.7.2); it may be empty unless `required`.
+fn check_name(c: &mut Checker, path: &str, name: &str, required: bool) {
+    if name.is_empty() {
+        c.check(!required, path, || "must not be empty".into());
+        return;
+    }
+    let lower = name.to_ascii_lowercase();
+    c.check(
+        ["iqn.", "eui.", "naa."]
+            .iter()
+            .any(|p| lower.starts_with(p)),
+        path,
+        || format!("{name:?} is not an iqn., eui. or naa. name"),
+    );
+    c.check(!name.contains(char::is_whitespace), path, || {
+        format!("{name:?} contains whitespace")
+    });
+}
+
+fn check_auth(c: &mut Checker, auth: &AuthConfig) {
+    match auth {
+        AuthConfig::None => {},
+        AuthConfig::Chap(chap) => {
+            c.check(
+                chap.target_username.is_none() || chap.is_mutual(),
+                "login.auth.target_username",
+                || "requires target_secret".into(),
+            );
+            c.check(
+                !chap.distinct_secrets
+                    || chap.target_secret.as_deref() != Some(chap.secret.as_str()),
+                "login.auth.target_secret",
+                || "must differ from secret".into(),
+            );
+        },
+        AuthConfig::Srp(srp) => {
+            c.check(!srp.username.is_empty(), "login.auth.username", || {
+                "SRP username must not be empty".into()
+            });
+            c.check(!srp.password.is_empty(), "login.auth.password", || {
+                "set password, password_file or password_env".into()
+            });
+        },
+    }
+}
+
+fn check_negotiation(c: &mut Checker, cfg: &Config) {
+    let login = &cfg.login;
+
+    let flow = &login.flow;
+    for (path, value) in [
+        (
+            "login.flow.MaxRecvDataSegmentLength",
+            flow.max_recv_data_segment_length,
+        ),
+        ("login.flow.MaxBurstLength", flow.max_burst_length),
+        ("login.flow.FirstBurstLength", flow.first_burst_length),
+    ] {
+        c.check(
+            (MIN_DATA_LENGTH..=MAX_DATA_LENGTH).contains(&value),
+            path,
+            || format!("must be {MIN_DATA_LENGTH}..={MAX_DATA_LENGTH}, got {value}"),
+        );
+    }
+    c.check(
+        flow.max_recv_data_segment_length
+            .is_multiple_of(MIN_DATA_LENGTH),
+        "login.flow.MaxRecvDataSegmentLength",
+        || {
+            format!(
+                "must be a multiple of {MIN_DATA_LENGTH}, got {}",
+                flow.max_recv_data_segment_length
+            )
+        },
+    );
+    c.check(
+        flow.first_burst_length <= flow.max_burst_length,
+        "login.flow.FirstBurstLength",
+        || {
+            format!(
+                "{} exceeds MaxBurstLength {}",
+                flow.first_burst_length, flow.max_burst_length
+            )
+        },
+    );
+    c.check(
+        login.write_flow.max_outstanding_r2t >= 1,
+        "login.write_flow.MaxOutstandingR2T",
+        || "must be >= 1".into(),
+    );
+
+    let erl = login.recovery.error_recovery_level;
+    c.check(erl <= 2, "login.recovery.ErrorRecoveryLevel", || {
+        format!("must be 0, 1 or 2, got {erl}")
+    });
+    c.check(
+        erl < 2 || !login.timers.default_time2retain.is_zero(),
+        "login.timers.DefaultTime2Retain",
+        || "must be > 0 with ErrorRecoveryLevel 2, or no task can be reassigned".into(),
+    );
+
+    c.check(
+        login.limits.max_connections >= 1,
+        "login.limits.MaxConnections",
+        || "must be >= 1".into(),
+    );
+    if let Some(lv) = login.extensions.iscsi_protocol_level {
+        c.check(lv >= 1, "login.extensions.iSCSIProtocolLevel", || {
+            "must be >= 1".into()
+        });
+    }
+}
+
+fn check_transport(c: &mut Checker, cfg: &Config) {
+    let transport = &cfg.login.transport;
+    for portal in transport.all_portals() {
+        if let Err(e) = portal.parse::<PortalTarget>() {
+            let path = if portal == transport.target_address.trim() {
+                "login.transport.TargetAddress"
+            } else {
+                "login.transport.Portals"
+            };
+            c.fail(path, format!("{e:#}"));
+        }
+    }
+    if let Some(proxy) = &transport.socks5_proxy {
+        if let Err(e) = proxy.portal() {
+            c.fail("login.transport.Socks5Proxy.Address", format!("{e:#}"));
+        }
+        c.check(
+            proxy.password.is_none() || proxy.username.is_some(),
+            "login.transport.Socks5Proxy.Password",
+            || "requires Socks5Proxy.Username".into(),
+        );
+    }
+    c.check(
+        transport.tls.is_none() || cfg!(feature = "tls"),
+        "login.transport.Tls",
+        || "configured but the `tls` feature is disabled".into(),
+    );
+    #[cfg(feature = "tls")]
+    if let Some(tls) = &transport.tls
+        && let Err(e) = crate::client::tls::client_config(tls)
+    {
+        c.fail("login.transport.Tls", format!("{e:#}"));
+    }
+    if let Some(interface) = &transport.bind_interface {
+        c.check(
+            cfg!(target_os = "linux"),
+            "login.transport.BindInterface",
+            || "only supported on Linux".into(),
+        );
+        c.check(
+            !interface.is_empty(),
+            "login.transport.BindInterface",
+            || "must not be empty".into(),
+        );
+    }
+}
+
+fn check_runtime(c: &mut Checker, cfg: &Config) {
+    let runtime = &cfg.runtime;
+    c.check(runtime.max_sessions >= 1, "runtime.MaxSessions", || {
+        "must be >= 1".into()
+    });
+    c.check(
+        runtime.response_queue_capacity >= 1,
+        "runtime.ResponseQueueCapacity",
+        || "must be >= 1".into(),
+    );
+    c.check(
+        runtime.login_parallelism >= 1,
+        "runtime.LoginParallelism",
+        || "must be >= 1".into(),
+    );
+    for (path, value) in [
+        (
+            "runtime.TimeoutConnection",
+            Some(runtime.timeout_connection),
+        ),
+        ("runtime.Timeouts.Connect", runtime.timeouts.connect),
+        ("runtime.Timeouts.Login", runtime.timeouts.login),
+        ("runtime.Timeouts.Io", runtime.timeouts.io),
+        ("runtime.Timeouts.Tmf", runtime.timeouts.tmf),
+    ] {
+        c.check(value.is_none_or(|d| !d.is_zero()), path, || {
+            "must be > 0".into()
+        });
+    }
+    if let Some(dscp) = runtime.tcp.dscp {
+        c.check(dscp <= 63, "runtime.Tcp.Dscp", || {
+            format!("must be 0..=63, got {dscp}")
+        });
+    }
+    c.check(
+        runtime.load_balance.strategy != BalanceStrategy::LbaAffinity
+            || runtime.load_balance.affinity_stripe_blocks > 0,
+        "runtime.LoadBalance.AffinityStripeBlocks",
+        || "must be > 0 for LbaAffinity".into(),
+    );
+    c.check(
+        runtime.io_backend != IoBackend::IoUring
+            || cfg!(all(target_os = "linux", feature = "io-uring")),
+        "runtime.IoBackend",
+        || "IoUring needs Linux and the `io-uring` feature".into(),
+    );
+
+    let retry = &runtime.retry;
+    c.check(retry.max_attempts >= 1, "runtime.Retry.MaxAttempts", || {
+        "must be >= 1".into()
+    });
+    c.check(
+        retry.backoff_multiplier.is_finite() && retry.backoff_multiplier >= 1.0,
+        "runtime.Retry.BackoffMultiplier",
+        || "must be a finite number >= 1.0".into(),
+    );
+    c.check(
+        retry.max_backoff_ms >= retry.initial_backoff_ms,
+        "runtime.Retry.MaxBackoffMs",
+        || "must be >= Retry.InitialBackoffMs".into(),
+    );
+}
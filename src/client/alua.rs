@@ -0,0 +1,102 @@
+//! Asymmetric logical unit access (ALUA).
+//!
+//! [`LunHandle::report_target_port_groups`] reads the access state of every
+//! target port group and [`LunHandle::target_port`] tells which group the
+//! handle's session goes through.
+//! [`Multipath`](crate::client::multipath::Multipath) uses both to prefer
+//! active/optimized portals.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use crate::{
+    client::{handles::LunHandle, retry::SenseMatch},
+    control_block::{
+        alua::{
+            AluaState, TargetPortGroups, build_report_target_port_groups,
+            build_set_target_port_groups, fill_set_target_port_groups,
+            parse_report_target_port_groups, parse_target_port_groups_length,
+        },
+        inquiry::{VpdPage, parse_vpd_device_id, target_port_designators},
+    },
+    error,
+};
+
+/// Allocation length of the first REPORT TARGET PORT GROUPS.
+const RTPG_INITIAL_ALLOC: u32 = 1024;
+
+/// NOT READY sense codes a target returns for commands sent through a port
+/// group that cannot serve them, with the state they imply.
+pub const ALUA_NOT_READY: [(SenseMatch, AluaState); 3] = [
+    // ASYMMETRIC ACCESS STATE TRANSITION
+    (
+        SenseMatch::exact(0x02, 0x04, 0x0A),
+        AluaState::Transitioning,
+    ),
+    // TARGET PORT IN STANDBY STATE
+    (SenseMatch::exact(0x02, 0x04, 0x0B), AluaState::Standby),
+    // TARGET PORT IN UNAVAILABLE STATE
+    (SenseMatch::exact(0x02, 0x04, 0x0C), AluaState::Unavailable),
+];
+
+/// The target port a session reached, from VPD 0x83.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TargetPort {
+    /// Target port group identifier.
+    pub group: Option<u16>,
+    /// Relative target port identifier.
+    pub relative_port: Option<u16>,
+}
+
+/// The ALUA state a NOT READY sense triple reports for the path it came
+/// through.
+pub fn alua_state_from_sense(key: u8, asc: u8, ascq: u8) -> Option<AluaState> {
+    ALUA_NOT_READY
+        .iter()
+        .find(|(m, _)| m.matches(key, asc, ascq))
+        .map(|&(_, state)| state)
+}
+
+impl LunHandle {
+    /// Access state of every target port group (REPORT TARGET PORT GROUPS).
+    /// Re-issued with the reported length when the first answer was cut
+    /// off.
+    pub async fn report_target_port_groups(&self) -> error::Result<TargetPortGroups> {
+        let mut alloc_len = RTPG_INITIAL_ALLOC;
+        loop {
+            let mut cdb = [0u8; 16];
+            build_report_target_port_groups(&mut cdb, alloc_len, true, 0);
+            let data = self.read_cdb(cdb, alloc_len, None).await?;
+            let needed = parse_target_port_groups_length(&data)?.saturating_add(4);
+            if needed <= alloc_len || data.len() < alloc_len as usize {
+                return Ok(parse_report_target_port_groups(&data)?);
+            }
+            alloc_len = needed;
+        }
+    }
+
+    /// Ask the target to move port groups to new states (SET TARGET PORT
+    /// GROUPS), as `(group id, state)` pairs. Only targets with explicit
+    /// ALUA accept it.
+    pub async fn set_target_port_groups(
+        &self,
+        states: &[(u16, AluaState)],
+    ) -> error::Result<()> {
+        let params = fill_set_target_port_groups(states);
+        let mut cdb = [0u8; 16];
+        build_set_target_port_groups(&mut cdb, params.len() as u32, 0);
+        self.write_cdb(cdb, params, None).await.map_err(Into::into)
+    }
+
+    /// The target port group and relative port this handle's session goes
+    /// through.
+    pub async fn target_port(&self) -> error::Result<TargetPort> {
+        let page = self.vpd_page(VpdPage::DeviceId).await?;
+        let (group, relative_port) =
+            target_port_designators(&parse_vpd_device_id(&page)?);
+        Ok(TargetPort {
+            group,
+            relative_port,
+        })
+    }
+}
@@ -0,0 +1,174 @@
+//! ATA commands through a SCSI / ATA Translation layer (SAT).
+//!
+//! SATA disks behind iSCSI bridges answer ATA PASS-THROUGH;
+//! [`LunHandle::ata_pass_through`] sends a raw ATA taskfile and returns the
+//! data together with the ATA registers the target hands back in
+//! descriptor-format sense data. [`LunHandle::ata_identify`],
+//! [`LunHandle::smart_read_data`] and [`LunHandle::smart_return_status`]
+//! cover the usual health queries.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use anyhow::anyhow;
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::ata_passthrough::{
+        AtaCommand, AtaDirection, AtaRegisters, SMART_DATA_LEN, SMART_LBA_SIGNATURE,
+        SmartAttribute, ata_command, build_ata_pass_through16, parse_ata_sense,
+        parse_smart_attributes, smart_feature,
+    },
+    error::{self, IscsiError},
+};
+
+/// Bytes per ATA block (COUNT unit with T_LENGTH = 2).
+const ATA_BLOCK_LEN: u32 = 512;
+
+/// RECOVERED ERROR / ATA PASS THROUGH INFORMATION AVAILABLE: how a target
+/// answers a command with CK_COND set.
+const ATA_INFO_AVAILABLE: (u8, u8, u8) = (0x01, 0x00, 0x1D);
+
+/// LBA mid/high of SMART RETURN STATUS when a threshold was exceeded.
+const SMART_THRESHOLD_EXCEEDED: u64 = 0x2CF4;
+
+/// Outcome of an ATA PASS-THROUGH command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtaResponse {
+    /// Data read from the device (empty without PIO / DMA in).
+    pub data: Vec<u8>,
+    /// ATA registers, when the target returned them (CK_COND).
+    pub registers: Option<AtaRegisters>,
+}
+
+/// Health verdict of SMART RETURN STATUS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmartStatus {
+    /// No threshold exceeded.
+    Passed,
+    /// A pre-failure threshold was exceeded; the disk predicts its failure.
+    ThresholdExceeded,
+}
+
+impl LunHandle {
+    /// Send `cmd` with ATA PASS-THROUGH(16).
+    ///
+    /// `data` is written for [`AtaDirection::Out`] and ignored otherwise; for
+    /// [`AtaDirection::In`] COUNT 512-byte blocks are read. The CHECK
+    /// CONDITION a CK_COND command ends with is turned into the returned
+    /// registers; ATA errors stay a [`IscsiError::Scsi`] whose raw sense
+    /// [`parse_ata_sense`] decodes.
+    pub async fn ata_pass_through(
+        &self,
+        cmd: &AtaCommand,
+        data: Vec<u8>,
+    ) -> error::Result<AtaResponse> {
+        let mut cdb = [0u8; 16];
+        build_ata_pass_through16(&mut cdb, cmd, 0);
+        let outcome = match cmd.direction {
+            AtaDirection::In => {
+                let len = cmd.count as u32 * ATA_BLOCK_LEN;
+                self.read_cdb(cdb, len, None).await
+            },
+            AtaDirection::Out => {
+                self.write_cdb(cdb, data, None).await.map(|()| Vec::new())
+            },
+            AtaDirection::None => self
+                .write_cdb(cdb, Vec::new(), None)
+                .await
+                .map(|()| Vec::new()),
+        };
+        match outcome.map_err(IscsiError::from) {
+            Ok(data) => Ok(AtaResponse {
+                data,
+                registers: None,
+            }),
+            Err(IscsiError::Scsi(e)) if e.sense_codes() == Some(ATA_INFO_AVAILABLE) => {
+                Ok(AtaResponse {
+                    data: Vec::new(),
+                    registers: Some(parse_ata_sense(&e.raw_sense)?),
+                })
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// IDENTIFY DEVICE: the 512-byte identify data of the ATA device.
+    pub async fn ata_identify(&self) -> error::Result<Vec<u8>> {
+        let cmd = AtaCommand::pio_in(ata_command::IDENTIFY_DEVICE, 1);
+        Ok(self.ata_pass_through(&cmd, Vec::new()).await?.data)
+    }
+
+    /// SMART READ DATA: the attribute table of the device.
+    pub async fn smart_read_data(&self) -> error::Result<Vec<SmartAttribute>> {
+        let cmd = AtaCommand {
+            features: smart_feature::READ_DATA,
+            lba: SMART_LBA_SIGNATURE,
+            ..AtaCommand::pio_in(ata_command::SMART, 1)
+        };
+        let data = self.ata_pass_through(&cmd, Vec::new()).await?.data;
+        if data.len() < SMART_DATA_LEN {
+            return Err(IscsiError::msg(format!(
+                "SMART READ DATA returned {} bytes",
+                data.len()
+            )));
+        }
+        Ok(parse_smart_attributes(&data)?)
+    }
+
+    /// SMART RETURN STATUS: whether the device predicts its own failure.
+    pub async fn smart_return_status(&self) -> error::Result<SmartStatus> {
+        let cmd = AtaCommand {
+            features: smart_feature::RETURN_STATUS,
+            lba: SMART_LBA_SIGNATURE,
+            ck_cond: true,
+            ..AtaCommand::non_data(ata_command::SMART)
+        };
+        let regs = self
+            .ata_pass_through(&cmd, Vec::new())
+            .await?
+            .registers
+            .ok_or_else(|| anyhow!("SMART RETURN STATUS returned no ATA registers"))?;
+        Ok(smart_status(&regs)?)
+    }
+}
+
+/// Verdict of SMART RETURN STATUS from the LBA mid/high registers.
+fn smart_status(regs: &AtaRegisters) -> anyhow::Result<SmartStatus> {
+    match (regs.lba >> 8) & 0xFFFF {
+        sig if sig == SMART_LBA_SIGNATURE >> 8 => Ok(SmartStatus::Passed),
+        SMART_THRESHOLD_EXCEEDED => Ok(SmartStatus::ThresholdExceeded),
+        other => Err(anyhow!(
+            "unexpected SMART RETURN STATUS signature {other:04X}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regs(lba: u64) -> AtaRegisters {
+        AtaRegisters {
+            extend: false,
+            error: 0,
+            count: 0,
+            lba,
+            device: 0,
+            status: 0x50,
+        }
+    }
+
+    #[test]
+    fn decodes_smart_status() {
+        assert_eq!(
+            smart_status(&regs(0xC2_4F00)).expect("status"),
+            SmartStatus::Passed
+        );
+        assert_eq!(
+            smart_status(&regs(0x2C_F400)).expect("status"),
+            SmartStatus::ThresholdExceeded
+        );
+        assert!(smart_status(&regs(0)).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Target buffers (READ BUFFER / WRITE BUFFER) on a LUN.
+//!
+//! [`LunHandle::echo_test`] round-trips a pattern through the echo buffer to
+//! check the data path end to end without touching the medium.
+//! [`LunHandle::download_microcode`] sends a firmware image in chunks sized
+//! by the buffer descriptor.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use anyhow::ensure;
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::buffer::{
+        BUFFER_DESCRIPTOR_LEN, BUFFER_FIELD_MAX, BufferDescriptor, EchoBufferDescriptor,
+        ReadBufferMode, WriteBufferMode, build_read_buffer, build_write_buffer,
+        parse_buffer_descriptor, parse_echo_buffer_descriptor,
+    },
+    error,
+};
+
+/// Largest microcode chunk sent by one WRITE BUFFER, whatever the buffer
+/// capacity.
+const MICROCODE_CHUNK_MAX: u32 = 1024 * 1024;
+
+impl LunHandle {
+    /// READ BUFFER in `mode` from buffer `buffer_id` at `offset`, returning
+    /// up to `len` bytes.
+    pub async fn read_buffer(
+        &self,
+        mode: ReadBufferMode,
+        buffer_id: u8,
+        offset: u32,
+        len: u32,
+    ) -> error::Result<Vec<u8>> {
+        check_field("buffer offset", offset as usize)?;
+        check_field("allocation length", len as usize)?;
+        let mut cdb = [0u8; 16];
+        build_read_buffer(&mut cdb, mode, buffer_id, offset, len, 0);
+        self.read_cdb(cdb, len, None).await.map_err(Into::into)
+    }
+
+    /// WRITE BUFFER `data` in `mode` to buffer `buffer_id` at `offset`.
+    pub async fn write_buffer(
+        &self,
+        mode: WriteBufferMode,
+        buffer_id: u8,
+        offset: u32,
+        data: impl Into<Vec<u8>>,
+    ) -> error::Result<()> {
+        let data = data.into();
+        check_field("buffer offset", offset as usize)?;
+        let len = check_field("parameter list length", data.len())?;
+        let mut cdb = [0u8; 16];
+        build_write_buffer(&mut cdb, mode, buffer_id, offset, len, 0);
+        self.write_cdb(cdb, data, None).await.map_err(Into::into)
+    }
+
+    /// Capacity and offset alignment of buffer `buffer_id`.
+    pub async fn buffer_descriptor(
+        &self,
+        buffer_id: u8,
+    ) -> error::Result<BufferDescriptor> {
+        let data = self
+            .read_buffer(
+                ReadBufferMode::Descriptor,
+                buffer_id,
+                0,
+                BUFFER_DESCRIPTOR_LEN,
+            )
+            .await?;
+        Ok(parse_buffer_descriptor(&data)?)
+    }
+
+    /// Capacity of the echo buffer and whether other initiators share it.
+    pub async fn echo_buffer_descriptor(&self) -> error::Result<EchoBufferDescriptor> {
+        let data = self
+            .read_buffer(
+                ReadBufferMode::EchoBufferDescriptor,
+                0,
+                0,
+                BUFFER_DESCRIPTOR_LEN,
+            )
+            .await?;
+        Ok(parse_echo_buffer_descriptor(&data)?)
+    }
+
+    /// Write `pattern` to the echo buffer and read it back, failing if the
+    /// target returns different bytes. `pattern` must fit the echo buffer;
+    /// on a shared one ([`EchoBufferDescriptor::shared`]) another initiator
+    /// can cause a spurious mismatch.
+    pub async fn echo_test(&self, pattern: &[u8]) -> error::Result<()> {
+        self.write_buffer(WriteBufferMode::EchoBuffer, 0, 0, pattern)
+            .await?;
+        let echoed = self
+            .read_buffer(ReadBufferMode::EchoBuffer, 0, 0, pattern.len() as u32)
+            .await?;
+        if let Some(at) = pattern.iter().zip(&echoed).position(|(a, b)| a != b) {
+            return Err(error::IscsiError::msg(format!(
+                "echo buffer mismatch at byte {at}"
+            )));
+        }
+        if echoed.len() != pattern.len() {
+            return Err(error::IscsiError::msg(format!(
+                "echo buffer returned {} of {} bytes",
+                echoed.len(),
+                pattern.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Download `image` as microcode to buffer `buffer_id`, in chunks no
+    /// larger than the buffer and aligned to its offset boundary. With
+    /// `defer` the image is saved but only activated by
+    /// [`activate_microcode`](Self::activate_microcode) (or the next reset);
+    /// otherwise the target activates it after the last chunk.
+    pub async fn download_microcode(
+        &self,
+        buffer_id: u8,
+        image: &[u8],
+        defer: bool,
+    ) -> error::Result<()> {
+        if image.is_empty() {
+            return Err(error::IscsiError::msg("empty microcode image"));
+        }
+        let len = check_field("microcode image length", image.len())?;
+        let desc = self.buffer_descriptor(buffer_id).await?;
+        let chunk = microcode_chunk_len(&desc, len)?;
+        let mode = if defer {
+            WriteBufferMode::DownloadOffsetsSaveDefer
+        } else {
+            WriteBufferMode::DownloadOffsetsSaveActivate
+        };
+        for (i, part) in image.chunks(chunk as usize).enumerate() {
+            let offset = i as u32 * chunk;
+            self.write_buffer(mode, buffer_id, offset, part).await?;
+        }
+        Ok(())
+    }
+
+    /// Activate microcode saved by a deferred
+    /// [`download_microcode`](Self::download_microcode).
+    pub async fn activate_microcode(&self, buffer_id: u8) -> error::Result<()> {
+        self.write_buffer(WriteBufferMode::ActivateDeferred, buffer_id, 0, Vec::new())
+            .await
+    }
+}
+
+/// `value` as a 24-bit CDB field.
+fn check_field(what: &str, value: usize) -> anyhow::Result<u32> {
+    ensure!(
+        value <= BUFFER_FIELD_MAX as usize,
+        "{what} {value} does not fit the 24-bit CDB field"
+    );
+    Ok(value as u32)
+}
+
+/// Bytes per WRITE BUFFER for an image of `len` bytes: the whole image if
+/// the target requires offset 0, else the buffer capacity rounded down to
+/// the offset boundary.
+fn microcode_chunk_len(desc: &BufferDescriptor, len: u32) -> anyhow::Result<u32> {
+    let Some(align) = desc.alignment() else {
+        return Ok(len);
+    };
+    let chunk = desc.capacity.min(MICROCODE_CHUNK_MAX) / align * align;
+    ensure!(
+        chunk > 0,
+        "buffer capacity {} is below its offset boundary {align}",
+        desc.capacity
+    );
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_follow_descriptor() {
+        let desc = BufferDescriptor {
+            offset_boundary: Some(9),
+            capacity: 3000,
+        };
+        assert_eq!(microcode_chunk_len(&desc, 10_000).expect("chunk"), 2560);
+
+        let big = BufferDescriptor {
+            offset_boundary: Some(0),
+            capacity: BUFFER_FIELD_MAX,
+        };
+        assert_eq!(
+            microcode_chunk_len(&big, 10_000_000).expect("chunk"),
+            MICROCODE_CHUNK_MAX
+        );
+
+        let fixed = BufferDescriptor {
+            offset_boundary: None,
+            capacity: 0,
+        };
+        assert_eq!(microcode_chunk_len(&fixed, 777).expect("chunk"), 777);
+
+        let tiny = BufferDescriptor {
+            offset_boundary: Some(12),
+            capacity: 512,
+        };
+        assert!(microcode_chunk_len(&tiny, 100).is_err());
+    }
+}
@@ -0,0 +1,293 @@
+//! What a logical unit supports, gathered in one place.
+//!
+//! [`LunHandle::capabilities`] reads the supported VPD pages, Block Limits,
+//! Logical Block Provisioning, READ CAPACITY(16) and, on LUNs with a copy
+//! manager, its EXTENDED COPY limits once, as well as the commands listed by
+//! REPORT SUPPORTED OPERATION CODES. Helpers then decide from
+//! [`TargetCapabilities`] which command to use (e.g. UNMAP or WRITE SAME
+//! with UNMAP=1, EXTENDED COPY or READ / WRITE) instead of probing with
+//! commands that may fail.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::{
+        inquiry::{
+            BlockLimits, LogicalBlockProvisioning, VpdPage, parse_vpd_supported_pages,
+        },
+        read_capacity::ReadCapacity16,
+        supported_opcodes::{
+            CommandDescriptor, OneCommandSupport, ReportingOptions,
+            build_report_supported_opcodes, parse_supported_opcode_one,
+            parse_supported_opcodes_all, parse_supported_opcodes_length,
+        },
+        xcopy::{CopyOperatingParameters, EXTENDED_COPY},
+    },
+    error,
+};
+
+/// Allocation length of the first all-commands REPORT SUPPORTED OPERATION
+/// CODES; longer lists are read again at their full size.
+const SUPPORTED_OPCODES_ALLOC_LEN: u32 = 8192;
+
+/// Largest all-commands REPORT SUPPORTED OPERATION CODES read, whatever
+/// length the target claims; descriptors past it are dropped.
+const SUPPORTED_OPCODES_MAX_ALLOC_LEN: u32 = 1 << 20;
+
+/// Allocation length of a one-command REPORT SUPPORTED OPERATION CODES:
+/// the header, a 32-byte CDB usage map and the timeouts descriptor.
+const ONE_COMMAND_ALLOC_LEN: u32 = 48;
+
+/// Command that deallocates logical blocks on a thin-provisioned LUN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscardMethod {
+    /// UNMAP.
+    Unmap,
+    /// WRITE SAME(16) with UNMAP=1.
+    WriteSame16,
+    /// WRITE SAME(10) with UNMAP=1.
+    WriteSame10,
+}
+
+/// Features of a logical unit. A page the target does not implement is
+/// `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetCapabilities {
+    /// Page codes listed by the Supported VPD Pages page (0x00).
+    pub vpd_pages: Vec<u8>,
+    /// READ CAPACITY(16) data.
+    pub read_capacity16: Option<ReadCapacity16>,
+    /// Block Limits VPD page (0xB0).
+    pub block_limits: Option<BlockLimits>,
+    /// Logical Block Provisioning VPD page (0xB2).
+    pub provisioning: Option<LogicalBlockProvisioning>,
+    /// 3PC bit of the standard INQUIRY: the LUN has a copy manager.
+    pub third_party_copy: bool,
+    /// RECEIVE COPY RESULTS / OPERATING PARAMETERS of the copy manager.
+    pub copy_manager: Option<CopyOperatingParameters>,
+    /// Commands listed by REPORT SUPPORTED OPERATION CODES.
+    pub supported_opcodes: Option<Vec<CommandDescriptor>>,
+}
+
+impl TargetCapabilities {
+    /// Whether the target lists `page` as supported.
+    pub fn supports_vpd(&self, page: VpdPage) -> bool {
+        self.vpd_pages.contains(&page.into())
+    }
+
+    /// Logical block provisioning management is enabled (LBPME).
+    pub fn is_thin_provisioned(&self) -> bool {
+        self.read_capacity16.is_some_and(|rc| rc.lbpme)
+    }
+
+    /// The command to deallocate blocks with, preferring UNMAP, then WRITE
+    /// SAME(16) and WRITE SAME(10). `None` unless the LUN is thin
+    /// provisioned and reports one of them.
+    pub fn discard_method(&self) -> Option<DiscardMethod> {
+        if !self.is_thin_provisioned() {
+            return None;
+        }
+        let lbp = self.provisioning?;
+        if lbp.lbpu {
+            Some(DiscardMethod::Unmap)
+        } else if lbp.lbpws {
+            Some(DiscardMethod::WriteSame16)
+        } else if lbp.lbpws10 {
+            Some(DiscardMethod::WriteSame10)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the LUN lists `opcode` (with `service_action`, if given) in
+    /// REPORT SUPPORTED OPERATION CODES; `None` when the target did not
+    /// report its commands.
+    pub fn supports_command(
+        &self,
+        opcode: u8,
+        service_action: Option<u16>,
+    ) -> Option<bool> {
+        let commands = self.supported_opcodes.as_ref()?;
+        Some(commands.iter().any(|c| {
+            c.opcode == opcode
+                && match (c.service_action, service_action) {
+                    (Some(have), Some(want)) => have == want,
+                    _ => true,
+                }
+        }))
+    }
+
+    /// Whether block-to-block EXTENDED COPY can be issued to the LUN: it
+    /// reports 3PC, does not leave EXTENDED COPY out of its supported
+    /// commands and its copy manager implements the descriptors needed.
+    pub fn supports_xcopy(&self) -> bool {
+        self.third_party_copy
+            && self.supports_command(EXTENDED_COPY, None) != Some(false)
+            && self
+                .copy_manager
+                .as_ref()
+                .is_some_and(|cm| cm.supports_block_copy())
+    }
+
+    /// Deallocated blocks read back as zeros (LBPRZ).
+    pub fn unmapped_reads_zero(&self) -> bool {
+        self.provisioning.is_some_and(|lbp| lbp.lbprz & 0x01 != 0)
+            || self.read_capacity16.is_some_and(|rc| rc.lbprz)
+    }
+}
+
+impl LunHandle {
+    /// Read the [`TargetCapabilities`] of the LUN. Only the VPD pages the
+    /// target lists are requested; READ CAPACITY(16) failing (e.g. on an
+    /// SBC-2 device) leaves `read_capacity16` empty, and so does RECEIVE
+    /// COPY RESULTS failing for `copy_manager`.
+    pub async fn capabilities(&self) -> error::Result<TargetCapabilities> {
+        let pages = self.vpd_page(VpdPage::SupportedPages).await?;
+        let mut caps = TargetCapabilities {
+            vpd_pages: parse_vpd_supported_pages(&pages)?,
+            third_party_copy: self.inquiry().await?.third_party_copy,
+            ..TargetCapabilities::default()
+        };
+        if caps.supports_vpd(VpdPage::BlockLimits) {
+            caps.block_limits = Some(self.block_limits().await?);
+        }
+        if caps.supports_vpd(VpdPage::LbProvisioning) {
+            caps.provisioning = Some(self.logical_block_provisioning().await?);
+        }
+        caps.read_capacity16 = self.read_capacity16().await.ok();
+        if caps.third_party_copy {
+            caps.copy_manager = self.copy_operating_parameters().await.ok();
+        }
+        caps.supported_opcodes = self.supported_operation_codes().await.ok();
+        Ok(caps)
+    }
+
+    /// Every command the LUN supports (REPORT SUPPORTED OPERATION CODES,
+    /// all-commands format), with their timeouts when the target reports
+    /// them. At most 1 MiB of descriptors is read.
+    pub async fn supported_operation_codes(
+        &self,
+    ) -> error::Result<Vec<CommandDescriptor>> {
+        let mut alloc_len = SUPPORTED_OPCODES_ALLOC_LEN;
+        loop {
+            let mut cdb = [0u8; 16];
+            build_report_supported_opcodes(
+                &mut cdb,
+                ReportingOptions::All,
+                true,
+                alloc_len,
+                0,
+            );
+            let data = self.read_cdb(cdb, alloc_len, None).await?;
+            let full = parse_supported_opcodes_length(&data)?;
+            if full <= data.len()
+                || full <= alloc_len as usize
+                || alloc_len == SUPPORTED_OPCODES_MAX_ALLOC_LEN
+            {
+                return Ok(parse_supported_opcodes_all(&data)?);
+            }
+            alloc_len = u32::try_from(full)
+                .unwrap_or(u32::MAX)
+                .min(SUPPORTED_OPCODES_MAX_ALLOC_LEN);
+        }
+    }
+
+    /// Support of one command (REPORT SUPPORTED OPERATION CODES,
+    /// one-command format). `service_action` is required for opcodes that
+    /// have service actions.
+    pub async fn command_support(
+        &self,
+        opcode: u8,
+        service_action: Option<u16>,
+    ) -> error::Result<OneCommandSupport> {
+        let options = match service_action {
+            Some(sa) => ReportingOptions::OneServiceAction(opcode, sa),
+            None => ReportingOptions::OneCommand(opcode),
+        };
+        let mut cdb = [0u8; 16];
+        build_report_supported_opcodes(&mut cdb, options, true, ONE_COMMAND_ALLOC_LEN, 0);
+        let data = self.read_cdb(cdb, ONE_COMMAND_ALLOC_LEN, None).await?;
+        Ok(parse_supported_opcode_one(&data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_block::{
+        inquiry::parse_vpd_logical_block_provisioning,
+        read_capacity::parse_read_capacity16,
+    };
+
+    fn caps(lbpme: bool, lbp_flags: u8) -> TargetCapabilities {
+        let mut rc16 = [0u8; 32];
+        rc16[8..12].copy_from_slice(&512u32.to_be_bytes());
+        rc16[14] = if lbpme { 0x80 } else { 0 };
+        let lbp = [0x00, 0xB2, 0x00, 0x04, 0x00, lbp_flags, 0x02, 0x00];
+        TargetCapabilities {
+            vpd_pages: vec![0x00, 0xB0, 0xB2],
+            read_capacity16: parse_read_capacity16(&rc16).ok(),
+            block_limits: None,
+            provisioning: parse_vpd_logical_block_provisioning(&lbp).ok(),
+            ..TargetCapabilities::default()
+        }
+    }
+
+    #[test]
+    fn picks_discard_method_by_preference() {
+        assert_eq!(
+            caps(true, 0xE0).discard_method(),
+            Some(DiscardMethod::Unmap)
+        );
+        assert_eq!(
+            caps(true, 0x60).discard_method(),
+            Some(DiscardMethod::WriteSame16)
+        );
+        assert_eq!(
+            caps(true, 0x20).discard_method(),
+            Some(DiscardMethod::WriteSame10)
+        );
+        assert_eq!(caps(true, 0x00).discard_method(), None);
+        assert_eq!(caps(false, 0xE0).discard_method(), None);
+    }
+
+    #[test]
+    fn checks_reported_commands() {
+        let mut caps = caps(true, 0x80);
+        assert_eq!(caps.supports_command(0x28, None), None);
+
+        caps.supported_opcodes = Some(vec![
+            CommandDescriptor {
+                opcode: 0x28,
+                service_action: None,
+                cdb_len: 10,
+                timeouts: None,
+            },
+            CommandDescriptor {
+                opcode: 0x9E,
+                service_action: Some(0x10),
+                cdb_len: 16,
+                timeouts: None,
+            },
+        ]);
+        assert_eq!(caps.supports_command(0x28, None), Some(true));
+        assert_eq!(caps.supports_command(0x9E, Some(0x10)), Some(true));
+        assert_eq!(caps.supports_command(0x9E, Some(0x12)), Some(false));
+        assert_eq!(caps.supports_command(0x83, None), Some(false));
+
+        caps.third_party_copy = true;
+        caps.copy_manager = None;
+        assert!(!caps.supports_xcopy());
+    }
+
+    #[test]
+    fn reports_zeroing_and_pages() {
+        let thin = caps(true, 0x84);
+        assert!(thin.unmapped_reads_zero());
+        assert!(thin.supports_vpd(VpdPage::LbProvisioning));
+        assert!(!thin.supports_vpd(VpdPage::UnitSerial));
+        assert!(!caps(true, 0x80).unmapped_reads_zero());
+    }
+}
@@ -7,19 +7,18 @@ mod write;
 use std::{
     sync::{
         Arc, Weak,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU8, AtomicU32, Ordering},
     },
     time::Duration,
 };
 
 use anyhow::{Result, anyhow, bail};
 use once_cell::sync::OnceCell;
+use thiserror::Error;
+#[cfg(unix)]
+use tokio::net::UnixStream;
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::{
-        TcpStream,
-        tcp::{OwnedReadHalf, OwnedWriteHalf},
-    },
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     select,
     sync::Mutex,
     time::{Instant, sleep},
@@ -31,11 +30,20 @@ use crate::{
     cfg::config::Config,
     client::{
         common::{io_with_timeout, is_timeout_error},
+        events::PoolEvent,
         pending_requests::PendingRequests,
         pool_sessions::Pool,
+        portal::{LocalBind, PortalTarget, connect_happy_eyeballs},
+        socks5,
+        spawn::{CatchUnwind, Spawner, TokioSpawner},
+        status::ConnectionHealth,
+        throttle::TokenBucket,
+        tls::TlsSettings,
+        transport::{self, BoxedReader, BoxedWriter, Connected, ReadStream, WriteStream},
     },
+    error::{self, IscsiError},
     models::{
-        identifiers::{Cid, Lun, Tsih},
+        identifiers::{Cid, Itt, Lun, Tsih},
         nop::request::NopOutRequest,
     },
     state_machine::nop_states::NopCtx,
@@ -56,7 +64,32 @@ struct SessionRef {
     cid: Cid,
 }
 
-/// Represents a single iSCSI connection over a TCP stream.
+/// The connection failed (its read loop exited or panicked, a keep-alive was
+/// not answered, ...) while the request was waiting for its response.
+#[derive(Debug, Error)]
+#[error("connection lost: {reason}")]
+pub struct DisconnectError {
+    /// Why the connection was poisoned.
+    pub reason: String,
+}
+
+/// The connection was shut down on purpose (`kill_now`, quiesced writes,
+/// pool cancellation) before the request got its response.
+#[derive(Debug, Error)]
+#[error("connection shut down: {reason}")]
+pub struct ShutdownError {
+    /// What shut the connection down.
+    pub reason: String,
+}
+
+/// The connection refused a SCSI Command PDU before writing any byte of it
+/// (poisoned, draining, shut down), so the target never saw the command and
+/// it can be re-issued on another connection.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct RequestNotSentError(pub anyhow::Error);
+
+/// Represents a single iSCSI connection over a byte stream (TCP or TLS).
 ///
 /// This struct manages sending requests (PDUs) and receiving responses, and is
 /// responsible for framing PDUs based on the information in their headers. It
@@ -64,10 +97,10 @@ struct SessionRef {
 /// iSCSI protocol.
 #[derive(Debug)]
 pub struct ClientConnection {
-    /// TCP read half protected by mutex for concurrent access
-    pub(crate) reader: Mutex<OwnedReadHalf>,
-    /// TCP write half protected by mutex for concurrent access
-    pub(crate) writer: Mutex<OwnedWriteHalf>,
+    /// Stream read half protected by mutex for concurrent access
+    pub(crate) reader: Mutex<BoxedReader>,
+    /// Stream write half protected by mutex for concurrent access
+    pub(crate) writer: Mutex<BoxedWriter>,
     /// Configuration parameters for this connection
     pub(crate) cfg: Config,
     /// Routes responses from the read loop to the request that owns the ITT.
@@ -82,42 +115,265 @@ pub struct ClientConnection {
     /// "Soft stop" gate for writes: when cancelled, new writes are rejected,
     /// but the read loop keeps draining in-flight responses.
     pub(crate) stop_writes: CancellationToken,
+    /// Set by [`ClientConnection::drain`]: no new tasks, but in-flight ones
+    /// may finish and a Logout may still be sent.
+    draining: AtomicBool,
+    /// [`ConnectionHealth`] as `u8`.
+    health: AtomicU8,
     poisoned: AtomicBool,
+    /// Why the connection was poisoned (first reason wins).
+    poison_reason: OnceCell<String>,
+    /// Why the connection was shut down on purpose, if it was.
+    shutdown_reason: OnceCell<String>,
+    /// Next StatSN expected from the target. Maintained by the read loop for
+    /// every status-bearing PDU; contexts only read it.
+    pub(crate) exp_stat_sn: Arc<AtomicU32>,
+    /// Latest ExpCmdSN / MaxCmdSN announced by the target on this connection.
+    exp_cmd_sn: AtomicU32,
+    max_cmd_sn: AtomicU32,
+    /// ExpStatSN carried by the last PDU written, i.e. what the target has
+    /// been told so far.
+    acked_stat_sn: AtomicU32,
+    /// Set while an automatic ExpStatSN acknowledgment is being sent.
+    stat_sn_ack_pending: AtomicBool,
+    /// Rate limit for Data-Out / immediate data payload.
+    data_out_limit: Option<TokenBucket>,
+    /// Rate limit for Data-In payload.
+    data_in_limit: Option<TokenBucket>,
+    /// Runs the read loop and the tasks it starts (NOP-In auto-replies).
+    spawner: Arc<dyn Spawner>,
 }
 
 impl ClientConnection {
-    /// Establishes a new TCP connection to the given address.
-    pub async fn connect(cfg: Config, cancel: CancellationToken) -> Result<Arc<Self>> {
+    /// Establishes a new connection to the target, trying `TargetAddress`
+    /// and then every entry of `Portals` in order until one accepts, and
+    /// wraps it in TLS when `login.transport.Tls` is set.
+    pub async fn connect(
+        cfg: Config,
+        cancel: CancellationToken,
+    ) -> error::Result<Arc<Self>> {
+        Self::connect_with_spawner(cfg, cancel, Arc::new(TokioSpawner)).await
+    }
+
+    /// Like [`ClientConnection::connect`], with the background tasks of the
+    /// connection started through `spawner`.
+    pub async fn connect_with_spawner(
+        cfg: Config,
+        cancel: CancellationToken,
+        spawner: Arc<dyn Spawner>,
+    ) -> error::Result<Arc<Self>> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
-        let stream = io_with_timeout(
-            "connect",
-            TcpStream::connect(&cfg.login.transport.target_address),
-            cfg.runtime.timeout_connection,
-            &cancel,
+        let (stream, portal) = Self::connect_any_portal(&cfg, &cancel).await?;
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        let stream = match stream {
+            Connected::Tcp(tcp)
+                if cfg.runtime.io_backend == crate::cfg::enums::IoBackend::IoUring =>
+            {
+                Connected::Uring(Box::new(
+                    crate::client::uring::UringStream::new(tcp)
+                        .map_err(|e| anyhow!("io_uring setup for {portal}: {e}"))?,
+                ))
+            },
+            other => other,
+        };
+
+        let (r, w) = match &cfg.login.transport.tls {
+            None => stream.into_split(),
+            Some(settings) => {
+                Self::tls_handshake(stream, settings, &portal, &cfg, &cancel).await?
+            },
+        };
+        Ok(Self::start(r, w, cfg, cancel, spawner))
+    }
+
+    #[cfg(feature = "tls")]
+    async fn tls_handshake(
+        stream: Connected,
+        settings: &TlsSettings,
+        portal: &PortalTarget,
+        cfg: &Config,
+        cancel: &CancellationToken,
+    ) -> Result<(BoxedReader, BoxedWriter)> {
+        let name = settings
+            .server_name_for(portal)
+            .ok_or_else(|| anyhow!("Tls over {portal} needs ServerName"))?;
+        let client = crate::client::tls::TlsClient::new(settings, name)?;
+        let handshake = async {
+            Ok(match stream {
+                Connected::Tcp(tcp) => transport::split(client.connect(tcp).await?),
+                #[cfg(unix)]
+                Connected::Unix(unix) => transport::split(client.connect(unix).await?),
+                #[cfg(all(target_os = "linux", feature = "io-uring"))]
+                Connected::Uring(uring) => transport::split(client.connect(uring).await?),
+            })
+        };
+        io_with_timeout(
+            "TLS handshake",
+            handshake,
+            cfg.runtime.connect_timeout(),
+            cancel,
         )
-        .await?;
-        stream.set_nodelay(true)?;
+        .await
+    }
 
-        let (r, w) = stream.into_split();
+    #[cfg(not(feature = "tls"))]
+    async fn tls_handshake(
+        _stream: Connected,
+        _settings: &TlsSettings,
+        portal: &PortalTarget,
+        _cfg: &Config,
+        _cancel: &CancellationToken,
+    ) -> Result<(BoxedReader, BoxedWriter)> {
+        bail!("Tls is configured for {portal} but the `tls` feature is disabled")
+    }
 
-        let conn = Self::from_split_no_reader(r, w, cfg, cancel);
+    /// Runs a connection over an already established stream, e.g. one
+    /// opened by the application through a transport this crate does not
+    /// provide.
+    pub fn from_stream<S>(
+        stream: S,
+        cfg: Config,
+        cancel: CancellationToken,
+    ) -> Arc<Self>
+    where
+        S: AsyncRead + AsyncWrite + Send + std::fmt::Debug + 'static,
+    {
+        Self::from_stream_with_spawner(stream, cfg, cancel, Arc::new(TokioSpawner))
+    }
+
+    /// Like [`ClientConnection::from_stream`], with the background tasks of
+    /// the connection started through `spawner`.
+    pub fn from_stream_with_spawner<S>(
+        stream: S,
+        cfg: Config,
+        cancel: CancellationToken,
+        spawner: Arc<dyn Spawner>,
+    ) -> Arc<Self>
+    where
+        S: AsyncRead + AsyncWrite + Send + std::fmt::Debug + 'static,
+    {
+        let (r, w) = transport::split(stream);
+        Self::start(r, w, cfg, cancel, spawner)
+    }
+
+    /// Wraps the halves and spawns the read loop under a supervisor.
+    ///
+    /// Whenever the read loop ends, by error or by panic, the supervisor
+    /// poisons the connection: pending waiters fail with
+    /// [`DisconnectError`], new requests are refused and, for pooled
+    /// connections, `runtime.Reconnect` takes over.
+    fn start(
+        r: impl ReadStream + 'static,
+        w: impl WriteStream + 'static,
+        cfg: Config,
+        cancel: CancellationToken,
+        spawner: Arc<dyn Spawner>,
+    ) -> Arc<Self> {
+        let conn = Self::from_split_no_reader(r, w, cfg, cancel, spawner);
 
         let reader = Arc::clone(&conn);
-        tokio::spawn(async move {
-            #[cfg(feature = "profiling-puffin")]
-            profiling::register_thread!("iscsi-client-rs::read-loop");
-            if let Err(e) = Arc::clone(&reader).read_loop().await {
-                if is_timeout_error(&e) {
-                    reader.poison(format!("read loop timeout: {e}"));
-                } else {
-                    reader.poison(format!("read loop exited: {e}"));
+        conn.spawner.spawn(Box::pin(async move {
+            let outcome = CatchUnwind::new({
+                let reader = Arc::clone(&reader);
+                async move {
+                    #[cfg(feature = "profiling-puffin")]
+                    profiling::register_thread!("iscsi-client-rs::read-loop");
+                    reader.read_loop().await
                 }
-                warn!("read loop exited: {e}");
+            })
+            .await;
+            let reason = match outcome {
+                Ok(Ok(())) => "read loop stopped".to_string(),
+                Ok(Err(e)) if is_timeout_error(&e) => format!("read loop timeout: {e}"),
+                Ok(Err(e)) => format!("read loop exited: {e:#}"),
+                Err(payload) => {
+                    format!("read loop panicked: {}", panic_message(payload))
+                },
+            };
+            // A cancelled token without a failure first is a shutdown, so
+            // waiters get a ShutdownError rather than a DisconnectError.
+            if reader.cancel.is_cancelled() && !reader.is_poisoned() {
+                reader.record_shutdown("connection cancelled");
+            } else {
+                warn!("{reason}");
             }
-        });
+            reader.poison(reason);
+        }));
 
-        Ok(conn)
+        conn
+    }
+
+    async fn connect_any_portal(
+        cfg: &Config,
+        cancel: &CancellationToken,
+    ) -> Result<(Connected, PortalTarget)> {
+        let portals = cfg.login.transport.all_portals();
+        if portals.is_empty() {
+            bail!("no target portal configured");
+        }
+
+        let local = LocalBind::from_config(cfg);
+        let mut failures = Vec::with_capacity(portals.len());
+        let proxy = match &cfg.login.transport.socks5_proxy {
+            Some(proxy) => Some((proxy, proxy.portal()?)),
+            None => None,
+        };
+        for portal in &portals {
+            let result = async {
+                let target: PortalTarget = portal.parse()?;
+                let stream = match &target {
+                    PortalTarget::Tcp(addr) => {
+                        let connect = async {
+                            match &proxy {
+                                None => connect_happy_eyeballs(addr, &local).await,
+                                Some((proxy, proxy_addr)) => {
+                                    let mut stream =
+                                        connect_happy_eyeballs(proxy_addr, &local)
+                                            .await?;
+                                    socks5::handshake(&mut stream, proxy, addr).await?;
+                                    Ok(stream)
+                                },
+                            }
+                        };
+                        Connected::Tcp(
+                            io_with_timeout(
+                                "connect",
+                                connect,
+                                cfg.runtime.connect_timeout(),
+                                cancel,
+                            )
+                            .await?,
+                        )
+                    },
+                    #[cfg(unix)]
+                    PortalTarget::Unix(path) => Connected::Unix(
+                        io_with_timeout(
+                            "connect",
+                            UnixStream::connect(path),
+                            cfg.runtime.connect_timeout(),
+                            cancel,
+                        )
+                        .await?,
+                    ),
+                    #[cfg(not(unix))]
+                    PortalTarget::Unix(_) => {
+                        bail!("Unix domain sockets are not supported on this platform")
+                    },
+                };
+                anyhow::Ok((stream, target))
+            }
+            .await;
+            match result {
+                Ok(connected) => return Ok(connected),
+                Err(error) if cancel.is_cancelled() => return Err(error),
+                Err(error) => {
+                    warn!("portal {portal} unreachable: {error:#}");
+                    failures.push(format!("{portal}: {error:#}"));
+                },
+            }
+        }
+        bail!("all portals unreachable: {}", failures.join("; "))
     }
 
     pub(crate) fn bind_pool_session(&self, pool: Weak<Pool>, tsih: Tsih, cid: Cid) {
@@ -126,11 +382,8 @@ impl ClientConnection {
 
     #[inline]
     pub(super) fn ensure_active(&self) -> Result<()> {
-        if self.is_poisoned() {
-            bail!("connection poisoned");
-        }
-        if self.cancel.is_cancelled() {
-            bail!("cancelled");
+        if self.is_poisoned() || self.cancel.is_cancelled() {
+            return Err(self.closed_error());
         }
         Ok(())
     }
@@ -139,7 +392,10 @@ impl ClientConnection {
     pub(super) fn ensure_writable(&self) -> Result<()> {
         self.ensure_active()?;
         if self.stop_writes.is_cancelled() {
-            bail!("writes are quiesced");
+            return Err(ShutdownError {
+                reason: "writes are quiesced".to_string(),
+            }
+            .into());
         }
         Ok(())
     }
@@ -154,7 +410,7 @@ impl ClientConnection {
 
     pub(super) async fn read_exact_with_timeout(
         &self,
-        reader: &mut OwnedReadHalf,
+        reader: &mut BoxedReader,
         buf: &mut [u8],
         label: &'static str,
     ) -> Result<()> {
@@ -163,7 +419,7 @@ impl ClientConnection {
         io_with_timeout(
             label,
             reader.read_exact(buf),
-            self.cfg.runtime.timeout_connection,
+            self.cfg.runtime.io_timeout(),
             &self.cancel,
         )
         .await
@@ -178,7 +434,7 @@ impl ClientConnection {
 
     pub(super) async fn write_all_with_timeout(
         &self,
-        writer: &mut OwnedWriteHalf,
+        writer: &mut BoxedWriter,
         buf: &[u8],
         label: &'static str,
     ) -> Result<()> {
@@ -187,7 +443,7 @@ impl ClientConnection {
         io_with_timeout(
             label,
             writer.write_all(buf),
-            self.cfg.runtime.timeout_connection,
+            self.cfg.runtime.io_timeout(),
             &self.cancel,
         )
         .await
@@ -206,21 +462,36 @@ impl ClientConnection {
     }
 
     pub(crate) fn from_split_no_reader(
-        r: OwnedReadHalf,
-        w: OwnedWriteHalf,
+        r: impl ReadStream + 'static,
+        w: impl WriteStream + 'static,
         cfg: Config,
         cancel: CancellationToken,
+        spawner: Arc<dyn Spawner>,
     ) -> Arc<Self> {
         let pending = PendingRequests::new(cfg.runtime.response_queue_capacity);
+        let data_out_limit = cfg.runtime.throttle.data_out();
+        let data_in_limit = cfg.runtime.throttle.data_in();
         Arc::new(Self {
-            reader: Mutex::new(r),
-            writer: Mutex::new(w),
+            reader: Mutex::new(Box::new(r)),
+            writer: Mutex::new(Box::new(w)),
             cfg,
             pending,
             session_ref: OnceCell::new(),
             cancel,
             stop_writes: CancellationToken::new(),
+            draining: AtomicBool::new(false),
+            health: AtomicU8::new(ConnectionHealth::Connecting as u8),
             poisoned: AtomicBool::new(false),
+            poison_reason: OnceCell::new(),
+            shutdown_reason: OnceCell::new(),
+            exp_stat_sn: Arc::new(AtomicU32::new(0)),
+            exp_cmd_sn: AtomicU32::new(0),
+            max_cmd_sn: AtomicU32::new(0),
+            acked_stat_sn: AtomicU32::new(0),
+            stat_sn_ack_pending: AtomicBool::new(false),
+            data_out_limit,
+            data_in_limit,
+            spawner,
         })
     }
 
@@ -257,16 +528,49 @@ impl ClientConnection {
     /// Convenience: forbid new writes and wait for the input side to drain.
     /// No FIN is sent; use `half_close_writes()` if you also want a write-side
     /// FIN.
-    pub async fn graceful_quiesce(&self, max_wait: Duration) -> Result<()> {
+    pub async fn graceful_quiesce(&self, max_wait: Duration) -> error::Result<()> {
+        self.set_health(ConnectionHealth::Draining);
         self.quiesce_writes();
-        self.wait_inflight_drained(max_wait).await
+        self.wait_inflight_drained(max_wait)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Stop accepting new tasks on this connection and wait for the in-flight
+    /// ones to complete. Unlike [`ClientConnection::graceful_quiesce`],
+    /// Data-Out for running tasks and a final Logout can still be written,
+    /// so the connection can be logged out on its own.
+    pub async fn drain(&self, max_wait: Duration) -> error::Result<()> {
+        self.draining.store(true, Ordering::SeqCst);
+        self.set_health(ConnectionHealth::Draining);
+        self.wait_inflight_drained(max_wait)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Whether [`ClientConnection::drain`] was called.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
     }
 
     /// Hard stop: cancel both read and write paths immediately.
     /// Prefer `graceful_quiesce()` + `half_close_writes()` for graceful
     /// shutdowns.
+    ///
+    /// Requests still waiting for a response fail with [`ShutdownError`].
     pub fn kill_now(&self) {
+        self.record_shutdown("connection killed");
         self.cancel_now();
+        self.pending.abort_all();
+    }
+
+    fn record_shutdown(&self, reason: &str) {
+        let _ = self.shutdown_reason.set(reason.to_string());
+    }
+
+    /// Whether the connection was shut down on purpose rather than failing.
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown_reason.get().is_some()
     }
 
     pub fn is_poisoned(&self) -> bool {
@@ -275,31 +579,195 @@ impl ClientConnection {
 
     pub(crate) fn poison(&self, reason: impl Into<String>) {
         let reason = reason.into();
-        let first_poison = !self.poisoned.swap(true, Ordering::SeqCst);
+        // Record the reason first so waiters woken below can report it.
+        if self.poison_reason.get().is_none() {
+            warn!("connection poisoned: {reason}");
+        }
+        let _ = self.poison_reason.set(reason);
+        if !self.poisoned.swap(true, Ordering::SeqCst) {
+            self.set_health(ConnectionHealth::Failed);
+        }
         self.stop_writes.cancel();
         self.cancel.cancel();
         self.pending.abort_all();
+    }
 
-        if first_poison {
-            warn!("connection poisoned: {reason}");
+    /// Current lifecycle state of the connection.
+    pub fn health(&self) -> ConnectionHealth {
+        ConnectionHealth::from_u8(self.health.load(Ordering::SeqCst))
+    }
+
+    /// Move to `to` unless the connection already `Failed` (terminal) or is
+    /// `Draining` (which only ends in `Failed`). Only a logged-in connection
+    /// can become `Degraded`.
+    pub(crate) fn set_health(&self, to: ConnectionHealth) {
+        self.transition_health(
+            |from| match (from, to) {
+                (ConnectionHealth::Failed, _) => false,
+                (ConnectionHealth::Draining, _) => to == ConnectionHealth::Failed,
+                (_, ConnectionHealth::Degraded) => from == ConnectionHealth::LoggedIn,
+                _ => from != to,
+            },
+            to,
+        );
+    }
+
+    /// Apply `to` if `allowed(current)`; once the connection is bound to a
+    /// pool the change is published as [`PoolEvent::HealthChanged`].
+    fn transition_health(
+        &self,
+        allowed: impl Fn(ConnectionHealth) -> bool,
+        to: ConnectionHealth,
+    ) {
+        let Ok(previous) =
+            self.health
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                    allowed(ConnectionHealth::from_u8(current)).then_some(to as u8)
+                })
+        else {
+            return;
+        };
+        let from = ConnectionHealth::from_u8(previous);
+        debug!("connection health {from:?} -> {to:?}");
+        if let Some(sr) = self.session_ref.get()
+            && let Some(pool) = sr.pool.upgrade()
+        {
+            pool.emit_event(PoolEvent::HealthChanged {
+                tsih: sr.tsih,
+                cid: sr.cid,
+                from,
+                to,
+            });
         }
     }
 
-    pub async fn send_keepalive_via_pool_lun(self: &Arc<Self>, lun: Lun) -> Result<()> {
-        let sr = self
-            .session_ref
-            .get()
-            .ok_or_else(|| anyhow!("connection is not bound to a pool/session"))?;
+    /// Reason passed to the first `poison()` call, if any.
+    pub fn poison_reason(&self) -> Option<&str> {
+        self.poison_reason.get().map(String::as_str)
+    }
+
+    /// Error for a request that cannot complete because the connection
+    /// stopped: [`ShutdownError`] if it was shut down on purpose,
+    /// [`DisconnectError`] if it failed.
+    pub(super) fn closed_error(&self) -> anyhow::Error {
+        if let Some(reason) = self.shutdown_reason.get() {
+            return ShutdownError {
+                reason: reason.clone(),
+            }
+            .into();
+        }
+        if self.is_poisoned() {
+            return DisconnectError {
+                reason: self.poison_reason().unwrap_or("poisoned").to_string(),
+            }
+            .into();
+        }
+        ShutdownError {
+            reason: "cancelled".to_string(),
+        }
+        .into()
+    }
+
+    /// Resolves once the connection stops, i.e. it was poisoned, killed or
+    /// its pool was cancelled.
+    pub(crate) async fn closed(&self) {
+        self.cancel.cancelled().await
+    }
+
+    /// Latest (ExpCmdSN, MaxCmdSN) command window announced by the target.
+    pub fn cmd_window(&self) -> (u32, u32) {
+        (
+            self.exp_cmd_sn.load(Ordering::SeqCst),
+            self.max_cmd_sn.load(Ordering::SeqCst),
+        )
+    }
+
+    /// ITTs of the requests still waiting for their final response.
+    pub fn pending_itts(&self) -> Vec<Itt> {
+        self.pending.inflight_tags()
+    }
+
+    /// Number of StatSNs received (up to `exp_stat_sn`) but not yet
+    /// acknowledged in any PDU sent on this connection.
+    pub(crate) fn unacked_stat_sn(&self, exp_stat_sn: u32) -> u32 {
+        let lag = exp_stat_sn.wrapping_sub(self.acked_stat_sn.load(Ordering::SeqCst));
+        // Serial arithmetic: a "negative" lag means we are already ahead.
+        if (lag as i32) < 0 { 0 } else { lag }
+    }
+
+    /// Claim the right to send the next automatic acknowledgment; released
+    /// by [`ClientConnection::finish_stat_sn_ack`].
+    pub(crate) fn begin_stat_sn_ack(&self) -> bool {
+        !self.stat_sn_ack_pending.swap(true, Ordering::SeqCst)
+    }
+
+    pub(crate) fn finish_stat_sn_ack(&self) {
+        self.stat_sn_ack_pending.store(false, Ordering::SeqCst);
+    }
+
+    /// Give up on `itt`: drop its response channels and silently discard
+    /// any PDU the target still sends for it.
+    pub(crate) fn abandon_task(&self, itt: Itt) {
+        self.pending.abandon(itt);
+    }
+
+    /// Fail the waiter of `itt` with a task-terminated error because a TMF
+    /// ended the task. `expect_late` keeps dropping PDUs the target may
+    /// still send for it (FastAbort).
+    pub(crate) fn terminate_task(&self, itt: Itt, expect_late: bool) {
+        self.pending.terminate(itt, expect_late);
+    }
+
+    /// Stop filtering late PDUs for an abandoned `itt` (e.g. once the target
+    /// confirmed the abort, after which it sends nothing more for the task).
+    pub(crate) fn forget_abandoned_task(&self, itt: Itt) {
+        self.pending.forget_abandoned(itt);
+    }
+
+    pub async fn send_keepalive_via_pool_lun(
+        self: &Arc<Self>,
+        lun: Lun,
+    ) -> error::Result<()> {
+        let sr = self.session_ref.get().ok_or_else(|| {
+            IscsiError::msg("connection is not bound to a pool/session")
+        })?;
         let pool = sr
             .pool
             .upgrade()
-            .ok_or_else(|| anyhow!("pool has been dropped"))?;
+            .ok_or_else(|| IscsiError::msg("pool has been dropped"))?;
         let ttt = NopOutRequest::DEFAULT_TAG;
 
-        pool.execute_with_ctx(sr.tsih, sr.cid, move |env| {
-            NopCtx::from_execute_env(env, lun, ttt)
-        })
-        .await?;
-        Ok(())
+        let result = pool
+            .execute_with_ctx(sr.tsih, sr.cid, move |env| {
+                NopCtx::from_execute_env(env, lun, ttt)
+            })
+            .await;
+        // A keep-alive the target does not answer means the link is dead; an
+        // answered one clears an earlier degradation.
+        if result.is_ok() {
+            self.transition_health(
+                |from| from == ConnectionHealth::Degraded,
+                ConnectionHealth::LoggedIn,
+            );
+        }
+        if let Err(error) = &result {
+            pool.emit_event(PoolEvent::NopTimeout {
+                tsih: sr.tsih,
+                cid: sr.cid,
+                error: format!("{error:#}"),
+            });
+            self.poison(format!("keepalive failed: {error:#}"));
+        }
+        result.map(drop)
+    }
+}
+
+/// Text of a panic payload (`&str` or `String`, as produced by `panic!`).
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map_or_else(|| "non-string payload".to_string(), |m| (*m).to_string()),
     }
 }
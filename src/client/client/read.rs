@@ -1,7 +1,12 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::{any::type_name, fmt::Debug, sync::Arc};
+use std::{
+    any::type_name,
+    fmt::Debug,
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
 
 use anyhow::{Result, anyhow, bail};
 use bytes::{Bytes, BytesMut};
@@ -9,13 +14,21 @@ use tracing::{debug, warn};
 
 use super::ClientConnection;
 use crate::{
-    client::{common::RawPdu, pdu_connection::FromBytes},
+    client::{
+        common::RawPdu, events::PoolEvent, pdu_connection::FromBytes,
+        pool_sessions::TaskTerminatedError, status::ConnectionHealth,
+    },
     models::{
+        async_message::{common::AsyncEvent, response::AsyncMessage},
         common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data_fromat::{PduResponse, ZeroCopyType},
+        data::common::DataInFlags,
+        data_fromat::{DigestMismatchError, PduResponse, ZeroCopyType},
         identifiers::Itt,
+        login::common::LoginFlags,
         nop::response::NopInResponse,
+        opcode::Opcode,
         parse::Pdu,
+        snapshot::PduSnapshot,
     },
 };
 
@@ -27,6 +40,9 @@ impl ClientConnection {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
         self.ensure_active()?;
+        if self.pending.take_terminated(itt) {
+            return Err(TaskTerminatedError { itt }.into());
+        }
         let mut receiver = self.pending.take_receiver(itt)?;
 
         let RawPdu {
@@ -34,13 +50,25 @@ impl ClientConnection {
             payload,
         } = tokio::select! {
             biased;
-            response = receiver.recv() => {
-                response.ok_or_else(|| anyhow!("connection closed before response"))?
+            response = receiver.recv() => match response {
+                Some(response) => response,
+                None if self.pending.take_terminated(itt) => {
+                    return Err(TaskTerminatedError { itt }.into());
+                },
+                None if self.is_poisoned() || self.cancel.is_cancelled() => {
+                    return Err(self.closed_error());
+                },
+                None => return Err(anyhow!("connection closed before response")),
             },
-            _ = self.cancel.cancelled() => return Err(anyhow!("cancelled")),
+            _ = self.cancel.cancelled() => return Err(self.closed_error()),
         };
 
-        let pdu_header = Pdu::from_bhs_bytes(&mut header)?;
+        let pdu_header = match Pdu::from_bhs_bytes(&mut header) {
+            Ok(pdu_header) => pdu_header,
+            Err(error) => {
+                return Err(error.into_anyhow().context(PduSnapshot::new(&header)));
+            },
+        };
         debug!(
             "{} is final bit: {}",
             type_name::<T>(),
@@ -67,7 +95,13 @@ impl ClientConnection {
         let (mut pdu, data) = self.read_response_raw(itt).await?;
         if let Err(error) = pdu.parse_with_buff(&data) {
             self.poison(format!("invalid response PDU: {error}"));
-            return Err(error);
+            // Digest errors carry the header themselves.
+            if error.is::<DigestMismatchError>() {
+                return Err(error.into_anyhow());
+            }
+            return Err(error
+                .into_anyhow()
+                .context(PduSnapshot::new(&pdu.header_buf)));
         }
         Ok(pdu)
     }
@@ -82,6 +116,22 @@ impl ClientConnection {
             #[cfg(feature = "profiling-puffin")]
             profiling::finish_frame!();
             let (raw_itt, is_final, pdu) = self.read_pdu(&mut scratch).await?;
+            self.observe_stat_sn(&pdu.header);
+            self.observe_cmd_window(&pdu.header);
+            if let Some(limit) = &self.data_in_limit
+                && Opcode::from_u6(pdu.header[0] & 0x3F) == Some(Opcode::ScsiDataIn)
+                && !pdu.payload.is_empty()
+            {
+                tokio::select! {
+                    _ = limit.acquire(pdu.payload.len()) => {},
+                    _ = self.cancel.cancelled() => bail!("cancelled"),
+                }
+            }
+
+            if Opcode::from_u6(pdu.header[0] & 0x3F) == Some(Opcode::AsyncMsg) {
+                self.handle_async_message(pdu.header);
+                continue;
+            }
 
             if self
                 .pending
@@ -92,6 +142,11 @@ impl ClientConnection {
                 continue;
             }
 
+            if self.pending.absorb_abandoned(raw_itt, is_final) {
+                debug!("dropping late PDU for abandoned itt={}", raw_itt);
+                continue;
+            }
+
             if self
                 .try_handle_unsolicited_nop_in(pdu.header, pdu.payload)
                 .await
@@ -109,7 +164,143 @@ impl ClientConnection {
                 continue;
             }
 
-            bail!("no pending request for itt={raw_itt}");
+            return Err(anyhow!("no pending request for itt={raw_itt}")
+                .context(PduSnapshot::new(&pdu.header)));
+        }
+    }
+
+    /// Advance ExpStatSN from a received BHS (RFC 7143 § 4.2.2.2).
+    ///
+    /// Login responses seed the counter; other status-bearing PDUs only move
+    /// it forward in serial-number order. Data-In counts only with the S bit,
+    /// while R2T and task-less NOP-In merely announce the next StatSN.
+    fn observe_stat_sn(&self, header: &[u8; HEADER_LEN]) {
+        let stat_sn =
+            u32::from_be_bytes([header[24], header[25], header[26], header[27]]);
+        let itt = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+        let next = match Opcode::from_u6(header[0] & 0x3F) {
+            Some(Opcode::LoginResp) => {
+                self.exp_stat_sn
+                    .store(stat_sn.wrapping_add(1), Ordering::SeqCst);
+                // Successful final response moving to Full Feature Phase.
+                let flags = LoginFlags::from_bits_truncate(header[1]);
+                if flags.contains(LoginFlags::TRANSIT)
+                    && flags.bits() & LoginFlags::NSG_MASK.bits() == 3
+                    && header[36] == 0
+                {
+                    self.set_health(ConnectionHealth::LoggedIn);
+                }
+                return;
+            },
+            Some(
+                Opcode::ScsiCommandResp
+                | Opcode::ScsiTaskMgmtResp
+                | Opcode::TextResp
+                | Opcode::LogoutResp
+                | Opcode::AsyncMsg
+                | Opcode::Reject,
+            ) => stat_sn.wrapping_add(1),
+            Some(Opcode::ScsiDataIn) if header[1] & DataInFlags::S.bits() != 0 => {
+                stat_sn.wrapping_add(1)
+            },
+            Some(Opcode::NopIn) if itt != u32::MAX => stat_sn.wrapping_add(1),
+            Some(Opcode::NopIn | Opcode::ReadyToTransfer) => stat_sn,
+            _ => return,
+        };
+        let _ = self.exp_stat_sn.fetch_update(
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            |current| ((next.wrapping_sub(current) as i32) > 0).then_some(next),
+        );
+    }
+
+    /// Track the ExpCmdSN / MaxCmdSN window every target PDU carries in
+    /// bytes 28..36. Login responses seed it; afterwards values only advance
+    /// in serial-number order, so a reordered older PDU cannot shrink it.
+    fn observe_cmd_window(&self, header: &[u8; HEADER_LEN]) {
+        let exp = u32::from_be_bytes([header[28], header[29], header[30], header[31]]);
+        let max = u32::from_be_bytes([header[32], header[33], header[34], header[35]]);
+        match Opcode::from_u6(header[0] & 0x3F) {
+            Some(Opcode::LoginResp) => {
+                self.exp_cmd_sn.store(exp, Ordering::SeqCst);
+                self.max_cmd_sn.store(max, Ordering::SeqCst);
+            },
+            Some(
+                Opcode::NopIn
+                | Opcode::ScsiCommandResp
+                | Opcode::ScsiTaskMgmtResp
+                | Opcode::TextResp
+                | Opcode::ScsiDataIn
+                | Opcode::LogoutResp
+                | Opcode::ReadyToTransfer
+                | Opcode::AsyncMsg
+                | Opcode::Reject,
+            ) => {
+                let advance = |next: u32| {
+                    move |current: u32| {
+                        ((next.wrapping_sub(current) as i32) > 0).then_some(next)
+                    }
+                };
+                let _ = self.exp_cmd_sn.fetch_update(
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                    advance(exp),
+                );
+                let _ = self.max_cmd_sn.fetch_update(
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                    advance(max),
+                );
+            },
+            _ => {},
+        }
+    }
+
+    /// Log an Asynchronous Message (RFC 7143 § 11.9) and publish a target
+    /// logout request on the pool's event bus. The message carries no task,
+    /// so the read loop keeps going whatever the event is.
+    fn handle_async_message(&self, mut header: [u8; HEADER_LEN]) {
+        let msg = match AsyncMessage::from_bhs_bytes(&mut header) {
+            Ok(msg) => msg,
+            Err(error) => {
+                warn!("invalid Async Message header: {error}");
+                return;
+            },
+        };
+        let event = msg.event();
+        let (p1, p2, p3) = (
+            msg.parameter1.get(),
+            msg.parameter2.get(),
+            msg.parameter3.get(),
+        );
+        if matches!(
+            event,
+            AsyncEvent::LogoutRequested
+                | AsyncEvent::ConnectionDropped
+                | AsyncEvent::SessionDropped
+        ) {
+            self.set_health(ConnectionHealth::Degraded);
+        }
+        match event {
+            AsyncEvent::ScsiEvent
+            | AsyncEvent::VendorSpecific
+            | AsyncEvent::Reserved(_) => {
+                debug!("Async Message {event:?} vcode={}", msg.async_vcode);
+            },
+            _ => warn!(
+                "Async Message {event:?} parameter1={p1} parameter2={p2} parameter3={p3}"
+            ),
+        }
+
+        if event == AsyncEvent::LogoutRequested
+            && let Some(sr) = self.session_ref.get()
+            && let Some(pool) = sr.pool.upgrade()
+        {
+            pool.emit_event(PoolEvent::TargetAsyncLogoutRequested {
+                tsih: sr.tsih,
+                cid: sr.cid,
+                logout_within: Duration::from_secs(u64::from(p3)),
+            });
         }
     }
 
@@ -128,7 +319,12 @@ impl ClientConnection {
         )
         .await?;
 
-        let header = Pdu::from_bhs_bytes(&mut scratch[..HEADER_LEN])?;
+        let header = match Pdu::from_bhs_bytes(&mut scratch[..HEADER_LEN]) {
+            Ok(header) => header,
+            Err(error) => {
+                return Err(error.into_anyhow().context(PduSnapshot::new(scratch)));
+            },
+        };
         debug!("RECV BHS: {header:?}");
 
         let itt = header.get_initiator_task_tag();
@@ -206,7 +402,7 @@ impl ClientConnection {
             return false;
         };
 
-        tokio::spawn(async move {
+        self.spawner.spawn(Box::pin(async move {
             let result = pool
                 .execute_nop_reply(session_ref.tsih, session_ref.cid, pdu)
                 .await;
@@ -218,7 +414,7 @@ impl ClientConnection {
                     session_ref.tsih, session_ref.cid
                 );
             }
-        });
+        }));
 
         true
     }
@@ -1,17 +1,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::{fmt, fmt::Debug};
+use std::{fmt, fmt::Debug, sync::atomic::Ordering};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use bytes::Bytes;
 use tokio::io::AsyncWriteExt;
 use tracing::debug;
 
-use super::ClientConnection;
+use super::{ClientConnection, RequestNotSentError};
 use crate::{
     client::pdu_connection::ToBytes,
-    models::{common::HEADER_LEN, identifiers::Itt},
+    models::{common::HEADER_LEN, identifiers::Itt, opcode::Opcode},
 };
 
 impl ClientConnection {
@@ -32,11 +32,37 @@ impl ClientConnection {
     ) -> Result<()> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
-        self.ensure_writable()?;
-
-        let mut writer = self.writer.lock().await;
         let (header, data) = request
             .to_bytes(self.cfg.login.flow.max_recv_data_segment_length as usize)?;
+        let opcode = Opcode::from_u6(header[0] & 0x3F);
+        // A SCSI command refused here never reached the target.
+        let refuse = |error: anyhow::Error| -> anyhow::Error {
+            if opcode == Some(Opcode::ScsiCommandReq) {
+                RequestNotSentError(error).into()
+            } else {
+                error
+            }
+        };
+        self.ensure_writable().map_err(refuse)?;
+        if self.is_draining()
+            && matches!(
+                opcode,
+                Some(Opcode::ScsiCommandReq | Opcode::ScsiTaskMgmtReq | Opcode::TextReq)
+            )
+        {
+            return Err(refuse(anyhow!("connection is draining")));
+        }
+        if let Some(limit) = &self.data_out_limit
+            && !data.is_empty()
+            && matches!(opcode, Some(Opcode::ScsiDataOut | Opcode::ScsiCommandReq))
+        {
+            tokio::select! {
+                _ = limit.acquire(data.len()) => {},
+                _ = self.cancel.cancelled() => return Err(refuse(anyhow!("cancelled"))),
+            }
+        }
+
+        let mut writer = self.writer.lock().await;
         debug!("SEND {request:?}");
         debug!("Size_header: {} Size_data: {}", header.len(), data.len());
 
@@ -48,6 +74,12 @@ impl ClientConnection {
                 .await?;
         }
 
+        // Every initiator PDU carries ExpStatSN in bytes 28..32.
+        let mut exp_stat_sn = [0u8; 4];
+        exp_stat_sn.copy_from_slice(&header[28..32]);
+        self.acked_stat_sn
+            .store(u32::from_be_bytes(exp_stat_sn), Ordering::SeqCst);
+
         Ok(())
     }
 
@@ -58,8 +90,6 @@ impl ClientConnection {
     ) -> Result<()> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
-        self.ensure_writable()?;
-
         let expects_response = itt.get() != u32::MAX;
         if expects_response {
             self.pending.register(itt);
@@ -1,7 +1,14 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::{fs, time::Duration};
+use std::{
+    fs,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use hex::FromHex;
@@ -14,15 +21,29 @@ use tokio_util::sync::CancellationToken;
 
 use crate::{
     cfg::{config::Config, enums::Digest},
-    client::client::ClientConnection,
+    client::{
+        client::{ClientConnection, DisconnectError, RequestNotSentError, ShutdownError},
+        events::PoolEvent,
+        pool_sessions::Pool,
+        spawn::CountingSpawner,
+        status::ConnectionHealth,
+    },
+    control_block::verify::{ByteCheck, build_verify10},
+    error::IscsiError,
     models::{
+        command::request::{ScsiCommandRequest, ScsiCommandRequestBuilder},
         common::HEADER_LEN,
         data_fromat::PduRequest,
+        identifiers::{Cid, Itt, IttGen, Lun, Tsih},
         nop::{
             request::{NopOutRequest, NopOutRequestBuilder},
             response::NopInResponse,
         },
     },
+    state_machine::{
+        common::{ScsiStatusError, StateMachineCtx},
+        write_states::WriteCtx,
+    },
 };
 
 fn load_fixture(path: &str) -> Result<Vec<u8>> {
@@ -97,6 +118,36 @@ async fn truncated_bhs_poisons_connection() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn read_loop_runs_on_custom_spawner() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.expect("accept");
+        drop(stream);
+    });
+
+    let cfg = test_config(
+        address.to_string(),
+        Duration::from_millis(100),
+        Digest::None,
+    )?;
+    let spawner = Arc::new(CountingSpawner::default());
+    let conn = ClientConnection::connect_with_spawner(
+        cfg,
+        CancellationToken::new(),
+        spawner.clone(),
+    )
+    .await?;
+
+    // The supervised read loop is the only task a bare connection starts;
+    // it notices the EOF and poisons the connection.
+    wait_until_poisoned(&conn).await?;
+    assert_eq!(spawner.count(), 1);
+    server.await?;
+    Ok(())
+}
+
 #[tokio::test]
 async fn truncated_payload_poisons_connection() -> Result<()> {
     let listener = TcpListener::bind("127.0.0.1:0").await?;
@@ -122,6 +173,503 @@ async fn truncated_payload_poisons_connection() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn connect_falls_back_to_next_portal() -> Result<()> {
+    let dead = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let _ = listener.accept().await.expect("accept");
+    });
+
+    let mut cfg = test_config(dead.to_string(), Duration::from_secs(1), Digest::None)?;
+    cfg.login.transport.portals = vec![address.to_string()];
+    let conn = ClientConnection::connect(cfg, CancellationToken::new()).await?;
+
+    assert!(!conn.is_poisoned());
+    server.await?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn connects_over_unix_socket() -> Result<()> {
+    let path =
+        std::env::temp_dir().join(format!("iscsi-uds-{}.sock", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        // Task-less NOP-In announcing StatSN 7.
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = 0x20;
+        header[1] = 0x80;
+        header[16..20].copy_from_slice(&u32::MAX.to_be_bytes());
+        header[20..24].copy_from_slice(&u32::MAX.to_be_bytes());
+        header[24..28].copy_from_slice(&7u32.to_be_bytes());
+        stream.write_all(&header).await.expect("NOP-In");
+        sleep(Duration::from_millis(500)).await;
+    });
+
+    let cfg = test_config(
+        format!("unix://{}", path.display()),
+        Duration::from_secs(1),
+        Digest::None,
+    )?;
+    let conn = ClientConnection::connect(cfg, CancellationToken::new()).await?;
+
+    sleep(Duration::from_millis(200)).await;
+    assert!(!conn.is_poisoned());
+    assert_eq!(conn.exp_stat_sn.load(Ordering::SeqCst), 7);
+    server.abort();
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+#[tokio::test]
+async fn io_uring_backend_runs_read_loop() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = 0x20;
+        header[1] = 0x80;
+        header[16..20].copy_from_slice(&u32::MAX.to_be_bytes());
+        header[20..24].copy_from_slice(&u32::MAX.to_be_bytes());
+        header[24..28].copy_from_slice(&9u32.to_be_bytes());
+        stream.write_all(&header).await.expect("NOP-In");
+        sleep(Duration::from_millis(500)).await;
+    });
+
+    let mut cfg = test_config(address.to_string(), Duration::from_secs(1), Digest::None)?;
+    cfg.runtime.io_backend = crate::cfg::enums::IoBackend::IoUring;
+    let conn = ClientConnection::connect(cfg, CancellationToken::new()).await?;
+
+    sleep(Duration::from_millis(200)).await;
+    assert!(!conn.is_poisoned());
+    assert_eq!(conn.exp_stat_sn.load(Ordering::SeqCst), 9);
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_loop_tracks_exp_stat_sn() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let bhs = |opcode: u8, itt: u32, stat_sn: u32| {
+            let mut header = [0u8; HEADER_LEN];
+            header[0] = opcode;
+            header[1] = 0x80;
+            header[16..20].copy_from_slice(&itt.to_be_bytes());
+            header[20..24].copy_from_slice(&u32::MAX.to_be_bytes());
+            header[24..28].copy_from_slice(&stat_sn.to_be_bytes());
+            // ExpCmdSN / MaxCmdSN follow StatSN so the window is checked too.
+            header[28..32].copy_from_slice(&stat_sn.to_be_bytes());
+            header[32..36].copy_from_slice(&(stat_sn + 64).to_be_bytes());
+            header
+        };
+        // Task-less NOP-In only announces the next StatSN.
+        stream
+            .write_all(&bhs(0x20, u32::MAX, 41))
+            .await
+            .expect("NOP-In");
+        // Logout Response consumes StatSN 41.
+        stream.write_all(&bhs(0x26, 5, 41)).await.expect("Logout");
+        // A stale StatSN never moves ExpStatSN backwards.
+        stream.write_all(&bhs(0x26, 6, 10)).await.expect("Logout");
+        sleep(Duration::from_millis(500)).await;
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(1), Digest::None)?;
+    let conn = ClientConnection::connect(cfg, CancellationToken::new()).await?;
+
+    sleep(Duration::from_millis(200)).await;
+    assert!(!conn.is_poisoned());
+    assert_eq!(conn.exp_stat_sn.load(Ordering::SeqCst), 42);
+    assert_eq!(conn.cmd_window(), (41, 105));
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn async_logout_request_is_published_on_event_bus() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = 0x32;
+        header[1] = 0x80;
+        header[16..20].copy_from_slice(&u32::MAX.to_be_bytes());
+        header[24..28].copy_from_slice(&7u32.to_be_bytes());
+        header[36] = 1; // AsyncEvent: logout requested
+        header[42..44].copy_from_slice(&20u16.to_be_bytes());
+        // Give the test time to bind the connection to the pool.
+        sleep(Duration::from_millis(100)).await;
+        stream.write_all(&header).await.expect("Async Message");
+        sleep(Duration::from_millis(500)).await;
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(1), Digest::None)?;
+    let pool = Pool::new(&cfg);
+    let mut events = pool.subscribe_events();
+    let conn = ClientConnection::connect(cfg, CancellationToken::new()).await?;
+    conn.bind_pool_session(Arc::downgrade(&pool), Tsih::new(3), Cid::new(1));
+
+    let event = timeout(Duration::from_secs(2), events.recv()).await??;
+    assert_eq!(
+        event,
+        PoolEvent::TargetAsyncLogoutRequested {
+            tsih: Tsih::new(3),
+            cid: Cid::new(1),
+            logout_within: Duration::from_secs(20),
+        }
+    );
+    assert!(!conn.is_poisoned());
+    assert_eq!(conn.exp_stat_sn.load(Ordering::SeqCst), 8);
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn health_follows_login_target_drop_and_transport_failure() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let bhs = |opcode: u8, flags: u8| {
+            let mut header = [0u8; HEADER_LEN];
+            header[0] = opcode;
+            header[1] = flags;
+            header[16..20].copy_from_slice(&u32::MAX.to_be_bytes());
+            header
+        };
+        // Final Login Response: T=1, NSG=FullFeature, status Success.
+        stream.write_all(&bhs(0x23, 0x87)).await.expect("Login");
+        sleep(Duration::from_millis(100)).await;
+        let mut drop_announced = bhs(0x32, 0x80);
+        drop_announced[36] = 2; // AsyncEvent: connection will be dropped
+        stream
+            .write_all(&drop_announced)
+            .await
+            .expect("Async Message");
+        sleep(Duration::from_millis(100)).await;
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(1), Digest::None)?;
+    let pool = Pool::new(&cfg);
+    let mut events = pool.subscribe_events();
+    let conn = ClientConnection::connect(cfg, CancellationToken::new()).await?;
+    timeout(Duration::from_secs(2), async {
+        while conn.health() != ConnectionHealth::LoggedIn {
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await?;
+    conn.bind_pool_session(Arc::downgrade(&pool), Tsih::new(3), Cid::new(0));
+
+    let mut seen = Vec::new();
+    while seen.len() < 2 {
+        if let PoolEvent::HealthChanged { from, to, .. } =
+            timeout(Duration::from_secs(2), events.recv()).await??
+        {
+            seen.push((from, to));
+        }
+    }
+    assert_eq!(
+        seen,
+        [
+            (ConnectionHealth::LoggedIn, ConnectionHealth::Degraded),
+            (ConnectionHealth::Degraded, ConnectionHealth::Failed),
+        ]
+    );
+    assert_eq!(conn.health(), ConnectionHealth::Failed);
+    server.await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn data_in_throttle_paces_read_loop() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let pdu = |opcode: u8, stat_sn: u32, payload: usize| {
+            let mut pdu = vec![0u8; HEADER_LEN + payload];
+            pdu[0] = opcode;
+            pdu[1] = 0x80;
+            pdu[5..8].copy_from_slice(&(payload as u32).to_be_bytes()[1..]);
+            pdu[16..20].copy_from_slice(&u32::MAX.to_be_bytes());
+            pdu[20..24].copy_from_slice(&u32::MAX.to_be_bytes());
+            pdu[24..28].copy_from_slice(&stat_sn.to_be_bytes());
+            pdu
+        };
+        for _ in 0..3 {
+            stream.write_all(&pdu(0x25, 0, 500)).await.expect("Data-In");
+        }
+        stream.write_all(&pdu(0x20, 5, 0)).await.expect("NOP-In");
+        sleep(Duration::from_secs(3)).await;
+    });
+
+    let mut cfg = test_config(address.to_string(), Duration::from_secs(5), Digest::None)?;
+    cfg.runtime.throttle.data_in_bytes_per_sec = 1_000;
+    cfg.runtime.throttle.burst_bytes = 500;
+    let started = tokio::time::Instant::now();
+    let conn = ClientConnection::connect(cfg, CancellationToken::new()).await?;
+
+    timeout(Duration::from_secs(3), async {
+        while conn.exp_stat_sn.load(Ordering::SeqCst) != 5 {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    // 1500 bytes at 1000 B/s with a 500-byte burst: about one second.
+    assert!(started.elapsed() >= Duration::from_millis(900));
+    assert!(!conn.is_poisoned());
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn drain_waits_for_in_flight_and_rejects_new_tasks() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let mut response = load_fixture("tests/unit_tests/fixtures/nop/nop_in_response.hex")?;
+    response.truncate(HEADER_LEN);
+    response[5..8].fill(0);
+
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let mut request = [0u8; HEADER_LEN];
+        stream.read_exact(&mut request).await.expect("NOP-Out");
+        sleep(Duration::from_millis(300)).await;
+        response[16..20].copy_from_slice(&request[16..20]);
+        stream.write_all(&response).await.expect("NOP-In");
+        // Only the second NOP-Out may follow; the SCSI command is refused.
+        stream.read_exact(&mut request).await.expect("NOP-Out");
+        request[0] & 0x3F
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(2), Digest::None)?;
+    let conn = ClientConnection::connect(cfg.clone(), CancellationToken::new()).await?;
+    let nop = |itt: u32| -> Result<PduRequest<NopOutRequest>> {
+        let header = NopOutRequestBuilder::new()
+            .initiator_task_tag(itt)
+            .target_task_tag(NopOutRequest::DEFAULT_TAG)
+            .immediate();
+        let mut header_buf = [0u8; HEADER_LEN];
+        header.header.to_bhs_bytes(&mut header_buf)?;
+        Ok(PduRequest::new_request(header_buf, &cfg))
+    };
+
+    conn.send_request(7.into(), nop(7)?).await?;
+    let started = tokio::time::Instant::now();
+    conn.drain(Duration::from_secs(2)).await?;
+    assert!(started.elapsed() >= Duration::from_millis(250));
+    assert!(conn.is_draining());
+    conn.read_response::<NopInResponse>(7.into()).await?;
+
+    let header = ScsiCommandRequestBuilder::new()
+        .initiator_task_tag(8u32)
+        .scsi_descriptor_block(&[0u8; 16]);
+    let mut header_buf = [0u8; HEADER_LEN];
+    header.header.to_bhs_bytes(&mut header_buf)?;
+    let command = PduRequest::<ScsiCommandRequest>::new_request(header_buf, &cfg);
+    let error = conn
+        .send_request(8.into(), command)
+        .await
+        .expect_err("draining connection must refuse new tasks");
+    assert!(error.to_string().contains("draining"));
+
+    conn.send_request(u32::MAX.into(), nop(u32::MAX)?).await?;
+    assert_eq!(server.await?, 0x00);
+    assert!(!conn.is_poisoned());
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_loop_exit_fails_pending_waiters_with_disconnect_error() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let mut request = [0u8; HEADER_LEN];
+        stream.read_exact(&mut request).await.expect("NOP-Out");
+        // Close without answering: the read loop hits EOF.
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(5), Digest::None)?;
+    let conn = ClientConnection::connect(cfg.clone(), CancellationToken::new()).await?;
+    let header = NopOutRequestBuilder::new()
+        .initiator_task_tag(7u32)
+        .target_task_tag(NopOutRequest::DEFAULT_TAG)
+        .immediate();
+    let mut header_buf = [0u8; HEADER_LEN];
+    header.header.to_bhs_bytes(&mut header_buf)?;
+    conn.send_request(
+        7.into(),
+        PduRequest::<NopOutRequest>::new_request(header_buf, &cfg),
+    )
+    .await?;
+
+    let error = timeout(
+        Duration::from_secs(2),
+        conn.read_response::<NopInResponse>(7.into()),
+    )
+    .await?
+    .expect_err("the target closed the connection");
+    let disconnect = error
+        .downcast_ref::<DisconnectError>()
+        .context("waiter must see a DisconnectError")?;
+    assert!(disconnect.reason.contains("read loop"), "{disconnect}");
+    assert_eq!(conn.health(), ConnectionHealth::Failed);
+    server.await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn undefined_opcode_reports_the_offending_bhs() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let mut request = [0u8; HEADER_LEN];
+        stream.read_exact(&mut request).await.expect("NOP-Out");
+        let mut bogus = [0u8; HEADER_LEN];
+        bogus[0] = 0x1f;
+        bogus[16..20].copy_from_slice(&request[16..20]);
+        stream.write_all(&bogus).await.expect("bogus BHS");
+        sleep(Duration::from_millis(100)).await;
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(5), Digest::None)?;
+    let conn = ClientConnection::connect(cfg.clone(), CancellationToken::new()).await?;
+    let header = NopOutRequestBuilder::new()
+        .initiator_task_tag(9u32)
+        .target_task_tag(NopOutRequest::DEFAULT_TAG)
+        .immediate();
+    let mut header_buf = [0u8; HEADER_LEN];
+    header.header.to_bhs_bytes(&mut header_buf)?;
+    conn.send_request(
+        9.into(),
+        PduRequest::<NopOutRequest>::new_request(header_buf, &cfg),
+    )
+    .await?;
+
+    let error = timeout(
+        Duration::from_secs(2),
+        conn.read_response::<NopInResponse>(9.into()),
+    )
+    .await?
+    .expect_err("an undefined opcode fails the connection");
+    let disconnect = error
+        .downcast_ref::<DisconnectError>()
+        .context("waiter must see a DisconnectError")?;
+    assert!(
+        disconnect.reason.contains("PDU opcode=0x1f ITT=0x00000009"),
+        "{disconnect}"
+    );
+    assert!(disconnect.reason.contains("BHS=1f00"), "{disconnect}");
+    server.await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn kill_now_fails_pending_waiters_with_shutdown_error() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let mut request = [0u8; HEADER_LEN];
+        stream.read_exact(&mut request).await.expect("NOP-Out");
+        // Never answer.
+        sleep(Duration::from_secs(5)).await;
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(10), Digest::None)?;
+    let conn = ClientConnection::connect(cfg.clone(), CancellationToken::new()).await?;
+    let header = NopOutRequestBuilder::new()
+        .initiator_task_tag(7u32)
+        .target_task_tag(NopOutRequest::DEFAULT_TAG)
+        .immediate();
+    let mut header_buf = [0u8; HEADER_LEN];
+    header.header.to_bhs_bytes(&mut header_buf)?;
+    conn.send_request(
+        7.into(),
+        PduRequest::<NopOutRequest>::new_request(header_buf, &cfg),
+    )
+    .await?;
+
+    let waiter = tokio::spawn({
+        let conn = conn.clone();
+        async move { conn.read_response::<NopInResponse>(7.into()).await }
+    });
+    sleep(Duration::from_millis(100)).await;
+    conn.kill_now();
+
+    let error = timeout(Duration::from_secs(1), waiter)
+        .await??
+        .expect_err("killed connection cannot answer");
+    let shutdown = error
+        .downcast_ref::<ShutdownError>()
+        .context("waiter must see a ShutdownError")?;
+    assert_eq!(shutdown.reason, "connection killed");
+    assert!(conn.is_shut_down());
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn refused_scsi_command_is_reported_as_not_sent() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (_stream, _) = listener.accept().await.expect("accept");
+        sleep(Duration::from_millis(500)).await;
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(5), Digest::None)?;
+    let conn = ClientConnection::connect(cfg.clone(), CancellationToken::new()).await?;
+    conn.poison("test");
+
+    let header = ScsiCommandRequestBuilder::new()
+        .initiator_task_tag(8u32)
+        .scsi_descriptor_block(&[0u8; 16]);
+    let mut header_buf = [0u8; HEADER_LEN];
+    header.header.to_bhs_bytes(&mut header_buf)?;
+    let command = PduRequest::<ScsiCommandRequest>::new_request(header_buf, &cfg);
+    let error = conn
+        .send_request(8.into(), command)
+        .await
+        .expect_err("poisoned connection must refuse the command");
+    let not_sent = error
+        .downcast_ref::<RequestNotSentError>()
+        .context("a refused SCSI command is never on the wire")?;
+    assert!(not_sent.0.downcast_ref::<DisconnectError>().is_some());
+
+    // Only SCSI commands are candidates for re-dispatch.
+    let header = NopOutRequestBuilder::new()
+        .initiator_task_tag(9u32)
+        .target_task_tag(NopOutRequest::DEFAULT_TAG)
+        .immediate();
+    let mut header_buf = [0u8; HEADER_LEN];
+    header.header.to_bhs_bytes(&mut header_buf)?;
+    let error = conn
+        .send_request(
+            9.into(),
+            PduRequest::<NopOutRequest>::new_request(header_buf, &cfg),
+        )
+        .await
+        .expect_err("poisoned connection must refuse the NOP-Out");
+    assert!(error.downcast_ref::<RequestNotSentError>().is_none());
+    server.abort();
+    Ok(())
+}
+
 #[tokio::test]
 async fn read_timeout_poisons_connection() -> Result<()> {
     let listener = TcpListener::bind("127.0.0.1:0").await?;
@@ -178,6 +726,11 @@ async fn invalid_header_digest_poisons_connection() -> Result<()> {
         .await
         .expect_err("invalid digest must fail");
     assert!(error.to_string().contains("HeaderDigest mismatch"));
+    let snapshot = IscsiError::from(error)
+        .pdu()
+        .cloned()
+        .context("digest errors carry the BHS")?;
+    assert_eq!(snapshot.itt(), 42);
     assert!(conn.is_poisoned());
     server.await?;
     Ok(())
@@ -234,3 +787,113 @@ async fn invalid_data_digest_poisons_connection() -> Result<()> {
     server.await?;
     Ok(())
 }
+
+#[tokio::test]
+async fn write_ended_early_with_miscompare_reports_sense() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let mut command = [0u8; HEADER_LEN];
+        stream.read_exact(&mut command).await.expect("SCSI Command");
+        // No R2T: the target checks the medium and fails the compare at once.
+        let mut sense = [0u8; 20];
+        sense[0..2].copy_from_slice(&18u16.to_be_bytes());
+        sense[2] = 0xF0; // VALID | current, fixed format
+        sense[4] = 0x0E; // MISCOMPARE
+        sense[5..9].copy_from_slice(&0x0200u32.to_be_bytes());
+        sense[9] = 10;
+        sense[14] = 0x1D; // MISCOMPARE DURING VERIFY OPERATION
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = 0x21;
+        header[1] = 0x80;
+        header[3] = 0x02; // CHECK CONDITION
+        header[7] = sense.len() as u8;
+        header[16..20].copy_from_slice(&command[16..20]);
+        header[24..28].copy_from_slice(&1u32.to_be_bytes());
+        header[28..32].copy_from_slice(&1u32.to_be_bytes());
+        header[32..36].copy_from_slice(&64u32.to_be_bytes());
+        stream.write_all(&header).await.expect("SCSI Response");
+        stream.write_all(&sense).await.expect("sense");
+        sleep(Duration::from_millis(500)).await;
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(1), Digest::None)?;
+    let conn = ClientConnection::connect(cfg, CancellationToken::new()).await?;
+    let mut cdb = [0u8; 16];
+    build_verify10(&mut cdb, 0, 1, ByteCheck::Compare, 0, 0);
+    let mut ctx = WriteCtx::new(
+        conn.clone(),
+        Lun::ZERO,
+        &IttGen::new(Itt::new(1)?),
+        Arc::new(AtomicU32::new(0)),
+        Arc::new(AtomicU32::new(0)),
+        cdb,
+        vec![0xA5; 512],
+    );
+    let error = ctx
+        .execute(&CancellationToken::new())
+        .await
+        .expect_err("MISCOMPARE must fail the task");
+    let status = error
+        .downcast_ref::<ScsiStatusError>()
+        .context("early SCSI Response is reported as a status error")?;
+    assert!(status.is_miscompare());
+    assert_eq!(status.miscompare_offset(), Some(0x0200));
+    assert_eq!(ctx.sent_bytes, 0);
+    assert!(!conn.is_poisoned());
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn ping_times_the_nop_exchange_and_refuses_oversized_payload() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("accept");
+        let mut nop_out = [0u8; HEADER_LEN];
+        stream.read_exact(&mut nop_out).await.expect("NOP-Out");
+        let len = u32::from_be_bytes([0, nop_out[5], nop_out[6], nop_out[7]]) as usize;
+        let mut payload = vec![0u8; len.next_multiple_of(4)];
+        stream.read_exact(&mut payload).await.expect("ping data");
+        sleep(Duration::from_millis(50)).await;
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = 0x20;
+        header[1] = 0x80;
+        header[5..8].copy_from_slice(&nop_out[5..8]);
+        header[16..20].copy_from_slice(&nop_out[16..20]);
+        header[20..24].copy_from_slice(&u32::MAX.to_be_bytes());
+        header[24..28].copy_from_slice(&1u32.to_be_bytes());
+        header[28..32].copy_from_slice(&1u32.to_be_bytes());
+        header[32..36].copy_from_slice(&64u32.to_be_bytes());
+        stream.write_all(&header).await.expect("NOP-In");
+        stream.write_all(&payload).await.expect("echo");
+        // A second NOP-Out would show up here.
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.expect("EOF");
+        rest
+    });
+
+    let cfg = test_config(address.to_string(), Duration::from_secs(1), Digest::None)?;
+    let pool = Pool::new(&cfg);
+    let conn = ClientConnection::connect(cfg.clone(), CancellationToken::new()).await?;
+    pool.insert_session(Tsih::new(1), "iqn.2025-08.example:disk0", conn);
+
+    let rtt = pool.ping(Tsih::new(1), Cid::ZERO, b"ping".to_vec()).await?;
+    assert!(rtt >= Duration::from_millis(50), "{rtt:?}");
+
+    let mrdsl = cfg.login.flow.max_recv_data_segment_length as usize;
+    let error = pool
+        .ping(Tsih::new(1), Cid::ZERO, vec![0u8; mrdsl + 1])
+        .await
+        .expect_err("oversized ping must be refused");
+    assert!(
+        error.to_string().contains("MaxRecvDataSegmentLength"),
+        "{error}"
+    );
+
+    pool.drop_session_local(Tsih::new(1), "test");
+    assert!(server.await?.is_empty());
+    Ok(())
+}
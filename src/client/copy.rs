@@ -0,0 +1,351 @@
+//! Server-side copy (EXTENDED COPY) between logical units of one target.
+//!
+//! [`LunHandle::extended_copy`] hands a parameter list to the copy manager
+//! of the LUN and [`LunHandle::copy_status`] monitors it by its list
+//! identifier. [`LunHandle::copy_operating_parameters`] tells which
+//! descriptors and sizes the copy manager accepts.
+//!
+//! [`copy_lba_range`] copies a block range with EXTENDED COPY when the
+//! capability probe says the source LUN can, and with READ / WRITE through
+//! the pool otherwise.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use tracing::debug;
+
+use crate::{
+    client::{
+        capabilities::TargetCapabilities,
+        handles::{LunHandle, check_range},
+    },
+    control_block::xcopy::{
+        BLOCK_SEGMENT_LEN, BlockSegment, COPY_STATUS_LEN, CSCD_DESCRIPTOR_LEN,
+        CopyOperatingParameters, CopyResultsAction, CopyStatus, ExtendedCopyParameters,
+        IdentificationCscd, OPERATING_PARAMETERS_LEN, XCOPY_HEADER_LEN,
+        build_extended_copy, build_receive_copy_results, parse_copy_operating_parameters,
+        parse_copy_status,
+    },
+    error::{self, IscsiError},
+};
+
+/// Allocation length of RECEIVE COPY RESULTS / OPERATING PARAMETERS; room
+/// for every descriptor type code.
+const OPERATING_PARAMETERS_ALLOC_LEN: u32 = OPERATING_PARAMETERS_LEN as u32 + 255;
+
+/// Bytes per READ / WRITE of the fallback copy unless Block Limits asks for
+/// less.
+const DEFAULT_COPY_CHUNK_BYTES: u32 = 1 << 20;
+
+/// LIST IDENTIFIER of the next EXTENDED COPY, so copies running at the same
+/// time on one I_T nexus do not collide.
+static NEXT_LIST_ID: AtomicU8 = AtomicU8::new(0);
+
+/// How [`copy_lba_range`] moved the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// The target copied the blocks itself.
+    ExtendedCopy,
+    /// The blocks were read and written back through the initiator.
+    ReadWrite,
+}
+
+/// Progress of a [`copy_lba_range`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyProgress {
+    /// Method the last blocks were copied with.
+    pub method: CopyMethod,
+    /// Blocks copied so far.
+    pub blocks_done: u64,
+    /// Blocks of the whole range.
+    pub blocks_total: u64,
+}
+
+impl LunHandle {
+    /// Issue EXTENDED COPY (LID1) with `params`. The command completes when
+    /// the copy manager finished every segment.
+    pub async fn extended_copy(
+        &self,
+        params: &ExtendedCopyParameters,
+    ) -> error::Result<()> {
+        let data = params.to_bytes()?;
+        let mut cdb = [0u8; 16];
+        build_extended_copy(&mut cdb, data.len() as u32, 0);
+        self.write_cdb(cdb, data, None).await.map_err(Into::into)
+    }
+
+    /// Progress of the copy started with `list_id` (RECEIVE COPY RESULTS /
+    /// COPY STATUS).
+    pub async fn copy_status(&self, list_id: u8) -> error::Result<CopyStatus> {
+        let mut cdb = [0u8; 16];
+        build_receive_copy_results(
+            &mut cdb,
+            CopyResultsAction::CopyStatus,
+            list_id,
+            COPY_STATUS_LEN as u32,
+            0,
+        );
+        let data = self.read_cdb(cdb, COPY_STATUS_LEN as u32, None).await?;
+        Ok(parse_copy_status(&data)?)
+    }
+
+    /// Limits of the LUN's copy manager (RECEIVE COPY RESULTS / OPERATING
+    /// PARAMETERS).
+    pub async fn copy_operating_parameters(
+        &self,
+    ) -> error::Result<CopyOperatingParameters> {
+        let mut cdb = [0u8; 16];
+        build_receive_copy_results(
+            &mut cdb,
+            CopyResultsAction::OperatingParameters,
+            0,
+            OPERATING_PARAMETERS_ALLOC_LEN,
+            0,
+        );
+        let data = self
+            .read_cdb(cdb, OPERATING_PARAMETERS_ALLOC_LEN, None)
+            .await?;
+        Ok(parse_copy_operating_parameters(&data)?)
+    }
+}
+
+/// Copy `blocks` logical blocks from `src_lba` of `src` to `dst_lba` of
+/// `dst`; both LUNs must have the same block size.
+///
+/// EXTENDED COPY is used when [`TargetCapabilities::supports_xcopy`] holds
+/// for `src` and both LUNs report an NAA or EUI-64 identity; the range is
+/// split by the copy manager's segment limits. Otherwise, or when the target
+/// rejects the copy, the rest of the range is read and written in chunks,
+/// reading the next chunk while the previous one is written. `on_progress`
+/// is called after each command. Returns the method that finished the copy.
+pub async fn copy_lba_range(
+    src: &LunHandle,
+    src_lba: u64,
+    dst: &LunHandle,
+    dst_lba: u64,
+    blocks: u64,
+    mut on_progress: impl FnMut(CopyProgress),
+) -> error::Result<CopyMethod> {
+    let src_cap = src.capacity().await?;
+    let dst_cap = dst.capacity().await?;
+    if src_cap.block_size != dst_cap.block_size {
+        return Err(IscsiError::msg(format!(
+            "block sizes differ: {} vs {}",
+            src_cap.block_size, dst_cap.block_size
+        )));
+    }
+    check_range(&src_cap, src_lba, blocks)?;
+    check_range(&dst_cap, dst_lba, blocks)?;
+    if blocks == 0 {
+        return Ok(CopyMethod::ReadWrite);
+    }
+
+    let caps = src.capabilities().await?;
+    let mut range = CopyRange {
+        src_lba,
+        dst_lba,
+        blocks,
+        done: 0,
+    };
+    if caps.supports_xcopy() {
+        match xcopy_range(
+            src,
+            dst,
+            &caps,
+            src_cap.block_size,
+            &mut range,
+            &mut on_progress,
+        )
+        .await
+        {
+            Ok(true) => return Ok(CopyMethod::ExtendedCopy),
+            Ok(false) => {},
+            Err(IscsiError::Scsi(e)) => {
+                debug!("EXTENDED COPY failed at block {}: {e}", range.done);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    let default_blocks = (DEFAULT_COPY_CHUNK_BYTES / src_cap.block_size).max(1);
+    let chunk = caps
+        .block_limits
+        .map_or(default_blocks, |l| l.transfer_blocks(default_blocks));
+    read_write_range(src, dst, chunk, &mut range, &mut on_progress).await?;
+    Ok(CopyMethod::ReadWrite)
+}
+
+/// Range of a [`copy_lba_range`] and how much of it is copied.
+struct CopyRange {
+    src_lba: u64,
+    dst_lba: u64,
+    blocks: u64,
+    done: u64,
+}
+
+impl CopyRange {
+    fn progress(&self, method: CopyMethod) -> CopyProgress {
+        CopyProgress {
+            method,
+            blocks_done: self.done,
+            blocks_total: self.blocks,
+        }
+    }
+}
+
+/// Copy `range` with EXTENDED COPY. `Ok(false)` when a LUN has no binary
+/// designator to name it by.
+async fn xcopy_range(
+    src: &LunHandle,
+    dst: &LunHandle,
+    caps: &TargetCapabilities,
+    block_size: u32,
+    range: &mut CopyRange,
+    on_progress: &mut impl FnMut(CopyProgress),
+) -> error::Result<bool> {
+    let (Some(src_id), Some(dst_id)) = (src.identity().await?, dst.identity().await?)
+    else {
+        return Ok(false);
+    };
+    let mut targets = vec![IdentificationCscd::from_identity(&src_id, block_size)?];
+    if dst_id != src_id {
+        targets.push(IdentificationCscd::from_identity(&dst_id, block_size)?);
+    }
+    let dst_index = targets.len() as u16 - 1;
+    let (segment_blocks, segments_per_copy) = match &caps.copy_manager {
+        Some(cm) => xcopy_limits(cm, block_size, targets.len()),
+        None => return Ok(false),
+    };
+
+    while range.done < range.blocks {
+        let mut segments = Vec::with_capacity(segments_per_copy);
+        let mut next = range.done;
+        while next < range.blocks && segments.len() < segments_per_copy {
+            let n = (range.blocks - next).min(segment_blocks as u64) as u16;
+            segments.push(BlockSegment {
+                src: 0,
+                dst: dst_index,
+                blocks: n,
+                src_lba: range.src_lba + next,
+                dst_lba: range.dst_lba + next,
+            });
+            next += n as u64;
+        }
+        let params = ExtendedCopyParameters {
+            list_id: NEXT_LIST_ID.fetch_add(1, Ordering::Relaxed),
+            targets: targets.clone(),
+            segments,
+            ..Default::default()
+        };
+        src.extended_copy(&params).await?;
+        range.done = next;
+        on_progress(range.progress(CopyMethod::ExtendedCopy));
+    }
+    Ok(true)
+}
+
+/// Blocks per segment and segments per EXTENDED COPY the copy manager
+/// accepts, at least 1 each. A limit of 0 means none was reported.
+fn xcopy_limits(
+    cm: &CopyOperatingParameters,
+    block_size: u32,
+    cscds: usize,
+) -> (u16, usize) {
+    let mut segment_blocks = u16::MAX as u32;
+    if cm.max_segment_len > 0 {
+        segment_blocks = segment_blocks.min(cm.max_segment_len / block_size);
+    }
+    let mut segments = match cm.max_segment_descriptors {
+        0 => usize::from(u16::MAX),
+        n => n as usize,
+    };
+    if cm.max_descriptor_list_len > 0 {
+        let room = (cm.max_descriptor_list_len as usize)
+            .saturating_sub(cscds * CSCD_DESCRIPTOR_LEN)
+            / BLOCK_SEGMENT_LEN;
+        segments = segments.min(room);
+    }
+    // Keep the parameter list well inside a single command.
+    segments = segments.min((u16::MAX as usize - XCOPY_HEADER_LEN) / BLOCK_SEGMENT_LEN);
+    (segment_blocks.max(1) as u16, segments.max(1))
+}
+
+/// Copy the rest of `range` with READ / WRITE of `chunk` blocks, the next
+/// READ running while the previous chunk is written.
+async fn read_write_range(
+    src: &LunHandle,
+    dst: &LunHandle,
+    chunk: u32,
+    range: &mut CopyRange,
+    on_progress: &mut impl FnMut(CopyProgress),
+) -> error::Result<()> {
+    let mut next = range.done;
+    let mut pending: Option<(u64, u32, Vec<u8>)> = None;
+    while next < range.blocks || pending.is_some() {
+        let n = (range.blocks - next).min(chunk as u64) as u32;
+        let read_lba = range.src_lba + next;
+        let read = async {
+            if n == 0 {
+                return Ok(None);
+            }
+            src.read_at(read_lba, n).await.map(Some)
+        };
+        let job = pending.take();
+        let dst_lba = range.dst_lba;
+        let write = async {
+            let Some((offset, blocks, data)) = job else {
+                return Ok(None);
+            };
+            dst.write_at(dst_lba + offset, data).await?;
+            Ok::<_, IscsiError>(Some(blocks))
+        };
+        let (read, written) = tokio::join!(read, write);
+        if let Some(blocks) = written? {
+            range.done += blocks as u64;
+            on_progress(range.progress(CopyMethod::ReadWrite));
+        }
+        if let Some(data) = read? {
+            pending = Some((next, n, data));
+            next += n as u64;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(
+        max_segments: u16,
+        max_list: u32,
+        max_segment_len: u32,
+    ) -> CopyOperatingParameters {
+        CopyOperatingParameters {
+            snlid: false,
+            max_cscd_descriptors: 2,
+            max_segment_descriptors: max_segments,
+            max_descriptor_list_len: max_list,
+            max_segment_len,
+            max_inline_data_len: 0,
+            total_concurrent_copies: 1,
+            max_concurrent_copies: 1,
+            data_segment_granularity: 0,
+            descriptor_types: vec![0x02, 0xE4],
+        }
+    }
+
+    #[test]
+    fn splits_by_copy_manager_limits() {
+        // 4 MiB segments of 512-byte blocks, 1 segment per command (LIO).
+        assert_eq!(xcopy_limits(&manager(1, 1024, 4 << 20), 512, 2), (8192, 1));
+        // Segment length above what the 16-bit block count holds.
+        assert_eq!(xcopy_limits(&manager(8, 0, 0), 512, 2), (u16::MAX, 8));
+        // Descriptor list room: (200 - 64) / 28 = 4 segments.
+        assert_eq!(xcopy_limits(&manager(16, 200, 1 << 20), 4096, 2), (256, 4));
+        // Limits too small for anything still make progress.
+        assert_eq!(xcopy_limits(&manager(0, 10, 100), 512, 2), (1, 1));
+    }
+}
@@ -0,0 +1,314 @@
+//! dd-like copies between a LUN and a local file or raw device.
+//!
+//! [`copy_lun_to_file`] and [`copy_file_to_lun`] move a byte range in
+//! chunks of [`DdOptions::chunk_bytes`]. Several READs or WRITEs are kept in
+//! flight ([`DdOptions::parallelism`]) so the pool spreads them over the
+//! connections of the session (MC/S). Every [`DdProgress`] carries a
+//! resume offset below which everything is copied; an interrupted copy
+//! continues from it with [`DdOptions::resume_from`].
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{collections::BTreeMap, io::SeekFrom, path::Path};
+
+use anyhow::Context;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    task::JoinSet,
+};
+
+use crate::{
+    client::handles::{LunHandle, check_range},
+    error,
+};
+
+/// Bytes per READ / WRITE unless [`DdOptions::chunk_bytes`] says otherwise.
+pub const DEFAULT_DD_CHUNK_BYTES: u32 = 1 << 20;
+
+/// Commands in flight per connection of the session when
+/// [`DdOptions::parallelism`] is not set.
+const JOBS_PER_CONNECTION: usize = 2;
+
+/// Range and tuning of a [`copy_lun_to_file`] / [`copy_file_to_lun`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DdOptions {
+    /// Bytes per READ / WRITE, rounded down to whole LUN blocks (at least
+    /// one block).
+    pub chunk_bytes: u32,
+    /// Commands in flight; `None` for two per connection of the session.
+    pub parallelism: Option<usize>,
+    /// First LBA of the range on the LUN.
+    pub lba: u64,
+    /// Byte offset of the range in the file or device.
+    pub file_offset: u64,
+    /// Bytes to copy; `None` to copy up to the end of the source.
+    pub len: Option<u64>,
+    /// Bytes of the range an earlier run already copied, as reported by
+    /// [`DdProgress::resume_offset`]. Must be a whole number of blocks.
+    pub resume_from: u64,
+}
+
+impl Default for DdOptions {
+    fn default() -> Self {
+        Self {
+            chunk_bytes: DEFAULT_DD_CHUNK_BYTES,
+            parallelism: None,
+            lba: 0,
+            file_offset: 0,
+            len: None,
+            resume_from: 0,
+        }
+    }
+}
+
+/// Progress of a copy, reported after every chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DdProgress {
+    /// Bytes copied so far, including [`DdOptions::resume_from`].
+    pub bytes_done: u64,
+    /// Bytes of the whole range.
+    pub bytes_total: u64,
+    /// Every byte below this offset of the range is copied; chunks finish
+    /// out of order, so it can trail `bytes_done`.
+    pub resume_offset: u64,
+}
+
+/// Copy from `lun` to the file or raw device at `path`, which is created
+/// if missing and not truncated. Returns the bytes copied by this call.
+///
+/// Without [`DdOptions::len`] the copy runs to the end of the LUN; a given
+/// length must be a whole number of blocks.
+pub async fn copy_lun_to_file(
+    lun: &LunHandle,
+    path: impl AsRef<Path>,
+    opts: &DdOptions,
+    mut on_progress: impl FnMut(DdProgress),
+) -> error::Result<u64> {
+    let path = path.as_ref();
+    let cap = lun.capacity().await?;
+    let bs = cap.block_size as u64;
+    let total = match opts.len {
+        Some(len) => len,
+        None => cap.blocks.saturating_sub(opts.lba) * bs,
+    };
+    if !total.is_multiple_of(bs) {
+        return Err(error::IscsiError::msg(format!(
+            "{total} bytes are not whole {bs}-byte blocks"
+        )));
+    }
+    check_range(&cap, opts.lba, total / bs)?;
+    let start = resume_point(opts, total, bs)?;
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .await
+        .with_context(|| format!("cannot open {}", path.display()))?;
+    let chunk = chunk_len(opts, bs);
+    let jobs = parallelism(lun, opts);
+    let mut tasks = JoinSet::new();
+    let mut mark = Watermark::new(start);
+    let (mut next, mut done) = (start, start);
+    while next < total || !tasks.is_empty() {
+        while next < total && tasks.len() < jobs {
+            let n = (total - next).min(chunk);
+            let (lun, lba, off) = (lun.clone(), opts.lba + next / bs, next);
+            tasks.spawn(async move {
+                let data = lun.read_at(lba, (n / bs) as u32).await?;
+                Ok::<_, error::IscsiError>((off, data))
+            });
+            next += n;
+        }
+        let Some(joined) = tasks.join_next().await else {
+            break;
+        };
+        let (off, data) = joined.context("read task failed")??;
+        file.seek(SeekFrom::Start(opts.file_offset + off))
+            .await
+            .with_context(|| format!("cannot seek in {}", path.display()))?;
+        file.write_all(&data)
+            .await
+            .with_context(|| format!("write to {} failed", path.display()))?;
+        done += data.len() as u64;
+        on_progress(DdProgress {
+            bytes_done: done,
+            bytes_total: total,
+            resume_offset: mark.complete(off, data.len() as u64),
+        });
+    }
+    file.sync_data()
+        .await
+        .with_context(|| format!("sync of {} failed", path.display()))?;
+    Ok(done - start)
+}
+
+/// Copy from the file or raw device at `path` to `lun`. Returns the bytes
+/// copied by this call.
+///
+/// Without [`DdOptions::len`] the copy runs to the end of the file. A
+/// final partial block is padded with zeros on the LUN. The written blocks
+/// are flushed from the target's cache (SYNCHRONIZE CACHE) before it
+/// returns.
+pub async fn copy_file_to_lun(
+    path: impl AsRef<Path>,
+    lun: &LunHandle,
+    opts: &DdOptions,
+    mut on_progress: impl FnMut(DdProgress),
+) -> error::Result<u64> {
+    let path = path.as_ref();
+    let mut file = File::open(path)
+        .await
+        .with_context(|| format!("cannot open {}", path.display()))?;
+    // Seeking to the end also sizes block devices, whose metadata says 0.
+    let file_len = file
+        .seek(SeekFrom::End(0))
+        .await
+        .with_context(|| format!("cannot size {}", path.display()))?;
+    let available = file_len.saturating_sub(opts.file_offset);
+    let total = opts.len.unwrap_or(available);
+    if total > available {
+        return Err(error::IscsiError::msg(format!(
+            "{} has {available} bytes after offset {}, {total} requested",
+            path.display(),
+            opts.file_offset
+        )));
+    }
+    let cap = lun.capacity().await?;
+    let bs = cap.block_size as u64;
+    check_range(&cap, opts.lba, total.div_ceil(bs))?;
+    let start = resume_point(opts, total, bs)?;
+
+    let chunk = chunk_len(opts, bs);
+    let jobs = parallelism(lun, opts);
+    let mut tasks = JoinSet::new();
+    let mut mark = Watermark::new(start);
+    let (mut next, mut done) = (start, start);
+    file.seek(SeekFrom::Start(opts.file_offset + start))
+        .await
+        .with_context(|| format!("cannot seek in {}", path.display()))?;
+    while next < total || !tasks.is_empty() {
+        while next < total && tasks.len() < jobs {
+            let n = (total - next).min(chunk);
+            let mut data = vec![0u8; n.div_ceil(bs) as usize * bs as usize];
+            file.read_exact(&mut data[..n as usize])
+                .await
+                .with_context(|| format!("read from {} failed", path.display()))?;
+            let (lun, lba, off) = (lun.clone(), opts.lba + next / bs, next);
+            tasks.spawn(async move {
+                lun.write_at(lba, data).await?;
+                Ok::<_, error::IscsiError>((off, n))
+            });
+            next += n;
+        }
+        let Some(joined) = tasks.join_next().await else {
+            break;
+        };
+        let (off, n) = joined.context("write task failed")??;
+        done += n;
+        on_progress(DdProgress {
+            bytes_done: done,
+            bytes_total: total,
+            resume_offset: mark.complete(off, n),
+        });
+    }
+    // Like the sync of the file in `copy_lun_to_file`: no count is
+    // returned before the data is durable.
+    lun.flush(opts.lba + start / bs..opts.lba + total.div_ceil(bs))
+        .await?;
+    Ok(done - start)
+}
+
+/// Checked [`DdOptions::resume_from`].
+fn resume_point(opts: &DdOptions, total: u64, bs: u64) -> error::Result<u64> {
+    if !opts.resume_from.is_multiple_of(bs) {
+        return Err(error::IscsiError::msg(format!(
+            "resume offset {} is not a multiple of the {bs}-byte block size",
+            opts.resume_from
+        )));
+    }
+    Ok(opts.resume_from.min(total))
+}
+
+/// Bytes per command: [`DdOptions::chunk_bytes`] in whole blocks, at least
+/// one block.
+fn chunk_len(opts: &DdOptions, bs: u64) -> u64 {
+    (opts.chunk_bytes as u64 / bs).max(1) * bs
+}
+
+fn parallelism(lun: &LunHandle, opts: &DdOptions) -> usize {
+    opts.parallelism
+        .unwrap_or_else(|| default_parallelism(lun))
+        .max(1)
+}
+
+/// Commands to keep in flight on `lun` when the caller does not say: two
+/// per connection of its session.
+pub(crate) fn default_parallelism(lun: &LunHandle) -> usize {
+    let session = lun.session();
+    let conns = session
+        .pool()
+        .sessions
+        .get(&session.tsih())
+        .map_or(1, |s| s.conns.len());
+    JOBS_PER_CONNECTION * conns.max(1)
+}
+
+/// Contiguous prefix of a range whose chunks finish in any order.
+#[derive(Debug)]
+struct Watermark {
+    next: u64,
+    /// Finished chunks past `next`: offset -> length.
+    ahead: BTreeMap<u64, u64>,
+}
+
+impl Watermark {
+    fn new(start: u64) -> Self {
+        Self {
+            next: start,
+            ahead: BTreeMap::new(),
+        }
+    }
+
+    /// Record the chunk of `len` bytes at `off`; returns the new end of the
+    /// contiguous prefix.
+    fn complete(&mut self, off: u64, len: u64) -> u64 {
+        self.ahead.insert(off, len);
+        while let Some(len) = self.ahead.remove(&self.next) {
+            self.next += len;
+        }
+        self.next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watermark_follows_the_contiguous_prefix() {
+        let mut mark = Watermark::new(512);
+        assert_eq!(mark.complete(1536, 1024), 512);
+        assert_eq!(mark.complete(1024, 512), 512);
+        assert_eq!(mark.complete(512, 512), 2560);
+    }
+
+    #[test]
+    fn chunks_are_whole_blocks() {
+        let opts = DdOptions {
+            chunk_bytes: 10_000,
+            ..Default::default()
+        };
+        assert_eq!(chunk_len(&opts, 4096), 8192);
+        assert_eq!(chunk_len(&opts, 16384), 16384);
+        assert!(resume_point(&opts, 1 << 20, 512).is_ok());
+        let misaligned = DdOptions {
+            resume_from: 100,
+            ..opts
+        };
+        assert!(resume_point(&misaligned, 1 << 20, 512).is_err());
+    }
+}
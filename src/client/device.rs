@@ -0,0 +1,504 @@
+//! Byte-addressed access to one logical unit.
+//!
+//! [`IscsiDevice`] logs in, learns the block size and capacity and then
+//! reads, writes, discards and flushes byte ranges: offsets need not be block
+//! aligned and large transfers are split into several SCSI commands, sized
+//! by the LUN's Block Limits VPD page when the target reports one. It is
+//! built on a [`LunHandle`], which remains available for block-level calls.
+//!
+//! On a host-managed zoned LUN the device keeps a [`ZoneMap`] and refuses
+//! writes that would not land on the write pointer of a
+//! sequential-write-required zone, instead of letting the target fail them.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use tokio::sync::{Mutex, RwLock};
+use tracing::debug;
+
+use crate::{
+    cfg::config::Config,
+    client::{
+        handles::{Capacity, LunHandle},
+        pool_sessions::Pool,
+        zones::ZoneMap,
+    },
+    control_block::{
+        inquiry::BlockLimits,
+        zbc::{DEVICE_TYPE_HOST_MANAGED_ZONED, ZoneAction},
+    },
+    error,
+    models::identifiers::Lun,
+};
+
+/// Largest transfer issued as a single command unless changed with
+/// [`IscsiDevice::with_max_transfer_bytes`].
+const DEFAULT_MAX_TRANSFER_BYTES: u32 = 1 << 20;
+
+/// A logical unit seen as a flat array of bytes.
+#[derive(Debug, Clone)]
+pub struct IscsiDevice {
+    lun: LunHandle,
+    capacity: Capacity,
+    limits: Option<BlockLimits>,
+    max_transfer_blocks: u32,
+    zones: Option<Arc<Mutex<ZoneMap>>>,
+    /// Taken exclusively across the read-modify-write of partly covered
+    /// blocks and shared by aligned writes, so no write to a block can be
+    /// undone by a merge that read it earlier.
+    partial: Arc<RwLock<()>>,
+}
+
+impl IscsiDevice {
+    /// Log in the sessions described by `cfg` and open `lun` on the first
+    /// one.
+    pub async fn open(cfg: &Config, lun: Lun) -> error::Result<Self> {
+        let pool = Pool::new(cfg);
+        let tsih = *pool
+            .login_sessions_from_cfg(cfg)
+            .await?
+            .first()
+            .context("no session logged in")?;
+        Self::from_lun(pool.session(tsih)?.lun(lun)).await
+    }
+
+    /// Open a LUN of an already logged-in session.
+    pub async fn from_lun(lun: LunHandle) -> error::Result<Self> {
+        // The first command after login usually reports a Unit Attention.
+        let _ = lun.test_unit_ready().await;
+        let capacity = lun.capacity().await?;
+        if capacity.block_size == 0 {
+            return Err(error::IscsiError::msg(
+                "target reported a 0-byte block size",
+            ));
+        }
+        // Block Limits is optional (SBC-3 and later); without it only the
+        // default transfer size applies.
+        let limits = lun
+            .block_limits()
+            .await
+            .inspect_err(|e| debug!("{}: no Block Limits VPD page: {e}", lun.lun()))
+            .ok();
+        let zoned = lun
+            .inquiry()
+            .await
+            .is_ok_and(|inq| inq.device_type == DEVICE_TYPE_HOST_MANAGED_ZONED);
+        let zones = if zoned {
+            Some(Arc::new(Mutex::new(lun.zone_map().await?)))
+        } else {
+            None
+        };
+        let default_blocks = (DEFAULT_MAX_TRANSFER_BYTES / capacity.block_size).max(1);
+        Ok(Self {
+            lun,
+            capacity,
+            limits,
+            max_transfer_blocks: limits
+                .map_or(default_blocks, |l| l.transfer_blocks(default_blocks)),
+            zones,
+            partial: Arc::default(),
+        })
+    }
+
+    /// Split transfers into commands of at most `bytes` (rounded down to
+    /// whole blocks, at least one block), but never more than the LUN's
+    /// MAXIMUM TRANSFER LENGTH.
+    pub fn with_max_transfer_bytes(mut self, bytes: u32) -> Self {
+        let blocks = (bytes / self.capacity.block_size).max(1);
+        self.max_transfer_blocks = self
+            .limits
+            .and_then(|l| l.max_transfer_len)
+            .map_or(blocks, |max| blocks.min(max));
+        self
+    }
+
+    /// Block Limits VPD page of the LUN, if the target reported one.
+    #[inline]
+    pub fn block_limits(&self) -> Option<&BlockLimits> {
+        self.limits.as_ref()
+    }
+
+    /// Zones with the write pointers the device tracks, on a host-managed
+    /// zoned LUN.
+    pub async fn zone_map(&self) -> Option<ZoneMap> {
+        match &self.zones {
+            Some(zones) => Some(zones.lock().await.clone()),
+            None => None,
+        }
+    }
+
+    /// Re-read every zone, e.g. after another initiator wrote to the LUN.
+    pub async fn refresh_zones(&self) -> error::Result<()> {
+        if let Some(zones) = &self.zones {
+            let mut map = zones.lock().await;
+            *map = self.lun.zone_map().await?;
+        }
+        Ok(())
+    }
+
+    /// Apply `action` to the zone starting at `zone_start`, or to every zone
+    /// when `zone_start` is `None`, and record the result in the zone map.
+    pub async fn zone_action(
+        &self,
+        action: ZoneAction,
+        zone_start: Option<u64>,
+    ) -> error::Result<()> {
+        let Some(zones) = &self.zones else {
+            return self.lun.zone_action(action, zone_start).await;
+        };
+        // Held across the command so no write checks a stale pointer.
+        let mut map = zones.lock().await;
+        let done = self.lun.zone_action(action, zone_start).await;
+        // Even a failed action may have moved some write pointers.
+        let refreshed = match zone_start {
+            Some(start) => self.lun.zone_at(start).await.map(|zone| map.update(zone)),
+            None => self.lun.zone_map().await.map(|fresh| *map = fresh),
+        };
+        done?;
+        refreshed
+    }
+
+    /// Reset the write pointer of the zone starting at `zone_start` and
+    /// record it in the zone map.
+    pub async fn reset_zone(&self, zone_start: u64) -> error::Result<()> {
+        self.zone_action(ZoneAction::ResetWritePointer, Some(zone_start))
+            .await
+    }
+
+    /// The underlying LUN handle.
+    #[inline]
+    pub fn lun(&self) -> &LunHandle {
+        &self.lun
+    }
+
+    /// Logical block length in bytes.
+    #[inline]
+    pub fn block_size(&self) -> u32 {
+        self.capacity.block_size
+    }
+
+    /// Device size in bytes, following resizes the session has picked up
+    /// (see [`LunHandle::cached_capacity`]). The block size stays the one
+    /// read at open.
+    pub fn size(&self) -> u64 {
+        let capacity = self.lun.cached_capacity().unwrap_or(self.capacity);
+        u64::try_from(capacity.bytes()).unwrap_or(u64::MAX)
+    }
+
+    /// Read `len` bytes at `offset`.
+    pub async fn read(&self, offset: u64, len: usize) -> error::Result<Vec<u8>> {
+        let span = self.span(offset, len)?;
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let mut data = self.read_blocks(span.lba, span.blocks).await?;
+        data.drain(..span.head);
+        data.truncate(len);
+        Ok(data)
+    }
+
+    /// Write `data` at `offset`.
+    ///
+    /// Blocks only partly covered by `data` are read first and written back
+    /// with the new bytes merged in; such writes are serialized against every
+    /// other write through any clone of the device, while aligned writes
+    /// still run concurrently with each other. On a zoned LUN writes to
+    /// a sequential-write-required zone must start at its write pointer, so
+    /// only block-aligned appends succeed there.
+    pub async fn write(&self, offset: u64, data: &[u8]) -> error::Result<()> {
+        let span = self.span(offset, data.len())?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        let bs = self.capacity.block_size as usize;
+        if span.head == 0 && data.len().is_multiple_of(bs) {
+            let _shared = self.partial.read().await;
+            return self.write_blocks(span.lba, data).await.map_err(Into::into);
+        }
+
+        let _partial = self.partial.write().await;
+        let mut buf = vec![0u8; span.blocks as usize * bs];
+        if span.head != 0 {
+            let first = self.lun.read_at(span.lba, 1).await?;
+            buf[..bs].copy_from_slice(&first);
+        }
+        let tail = (span.head + data.len()) % bs;
+        if tail != 0 && (span.blocks > 1 || span.head == 0) {
+            let last_lba = span.lba + span.blocks - 1;
+            let last = self.lun.read_at(last_lba, 1).await?;
+            let at = buf.len() - bs;
+            buf[at..].copy_from_slice(&last);
+        }
+        buf[span.head..span.head + data.len()].copy_from_slice(data);
+        self.write_blocks(span.lba, &buf).await.map_err(Into::into)
+    }
+
+    /// Deallocate the unmap granules lying entirely inside `len` bytes at
+    /// `offset` (see [`LunHandle::punch_hole`]). Partly covered granules at
+    /// either end are left alone; without a Block Limits page the UNMAP
+    /// commands use the conservative fallback limits of
+    /// [`plan_unmap`](crate::control_block::unmap::plan_unmap).
+    pub async fn discard(&self, offset: u64, len: u64) -> error::Result<()> {
+        self.end_of(offset, len)?;
+        self.lun
+            .punch_hole_with(offset, len, &self.limits.unwrap_or_default())
+            .await?;
+        Ok(())
+    }
+
+    /// Make completed writes durable (SYNCHRONIZE CACHE).
+    pub async fn flush(&self) -> error::Result<()> {
+        self.lun.flush(..).await
+    }
+
+    /// Make completed writes to the blocks touching `len` bytes at `offset`
+    /// durable, leaving the rest of the cache alone.
+    pub async fn flush_range(&self, offset: u64, len: u64) -> error::Result<()> {
+        let end = self.end_of(offset, len)?;
+        let bs = self.capacity.block_size as u64;
+        self.lun.flush(offset / bs..end.div_ceil(bs)).await
+    }
+
+    /// Log out every session of the pool behind this device.
+    pub async fn close(self, max_wait: Duration) -> error::Result<()> {
+        self.lun
+            .session()
+            .pool()
+            .shutdown_gracefully(max_wait)
+            .await
+    }
+
+    fn span(&self, offset: u64, len: usize) -> Result<Span> {
+        let end = self.end_of(offset, len as u64)?;
+        Ok(Span::new(offset, end, self.capacity.block_size))
+    }
+
+    /// End of the byte range, checked against the device size.
+    fn end_of(&self, offset: u64, len: u64) -> Result<u64> {
+        offset
+            .checked_add(len)
+            .filter(|&end| end <= self.size())
+            .with_context(|| {
+                format!(
+                    "range {offset}+{len} is beyond the end of the device ({} bytes)",
+                    self.size()
+                )
+            })
+    }
+
+    async fn read_blocks(&self, lba: u64, blocks: u64) -> Result<Vec<u8>> {
+        let mut out =
+            Vec::with_capacity(blocks as usize * self.capacity.block_size as usize);
+        let mut done = 0;
+        while done < blocks {
+            let n = (blocks - done).min(self.max_transfer_blocks as u64) as u32;
+            out.extend_from_slice(&self.lun.read_at(lba + done, n).await?);
+            done += n as u64;
+        }
+        Ok(out)
+    }
+
+    async fn write_blocks(&self, lba: u64, data: &[u8]) -> Result<()> {
+        let bs = self.capacity.block_size as usize;
+        let chunk_len = self.max_transfer_blocks as usize * bs;
+        let mut lba = lba;
+        for chunk in data.chunks(chunk_len) {
+            let blocks = (chunk.len() / bs) as u64;
+            let Some(zones) = &self.zones else {
+                self.lun.write_at(lba, chunk).await?;
+                lba += blocks;
+                continue;
+            };
+            // Check, write and advance under one lock: a concurrent write
+            // to the same zone must see the pointer this one leaves.
+            let mut map = zones.lock().await;
+            map.check_write(lba, blocks)?;
+            if let Err(e) = self.lun.write_at(lba, chunk).await {
+                // The target may have taken part of the write; learn where
+                // the write pointer really is.
+                match self.lun.zone_at(lba).await {
+                    Ok(zone) => map.update(zone),
+                    Err(refresh) => debug!("zone at LBA {lba} not refreshed: {refresh}"),
+                }
+                return Err(e.into());
+            }
+            map.advance(lba, blocks);
+            lba += blocks;
+        }
+        Ok(())
+    }
+}
+
+/// Whole blocks covering a byte range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    /// First block touched.
+    lba: u64,
+    /// Number of blocks touched.
+    blocks: u64,
+    /// Offset of the range inside the first block.
+    head: usize,
+}
+
+impl Span {
+    fn new(start: u64, end: u64, block_size: u32) -> Self {
+        let bs = block_size as u64;
+        let lba = start / bs;
+        Self {
+            lba,
+            blocks: end.div_ceil(bs) - lba,
+            head: (start % bs) as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        time::sleep,
+    };
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::{
+        cfg::enums::YesNo,
+        client::client::ClientConnection,
+        models::{common::HEADER_LEN, identifiers::Tsih},
+    };
+
+    /// Serve READ CAPACITY(10), READ(10) and WRITE(10) with immediate data
+    /// from `disk` (512-byte blocks); every other command gets CHECK
+    /// CONDITION / INVALID COMMAND OPERATION CODE. READs are answered late,
+    /// so a second read-modify-write issued meanwhile reads stale data.
+    async fn serve_disk(mut stream: TcpStream, mut disk: Vec<u8>) -> Result<()> {
+        let mut stat_sn = 1u32;
+        loop {
+            let mut bhs = [0u8; HEADER_LEN];
+            if stream.read_exact(&mut bhs).await.is_err() {
+                return Ok(());
+            }
+            let len = u32::from_be_bytes([0, bhs[5], bhs[6], bhs[7]]) as usize;
+            let mut data = vec![0u8; len.next_multiple_of(4)];
+            stream.read_exact(&mut data).await?;
+            data.truncate(len);
+            let cmd_sn = u32::from_be_bytes(bhs[24..28].try_into()?);
+            let cdb = &bhs[32..48];
+            let lba = u32::from_be_bytes(cdb[2..6].try_into()?) as usize * 512;
+            let blocks = u16::from_be_bytes([cdb[7], cdb[8]]) as usize;
+
+            let mut header = [0u8; HEADER_LEN];
+            header[16..20].copy_from_slice(&bhs[16..20]);
+            header[24..28].copy_from_slice(&stat_sn.to_be_bytes());
+            header[28..32].copy_from_slice(&cmd_sn.wrapping_add(1).to_be_bytes());
+            header[32..36].copy_from_slice(&cmd_sn.wrapping_add(64).to_be_bytes());
+            stat_sn += 1;
+            let payload = match cdb[0] {
+                0x25 => [
+                    (disk.len() as u32 / 512 - 1).to_be_bytes(),
+                    512u32.to_be_bytes(),
+                ]
+                .concat(),
+                0x28 => {
+                    sleep(Duration::from_millis(50)).await;
+                    disk[lba..lba + blocks * 512].to_vec()
+                },
+                0x2A => {
+                    disk[lba..lba + data.len()].copy_from_slice(&data);
+                    Vec::new()
+                },
+                _ => {
+                    let mut sense = [0u8; 20];
+                    sense[0..2].copy_from_slice(&18u16.to_be_bytes());
+                    sense[2] = 0x70;
+                    sense[4] = 0x05; // ILLEGAL REQUEST
+                    sense[9] = 10;
+                    sense[14] = 0x20; // INVALID COMMAND OPERATION CODE
+                    header[0] = 0x21;
+                    header[1] = 0x80;
+                    header[3] = 0x02; // CHECK CONDITION
+                    header[7] = sense.len() as u8;
+                    stream.write_all(&header).await?;
+                    stream.write_all(&sense).await?;
+                    continue;
+                },
+            };
+            if payload.is_empty() {
+                header[0] = 0x21;
+                header[1] = 0x80;
+            } else {
+                // Data-In carrying the final status.
+                header[0] = 0x25;
+                header[1] = 0x81;
+                header[5..8].copy_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+                header[20..24].copy_from_slice(&u32::MAX.to_be_bytes());
+            }
+            stream.write_all(&header).await?;
+            stream.write_all(&payload).await?;
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_unaligned_writes_to_one_block_both_land() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+        cfg.login.transport.target_address = listener.local_addr()?.to_string();
+        cfg.login.write_flow.initial_r2t = YesNo::No;
+        cfg.login.write_flow.immediate_data = YesNo::Yes;
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            serve_disk(stream, vec![0u8; 8 * 512]).await
+        });
+
+        let pool = Pool::new(&cfg);
+        let conn =
+            ClientConnection::connect(cfg.clone(), CancellationToken::new()).await?;
+        pool.insert_session(Tsih::new(1), "iqn.2025-08.example:disk0", conn);
+        let dev =
+            IscsiDevice::from_lun(pool.session(Tsih::new(1))?.lun(Lun::ZERO)).await?;
+        assert_eq!(dev.size(), 8 * 512);
+
+        let other = dev.clone();
+        let (a, b) = tokio::join!(dev.write(520, b"first"), other.write(1000, b"second"));
+        a?;
+        b?;
+        let block = dev.read(512, 512).await?;
+        assert_eq!(&block[8..13], b"first");
+        assert_eq!(&block[488..494], b"second");
+        server.abort();
+        Ok(())
+    }
+
+    #[test]
+    fn span_covers_partial_blocks() {
+        assert_eq!(
+            Span::new(0, 4096, 512),
+            Span {
+                lba: 0,
+                blocks: 8,
+                head: 0
+            }
+        );
+        assert_eq!(
+            Span::new(700, 1100, 512),
+            Span {
+                lba: 1,
+                blocks: 2,
+                head: 188
+            }
+        );
+        assert_eq!(
+            Span::new(513, 514, 512),
+            Span {
+                lba: 1,
+                blocks: 1,
+                head: 1
+            }
+        );
+    }
+}
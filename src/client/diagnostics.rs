@@ -0,0 +1,87 @@
+//! Self-tests and diagnostic pages (SEND DIAGNOSTIC / RECEIVE DIAGNOSTIC
+//! RESULTS) on a LUN.
+//!
+//! [`LunHandle::self_test`] runs one of the device self-tests. The page
+//! methods read and write diagnostic pages; the `ses_*` ones decode the SCSI
+//! Enclosure Services pages, so the element status of an enclosure-backed
+//! target can be inspected and its elements controlled.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::diagnostic::{
+        DiagnosticPage, SelfTest, SesConfiguration, SesEnclosureStatus,
+        build_receive_diagnostic, build_send_diagnostic, page, parse_diagnostic_page,
+        parse_diagnostic_page_length, parse_ses_configuration,
+        parse_ses_enclosure_status, parse_supported_diagnostic_pages,
+    },
+    error,
+};
+
+/// Allocation length of the first RECEIVE DIAGNOSTIC RESULTS; pages that
+/// are longer are read again at their full size.
+const DIAGNOSTIC_ALLOC_LEN: u16 = 1024;
+
+impl LunHandle {
+    /// Run `test` with SEND DIAGNOSTIC. Foreground and default tests finish
+    /// before the command completes; background ones are only started.
+    pub async fn self_test(&self, test: SelfTest) -> error::Result<()> {
+        let mut cdb = [0u8; 16];
+        build_send_diagnostic(&mut cdb, Some(test), false, 0, 0);
+        self.read_cdb(cdb, 0, None).await?;
+        Ok(())
+    }
+
+    /// Send `page` with SEND DIAGNOSTIC (PF=1), e.g. an SES control page.
+    pub async fn send_diagnostic_page(&self, page: &DiagnosticPage) -> error::Result<()> {
+        let data = page.to_bytes();
+        let mut cdb = [0u8; 16];
+        build_send_diagnostic(&mut cdb, None, true, data.len() as u16, 0);
+        self.write_cdb(cdb, data, None).await.map_err(Into::into)
+    }
+
+    /// Read diagnostic page `page_code` with RECEIVE DIAGNOSTIC RESULTS.
+    pub async fn receive_diagnostic_page(
+        &self,
+        page_code: u8,
+    ) -> error::Result<DiagnosticPage> {
+        let data = self.receive_diagnostic(page_code).await?;
+        Ok(parse_diagnostic_page(&data)?)
+    }
+
+    /// Page codes listed in the Supported Diagnostic Pages page.
+    pub async fn supported_diagnostic_pages(&self) -> error::Result<Vec<u8>> {
+        let data = self.receive_diagnostic(page::SUPPORTED).await?;
+        Ok(parse_supported_diagnostic_pages(&data)?)
+    }
+
+    /// The SES Configuration page: element types and counts.
+    pub async fn ses_configuration(&self) -> error::Result<SesConfiguration> {
+        let data = self.receive_diagnostic(page::SES_CONFIGURATION).await?;
+        Ok(parse_ses_configuration(&data)?)
+    }
+
+    /// The SES Enclosure Status page. Group its elements with
+    /// [`SesEnclosureStatus::group_by_type`] and the configuration.
+    pub async fn ses_enclosure_status(&self) -> error::Result<SesEnclosureStatus> {
+        let data = self.receive_diagnostic(page::SES_ENCLOSURE_STATUS).await?;
+        Ok(parse_ses_enclosure_status(&data)?)
+    }
+
+    async fn receive_diagnostic(&self, page_code: u8) -> anyhow::Result<Vec<u8>> {
+        let mut cdb = [0u8; 16];
+        build_receive_diagnostic(&mut cdb, Some(page_code), DIAGNOSTIC_ALLOC_LEN, 0);
+        let data = self
+            .read_cdb(cdb, DIAGNOSTIC_ALLOC_LEN as u32, None)
+            .await?;
+        let full = parse_diagnostic_page_length(&data)?;
+        if full <= data.len() || data.len() < DIAGNOSTIC_ALLOC_LEN as usize {
+            return Ok(data);
+        }
+        let alloc_len = u16::try_from(full).unwrap_or(u16::MAX);
+        build_receive_diagnostic(&mut cdb, Some(page_code), alloc_len, 0);
+        self.read_cdb(cdb, alloc_len as u32, None).await
+    }
+}
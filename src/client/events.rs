@@ -0,0 +1,80 @@
+//! Lifecycle events of the sessions and connections of a pool.
+//!
+//! [`Pool::subscribe_events`](crate::client::pool_sessions::Pool::subscribe_events)
+//! hands out receivers of a `tokio::sync::broadcast` channel, so embedding
+//! applications can alert or fail over without polling
+//! [`Pool::status`](crate::client::pool_sessions::Pool::status). Events are
+//! advisory: a subscriber that falls behind loses the oldest ones
+//! (`RecvError::Lagged`).
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    client::{handles::Capacity, status::ConnectionHealth},
+    models::identifiers::{Cid, Isid, Lun, Tsih},
+};
+
+/// Something that happened to a session or connection of the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolEvent {
+    /// A new session finished its leading login.
+    SessionLoggedIn {
+        tsih: Tsih,
+        isid: Isid,
+        target_name: Arc<str>,
+    },
+    /// A connection's transport failed and it stopped serving commands.
+    ConnectionFailed {
+        tsih: Tsih,
+        cid: Cid,
+        /// Why the connection was considered dead.
+        reason: String,
+    },
+    /// A failed connection was logged in again by the reconnection
+    /// supervisor.
+    Reconnected { tsih: Tsih, cid: Cid, attempts: u32 },
+    /// The target sent an Asynchronous Message asking the initiator to log
+    /// the connection out.
+    TargetAsyncLogoutRequested {
+        tsih: Tsih,
+        cid: Cid,
+        /// Time the target waits for the Logout before dropping the
+        /// connection itself.
+        logout_within: Duration,
+    },
+    /// A connection bound to the pool changed its
+    /// [`ConnectionHealth`].
+    HealthChanged {
+        tsih: Tsih,
+        cid: Cid,
+        from: ConnectionHealth,
+        to: ConnectionHealth,
+    },
+    /// A keep-alive NOP-Out was not answered.
+    NopTimeout { tsih: Tsih, cid: Cid, error: String },
+    /// A refreshed REPORT LUNS list contains a LUN the previous one did not.
+    LunAdded { tsih: Tsih, lun: Lun },
+    /// A refreshed REPORT LUNS list no longer contains a LUN.
+    LunRemoved { tsih: Tsih, lun: Lun },
+    /// READ CAPACITY returned a different size than the cached one, e.g.
+    /// after CAPACITY DATA HAS CHANGED.
+    CapacityChanged {
+        tsih: Tsih,
+        lun: Lun,
+        previous: Capacity,
+        capacity: Capacity,
+    },
+    /// A READ or WRITE failed with MEDIUM ERROR at `lba`, under a
+    /// [`MediumErrorPolicy`](crate::client::remap::MediumErrorPolicy) other
+    /// than `Off`.
+    MediumError {
+        tsih: Tsih,
+        lun: Lun,
+        lba: u64,
+        /// The block was moved to a spare with REASSIGN BLOCKS.
+        reassigned: bool,
+    },
+}
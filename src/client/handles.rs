@@ -0,0 +1,1083 @@
+//! Session and LUN handles for application code.
+//!
+//! [`Pool::session`](crate::client::pool_sessions::Pool::session) returns a
+//! [`SessionHandle`] and [`SessionHandle::lun`] a [`LunHandle`]. Its
+//! `read_at`, `write_at`, `unmap`, `flush`, `inquiry`, ... build the CDBs and
+//! let the pool pick the connection, so callers never deal with ITTs, CmdSN,
+//! ExpStatSN or CIDs.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    fmt,
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow, ensure};
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+use crate::{
+    client::pool_sessions::{ExecuteEnv, Pool},
+    control_block::{
+        control::{CdbControl, split_cdb32},
+        get_lba_status::{
+            GET_LBA_STATUS_HEADER_LEN, LBA_STATUS_DESCRIPTOR_LEN, LbaStatus,
+            build_get_lba_status, parse_get_lba_status,
+        },
+        inquiry::{
+            BlockLimits, DeviceIdentity, InquiryStandard, LogicalBlockProvisioning,
+            VpdPage, device_identity, fill_inquiry_standard_simple,
+            fill_inquiry_vpd_simple, parse_inquiry_standard, parse_vpd_block_limits,
+            parse_vpd_device_id, parse_vpd_logical_block_provisioning,
+        },
+        prefetch::build_prefetch,
+        protection::{
+            PROTECT_CHECK_ALL, PiParams, ProtectionFormat, ProtectionType, protect_flags,
+        },
+        read::{build_read10, build_read16, build_read32},
+        read_capacity::{
+            ReadCapacity16, build_read_capacity10, build_read_capacity16,
+            parse_read_capacity10_zerocopy, parse_read_capacity16,
+        },
+        retry_safety::RetrySafety,
+        start_stop_unit::{PowerCondition, build_start_stop_unit},
+        sync_cache::build_sync_cache,
+        unmap::{build_unmap, fill_unmap_parameters},
+        verify::{
+            ByteCheck, build_verify10, build_verify16, build_write_verify10,
+            build_write_verify16,
+        },
+        write::{build_write10, build_write16, build_write32},
+    },
+    error::{self, IscsiError},
+    models::{
+        command::common::{ScsiStatus, TaskAttribute},
+        identifiers::{Cid, Lun, Tsih},
+    },
+    state_machine::{
+        common::StateMachineCtx, read_states::ReadCtx, tur_states::TurCtx,
+        write_states::WriteCtx,
+    },
+};
+
+/// Allocation length of the standard INQUIRY issued by
+/// [`LunHandle::inquiry`].
+const INQUIRY_ALLOC_LEN: u8 = 96;
+
+/// Descriptors requested per GET LBA STATUS.
+const LBA_STATUS_DESCRIPTORS: usize = 256;
+
+/// Allocation length of VPD INQUIRYs, the most a 1-byte field allows.
+const VPD_ALLOC_LEN: u8 = 255;
+
+/// ILLEGAL REQUEST / INVALID COMMAND OPERATION CODE: the target does not
+/// implement the CDB that was sent.
+pub(crate) const INVALID_OPCODE: (u8, u8) = (0x05, 0x20);
+
+/// A logged-in session of a [`Pool`].
+#[derive(Clone)]
+pub struct SessionHandle {
+    pool: Arc<Pool>,
+    tsih: Tsih,
+}
+
+impl fmt::Debug for SessionHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionHandle")
+            .field("tsih", &self.tsih)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionHandle {
+    pub(crate) fn new(pool: Arc<Pool>, tsih: Tsih) -> Self {
+        Self { pool, tsih }
+    }
+
+    /// TSIH of the session.
+    #[inline]
+    pub fn tsih(&self) -> Tsih {
+        self.tsih
+    }
+
+    /// The pool the session belongs to, for the lower-level `execute_*`
+    /// calls.
+    #[inline]
+    pub fn pool(&self) -> &Arc<Pool> {
+        &self.pool
+    }
+
+    /// Handle for logical unit `lun` of this session. The LUN is not checked
+    /// until the first command.
+    pub fn lun(&self, lun: Lun) -> LunHandle {
+        LunHandle {
+            session: self.clone(),
+            lun,
+            control: CdbControl::default(),
+            retry: true,
+            cid: None,
+        }
+    }
+
+    /// Flush the whole cache of each of `luns`, or of every LUN the session
+    /// reports (REPORT LUNS) when empty, and wait for all of them. The
+    /// flushes run in parallel, each queued with `attr` as by
+    /// [`LunHandle::flush_with`]. [`TaskAttribute::Ordered`] orders each
+    /// flush only against the commands of its own LUN; commands to other
+    /// LUNs are not held back, so the call is not a barrier across LUNs.
+    /// Returns the flushed LUNs; if some flushes fail, the first failure (in
+    /// LUN order) is returned once all completed.
+    pub async fn flush_all(
+        &self,
+        luns: &[Lun],
+        attr: TaskAttribute,
+    ) -> error::Result<Vec<Lun>> {
+        let luns = match luns {
+            [] => match self.pool.cached_luns(self.tsih) {
+                Some(luns) => luns,
+                None => self.pool.report_luns(self.tsih).await?,
+            },
+            luns => luns.to_vec(),
+        };
+        let mut flushes = JoinSet::new();
+        for (index, &lun) in luns.iter().enumerate() {
+            let handle = self.lun(lun);
+            flushes.spawn(async move { (index, handle.flush_with(.., attr).await) });
+        }
+        let mut failures = Vec::new();
+        while let Some(joined) = flushes.join_next().await {
+            let (index, res) = joined.context("flush task failed")?;
+            if let Err(e) = res {
+                warn!("TSIH={}: flush of {} failed: {e}", self.tsih, luns[index]);
+                failures.push((index, e));
+            }
+        }
+        match failures.into_iter().min_by_key(|&(index, _)| index) {
+            Some((_, e)) => Err(e),
+            None => Ok(luns),
+        }
+    }
+}
+
+/// Size of a logical unit as reported by READ CAPACITY.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capacity {
+    /// Number of logical blocks (max LBA + 1).
+    pub blocks: u64,
+    /// Logical block length in bytes.
+    pub block_size: u32,
+}
+
+impl Capacity {
+    /// Capacity in bytes.
+    #[inline]
+    pub fn bytes(&self) -> u128 {
+        self.blocks as u128 * self.block_size as u128
+    }
+}
+
+/// A logical unit reached through a [`SessionHandle`].
+///
+/// The capacity learned by the first block-addressed command is cached on
+/// the session and shared by every handle of the LUN.
+#[derive(Debug, Clone)]
+pub struct LunHandle {
+    session: SessionHandle,
+    lun: Lun,
+    control: CdbControl,
+    /// Whether commands may be re-issued after an ambiguous failure when
+    /// their opcode allows it.
+    retry: bool,
+    /// Connection every command goes through, instead of the one the pool
+    /// picks.
+    cid: Option<Cid>,
+}
+
+impl LunHandle {
+    /// Logical unit number.
+    #[inline]
+    pub fn lun(&self) -> Lun {
+        self.lun
+    }
+
+    /// Handle whose commands carry the NACA and vendor bits of `control` in
+    /// their CONTROL byte, e.g. [`CdbControl::NACA`] to have a CHECK
+    /// CONDITION establish ACA (see [`Pool::clear_aca`]).
+    pub fn with_control(mut self, control: CdbControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Handle whose commands are never re-issued after an ambiguous
+    /// failure, whatever their opcode says.
+    pub(crate) fn without_retry(mut self) -> Self {
+        self.retry = false;
+        self
+    }
+
+    /// Handle whose commands all go through connection `cid`.
+    pub(crate) fn on_connection(mut self, cid: Cid) -> Self {
+        self.cid = Some(cid);
+        self
+    }
+
+    /// CONTROL byte settings of this handle's commands.
+    #[inline]
+    pub fn control(&self) -> CdbControl {
+        self.control
+    }
+
+    /// Session the LUN is reached through.
+    #[inline]
+    pub fn session(&self) -> &SessionHandle {
+        &self.session
+    }
+
+    /// Capacity of the LUN, read once and cached on the session. The cache
+    /// is refreshed when the target reports CAPACITY DATA HAS CHANGED.
+    pub async fn capacity(&self) -> error::Result<Capacity> {
+        match self.cached_capacity() {
+            Some(capacity) => Ok(capacity),
+            None => self.read_capacity().await,
+        }
+    }
+
+    /// Capacity cached on the session, without a round trip.
+    pub fn cached_capacity(&self) -> Option<Capacity> {
+        self.session
+            .pool
+            .sessions
+            .get(&self.session.tsih)?
+            .inventory
+            .capacity(self.lun)
+    }
+
+    /// Issue READ CAPACITY(10), and READ CAPACITY(16) when the LUN is too
+    /// large for it, and store the result in the session's cache.
+    pub async fn read_capacity(&self) -> error::Result<Capacity> {
+        let capacity = self.query_capacity().await?;
+        self.session
+            .pool
+            .update_capacity(self.session.tsih, self.lun, capacity);
+        Ok(capacity)
+    }
+
+    /// READ CAPACITY(16) with every field decoded: protection type,
+    /// thin-provisioning bits and physical block geometry. Does not touch
+    /// the cached capacity.
+    pub async fn read_capacity16(&self) -> error::Result<ReadCapacity16> {
+        let mut cdb = [0u8; 16];
+        build_read_capacity16(&mut cdb, 0, false, 32, 0);
+        let data = self.read_cdb(cdb, 32, None).await?;
+        let rc16 = parse_read_capacity16(&data)?;
+        self.set_protection(ProtectionFormat::from_read_capacity16(&rc16)?);
+        Ok(rc16)
+    }
+
+    /// Protection information format of the LUN, from READ CAPACITY(16)
+    /// unless already known. Once known, [`LunHandle::read_at`] and
+    /// [`LunHandle::write_at`] transfer and check PI tuples; type 2 LUNs
+    /// are read and written with READ(32) / WRITE(32).
+    pub async fn protection(&self) -> error::Result<Option<ProtectionFormat>> {
+        match self.cached_protection() {
+            Some(format) => Ok(format),
+            None => Ok(ProtectionFormat::from_read_capacity16(
+                &self.read_capacity16().await?,
+            )?),
+        }
+    }
+
+    /// Protection format cached on the session: `None` until READ
+    /// CAPACITY(16) was read, `Some(None)` for a LUN without PI.
+    pub fn cached_protection(&self) -> Option<Option<ProtectionFormat>> {
+        self.session
+            .pool
+            .sessions
+            .get(&self.session.tsih)?
+            .inventory
+            .protection(self.lun)
+    }
+
+    fn set_protection(&self, format: Option<ProtectionFormat>) {
+        if let Some(session) = self.session.pool.sessions.get(&self.session.tsih) {
+            session.inventory.set_protection(self.lun, format);
+        }
+    }
+
+    /// PI parameters of a READ / WRITE at `lba`, when the LUN's format is
+    /// known.
+    fn data_protection(&self, cap: &Capacity, lba: u64) -> Option<PiParams> {
+        self.cached_protection()
+            .flatten()
+            .map(|f| f.params(cap.block_size, lba))
+    }
+
+    /// Whether the LUN is known to reject READ(16) / WRITE(16).
+    fn cdb16_unsupported(&self) -> bool {
+        self.session
+            .pool
+            .sessions
+            .get(&self.session.tsih)
+            .is_some_and(|s| s.inventory.cdb16_unsupported(self.lun))
+    }
+
+    /// Whether `e`, the failure of a READ(16) / WRITE(16), says the target
+    /// does not implement the opcode; remembered for the session if so.
+    /// Type 2 LUNs use the 32-byte CDBs, which have no shorter form.
+    fn rejects_cdb16(&self, e: &IscsiError) -> bool {
+        let IscsiError::Scsi(e) = e else {
+            return false;
+        };
+        let invalid_opcode = e
+            .sense_codes()
+            .is_some_and(|(key, asc, _)| (key, asc) == INVALID_OPCODE);
+        let type2 = self
+            .cached_protection()
+            .flatten()
+            .is_some_and(|f| f.ptype == ProtectionType::Type2);
+        if !invalid_opcode || type2 {
+            return false;
+        }
+        debug!(
+            "{}: 16-byte READ / WRITE not supported, using the 10-byte forms",
+            self.lun
+        );
+        if let Some(session) = self.session.pool.sessions.get(&self.session.tsih) {
+            session.inventory.note_cdb16_unsupported(self.lun);
+        }
+        true
+    }
+
+    async fn query_capacity(&self) -> Result<Capacity> {
+        let mut cdb = [0u8; 16];
+        build_read_capacity10(&mut cdb, 0, false, 0);
+        let data = self.read_cdb(cdb, 8, None).await?;
+        let rc10 = parse_read_capacity10_zerocopy(&data)?;
+        if !rc10.indicates_overflow() {
+            return Ok(Capacity {
+                blocks: rc10.max_lba.get() as u64 + 1,
+                block_size: rc10.block_len.get(),
+            });
+        }
+
+        build_read_capacity16(&mut cdb, 0, false, 32, 0);
+        let data = self.read_cdb(cdb, 32, None).await?;
+        let rc16 = parse_read_capacity16(&data)?;
+        self.set_protection(ProtectionFormat::from_read_capacity16(&rc16)?);
+        Ok(Capacity {
+            blocks: rc16.blocks(),
+            block_size: rc16.block_len,
+        })
+    }
+
+    /// TEST UNIT READY; fails with the SCSI status (e.g. a Unit Attention)
+    /// when the LUN is not ready.
+    pub async fn test_unit_ready(&self) -> error::Result<()> {
+        let lun = self.lun;
+        let control = self.control;
+        self.execute(None, |env| {
+            TurCtx::from_execute_env(env, lun).with_control(control)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// START STOP UNIT with START set: spin the LUN up and wait until it
+    /// is ready.
+    pub async fn start_unit(&self) -> error::Result<()> {
+        self.start_stop(PowerCondition::StartValid, true).await
+    }
+
+    /// START STOP UNIT with START cleared: stop the LUN after it wrote its
+    /// cache. Media access then fails with NOT READY until
+    /// [`start_unit`](Self::start_unit).
+    pub async fn stop_unit(&self) -> error::Result<()> {
+        self.start_stop(PowerCondition::StartValid, false).await
+    }
+
+    /// Move the LUN to `power` (active, idle, standby, ...) with START STOP
+    /// UNIT. [`PowerCondition::StartValid`] carries no power condition of
+    /// its own and is sent like [`start_unit`](Self::start_unit), so it never
+    /// stops the LUN.
+    pub async fn set_power_condition(&self, power: PowerCondition) -> error::Result<()> {
+        self.start_stop(power, power == PowerCondition::StartValid)
+            .await
+    }
+
+    async fn start_stop(&self, power: PowerCondition, start: bool) -> error::Result<()> {
+        let mut cdb = [0u8; 16];
+        build_start_stop_unit(&mut cdb, power, start, false, false, false, 0);
+        self.read_cdb(cdb, 0, None).await?;
+        Ok(())
+    }
+
+    /// Standard INQUIRY data (vendor, product, device type, ...).
+    pub async fn inquiry(&self) -> error::Result<InquiryStandard> {
+        let mut cdb = [0u8; 16];
+        fill_inquiry_standard_simple(&mut cdb, INQUIRY_ALLOC_LEN);
+        let data = self.read_cdb(cdb, INQUIRY_ALLOC_LEN as u32, None).await?;
+        Ok(parse_inquiry_standard(&data)?)
+    }
+
+    /// Identity of the logical unit from VPD 0x83 (NAA, else EUI-64, else
+    /// SCSI name string), the same on every session and portal that reaches
+    /// it. `None` when the target reports none of these designators.
+    pub async fn identity(&self) -> error::Result<Option<DeviceIdentity>> {
+        let data = self.vpd_page(VpdPage::DeviceId).await?;
+        Ok(device_identity(&parse_vpd_device_id(&data)?))
+    }
+
+    /// Block Limits VPD page (0xB0): transfer and UNMAP limits of the LUN.
+    /// Fails with ILLEGAL REQUEST on targets that do not implement it.
+    pub async fn block_limits(&self) -> error::Result<BlockLimits> {
+        Ok(parse_vpd_block_limits(
+            &self.vpd_page(VpdPage::BlockLimits).await?,
+        )?)
+    }
+
+    /// Logical Block Provisioning VPD page (0xB2): which deallocation
+    /// commands the LUN supports and its provisioning type.
+    pub async fn logical_block_provisioning(
+        &self,
+    ) -> error::Result<LogicalBlockProvisioning> {
+        let page = self.vpd_page(VpdPage::LbProvisioning).await?;
+        Ok(parse_vpd_logical_block_provisioning(&page)?)
+    }
+
+    /// Read `blocks` logical blocks starting at `lba`.
+    ///
+    /// READ(10) is used when the LBA and length fit, READ(16) otherwise. A
+    /// target that rejects READ(16) gets a run of READ(10)s instead, as long
+    /// as the range stays below 2^32 blocks.
+    ///
+    /// On a LUN whose protection information is known (see
+    /// [`LunHandle::protection`]) the target sends the PI tuples with the
+    /// data (RDPROTECT=001b); they are checked and stripped before the data
+    /// is returned.
+    pub async fn read_at(&self, lba: u64, blocks: u32) -> error::Result<Vec<u8>> {
+        if blocks == 0 {
+            return Ok(Vec::new());
+        }
+        let cap = self.capacity().await?;
+        check_range(&cap, lba, blocks as u64)?;
+        let split = splittable_to_cdb10(lba, blocks);
+        if !split || !self.cdb16_unsupported() {
+            match self.read_blocks(&cap, lba, blocks).await {
+                Err(e) if split && self.rejects_cdb16(&e) => {},
+                other => return other,
+            }
+        }
+        let mut data = Vec::with_capacity(blocks as usize * cap.block_size as usize);
+        for (lba, blocks) in cdb10_segments(lba, blocks) {
+            data.extend(self.read_blocks(&cap, lba, blocks).await?);
+        }
+        Ok(data)
+    }
+
+    /// [`Self::read_at`] with a single READ command.
+    async fn read_blocks(
+        &self,
+        cap: &Capacity,
+        lba: u64,
+        blocks: u32,
+    ) -> error::Result<Vec<u8>> {
+        let len = blocks
+            .checked_mul(cap.block_size)
+            .context("read length exceeds 4 GiB")?;
+        let pi = self.data_protection(cap, lba);
+        let ((cdb, ext), wire_len) = match &pi {
+            Some(p) => (
+                read_cdb_pi(lba, blocks, p),
+                u32::try_from(p.protected_len(len as usize))
+                    .context("read length exceeds 4 GiB")?,
+            ),
+            None => ((read_cdb(lba, blocks, 0), None), len),
+        };
+        let data = match self.read_cdb_pi(cdb, ext, wire_len, Some(lba), pi).await {
+            Ok(data) => data,
+            Err(e) => {
+                let e = IscsiError::from(e);
+                if !self.handle_medium_error(&e, lba, blocks).await {
+                    return Err(e);
+                }
+                self.read_cdb_pi(cdb, ext, wire_len, Some(lba), pi).await?
+            },
+        };
+        if data.len() != len as usize {
+            return Err(IscsiError::msg(format!(
+                "short read at LBA {lba}: {} of {len} bytes",
+                data.len()
+            )));
+        }
+        Ok(data)
+    }
+
+    /// Write `data`, a whole number of logical blocks, starting at `lba`.
+    /// The CDB size, the fallback to WRITE(10) and the PI tuples (sent with
+    /// WRPROTECT=001b) are as for [`LunHandle::read_at`].
+    pub async fn write_at(&self, lba: u64, data: impl AsRef<[u8]>) -> error::Result<()> {
+        let data = data.as_ref();
+        if data.is_empty() {
+            return Ok(());
+        }
+        let cap = self.capacity().await?;
+        let blocks = whole_blocks(&cap, data.len(), "write")?;
+        check_range(&cap, lba, blocks as u64)?;
+        let split = splittable_to_cdb10(lba, blocks);
+        if !split || !self.cdb16_unsupported() {
+            match self.write_blocks(&cap, lba, blocks, data).await {
+                Err(e) if split && self.rejects_cdb16(&e) => {},
+                other => return other,
+            }
+        }
+        self.write_cdb10_segments(&cap, lba, blocks, data).await
+    }
+
+    /// [`Self::write_at`] with one WRITE(10) per [`cdb10_segments`] range.
+    async fn write_cdb10_segments(
+        &self,
+        cap: &Capacity,
+        lba: u64,
+        blocks: u32,
+        data: &[u8],
+    ) -> error::Result<()> {
+        let block_size = cap.block_size as usize;
+        for (seg_lba, seg_blocks) in cdb10_segments(lba, blocks) {
+            let off = (seg_lba - lba) as usize * block_size;
+            let end = off + seg_blocks as usize * block_size;
+            self.write_blocks(cap, seg_lba, seg_blocks, &data[off..end])
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// [`Self::write_at`] with a single WRITE command.
+    async fn write_blocks(
+        &self,
+        cap: &Capacity,
+        lba: u64,
+        blocks: u32,
+        data: &[u8],
+    ) -> error::Result<()> {
+        let pi = self.data_protection(cap, lba);
+        let (cdb, ext) = match &pi {
+            Some(p) => write_cdb_pi(lba, blocks, p),
+            None => (write_cdb(lba, blocks, 0), None),
+        };
+        match self.write_cdb_pi(cdb, ext, data, Some(lba), pi).await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let e = IscsiError::from(e);
+                // Only a reassigned block is written again.
+                if !self.handle_medium_error(&e, lba, blocks).await {
+                    return Err(e);
+                }
+                self.write_cdb_pi(cdb, ext, data, Some(lba), pi)
+                    .await
+                    .map_err(Into::into)
+            },
+        }
+    }
+
+    /// Have the target check that `blocks` logical blocks starting at `lba`
+    /// are readable (VERIFY with BYTCHK=0); nothing is transferred.
+    pub async fn verify(&self, lba: u64, blocks: u32) -> error::Result<()> {
+        if blocks == 0 {
+            return Ok(());
+        }
+        let cap = self.capacity().await?;
+        check_range(&cap, lba, blocks as u64)?;
+        let cdb = verify_cdb(lba, blocks, ByteCheck::Medium);
+        self.write_cdb(cdb, Vec::new(), Some(lba))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Have the target compare the blocks starting at `lba` against `data`
+    /// (VERIFY with BYTCHK=1) instead of reading them back. A difference
+    /// fails with MISCOMPARE; see [`ScsiStatusError::miscompare_offset`].
+    ///
+    /// [`ScsiStatusError::miscompare_offset`]: crate::state_machine::common::ScsiStatusError::miscompare_offset
+    pub async fn compare_at(
+        &self,
+        lba: u64,
+        data: impl Into<Vec<u8>>,
+    ) -> error::Result<()> {
+        let data = data.into();
+        if data.is_empty() {
+            return Ok(());
+        }
+        let cap = self.capacity().await?;
+        let blocks = whole_blocks(&cap, data.len(), "compare")?;
+        check_range(&cap, lba, blocks as u64)?;
+        let cdb = verify_cdb(lba, blocks, ByteCheck::Compare);
+        self.write_cdb(cdb, data, Some(lba))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Write `data` and have the target verify it on the medium (WRITE AND
+    /// VERIFY with BYTCHK=1), failing with MISCOMPARE if it reads back
+    /// differently.
+    pub async fn write_verify_at(
+        &self,
+        lba: u64,
+        data: impl Into<Vec<u8>>,
+    ) -> error::Result<()> {
+        let data = data.into();
+        if data.is_empty() {
+            return Ok(());
+        }
+        let cap = self.capacity().await?;
+        let blocks = whole_blocks(&cap, data.len(), "write")?;
+        check_range(&cap, lba, blocks as u64)?;
+        self.write_cdb(write_verify_cdb(lba, blocks), data, Some(lba))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Flush the target's volatile cache for the logical blocks in `lbas`
+    /// (SYNCHRONIZE CACHE), making earlier writes to them durable. `..`
+    /// flushes the whole LUN; an open end flushes to the end of the medium.
+    /// The 16-byte CDB is used only when the range does not fit the 10-byte
+    /// one.
+    pub async fn flush(&self, lbas: impl RangeBounds<u64>) -> error::Result<()> {
+        self.flush_with(lbas, TaskAttribute::Simple).await
+    }
+
+    /// [`LunHandle::flush`] queued with `attr`. With
+    /// [`TaskAttribute::Ordered`] the target completes every command queued
+    /// before the flush first and starts none queued after it until the
+    /// flush is done: a write barrier and durability point for the LUN.
+    pub async fn flush_with(
+        &self,
+        lbas: impl RangeBounds<u64>,
+        attr: TaskAttribute,
+    ) -> error::Result<()> {
+        let Some((lba, blocks)) = cache_range(lbas) else {
+            return Ok(());
+        };
+        let mut cdb = [0u8; 16];
+        build_sync_cache(&mut cdb, lba, blocks, false);
+        let (lun, cdb) = (self.lun, self.controlled(cdb));
+        self.execute(None, |env| {
+            ReadCtx::from_execute_env(env, lun, 0, cdb).with_task_attribute(attr)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Ask the target to stage the logical blocks in `lbas` in its cache
+    /// (PRE-FETCH), with the same range rules as [`LunHandle::flush`]. With
+    /// `immed` the target answers before reading them.
+    ///
+    /// Returns `true` on CONDITION MET (the cache can hold all the blocks)
+    /// and `false` on GOOD (it cannot; some of them were still staged).
+    pub async fn prefetch(
+        &self,
+        lbas: impl RangeBounds<u64>,
+        immed: bool,
+    ) -> error::Result<bool> {
+        let Some((lba, blocks)) = cache_range(lbas) else {
+            return Ok(true);
+        };
+        let mut cdb = [0u8; 16];
+        build_prefetch(&mut cdb, lba, blocks, immed);
+        match self.read_cdb(cdb, 0, None).await.map_err(IscsiError::from) {
+            Ok(_) => Ok(false),
+            Err(IscsiError::Scsi(e)) if e.status == ScsiStatus::ConditionMet => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Deallocate `blocks` logical blocks starting at `lba` (UNMAP). The
+    /// LUN must support logical block provisioning.
+    pub async fn unmap(&self, lba: u64, blocks: u32) -> error::Result<()> {
+        if blocks == 0 {
+            return Ok(());
+        }
+        let cap = self.capacity().await?;
+        check_range(&cap, lba, blocks as u64)?;
+
+        let params = fill_unmap_parameters(&[(lba, blocks)]);
+        let mut cdb = [0u8; 16];
+        build_unmap(&mut cdb, false, params.len() as u16, 0);
+        self.write_cdb(cdb, params, Some(lba))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Provisioning status (mapped / deallocated / anchored) of `blocks`
+    /// logical blocks starting at `lba` (GET LBA STATUS), as extents in LBA
+    /// order. The first and last extents are clipped to the range; more
+    /// commands are sent when the target reports fewer extents than needed.
+    pub async fn lba_status(
+        &self,
+        lba: u64,
+        blocks: u64,
+    ) -> error::Result<Vec<LbaStatus>> {
+        let end = lba.checked_add(blocks).context("LBA range overflows")?;
+        let alloc_len = GET_LBA_STATUS_HEADER_LEN
+            + LBA_STATUS_DESCRIPTORS * LBA_STATUS_DESCRIPTOR_LEN;
+        let mut extents: Vec<LbaStatus> = Vec::new();
+        let mut next = lba;
+        while next < end {
+            let mut cdb = [0u8; 16];
+            build_get_lba_status(&mut cdb, next, alloc_len as u32, 0);
+            let data = self.read_cdb(cdb, alloc_len as u32, Some(next)).await?;
+            let before = next;
+            for mut extent in parse_get_lba_status(&data)? {
+                if extent.end() <= next || extent.lba >= end {
+                    continue;
+                }
+                // Clip to [next, end).
+                let start = extent.lba.max(next);
+                extent.blocks = (extent.end().min(end) - start) as u32;
+                extent.lba = start;
+                next = extent.end();
+                extents.push(extent);
+            }
+            if next == before {
+                return Err(
+                    anyhow!("GET LBA STATUS made no progress at LBA {next}").into()
+                );
+            }
+        }
+        Ok(extents)
+    }
+
+    /// VPD page `page`, clipped to the bytes that fit the allocation length.
+    pub(crate) async fn vpd_page(&self, page: VpdPage) -> Result<Vec<u8>> {
+        let mut cdb = [0u8; 16];
+        fill_inquiry_vpd_simple(&mut cdb, page, VPD_ALLOC_LEN);
+        let mut data = self.read_cdb(cdb, VPD_ALLOC_LEN as u32, None).await?;
+        clip_vpd_page_length(&mut data);
+        Ok(data)
+    }
+
+    pub(crate) async fn write_cdb(
+        &self,
+        cdb: [u8; 16],
+        data: Vec<u8>,
+        lba: Option<u64>,
+    ) -> Result<()> {
+        self.write_cdb_pi(cdb, None, &data, lba, None).await
+    }
+
+    pub(crate) async fn read_cdb(
+        &self,
+        cdb: [u8; 16],
+        len: u32,
+        lba: Option<u64>,
+    ) -> Result<Vec<u8>> {
+        self.read_cdb_pi(cdb, None, len, lba, None).await
+    }
+
+    /// [`Self::write_cdb`] interleaving PI tuples into `data` when
+    /// `protection` is set; `ext` carries bytes 16..32 of a 32-byte CDB.
+    async fn write_cdb_pi(
+        &self,
+        cdb: [u8; 16],
+        ext: Option<[u8; 16]>,
+        data: &[u8],
+        lba: Option<u64>,
+        protection: Option<PiParams>,
+    ) -> Result<()> {
+        let lun = self.lun;
+        let cdb = self.controlled(cdb);
+        let retry = self.retry;
+        self.execute(lba, |env| {
+            let mut ctx = WriteCtx::from_execute_env(env, lun, cdb, data.to_vec());
+            if let Some(ext) = ext {
+                ctx = ctx.with_cdb_extension(ext);
+            }
+            if !retry {
+                ctx = ctx.with_retry_safety(RetrySafety::Never);
+            }
+            match protection {
+                Some(p) => ctx.with_protection(p),
+                None => ctx,
+            }
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// [`Self::read_cdb`] checking and stripping PI tuples when
+    /// `protection` is set; `len` counts the tuples and `ext` carries bytes
+    /// 16..32 of a 32-byte CDB.
+    async fn read_cdb_pi(
+        &self,
+        cdb: [u8; 16],
+        ext: Option<[u8; 16]>,
+        len: u32,
+        lba: Option<u64>,
+        protection: Option<PiParams>,
+    ) -> Result<Vec<u8>> {
+        let lun = self.lun;
+        let cdb = self.controlled(cdb);
+        let retry = self.retry;
+        let outcome = self
+            .execute(lba, |env| {
+                let mut ctx = ReadCtx::from_execute_env(env, lun, len, cdb);
+                if let Some(ext) = ext {
+                    ctx = ctx.with_cdb_extension(ext);
+                }
+                if !retry {
+                    ctx = ctx.with_retry_safety(RetrySafety::Never);
+                }
+                match protection {
+                    Some(p) => ctx.with_protection(p),
+                    None => ctx,
+                }
+            })
+            .await?;
+        Ok(outcome.data)
+    }
+
+    /// Run a command on the handle's connection, or the one the pool picks
+    /// for `lba`.
+    async fn execute<Ctx, Res, Build>(
+        &self,
+        lba: Option<u64>,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        match self.cid {
+            Some(cid) => {
+                self.session
+                    .pool
+                    .execute_with_ctx(self.session.tsih, cid, build)
+                    .await
+            },
+            None => {
+                self.session
+                    .pool
+                    .execute_balanced(self.session.tsih, lba, build)
+                    .await
+            },
+        }
+    }
+
+    /// `cdb` with the handle's CONTROL settings, unless they are the
+    /// defaults (then the builder's CONTROL byte is kept).
+    fn controlled(&self, mut cdb: [u8; 16]) -> [u8; 16] {
+        if self.control != CdbControl::default() {
+            self.control.apply(&mut cdb);
+        }
+        cdb
+    }
+}
+
+/// Shrink the PAGE LENGTH of a VPD page cut off by the allocation length,
+/// so the descriptors that did arrive can still be parsed.
+fn clip_vpd_page_length(page: &mut [u8]) {
+    if page.len() < 4 {
+        return;
+    }
+    let present = u16::try_from(page.len() - 4).unwrap_or(u16::MAX);
+    if u16::from_be_bytes([page[2], page[3]]) > present {
+        page[2..4].copy_from_slice(&present.to_be_bytes());
+    }
+}
+
+pub(crate) fn check_range(cap: &Capacity, lba: u64, blocks: u64) -> Result<()> {
+    ensure!(
+        lba.checked_add(blocks).is_some_and(|end| end <= cap.blocks),
+        "LBA range {lba}+{blocks} is beyond the end of the LUN ({} blocks)",
+        cap.blocks
+    );
+    Ok(())
+}
+
+/// First LBA and NUMBER OF BLOCKS of a SYNCHRONIZE CACHE / PRE-FETCH over
+/// `lbas`, or `None` for an empty range. An open end, or one beyond what
+/// the 32-bit field holds, becomes 0: to the end of the medium.
+fn cache_range(lbas: impl RangeBounds<u64>) -> Option<(u64, u32)> {
+    let lba = match lbas.start_bound() {
+        Bound::Included(&lba) => lba,
+        Bound::Excluded(&lba) => lba.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match lbas.end_bound() {
+        Bound::Included(&end) => Some(end.saturating_add(1)),
+        Bound::Excluded(&end) => Some(end),
+        Bound::Unbounded => None,
+    };
+    match end {
+        Some(end) if end <= lba => None,
+        Some(end) => Some((lba, u32::try_from(end - lba).unwrap_or(0))),
+        None => Some((lba, 0)),
+    }
+}
+
+/// READ(10) when LBA and length fit, READ(16) otherwise.
+fn read_cdb(lba: u64, blocks: u32, flags: u8) -> [u8; 16] {
+    let mut cdb = [0u8; 16];
+    match (u32::try_from(lba), u16::try_from(blocks)) {
+        (Ok(lba), Ok(blocks)) => build_read10(&mut cdb, lba, blocks, flags, 0),
+        _ => build_read16(&mut cdb, lba, blocks, flags, 0),
+    }
+    cdb
+}
+
+/// READ with RDPROTECT=001b for `p`: READ(32) carrying the expected tags
+/// on a type 2 LUN, [`read_cdb`] otherwise. The second half of a 32-byte
+/// CDB goes in an Extended CDB AHS.
+fn read_cdb_pi(lba: u64, blocks: u32, p: &PiParams) -> ([u8; 16], Option<[u8; 16]>) {
+    let flags = protect_flags(PROTECT_CHECK_ALL);
+    if p.ptype != ProtectionType::Type2 {
+        return (read_cdb(lba, blocks, flags), None);
+    }
+    let mut cdb = [0u8; 32];
+    build_read32(&mut cdb, lba, blocks, flags, p.tags(), 0);
+    let (head, ext) = split_cdb32(&cdb);
+    (head, Some(ext))
+}
+
+/// Whether `blocks` blocks at `lba` need a 16-byte READ / WRITE that can be
+/// replaced by 10-byte ones: every segment must start below 2^32.
+fn splittable_to_cdb10(lba: u64, blocks: u32) -> bool {
+    let needs_cdb16 = u32::try_from(lba).is_err() || u16::try_from(blocks).is_err();
+    needs_cdb16 && lba + blocks as u64 <= u32::MAX as u64 + 1
+}
+
+/// `(lba, blocks)` ranges of at most `u16::MAX` blocks covering `blocks`
+/// blocks at `lba`, one per READ(10) / WRITE(10).
+fn cdb10_segments(lba: u64, blocks: u32) -> impl Iterator<Item = (u64, u32)> {
+    let step = u16::MAX as u32;
+    (0..blocks)
+        .step_by(step as usize)
+        .map(move |off| (lba + off as u64, step.min(blocks - off)))
+}
+
+/// Number of blocks in `len` bytes of `what` data, which must be whole.
+fn whole_blocks(cap: &Capacity, len: usize, what: &str) -> Result<u32> {
+    ensure!(
+        len.is_multiple_of(cap.block_size as usize),
+        "{what} of {len} bytes is not a multiple of the {}-byte block size",
+        cap.block_size
+    );
+    u32::try_from(len / cap.block_size as usize)
+        .with_context(|| format!("{what} length exceeds 2^32 blocks"))
+}
+
+fn verify_cdb(lba: u64, blocks: u32, bytchk: ByteCheck) -> [u8; 16] {
+    let mut cdb = [0u8; 16];
+    match (u32::try_from(lba), u16::try_from(blocks)) {
+        (Ok(lba), Ok(blocks)) => build_verify10(&mut cdb, lba, blocks, bytchk, 0, 0),
+        _ => build_verify16(&mut cdb, lba, blocks, bytchk, 0, 0),
+    }
+    cdb
+}
+
+fn write_verify_cdb(lba: u64, blocks: u32) -> [u8; 16] {
+    let mut cdb = [0u8; 16];
+    match (u32::try_from(lba), u16::try_from(blocks)) {
+        (Ok(lba), Ok(blocks)) => build_write_verify10(&mut cdb, lba, blocks, true, 0, 0),
+        _ => build_write_verify16(&mut cdb, lba, blocks, true, 0, 0),
+    }
+    cdb
+}
+
+/// WRITE(10) when LBA and length fit, WRITE(16) otherwise.
+fn write_cdb(lba: u64, blocks: u32, flags: u8) -> [u8; 16] {
+    let mut cdb = [0u8; 16];
+    match (u32::try_from(lba), u16::try_from(blocks)) {
+        (Ok(lba), Ok(blocks)) => build_write10(&mut cdb, lba, blocks, flags, 0),
+        _ => build_write16(&mut cdb, lba, blocks, flags, 0),
+    }
+    cdb
+}
+
+/// WRITE with WRPROTECT=001b for `p`, as [`read_cdb_pi`].
+fn write_cdb_pi(lba: u64, blocks: u32, p: &PiParams) -> ([u8; 16], Option<[u8; 16]>) {
+    let flags = protect_flags(PROTECT_CHECK_ALL);
+    if p.ptype != ProtectionType::Type2 {
+        return (write_cdb(lba, blocks, flags), None);
+    }
+    let mut cdb = [0u8; 32];
+    build_write32(&mut cdb, lba, blocks, flags, p.tags(), 0);
+    let (head, ext) = split_cdb32(&cdb);
+    (head, Some(ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clips_truncated_vpd_pages() {
+        let mut page = [0x00, 0x83, 0x01, 0x00, 0xAA, 0xBB];
+        clip_vpd_page_length(&mut page);
+        assert_eq!(page[2..4], [0, 2]);
+
+        let mut short = [0x00, 0x83, 0x00, 0x01, 0xAA, 0xBB];
+        clip_vpd_page_length(&mut short);
+        assert_eq!(short[2..4], [0, 1]);
+    }
+
+    #[test]
+    fn picks_16_byte_cdb_only_when_needed() {
+        assert_eq!(read_cdb(0, 8, 0)[0], 0x28);
+        assert_eq!(read_cdb(u32::MAX as u64 + 1, 8, 0)[0], 0x88);
+        assert_eq!(read_cdb(0, u16::MAX as u32 + 1, 0)[0], 0x88);
+        assert_eq!(write_cdb(100, 1, 0)[0], 0x2A);
+        assert_eq!(write_cdb(1 << 40, 1, 0)[0], 0x8A);
+        assert_eq!(verify_cdb(0, 8, ByteCheck::Medium)[0], 0x2F);
+        assert_eq!(verify_cdb(1 << 32, 8, ByteCheck::Compare)[0], 0x8F);
+        assert_eq!(write_verify_cdb(0, 8)[0], 0x2E);
+        assert_eq!(write_verify_cdb(0, 1 << 16)[0], 0x8E);
+    }
+
+    #[test]
+    fn type2_protection_uses_32_byte_cdbs() {
+        let p = PiParams::for_lba(ProtectionType::Type2, 512, 0, 0x1_0000_0010);
+        let (cdb, ext) = read_cdb_pi(0x1_0000_0010, 8, &p);
+        assert_eq!((cdb[0], cdb[7], cdb[9], cdb[10]), (0x7F, 0x18, 0x09, 0x20));
+        let ext = ext.expect("READ(32) extension");
+        assert_eq!(ext[4..8], 0x10u32.to_be_bytes());
+        assert_eq!(ext[12..16], 8u32.to_be_bytes());
+
+        let (cdb, ext) = write_cdb_pi(0, 8, &p);
+        assert_eq!(cdb[9], 0x0B);
+        assert!(ext.is_some());
+
+        let p = PiParams::for_lba(ProtectionType::Type1, 512, 0, 0);
+        let (cdb, ext) = write_cdb_pi(0, 8, &p);
+        assert_eq!((cdb[0], cdb[1], ext), (0x2A, 0x20, None));
+    }
+
+    #[test]
+    fn splits_into_10_byte_cdbs() {
+        assert!(!splittable_to_cdb10(0, 8));
+        assert!(splittable_to_cdb10(0, 0x2_0000));
+        assert!(!splittable_to_cdb10(u32::MAX as u64 + 1, 8));
+        assert!(!splittable_to_cdb10(u32::MAX as u64, 0x1_0000));
+
+        let segs: Vec<_> = cdb10_segments(10, 0x2_0000).collect();
+        assert_eq!(
+            segs,
+            [(10, 0xFFFF), (10 + 0xFFFF, 0xFFFF), (10 + 0x1_FFFE, 2)]
+        );
+        assert!(segs.iter().all(|&(lba, n)| read_cdb(lba, n, 0)[0] == 0x28));
+    }
+
+    #[test]
+    fn rejects_ranges_past_the_end() {
+        let cap = Capacity {
+            blocks: 100,
+            block_size: 512,
+        };
+        assert!(check_range(&cap, 90, 10).is_ok());
+        assert!(check_range(&cap, 90, 11).is_err());
+        assert!(check_range(&cap, u64::MAX, 2).is_err());
+        assert_eq!(cap.bytes(), 51_200);
+    }
+}
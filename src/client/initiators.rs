@@ -0,0 +1,217 @@
+//! Several independent initiators in one process.
+//!
+//! [`InitiatorFleet`] logs in N initiators from one [`Config`], each with its
+//! own InitiatorName, its own ISIDs and its own [`Pool`], so the target sees
+//! N hosts. It reproduces multi-host scenarios (reservation conflicts, ALUA
+//! transitions, write contention) in a single test binary;
+//! [`InitiatorFleet::run_each`] starts the same step on every initiator at
+//! once.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use tokio::{sync::Barrier, task::JoinSet};
+use tracing::{info, warn};
+
+use crate::{
+    cfg::config::Config,
+    client::{
+        handles::{LunHandle, SessionHandle},
+        pool_sessions::Pool,
+    },
+    error,
+    models::identifiers::{Isid, Lun, Tsih},
+};
+
+/// One initiator of an [`InitiatorFleet`].
+#[derive(Clone)]
+pub struct SimulatedInitiator {
+    /// Position in the fleet.
+    pub index: usize,
+    /// InitiatorName it logged in with.
+    pub name: String,
+    /// ISID of its first session; the others follow in the qualifier.
+    pub isid: Isid,
+    /// Pool holding its sessions.
+    pub pool: Arc<Pool>,
+    /// Its sessions, in configuration order.
+    pub tsihs: Vec<Tsih>,
+}
+
+impl fmt::Debug for SimulatedInitiator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimulatedInitiator")
+            .field("index", &self.index)
+            .field("name", &self.name)
+            .field("isid", &self.isid)
+            .field("tsihs", &self.tsihs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SimulatedInitiator {
+    /// Handle for the first session of this initiator.
+    pub fn session(&self) -> error::Result<SessionHandle> {
+        let tsih = *self.tsihs.first().context("initiator has no session")?;
+        self.pool.session(tsih)
+    }
+
+    /// Handle for `lun` on the first session of this initiator.
+    pub fn lun(&self, lun: Lun) -> error::Result<LunHandle> {
+        Ok(self.session()?.lun(lun))
+    }
+}
+
+/// N initiators logged in to the same target.
+#[derive(Debug)]
+pub struct InitiatorFleet {
+    initiators: Vec<SimulatedInitiator>,
+}
+
+impl InitiatorFleet {
+    /// Log in `count` initiators as described by `cfg`. The `index`-th one
+    /// is named by [`initiator_name`]; the ISIDs start at the configured
+    /// `Isid` (random when unset) and are kept apart through the qualifier,
+    /// `MaxSessions` per initiator. If a login fails, the initiators logged
+    /// in so far are shut down.
+    pub async fn login(cfg: &Config, count: usize) -> error::Result<Self> {
+        let base = match &cfg.login.identity.isid {
+            Some(isid) => isid.parse::<Isid>()?,
+            None => Isid::generate().0,
+        };
+        let per_initiator = cfg.runtime.max_sessions.max(1) as usize;
+        let mut fleet = Self {
+            initiators: Vec::with_capacity(count),
+        };
+        for index in 0..count {
+            let isid = base.with_qualifier_offset((index * per_initiator) as u16);
+            let mut cfg = cfg.clone();
+            cfg.login.identity.initiator_name =
+                initiator_name(&cfg.login.identity.initiator_name, index);
+            cfg.login.identity.isid = Some(isid.to_string());
+            let pool = Pool::new(&cfg);
+            match pool.login_sessions_from_cfg(&cfg).await {
+                Ok(tsihs) => {
+                    info!(
+                        "initiator {} logged in (ISID={isid}, {} sessions)",
+                        cfg.login.identity.initiator_name,
+                        tsihs.len()
+                    );
+                    fleet.initiators.push(SimulatedInitiator {
+                        index,
+                        name: cfg.login.identity.initiator_name,
+                        isid,
+                        pool,
+                        tsihs,
+                    });
+                },
+                Err(e) => {
+                    let _ = pool.shutdown_gracefully(Duration::from_secs(1)).await;
+                    fleet.shutdown(Duration::from_secs(1)).await;
+                    return Err(e);
+                },
+            }
+        }
+        Ok(fleet)
+    }
+
+    /// The initiators, by index.
+    #[inline]
+    pub fn initiators(&self) -> &[SimulatedInitiator] {
+        &self.initiators
+    }
+
+    /// One handle for `lun` per initiator, by index, e.g. for
+    /// [`run_workload`](crate::client::workload::run_workload).
+    pub fn luns(&self, lun: Lun) -> error::Result<Vec<LunHandle>> {
+        self.initiators.iter().map(|i| i.lun(lun)).collect()
+    }
+
+    /// Run `step` on every initiator concurrently with its handle for
+    /// `lun`. The steps are released together once all of them are
+    /// spawned. Returns each initiator's result, by index.
+    pub async fn run_each<F, Fut, T>(&self, lun: Lun, step: F) -> Vec<error::Result<T>>
+    where
+        F: Fn(usize, LunHandle) -> Fut,
+        Fut: Future<Output = error::Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut results: Vec<Option<error::Result<T>>> =
+            self.initiators.iter().map(|_| None).collect();
+        let start = Arc::new(Barrier::new(self.initiators.len()));
+        let mut tasks = JoinSet::new();
+        for initiator in &self.initiators {
+            let index = initiator.index;
+            match initiator.lun(lun) {
+                Ok(handle) => {
+                    let (start, fut) = (Arc::clone(&start), step(index, handle));
+                    tasks.spawn(async move {
+                        start.wait().await;
+                        (index, fut.await)
+                    });
+                },
+                Err(e) => {
+                    // Keep the barrier count: release the others anyway.
+                    let start = Arc::clone(&start);
+                    tasks.spawn(async move {
+                        start.wait().await;
+                        (index, Err(e))
+                    });
+                },
+            }
+        }
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((index, res)) => results[index] = Some(res),
+                Err(e) => warn!("initiator step panicked: {e}"),
+            }
+        }
+        results
+            .into_iter()
+            .map(|r| {
+                r.unwrap_or_else(|| {
+                    Err(error::IscsiError::msg("initiator step panicked"))
+                })
+            })
+            .collect()
+    }
+
+    /// Log out every initiator; failures are logged, not returned.
+    pub async fn shutdown(self, max_wait_per_conn: Duration) {
+        for initiator in self.initiators {
+            if let Err(e) = initiator.pool.shutdown_gracefully(max_wait_per_conn).await {
+                warn!("shutdown of initiator {} failed: {e}", initiator.name);
+            }
+        }
+    }
+}
+
+/// InitiatorName of the `index`-th simulated initiator: `base` with
+/// `-<index>` appended. That keeps an IQN valid, but not an `eui.` or
+/// `naa.` name, which is a fixed number of hex digits; use an IQN base.
+pub fn initiator_name(base: &str, index: usize) -> String {
+    format!("{base}-{index}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initiators_get_distinct_names_and_isids() {
+        let base: Isid = "400001370000".parse().expect("valid ISID");
+        assert_eq!(
+            initiator_name("iqn.2004-10.com.example:host", 2),
+            "iqn.2004-10.com.example:host-2"
+        );
+        let second = base.with_qualifier_offset(2);
+        assert_eq!(second.to_string(), "400001370002");
+        assert_eq!(
+            second.to_string().parse::<Isid>().expect("round trip"),
+            second
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! LUN inventory of a session.
+//!
+//! [`Pool::report_luns`] sends REPORT LUNS to LUN 0 of a session and keeps
+//! the list on the session; [`Pool::cached_luns`] returns the last list
+//! without a round trip. [`Pool::enumerate_luns`] adds INQUIRY and READ
+//! CAPACITY data for every reported LUN. The capacity of each LUN read
+//! through a [`LunHandle`](crate::client::handles::LunHandle) is cached on
+//! the session as well.
+//!
+//! Both caches follow the target's Unit Attentions. REPORTED LUNS DATA HAS
+//! CHANGED marks the list stale and re-reads it in the background; every
+//! refresh that changes a known list publishes [`PoolEvent::LunAdded`] and
+//! [`PoolEvent::LunRemoved`]. CAPACITY DATA HAS CHANGED re-reads the
+//! capacity of the LUN that reported it and publishes
+//! [`PoolEvent::CapacityChanged`].
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::{
+        RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    client::{
+        events::PoolEvent,
+        handles::Capacity,
+        pool_sessions::Pool,
+        retry::{SENSE_KEY_UNIT_ATTENTION, SenseMatch},
+    },
+    control_block::{
+        inquiry::InquiryStandard,
+        protection::ProtectionFormat,
+        report_luns::{
+            REPORT_LUNS_HEADER_LEN, fill_report_luns, parse_lun_list_length,
+            parse_report_luns, select_report,
+        },
+    },
+    error,
+    models::identifiers::{Lun, Tsih},
+    state_machine::{common::ScsiStatusError, read_states::ReadCtx},
+};
+
+/// Unit Attention REPORTED LUNS DATA HAS CHANGED (ASC/ASCQ 3Fh/0Eh).
+pub const UA_REPORTED_LUNS_DATA_CHANGED: SenseMatch =
+    SenseMatch::exact(SENSE_KEY_UNIT_ATTENTION, 0x3F, 0x0E);
+
+/// Unit Attention CAPACITY DATA HAS CHANGED (ASC/ASCQ 2Ah/09h).
+pub const UA_CAPACITY_DATA_CHANGED: SenseMatch =
+    SenseMatch::exact(SENSE_KEY_UNIT_ATTENTION, 0x2A, 0x09);
+
+/// Allocation length of the first REPORT LUNS: the header and 255 entries.
+/// A longer list is read again with the length the target reported.
+const REPORT_LUNS_INITIAL_ALLOC: u32 = (REPORT_LUNS_HEADER_LEN + 255 * 8) as u32;
+
+/// Peripheral device types that answer READ CAPACITY: direct access (SBC),
+/// write-once, CD/DVD (MMC), optical memory and RBC.
+const BLOCK_DEVICE_TYPES: [u8; 5] = [0x00, 0x04, 0x05, 0x07, 0x0E];
+
+/// One logical unit as seen by [`Pool::enumerate_luns`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LunInfo {
+    /// Logical unit number.
+    pub lun: Lun,
+    /// Peripheral qualifier; non-zero when no device is connected to the LUN.
+    pub peripheral_qualifier: u8,
+    /// Peripheral device type (0x00 = disk, 0x01 = tape, ...).
+    pub device_type: u8,
+    /// Removable medium.
+    pub removable: bool,
+    /// T10 vendor identification.
+    pub vendor: String,
+    /// Product identification.
+    pub product: String,
+    /// Product revision level.
+    pub revision: String,
+    /// Size from READ CAPACITY; `None` for LUNs that are not
+    /// block-addressed or have no device connected.
+    pub capacity: Option<Capacity>,
+}
+
+impl LunInfo {
+    fn new(lun: Lun, inquiry: InquiryStandard, capacity: Option<Capacity>) -> Self {
+        Self {
+            lun,
+            peripheral_qualifier: inquiry.peripheral_qualifier,
+            device_type: inquiry.device_type,
+            removable: inquiry.rmb,
+            vendor: inquiry.vendor_id,
+            product: inquiry.product_id,
+            revision: inquiry.product_rev,
+            capacity,
+        }
+    }
+
+    /// Whether the LUN is block-addressed and READ CAPACITY applies.
+    #[inline]
+    pub fn is_block_device(&self) -> bool {
+        self.peripheral_qualifier == 0 && BLOCK_DEVICE_TYPES.contains(&self.device_type)
+    }
+
+    /// Logical block length in bytes, if known.
+    #[inline]
+    pub fn block_size(&self) -> Option<u32> {
+        self.capacity.map(|c| c.block_size)
+    }
+
+    /// Number of logical blocks, if known.
+    #[inline]
+    pub fn blocks(&self) -> Option<u64> {
+        self.capacity.map(|c| c.blocks)
+    }
+}
+
+/// LUN state the pool keeps for a session.
+#[derive(Debug, Default)]
+pub(crate) struct LunInventory {
+    luns: RwLock<Option<Vec<Lun>>>,
+    /// The target reported that the cached list is out of date.
+    stale: AtomicBool,
+    capacities: RwLock<HashMap<Lun, Capacity>>,
+    /// Protection information format from READ CAPACITY(16), per LUN.
+    protection: RwLock<HashMap<Lun, Option<ProtectionFormat>>>,
+    /// LBAs that failed with MEDIUM ERROR, per LUN.
+    bad_blocks: RwLock<HashMap<Lun, BTreeSet<u64>>>,
+    /// LUNs that rejected READ(16) / WRITE(16) as an invalid opcode.
+    no_cdb16: RwLock<HashSet<Lun>>,
+}
+
+impl LunInventory {
+    /// Cached LUN list, unless none was read yet or it went stale.
+    pub(crate) fn luns(&self) -> Option<Vec<Lun>> {
+        if self.stale.load(Ordering::Acquire) {
+            return None;
+        }
+        self.luns.read().expect("LUN inventory lock").clone()
+    }
+
+    /// Store a fresh list and return the previous one, stale or not.
+    pub(crate) fn replace(&self, luns: Vec<Lun>) -> Option<Vec<Lun>> {
+        let previous = self.luns.write().expect("LUN inventory lock").replace(luns);
+        self.stale.store(false, Ordering::Release);
+        previous
+    }
+
+    /// Mark the cached list stale; returns whether there is one to refresh.
+    pub(crate) fn invalidate(&self) -> bool {
+        self.stale.store(true, Ordering::Release);
+        self.luns.read().expect("LUN inventory lock").is_some()
+    }
+
+    pub(crate) fn capacity(&self, lun: Lun) -> Option<Capacity> {
+        self.capacities
+            .read()
+            .expect("LUN inventory lock")
+            .get(&lun)
+            .copied()
+    }
+
+    /// Store the capacity of `lun` and return the previous one.
+    pub(crate) fn set_capacity(&self, lun: Lun, capacity: Capacity) -> Option<Capacity> {
+        self.capacities
+            .write()
+            .expect("LUN inventory lock")
+            .insert(lun, capacity)
+    }
+
+    /// Protection format of `lun`; `None` until READ CAPACITY(16) was read.
+    pub(crate) fn protection(&self, lun: Lun) -> Option<Option<ProtectionFormat>> {
+        self.protection
+            .read()
+            .expect("LUN inventory lock")
+            .get(&lun)
+            .copied()
+    }
+
+    pub(crate) fn set_protection(&self, lun: Lun, format: Option<ProtectionFormat>) {
+        self.protection
+            .write()
+            .expect("LUN inventory lock")
+            .insert(lun, format);
+    }
+
+    pub(crate) fn bad_blocks(&self, lun: Lun) -> Vec<u64> {
+        self.bad_blocks
+            .read()
+            .expect("LUN inventory lock")
+            .get(&lun)
+            .map(|lbas| lbas.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record a MEDIUM ERROR at `lba` of `lun`.
+    pub(crate) fn note_bad_block(&self, lun: Lun, lba: u64) {
+        self.bad_blocks
+            .write()
+            .expect("LUN inventory lock")
+            .entry(lun)
+            .or_default()
+            .insert(lba);
+    }
+
+    pub(crate) fn cdb16_unsupported(&self, lun: Lun) -> bool {
+        self.no_cdb16
+            .read()
+            .expect("LUN inventory lock")
+            .contains(&lun)
+    }
+
+    /// Record that `lun` rejected a 16-byte READ / WRITE.
+    pub(crate) fn note_cdb16_unsupported(&self, lun: Lun) {
+        self.no_cdb16
+            .write()
+            .expect("LUN inventory lock")
+            .insert(lun);
+    }
+}
+
+/// Events describing the change from `previous` to `current`: removals in
+/// the old order, then additions in the new one.
+fn lun_changes(tsih: Tsih, previous: &[Lun], current: &[Lun]) -> Vec<PoolEvent> {
+    let removed = previous
+        .iter()
+        .filter(|lun| !current.contains(lun))
+        .map(|&lun| PoolEvent::LunRemoved { tsih, lun });
+    let added = current
+        .iter()
+        .filter(|lun| !previous.contains(lun))
+        .map(|&lun| PoolEvent::LunAdded { tsih, lun });
+    removed.chain(added).collect()
+}
+
+impl Pool {
+    /// Logical units of session `tsih`, in the order the target reports
+    /// them (REPORT LUNS, SELECT REPORT = all logical units).
+    ///
+    /// The first request asks for up to 255 LUNs; when the target has more,
+    /// it is repeated with the full LUN LIST LENGTH. The result replaces the
+    /// list cached on the session; LUNs that appeared or disappeared since
+    /// the previous list are published as [`PoolEvent::LunAdded`] and
+    /// [`PoolEvent::LunRemoved`].
+    pub async fn report_luns(&self, tsih: Tsih) -> error::Result<Vec<Lun>> {
+        let mut data = self
+            .report_luns_data(tsih, REPORT_LUNS_INITIAL_ALLOC)
+            .await?;
+        let needed =
+            parse_lun_list_length(&data)?.saturating_add(REPORT_LUNS_HEADER_LEN as u32);
+        if needed > REPORT_LUNS_INITIAL_ALLOC {
+            data = self.report_luns_data(tsih, needed).await?;
+        }
+        let luns = parse_report_luns(&data)?;
+
+        let previous = self
+            .sessions
+            .get(&tsih)
+            .and_then(|session| session.inventory.replace(luns.clone()));
+        for event in lun_changes(tsih, previous.as_deref().unwrap_or(&luns), &luns) {
+            info!("TSIH={tsih}: {event:?}");
+            self.emit_event(event);
+        }
+        Ok(luns)
+    }
+
+    /// LUN list of session `tsih` from its last [`Pool::report_luns`], if
+    /// any and not reported as changed since.
+    pub fn cached_luns(&self, tsih: Tsih) -> Option<Vec<Lun>> {
+        self.sessions.get(&tsih)?.inventory.luns()
+    }
+
+    /// Every LUN of session `tsih` with its INQUIRY data and, for
+    /// block-addressed LUNs, its capacity (READ CAPACITY(10), then (16) when
+    /// the LUN is too large).
+    ///
+    /// Each LUN gets a TEST UNIT READY first so a pending Unit Attention
+    /// (e.g. the one following login) does not fail the INQUIRY. The LUN
+    /// list is refreshed as by [`Pool::report_luns`].
+    pub async fn enumerate_luns(&self, tsih: Tsih) -> error::Result<Vec<LunInfo>> {
+        let session = self.session(tsih)?;
+        let luns = self.report_luns(tsih).await?;
+        let mut infos = Vec::with_capacity(luns.len());
+        for lun in luns {
+            let handle = session.lun(lun);
+            let _ = handle.test_unit_ready().await;
+            let mut info = LunInfo::new(lun, handle.inquiry().await?, None);
+            if info.is_block_device() {
+                info.capacity = Some(handle.read_capacity().await?);
+            }
+            infos.push(info);
+        }
+        Ok(infos)
+    }
+
+    /// Record the capacity of (`tsih`, `lun`) read by READ CAPACITY and
+    /// publish [`PoolEvent::CapacityChanged`] when it differs from the
+    /// cached one.
+    pub(crate) fn update_capacity(&self, tsih: Tsih, lun: Lun, capacity: Capacity) {
+        let Some(previous) = self
+            .sessions
+            .get(&tsih)
+            .and_then(|session| session.inventory.set_capacity(lun, capacity))
+        else {
+            return;
+        };
+        if previous != capacity {
+            info!(
+                "TSIH={tsih}, {lun}: capacity changed from {previous:?} to {capacity:?}"
+            );
+            self.emit_event(PoolEvent::CapacityChanged {
+                tsih,
+                lun,
+                previous,
+                capacity,
+            });
+        }
+    }
+
+    /// React to the Unit Attention of a failed command on `lun`: REPORTED
+    /// LUNS DATA HAS CHANGED invalidates the session's LUN list and, when one
+    /// was cached, re-reads it in the background; CAPACITY DATA HAS CHANGED
+    /// re-reads the capacity of `lun` when it was cached.
+    pub(crate) fn note_unit_attention(
+        &self,
+        tsih: Tsih,
+        lun: Option<Lun>,
+        error: &anyhow::Error,
+    ) {
+        let Some((key, asc, ascq)) = error
+            .downcast_ref::<ScsiStatusError>()
+            .and_then(ScsiStatusError::sense_codes)
+        else {
+            return;
+        };
+        let Some(session) = self.sessions.get(&tsih) else {
+            return;
+        };
+        let refresh_luns = UA_REPORTED_LUNS_DATA_CHANGED.matches(key, asc, ascq)
+            && session.inventory.invalidate();
+        let refresh_capacity = lun.filter(|&lun| {
+            UA_CAPACITY_DATA_CHANGED.matches(key, asc, ascq)
+                && session.inventory.capacity(lun).is_some()
+        });
+        drop(session);
+        if !refresh_luns && refresh_capacity.is_none() {
+            return;
+        }
+        let Ok(handle) = self.session(tsih) else {
+            return;
+        };
+        if refresh_luns {
+            info!("TSIH={tsih}: REPORTED LUNS DATA HAS CHANGED, refreshing the LUN list");
+            let handle = handle.clone();
+            self.spawner().spawn(Box::pin(async move {
+                if let Err(e) = handle.pool().report_luns(tsih).await {
+                    warn!("TSIH={tsih}: LUN list refresh failed: {e}");
+                }
+            }));
+        }
+        if let Some(lun) = refresh_capacity {
+            info!(
+                "TSIH={tsih}, {lun}: CAPACITY DATA HAS CHANGED, refreshing the capacity"
+            );
+            self.spawner().spawn(Box::pin(async move {
+                if let Err(e) = handle.lun(lun).read_capacity().await {
+                    warn!("TSIH={tsih}, {lun}: capacity refresh failed: {e}");
+                }
+            }));
+        }
+    }
+
+    async fn report_luns_data(
+        &self,
+        tsih: Tsih,
+        alloc_len: u32,
+    ) -> error::Result<Vec<u8>> {
+        let mut cdb = [0u8; 16];
+        fill_report_luns(&mut cdb, select_report::ALL, alloc_len, 0);
+        let outcome = self
+            .execute_balanced(tsih, None, |env| {
+                ReadCtx::from_execute_env(env, Lun::ZERO, alloc_len, cdb)
+            })
+            .await?;
+        Ok(outcome.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_lists_are_hidden_until_replaced() {
+        let inventory = LunInventory::default();
+        assert!(!inventory.invalidate());
+        assert_eq!(inventory.replace(vec![Lun::ZERO]), None);
+        assert_eq!(inventory.luns(), Some(vec![Lun::ZERO]));
+
+        assert!(inventory.invalidate());
+        assert_eq!(inventory.luns(), None);
+        let one = Lun::from_number(1);
+        assert_eq!(inventory.replace(vec![one]), Some(vec![Lun::ZERO]));
+        assert_eq!(inventory.luns(), Some(vec![one]));
+    }
+
+    #[test]
+    fn caches_capacity_per_lun() {
+        let inventory = LunInventory::default();
+        let one = Lun::from_number(1);
+        let small = Capacity {
+            blocks: 8,
+            block_size: 512,
+        };
+        let large = Capacity {
+            blocks: 16,
+            ..small
+        };
+        assert_eq!(inventory.set_capacity(one, small), None);
+        assert_eq!(inventory.capacity(one), Some(small));
+        assert_eq!(inventory.capacity(Lun::ZERO), None);
+        assert_eq!(inventory.set_capacity(one, large), Some(small));
+        assert_eq!(inventory.capacity(one), Some(large));
+    }
+
+    #[test]
+    fn diffs_lun_lists() {
+        let tsih = Tsih::new(1);
+        let [a, b, c] = [0u16, 1, 2].map(Lun::from_number);
+        assert_eq!(lun_changes(tsih, &[a, b], &[a, b]), []);
+        assert_eq!(
+            lun_changes(tsih, &[a, b], &[c, a]),
+            [
+                PoolEvent::LunRemoved { tsih, lun: b },
+                PoolEvent::LunAdded { tsih, lun: c },
+            ]
+        );
+    }
+}
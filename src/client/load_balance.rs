@@ -0,0 +1,188 @@
+//! Spreading commands over the connections of an MC/S session.
+//!
+//! When the caller does not pin a CID, the
+//! [`Pool`](crate::client::pool_sessions::Pool) asks a [`ConnectionSelector`]
+//! which connection of the session should carry the command. The built-in
+//! selectors are chosen with `runtime.LoadBalance`; applications can install
+//! their own with
+//! [`Pool::set_load_balancer`](crate::client::pool_sessions::Pool::set_load_balancer).
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    fmt::Debug,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::identifiers::{Cid, Tsih};
+
+/// Built-in connection selection strategies.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BalanceStrategy {
+    /// Each command goes to the next connection in turn.
+    #[default]
+    #[serde(rename = "RoundRobin")]
+    RoundRobin,
+    /// The connection with the fewest commands in flight.
+    #[serde(rename = "LeastInFlight")]
+    LeastInFlight,
+    /// Commands touching the same LBA stripe share a connection, which keeps
+    /// sequential streams ordered on one TCP connection.
+    #[serde(rename = "LbaAffinity")]
+    LbaAffinity,
+}
+
+/// Connection selection of the pool (`runtime.LoadBalance`).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LoadBalancePolicy {
+    #[serde(rename = "Strategy")]
+    /// Selection strategy; `RoundRobin` by default.
+    pub strategy: BalanceStrategy,
+
+    #[serde(rename = "AffinityStripeBlocks")]
+    /// Blocks per stripe for `LbaAffinity`.
+    pub affinity_stripe_blocks: u64,
+}
+
+impl Default for LoadBalancePolicy {
+    fn default() -> Self {
+        Self {
+            strategy: BalanceStrategy::default(),
+            affinity_stripe_blocks: 2048,
+        }
+    }
+}
+
+impl LoadBalancePolicy {
+    /// Selector implementing this policy.
+    pub fn selector(&self) -> Arc<dyn ConnectionSelector> {
+        match self.strategy {
+            BalanceStrategy::RoundRobin => Arc::new(RoundRobin::default()),
+            BalanceStrategy::LeastInFlight => Arc::new(LeastInFlight),
+            BalanceStrategy::LbaAffinity => {
+                Arc::new(LbaAffinity::new(self.affinity_stripe_blocks))
+            },
+        }
+    }
+}
+
+/// A connection eligible to carry a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionLoad {
+    /// Connection ID.
+    pub cid: Cid,
+    /// Commands currently executing on the connection.
+    pub in_flight: usize,
+}
+
+/// Picks the connection for a command that was not pinned to a CID.
+pub trait ConnectionSelector: Send + Sync + Debug {
+    /// Chooses one of `candidates` (never empty, sorted by CID) for a command
+    /// on session `tsih`. `lba` is the first block the command touches, when
+    /// the caller knows it. Returning a CID that is not a candidate fails the
+    /// command.
+    fn select(&self, tsih: Tsih, candidates: &[ConnectionLoad], lba: Option<u64>) -> Cid;
+}
+
+/// Cycles through the connections.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl ConnectionSelector for RoundRobin {
+    fn select(&self, _: Tsih, candidates: &[ConnectionLoad], _: Option<u64>) -> Cid {
+        let turn = self.next.fetch_add(1, Ordering::Relaxed);
+        candidates[turn % candidates.len()].cid
+    }
+}
+
+/// Prefers the connection with the fewest commands in flight, the lowest CID
+/// on ties.
+#[derive(Debug, Default)]
+pub struct LeastInFlight;
+
+impl ConnectionSelector for LeastInFlight {
+    fn select(&self, _: Tsih, candidates: &[ConnectionLoad], _: Option<u64>) -> Cid {
+        candidates
+            .iter()
+            .min_by_key(|c| c.in_flight)
+            .map_or(candidates[0].cid, |c| c.cid)
+    }
+}
+
+/// Maps LBA stripes onto connections; commands without an LBA fall back to
+/// [`LeastInFlight`].
+#[derive(Debug)]
+pub struct LbaAffinity {
+    stripe_blocks: u64,
+}
+
+impl LbaAffinity {
+    /// `stripe_blocks` consecutive blocks go to the same connection.
+    pub fn new(stripe_blocks: u64) -> Self {
+        Self {
+            stripe_blocks: stripe_blocks.max(1),
+        }
+    }
+}
+
+impl ConnectionSelector for LbaAffinity {
+    fn select(&self, tsih: Tsih, candidates: &[ConnectionLoad], lba: Option<u64>) -> Cid {
+        match lba {
+            Some(lba) => {
+                let stripe = lba / self.stripe_blocks;
+                candidates[(stripe % candidates.len() as u64) as usize].cid
+            },
+            None => LeastInFlight.select(tsih, candidates, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loads(in_flight: &[usize]) -> Vec<ConnectionLoad> {
+        in_flight
+            .iter()
+            .enumerate()
+            .map(|(i, &in_flight)| ConnectionLoad {
+                cid: Cid::new(i as u16),
+                in_flight,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_cycles() {
+        let rr = RoundRobin::default();
+        let c = loads(&[0, 0, 0]);
+        let picks: Vec<_> = (0..4).map(|_| rr.select(Tsih::NONE, &c, None)).collect();
+        assert_eq!(picks, [Cid::new(0), Cid::new(1), Cid::new(2), Cid::new(0)]);
+    }
+
+    #[test]
+    fn least_in_flight_prefers_idle_connection() {
+        let c = loads(&[3, 1, 1]);
+        assert_eq!(LeastInFlight.select(Tsih::NONE, &c, None), Cid::new(1));
+    }
+
+    #[test]
+    fn lba_affinity_keeps_stripe_on_one_connection() {
+        let sel = LbaAffinity::new(100);
+        let c = loads(&[0, 5]);
+        assert_eq!(sel.select(Tsih::NONE, &c, Some(0)), Cid::new(0));
+        assert_eq!(sel.select(Tsih::NONE, &c, Some(99)), Cid::new(0));
+        assert_eq!(sel.select(Tsih::NONE, &c, Some(100)), Cid::new(1));
+        assert_eq!(sel.select(Tsih::NONE, &c, Some(250)), Cid::new(0));
+        assert_eq!(sel.select(Tsih::NONE, &c, None), Cid::new(0));
+    }
+}
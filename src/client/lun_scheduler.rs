@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use dashmap::DashMap;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use crate::models::identifiers::{Lun, Tsih};
+
+/// Caps outstanding commands per (session, LUN).
+///
+/// Every (TSIH, LUN) pair gets its own semaphore with `depth` permits. Tokio
+/// semaphores queue waiters in FIFO order, so excess submissions are admitted
+/// fairly across callers instead of racing onto the wire. A depth of `0`
+/// disables the depth limit.
+///
+/// While an ACA condition is established on a LUN, new submissions are held
+/// before taking a slot until the condition is cleared; the target would
+/// only reject them with ACA ACTIVE.
+#[derive(Debug)]
+pub(crate) struct LunScheduler {
+    depth: usize,
+    queues: DashMap<(Tsih, Lun), Arc<Semaphore>>,
+    /// LUNs with an established ACA; the token is cancelled on CLEAR ACA.
+    aca: DashMap<(Tsih, Lun), CancellationToken>,
+    /// LUNs fenced by an outstanding TMF (TaskReporting=ResponseFence).
+    fences: DashMap<(Tsih, Lun), CancellationToken>,
+}
+
+impl LunScheduler {
+    pub(crate) fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            queues: DashMap::new(),
+            aca: DashMap::new(),
+            fences: DashMap::new(),
+        }
+    }
+
+    /// Configured depth; `0` means unlimited.
+    #[inline]
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Wait for a free slot on (TSIH, LUN). Returns `None` when the depth
+    /// limit is disabled. The slot is released when the permit drops.
+    /// `bypass_aca` lets ACA-attributed commands through an established ACA.
+    pub(crate) async fn acquire(
+        &self,
+        tsih: Tsih,
+        lun: Lun,
+        bypass_aca: bool,
+        cancel: &CancellationToken,
+    ) -> Result<Option<OwnedSemaphorePermit>> {
+        while !bypass_aca
+            && let Some(cleared) = self.aca.get(&(tsih, lun)).map(|e| e.clone())
+        {
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    bail!("cancelled while held by ACA on TSIH={tsih}, {lun}")
+                },
+                _ = cleared.cancelled() => {},
+            }
+        }
+        while let Some(lifted) = self.fences.get(&(tsih, lun)).map(|e| e.clone()) {
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    bail!("cancelled while fenced by TMF on TSIH={tsih}, {lun}")
+                },
+                _ = lifted.cancelled() => {},
+            }
+        }
+        if self.depth == 0 {
+            return Ok(None);
+        }
+
+        let sem = self
+            .queues
+            .entry((tsih, lun))
+            .or_insert_with(|| Arc::new(Semaphore::new(self.depth)))
+            .clone();
+
+        tokio::select! {
+            _ = cancel.cancelled() => bail!("cancelled while queued on TSIH={tsih}, {lun}"),
+            permit = sem.acquire_owned() => Ok(Some(permit?)),
+        }
+    }
+
+    /// Number of commands currently holding a slot on (TSIH, LUN).
+    pub(crate) fn in_flight(&self, tsih: Tsih, lun: Lun) -> usize {
+        self.queues
+            .get(&(tsih, lun))
+            .map_or(0, |sem| self.depth.saturating_sub(sem.available_permits()))
+    }
+
+    /// Hold new submissions on (TSIH, LUN). Returns `false` if ACA was
+    /// already established.
+    pub(crate) fn establish_aca(&self, tsih: Tsih, lun: Lun) -> bool {
+        let mut fresh = false;
+        self.aca.entry((tsih, lun)).or_insert_with(|| {
+            fresh = true;
+            CancellationToken::new()
+        });
+        fresh
+    }
+
+    /// Release submissions held by ACA on (TSIH, LUN).
+    pub(crate) fn clear_aca(&self, tsih: Tsih, lun: Lun) {
+        if let Some((_, cleared)) = self.aca.remove(&(tsih, lun)) {
+            cleared.cancel();
+        }
+    }
+
+    /// Fence new submissions on (TSIH, LUN) while a TMF is outstanding.
+    /// Returns `false` if another TMF already holds the fence.
+    pub(crate) fn fence(&self, tsih: Tsih, lun: Lun) -> bool {
+        let mut fresh = false;
+        self.fences.entry((tsih, lun)).or_insert_with(|| {
+            fresh = true;
+            CancellationToken::new()
+        });
+        fresh
+    }
+
+    /// Lift a fence set by [`LunScheduler::fence`].
+    pub(crate) fn unfence(&self, tsih: Tsih, lun: Lun) {
+        if let Some((_, lifted)) = self.fences.remove(&(tsih, lun)) {
+            lifted.cancel();
+        }
+    }
+
+    /// Whether an ACA condition is tracked on (TSIH, LUN).
+    pub(crate) fn is_aca_active(&self, tsih: Tsih, lun: Lun) -> bool {
+        self.aca.contains_key(&(tsih, lun))
+    }
+
+    /// Drop all queues that belong to a session that left the pool.
+    pub(crate) fn forget_session(&self, tsih: Tsih) {
+        self.queues.retain(|(t, _), _| *t != tsih);
+        for holds in [&self.aca, &self.fences] {
+            holds.retain(|(t, _), released| {
+                if *t == tsih {
+                    released.cancel();
+                }
+                *t != tsih
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn caps_in_flight_per_lun() -> Result<()> {
+        let sched = LunScheduler::new(2);
+        let cancel = CancellationToken::new();
+        let (tsih, lun) = (Tsih::new(1), Lun::new(0));
+
+        let a = sched.acquire(tsih, lun, false, &cancel).await?;
+        let _b = sched.acquire(tsih, lun, false, &cancel).await?;
+        assert_eq!(sched.in_flight(tsih, lun), 2);
+
+        // A different LUN has its own queue.
+        let _other = sched.acquire(tsih, Lun::new(1), false, &cancel).await?;
+
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(20),
+            sched.acquire(tsih, lun, false, &cancel),
+        )
+        .await;
+        assert!(blocked.is_err(), "third command must wait for a free slot");
+
+        drop(a);
+        let _c = sched.acquire(tsih, lun, false, &cancel).await?;
+        assert_eq!(sched.in_flight(tsih, lun), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn aca_holds_submissions_until_cleared() -> Result<()> {
+        let sched = LunScheduler::new(0);
+        let cancel = CancellationToken::new();
+        let (tsih, lun) = (Tsih::new(1), Lun::new(0));
+
+        assert!(sched.establish_aca(tsih, lun));
+        assert!(!sched.establish_aca(tsih, lun));
+        let held = tokio::time::timeout(
+            Duration::from_millis(20),
+            sched.acquire(tsih, lun, false, &cancel),
+        )
+        .await;
+        assert!(held.is_err(), "submission must wait while ACA is active");
+
+        // ACA-attributed commands (e.g. the failed task's recovery) pass.
+        sched.acquire(tsih, lun, true, &cancel).await?;
+        // Other LUNs are not affected.
+        sched.acquire(tsih, Lun::new(1), false, &cancel).await?;
+
+        sched.clear_aca(tsih, lun);
+        assert!(!sched.is_aca_active(tsih, lun));
+        sched.acquire(tsih, lun, false, &cancel).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tmf_fence_holds_even_aca_commands() -> Result<()> {
+        let sched = LunScheduler::new(0);
+        let cancel = CancellationToken::new();
+        let (tsih, lun) = (Tsih::new(1), Lun::new(0));
+
+        assert!(sched.fence(tsih, lun));
+        assert!(!sched.fence(tsih, lun));
+        let held = tokio::time::timeout(
+            Duration::from_millis(20),
+            sched.acquire(tsih, lun, true, &cancel),
+        )
+        .await;
+        assert!(held.is_err(), "fence must hold until the TMF completes");
+
+        sched.unfence(tsih, lun);
+        sched.acquire(tsih, lun, true, &cancel).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn zero_depth_is_unlimited() -> Result<()> {
+        let sched = LunScheduler::new(0);
+        let cancel = CancellationToken::new();
+        assert!(
+            sched
+                .acquire(Tsih::new(1), Lun::ZERO, false, &cancel)
+                .await?
+                .is_none()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+//! CD / DVD / BD devices (MMC), e.g. ISO images exported by LIO.
+//!
+//! [`LunHandle::read_toc`], [`LunHandle::get_configuration`] and
+//! [`LunHandle::read_disc_information`] describe the medium;
+//! [`LunHandle::read_sectors`] reads 2048-byte user data sectors with
+//! READ(10), without the READ CAPACITY the block helpers start with.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::{
+        mmc::{
+            Configuration, ConfigurationRequest, DISC_INFORMATION_LEN, DiscInformation,
+            MMC_SECTOR_LEN, TOC_DESCRIPTOR_LEN, TOC_HEADER_LEN, Toc,
+            build_get_configuration, build_read_disc_information, build_read_toc,
+            parse_configuration, parse_configuration_length, parse_disc_information,
+            parse_toc,
+        },
+        read::build_read10,
+    },
+    error,
+};
+
+/// Allocation length of READ TOC: 99 tracks and the lead-out.
+const TOC_ALLOC_LEN: u16 = (TOC_HEADER_LEN + 100 * TOC_DESCRIPTOR_LEN) as u16;
+
+/// First allocation length of GET CONFIGURATION; re-read with the reported
+/// length when the target has more.
+const CONFIGURATION_ALLOC_LEN: u16 = 4096;
+
+impl LunHandle {
+    /// Formatted TOC with LBA track addresses.
+    pub async fn read_toc(&self) -> error::Result<Toc> {
+        let mut cdb = [0u8; 16];
+        build_read_toc(&mut cdb, false, 0, TOC_ALLOC_LEN, 0);
+        let data = self.read_cdb(cdb, TOC_ALLOC_LEN as u32, None).await?;
+        Ok(parse_toc(&data, false)?)
+    }
+
+    /// Features (and profiles) of the device and loaded medium.
+    pub async fn get_configuration(
+        &self,
+        rt: ConfigurationRequest,
+    ) -> error::Result<Configuration> {
+        let mut alloc = CONFIGURATION_ALLOC_LEN;
+        loop {
+            let mut cdb = [0u8; 16];
+            build_get_configuration(&mut cdb, rt, 0, alloc, 0);
+            let data = self.read_cdb(cdb, alloc as u32, None).await?;
+            let total = parse_configuration_length(&data)?;
+            // Stop once everything arrived, the target sent less than it
+            // was allowed to, or the allocation cannot grow any more.
+            if total <= data.len() || data.len() < alloc as usize || alloc == u16::MAX {
+                return Ok(parse_configuration(&data)?);
+            }
+            alloc = u16::try_from(total).unwrap_or(u16::MAX);
+        }
+    }
+
+    /// Standard disc information: status, sessions and tracks.
+    pub async fn read_disc_information(&self) -> error::Result<DiscInformation> {
+        let mut cdb = [0u8; 16];
+        build_read_disc_information(&mut cdb, DISC_INFORMATION_LEN as u16, 0);
+        let data = self
+            .read_cdb(cdb, DISC_INFORMATION_LEN as u32, None)
+            .await?;
+        Ok(parse_disc_information(&data)?)
+    }
+
+    /// Read `sectors` 2048-byte user data sectors starting at `lba`
+    /// (READ(10)).
+    pub async fn read_sectors(&self, lba: u32, sectors: u16) -> error::Result<Vec<u8>> {
+        if sectors == 0 {
+            return Ok(Vec::new());
+        }
+        let len = sectors as u32 * MMC_SECTOR_LEN;
+        let mut cdb = [0u8; 16];
+        build_read10(&mut cdb, lba, sectors, 0, 0);
+        let data = self.read_cdb(cdb, len, Some(lba as u64)).await?;
+        if data.len() != len as usize {
+            return Err(error::IscsiError::msg(format!(
+                "short read at sector {lba}: {} of {len} bytes",
+                data.len()
+            )));
+        }
+        Ok(data)
+    }
+}
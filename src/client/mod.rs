@@ -4,13 +4,86 @@
 // Copyright (C) 2012-2025 Andrei Maltsev
 
 #![allow(clippy::module_inception)]
+/// Asymmetric logical unit access (target port group states).
+pub mod alua;
+/// ATA PASS-THROUGH and SMART queries for SATA devices behind SAT.
+pub mod ata;
+/// Target buffers: echo-buffer tests and microcode download.
+pub mod buffer;
+/// Feature summary of a LUN (VPD pages, READ CAPACITY(16)).
+pub mod capabilities;
 /// The main iSCSI client implementation.
 pub mod client;
 #[cfg(test)]
 mod client_faults_tests;
-mod common;
+pub(crate) mod common;
+/// Server-side copy between LUNs (EXTENDED COPY).
+pub mod copy;
+/// dd-like copies between a LUN and a local file or raw device.
+pub mod dd;
+/// Byte-addressed device on top of a LUN handle.
+pub mod device;
+/// Self-tests and diagnostic / SES pages.
+pub mod diagnostics;
+/// Lifecycle events published by the pool.
+pub mod events;
+/// Session and LUN handles hiding the per-command plumbing.
+pub mod handles;
+/// Several independent initiators (names, ISIDs, pools) in one process.
+pub mod initiators;
+/// LUN inventory of a session (REPORT LUNS).
+pub mod inventory;
+/// Connection selection for commands not pinned to a CID.
+pub mod load_balance;
+mod lun_scheduler;
+/// CD / DVD / BD devices (MMC): TOC, configuration, disc information.
+pub mod mmc;
+/// Failover of a session across the portals of one target.
+pub mod multipath;
 /// Traits for handling PDU serialization and deserialization.
 pub mod pdu_connection;
 mod pending_requests;
 /// Manages a pool of iSCSI sessions.
 pub mod pool_sessions;
+/// Portal address parsing (IPv4, IPv6 literals, DNS names).
+pub mod portal;
+/// Readiness checks of every connection and LUN before a workload.
+pub mod preflight;
+/// Automatic reconnection policy and events.
+pub mod reconnect;
+/// REASSIGN BLOCKS and the medium-error policy of READ / WRITE.
+pub mod remap;
+/// Persistent reservations (PERSISTENT RESERVE IN / OUT).
+pub mod reservations;
+/// Retry policy for transient SCSI statuses.
+pub mod retry;
+/// Secure erase with SANITIZE and progress polling.
+pub mod sanitize;
+/// Scrub passes: end-to-end reads checked against a pattern or checksums.
+pub mod scrub;
+/// SOCKS5 proxy client for the TCP transport.
+pub mod socks5;
+/// Pluggable executor for background tasks.
+pub mod spawn;
+/// Read-only snapshots of sessions and connections.
+pub mod status;
+/// Tape drives (SSC): fixed and variable block reads and writes, filemarks
+/// and positioning.
+pub mod tape;
+/// Sessions to several targets, addressed by target name.
+pub mod targets;
+/// Thin provisioning: UNMAP of byte ranges split by the Block Limits.
+pub mod thin;
+/// Token-bucket bandwidth limits per connection.
+pub mod throttle;
+/// TLS settings and handshake (handshake behind the `tls` feature).
+pub mod tls;
+/// Stream abstraction the connection I/O runs over.
+pub mod transport;
+/// io_uring-backed socket I/O.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
+/// Workload generator reporting IOPS, throughput and latency percentiles.
+pub mod workload;
+/// Zoned block devices: zone reports, zone actions and write pointers.
+pub mod zones;
@@ -0,0 +1,571 @@
+//! Multipath failover across the portals of one target.
+//!
+//! A target may be reachable through several portals
+//! (`login.transport.TargetAddress` plus `login.transport.Portals`).
+//! [`Multipath`] keeps one session open through the most preferred portal
+//! that works. When that path's connections die and the pool cannot recover
+//! them, the session is re-established through the next portal with the same
+//! ISID (so the target reinstates it) and the failed command is re-issued
+//! once. With `runtime.FailbackInterval` set, a background task probes the
+//! more preferred portals and moves the session back once one answers.
+//!
+//! After [`Multipath::refresh_alua`] the portals are also ranked by the ALUA
+//! state of their target port group: failover and failback pick
+//! active/optimized portals before active/non-optimized ones and avoid
+//! standby or unavailable groups. A command refused with TARGET PORT IN
+//! STANDBY / UNAVAILABLE STATE moves the session to a better portal and is
+//! re-issued once.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use tokio::{net::TcpStream, sync::RwLock};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{
+    cfg::config::Config,
+    client::{
+        alua::alua_state_from_sense,
+        pool_sessions::{ExecuteEnv, Pool},
+        portal::PortalTarget,
+    },
+    control_block::alua::{AluaState, TargetPortGroups},
+    error::{self, IscsiError},
+    models::identifiers::{Cid, Isid, Lun, Tsih},
+    state_machine::common::StateMachineCtx,
+};
+
+/// ALUA view of one portal.
+#[derive(Debug, Clone, Copy, Default)]
+struct PortalAlua {
+    group: Option<u16>,
+    state: Option<AluaState>,
+}
+
+/// Health of one configured portal.
+#[derive(Debug)]
+struct Portal {
+    address: String,
+    healthy: AtomicBool,
+    failures: AtomicU32,
+    alua: Mutex<PortalAlua>,
+}
+
+impl Portal {
+    fn new(address: String) -> Self {
+        Self {
+            address,
+            healthy: AtomicBool::new(true),
+            failures: AtomicU32::new(0),
+            alua: Mutex::new(PortalAlua::default()),
+        }
+    }
+
+    fn alua(&self) -> PortalAlua {
+        *self.alua.lock().expect("portal ALUA lock")
+    }
+
+    fn set_alua_state(&self, state: AluaState) {
+        self.alua.lock().expect("portal ALUA lock").state = Some(state);
+    }
+
+    /// Take the state of this portal's group from `groups`, if known.
+    fn apply_alua(&self, groups: &TargetPortGroups) {
+        let mut alua = self.alua.lock().expect("portal ALUA lock");
+        if let Some(group) = alua.group.and_then(|id| groups.group(id)) {
+            alua.state = Some(group.state);
+        }
+    }
+
+    /// ALUA preference, lower is better; portals of unknown state rank with
+    /// active/non-optimized ones.
+    fn rank(&self) -> u8 {
+        self.alua()
+            .state
+            .map_or(AluaState::ActiveNonOptimized.rank(), AluaState::rank)
+    }
+
+    fn mark_up(&self) {
+        self.healthy.store(true, Ordering::SeqCst);
+    }
+
+    fn mark_down(&self) {
+        self.healthy.store(false, Ordering::SeqCst);
+        self.failures.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Portals to try after `failed`: best ALUA rank first, equally ranked
+/// portals in rotation order starting after `failed`.
+fn failover_order(portals: &[Portal], failed: usize) -> Vec<usize> {
+    let n = portals.len();
+    let mut order: Vec<usize> = (1..=n).map(|step| (failed + step) % n).collect();
+    // Stable: equally ranked portals keep their rotation order.
+    order.sort_by_key(|&idx| portals[idx].rank());
+    order
+}
+
+/// Snapshot of a portal as seen by [`Multipath::portals`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortalStatus {
+    /// `host:port` of the portal.
+    pub address: String,
+    /// Whether the last login or probe through this portal succeeded.
+    pub healthy: bool,
+    /// Number of times this portal was marked down.
+    pub failures: u32,
+    /// Whether the session currently runs through this portal.
+    pub active: bool,
+    /// Target port group of the portal, once learned by
+    /// [`Multipath::refresh_alua`].
+    pub target_port_group: Option<u16>,
+    /// Last known ALUA state of that group.
+    pub alua_state: Option<AluaState>,
+}
+
+/// The session currently carrying traffic.
+#[derive(Debug, Clone, Copy)]
+struct ActivePath {
+    portal: usize,
+    tsih: Tsih,
+}
+
+/// One logical session to a target, kept alive across its portals.
+pub struct Multipath {
+    pool: Arc<Pool>,
+    cfg: Config,
+    isid: Isid,
+    portals: Vec<Portal>,
+    /// Commands hold the read side while running; switching paths takes the
+    /// write side so it never races in-flight I/O on the old path.
+    active: RwLock<Option<ActivePath>>,
+    /// LUN whose ALUA states rank the portals, set by `refresh_alua`.
+    alua_lun: Mutex<Option<Lun>>,
+    cancel: CancellationToken,
+}
+
+impl Multipath {
+    /// Logs in through the first reachable portal of `cfg` and, when
+    /// `runtime.FailbackInterval` is non-zero, starts the failback probe.
+    pub async fn connect(pool: Arc<Pool>, cfg: &Config) -> error::Result<Arc<Self>> {
+        let portals: Vec<Portal> = cfg
+            .login
+            .transport
+            .all_portals()
+            .into_iter()
+            .map(Portal::new)
+            .collect();
+        if portals.is_empty() {
+            return Err(IscsiError::msg("multipath needs at least one portal"));
+        }
+
+        let this = Arc::new(Self {
+            cancel: pool.cancel_token().child_token(),
+            pool,
+            cfg: cfg.clone(),
+            isid: cfg.login.identity.session_isid(0)?,
+            portals,
+            active: RwLock::new(None),
+            alua_lun: Mutex::new(None),
+        });
+
+        {
+            let mut active = this.active.write().await;
+            *active = Some(this.establish(0..this.portals.len()).await?);
+        }
+
+        let interval = cfg.runtime.failback_interval;
+        if !interval.is_zero() {
+            this.pool.spawner().spawn(Box::pin(Self::failback_loop(
+                Arc::downgrade(&this),
+                interval,
+                this.cancel.clone(),
+            )));
+        }
+        Ok(this)
+    }
+
+    /// Address of the portal currently carrying the session.
+    pub async fn active_portal(&self) -> Option<String> {
+        let active = *self.active.read().await;
+        active.map(|path| self.portals[path.portal].address.clone())
+    }
+
+    /// TSIH of the session currently carrying traffic.
+    pub async fn active_tsih(&self) -> Option<Tsih> {
+        self.active.read().await.map(|path| path.tsih)
+    }
+
+    /// Health of every configured portal, in preference order.
+    pub async fn portals(&self) -> Vec<PortalStatus> {
+        let active = self.active.read().await.map(|path| path.portal);
+        self.portals
+            .iter()
+            .enumerate()
+            .map(|(idx, portal)| {
+                let alua = portal.alua();
+                PortalStatus {
+                    address: portal.address.clone(),
+                    healthy: portal.healthy.load(Ordering::SeqCst),
+                    failures: portal.failures.load(Ordering::SeqCst),
+                    active: active == Some(idx),
+                    target_port_group: alua.group,
+                    alua_state: alua.state,
+                }
+            })
+            .collect()
+    }
+
+    /// Read the target port group states of `lun` through the active path
+    /// and rank the portals by them. The group of each portal is learned
+    /// when a session runs through it, so portals not used yet stay
+    /// unranked until then.
+    pub async fn refresh_alua(&self, lun: Lun) -> error::Result<TargetPortGroups> {
+        *self.alua_lun.lock().expect("ALUA LUN lock") = Some(lun);
+        let path = (*self.active.read().await)
+            .ok_or_else(|| anyhow!("multipath has no active path"))?;
+        self.learn_group(path, lun).await?;
+        let groups = self
+            .pool
+            .session(path.tsih)?
+            .lun(lun)
+            .report_target_port_groups()
+            .await?;
+        self.apply_alua(&groups);
+        Ok(groups)
+    }
+
+    /// Record the target port group `path`'s portal belongs to.
+    async fn learn_group(&self, path: ActivePath, lun: Lun) -> error::Result<()> {
+        let port = self.pool.session(path.tsih)?.lun(lun).target_port().await?;
+        self.portals[path.portal]
+            .alua
+            .lock()
+            .expect("portal ALUA lock")
+            .group = port.group;
+        Ok(())
+    }
+
+    fn apply_alua(&self, groups: &TargetPortGroups) {
+        for portal in &self.portals {
+            portal.apply_alua(groups);
+        }
+    }
+
+    /// After a login through `path`, learn its group and refresh the states
+    /// when ALUA ranking is in use. Failures only leave the portal unranked.
+    async fn refresh_alua_after_login(&self, path: ActivePath) {
+        let Some(lun) = *self.alua_lun.lock().expect("ALUA LUN lock") else {
+            return;
+        };
+        let refreshed = async {
+            self.learn_group(path, lun).await?;
+            let groups = self
+                .pool
+                .session(path.tsih)?
+                .lun(lun)
+                .report_target_port_groups()
+                .await?;
+            self.apply_alua(&groups);
+            Ok::<_, IscsiError>(())
+        };
+        if let Err(error) = refreshed.await {
+            warn!(
+                "multipath: ALUA refresh via {} failed: {error:#}",
+                self.portals[path.portal].address
+            );
+        }
+    }
+
+    /// Runs a command on the active path like [`Pool::execute_with_ctx`].
+    ///
+    /// If the path turns out to be dead, fails over to the next portal and
+    /// re-issues the command once, except when its outcome is ambiguous
+    /// ([`IscsiError::AmbiguousOutcome`]).
+    pub async fn execute_with_ctx<Ctx, Res, Build>(
+        &self,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let failed_tsih = {
+            let active = self.active.read().await;
+            let path =
+                (*active).ok_or_else(|| anyhow!("multipath has no active path"))?;
+            match self
+                .pool
+                .execute_with_ctx(path.tsih, Cid::ZERO, &build)
+                .await
+            {
+                Ok(res) => return Ok(res),
+                Err(IscsiError::Scsi(error))
+                    if let Some(state) =
+                        error.sense_codes().and_then(|(key, asc, ascq)| {
+                            alua_state_from_sense(key, asc, ascq)
+                        }) =>
+                {
+                    warn!(
+                        "multipath: {} reports {state:?} ({error}); switching path",
+                        self.portals[path.portal].address
+                    );
+                    self.portals[path.portal].set_alua_state(state);
+                    path.tsih
+                },
+                Err(error) if self.pool.session_is_healthy(path.tsih) => {
+                    return Err(error);
+                },
+                Err(error @ IscsiError::AmbiguousOutcome(_)) => {
+                    drop(active);
+                    let _ = self.fail_over_from(path.tsih).await;
+                    return Err(error);
+                },
+                Err(error) => {
+                    warn!(
+                        "multipath: TSIH={} failed ({error:#}); failing over",
+                        path.tsih
+                    );
+                    path.tsih
+                },
+            }
+        };
+
+        let tsih = self.fail_over_from(failed_tsih).await?;
+        let _active = self.active.read().await;
+        self.pool.execute_with_ctx(tsih, Cid::ZERO, build).await
+    }
+
+    /// Moves the session to the next working portal now.
+    pub async fn failover(&self) -> error::Result<Tsih> {
+        let current = self.active.read().await.map(|path| path.tsih);
+        match current {
+            Some(tsih) => self.fail_over_from(tsih).await.map_err(Into::into),
+            None => {
+                let mut active = self.active.write().await;
+                let path = self.establish(0..self.portals.len()).await?;
+                *active = Some(path);
+                Ok(path.tsih)
+            },
+        }
+    }
+
+    /// Switches away from `failed` unless another caller already did.
+    async fn fail_over_from(&self, failed: Tsih) -> Result<Tsih> {
+        let mut active = self.active.write().await;
+        let Some(path) = *active else {
+            return Err(anyhow!("multipath has no active path"));
+        };
+        if path.tsih != failed {
+            return Ok(path.tsih);
+        }
+
+        self.portals[path.portal].mark_down();
+        self.pool
+            .drop_session_local(path.tsih, "portal failed over");
+        let next = self
+            .establish(failover_order(&self.portals, path.portal))
+            .await?;
+        info!(
+            "multipath: failed over from {} to {} (TSIH={})",
+            self.portals[path.portal].address,
+            self.portals[next.portal].address,
+            next.tsih
+        );
+        *active = Some(next);
+        Ok(next.tsih)
+    }
+
+    /// Logs in through the first portal of `order` that accepts us.
+    async fn establish(
+        &self,
+        order: impl IntoIterator<Item = usize>,
+    ) -> Result<ActivePath> {
+        let mut last_error = None;
+        for idx in order {
+            let portal = &self.portals[idx];
+            let mut cfg = self.cfg.clone();
+            cfg.login.transport.target_address = portal.address.clone();
+            cfg.login.transport.portals.clear();
+            match self.pool.reinstate_session(&cfg, self.isid).await {
+                Ok(tsih) => {
+                    portal.mark_up();
+                    let path = ActivePath { portal: idx, tsih };
+                    self.refresh_alua_after_login(path).await;
+                    return Ok(path);
+                },
+                Err(error) => {
+                    warn!("multipath: login via {} failed: {error:#}", portal.address);
+                    portal.mark_down();
+                    last_error = Some(error);
+                },
+            }
+        }
+        Err(last_error
+            .map(anyhow::Error::from)
+            .unwrap_or_else(|| anyhow!("no portal available"))
+            .context("multipath: every portal failed"))
+    }
+
+    /// Periodically probes portals preferred over the active one and moves
+    /// the session back to the first that accepts a TCP connection.
+    async fn failback_loop(
+        this: Weak<Self>,
+        interval: Duration,
+        cancel: CancellationToken,
+    ) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {},
+                _ = cancel.cancelled() => return,
+            }
+            let Some(this) = this.upgrade() else {
+                return;
+            };
+            if let Err(error) = this.try_failback().await {
+                warn!("multipath: failback failed: {error:#}");
+            }
+        }
+    }
+
+    async fn try_failback(&self) -> Result<()> {
+        let Some(current) = self.active.read().await.map(|path| path.portal) else {
+            return Ok(());
+        };
+        // Better ALUA state first, then configured preference.
+        let key = |idx: usize| (self.portals[idx].rank(), idx);
+        let mut candidates: Vec<usize> = (0..self.portals.len())
+            .filter(|&idx| key(idx) < key(current))
+            .collect();
+        candidates.sort_by_key(|&idx| key(idx));
+        let mut preferred = None;
+        for idx in candidates {
+            if self.probe(idx).await {
+                preferred = Some(idx);
+                break;
+            }
+        }
+        let Some(preferred) = preferred else {
+            return Ok(());
+        };
+
+        let mut active = self.active.write().await;
+        let Some(path) = *active else {
+            return Ok(());
+        };
+        if key(path.portal) <= key(preferred) {
+            return Ok(());
+        }
+        if let Err(error) = self.pool.logout_session(path.tsih).await {
+            warn!("multipath: logout before failback failed: {error:#}");
+            self.pool.drop_session_local(path.tsih, "failing back");
+        }
+        // Preferred portal first; fall back to any other if it refuses.
+        let n = self.portals.len();
+        let order = (0..n).map(|step| (preferred + step) % n);
+        let next = self.establish(order).await?;
+        info!(
+            "multipath: failed back to {} (TSIH={})",
+            self.portals[next.portal].address, next.tsih
+        );
+        *active = Some(next);
+        Ok(())
+    }
+
+    /// Cheap reachability check: can a TCP connection be opened?
+    async fn probe(&self, idx: usize) -> bool {
+        let portal = &self.portals[idx];
+        let limit = self.cfg.runtime.connect_timeout();
+        let ok = match portal.address.parse::<PortalTarget>() {
+            Ok(PortalTarget::Tcp(addr)) => matches!(
+                tokio::time::timeout(limit, TcpStream::connect(addr.as_connect_target()))
+                    .await,
+                Ok(Ok(_))
+            ),
+            #[cfg(unix)]
+            Ok(PortalTarget::Unix(path)) => matches!(
+                tokio::time::timeout(limit, tokio::net::UnixStream::connect(path)).await,
+                Ok(Ok(_))
+            ),
+            _ => false,
+        };
+        if ok {
+            portal.mark_up();
+        }
+        ok
+    }
+}
+
+impl Drop for Multipath {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_block::alua::TargetPortGroup;
+
+    fn group(id: u16, state: AluaState) -> TargetPortGroup {
+        TargetPortGroup {
+            id,
+            state,
+            preferred: false,
+            supported_states: 0,
+            status_code: 0,
+            ports: vec![id],
+        }
+    }
+
+    fn portal(address: &str, group: Option<u16>) -> Portal {
+        let portal = Portal::new(address.to_string());
+        portal.alua.lock().expect("portal ALUA lock").group = group;
+        portal
+    }
+
+    #[test]
+    fn failover_prefers_better_alua_states() {
+        let portals = [
+            portal("10.0.0.1:3260", Some(1)),
+            portal("10.0.0.2:3260", Some(2)),
+            portal("10.0.0.3:3260", None),
+            portal("10.0.0.4:3260", Some(3)),
+        ];
+        let groups = TargetPortGroups {
+            implicit_transition_secs: None,
+            groups: vec![
+                group(1, AluaState::ActiveOptimized),
+                group(2, AluaState::Standby),
+                group(3, AluaState::ActiveOptimized),
+            ],
+        };
+        for portal in &portals {
+            portal.apply_alua(&groups);
+        }
+        assert_eq!(portals[1].alua().state, Some(AluaState::Standby));
+        assert_eq!(portals[2].alua().state, None);
+
+        // Optimized first in rotation order, then the unranked portal, which
+        // ranks as non-optimized, then standby.
+        assert_eq!(failover_order(&portals, 0), vec![3, 0, 2, 1]);
+        assert_eq!(failover_order(&portals, 3), vec![0, 3, 2, 1]);
+
+        // A path reporting STANDBY sense drops behind the others.
+        let (key, asc, ascq) = (0x02, 0x04, 0x0B);
+        let state = alua_state_from_sense(key, asc, ascq).expect("ALUA sense");
+        portals[3].set_alua_state(state);
+        assert_eq!(failover_order(&portals, 3), vec![0, 2, 1, 3]);
+        assert_eq!(alua_state_from_sense(0x02, 0x04, 0x01), None);
+    }
+}
@@ -3,10 +3,8 @@
 
 use anyhow::Result;
 
-use crate::models::{
-    common::{BasicHeaderSegment, Builder},
-    opcode::BhsOpcode,
-};
+use crate::models::common::Builder;
+pub use crate::models::common::FromBytes;
 
 /// A trait for serializing a Protocol Data Unit (PDU) into a byte
 /// representation for transmission.
@@ -30,23 +28,6 @@ pub trait ToBytes: Sized {
     ) -> Result<(Self::Header, Self::Body)>;
 }
 
-/// A trait for deserializing a Protocol Data Unit (PDU) from a raw byte stream.
-///
-/// This trait provides functionality to parse incoming binary data into
-/// structured PDU objects. It requires the implementing type to also implement
-/// BasicHeaderSegment for header access.
-pub trait FromBytes: Sized + BasicHeaderSegment {
-    /// Parse the full PDU from a contiguous byte buffer.
-    ///
-    /// The parsed `Response` (often a tuple of header struct, payload bytes,
-    /// and digest), or an error if parsing fails.
-    fn from_bhs_bytes(bytes: &mut [u8]) -> Result<&mut Self> {
-        let _ = BhsOpcode::try_from(bytes[0])
-            .map_err(|e| anyhow::anyhow!("invalid opcode: {}", e))?;
-        Self::from_bhs_bytes(bytes)
-    }
-}
-
 impl<B> ToBytes for B
 where B: Builder
 {
@@ -57,6 +38,6 @@ where B: Builder
         &mut self,
         max_recv_data_segment_length: usize,
     ) -> Result<(Self::Header, Self::Body)> {
-        self.build(max_recv_data_segment_length)
+        self.build(max_recv_data_segment_length).map_err(Into::into)
     }
 }
@@ -11,6 +11,13 @@ use crate::{client::common::RawPdu, models::identifiers::Itt};
 pub(super) struct PendingRequests {
     senders: DashMap<Itt, mpsc::Sender<RawPdu>>,
     receivers: DashMap<Itt, mpsc::Receiver<RawPdu>>,
+    /// Tasks given up by the initiator (e.g. timed out and aborted). Late
+    /// PDUs for these tags are dropped instead of being treated as
+    /// unsolicited.
+    abandoned: DashMap<Itt, ()>,
+    /// Tasks terminated by a multi-task TMF; their waiters fail with
+    /// [`TaskTerminatedError`](crate::client::pool_sessions::TaskTerminatedError).
+    terminated: DashMap<Itt, ()>,
     response_queue_capacity: usize,
 }
 
@@ -20,6 +27,8 @@ impl PendingRequests {
         Self {
             senders: DashMap::new(),
             receivers: DashMap::new(),
+            abandoned: DashMap::new(),
+            terminated: DashMap::new(),
             response_queue_capacity,
         }
     }
@@ -39,6 +48,42 @@ impl PendingRequests {
         self.receivers.remove(&itt);
     }
 
+    pub(super) fn abandon(&self, itt: Itt) {
+        self.remove(itt);
+        self.abandoned.insert(itt, ());
+    }
+
+    /// Terminate `itt` without a response from the target. With
+    /// `expect_late` the target may still send PDUs for it, which are then
+    /// dropped like those of an abandoned task.
+    pub(super) fn terminate(&self, itt: Itt, expect_late: bool) {
+        self.remove(itt);
+        self.terminated.insert(itt, ());
+        if expect_late {
+            self.abandoned.insert(itt, ());
+        }
+    }
+
+    /// Returns (and clears) whether `itt` was terminated by a TMF.
+    pub(super) fn take_terminated(&self, itt: Itt) -> bool {
+        self.terminated.remove(&itt).is_some()
+    }
+
+    pub(super) fn forget_abandoned(&self, itt: Itt) {
+        self.abandoned.remove(&itt);
+    }
+
+    /// Returns whether `itt` was abandoned; the final PDU clears the mark.
+    pub(super) fn absorb_abandoned(&self, itt: Itt, is_final: bool) -> bool {
+        if !self.abandoned.contains_key(&itt) {
+            return false;
+        }
+        if is_final {
+            self.abandoned.remove(&itt);
+        }
+        true
+    }
+
     pub(super) fn take_receiver(&self, itt: Itt) -> Result<mpsc::Receiver<RawPdu>> {
         self.receivers
             .remove(&itt)
@@ -95,5 +140,7 @@ impl PendingRequests {
     pub(super) fn abort_all(&self) {
         self.senders.clear();
         self.receivers.clear();
+        self.abandoned.clear();
+        self.terminated.clear();
     }
 }
@@ -2,31 +2,62 @@
 // Copyright (C) 2012-2025 Andrei Maltsev
 
 use std::{
-    sync::{Arc, Weak, atomic::AtomicU32},
+    sync::{
+        Arc, RwLock, Weak,
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+    },
     time::Duration,
 };
 
-use anyhow::{Context, Result, ensure};
+use anyhow::{Context, Result, anyhow, ensure};
 use dashmap::DashMap;
+use thiserror::Error;
+use tokio::{
+    sync::{Semaphore, broadcast},
+    task::JoinSet,
+    time::Instant,
+};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
 use crate::{
-    cfg::config::{AuthConfig, Config},
-    client::client::ClientConnection,
+    cfg::{
+        config::{Config, TaskReporting},
+        secrets::SecretProvider,
+    },
+    client::{
+        client::{ClientConnection, RequestNotSentError},
+        events::PoolEvent,
+        handles::SessionHandle,
+        inventory::LunInventory,
+        load_balance::{ConnectionLoad, ConnectionSelector},
+        lun_scheduler::LunScheduler,
+        reconnect::{ReconnectEvent, ReconnectPolicy},
+        remap::MediumErrorPolicy,
+        retry::{AmbiguousOutcomeError, RetryPolicy},
+        spawn::{Spawner, TokioSpawner},
+        status::{
+            ConnectionHealth, ConnectionStatus, PoolStatus, SessionStatus, TaskStatus,
+        },
+    },
+    control_block::control::has_naca,
+    error::{self, IscsiError},
     models::{
+        command::common::{ScsiStatus, TaskAttribute},
         common::BasicHeaderSegment,
-        data_fromat,
-        identifiers::{Cid, Isid, IttGen, Tsih},
+        data_fromat::{self, PduResponse},
+        identifiers::{Cid, Isid, Itt, IttGen, Lun, Tsih},
         logout::common::LogoutReason,
-        nop::response::NopInResponse,
+        nop::{request::NopOutRequest, response::NopInResponse},
+        task_mgmt::{common::TaskManagementFunction, response::TaskMgmtResponse},
     },
     state_machine::{
-        common::StateMachineCtx,
+        common::{ScsiStatusError, StateMachineCtx, TaskRef},
         discovery::{DiscoveredTarget, DiscoveryCtx},
-        login::common::LoginCtx,
+        login::common::{LoginCtx, LoginStatusError, response_key},
         logout_states::LogoutCtx,
-        nop_states::NopCtx,
+        nop_states::{NopCtx, PingCtx},
+        tmf_states::TmfCtx,
     },
 };
 
@@ -42,9 +73,36 @@ pub struct Connection {
     pub cid: Cid,
     /// Reference to the underlying client connection handling TCP communication
     pub conn: Arc<ClientConnection>,
-    /// Next Expected StatSN (ACK). Bumped when we accept a reply from target.
-    /// Used to track the sequence of status responses from the target.
+    /// Next Expected StatSN (ACK), shared with the connection's read loop
+    /// which advances it for every status-bearing PDU.
     pub exp_stat_sn: Arc<AtomicU32>,
+    /// Commands currently executing on this connection, fed to the
+    /// [`ConnectionSelector`].
+    in_flight: AtomicUsize,
+}
+
+impl Connection {
+    /// Number of commands currently executing on this connection.
+    #[inline]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts a command in [`Connection::in_flight`] while it executes.
+struct InFlightGuard<'a>(&'a AtomicUsize);
+
+impl<'a> InFlightGuard<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 /// Per-session state identified by ISID+TSIH combination
@@ -66,6 +124,12 @@ pub struct Session {
     pub target_name: Arc<str>,
     /// Map of connection ID to connection objects within this session
     pub conns: DashMap<Cid, Arc<Connection>>,
+    /// MaxConnections negotiated by the leading login.
+    max_connections: u16,
+    /// TaskReporting negotiated by the leading login.
+    task_reporting: TaskReporting,
+    /// DefaultTime2Wait negotiated by the leading login.
+    time2wait: Duration,
 
     /// CmdSN generator for numbered commands (incremented on every
     /// non-immediate command). Ensures proper command ordering.
@@ -73,6 +137,28 @@ pub struct Session {
     /// ITT (Initiator Task Tag) generator - unique within a session.
     /// Used to match requests with responses.
     itt_gen: Arc<IttGen>,
+    /// LUNs and per-LUN state learned through the session.
+    pub(crate) inventory: LunInventory,
+}
+
+impl Session {
+    /// MaxConnections negotiated for this session.
+    #[inline]
+    pub fn max_connections(&self) -> u16 {
+        self.max_connections
+    }
+
+    /// TaskReporting negotiated for this session.
+    #[inline]
+    pub fn task_reporting(&self) -> TaskReporting {
+        self.task_reporting
+    }
+
+    /// DefaultTime2Wait negotiated for this session.
+    #[inline]
+    pub fn time2wait(&self) -> Duration {
+        self.time2wait
+    }
 }
 
 /// Pool of iSCSI sessions and connections
@@ -85,10 +171,38 @@ pub struct Pool {
     pub sessions: DashMap<Tsih, Arc<Session>>,
     /// Maximum number of sessions allowed in this pool
     max_sessions: u32,
-    /// Maximum number of connections per session
-    max_connections: u16,
     /// Retries allowed after the initial connection failure.
     max_connection_recovery_attempts: usize,
+    /// Pool-wide retry policy applied by [`Pool::execute_with_ctx`].
+    retry_policy: RwLock<Arc<RetryPolicy>>,
+    /// What [`LunHandle`](crate::client::handles::LunHandle) reads and
+    /// writes do on a MEDIUM ERROR.
+    medium_error_policy: RwLock<MediumErrorPolicy>,
+    /// Picks the connection for commands not pinned to a CID.
+    load_balancer: RwLock<Arc<dyn ConnectionSelector>>,
+    /// Runs connection read loops and the pool's own background tasks.
+    spawner: RwLock<Arc<dyn Spawner>>,
+    /// Supplies CHAP credentials per target, overriding the configured ones.
+    secret_provider: RwLock<Option<Arc<dyn SecretProvider>>>,
+    /// Rotates [`Pool::pick_session`] among equally loaded sessions.
+    next_session: AtomicUsize,
+    /// Per-(session, LUN) cap on outstanding commands.
+    lun_scheduler: LunScheduler,
+    /// SCSI tasks currently executing, keyed by (TSIH, ITT), so a
+    /// multi-task TMF can terminate the ones it affects.
+    tasks: DashMap<(Tsih, Itt), (Cid, Lun)>,
+    /// Per-command timeout after which the task is aborted.
+    command_timeout: Option<Duration>,
+    /// How long to wait for the ABORT TASK response.
+    tmf_timeout: Duration,
+    /// Unacknowledged StatSNs that trigger an immediate NOP-Out; `0` = off.
+    stat_sn_ack_threshold: u32,
+    /// Background reconnection of dead connections.
+    reconnect_policy: ReconnectPolicy,
+    /// Publishes [`ReconnectEvent`]s to subscribers.
+    reconnect_events: broadcast::Sender<ReconnectEvent>,
+    /// Publishes lifecycle [`PoolEvent`]s to subscribers.
+    events: broadcast::Sender<PoolEvent>,
     /// Weak self-reference to avoid circular dependencies
     self_weak: Weak<Pool>,
 
@@ -98,6 +212,168 @@ pub struct Pool {
     cancel: CancellationToken,
 }
 
+/// A command did not complete within the configured timeout.
+#[derive(Debug, Error)]
+#[error(
+    "command {} on TSIH={tsih}, CID={cid} timed out after {timeout:?} (aborted: {aborted})",
+    itt.map_or_else(|| "-".to_string(), |itt| itt.to_string())
+)]
+pub struct CommandTimeoutError {
+    /// Session the command was issued on.
+    pub tsih: Tsih,
+    /// Connection the command was issued on.
+    pub cid: Cid,
+    /// ITT of the timed-out task, if the command is a SCSI task.
+    pub itt: Option<Itt>,
+    /// Timeout that elapsed.
+    pub timeout: Duration,
+    /// Whether the target confirmed the ABORT TASK.
+    pub aborted: bool,
+}
+
+/// A task management function (ABORT TASK SET, CLEAR TASK SET, LOGICAL UNIT
+/// RESET, TARGET RESET) terminated the task before it completed.
+#[derive(Debug, Error)]
+#[error("task {itt} was terminated by a task management function")]
+pub struct TaskTerminatedError {
+    /// ITT of the terminated task.
+    pub itt: Itt,
+}
+
+/// Whether `error` says the connection refused the command before any byte
+/// of it reached the target.
+fn was_not_sent(error: &anyhow::Error) -> bool {
+    error.chain().any(|e| e.is::<RequestNotSentError>())
+}
+
+/// Outcome of [`Pool::scale_connections`].
+#[derive(Debug, Default)]
+pub struct ScaleReport {
+    /// Connections opened and logged in, by CID.
+    pub added: Vec<Cid>,
+    /// Connections that could not be added, with the reason.
+    pub failed: Vec<(Cid, IscsiError)>,
+}
+
+impl ScaleReport {
+    /// Whether every requested connection was added.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Some sessions of [`Pool::login_sessions_from_cfg`] failed to log in.
+///
+/// The sessions that did log in stay in the pool and are listed in
+/// `logged_in`.
+#[derive(Debug, Error)]
+#[error(
+    "{} of {} session logins failed: {}",
+    failed.len(),
+    failed.len() + logged_in.len(),
+    failed
+        .iter()
+        .map(|(isid, e)| format!("ISID={isid}: {e:#}"))
+        .collect::<Vec<_>>()
+        .join("; ")
+)]
+pub struct SessionLoginError {
+    /// Sessions logged in, in configuration order.
+    pub logged_in: Vec<Tsih>,
+    /// Sessions that failed, by ISID, in configuration order.
+    pub failed: Vec<(Isid, IscsiError)>,
+}
+
+/// Adding a connection would exceed the MaxConnections negotiated for the
+/// session.
+#[derive(Debug, Error)]
+#[error("TSIH={tsih} already has {max} connection(s), the negotiated MaxConnections")]
+pub struct MaxConnectionsError {
+    /// Session that is full.
+    pub tsih: Tsih,
+    /// Negotiated MaxConnections.
+    pub max: u16,
+}
+
+/// Removes a task from [`Pool::tasks`] once its execution ends.
+struct TaskGuard<'a> {
+    tasks: &'a DashMap<(Tsih, Itt), (Cid, Lun)>,
+    key: (Tsih, Itt),
+}
+
+impl Drop for TaskGuard<'_> {
+    fn drop(&mut self) {
+        self.tasks.remove(&self.key);
+    }
+}
+
+/// The caller's deadline expired before the command could be sent (while
+/// queued, recovering the connection or backing off between retries).
+#[derive(Debug, Error)]
+#[error("deadline expired before command on TSIH={tsih}, CID={cid} was sent")]
+pub struct DeadlineExceededError {
+    /// Session the command was meant for.
+    pub tsih: Tsih,
+    /// Connection the command was meant for.
+    pub cid: Cid,
+}
+
+/// Per-call overrides for [`Pool::execute_with_options`].
+///
+/// ```ignore
+/// let opts = ExecuteOptions::new()
+///     .timeout(Duration::from_secs(2))
+///     .retry_policy(RetryPolicy::transient(3));
+/// pool.execute_with_options(tsih, cid, opts, |env| {
+///     TurCtx::from_execute_env(env, lun)
+/// }).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ExecuteOptions {
+    deadline: Option<Instant>,
+    retry_policy: Option<Arc<RetryPolicy>>,
+}
+
+impl ExecuteOptions {
+    /// No deadline, pool-wide retry policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the call once `deadline` passes. The deadline covers queueing,
+    /// every attempt and the backoff between them; a command still in
+    /// flight when it expires is aborted like a timed-out one.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Same as [`ExecuteOptions::deadline`], relative to now.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// Use `policy` instead of the pool-wide retry policy.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(Arc::new(policy));
+        self
+    }
+}
+
+/// How long [`Pool::logout`] lets a connection drain before logging it out.
+const LOGOUT_DRAIN_WAIT: Duration = Duration::from_secs(30);
+
+/// Reconnect events buffered per subscriber before the oldest are dropped.
+const RECONNECT_EVENT_CAPACITY: usize = 64;
+
+/// Lifecycle events buffered per subscriber before the oldest are dropped.
+const POOL_EVENT_CAPACITY: usize = 256;
+
+/// Time left until `deadline`, or `None` when there is no deadline.
+fn remaining(deadline: Option<Instant>) -> Option<Duration> {
+    deadline.map(|d| d.saturating_duration_since(Instant::now()))
+}
+
 /// Injected session/connection state used to build a state-machine context.
 #[derive(Clone)]
 pub struct ExecuteEnv {
@@ -118,10 +394,24 @@ impl Pool {
         Arc::new_cyclic(|self_weak| Self {
             sessions: DashMap::with_capacity(cfg.runtime.max_sessions as usize),
             max_sessions: cfg.runtime.max_sessions,
-            max_connections: cfg.login.limits.max_connections,
             max_connection_recovery_attempts: cfg
                 .runtime
                 .max_connection_recovery_attempts,
+            retry_policy: RwLock::new(Arc::new(cfg.runtime.retry.clone())),
+            medium_error_policy: RwLock::new(cfg.runtime.medium_error),
+            load_balancer: RwLock::new(cfg.runtime.load_balance.selector()),
+            spawner: RwLock::new(Arc::new(TokioSpawner)),
+            secret_provider: RwLock::new(None),
+            next_session: AtomicUsize::new(0),
+            lun_scheduler: LunScheduler::new(cfg.runtime.lun_queue_depth),
+            tasks: DashMap::new(),
+            command_timeout: (!cfg.runtime.command_timeout.is_zero())
+                .then_some(cfg.runtime.command_timeout),
+            tmf_timeout: cfg.runtime.tmf_timeout(),
+            stat_sn_ack_threshold: cfg.runtime.stat_sn_ack_threshold,
+            reconnect_policy: cfg.runtime.reconnect.clone(),
+            reconnect_events: broadcast::channel(RECONNECT_EVENT_CAPACITY).0,
+            events: broadcast::channel(POOL_EVENT_CAPACITY).0,
             self_weak: self_weak.clone(),
             cancel,
         })
@@ -133,28 +423,430 @@ impl Pool {
         self.cancel.clone()
     }
 
-    /// Login all sessions sequentially.
-    pub async fn login_sessions_from_cfg(&self, cfg: &Config) -> Result<Vec<Tsih>> {
+    /// Current pool-wide retry policy.
+    pub fn retry_policy(&self) -> Arc<RetryPolicy> {
+        self.retry_policy
+            .read()
+            .expect("retry policy lock poisoned")
+            .clone()
+    }
+
+    /// Replace the pool-wide retry policy used by [`Pool::execute_with_ctx`].
+    pub fn set_retry_policy(&self, policy: RetryPolicy) {
+        *self
+            .retry_policy
+            .write()
+            .expect("retry policy lock poisoned") = Arc::new(policy);
+    }
+
+    /// Current policy for MEDIUM ERRORs of LUN handle reads and writes.
+    pub fn medium_error_policy(&self) -> MediumErrorPolicy {
+        *self
+            .medium_error_policy
+            .read()
+            .expect("medium error policy lock poisoned")
+    }
+
+    /// Replace the policy for MEDIUM ERRORs of LUN handle reads and writes
+    /// (see [`crate::client::remap`]).
+    pub fn set_medium_error_policy(&self, policy: MediumErrorPolicy) {
+        *self
+            .medium_error_policy
+            .write()
+            .expect("medium error policy lock poisoned") = policy;
+    }
+
+    /// Replace the selector that spreads unpinned commands over the
+    /// connections of a session (see [`Pool::execute_balanced`]).
+    pub fn set_load_balancer(&self, selector: impl ConnectionSelector + 'static) {
+        *self
+            .load_balancer
+            .write()
+            .expect("load balancer lock poisoned") = Arc::new(selector);
+    }
+
+    /// Executor of the pool's background tasks.
+    pub fn spawner(&self) -> Arc<dyn Spawner> {
+        self.spawner.read().expect("spawner lock poisoned").clone()
+    }
+
+    /// Replace the executor of background tasks: read loops of connections
+    /// opened from now on, NOP-In replies, ExpStatSN acknowledgments,
+    /// connection supervision and reconnection. Tasks already running stay
+    /// where they are.
+    pub fn set_spawner(&self, spawner: impl Spawner + 'static) {
+        *self.spawner.write().expect("spawner lock poisoned") = Arc::new(spawner);
+    }
+
+    /// Installed source of CHAP credentials, if any.
+    pub fn secret_provider(&self) -> Option<Arc<dyn SecretProvider>> {
+        self.secret_provider
+            .read()
+            .expect("secret provider lock poisoned")
+            .clone()
+    }
+
+    /// Ask `provider` for the CHAP credentials of every login from now on
+    /// (see [`crate::cfg::secrets`]).
+    pub fn set_secret_provider(&self, provider: impl SecretProvider + 'static) {
+        *self
+            .secret_provider
+            .write()
+            .expect("secret provider lock poisoned") = Some(Arc::new(provider));
+    }
+
+    /// Connection of `tsih` the load balancer would use for a command
+    /// starting at `lba`.
+    ///
+    /// Draining connections are never picked; poisoned ones are skipped while
+    /// a healthy sibling exists.
+    pub fn pick_cid(&self, tsih: Tsih, lba: Option<u64>) -> error::Result<Cid> {
+        let sess = self
+            .sessions
+            .get(&tsih)
+            .with_context(|| format!("unknown TSIH={tsih}"))?
+            .clone();
+        let mut all: Vec<_> = sess
+            .conns
+            .iter()
+            .filter(|c| !c.conn.is_draining())
+            .map(|c| {
+                (
+                    c.conn.is_poisoned(),
+                    ConnectionLoad {
+                        cid: c.cid,
+                        in_flight: c.in_flight(),
+                    },
+                )
+            })
+            .collect();
+        if all.is_empty() {
+            return Err(IscsiError::msg(format!(
+                "TSIH={tsih} has no usable connections"
+            )));
+        }
+        all.sort_by_key(|(_, load)| load.cid);
+        let healthy: Vec<_> = all
+            .iter()
+            .filter(|(poisoned, _)| !poisoned)
+            .map(|(_, load)| *load)
+            .collect();
+        let candidates = if healthy.is_empty() {
+            all.into_iter().map(|(_, load)| load).collect()
+        } else {
+            healthy
+        };
+
+        let selector = self
+            .load_balancer
+            .read()
+            .expect("load balancer lock poisoned")
+            .clone();
+        let cid = selector.select(tsih, &candidates, lba);
+        if !candidates.iter().any(|c| c.cid == cid) {
+            return Err(IscsiError::msg(format!(
+                "load balancer picked CID={cid}, which is not a connection of \
+                 TSIH={tsih}"
+            )));
+        }
+        Ok(cid)
+    }
+
+    /// Same as [`Pool::execute_with_ctx`], but the connection is chosen by
+    /// the load balancer (`runtime.LoadBalance`). `lba` is the first block
+    /// the command touches, used by LBA affinity.
+    pub async fn execute_balanced<Ctx, Res, Build>(
+        &self,
+        tsih: Tsih,
+        lba: Option<u64>,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let cid = self.pick_cid(tsih, lba)?;
+        self.execute_with_ctx(tsih, cid, build).await
+    }
+
+    /// Handle for session `tsih`, the entry point for LUN-level I/O without
+    /// per-command contexts (see [`SessionHandle::lun`]).
+    pub fn session(&self, tsih: Tsih) -> error::Result<SessionHandle> {
+        if !self.sessions.contains_key(&tsih) {
+            return Err(IscsiError::msg(format!("unknown TSIH={tsih}")));
+        }
+        let pool = self.self_weak.upgrade().context("pool is being dropped")?;
+        Ok(SessionHandle::new(pool, tsih))
+    }
+
+    /// Session the pool would use for a command that may run anywhere.
+    ///
+    /// Sessions with a healthy connection are preferred. Among those, the
+    /// one with the fewest commands in flight for `lun` (when known) and then
+    /// overall wins; ties rotate so idle sessions share the work.
+    ///
+    /// Fails when the pool holds sessions to more than one target: the same
+    /// LUN number names a different logical unit on each, so a command could
+    /// land on the wrong device. Use [`Pool::target`] or
+    /// [`Pool::execute_on_target`] then.
+    pub fn pick_session(&self, lun: Option<Lun>) -> error::Result<Tsih> {
+        let targets = self.target_names();
+        if targets.len() > 1 {
+            return Err(IscsiError::msg(format!(
+                "pool has sessions to {} targets ({}); address one by name",
+                targets.len(),
+                targets.join(", ")
+            )));
+        }
+        self.pick_session_of(None, lun)
+    }
+
+    /// [`Pool::pick_session`] among the sessions to `target_name`, or all
+    /// sessions when `None`.
+    pub(crate) fn pick_session_of(
+        &self,
+        target_name: Option<&str>,
+        lun: Option<Lun>,
+    ) -> error::Result<Tsih> {
+        let mut candidates: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|sess| target_name.is_none_or(|name| *sess.target_name == *name))
+            .filter(|sess| sess.conns.iter().any(|c| !c.conn.is_draining()))
+            .map(|sess| {
+                let healthy = sess
+                    .conns
+                    .iter()
+                    .any(|c| !c.conn.is_poisoned() && !c.conn.is_draining());
+                let lun_load = lun.map_or(0, |lun| self.lun_in_flight(sess.tsih, lun));
+                let load: usize = sess.conns.iter().map(|c| c.in_flight()).sum();
+                (sess.tsih, (!healthy, lun_load, load))
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Err(match target_name {
+                Some(name) => anyhow!("pool has no logged-in sessions to {name}"),
+                None => anyhow!("pool has no logged-in sessions"),
+            }
+            .into());
+        }
+        candidates.sort_by_key(|(tsih, _)| *tsih);
+
+        let turn = self.next_session.fetch_add(1, Ordering::Relaxed);
+        let best = candidates
+            .iter()
+            .map(|(_, key)| *key)
+            .min()
+            .expect("candidates is not empty");
+        let tied: Vec<_> = candidates
+            .into_iter()
+            .filter(|(_, key)| *key == best)
+            .map(|(tsih, _)| tsih)
+            .collect();
+        Ok(tied[turn % tied.len()])
+    }
+
+    /// Run a command on whichever session and connection the pool picks
+    /// (see [`Pool::pick_session`] and [`Pool::pick_cid`]). Fails without
+    /// sending anything when the pool holds sessions to several targets.
+    ///
+    /// ```ignore
+    /// let rc = pool.execute_any(|env| {
+    ///     ReadCtx::from_execute_env(env, lun, 8, cdb)
+    /// }).await?;
+    /// ```
+    pub async fn execute_any<Ctx, Res, Build>(&self, build: Build) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let tsih = self.pick_session(None)?;
+        self.execute_balanced(tsih, None, build).await
+    }
+
+    /// Same as [`Pool::execute_any`] for a command addressing `lun`,
+    /// starting at block `lba` when known, so the per-LUN load and LBA
+    /// affinity steer the choice. Also refuses pools with sessions to
+    /// several targets.
+    pub async fn execute_any_for<Ctx, Res, Build>(
+        &self,
+        lun: Lun,
+        lba: Option<u64>,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let tsih = self.pick_session(Some(lun))?;
+        self.execute_balanced(tsih, lba, build).await
+    }
+
+    /// Snapshot of all sessions and connections, see [`PoolStatus`].
+    pub fn status(&self) -> PoolStatus {
+        // Serial-number maximum, so a window that wrapped still wins.
+        let ahead = |a: u32, b: u32| if (b.wrapping_sub(a) as i32) > 0 { b } else { a };
+        let mut sessions: Vec<SessionStatus> = self
+            .sessions
+            .iter()
+            .map(|sess| {
+                let mut connections: Vec<ConnectionStatus> = sess
+                    .conns
+                    .iter()
+                    .map(|c| {
+                        let health = c.conn.health();
+                        let mut tasks: Vec<TaskStatus> = self
+                            .tasks
+                            .iter()
+                            .filter(|t| t.key().0 == sess.tsih && t.value().0 == c.cid)
+                            .map(|t| TaskStatus {
+                                itt: t.key().1.get(),
+                                lun: t.value().1.get(),
+                            })
+                            .collect();
+                        tasks.sort_by_key(|t| t.itt);
+                        ConnectionStatus {
+                            cid: c.cid.get(),
+                            health,
+                            poison_reason: c.conn.poison_reason().map(str::to_string),
+                            exp_stat_sn: c.exp_stat_sn.load(Ordering::SeqCst),
+                            in_flight: c.in_flight(),
+                            pending_itts: c
+                                .conn
+                                .pending_itts()
+                                .into_iter()
+                                .map(Itt::get)
+                                .collect(),
+                            tasks,
+                        }
+                    })
+                    .collect();
+                connections.sort_by_key(|c| c.cid);
+                let windows: Vec<(u32, u32)> =
+                    sess.conns.iter().map(|c| c.conn.cmd_window()).collect();
+                let (exp_cmd_sn, max_cmd_sn) = windows
+                    .iter()
+                    .copied()
+                    .reduce(|(e1, m1), (e2, m2)| (ahead(e1, e2), ahead(m1, m2)))
+                    .unwrap_or_default();
+                SessionStatus {
+                    tsih: sess.tsih.get(),
+                    isid: sess.isid.to_string(),
+                    target_name: sess.target_name.to_string(),
+                    max_connections: sess.max_connections,
+                    cmd_sn: sess.cmd_sn.load(Ordering::SeqCst),
+                    exp_cmd_sn,
+                    max_cmd_sn,
+                    connections,
+                }
+            })
+            .collect();
+        sessions.sort_by_key(|s| s.tsih);
+        PoolStatus { sessions }
+    }
+
+    /// Maximum outstanding commands per (session, LUN); `0` means unlimited.
+    #[inline]
+    pub fn lun_queue_depth(&self) -> usize {
+        self.lun_scheduler.depth()
+    }
+
+    /// Number of commands currently admitted to the wire for (TSIH, LUN).
+    pub fn lun_in_flight(&self, tsih: Tsih, lun: Lun) -> usize {
+        self.lun_scheduler.in_flight(tsih, lun)
+    }
+
+    /// Subscribe to [`ReconnectEvent`]s emitted by the reconnection
+    /// supervisor (see `runtime.Reconnect`).
+    pub fn subscribe_reconnect_events(&self) -> broadcast::Receiver<ReconnectEvent> {
+        self.reconnect_events.subscribe()
+    }
+
+    /// Subscribe to lifecycle [`PoolEvent`]s (logins, connection failures,
+    /// reconnects, target logout requests, keep-alive timeouts).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<PoolEvent> {
+        self.events.subscribe()
+    }
+
+    pub(crate) fn emit_event(&self, event: PoolEvent) {
+        // No subscribers is fine; events are advisory.
+        let _ = self.events.send(event);
+    }
+
+    /// Login all sessions, up to `runtime.LoginParallelism` at a time.
+    ///
+    /// Returns the TSIHs in configuration order. If any login fails, the
+    /// others still complete and a [`SessionLoginError`] lists every failure.
+    pub async fn login_sessions_from_cfg(
+        &self,
+        cfg: &Config,
+    ) -> error::Result<Vec<Tsih>> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
-        ensure!(self.max_sessions > 0, "max_sessions must be > 0");
+        if self.max_sessions == 0 {
+            return Err(IscsiError::msg("max_sessions must be > 0"));
+        }
 
         let target_name: Arc<str> = Arc::from(cfg.login.identity.target_name.clone());
-        let mut tsihs = Vec::with_capacity(self.max_sessions as usize);
+        let isids = (0..self.max_sessions)
+            .map(|index| cfg.login.identity.session_isid(index as u16))
+            .collect::<Result<Vec<_>>>()?;
+        let pool = self.self_weak.upgrade().context("pool dropped")?;
+        let permits = Arc::new(Semaphore::new(cfg.runtime.login_parallelism.max(1)));
 
-        for _ in 0..self.max_sessions {
-            let child = self.cancel.child_token();
-            let conn = ClientConnection::connect(cfg.clone(), child).await?;
-            let (isid, _) = Isid::generate();
+        let mut logins = JoinSet::new();
+        for (index, isid) in isids.iter().copied().enumerate() {
+            let pool = pool.clone();
+            let cfg = cfg.clone();
+            let target_name = target_name.clone();
+            let permits = permits.clone();
+            logins.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let res = pool
+                    .connect_and_login(&cfg, target_name, isid, Tsih::NONE, Cid::ZERO)
+                    .await;
+                (index, res)
+            });
+        }
 
-            let tsih = self
-                .login_and_insert(target_name.clone(), isid, Cid::ZERO, conn)
-                .await?;
+        let mut results: Vec<Option<Result<Tsih>>> = isids.iter().map(|_| None).collect();
+        while let Some(joined) = logins.join_next().await {
+            let (index, res) = joined.context("login task failed")?;
+            results[index] = Some(res);
+        }
 
-            tsihs.push(tsih);
+        let mut logged_in = Vec::with_capacity(results.len());
+        let mut failed = Vec::new();
+        for (isid, res) in isids.into_iter().zip(results) {
+            match res.context("login task did not report")? {
+                Ok(tsih) => logged_in.push(tsih),
+                Err(e) => {
+                    warn!("login of session ISID={} failed: {:#}", isid, e);
+                    failed.push((isid, e.into()));
+                },
+            }
         }
+        if !failed.is_empty() {
+            return Err(SessionLoginError { logged_in, failed }.into());
+        }
+        Ok(logged_in)
+    }
 
-        Ok(tsihs)
+    /// Logs in a new session with `isid` and TSIH=0 (RFC 7143 § 6.3.5).
+    ///
+    /// If the target still holds a session for this ISID, e.g. one left over
+    /// after an initiator crash, it implicitly terminates that session and
+    /// the new one replaces it; any local session with the same ISID is
+    /// dropped and its outstanding commands fail.
+    pub async fn reinstate_session(
+        &self,
+        cfg: &Config,
+        isid: Isid,
+    ) -> error::Result<Tsih> {
+        let target_name: Arc<str> = Arc::from(cfg.login.identity.target_name.clone());
+        self.connect_and_login(cfg, target_name, isid, Tsih::NONE, Cid::ZERO)
+            .await
+            .map_err(Into::into)
     }
 
     /// Login via a single TCP connection.
@@ -165,20 +857,24 @@ impl Pool {
         isid: Isid,
         cid: Cid,
         conn: Arc<ClientConnection>,
-    ) -> Result<Tsih> {
+    ) -> error::Result<Tsih> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
         self.login_one_and_insert_impl(target_name, isid, Tsih::NONE, cid, conn)
             .await
+            .map_err(Into::into)
     }
 
     /// Add one more TCP connection into an existing session (known TSIH).
+    ///
+    /// Fails with [`MaxConnectionsError`] before logging in when the session
+    /// already has its negotiated MaxConnections.
     pub async fn add_connection_to_session(
         &self,
         tsih: Tsih,
         cid: Cid,
         conn: Arc<ClientConnection>,
-    ) -> Result<()> {
+    ) -> error::Result<()> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
         let (target_name, isid) = {
@@ -186,6 +882,13 @@ impl Pool {
                 .sessions
                 .get(&tsih)
                 .ok_or_else(|| anyhow::anyhow!("unknown TSIH={tsih}"))?;
+            if sess.conns.len() >= usize::from(sess.max_connections) {
+                return Err(anyhow::Error::from(MaxConnectionsError {
+                    tsih,
+                    max: sess.max_connections,
+                })
+                .into());
+            }
             (sess.target_name.clone(), sess.isid)
         };
         let _ = self
@@ -194,6 +897,80 @@ impl Pool {
         Ok(())
     }
 
+    /// Grow session `tsih` to `n` connections, opening and logging in the
+    /// missing ones concurrently on the lowest free CIDs.
+    ///
+    /// `n` may not exceed the session's MaxConnections. Connections that fail
+    /// to log in are listed in [`ScaleReport::failed`]; the ones that
+    /// succeeded stay in the session.
+    pub async fn scale_connections(
+        &self,
+        tsih: Tsih,
+        n: u16,
+    ) -> error::Result<ScaleReport> {
+        #[cfg(feature = "profiling-puffin")]
+        profiling::function_scope!();
+        let sess = self
+            .sessions
+            .get(&tsih)
+            .with_context(|| format!("unknown TSIH={tsih}"))?
+            .clone();
+        let cfg = sess
+            .conns
+            .iter()
+            .next()
+            .map(|c| c.conn.cfg.clone())
+            .with_context(|| format!("TSIH={tsih} has no connections"))?;
+        let max = sess.max_connections;
+        if n > max {
+            return Err(IscsiError::msg(format!(
+                "TSIH={tsih}: {n} connections requested but the negotiated \
+                 MaxConnections is {max}"
+            )));
+        }
+
+        let missing = usize::from(n).saturating_sub(sess.conns.len());
+        let cids: Vec<Cid> = (0..=u16::MAX)
+            .map(Cid::new)
+            .filter(|cid| !sess.conns.contains_key(cid))
+            .take(missing)
+            .collect();
+        let pool = self.self_weak.upgrade().context("pool dropped")?;
+
+        let mut logins = JoinSet::new();
+        for cid in cids {
+            let pool = pool.clone();
+            let cfg = cfg.clone();
+            let target_name = sess.target_name.clone();
+            let isid = sess.isid;
+            logins.spawn(async move {
+                let res = pool
+                    .connect_and_login(&cfg, target_name, isid, tsih, cid)
+                    .await;
+                (cid, res)
+            });
+        }
+
+        let mut report = ScaleReport::default();
+        while let Some(joined) = logins.join_next().await {
+            match joined {
+                Ok((cid, Ok(_))) => report.added.push(cid),
+                Ok((cid, Err(e))) => {
+                    warn!("adding CID={} to TSIH={} failed: {:#}", cid, tsih, e);
+                    report.failed.push((cid, e.into()));
+                },
+                Err(e) => {
+                    return Err(anyhow::Error::new(e)
+                        .context("login task failed")
+                        .into());
+                },
+            }
+        }
+        report.added.sort();
+        report.failed.sort_by_key(|(cid, _)| *cid);
+        Ok(report)
+    }
+
     fn drop_connection_local(&self, tsih: Tsih, cid: Cid) {
         let should_remove_session = if let Some(sess) = self.sessions.get(&tsih) {
             sess.conns.remove(&cid);
@@ -204,6 +981,7 @@ impl Pool {
 
         if should_remove_session {
             self.sessions.remove(&tsih);
+            self.lun_scheduler.forget_session(tsih);
         }
     }
 
@@ -241,15 +1019,10 @@ impl Pool {
             }
         }
 
-        let child = self.cancel.child_token();
-        let recovery = async {
-            let conn = ClientConnection::connect(cfg, child).await?;
-            let _ = self
-                .login_one_and_insert_impl(target_name, isid, tsih, cid, conn)
-                .await?;
-            Ok(())
-        }
-        .await;
+        let recovery = self
+            .connect_and_login(&cfg, target_name, isid, tsih, cid)
+            .await
+            .map(|_| ());
 
         if recovery.is_err()
             && let Some(previous) = removed
@@ -261,6 +1034,80 @@ impl Pool {
         recovery
     }
 
+    /// Opens a fresh TCP connection and logs it in. When the target answers
+    /// busy / resource unavailable, waits `DefaultTime2Wait` and tries again
+    /// on a new connection, up to `runtime.LoginBusyRetries` times. Logins to
+    /// an existing session wait the value negotiated for it; a new session
+    /// has negotiated nothing yet and waits the configured one.
+    async fn connect_and_login(
+        &self,
+        cfg: &Config,
+        target_name: Arc<str>,
+        isid: Isid,
+        tsih_hint: Tsih,
+        cid: Cid,
+    ) -> Result<Tsih> {
+        let retries = cfg.runtime.login_busy_retries;
+        let mut attempt = 0;
+        loop {
+            let conn = ClientConnection::connect_with_spawner(
+                cfg.clone(),
+                self.cancel.child_token(),
+                self.spawner(),
+            )
+            .await?;
+            let err = match self
+                .login_one_and_insert_impl(
+                    target_name.clone(),
+                    isid,
+                    tsih_hint,
+                    cid,
+                    conn,
+                )
+                .await
+            {
+                Ok(tsih) => return Ok(tsih),
+                Err(err) => err,
+            };
+
+            let status = err
+                .chain()
+                .find_map(|cause| match cause.downcast_ref::<IscsiError>() {
+                    Some(IscsiError::LoginRejected(status)) => Some(status),
+                    _ => cause.downcast_ref::<LoginStatusError>(),
+                })
+                .cloned();
+            if !tsih_hint.is_none()
+                && status
+                    .as_ref()
+                    .is_some_and(LoginStatusError::is_session_gone)
+            {
+                return Err(err.context(format!(
+                    "target no longer has TSIH={tsih_hint}; log in again with \
+                     Pool::reinstate_session"
+                )));
+            }
+            let busy = status.as_ref().is_some_and(LoginStatusError::is_retryable);
+            if !busy || attempt >= retries {
+                return Err(err);
+            }
+            attempt += 1;
+
+            let wait = self
+                .sessions
+                .get(&tsih_hint)
+                .map_or(cfg.login.timers.default_time2wait, |sess| sess.time2wait);
+            warn!(
+                "login to {target_name} rejected ({err:#}); retry {attempt}/{retries} \
+                 in {wait:?}"
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {},
+                _ = self.cancel.cancelled() => return Err(err.context("pool cancelled")),
+            }
+        }
+    }
+
     async fn login_one_and_insert_impl(
         &self,
         target_name: Arc<str>,
@@ -272,16 +1119,32 @@ impl Pool {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
         let mut l = LoginCtx::new(conn.clone(), isid, cid, tsih_hint);
-        match &conn.cfg.login.auth {
-            AuthConfig::Chap(_) => l.set_chap_login(),
-            AuthConfig::None => l.set_plain_login(),
-        }
+        l.set_auth_login(self.secret_provider().as_deref(), &target_name)
+            .await?;
 
         let login_pdu = l.execute(&self.cancel).await.context("login failed")?;
         let hdr = login_pdu.header_view()?;
+        // The target may lower the offer; a missing key means it accepted it.
+        let max_connections = response_key(&login_pdu, "MaxConnections")
+            .and_then(|v| v.parse::<u16>().ok())
+            .unwrap_or(conn.cfg.login.limits.max_connections)
+            .max(1);
+        // Targets that predate RFC 7143 do not answer the key; anything but
+        // an explicit ResponseFence or FastAbort keeps RFC 3720 semantics.
+        let task_reporting = match response_key(&login_pdu, "TaskReporting").as_deref() {
+            Some("ResponseFence") => TaskReporting::ResponseFence,
+            Some("FastAbort") => TaskReporting::FastAbort,
+            _ => TaskReporting::RFC3720,
+        };
+        let time2wait = response_key(&login_pdu, "DefaultTime2Wait")
+            .and_then(|v| v.parse::<u64>().ok())
+            .map_or(conn.cfg.login.timers.default_time2wait, Duration::from_secs);
 
         let tsih = Tsih::new(hdr.tsih.get());
         ensure!(!tsih.is_none(), "TSIH=0 in final Login Response");
+        if tsih_hint.is_none() {
+            self.drop_reinstated_sessions(&target_name, isid);
+        }
 
         let sess = self
             .sessions
@@ -291,11 +1154,15 @@ impl Pool {
                     tsih,
                     isid,
                     target_name: target_name.clone(),
-                    conns: DashMap::with_capacity(self.max_connections as usize),
+                    conns: DashMap::with_capacity(max_connections as usize),
+                    max_connections,
+                    task_reporting,
+                    time2wait,
                     cmd_sn: Arc::new(AtomicU32::new(hdr.exp_cmd_sn.get())),
                     itt_gen: Arc::new(IttGen::new(
                         hdr.get_initiator_task_tag().get().wrapping_add(1).into(),
                     )),
+                    inventory: LunInventory::default(),
                 })
             })
             .clone();
@@ -305,7 +1172,8 @@ impl Pool {
             Arc::new(Connection {
                 cid,
                 conn: conn.clone(),
-                exp_stat_sn: Arc::new(AtomicU32::new(hdr.stat_sn.get().wrapping_add(1))),
+                exp_stat_sn: conn.exp_stat_sn.clone(),
+                in_flight: AtomicUsize::new(0),
             }),
         );
         ensure!(
@@ -314,19 +1182,222 @@ impl Pool {
         );
 
         conn.bind_pool_session(self.self_weak.clone(), tsih, cid);
+        if let Some(entry) = sess.conns.get(&cid).map(|entry| entry.clone()) {
+            self.supervise_connection(tsih, entry);
+        }
+        if tsih_hint.is_none() {
+            self.emit_event(PoolEvent::SessionLoggedIn {
+                tsih,
+                isid,
+                target_name,
+            });
+        }
 
         Ok(tsih)
     }
 
-    /// Logout a single TCP connection (CID). Removes the entry on success.
-    async fn logout_connection(
+    /// A successful TSIH=0 login terminates every older session of the same
+    /// initiator port (ISID) on the target, so forget them locally too.
+    fn drop_reinstated_sessions(&self, target_name: &str, isid: Isid) {
+        let stale: Vec<Tsih> = self
+            .sessions
+            .iter()
+            .filter(|sess| sess.isid == isid && *sess.target_name == *target_name)
+            .map(|sess| sess.tsih)
+            .collect();
+        for tsih in stale {
+            warn!("TSIH={tsih} (ISID={isid}) was reinstated by a new login; dropping it");
+            self.drop_session_local(tsih, "session reinstated");
+        }
+    }
+
+    /// Register a session to `target_name` with `conn` as CID 0, as a login
+    /// would, without talking to the target.
+    #[cfg(test)]
+    pub(crate) fn insert_session(
+        &self,
+        tsih: Tsih,
+        target_name: &str,
+        conn: Arc<ClientConnection>,
+    ) {
+        let conns = DashMap::new();
+        conns.insert(
+            Cid::ZERO,
+            Arc::new(Connection {
+                cid: Cid::ZERO,
+                exp_stat_sn: conn.exp_stat_sn.clone(),
+                conn,
+                in_flight: AtomicUsize::new(0),
+            }),
+        );
+        self.sessions.insert(
+            tsih,
+            Arc::new(Session {
+                tsih,
+                isid: Isid::new([0x40, 0, 0, 0, 0, tsih.get() as u8]),
+                target_name: target_name.into(),
+                conns,
+                max_connections: 1,
+                task_reporting: TaskReporting::RFC3720,
+                time2wait: Duration::from_secs(2),
+                cmd_sn: Arc::new(AtomicU32::new(1)),
+                itt_gen: Arc::new(IttGen::new(1.into())),
+                inventory: LunInventory::default(),
+            }),
+        );
+    }
+
+    /// Whether `tsih` exists and has at least one connection that is not
+    /// poisoned.
+    pub fn session_is_healthy(&self, tsih: Tsih) -> bool {
+        self.sessions
+            .get(&tsih)
+            .is_some_and(|sess| sess.conns.iter().any(|entry| !entry.conn.is_poisoned()))
+    }
+
+    /// Forgets `tsih` without talking to the target: its connections are
+    /// poisoned, so outstanding commands fail with `reason`.
+    pub fn drop_session_local(&self, tsih: Tsih, reason: &str) {
+        let Some((_, sess)) = self.sessions.remove(&tsih) else {
+            return;
+        };
+        for entry in sess.conns.iter() {
+            entry.conn.poison(format!("TSIH={tsih}: {reason}"));
+        }
+        sess.conns.clear();
+        self.lun_scheduler.forget_session(tsih);
+    }
+
+    /// Watches `conn` and, once its transport dies, reports it as
+    /// [`PoolEvent::ConnectionFailed`] and re-establishes it in the
+    /// background according to `runtime.Reconnect`. A connection that was
+    /// logged out, killed or already replaced is left alone.
+    fn supervise_connection(&self, tsih: Tsih, conn: Arc<Connection>) {
+        let pool = self.self_weak.clone();
+        let cancel = self.cancel.clone();
+        self.spawner().spawn(Box::pin(async move {
+            conn.conn.closed().await;
+            if cancel.is_cancelled()
+                || !conn.conn.is_poisoned()
+                || conn.conn.is_shut_down()
+            {
+                return;
+            }
+            let cid = conn.cid;
+            let Some(this) = pool.upgrade() else {
+                return;
+            };
+            let reason = conn.conn.poison_reason().unwrap_or("unknown").to_string();
+            this.emit_event(PoolEvent::ConnectionFailed {
+                tsih,
+                cid,
+                reason: reason.clone(),
+            });
+            let policy = this.reconnect_policy.clone();
+            if !policy.is_enabled() {
+                return;
+            }
+            let time2wait = conn.conn.cfg.login.timers.default_time2wait;
+            this.emit_reconnect_event(ReconnectEvent::Lost { tsih, cid, reason });
+            drop(this);
+
+            for attempt in 1..=policy.max_attempts {
+                tokio::select! {
+                    _ = tokio::time::sleep(policy.backoff(attempt, time2wait)) => {},
+                    _ = cancel.cancelled() => return,
+                }
+                let Some(this) = pool.upgrade() else {
+                    return;
+                };
+                if !this.is_current_connection(tsih, &conn) {
+                    debug!("TSIH={tsih} CID={cid} no longer needs reconnecting");
+                    return;
+                }
+
+                this.emit_reconnect_event(ReconnectEvent::Attempt { tsih, cid, attempt });
+                match this.recover_connection(tsih, cid, conn.clone()).await {
+                    Ok(()) => {
+                        info!(
+                            "TSIH={tsih} CID={cid} reconnected after {attempt} \
+                             attempt(s)"
+                        );
+                        this.emit_reconnect_event(ReconnectEvent::Restored {
+                            tsih,
+                            cid,
+                            attempts: attempt,
+                        });
+                        this.emit_event(PoolEvent::Reconnected {
+                            tsih,
+                            cid,
+                            attempts: attempt,
+                        });
+                        return;
+                    },
+                    Err(error) => {
+                        warn!(
+                            "TSIH={tsih} CID={cid} reconnect attempt {attempt} failed: \
+                             {error:#}"
+                        );
+                        this.emit_reconnect_event(ReconnectEvent::AttemptFailed {
+                            tsih,
+                            cid,
+                            attempt,
+                            error: format!("{error:#}"),
+                        });
+                    },
+                }
+            }
+
+            if let Some(this) = pool.upgrade() {
+                warn!(
+                    "TSIH={tsih} CID={cid} giving up after {} reconnect attempt(s)",
+                    policy.max_attempts
+                );
+                this.emit_reconnect_event(ReconnectEvent::GaveUp {
+                    tsih,
+                    cid,
+                    attempts: policy.max_attempts,
+                });
+            }
+        }));
+    }
+
+    /// Whether `conn` is still the registered connection for (TSIH, CID).
+    fn is_current_connection(&self, tsih: Tsih, conn: &Arc<Connection>) -> bool {
+        self.sessions
+            .get(&tsih)
+            .and_then(|sess| sess.conns.get(&conn.cid).map(|c| Arc::ptr_eq(&c, conn)))
+            .unwrap_or(false)
+    }
+
+    fn emit_reconnect_event(&self, event: ReconnectEvent) {
+        // No subscribers is fine; events are advisory.
+        let _ = self.reconnect_events.send(event);
+    }
+
+    /// Log out one connection (CID) while its siblings keep serving traffic.
+    ///
+    /// The connection stops taking new commands (load-balanced calls move to
+    /// the siblings), the commands in flight on it get up to `max_wait` to
+    /// complete, then Logout with `reason` is sent on it and the CID is
+    /// removed from the session. With `CloseConnection` the session goes
+    /// away when this was its last connection; with
+    /// `RemoveConnectionForRecovery` it is kept so the CID can be added
+    /// again with [`Pool::add_connection_to_session`].
+    pub async fn logout_connection(
         &self,
         tsih: Tsih,
         cid: Cid,
         reason: LogoutReason,
-    ) -> Result<()> {
+        max_wait: Duration,
+    ) -> error::Result<()> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
+        if reason == LogoutReason::CloseSession {
+            return Err(
+                anyhow!("use Pool::logout_session to close the whole session").into(),
+            );
+        }
         let sess = self
             .sessions
             .get(&tsih)
@@ -338,6 +1409,13 @@ impl Pool {
             .with_context(|| format!("CID={cid} not found in TSIH={tsih}"))?
             .clone();
 
+        if let Err(e) = conn.conn.drain(max_wait).await {
+            warn!(
+                "TSIH={} CID={} did not drain before logout: {}",
+                tsih, cid, e
+            );
+        }
+
         let mut ctx = LogoutCtx::new(
             conn.conn.clone(),
             &sess.itt_gen,
@@ -348,20 +1426,21 @@ impl Pool {
         );
         ctx.execute(&conn.conn.stop_writes)
             .await
-            .context("logout (CloseConnection) failed")?;
+            .with_context(|| format!("logout ({reason:?}) of CID={cid} failed"))?;
 
         // Local cleanup
-        if reason != LogoutReason::RemoveConnectionForRecovery {
-            sess.conns.remove(&cid);
-            if sess.conns.is_empty() {
-                self.sessions.remove(&tsih);
-            }
+        sess.conns
+            .remove_if(&cid, |_, current| Arc::ptr_eq(current, &conn));
+        if reason == LogoutReason::CloseConnection && sess.conns.is_empty() {
+            self.sessions.remove(&tsih);
+            self.lun_scheduler.forget_session(tsih);
         }
+        conn.conn.kill_now();
         Ok(())
     }
 
     /// Logout all connections and remove the session from the pool.
-    pub async fn logout_session(&self, tsih: Tsih) -> Result<()> {
+    pub async fn logout_session(&self, tsih: Tsih) -> error::Result<()> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
         let sess = self
@@ -396,6 +1475,7 @@ impl Pool {
                 let _ = s.conns.remove(&cid);
             }
         }
+        self.lun_scheduler.forget_session(tsih);
         Ok(())
     }
 
@@ -408,12 +1488,15 @@ impl Pool {
     /// - RemoveConnectionForRecovery: requires `cid`, removes only that
     ///   connection; keeps the session even if it temporarily has 0 connections
     ///   (used for recovery).
+    ///
+    /// Connection logouts wait up to 30 s for in-flight commands; use
+    /// [`Pool::logout_connection`] to choose the bound.
     pub async fn logout(
         &self,
         tsih: Tsih,
         reason: LogoutReason,
         cid_opt: Option<Cid>,
-    ) -> Result<()> {
+    ) -> error::Result<()> {
         match reason {
             LogoutReason::CloseSession => self.logout_session(tsih).await,
             LogoutReason::CloseConnection | LogoutReason::RemoveConnectionForRecovery => {
@@ -421,6 +1504,7 @@ impl Pool {
                     tsih,
                     cid_opt.context("failed to get cid")?,
                     reason,
+                    LOGOUT_DRAIN_WAIT,
                 )
                 .await
             },
@@ -434,7 +1518,10 @@ impl Pool {
     /// 3) Send exactly one Logout(CloseSession) per session.
     /// 4) Half-close the write side (TCP FIN) on all connections.
     /// 5) Cancel the root token to stop remaining I/O.
-    pub async fn shutdown_gracefully(&self, max_wait_per_conn: Duration) -> Result<()> {
+    pub async fn shutdown_gracefully(
+        &self,
+        max_wait_per_conn: Duration,
+    ) -> error::Result<()> {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
         let all_connections: Vec<Arc<Connection>> = self
@@ -468,6 +1555,7 @@ impl Pool {
                         let _ = s.conns.remove(&cid);
                     }
                 }
+                self.lun_scheduler.forget_session(tsih);
             }
         }
 
@@ -489,6 +1577,12 @@ impl Pool {
     /// Build a state-machine context for (TSIH, CID), inject counters and run
     /// it.
     ///
+    /// Transient SCSI statuses are retried according to the pool-wide
+    /// [`RetryPolicy`] (see [`Pool::set_retry_policy`]). A poisoned
+    /// connection is recovered and the command re-issued only when the
+    /// context reports it as retry-safe; otherwise an
+    /// [`AmbiguousOutcomeError`] is returned.
+    ///
     /// Usage:
     /// ```ignore
     /// pool.execute_with_ctx(tsih, cid, |env| {
@@ -500,6 +1594,133 @@ impl Pool {
         tsih: Tsih,
         cid: Cid,
         build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let policy = self.retry_policy();
+        self.execute_with_policy(tsih, cid, &policy, build).await
+    }
+
+    /// Same as [`Pool::execute_with_ctx`], but with a per-call retry policy
+    /// instead of the pool-wide one.
+    ///
+    /// The context is rebuilt for every attempt, so each retry gets a fresh
+    /// ITT and CmdSN. Backoff sleeps are interrupted by pool cancellation.
+    pub async fn execute_with_policy<Ctx, Res, Build>(
+        &self,
+        tsih: Tsih,
+        cid: Cid,
+        policy: &RetryPolicy,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        self.execute_until(tsih, cid, policy, None, build).await
+    }
+
+    /// Same as [`Pool::execute_with_ctx`], but the whole call (queueing,
+    /// retries and the command itself) must finish before `deadline`.
+    ///
+    /// A command still in flight at the deadline is aborted with ABORT TASK
+    /// and reported as [`CommandTimeoutError`]; if the deadline passes
+    /// before the command is sent, [`DeadlineExceededError`] is returned.
+    pub async fn execute_with_deadline<Ctx, Res, Build>(
+        &self,
+        tsih: Tsih,
+        cid: Cid,
+        deadline: Instant,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        self.execute_with_options(
+            tsih,
+            cid,
+            ExecuteOptions::new().deadline(deadline),
+            build,
+        )
+        .await
+    }
+
+    /// Same as [`Pool::execute_with_ctx`], with per-call [`ExecuteOptions`].
+    pub async fn execute_with_options<Ctx, Res, Build>(
+        &self,
+        tsih: Tsih,
+        cid: Cid,
+        opts: ExecuteOptions,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let policy = opts.retry_policy.unwrap_or_else(|| self.retry_policy());
+        self.execute_until(tsih, cid, &policy, opts.deadline, build)
+            .await
+    }
+
+    async fn execute_until<Ctx, Res, Build>(
+        &self,
+        tsih: Tsih,
+        cid: Cid,
+        policy: &RetryPolicy,
+        deadline: Option<Instant>,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let max_attempts = policy.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            let error = match self
+                .execute_with_recovery(tsih, cid, deadline, &build)
+                .await
+            {
+                Ok(res) => return Ok(res),
+                Err(error) => IscsiError::from(error),
+            };
+            if attempt >= max_attempts || !policy.is_retriable(&error) {
+                return Err(error);
+            }
+
+            let delay = policy.backoff(attempt);
+            if remaining(deadline).is_some_and(|left| left <= delay) {
+                return Err(error);
+            }
+            warn!(
+                "TSIH={}, CID={} transient failure on attempt {}/{}, retrying in {:?}: \
+                 {}",
+                tsih, cid, attempt, max_attempts, delay, error
+            );
+            tokio::select! {
+                _ = self.cancel.cancelled() => return Err(error),
+                _ = tokio::time::sleep(delay) => {},
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Run one command, transparently recovering poisoned connections.
+    ///
+    /// A SCSI command that never reached the target because its connection
+    /// failed or started draining is re-dispatched to a healthy sibling of
+    /// the same session, with the CmdSN it already took so the command
+    /// window has no hole. One that was in flight is re-issued only when
+    /// retry-safe; otherwise it fails with [`AmbiguousOutcomeError`].
+    async fn execute_with_recovery<Ctx, Res, Build>(
+        &self,
+        tsih: Tsih,
+        cid: Cid,
+        deadline: Option<Instant>,
+        build: &Build,
     ) -> Result<Res>
     where
         Build: Fn(ExecuteEnv) -> Ctx,
@@ -507,7 +1728,14 @@ impl Pool {
     {
         #[cfg(feature = "profiling-puffin")]
         profiling::function_scope!();
-        for attempt in 0..=self.max_connection_recovery_attempts {
+        let mut cid = cid;
+        let mut reuse_cmd_sn = None;
+        let mut redispatches = 0;
+        let mut attempt = 0;
+        loop {
+            if remaining(deadline).is_some_and(|left| left.is_zero()) {
+                return Err(DeadlineExceededError { tsih, cid }.into());
+            }
             let sess = self
                 .sessions
                 .get(&tsih)
@@ -519,22 +1747,103 @@ impl Pool {
                 .with_context(|| format!("CID={cid} not found in TSIH={tsih}"))?
                 .clone();
 
-            if conn.conn.is_poisoned() {
+            let unsent = if conn.conn.is_poisoned() {
                 warn!(
                     "TSIH={}, CID={} is poisoned before execute attempt {}",
                     tsih,
                     cid,
                     attempt + 1,
                 );
+                true
             } else {
+                let cmd_sn = match reuse_cmd_sn.take() {
+                    Some(sn) => Arc::new(AtomicU32::new(sn)),
+                    None => sess.cmd_sn.clone(),
+                };
                 let mut ctx = build(ExecuteEnv {
                     conn: conn.conn.clone(),
                     itt_gen: sess.itt_gen.clone(),
-                    cmd_sn: sess.cmd_sn.clone(),
+                    cmd_sn,
                     exp_stat_sn: conn.exp_stat_sn.clone(),
                 });
-                match ctx.execute(&conn.conn.stop_writes).await {
+                // CmdSN is taken when the command is sent, so queueing here
+                // cannot leave a gap in the command window.
+                let bypass_aca = ctx.task_attribute() == TaskAttribute::ACA;
+                let _slot = match (ctx.target_lun(), deadline) {
+                    (Some(lun), Some(deadline)) => tokio::time::timeout_at(
+                        deadline,
+                        self.lun_scheduler
+                            .acquire(tsih, lun, bypass_aca, &self.cancel),
+                    )
+                    .await
+                    .map_err(|_| DeadlineExceededError { tsih, cid })??,
+                    (Some(lun), None) => {
+                        self.lun_scheduler
+                            .acquire(tsih, lun, bypass_aca, &self.cancel)
+                            .await?
+                    },
+                    (None, _) => None,
+                };
+                let _task = ctx.task_ref().map(|task| {
+                    self.tasks.insert((tsih, task.itt), (cid, task.lun));
+                    TaskGuard {
+                        tasks: &self.tasks,
+                        key: (tsih, task.itt),
+                    }
+                });
+                let _in_flight = InFlightGuard::new(&conn.in_flight);
+                let limit = match (self.command_timeout, remaining(deadline)) {
+                    (Some(timeout), Some(left)) => Some(timeout.min(left)),
+                    (timeout, left) => timeout.or(left),
+                };
+                let outcome = match limit {
+                    Some(limit) => {
+                        match tokio::time::timeout(
+                            limit,
+                            ctx.execute(&conn.conn.stop_writes),
+                        )
+                        .await
+                        {
+                            Ok(outcome) => outcome,
+                            Err(_) => {
+                                return Err(self
+                                    .abort_timed_out(&sess, &conn, ctx.task_ref(), limit)
+                                    .await
+                                    .into());
+                            },
+                        }
+                    },
+                    None => ctx.execute(&conn.conn.stop_writes).await,
+                };
+                self.ack_stat_sn_if_lagging(&sess, &conn);
+                match outcome {
                     Ok(res) => return Ok(res),
+                    Err(error)
+                        if was_not_sent(&error)
+                            && (conn.conn.is_poisoned()
+                                || (conn.conn.is_draining()
+                                    && self.sibling_cid(&sess, cid).is_some())) =>
+                    {
+                        warn!(
+                            "TSIH={}, CID={} refused the command before sending it: {}",
+                            tsih, cid, error
+                        );
+                        reuse_cmd_sn = ctx.task_ref().and_then(|task| task.cmd_sn);
+                        true
+                    },
+                    Err(error) if conn.conn.is_poisoned() && !ctx.is_retry_safe() => {
+                        warn!(
+                            "TSIH={}, CID={} poisoned during non-idempotent command; \
+                             not retrying: {}",
+                            tsih, cid, error
+                        );
+                        return Err(AmbiguousOutcomeError {
+                            tsih,
+                            cid,
+                            reason: format!("{error:#}"),
+                        }
+                        .into());
+                    },
                     Err(error) if conn.conn.is_poisoned() => {
                         warn!(
                             "TSIH={}, CID={} poisoned during execute attempt {}: {}",
@@ -543,9 +1852,30 @@ impl Pool {
                             attempt + 1,
                             error
                         );
+                        false
+                    },
+                    Err(error) => {
+                        if let Some(lun) = ctx.target_lun() {
+                            self.track_aca(tsih, lun, ctx.cdb(), &error);
+                        }
+                        self.note_unit_attention(tsih, ctx.target_lun(), &error);
+                        return Err(error);
                     },
-                    Err(error) => return Err(error),
                 }
+            };
+
+            // The target never saw the command: hand it to a sibling instead
+            // of waiting for this connection to be recovered.
+            if unsent
+                && redispatches < sess.conns.len()
+                && let Some(sibling) = self.sibling_cid(&sess, cid)
+            {
+                info!(
+                    "TSIH={tsih} re-dispatching command from CID={cid} to CID={sibling}"
+                );
+                redispatches += 1;
+                cid = sibling;
+                continue;
             }
 
             if attempt == self.max_connection_recovery_attempts {
@@ -574,13 +1904,305 @@ impl Pool {
                     );
                 },
             }
+            attempt += 1;
         }
+    }
 
-        Err(anyhow::anyhow!(
-            "connection recovery attempts exhausted for TSIH={}, CID={}",
-            tsih,
-            cid
-        ))
+    /// A healthy connection of `sess` other than `failed`, chosen by the load
+    /// balancer.
+    fn sibling_cid(&self, sess: &Session, failed: Cid) -> Option<Cid> {
+        self.pick_cid(sess.tsih, None).ok().filter(|&cid| {
+            cid != failed
+                && sess
+                    .conns
+                    .get(&cid)
+                    .is_some_and(|c| !c.conn.is_poisoned() && !c.conn.is_draining())
+        })
+    }
+
+    /// Hold the LUN's queue when a command's failure shows that an ACA
+    /// condition is now established: either a CHECK CONDITION for a CDB with
+    /// NACA=1 or an ACA ACTIVE rejection.
+    fn track_aca(
+        &self,
+        tsih: Tsih,
+        lun: Lun,
+        cdb: Option<&[u8; 16]>,
+        error: &anyhow::Error,
+    ) {
+        let Some(status) = error.downcast_ref::<ScsiStatusError>() else {
+            return;
+        };
+        let established = status.is_aca_active()
+            || (status.status == ScsiStatus::CheckCondition && cdb.is_some_and(has_naca));
+        if established && self.lun_scheduler.establish_aca(tsih, lun) {
+            warn!(
+                "ACA established on TSIH={}, {}; new commands are held until \
+                 Pool::clear_aca",
+                tsih, lun
+            );
+        }
+    }
+
+    /// Whether new commands on (TSIH, LUN) are held by an ACA condition.
+    pub fn is_aca_active(&self, tsih: Tsih, lun: Lun) -> bool {
+        self.lun_scheduler.is_aca_active(tsih, lun)
+    }
+
+    /// Send CLEAR ACA for `lun` on (TSIH, CID) and release commands held by
+    /// the queueing layer.
+    ///
+    /// Inspect the failed command (e.g. its sense data) before clearing: once
+    /// ACA is cleared the target may resume tasks that were blocked by it.
+    pub async fn clear_aca(&self, tsih: Tsih, cid: Cid, lun: Lun) -> error::Result<()> {
+        self.task_management(tsih, cid, lun, TaskManagementFunction::ClearAca)
+            .await
+            .context("CLEAR ACA failed")?;
+        self.lun_scheduler.clear_aca(tsih, lun);
+        Ok(())
+    }
+
+    /// Run a Task Management Function for `lun` on (TSIH, CID) and settle
+    /// the tasks it affects according to the TaskReporting key negotiated
+    /// for the session:
+    ///
+    /// - `RFC3720`: affected tasks are terminated once the TMF response
+    ///   arrives; the target sends nothing more for them.
+    /// - `ResponseFence`: as `RFC3720`, and new commands to the affected LUNs
+    ///   are fenced until the TMF response arrives.
+    /// - `FastAbort`: affected tasks are terminated on the TMF response, and
+    ///   responses the target still sends for them (e.g. TASK ABORTED) are
+    ///   dropped.
+    ///
+    /// Waiters of terminated tasks fail with [`TaskTerminatedError`]. Use
+    /// [`TmfCtx`] through [`Pool::execute_with_ctx`] for a bare exchange.
+    pub async fn task_management(
+        &self,
+        tsih: Tsih,
+        cid: Cid,
+        lun: Lun,
+        function: TaskManagementFunction,
+    ) -> error::Result<PduResponse<TaskMgmtResponse>> {
+        let (target_name, reporting) = {
+            let sess = self
+                .sessions
+                .get(&tsih)
+                .with_context(|| format!("unknown TSIH={tsih}"))?;
+            sess.conns
+                .get(&cid)
+                .with_context(|| format!("CID={cid} not found in TSIH={tsih}"))?;
+            (sess.target_name.clone(), sess.task_reporting)
+        };
+
+        let affected = self.affected_tasks(tsih, &target_name, lun, function);
+        let fenced: Vec<(Tsih, Lun)> = if reporting == TaskReporting::ResponseFence {
+            let mut luns: Vec<_> = affected.iter().map(|(t, _, _, l)| (*t, *l)).collect();
+            luns.push((tsih, lun));
+            // `fence` refuses duplicates, so each LUN is kept once.
+            luns.retain(|(t, l)| self.lun_scheduler.fence(*t, *l));
+            luns
+        } else {
+            Vec::new()
+        };
+
+        let outcome = self
+            .execute_with_ctx(tsih, cid, |env| {
+                TmfCtx::from_execute_env(env, lun, function)
+            })
+            .await;
+
+        if outcome.is_ok() {
+            let expect_late = reporting == TaskReporting::FastAbort;
+            for (t, itt, c, _) in &affected {
+                if let Some(conn) = self
+                    .sessions
+                    .get(t)
+                    .and_then(|sess| sess.conns.get(c).map(|e| e.clone()))
+                {
+                    conn.conn.terminate_task(*itt, expect_late);
+                }
+            }
+            if !affected.is_empty() {
+                debug!(
+                    "TMF {} on TSIH={}, {} terminated {} task(s) ({:?})",
+                    function,
+                    tsih,
+                    lun,
+                    affected.len(),
+                    reporting
+                );
+            }
+        }
+        for (t, l) in fenced {
+            self.lun_scheduler.unfence(t, l);
+        }
+        outcome
+    }
+
+    /// Tasks currently in flight that `function` terminates: the issuing
+    /// session's tasks on `lun` for ABORT TASK SET, every session's tasks on
+    /// `lun` for CLEAR TASK SET / LOGICAL UNIT RESET, and every task on the
+    /// target for a target reset.
+    fn affected_tasks(
+        &self,
+        tsih: Tsih,
+        target_name: &str,
+        lun: Lun,
+        function: TaskManagementFunction,
+    ) -> Vec<(Tsih, Itt, Cid, Lun)> {
+        use TaskManagementFunction as F;
+        let same_target = |t: &Tsih| {
+            self.sessions
+                .get(t)
+                .is_some_and(|sess| &*sess.target_name == target_name)
+        };
+        self.tasks
+            .iter()
+            .filter(|e| {
+                let ((t, _), (_, l)) = (e.key(), e.value());
+                match function {
+                    F::AbortTaskSet => *t == tsih && *l == lun,
+                    F::ClearTaskSet | F::LogicalUnitReset => *l == lun && same_target(t),
+                    F::TargetWarmReset | F::TargetColdReset => same_target(t),
+                    F::AbortTask | F::ClearAca | F::TaskReassign => false,
+                }
+            })
+            .map(|e| {
+                let ((t, itt), (c, l)) = (*e.key(), *e.value());
+                (t, itt, c, l)
+            })
+            .collect()
+    }
+
+    /// Acknowledge ExpStatSN with an immediate NOP-Out (ITT=0xFFFF_FFFF) once
+    /// `StatSnAckThreshold` StatSNs went unreported, so targets that hold
+    /// status resources until acknowledged do not stall. Runs in the
+    /// background; at most one acknowledgment per connection is in flight.
+    fn ack_stat_sn_if_lagging(&self, sess: &Session, conn: &Arc<Connection>) {
+        if self.stat_sn_ack_threshold == 0
+            || conn
+                .conn
+                .unacked_stat_sn(conn.exp_stat_sn.load(Ordering::SeqCst))
+                < self.stat_sn_ack_threshold
+            || !conn.conn.begin_stat_sn_ack()
+        {
+            return;
+        }
+
+        let mut ctx = NopCtx::new(
+            conn.conn.clone(),
+            Lun::ZERO,
+            &sess.itt_gen,
+            sess.cmd_sn.clone(),
+            conn.exp_stat_sn.clone(),
+            NopOutRequest::DEFAULT_TAG,
+        );
+        let conn = conn.clone();
+        self.spawner().spawn(Box::pin(async move {
+            if let Err(error) = ctx.acknowledge_stat_sn().await {
+                debug!(
+                    "ExpStatSN acknowledgment on CID={} failed: {error}",
+                    conn.cid
+                );
+            }
+            conn.conn.finish_stat_sn_ack();
+        }));
+    }
+
+    /// Abort a command that exceeded its timeout: drop its per-ITT channels,
+    /// send ABORT TASK if the command reached the target and build the error
+    /// reported to the caller. A connection that cannot even answer the
+    /// abort is poisoned so the next call recovers it.
+    async fn abort_timed_out(
+        &self,
+        sess: &Session,
+        conn: &Connection,
+        task: Option<TaskRef>,
+        limit: Duration,
+    ) -> CommandTimeoutError {
+        let mut err = CommandTimeoutError {
+            tsih: sess.tsih,
+            cid: conn.cid,
+            itt: task.map(|t| t.itt),
+            timeout: limit,
+            aborted: false,
+        };
+        let Some(task) = task else {
+            return err;
+        };
+
+        conn.conn.abandon_task(task.itt);
+        conn.conn.set_health(ConnectionHealth::Degraded);
+        let Some(ref_cmd_sn) = task.cmd_sn else {
+            return err;
+        };
+
+        let mut tmf = TmfCtx::abort_task(
+            ExecuteEnv {
+                conn: conn.conn.clone(),
+                itt_gen: sess.itt_gen.clone(),
+                cmd_sn: sess.cmd_sn.clone(),
+                exp_stat_sn: conn.exp_stat_sn.clone(),
+            },
+            task.lun,
+            task.itt,
+            ref_cmd_sn,
+        );
+        match tokio::time::timeout(self.tmf_timeout, tmf.execute(&conn.conn.stop_writes))
+            .await
+        {
+            Ok(Ok(_)) => {
+                conn.conn.forget_abandoned_task(task.itt);
+                err.aborted = true;
+            },
+            Ok(Err(error)) => {
+                warn!("{err}: ABORT TASK failed: {error}");
+            },
+            Err(_) => {
+                conn.conn
+                    .poison(format!("{err}: no response to ABORT TASK"));
+            },
+        }
+        err
+    }
+
+    /// Send a NOP-Out carrying `payload` on (TSIH, CID), check that the target
+    /// echoes it back in the NOP-In and return the measured round-trip time.
+    ///
+    /// A payload larger than the MaxRecvDataSegmentLength negotiated on the
+    /// connection is refused before anything is sent.
+    pub async fn ping(
+        &self,
+        tsih: Tsih,
+        cid: Cid,
+        payload: impl Into<Vec<u8>>,
+    ) -> error::Result<Duration> {
+        let payload = payload.into();
+        let mrdsl = self
+            .sessions
+            .get(&tsih)
+            .and_then(|sess| {
+                sess.conns
+                    .get(&cid)
+                    .map(|c| c.conn.cfg.login.flow.max_recv_data_segment_length)
+            })
+            .ok_or_else(|| IscsiError::msg(format!("unknown TSIH={tsih}, CID={cid}")))?;
+        if payload.len() > mrdsl as usize {
+            return Err(IscsiError::msg(format!(
+                "ping payload of {} bytes exceeds MaxRecvDataSegmentLength={mrdsl} on \
+                 TSIH={tsih}, CID={cid}",
+                payload.len()
+            )));
+        }
+        self.execute_with_ctx(tsih, cid, |env| {
+            PingCtx(
+                NopCtx::from_execute_env(env, Lun::ZERO, NopOutRequest::DEFAULT_TAG)
+                    .with_payload(payload.clone()),
+            )
+        })
+        .await
+        .with_context(|| format!("ping on TSIH={tsih}, CID={cid} failed"))
+        .map_err(Into::into)
     }
 
     /// Run SendTargets discovery against a portal using the provided config.
@@ -598,11 +2220,19 @@ impl Pool {
     ///     println!("Target: {} -> {:?}", t.target_name, t.target_addresses);
     /// }
     /// ```
-    pub async fn discover_targets(cfg: &Config) -> Result<Vec<DiscoveredTarget>> {
+    pub async fn discover_targets(cfg: &Config) -> error::Result<Vec<DiscoveredTarget>> {
         let cancel = CancellationToken::new();
         DiscoveryCtx::discover(cfg.clone(), cancel).await
     }
 
+    /// [`Pool::discover_targets`] with the CHAP credentials of the discovery
+    /// login taken from the pool's secret provider, if one is installed.
+    pub async fn discover(&self, cfg: &Config) -> error::Result<Vec<DiscoveredTarget>> {
+        let mut ctx = DiscoveryCtx::new(cfg.clone(), self.cancel.child_token());
+        ctx.secret_provider = self.secret_provider();
+        ctx.execute(&self.cancel).await.map_err(Into::into)
+    }
+
     pub(crate) async fn execute_nop_reply(
         &self,
         tsih: Tsih,
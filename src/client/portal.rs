@@ -0,0 +1,469 @@
+//! Parsing of iSCSI portal addresses.
+//!
+//! Portals come from configuration and from SendTargets `TargetAddress`
+//! values, which use the `host[:port][,tpgt]` syntax of RFC 7143 § 13.8.
+//! The host may be a DNS name, an IPv4 address or an IPv6 literal; IPv6
+//! literals need brackets when a port follows (`[fd00::1]:3260`), while a
+//! bare literal (`fd00::1`) is accepted and gets the default port.
+//!
+//! DNS names are resolved to every A/AAAA record and connected with a
+//! staggered race in the spirit of RFC 8305 ("Happy Eyeballs"), so dual-stack
+//! or round-robin targets do not stall on one unreachable address.
+//!
+//! Configured portals may also name a Unix domain socket
+//! (`unix:///run/target.sock`), for local target emulators and test
+//! harnesses.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::{
+    net::{TcpSocket, TcpStream, lookup_host},
+    task::JoinSet,
+    time::sleep,
+};
+
+use crate::cfg::config::{Config, TcpTuning};
+
+/// IANA-registered iSCSI port.
+pub const DEFAULT_ISCSI_PORT: u16 = 3260;
+
+/// Pause before starting the next connection attempt while earlier ones are
+/// still pending (RFC 8305 § 5 recommends 250 ms).
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Scheme prefix of portals that are Unix domain sockets.
+pub const UNIX_SCHEME: &str = "unix://";
+
+/// Where a configured portal points.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PortalTarget {
+    /// TCP endpoint.
+    Tcp(PortalAddr),
+    /// Unix domain socket path (`unix:///path/to/sock`).
+    Unix(PathBuf),
+}
+
+impl FromStr for PortalTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().strip_prefix(UNIX_SCHEME) {
+            Some("") => bail!("portal '{s}': empty socket path"),
+            Some(path) => Ok(Self::Unix(PathBuf::from(path))),
+            None => s.parse().map(Self::Tcp),
+        }
+    }
+}
+
+impl fmt::Display for PortalTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => addr.fmt(f),
+            Self::Unix(path) => write!(f, "{UNIX_SCHEME}{}", path.display()),
+        }
+    }
+}
+
+/// A target portal: host plus TCP port.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PortalAddr {
+    /// DNS name or IP address, without IPv6 brackets.
+    pub host: String,
+    /// TCP port.
+    pub port: u16,
+}
+
+impl PortalAddr {
+    /// `(host, port)` pair accepted by `TcpStream::connect`.
+    pub fn as_connect_target(&self) -> (&str, u16) {
+        (self.host.as_str(), self.port)
+    }
+}
+
+/// Local end of outgoing connections: the source binding for multi-homed
+/// initiators whose storage traffic must leave through a dedicated NIC, and
+/// the socket options set before connecting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalBind {
+    /// Source IP (any port).
+    pub address: Option<IpAddr>,
+    /// Interface name bound with `SO_BINDTODEVICE` (Linux only).
+    pub interface: Option<String>,
+    /// Buffer sizes, keep-alive, Nagle and DSCP settings.
+    pub tuning: TcpTuning,
+}
+
+impl LocalBind {
+    /// Takes `LocalAddress` / `BindInterface` from the transport section and
+    /// the socket options from `runtime.Tcp`.
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            address: cfg.login.transport.local_address,
+            interface: cfg.login.transport.bind_interface.clone(),
+            tuning: cfg.runtime.tcp.clone(),
+        }
+    }
+
+    /// Whether `target` can be reached from the bound source address.
+    fn allows(&self, target: &SocketAddr) -> bool {
+        self.address
+            .is_none_or(|ip| ip.is_ipv6() == target.is_ipv6())
+    }
+
+    /// Opens a socket bound as configured and connects it to `target`.
+    async fn connect(&self, target: SocketAddr) -> io::Result<TcpStream> {
+        let socket = if target.is_ipv6() {
+            TcpSocket::new_v6()?
+        } else {
+            TcpSocket::new_v4()?
+        };
+        if let Some(interface) = &self.interface {
+            #[cfg(target_os = "linux")]
+            socket.bind_device(Some(interface.as_bytes()))?;
+            #[cfg(not(target_os = "linux"))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("cannot bind to interface {interface}: Linux only"),
+            ));
+        }
+        if let Some(ip) = self.address {
+            socket.bind(SocketAddr::new(ip, 0))?;
+        }
+        tune(SockRef::from(&socket), &self.tuning, target.is_ipv6())?;
+        socket.connect(target).await
+    }
+}
+
+/// Applies `tuning` to a socket that is not connected yet.
+fn tune(socket: SockRef<'_>, tuning: &TcpTuning, ipv6: bool) -> io::Result<()> {
+    socket.set_tcp_nodelay(tuning.nodelay)?;
+    if tuning.recv_buffer_size > 0 {
+        socket.set_recv_buffer_size(tuning.recv_buffer_size)?;
+    }
+    if tuning.send_buffer_size > 0 {
+        socket.set_send_buffer_size(tuning.send_buffer_size)?;
+    }
+    if !tuning.keepalive_idle.is_zero() {
+        let mut keepalive = TcpKeepalive::new().with_time(tuning.keepalive_idle);
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd"
+        ))]
+        {
+            if !tuning.keepalive_interval.is_zero() {
+                keepalive = keepalive.with_interval(tuning.keepalive_interval);
+            }
+            if tuning.keepalive_retries > 0 {
+                keepalive = keepalive.with_retries(tuning.keepalive_retries);
+            }
+        }
+        socket.set_tcp_keepalive(&keepalive)?;
+    }
+    if let Some(dscp) = tuning.dscp {
+        let tos = u32::from(dscp) << 2;
+        if ipv6 {
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "freebsd"
+            ))]
+            socket.set_tclass_v6(tos)?;
+        } else {
+            socket.set_tos_v4(tos)?;
+        }
+    }
+    Ok(())
+}
+
+/// Orders resolved addresses by alternating families, starting with the
+/// family of the first record (RFC 8305 § 4).
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_v6 = first.is_ipv6();
+    let (mut primary, mut secondary): (Vec<_>, Vec<_>) =
+        addrs.into_iter().partition(|a| a.is_ipv6() == first_v6);
+    let mut out = Vec::with_capacity(primary.len() + secondary.len());
+    primary.reverse();
+    secondary.reverse();
+    loop {
+        match (primary.pop(), secondary.pop()) {
+            (None, None) => return out,
+            (a, b) => out.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Resolves `addr` and connects from `local` to whichever address answers
+/// first.
+///
+/// Attempts start in [`interleave_families`] order, each one
+/// [`CONNECTION_ATTEMPT_DELAY`] after the previous or as soon as the previous
+/// one fails; the first established socket wins and the others are dropped.
+pub(crate) async fn connect_happy_eyeballs(
+    addr: &PortalAddr,
+    local: &LocalBind,
+) -> io::Result<TcpStream> {
+    let resolved: Vec<SocketAddr> = lookup_host(addr.as_connect_target())
+        .await?
+        .filter(|target| local.allows(target))
+        .collect();
+    let mut pending = interleave_families(resolved).into_iter();
+    let Some(first) = pending.next() else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{addr}: resolved to no address usable from LocalAddress"),
+        ));
+    };
+
+    let mut attempts = JoinSet::new();
+    attempts.spawn({
+        let local = local.clone();
+        async move { local.connect(first).await }
+    });
+    loop {
+        let more = pending.len() > 0;
+        tokio::select! {
+            Some(joined) = attempts.join_next() => {
+                let error = match joined {
+                    Ok(Ok(stream)) => return Ok(stream),
+                    Ok(Err(error)) => error,
+                    Err(error) => io::Error::other(error),
+                };
+                if let Some(next) = pending.next() {
+                    attempts.spawn({
+                        let local = local.clone();
+                        async move { local.connect(next).await }
+                    });
+                } else if attempts.is_empty() {
+                    return Err(error);
+                }
+            },
+            _ = sleep(CONNECTION_ATTEMPT_DELAY), if more => {
+                if let Some(next) = pending.next() {
+                    attempts.spawn({
+                        let local = local.clone();
+                        async move { local.connect(next).await }
+                    });
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for PortalAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // Drop a trailing portal group tag (",1") from SendTargets replies.
+        let addr = s.split_once(',').map_or(s, |(addr, _)| addr).trim();
+        if addr.is_empty() {
+            bail!("empty portal address");
+        }
+
+        let (host, port) = if let Some(rest) = addr.strip_prefix('[') {
+            let (host, tail) = rest
+                .split_once(']')
+                .with_context(|| format!("portal '{s}': missing ']'"))?;
+            host.parse::<Ipv6Addr>()
+                .with_context(|| format!("portal '{s}': invalid IPv6 literal"))?;
+            let port = match tail {
+                "" => None,
+                tail => Some(
+                    tail.strip_prefix(':')
+                        .with_context(|| format!("portal '{s}': junk after ']'"))?,
+                ),
+            };
+            (host, port)
+        } else if addr.parse::<Ipv6Addr>().is_ok() {
+            (addr, None)
+        } else {
+            match addr.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (addr, None),
+            }
+        };
+
+        if host.is_empty() {
+            bail!("portal '{s}': empty host");
+        }
+        let port = match port {
+            Some(port) => port
+                .parse::<u16>()
+                .with_context(|| format!("portal '{s}': invalid port"))?,
+            None => DEFAULT_ISCSI_PORT,
+        };
+        Ok(Self {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for PortalAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> PortalAddr {
+        s.parse().expect("valid portal")
+    }
+
+    #[test]
+    fn parses_ipv4_names_and_default_port() {
+        assert_eq!(
+            parse("10.0.0.1:3261").as_connect_target(),
+            ("10.0.0.1", 3261)
+        );
+        assert_eq!(parse("10.0.0.1").port, DEFAULT_ISCSI_PORT);
+        assert_eq!(
+            parse("san.example:860,2").as_connect_target(),
+            ("san.example", 860)
+        );
+    }
+
+    #[test]
+    fn parses_ipv6_literals() {
+        assert_eq!(
+            parse("[fd00::1]:3261").as_connect_target(),
+            ("fd00::1", 3261)
+        );
+        assert_eq!(parse("[fd00::1]").port, DEFAULT_ISCSI_PORT);
+        assert_eq!(parse("fd00::1").as_connect_target(), ("fd00::1", 3260));
+        assert_eq!(parse("[fd00::1]:3260,1").to_string(), "[fd00::1]:3260");
+    }
+
+    #[test]
+    fn parses_unix_socket_portals() {
+        assert_eq!(
+            "unix:///run/tgt.sock".parse::<PortalTarget>().ok(),
+            Some(PortalTarget::Unix("/run/tgt.sock".into()))
+        );
+        assert_eq!(
+            "10.0.0.1".parse::<PortalTarget>().ok(),
+            Some(PortalTarget::Tcp(parse("10.0.0.1:3260")))
+        );
+        assert!("unix://".parse::<PortalTarget>().is_err());
+        assert_eq!(
+            PortalTarget::Unix("/tmp/t.sock".into()).to_string(),
+            "unix:///tmp/t.sock"
+        );
+    }
+
+    #[test]
+    fn interleaves_address_families() {
+        let addrs: Vec<SocketAddr> = ["[::1]:1", "[::2]:1", "[::3]:1", "10.0.0.1:1"]
+            .iter()
+            .map(|a| a.parse().expect("socket addr"))
+            .collect();
+        let ordered: Vec<String> = interleave_families(addrs)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(ordered, ["[::1]:1", "10.0.0.1:1", "[::2]:1", "[::3]:1"]);
+    }
+
+    #[tokio::test]
+    async fn happy_eyeballs_connects_through_dns_name() -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let accept = tokio::spawn(async move { listener.accept().await.map(|_| ()) });
+
+        let portal: PortalAddr = format!("localhost:{port}").parse()?;
+        let stream = connect_happy_eyeballs(&portal, &LocalBind::default()).await?;
+        assert!(stream.peer_addr()?.ip().is_loopback());
+        accept.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn binds_requested_local_address() -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let accept = tokio::spawn(async move { listener.accept().await });
+
+        let local = LocalBind {
+            address: Some("127.0.0.1".parse()?),
+            ..LocalBind::default()
+        };
+        let portal: PortalAddr = format!("localhost:{port}").parse()?;
+        let stream = connect_happy_eyeballs(&portal, &local).await?;
+        let (_, peer) = accept.await??;
+        assert_eq!(stream.local_addr()?, peer);
+        assert_eq!(peer.ip(), local.address.expect("set"));
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn applies_tcp_tuning() -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let accept = tokio::spawn(async move { listener.accept().await.map(|_| ()) });
+
+        let local = LocalBind {
+            tuning: TcpTuning {
+                nodelay: false,
+                recv_buffer_size: 1 << 20,
+                send_buffer_size: 1 << 20,
+                keepalive_idle: Duration::from_secs(30),
+                keepalive_interval: Duration::from_secs(5),
+                keepalive_retries: 4,
+                dscp: Some(46),
+            },
+            ..LocalBind::default()
+        };
+        let portal: PortalAddr = format!("127.0.0.1:{port}").parse()?;
+        let stream = connect_happy_eyeballs(&portal, &local).await?;
+        accept.await??;
+
+        let socket = SockRef::from(&stream);
+        assert!(!socket.tcp_nodelay()?);
+        assert!(socket.keepalive()?);
+        assert_eq!(socket.tcp_keepalive_time()?, Duration::from_secs(30));
+        assert_eq!(socket.tcp_keepalive_interval()?, Duration::from_secs(5));
+        assert_eq!(socket.tcp_keepalive_retries()?, 4);
+        assert_eq!(socket.tos_v4()?, 46 << 2);
+        // Linux doubles the requested size for bookkeeping overhead.
+        assert!(socket.recv_buffer_size()? >= 1 << 20);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_portals() {
+        for bad in [
+            "",
+            "[fd00::1",
+            "[nope]:3260",
+            "[fd00::1]x",
+            "host:port",
+            ":3260",
+        ] {
+            assert!(bad.parse::<PortalAddr>().is_err(), "{bad}");
+        }
+    }
+}
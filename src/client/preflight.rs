@@ -0,0 +1,245 @@
+//! Readiness checks run after login, before a workload starts.
+//!
+//! [`Pool::preflight`] sends TEST UNIT READY, INQUIRY and READ CAPACITY for
+//! each LUN on every connection of every session, so a bad path or an
+//! unready LUN is found before data is pushed. The [`PreflightReport`]
+//! lists one [`PreflightCheck`] per (connection, LUN);
+//! [`PreflightReport::ensure_ready`] turns the failures into an error.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{fmt, sync::Arc};
+
+use anyhow::Context;
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+use crate::{
+    client::{handles::Capacity, pool_sessions::Pool},
+    control_block::inquiry::InquiryStandard,
+    error,
+    models::identifiers::{Cid, Lun, Tsih},
+};
+
+/// Command of a preflight check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflightStep {
+    /// TEST UNIT READY.
+    TestUnitReady,
+    /// Standard INQUIRY.
+    Inquiry,
+    /// READ CAPACITY(10), and (16) for large LUNs.
+    ReadCapacity,
+}
+
+impl fmt::Display for PreflightStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::TestUnitReady => "TEST UNIT READY",
+            Self::Inquiry => "INQUIRY",
+            Self::ReadCapacity => "READ CAPACITY",
+        })
+    }
+}
+
+/// Outcome of the checks of one LUN on one connection.
+#[derive(Debug)]
+pub struct PreflightCheck {
+    /// Session checked.
+    pub tsih: Tsih,
+    /// Connection of the session the commands went through.
+    pub cid: Cid,
+    /// LUN checked.
+    pub lun: Lun,
+    /// Standard INQUIRY data, once INQUIRY succeeded.
+    pub inquiry: Option<InquiryStandard>,
+    /// Capacity, once READ CAPACITY succeeded.
+    pub capacity: Option<Capacity>,
+    /// The step that failed and why; the later steps were not run.
+    pub failure: Option<(PreflightStep, error::IscsiError)>,
+}
+
+impl PreflightCheck {
+    /// Every step succeeded.
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Result of a [`Pool::preflight`].
+#[derive(Debug, Default)]
+pub struct PreflightReport {
+    /// One check per (session, connection, LUN), in that order.
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// Every LUN passed on every connection.
+    pub fn is_ready(&self) -> bool {
+        self.checks.iter().all(PreflightCheck::is_ready)
+    }
+
+    /// The checks that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &PreflightCheck> {
+        self.checks.iter().filter(|c| !c.is_ready())
+    }
+
+    /// `self` when ready, else an error naming every failed check.
+    pub fn ensure_ready(self) -> error::Result<Self> {
+        if self.is_ready() {
+            return Ok(self);
+        }
+        let failed = self
+            .failures()
+            .filter_map(|c| {
+                let (step, e) = c.failure.as_ref()?;
+                Some(format!(
+                    "TSIH={} CID={} {}: {step} failed: {e}",
+                    c.tsih, c.cid, c.lun
+                ))
+            })
+            .collect::<Vec<_>>();
+        Err(error::IscsiError::msg(format!(
+            "preflight failed on {} of {} checks: {}",
+            failed.len(),
+            self.checks.len(),
+            failed.join("; ")
+        )))
+    }
+}
+
+impl Pool {
+    /// Check `luns` on every connection of every session: TEST UNIT READY
+    /// (repeated once, as the first one after login usually reports a Unit
+    /// Attention), INQUIRY and READ CAPACITY. An empty `luns` checks the
+    /// LUNs each session reports (REPORT LUNS). The connections are checked
+    /// in parallel; the capacities read update the session caches.
+    ///
+    /// Failed checks are part of the report, see
+    /// [`PreflightReport::ensure_ready`]; only an unreachable LUN list fails
+    /// the call.
+    pub async fn preflight(&self, luns: &[Lun]) -> error::Result<PreflightReport> {
+        let tsihs = self.sessions.iter().map(|s| *s.key()).collect::<Vec<_>>();
+        let mut tasks = JoinSet::new();
+        for tsih in tsihs {
+            let session_luns = match luns {
+                [] => match self.cached_luns(tsih) {
+                    Some(luns) => luns,
+                    None => self.report_luns(tsih).await?,
+                },
+                luns => luns.to_vec(),
+            };
+            let pool = Arc::clone(self.session(tsih)?.pool());
+            let Some(session) = self.sessions.get(&tsih) else {
+                continue;
+            };
+            let mut cids = session.conns.iter().map(|c| *c.key()).collect::<Vec<_>>();
+            drop(session);
+            cids.sort_unstable();
+            for cid in cids {
+                let (pool, luns) = (pool.clone(), session_luns.clone());
+                tasks.spawn(async move {
+                    let mut checks = Vec::with_capacity(luns.len());
+                    for lun in luns {
+                        checks.push(pool.preflight_lun(tsih, cid, lun).await);
+                    }
+                    checks
+                });
+            }
+        }
+
+        let mut report = PreflightReport::default();
+        while let Some(joined) = tasks.join_next().await {
+            report
+                .checks
+                .extend(joined.context("preflight task failed")?);
+        }
+        report.checks.sort_by_key(|c| (c.tsih, c.cid, c.lun.get()));
+        match report.failures().count() {
+            0 => info!("preflight: {} checks passed", report.checks.len()),
+            n => warn!("preflight: {n} of {} checks failed", report.checks.len()),
+        }
+        Ok(report)
+    }
+
+    async fn preflight_lun(&self, tsih: Tsih, cid: Cid, lun: Lun) -> PreflightCheck {
+        let mut check = PreflightCheck {
+            tsih,
+            cid,
+            lun,
+            inquiry: None,
+            capacity: None,
+            failure: None,
+        };
+        let handle = match self.session(tsih) {
+            Ok(session) => session.lun(lun).on_connection(cid),
+            Err(e) => {
+                check.failure = Some((PreflightStep::TestUnitReady, e));
+                return check;
+            },
+        };
+        if let Err(e) = handle.test_unit_ready().await {
+            debug!("TSIH={tsih} CID={cid} {lun}: first TEST UNIT READY: {e}");
+            if let Err(e) = handle.test_unit_ready().await {
+                check.failure = Some((PreflightStep::TestUnitReady, e));
+                return check;
+            }
+        }
+
+        match handle.inquiry().await {
+            Ok(inquiry) => check.inquiry = Some(inquiry),
+            Err(e) => {
+                check.failure = Some((PreflightStep::Inquiry, e));
+                return check;
+            },
+        }
+
+        // Always sent, so every connection is checked; the result lands in
+        // the session's capacity and protection caches like any other.
+        match handle.read_capacity().await {
+            Ok(capacity) => check.capacity = Some(capacity),
+            Err(e) => check.failure = Some((PreflightStep::ReadCapacity, e)),
+        }
+        check
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IscsiError;
+
+    fn check(cid: u16, failure: Option<(PreflightStep, IscsiError)>) -> PreflightCheck {
+        PreflightCheck {
+            tsih: Tsih::new(1),
+            cid: Cid::new(cid),
+            lun: Lun::ZERO,
+            inquiry: None,
+            capacity: None,
+            failure,
+        }
+    }
+
+    #[test]
+    fn failed_checks_make_the_report_not_ready() {
+        let ready = PreflightReport {
+            checks: vec![check(0, None), check(1, None)],
+        };
+        assert!(ready.ensure_ready().is_ok());
+
+        let failure = (
+            PreflightStep::TestUnitReady,
+            IscsiError::Timeout("no response".into()),
+        );
+        let report = PreflightReport {
+            checks: vec![check(0, None), check(1, Some(failure))],
+        };
+        assert!(!report.is_ready());
+        assert_eq!(report.failures().count(), 1);
+        let e = report.ensure_ready().expect_err("one check failed");
+        assert!(e.to_string().contains("CID=1"), "{e}");
+        assert!(e.to_string().contains("TEST UNIT READY failed"), "{e}");
+    }
+}
@@ -0,0 +1,139 @@
+//! Automatic reconnection of connections that died underneath a session.
+//!
+//! When the read loop of a pooled connection exits (target reset, TCP error,
+//! failed keep-alive), the pool's supervisor waits at least
+//! `DefaultTime2Wait`, opens a new TCP connection and logs it in with the
+//! same ISID/TSIH/CID, replacing the dead connection in its session.
+//! [`ReconnectPolicy`] bounds the attempts and spaces them with jittered
+//! exponential backoff; every step is published as a [`ReconnectEvent`].
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::time::Duration;
+
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+
+use crate::models::identifiers::{Cid, Tsih};
+
+/// How (and whether) the pool re-establishes a connection that died.
+///
+/// The default policy is disabled (`MaxAttempts: 0`); dead connections are
+/// then only recovered on demand by the next command issued on them.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    #[serde(rename = "MaxAttempts")]
+    /// Reconnect attempts per connection loss; `0` disables the supervisor.
+    pub max_attempts: u32,
+
+    #[serde(rename = "InitialBackoffMs")]
+    /// Pause before the first attempt, in milliseconds. Never shorter than
+    /// `DefaultTime2Wait`.
+    pub initial_backoff_ms: u64,
+
+    #[serde(rename = "MaxBackoffMs")]
+    /// Upper bound for a single pause, in milliseconds.
+    pub max_backoff_ms: u64,
+
+    #[serde(rename = "Jitter")]
+    /// Random spread applied to every pause, as a fraction in `0.0..=1.0`
+    /// (`0.2` means ±20 %).
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 0,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 30_000,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns whether dead connections are reconnected automatically.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.max_attempts > 0
+    }
+
+    /// Pause before attempt number `attempt` (1-based), doubling from
+    /// `InitialBackoffMs` up to `MaxBackoffMs`, jittered, and never shorter
+    /// than `time2wait`.
+    pub fn backoff(&self, attempt: u32, time2wait: Duration) -> Duration {
+        let exp = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let ms = (self.initial_backoff_ms as f64 * 2f64.powi(exp))
+            .min(self.max_backoff_ms as f64)
+            .max(0.0);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rand::rng().random_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+        Duration::from_millis((ms * factor) as u64).max(time2wait)
+    }
+}
+
+/// Progress of the reconnection supervisor for one connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReconnectEvent {
+    /// The connection died and will be re-established.
+    Lost {
+        tsih: Tsih,
+        cid: Cid,
+        /// Why the connection was considered dead.
+        reason: String,
+    },
+    /// A reconnect attempt is starting.
+    Attempt { tsih: Tsih, cid: Cid, attempt: u32 },
+    /// A reconnect attempt failed; another one may follow.
+    AttemptFailed {
+        tsih: Tsih,
+        cid: Cid,
+        attempt: u32,
+        error: String,
+    },
+    /// The connection was logged in again and is back in its session.
+    Restored { tsih: Tsih, cid: Cid, attempts: u32 },
+    /// All attempts failed; the connection stays down.
+    GaveUp { tsih: Tsih, cid: Cid, attempts: u32 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_is_capped_and_respects_time2wait() {
+        let p = ReconnectPolicy {
+            max_attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 350,
+            jitter: 0.0,
+        };
+        assert!(p.is_enabled());
+        assert_eq!(p.backoff(1, Duration::ZERO), Duration::from_millis(100));
+        assert_eq!(p.backoff(2, Duration::ZERO), Duration::from_millis(200));
+        assert_eq!(p.backoff(3, Duration::ZERO), Duration::from_millis(350));
+        assert_eq!(p.backoff(1, Duration::from_secs(2)), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let p = ReconnectPolicy {
+            max_attempts: 1,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 1_000,
+            jitter: 0.5,
+        };
+        for _ in 0..100 {
+            let d = p.backoff(1, Duration::ZERO);
+            assert!(d >= Duration::from_millis(500) && d <= Duration::from_millis(1_500));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Bad block handling: REASSIGN BLOCKS and the medium-error policy.
+//!
+//! [`LunHandle::reassign_blocks`] asks the target to move LBAs to spare
+//! blocks. With a [`MediumErrorPolicy`] other than `Off` (set through
+//! `runtime.MediumError` or [`Pool::set_medium_error_policy`]),
+//! [`LunHandle::read_at`] and [`LunHandle::write_at`] also look at the
+//! MEDIUM ERROR sense of a failed command: the LBA in its INFORMATION field
+//! is recorded on the session ([`LunHandle::bad_blocks`]) and published as
+//! [`PoolEvent::MediumError`]; under `Reassign` the block is reassigned and
+//! the command is sent once more.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+#[cfg(doc)]
+use crate::client::pool_sessions::Pool;
+use crate::{
+    client::{events::PoolEvent, handles::LunHandle},
+    control_block::reassign_blocks::{
+        SENSE_KEY_MEDIUM_ERROR, build_reassign_blocks, fill_reassign_blocks_parameters,
+    },
+    error::{self, IscsiError},
+};
+
+/// What READ / WRITE through a [`LunHandle`] do on a MEDIUM ERROR.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MediumErrorPolicy {
+    /// Fail the command, nothing else.
+    #[default]
+    #[serde(rename = "Off", alias = "off")]
+    Off,
+    /// Record the bad LBA and publish [`PoolEvent::MediumError`], then fail
+    /// the command.
+    #[serde(rename = "Record", alias = "record")]
+    Record,
+    /// Record the bad LBA, REASSIGN it and send the command once more.
+    #[serde(rename = "Reassign", alias = "reassign")]
+    Reassign,
+}
+
+impl LunHandle {
+    /// Move `lbas` to spare blocks (REASSIGN BLOCKS). The data of a
+    /// reassigned block is lost unless the target could still recover it.
+    pub async fn reassign_blocks(&self, lbas: &[u64]) -> error::Result<()> {
+        if lbas.is_empty() {
+            return Ok(());
+        }
+        let long_lba = lbas.iter().any(|&lba| lba > u32::MAX as u64);
+        let long_list = lbas.len() * if long_lba { 8 } else { 4 } > u16::MAX as usize;
+        let params = fill_reassign_blocks_parameters(lbas, long_lba, long_list)?;
+        let mut cdb = [0u8; 16];
+        build_reassign_blocks(&mut cdb, long_lba, long_list, 0);
+        self.clone()
+            .without_retry()
+            .write_cdb(cdb, params, None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// LBAs that failed with MEDIUM ERROR on this session, ascending, under
+    /// a [`MediumErrorPolicy`] other than `Off`.
+    pub fn bad_blocks(&self) -> Vec<u64> {
+        self.session()
+            .pool()
+            .sessions
+            .get(&self.session().tsih())
+            .map(|s| s.inventory.bad_blocks(self.lun()))
+            .unwrap_or_default()
+    }
+
+    /// Apply the pool's [`MediumErrorPolicy`] to `e`, the failure of a
+    /// READ / WRITE of `blocks` blocks at `lba`. Returns whether the bad
+    /// block was reassigned and the command should be sent again.
+    pub(crate) async fn handle_medium_error(
+        &self,
+        e: &IscsiError,
+        lba: u64,
+        blocks: u32,
+    ) -> bool {
+        let pool = self.session().pool();
+        let policy = pool.medium_error_policy();
+        if policy == MediumErrorPolicy::Off {
+            return false;
+        }
+        let IscsiError::Scsi(e) = e else {
+            return false;
+        };
+        let Some(sense) = e
+            .sense
+            .as_ref()
+            .filter(|s| s.sense_key == SENSE_KEY_MEDIUM_ERROR && s.valid)
+        else {
+            return false;
+        };
+        let bad = sense.information;
+        if !(lba..lba + blocks as u64).contains(&bad) {
+            return false;
+        }
+
+        let tsih = self.session().tsih();
+        let lun = self.lun();
+        if let Some(session) = pool.sessions.get(&tsih) {
+            session.inventory.note_bad_block(lun, bad);
+        }
+        let reassigned = policy == MediumErrorPolicy::Reassign
+            && match self.reassign_blocks(&[bad]).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("TSIH={tsih}, {lun}: REASSIGN BLOCKS of LBA {bad} failed: {e}");
+                    false
+                },
+            };
+        warn!("TSIH={tsih}, {lun}: medium error at LBA {bad} (reassigned: {reassigned})");
+        pool.emit_event(PoolEvent::MediumError {
+            tsih,
+            lun,
+            lba: bad,
+            reassigned,
+        });
+        reassigned
+    }
+}
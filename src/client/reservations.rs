@@ -0,0 +1,325 @@
+//! Persistent reservations (PERSISTENT RESERVE IN / OUT) on a LUN.
+//!
+//! The `pr_*` methods of [`LunHandle`] issue one service action each and
+//! decode the parameter data. A conflicting request fails with
+//! `IscsiError::Scsi` carrying `ScsiStatus::ReservationConflict`.
+//!
+//! [`PrFence`] wraps them for HA agents: it registers a key and reserves the
+//! LUN, preempts a failed peer and releases again. Its conflicts come back as
+//! [`IscsiError::ReservationConflict`] naming the current holder, and can be
+//! retried with a [`RetryPolicy`].
+//!
+//! [`LunHandle::reserve_unit`] and [`LunHandle::release_unit`] issue the
+//! legacy RESERVE / RELEASE commands still required by old arrays.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::fmt;
+
+use thiserror::Error;
+use tracing::debug;
+
+use crate::{
+    client::{
+        handles::{INVALID_OPCODE, LunHandle},
+        retry::RetryPolicy,
+    },
+    control_block::{
+        persistent_reserve::{
+            PR_IN_HEADER_LEN, PrCapabilities, PrInAction, PrKeys, PrOutAction,
+            PrOutParameters, PrReservation, PrReservationStatus, PrType, build_pr_in,
+            build_pr_out, parse_pr_read_keys, parse_pr_read_reservation,
+            parse_pr_report_capabilities,
+        },
+        reserve_release::{
+            build_release6, build_release10, build_reserve6, build_reserve10,
+        },
+    },
+    error::{self, IscsiError},
+    models::command::common::ScsiStatus,
+};
+
+/// Allocation length of the first READ KEYS; room for 127 keys.
+const READ_KEYS_ALLOC_LEN: u16 = 1024;
+
+/// Allocation length of READ RESERVATION and REPORT CAPABILITIES.
+const PR_IN_SHORT_ALLOC_LEN: u16 = 24;
+
+impl LunHandle {
+    /// Issue PERSISTENT RESERVE IN `action` and return the raw parameter
+    /// data.
+    pub async fn pr_in(
+        &self,
+        action: PrInAction,
+        alloc_len: u16,
+    ) -> error::Result<Vec<u8>> {
+        let mut cdb = [0u8; 16];
+        build_pr_in(&mut cdb, action, alloc_len, 0);
+        self.read_cdb(cdb, alloc_len as u32, None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Registered reservation keys (READ KEYS). Re-issued with a larger
+    /// allocation length when the first answer was cut off.
+    pub async fn pr_read_keys(&self) -> error::Result<PrKeys> {
+        let data = self
+            .pr_in(PrInAction::ReadKeys, READ_KEYS_ALLOC_LEN)
+            .await?;
+        let reported = data
+            .get(4..8)
+            .map_or(0, |l| u32::from_be_bytes([l[0], l[1], l[2], l[3]]) as usize);
+        if PR_IN_HEADER_LEN + reported <= data.len() {
+            return Ok(parse_pr_read_keys(&data)?);
+        }
+        let alloc_len = u16::try_from(PR_IN_HEADER_LEN + reported).unwrap_or(u16::MAX);
+        let data = self.pr_in(PrInAction::ReadKeys, alloc_len).await?;
+        Ok(parse_pr_read_keys(&data)?)
+    }
+
+    /// The current persistent reservation (READ RESERVATION).
+    pub async fn pr_read_reservation(&self) -> error::Result<PrReservationStatus> {
+        let data = self
+            .pr_in(PrInAction::ReadReservation, PR_IN_SHORT_ALLOC_LEN)
+            .await?;
+        Ok(parse_pr_read_reservation(&data)?)
+    }
+
+    /// Reservation features and types the LUN supports (REPORT
+    /// CAPABILITIES).
+    pub async fn pr_capabilities(&self) -> error::Result<PrCapabilities> {
+        let data = self
+            .pr_in(PrInAction::ReportCapabilities, PR_IN_SHORT_ALLOC_LEN)
+            .await?;
+        Ok(parse_pr_report_capabilities(&data)?)
+    }
+
+    /// Issue PERSISTENT RESERVE OUT `action` with the basic parameter list.
+    /// `pr_type` is required by RESERVE, RELEASE and PREEMPT.
+    pub async fn pr_out(
+        &self,
+        action: PrOutAction,
+        pr_type: Option<PrType>,
+        params: &PrOutParameters,
+    ) -> error::Result<()> {
+        let params = params.to_bytes();
+        let mut cdb = [0u8; 16];
+        build_pr_out(&mut cdb, action, pr_type, params.len() as u32, 0);
+        self.write_cdb(cdb, params.to_vec(), None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Reserve the whole LUN for this initiator with the legacy RESERVE
+    /// command: RESERVE(10), or RESERVE(6) when the target does not know the
+    /// 10-byte form. Fails with RESERVATION CONFLICT while another initiator
+    /// holds a reservation.
+    pub async fn reserve_unit(&self) -> error::Result<()> {
+        self.legacy_reservation(build_reserve10, build_reserve6)
+            .await
+    }
+
+    /// Release a reservation taken by [`reserve_unit`](Self::reserve_unit).
+    /// Releasing a LUN that is not reserved succeeds.
+    pub async fn release_unit(&self) -> error::Result<()> {
+        self.legacy_reservation(build_release10, build_release6)
+            .await
+    }
+
+    async fn legacy_reservation(
+        &self,
+        build10: fn(&mut [u8; 16], u8),
+        build6: fn(&mut [u8; 16], u8),
+    ) -> error::Result<()> {
+        let mut cdb = [0u8; 16];
+        build10(&mut cdb, 0);
+        match self.read_cdb(cdb, 0, None).await.map_err(IscsiError::from) {
+            Err(IscsiError::Scsi(e))
+                if e.sense_codes()
+                    .is_some_and(|(key, asc, _)| (key, asc) == INVALID_OPCODE) =>
+            {
+                debug!("{:02X}h not supported, using the 6-byte form", cdb[0]);
+                build6(&mut cdb, 0);
+                self.read_cdb(cdb, 0, None).await?;
+                Ok(())
+            },
+            other => other.map(drop),
+        }
+    }
+}
+
+/// A persistent reservation request was refused because another initiator
+/// holds a conflicting reservation.
+#[derive(Debug, Clone, Error)]
+pub struct ReservationConflictError {
+    /// Service action that conflicted.
+    pub action: PrOutAction,
+    /// Key the request was made with.
+    pub key: u64,
+    /// The reservation found on the LUN after the conflict, if it could be
+    /// read.
+    pub holder: Option<PrReservation>,
+}
+
+impl fmt::Display for ReservationConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} with key 0x{:016X} hit a reservation conflict",
+            self.action, self.key
+        )?;
+        match &self.holder {
+            Some(holder) => write!(
+                f,
+                "; held by key 0x{:016X} ({:?})",
+                holder.key, holder.pr_type
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Fencing with persistent reservations, for one registration key.
+#[derive(Debug, Clone)]
+pub struct PrFence {
+    lun: LunHandle,
+    key: u64,
+    pr_type: PrType,
+    conflict_retry: RetryPolicy,
+}
+
+impl PrFence {
+    /// Fence `lun` with reservations of `pr_type` under `key`. Nothing is
+    /// sent until [`register_and_reserve`](Self::register_and_reserve).
+    pub fn new(lun: LunHandle, key: u64, pr_type: PrType) -> Self {
+        Self {
+            lun,
+            key,
+            pr_type,
+            conflict_retry: RetryPolicy::disabled(),
+        }
+    }
+
+    /// Retry requests that hit RESERVATION CONFLICT up to
+    /// `policy.max_attempts` times in total, pausing by `policy.backoff`,
+    /// e.g. to wait for a peer to release.
+    pub fn with_conflict_retry(mut self, policy: RetryPolicy) -> Self {
+        self.conflict_retry = policy;
+        self
+    }
+
+    /// The registration key.
+    #[inline]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// The reservation type.
+    #[inline]
+    pub fn pr_type(&self) -> PrType {
+        self.pr_type
+    }
+
+    /// The fenced LUN.
+    #[inline]
+    pub fn lun(&self) -> &LunHandle {
+        &self.lun
+    }
+
+    /// Register the key (replacing any earlier key of this I_T nexus) and
+    /// take the reservation.
+    pub async fn register_and_reserve(&self) -> error::Result<()> {
+        self.out(
+            PrOutAction::RegisterAndIgnoreExistingKey,
+            None,
+            PrOutParameters {
+                service_action_key: self.key,
+                ..Default::default()
+            },
+        )
+        .await?;
+        self.out(PrOutAction::Reserve, Some(self.pr_type), self.params(0))
+            .await
+    }
+
+    /// Remove the registrations of `victim_key` and take over its
+    /// reservation, cutting the victim off the LUN.
+    pub async fn preempt(&self, victim_key: u64) -> error::Result<()> {
+        self.out(
+            PrOutAction::Preempt,
+            Some(self.pr_type),
+            self.params(victim_key),
+        )
+        .await
+    }
+
+    /// Release the reservation; the registration stays.
+    pub async fn release(&self) -> error::Result<()> {
+        self.out(PrOutAction::Release, Some(self.pr_type), self.params(0))
+            .await
+    }
+
+    /// Remove the registration (and with it a reservation held by it).
+    pub async fn unregister(&self) -> error::Result<()> {
+        self.out(PrOutAction::Register, None, self.params(0)).await
+    }
+
+    fn params(&self, service_action_key: u64) -> PrOutParameters {
+        PrOutParameters {
+            reservation_key: self.key,
+            service_action_key,
+            ..Default::default()
+        }
+    }
+
+    async fn out(
+        &self,
+        action: PrOutAction,
+        pr_type: Option<PrType>,
+        params: PrOutParameters,
+    ) -> error::Result<()> {
+        let mut attempt = 1;
+        loop {
+            let error = match self.lun.pr_out(action, pr_type, &params).await {
+                Ok(()) => return Ok(()),
+                Err(IscsiError::Scsi(e))
+                    if e.status == ScsiStatus::ReservationConflict =>
+                {
+                    e
+                },
+                Err(e) => return Err(e),
+            };
+            if attempt >= self.conflict_retry.max_attempts {
+                debug!("{action:?} conflicted: {error}");
+                let holder = self
+                    .lun
+                    .pr_read_reservation()
+                    .await
+                    .ok()
+                    .and_then(|status| status.reservation);
+                return Err(IscsiError::ReservationConflict(ReservationConflictError {
+                    action,
+                    key: self.key,
+                    holder,
+                }));
+            }
+            tokio::time::sleep(self.conflict_retry.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl LunHandle {
+    /// Register `key` and reserve the LUN with `pr_type`, returning the
+    /// [`PrFence`] to preempt peers and release with later.
+    pub async fn register_and_reserve(
+        &self,
+        key: u64,
+        pr_type: PrType,
+    ) -> error::Result<PrFence> {
+        let fence = PrFence::new(self.clone(), key, pr_type);
+        fence.register_and_reserve().await?;
+        Ok(fence)
+    }
+}
@@ -0,0 +1,261 @@
+//! Retry policy for transient SCSI conditions reported by the target.
+//!
+//! A target may complete a command with BUSY, TASK SET FULL or a CHECK
+//! CONDITION carrying a Unit Attention that only reports a past event. Such
+//! outcomes are worth re-issuing after a short pause instead of failing the
+//! caller's workload. [`RetryPolicy`] describes which outcomes qualify and how
+//! long to wait between attempts; [`crate::client::pool_sessions::Pool`]
+//! applies it around every command it executes.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    error::IscsiError,
+    models::{
+        command::common::ScsiStatus,
+        identifiers::{Cid, Tsih},
+    },
+};
+
+/// SCSI status code for BUSY.
+pub const STATUS_BUSY: u8 = 0x08;
+/// SCSI status code for TASK SET FULL.
+pub const STATUS_TASK_SET_FULL: u8 = 0x28;
+
+/// Sense key for NOT READY.
+pub const SENSE_KEY_NOT_READY: u8 = 0x02;
+/// Sense key for UNIT ATTENTION.
+pub const SENSE_KEY_UNIT_ATTENTION: u8 = 0x06;
+
+/// Matches a CHECK CONDITION by sense key and, optionally, ASC/ASCQ.
+///
+/// `None` in `asc` or `ascq` acts as a wildcard.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SenseMatch {
+    #[serde(rename = "SenseKey")]
+    /// Sense key (low nibble of byte 2 of fixed-format sense data).
+    pub sense_key: u8,
+
+    #[serde(rename = "Asc", default, skip_serializing_if = "Option::is_none")]
+    /// Additional Sense Code; any value when unset.
+    pub asc: Option<u8>,
+
+    #[serde(rename = "Ascq", default, skip_serializing_if = "Option::is_none")]
+    /// Additional Sense Code Qualifier; any value when unset.
+    pub ascq: Option<u8>,
+}
+
+impl SenseMatch {
+    /// Matches every ASC/ASCQ under `sense_key`.
+    pub const fn key(sense_key: u8) -> Self {
+        Self {
+            sense_key,
+            asc: None,
+            ascq: None,
+        }
+    }
+
+    /// Matches every ASCQ under `sense_key`/`asc`.
+    pub const fn asc(sense_key: u8, asc: u8) -> Self {
+        Self {
+            sense_key,
+            asc: Some(asc),
+            ascq: None,
+        }
+    }
+
+    /// Matches exactly `sense_key`/`asc`/`ascq`.
+    pub const fn exact(sense_key: u8, asc: u8, ascq: u8) -> Self {
+        Self {
+            sense_key,
+            asc: Some(asc),
+            ascq: Some(ascq),
+        }
+    }
+
+    /// Returns whether the given sense triple satisfies this matcher.
+    pub fn matches(&self, sense_key: u8, asc: u8, ascq: u8) -> bool {
+        self.sense_key == sense_key
+            && self.asc.is_none_or(|v| v == asc)
+            && self.ascq.is_none_or(|v| v == ascq)
+    }
+}
+
+/// How (and whether) to re-issue a command that failed with a transient
+/// SCSI status.
+///
+/// The default policy performs a single attempt, i.e. retries are disabled
+/// until `MaxAttempts` is raised, but already lists the usual transient
+/// conditions so enabling it only takes one key.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    #[serde(rename = "MaxAttempts")]
+    /// Total attempts including the first one; `1` disables retries.
+    pub max_attempts: u32,
+
+    #[serde(rename = "InitialBackoffMs")]
+    /// Pause before the first retry, in milliseconds.
+    pub initial_backoff_ms: u64,
+
+    #[serde(rename = "MaxBackoffMs")]
+    /// Upper bound for a single pause, in milliseconds.
+    pub max_backoff_ms: u64,
+
+    #[serde(rename = "BackoffMultiplier")]
+    /// Factor applied to the pause after every retry.
+    pub backoff_multiplier: f64,
+
+    #[serde(rename = "RetriableStatuses")]
+    /// Raw SCSI status codes (other than CHECK CONDITION) worth retrying.
+    pub retriable_statuses: Vec<u8>,
+
+    #[serde(rename = "RetriableSense")]
+    /// CHECK CONDITION sense codes worth retrying.
+    pub retriable_sense: Vec<SenseMatch>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 5_000,
+            backoff_multiplier: 2.0,
+            retriable_statuses: vec![STATUS_BUSY, STATUS_TASK_SET_FULL],
+            retriable_sense: vec![
+                // POWER ON, RESET, OR BUS DEVICE RESET OCCURRED
+                SenseMatch::asc(SENSE_KEY_UNIT_ATTENTION, 0x29),
+                // PARAMETERS CHANGED (mode/capacity/reservations)
+                SenseMatch::asc(SENSE_KEY_UNIT_ATTENTION, 0x2A),
+                // LOGICAL UNIT IS IN PROCESS OF BECOMING READY
+                SenseMatch::exact(SENSE_KEY_NOT_READY, 0x04, 0x01),
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that never retries.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Default transient conditions (BUSY, TASK SET FULL, selected Unit
+    /// Attentions) retried up to `max_attempts` times in total.
+    pub fn transient(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+
+    /// Returns whether more than one attempt may be made.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.max_attempts > 1
+    }
+
+    /// Pause before retry number `retry` (1-based), capped at `MaxBackoffMs`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exp = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let ms = self.initial_backoff_ms as f64 * self.backoff_multiplier.powi(exp);
+        let capped = ms.min(self.max_backoff_ms as f64).max(0.0);
+        Duration::from_millis(capped as u64)
+    }
+
+    /// Returns whether `status` (with optional sense triple) is transient
+    /// under this policy.
+    pub fn is_retriable_status(
+        &self,
+        status: &ScsiStatus,
+        sense: Option<(u8, u8, u8)>,
+    ) -> bool {
+        match status {
+            ScsiStatus::Good => false,
+            ScsiStatus::CheckCondition => sense.is_some_and(|(key, asc, ascq)| {
+                self.retriable_sense
+                    .iter()
+                    .any(|m| m.matches(key, asc, ascq))
+            }),
+            ScsiStatus::Other(raw) => self.retriable_statuses.contains(raw),
+            known => self.retriable_statuses.contains(&u8::from(known)),
+        }
+    }
+
+    /// Classifies an execution error; only [`IscsiError::Scsi`] can be
+    /// retriable.
+    pub fn is_retriable(&self, error: &IscsiError) -> bool {
+        matches!(
+            error,
+            IscsiError::Scsi(e) if self.is_retriable_status(&e.status, e.sense_codes())
+        )
+    }
+}
+
+/// The connection failed while a non-idempotent command was in flight, so
+/// it is unknown whether the target applied it. The command is not re-issued
+/// automatically; the caller must decide (e.g. re-read and compare).
+#[derive(Debug, Error)]
+#[error(
+    "outcome of command on TSIH={tsih}, CID={cid} is unknown (connection failed after \
+     data may have been accepted); not retrying: {reason}"
+)]
+pub struct AmbiguousOutcomeError {
+    /// Session the command was issued on.
+    pub tsih: Tsih,
+    /// Connection the command was issued on.
+    pub cid: Cid,
+    /// Original transport error.
+    pub reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_machine::common::ScsiStatusError;
+
+    #[test]
+    fn default_policy_is_disabled_but_classifies_transients() {
+        let p = RetryPolicy::default();
+        assert!(!p.is_enabled());
+        assert!(p.is_retriable_status(&ScsiStatus::Busy, None));
+        assert!(p.is_retriable_status(&ScsiStatus::TaskSetFull, None));
+        assert!(!p.is_retriable_status(&ScsiStatus::ReservationConflict, None));
+        assert!(
+            p.is_retriable_status(&ScsiStatus::CheckCondition, Some((0x06, 0x29, 0x00)))
+        );
+        assert!(
+            !p.is_retriable_status(&ScsiStatus::CheckCondition, Some((0x05, 0x24, 0x00)))
+        );
+        assert!(!p.is_retriable_status(&ScsiStatus::CheckCondition, None));
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let p = RetryPolicy {
+            initial_backoff_ms: 10,
+            max_backoff_ms: 50,
+            backoff_multiplier: 2.0,
+            ..RetryPolicy::transient(8)
+        };
+        assert_eq!(p.backoff(1), Duration::from_millis(10));
+        assert_eq!(p.backoff(2), Duration::from_millis(20));
+        assert_eq!(p.backoff(3), Duration::from_millis(40));
+        assert_eq!(p.backoff(4), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn classifies_typed_status_error() {
+        let p = RetryPolicy::transient(3);
+        let busy = IscsiError::Scsi(ScsiStatusError::new(0x28, ScsiStatus::Busy, &[]));
+        assert!(p.is_retriable(&busy));
+        assert!(!p.is_retriable(&IscsiError::Disconnected("socket closed".into())));
+    }
+}
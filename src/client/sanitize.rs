@@ -0,0 +1,166 @@
+//! Secure erase of a LUN with SANITIZE.
+//!
+//! [`LunHandle::start_sanitize`] starts the operation in the background
+//! (IMMED=1) and [`LunHandle::sanitize_progress`] polls REQUEST SENSE for
+//! its progress. [`LunHandle::sanitize`] does both and returns once the LUN
+//! is erased.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::{
+        request_sense::fill_request_sense_simple,
+        sanitize::{OverwriteParameters, SanitizeAction, build_sanitize},
+    },
+    error,
+    models::data::sense_data::SenseData,
+};
+
+/// Allocation length of the REQUEST SENSE used to poll the progress.
+const SENSE_ALLOC_LEN: u8 = 252;
+
+/// NOT READY / LOGICAL UNIT NOT READY, SANITIZE IN PROGRESS.
+const SANITIZE_IN_PROGRESS: (u8, u8, u8) = (0x02, 0x04, 0x1B);
+
+/// Sense key REQUEST SENSE returns once nothing is pending.
+const NO_SENSE: u8 = 0x00;
+
+/// MEDIUM ERROR / SANITIZE COMMAND FAILED.
+const SANITIZE_FAILED: (u8, u8) = (0x31, 0x03);
+
+/// What a SANITIZE does to the medium.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanitizeOp {
+    /// Overwrite every block with the given pattern.
+    Overwrite(OverwriteParameters),
+    /// Erase the medium.
+    BlockErase,
+    /// Replace the media encryption key.
+    CryptoErase,
+    /// Leave the failure mode of an earlier failed sanitize (needs the
+    /// failed one to have been started with AUSE).
+    ExitFailureMode,
+}
+
+impl SanitizeOp {
+    fn action(&self) -> SanitizeAction {
+        match self {
+            SanitizeOp::Overwrite(_) => SanitizeAction::Overwrite,
+            SanitizeOp::BlockErase => SanitizeAction::BlockErase,
+            SanitizeOp::CryptoErase => SanitizeAction::CryptoErase,
+            SanitizeOp::ExitFailureMode => SanitizeAction::ExitFailureMode,
+        }
+    }
+}
+
+impl LunHandle {
+    /// Start `op` with IMMED set; the target erases in the background and
+    /// fails media access with NOT READY until it is done. `ause` allows a
+    /// failed sanitize to be cleared with [`SanitizeOp::ExitFailureMode`].
+    pub async fn start_sanitize(&self, op: &SanitizeOp, ause: bool) -> error::Result<()> {
+        let params = match op {
+            SanitizeOp::Overwrite(params) => params.to_bytes()?,
+            _ => Vec::new(),
+        };
+        let mut cdb = [0u8; 16];
+        build_sanitize(
+            &mut cdb,
+            op.action(),
+            true,
+            ause,
+            false,
+            params.len() as u16,
+            0,
+        );
+        self.write_cdb(cdb, params, None).await.map_err(Into::into)
+    }
+
+    /// Progress of a running sanitize from REQUEST SENSE, as a fraction in
+    /// `0.0..1.0`; `None` once the LUN reports NO SENSE. Fails on any other
+    /// sense, such as SANITIZE COMMAND FAILED.
+    pub async fn sanitize_progress(&self) -> error::Result<Option<f32>> {
+        let mut cdb = [0u8; 16];
+        fill_request_sense_simple(&mut cdb, SENSE_ALLOC_LEN);
+        let data = self.read_cdb(cdb, SENSE_ALLOC_LEN as u32, None).await?;
+        let sense = SenseData::parse(&data)?;
+        Ok(sanitize_state(&sense)?)
+    }
+
+    /// Run `op` to completion: start it with IMMED and poll its progress
+    /// every `poll`, passing each value to `on_progress`.
+    pub async fn sanitize(
+        &self,
+        op: &SanitizeOp,
+        ause: bool,
+        poll: Duration,
+        mut on_progress: impl FnMut(f32),
+    ) -> error::Result<()> {
+        self.start_sanitize(op, ause).await?;
+        loop {
+            tokio::time::sleep(poll).await;
+            match self.sanitize_progress().await? {
+                Some(progress) => on_progress(progress),
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Progress of a sanitize from REQUEST SENSE data, `None` once it is done
+/// (NO SENSE). Any other sense is an error.
+fn sanitize_state(sense: &SenseData) -> anyhow::Result<Option<f32>> {
+    if (sense.sense_key, sense.asc, sense.ascq) == SANITIZE_IN_PROGRESS {
+        return Ok(Some(sense.progress().map_or(0.0, |p| p as f32 / 65536.0)));
+    }
+    if (sense.asc, sense.ascq) == SANITIZE_FAILED {
+        return Err(anyhow!("sanitize failed: {sense:?}"));
+    }
+    if sense.sense_key != NO_SENSE {
+        return Err(anyhow!("unexpected sense while sanitizing: {sense:?}"));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sense(key: u8, asc: u8, ascq: u8, sks: [u8; 3]) -> SenseData {
+        let mut buf = [0u8; 18];
+        buf[0] = 0x70;
+        buf[2] = key;
+        buf[7] = 10;
+        buf[12] = asc;
+        buf[13] = ascq;
+        buf[15..18].copy_from_slice(&sks);
+        SenseData::parse(&buf).expect("sense")
+    }
+
+    #[test]
+    fn reads_progress_from_sense() {
+        let running = sense(0x02, 0x04, 0x1B, [0x80, 0x40, 0x00]);
+        assert_eq!(running.progress(), Some(0x4000));
+        assert_eq!(sanitize_state(&running).expect("state"), Some(0.25));
+
+        let no_sksv = sense(0x02, 0x04, 0x1B, [0x00, 0x40, 0x00]);
+        assert_eq!(sanitize_state(&no_sksv).expect("state"), Some(0.0));
+
+        let idle = sense(0x00, 0x00, 0x00, [0; 3]);
+        assert_eq!(sanitize_state(&idle).expect("state"), None);
+
+        let failed = sense(0x03, 0x31, 0x03, [0; 3]);
+        assert!(sanitize_state(&failed).is_err());
+
+        let other_not_ready = sense(0x02, 0x04, 0x02, [0; 3]);
+        assert!(sanitize_state(&other_not_ready).is_err());
+
+        let unit_attention = sense(0x06, 0x29, 0x00, [0; 3]);
+        assert!(sanitize_state(&unit_attention).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! Scrubbing: reading a LUN end to end and checking what comes back.
+//!
+//! [`LunHandle::scrub`] reads a block range with several READs in flight.
+//! Blocks the target cannot read (MEDIUM ERROR) are reported instead of
+//! failing the pass. With [`ScrubOptions::pattern`] every block is
+//! compared against the pattern [`LunHandle::write_pattern`] wrote from the
+//! same seed; with [`ScrubOptions::checksum_extent_blocks`] a CRC32C is
+//! collected per extent, so two passes (or two LUNs) can be compared.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::ops::{Bound, RangeBounds};
+
+use anyhow::Context;
+use tokio::task::JoinSet;
+use tracing::warn;
+
+use crate::{
+    client::{
+        dd::default_parallelism,
+        handles::{LunHandle, check_range},
+    },
+    control_block::reassign_blocks::SENSE_KEY_MEDIUM_ERROR,
+    error::{self, IscsiError},
+};
+
+/// Bytes per READ / WRITE unless [`ScrubOptions::chunk_blocks`] is set.
+const DEFAULT_SCRUB_CHUNK_BYTES: u32 = 1 << 20;
+
+/// What [`LunHandle::scrub`] checks besides readability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScrubOptions {
+    /// Blocks per READ; `None` for 1 MiB worth. Rounded down to whole
+    /// checksum extents.
+    pub chunk_blocks: Option<u32>,
+    /// READs in flight; `None` for two per connection of the session.
+    pub parallelism: Option<usize>,
+    /// Compare every block with the pattern of this seed.
+    pub pattern: Option<u64>,
+    /// Collect a CRC32C per extent of this many blocks.
+    pub checksum_extent_blocks: Option<u32>,
+    /// Stop after this many mismatching or unreadable blocks; 0 for no
+    /// limit.
+    pub max_errors: usize,
+}
+
+/// CRC32C of the blocks `lba..lba + blocks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtentChecksum {
+    /// First LBA of the extent.
+    pub lba: u64,
+    /// Blocks in the extent; the last one can be shorter.
+    pub blocks: u32,
+    /// CRC32C of the extent's data.
+    pub crc32c: u32,
+}
+
+/// Outcome of a [`LunHandle::scrub`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ScrubReport {
+    /// Blocks read successfully.
+    pub blocks_read: u64,
+    /// Blocks whose data differs from the pattern, ascending.
+    pub mismatches: Vec<u64>,
+    /// `(lba, blocks)` ranges whose READ failed with MEDIUM ERROR, in LBA
+    /// order.
+    pub unreadable: Vec<(u64, u32)>,
+    /// Per-extent checksums in LBA order, when requested.
+    pub checksums: Vec<ExtentChecksum>,
+    /// The pass stopped at [`ScrubOptions::max_errors`] before the end of
+    /// the range.
+    pub truncated: bool,
+}
+
+impl ScrubReport {
+    /// No mismatching or unreadable block was found.
+    pub fn is_clean(&self) -> bool {
+        self.mismatches.is_empty() && self.unreadable.is_empty()
+    }
+
+    fn errors(&self) -> usize {
+        self.mismatches.len()
+            + self
+                .unreadable
+                .iter()
+                .map(|&(_, n)| n as usize)
+                .sum::<usize>()
+    }
+}
+
+/// Progress of a scrub, reported after every READ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrubProgress {
+    /// Blocks checked so far, readable or not.
+    pub blocks_done: u64,
+    /// Blocks of the whole range.
+    pub blocks_total: u64,
+    /// Mismatching and unreadable blocks so far.
+    pub errors: usize,
+}
+
+impl LunHandle {
+    /// Read the blocks in `lbas` (the whole LUN for `..`) and check them as
+    /// `opts` asks. `on_progress` is called after every READ. Errors other
+    /// than MEDIUM ERROR end the pass.
+    pub async fn scrub(
+        &self,
+        lbas: impl RangeBounds<u64>,
+        opts: &ScrubOptions,
+        mut on_progress: impl FnMut(ScrubProgress),
+    ) -> error::Result<ScrubReport> {
+        let cap = self.capacity().await?;
+        let (start, end) = block_range(&lbas, cap.blocks);
+        check_range(&cap, start, end.saturating_sub(start))?;
+        let total = end.saturating_sub(start);
+        let bs = cap.block_size;
+        let chunk = scrub_chunk(opts, bs) as u64;
+        let jobs = opts
+            .parallelism
+            .unwrap_or_else(|| default_parallelism(self))
+            .max(1);
+
+        let mut report = ScrubReport::default();
+        let mut tasks = JoinSet::new();
+        let (mut next, mut done) = (start, 0);
+        while next < end || !tasks.is_empty() {
+            while next < end && tasks.len() < jobs && !report.truncated {
+                let n = (end - next).min(chunk) as u32;
+                let (lun, lba, opts) = (self.clone(), next, *opts);
+                tasks.spawn(async move {
+                    let checked = match lun.read_at(lba, n).await {
+                        Ok(data) => Ok(check_chunk(&data, bs as usize, lba, &opts)),
+                        Err(e) => Err(e),
+                    };
+                    (lba, n, checked)
+                });
+                next += n as u64;
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+            let (lba, n, checked) = joined.context("scrub task failed")?;
+            match checked {
+                Ok((mismatches, checksums)) => {
+                    report.blocks_read += n as u64;
+                    report.mismatches.extend(mismatches);
+                    report.checksums.extend(checksums);
+                },
+                Err(e) if is_medium_error(&e) => {
+                    warn!("{}: LBA {lba}+{n} unreadable: {e}", self.lun());
+                    report.unreadable.push((lba, n));
+                },
+                Err(e) => return Err(e),
+            }
+            done += n as u64;
+            let errors = report.errors();
+            on_progress(ScrubProgress {
+                blocks_done: done,
+                blocks_total: total,
+                errors,
+            });
+            if opts.max_errors > 0 && errors >= opts.max_errors && next < end {
+                report.truncated = true;
+                next = end;
+            }
+        }
+        report.mismatches.sort_unstable();
+        report.unreadable.sort_unstable();
+        report.checksums.sort_unstable_by_key(|c| c.lba);
+        Ok(report)
+    }
+
+    /// Write the pattern of `seed` to `blocks` blocks at `lba`, for a later
+    /// [`LunHandle::scrub`] with [`ScrubOptions::pattern`].
+    pub async fn write_pattern(
+        &self,
+        lba: u64,
+        blocks: u64,
+        seed: u64,
+    ) -> error::Result<()> {
+        let cap = self.capacity().await?;
+        check_range(&cap, lba, blocks)?;
+        let bs = cap.block_size as usize;
+        let chunk = scrub_chunk(&ScrubOptions::default(), cap.block_size) as u64;
+        let mut next = lba;
+        while next < lba + blocks {
+            let n = (lba + blocks - next).min(chunk);
+            let mut data = vec![0u8; n as usize * bs];
+            fill_pattern(&mut data, bs, next, seed);
+            self.write_at(next, data).await?;
+            next += n;
+        }
+        Ok(())
+    }
+}
+
+/// Fill `buf`, whole blocks of `block_size` bytes starting at `lba`, with
+/// the scrub pattern of `seed`. Each block gets its own xorshift64 stream
+/// seeded from `seed` and its LBA, so misplaced blocks are caught too.
+pub fn fill_pattern(buf: &mut [u8], block_size: usize, lba: u64, seed: u64) {
+    for (i, block) in buf.chunks_mut(block_size).enumerate() {
+        let mut rng = XorShift::new(seed, lba + i as u64);
+        for word in block.chunks_mut(8) {
+            word.copy_from_slice(&rng.next().to_le_bytes()[..word.len()]);
+        }
+    }
+}
+
+/// xorshift64: cheap, `Send` and reproducible from a seed.
+#[derive(Debug)]
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    /// Stream number `stream` of `seed`.
+    pub(crate) fn new(seed: u64, stream: u64) -> Self {
+        Self((seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Blocks per READ: [`ScrubOptions::chunk_blocks`] (or 1 MiB), rounded
+/// down to whole checksum extents.
+fn scrub_chunk(opts: &ScrubOptions, block_size: u32) -> u32 {
+    let chunk = opts
+        .chunk_blocks
+        .unwrap_or(DEFAULT_SCRUB_CHUNK_BYTES / block_size)
+        .max(1);
+    match opts.checksum_extent_blocks {
+        Some(extent) if extent > 0 => (chunk / extent).max(1) * extent,
+        _ => chunk,
+    }
+}
+
+/// Mismatching LBAs and extent checksums of `data` read at `lba`.
+fn check_chunk(
+    data: &[u8],
+    block_size: usize,
+    lba: u64,
+    opts: &ScrubOptions,
+) -> (Vec<u64>, Vec<ExtentChecksum>) {
+    let mut mismatches = Vec::new();
+    if let Some(seed) = opts.pattern {
+        let mut expected = vec![0u8; block_size];
+        for (i, block) in data.chunks(block_size).enumerate() {
+            let block_lba = lba + i as u64;
+            fill_pattern(&mut expected, block_size, block_lba, seed);
+            if block != expected.as_slice() {
+                mismatches.push(block_lba);
+            }
+        }
+    }
+    let mut checksums = Vec::new();
+    if let Some(extent) = opts.checksum_extent_blocks.filter(|&e| e > 0) {
+        for (i, bytes) in data.chunks(extent as usize * block_size).enumerate() {
+            checksums.push(ExtentChecksum {
+                lba: lba + i as u64 * extent as u64,
+                blocks: (bytes.len() / block_size) as u32,
+                crc32c: crc32c::crc32c(bytes),
+            });
+        }
+    }
+    (mismatches, checksums)
+}
+
+fn is_medium_error(e: &IscsiError) -> bool {
+    matches!(e, IscsiError::Scsi(e)
+        if e.sense.as_ref().is_some_and(|s| s.sense_key == SENSE_KEY_MEDIUM_ERROR))
+}
+
+/// `[start, end)` of `lbas` on a LUN of `blocks` blocks.
+fn block_range(lbas: &impl RangeBounds<u64>, blocks: u64) -> (u64, u64) {
+    let start = match lbas.start_bound() {
+        Bound::Included(&s) => s,
+        Bound::Excluded(&s) => s.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match lbas.end_bound() {
+        Bound::Included(&e) => e.saturating_add(1),
+        Bound::Excluded(&e) => e,
+        Bound::Unbounded => blocks,
+    };
+    (start, end.max(start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_differs_per_block_and_seed() {
+        let mut a = vec![0u8; 1024];
+        fill_pattern(&mut a, 512, 7, 1);
+        assert_ne!(a[..512], a[512..]);
+
+        let mut b = vec![0u8; 512];
+        fill_pattern(&mut b, 512, 8, 1);
+        assert_eq!(a[512..], b);
+        fill_pattern(&mut b, 512, 8, 2);
+        assert_ne!(a[512..], b);
+    }
+
+    #[test]
+    fn finds_mismatching_blocks_and_checksums_extents() {
+        let mut data = vec![0u8; 4 * 512];
+        fill_pattern(&mut data, 512, 100, 42);
+        data[2 * 512 + 17] ^= 1;
+        let opts = ScrubOptions {
+            pattern: Some(42),
+            checksum_extent_blocks: Some(3),
+            ..Default::default()
+        };
+        let (mismatches, checksums) = check_chunk(&data, 512, 100, &opts);
+        assert_eq!(mismatches, [102]);
+        assert_eq!(
+            checksums
+                .iter()
+                .map(|c| (c.lba, c.blocks))
+                .collect::<Vec<_>>(),
+            [(100, 3), (103, 1)]
+        );
+        assert_eq!(checksums[1].crc32c, crc32c::crc32c(&data[3 * 512..]));
+    }
+
+    #[test]
+    fn chunks_hold_whole_extents() {
+        let opts = ScrubOptions {
+            chunk_blocks: Some(100),
+            checksum_extent_blocks: Some(32),
+            ..Default::default()
+        };
+        assert_eq!(scrub_chunk(&opts, 512), 96);
+        assert_eq!(scrub_chunk(&ScrubOptions::default(), 4096), 256);
+        assert_eq!(block_range(&(10..=19), 100), (10, 20));
+        assert_eq!(block_range(&(..), 100), (0, 100));
+    }
+}
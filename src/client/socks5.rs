@@ -0,0 +1,280 @@
+//! Minimal SOCKS5 client (RFC 1928) for reaching targets behind a proxy.
+//!
+//! Only the CONNECT command is implemented, with either no authentication or
+//! username/password authentication (RFC 1929). The target host is passed to
+//! the proxy unresolved, so DNS names are looked up on the proxy side.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{io, net::IpAddr};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::client::portal::PortalAddr;
+
+const VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USER_PASS: u8 = 0x02;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xFF;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// SOCKS5 proxy used to reach a target.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Proxy {
+    #[serde(rename = "Address")]
+    /// `host[:port]` of the proxy (port defaults to 1080).
+    pub address: String,
+
+    #[serde(rename = "Username", default, skip_serializing_if = "Option::is_none")]
+    /// Username for RFC 1929 authentication; no authentication when unset.
+    pub username: Option<String>,
+
+    #[serde(rename = "Password", default, skip_serializing_if = "Option::is_none")]
+    /// Password for RFC 1929 authentication.
+    pub password: Option<String>,
+}
+
+impl Socks5Proxy {
+    /// Default SOCKS port.
+    pub const DEFAULT_PORT: u16 = 1080;
+
+    /// Proxy address with the SOCKS default port applied.
+    pub fn portal(&self) -> anyhow::Result<PortalAddr> {
+        let mut addr: PortalAddr = self.address.parse()?;
+        if !has_explicit_port(&self.address) {
+            addr.port = Self::DEFAULT_PORT;
+        }
+        Ok(addr)
+    }
+}
+
+fn has_explicit_port(address: &str) -> bool {
+    match address.rsplit_once(']') {
+        Some((_, tail)) => tail.starts_with(':'),
+        None => address.matches(':').count() == 1,
+    }
+}
+
+fn proxy_error(msg: impl Into<String>) -> io::Error {
+    io::Error::other(format!("SOCKS5: {}", msg.into()))
+}
+
+/// Performs the SOCKS5 handshake on `stream` (already connected to the
+/// proxy) and asks it to CONNECT to `target`.
+pub(crate) async fn handshake(
+    stream: &mut TcpStream,
+    proxy: &Socks5Proxy,
+    target: &PortalAddr,
+) -> io::Result<()> {
+    let method = if proxy.username.is_some() {
+        METHOD_USER_PASS
+    } else {
+        METHOD_NO_AUTH
+    };
+    stream.write_all(&[VERSION, 1, method]).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err(proxy_error(format!("unexpected version {}", reply[0])));
+    }
+    match reply[1] {
+        METHOD_NO_AUTH if method == METHOD_NO_AUTH => {},
+        METHOD_USER_PASS if method == METHOD_USER_PASS => {
+            authenticate(stream, proxy).await?;
+        },
+        METHOD_NONE_ACCEPTABLE => {
+            return Err(proxy_error("no acceptable authentication method"));
+        },
+        other => return Err(proxy_error(format!("unexpected method {other:#04x}"))),
+    }
+
+    let mut request = vec![VERSION, CMD_CONNECT, 0x00];
+    match target.host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        },
+        Ok(IpAddr::V6(ip)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        },
+        Err(_) => {
+            let host = target.host.as_bytes();
+            let len = u8::try_from(host.len())
+                .map_err(|_| proxy_error("target host name longer than 255 bytes"))?;
+            request.push(ATYP_DOMAIN);
+            request.push(len);
+            request.extend_from_slice(host);
+        },
+    }
+    request.extend_from_slice(&target.port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    if head[1] != 0x00 {
+        return Err(proxy_error(format!(
+            "CONNECT to {target} refused: {}",
+            reply_message(head[1])
+        )));
+    }
+    // Skip BND.ADDR / BND.PORT.
+    let addr_len = match head[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => usize::from(stream.read_u8().await?),
+        other => return Err(proxy_error(format!("unknown address type {other:#04x}"))),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+async fn authenticate(stream: &mut TcpStream, proxy: &Socks5Proxy) -> io::Result<()> {
+    let user = proxy.username.as_deref().unwrap_or_default().as_bytes();
+    let pass = proxy.password.as_deref().unwrap_or_default().as_bytes();
+    let (Ok(user_len), Ok(pass_len)) =
+        (u8::try_from(user.len()), u8::try_from(pass.len()))
+    else {
+        return Err(proxy_error("username/password longer than 255 bytes"));
+    };
+
+    let mut request = Vec::with_capacity(3 + user.len() + pass.len());
+    request.extend_from_slice(&[0x01, user_len]);
+    request.extend_from_slice(user);
+    request.push(pass_len);
+    request.extend_from_slice(pass);
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0x00 {
+        return Err(proxy_error("username/password rejected"));
+    }
+    Ok(())
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "general failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Accepts one client, checks the handshake, answers CONNECT with
+    /// success and returns the requested (ATYP, address, port).
+    async fn fake_proxy(listener: TcpListener, auth: bool) -> Result<(u8, Vec<u8>, u16)> {
+        let (mut s, _) = listener.accept().await?;
+        let mut greet = [0u8; 3];
+        s.read_exact(&mut greet).await?;
+        let method = if auth {
+            METHOD_USER_PASS
+        } else {
+            METHOD_NO_AUTH
+        };
+        assert_eq!(greet, [VERSION, 1, method]);
+        s.write_all(&[VERSION, method]).await?;
+        if auth {
+            let ver = s.read_u8().await?;
+            assert_eq!(ver, 0x01);
+            let mut user = vec![0u8; usize::from(s.read_u8().await?)];
+            s.read_exact(&mut user).await?;
+            let mut pass = vec![0u8; usize::from(s.read_u8().await?)];
+            s.read_exact(&mut pass).await?;
+            let ok = user == b"lab" && pass == b"secret";
+            s.write_all(&[0x01, if ok { 0 } else { 1 }]).await?;
+        }
+
+        let mut head = [0u8; 4];
+        s.read_exact(&mut head).await?;
+        assert_eq!(&head[..3], &[VERSION, CMD_CONNECT, 0]);
+        let len = match head[3] {
+            ATYP_IPV4 => 4,
+            ATYP_IPV6 => 16,
+            _ => usize::from(s.read_u8().await?),
+        };
+        let mut addr = vec![0u8; len];
+        s.read_exact(&mut addr).await?;
+        let port = s.read_u16().await?;
+        s.write_all(&[VERSION, 0, 0, ATYP_IPV4, 127, 0, 0, 1, 0x0C, 0xBC])
+            .await?;
+        Ok((head[3], addr, port))
+    }
+
+    #[tokio::test]
+    async fn connects_by_domain_name_without_auth() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let proxy = Socks5Proxy {
+            address: listener.local_addr()?.to_string(),
+            username: None,
+            password: None,
+        };
+        let server = tokio::spawn(fake_proxy(listener, false));
+
+        let mut stream = TcpStream::connect(proxy.portal()?.as_connect_target()).await?;
+        handshake(&mut stream, &proxy, &"san.lab:3260".parse()?).await?;
+
+        let (atyp, addr, port) = server.await??;
+        assert_eq!(
+            (atyp, addr.as_slice(), port),
+            (ATYP_DOMAIN, &b"san.lab"[..], 3260)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn authenticates_and_connects_to_ipv6_literal() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let proxy = Socks5Proxy {
+            address: listener.local_addr()?.to_string(),
+            username: Some("lab".into()),
+            password: Some("secret".into()),
+        };
+        let server = tokio::spawn(fake_proxy(listener, true));
+
+        let mut stream = TcpStream::connect(proxy.portal()?.as_connect_target()).await?;
+        handshake(&mut stream, &proxy, &"[fd00::1]:3261".parse()?).await?;
+
+        let (atyp, addr, port) = server.await??;
+        assert_eq!(atyp, ATYP_IPV6);
+        assert_eq!(addr.len(), 16);
+        assert_eq!(port, 3261);
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_port_defaults_to_1080() -> Result<()> {
+        let proxy = |address: &str| Socks5Proxy {
+            address: address.into(),
+            username: None,
+            password: None,
+        };
+        assert_eq!(proxy("jump.lab").portal()?.port, 1080);
+        assert_eq!(proxy("jump.lab:9050").portal()?.port, 9050);
+        assert_eq!(proxy("[fd00::9]").portal()?.port, 1080);
+        assert_eq!(proxy("fd00::9").portal()?.port, 1080);
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! Where the background tasks of the initiator run.
+//!
+//! Besides the futures callers await, connections and the pool start a few
+//! tasks of their own: the supervised read loop of every connection, NOP-In
+//! auto-replies, ExpStatSN acknowledgments, connection supervision and
+//! reconnection, and multipath failback. They are all handed to a
+//! [`Spawner`]. The default, [`TokioSpawner`], calls `tokio::spawn`;
+//! applications that run their own executor or keep tasks in a
+//! structured-concurrency scope install theirs with
+//! [`Pool::set_spawner`](crate::client::pool_sessions::Pool::set_spawner) or
+//! [`ClientConnection::connect_with_spawner`](crate::client::client::ClientConnection::connect_with_spawner).
+//!
+//! The tasks still use tokio sockets and timers, so they must be polled from
+//! within a tokio runtime context.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    any::Any,
+    fmt::Debug,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A background task handed to a [`Spawner`].
+pub type BackgroundTask = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Runs the background tasks of connections and pools.
+pub trait Spawner: Send + Sync + Debug {
+    /// Starts `task` and returns immediately. The task must be polled to
+    /// completion: dropping it early leaves connections unsupervised.
+    fn spawn(&self, task: BackgroundTask);
+}
+
+/// Spawns on the current tokio runtime with `tokio::spawn`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioSpawner;
+
+impl Spawner for TokioSpawner {
+    fn spawn(&self, task: BackgroundTask) {
+        tokio::spawn(task);
+    }
+}
+
+/// Spawns on a specific tokio runtime.
+impl Spawner for tokio::runtime::Handle {
+    fn spawn(&self, task: BackgroundTask) {
+        tokio::runtime::Handle::spawn(self, task);
+    }
+}
+
+/// Resolves to `Err(payload)` if polling the inner future panics, so a
+/// supervisor can report the panic whatever executor runs it.
+pub(crate) struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> CatchUnwind<F> {
+    pub(crate) fn new(fut: F) -> Self {
+        Self(Box::pin(fut))
+    }
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let fut = self.0.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| fut.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(out)) => Poll::Ready(Ok(out)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+/// Spawns with `tokio::spawn` and counts the tasks, for tests.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct CountingSpawner(std::sync::atomic::AtomicUsize);
+
+#[cfg(test)]
+impl CountingSpawner {
+    /// Tasks spawned so far.
+    pub(crate) fn count(&self) -> usize {
+        self.0.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+impl Spawner for CountingSpawner {
+    fn spawn(&self, task: BackgroundTask) {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        tokio::spawn(task);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn catch_unwind_reports_panics() {
+        let ok = CatchUnwind::new(async { 7 }).await;
+        assert_eq!(ok.ok(), Some(7));
+
+        let panicked = CatchUnwind::new(async { panic!("boom") }).await;
+        let payload = panicked.expect_err("panic is caught");
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+    }
+
+    #[tokio::test]
+    async fn custom_spawner_runs_tasks() {
+        let spawner = Arc::new(CountingSpawner::default());
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        spawner.spawn(Box::pin(async move {
+            let _ = tx.send(());
+        }));
+        rx.await.expect("task ran");
+        assert_eq!(spawner.count(), 1);
+    }
+}
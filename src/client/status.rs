@@ -0,0 +1,145 @@
+//! Read-only snapshots of the pool state.
+//!
+//! [`Pool::status`](crate::client::pool_sessions::Pool::status) copies the
+//! sessions, their connections, the CmdSN window and the outstanding tasks
+//! into plain structs that serialize to JSON, for dashboards and for
+//! debugging stuck workloads. The snapshot is taken without stopping I/O, so
+//! counters of different connections may be a few commands apart.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use serde::Serialize;
+
+use crate::error::{self, IscsiError};
+
+/// Snapshot of every session in a pool.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// Sessions ordered by TSIH.
+    pub sessions: Vec<SessionStatus>,
+}
+
+impl PoolStatus {
+    /// The snapshot as a JSON document.
+    pub fn to_json(&self) -> error::Result<String> {
+        simd_json::to_string(self).map_err(|e| IscsiError::Other(e.into()))
+    }
+}
+
+/// Snapshot of one session.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionStatus {
+    /// TSIH the target assigned to the session.
+    pub tsih: u16,
+    /// ISID as 12 hex digits.
+    pub isid: String,
+    /// TargetName the session logged in to.
+    pub target_name: String,
+    /// MaxConnections negotiated by the leading login.
+    pub max_connections: u16,
+    /// CmdSN the next numbered command will carry.
+    pub cmd_sn: u32,
+    /// Most advanced ExpCmdSN announced on any connection.
+    pub exp_cmd_sn: u32,
+    /// Most advanced MaxCmdSN announced on any connection.
+    pub max_cmd_sn: u32,
+    /// Connections ordered by CID.
+    pub connections: Vec<ConnectionStatus>,
+}
+
+/// Lifecycle state of a connection, see
+/// [`ClientConnection::health`](crate::client::client::ClientConnection::health).
+///
+/// `Connecting` → `LoggedIn` ⇄ `Degraded`, then `Draining` once it is being
+/// quiesced; `Failed` is terminal and can be reached from any state.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ConnectionHealth {
+    /// Transport is up but the login has not reached Full Feature Phase.
+    Connecting,
+    /// Accepting commands.
+    LoggedIn,
+    /// Still accepting commands, but a command timed out or the target
+    /// announced it will drop the connection; a keep-alive that is answered
+    /// brings it back to `LoggedIn`.
+    Degraded,
+    /// Finishing in-flight commands before a logout; takes no new ones.
+    Draining,
+    /// The transport failed; see `poison_reason`.
+    Failed,
+}
+
+impl ConnectionHealth {
+    pub(crate) const fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Connecting,
+            1 => Self::LoggedIn,
+            2 => Self::Degraded,
+            3 => Self::Draining,
+            _ => Self::Failed,
+        }
+    }
+}
+
+/// Snapshot of one connection.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStatus {
+    /// Connection ID within the session.
+    pub cid: u16,
+    /// Lifecycle state of the connection.
+    pub health: ConnectionHealth,
+    /// Why the connection was poisoned, if it was.
+    pub poison_reason: Option<String>,
+    /// Next StatSN expected from the target.
+    pub exp_stat_sn: u32,
+    /// Commands executing through the pool on this connection.
+    pub in_flight: usize,
+    /// ITTs still waiting for their final response, ascending.
+    pub pending_itts: Vec<u32>,
+    /// SCSI tasks outstanding on this connection, ordered by ITT.
+    pub tasks: Vec<TaskStatus>,
+}
+
+/// An outstanding SCSI task.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskStatus {
+    /// Initiator Task Tag of the task.
+    pub itt: u32,
+    /// LUN the task addresses, as the 8-byte wire value.
+    pub lun: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_to_json() -> anyhow::Result<()> {
+        let status = PoolStatus {
+            sessions: vec![SessionStatus {
+                tsih: 7,
+                isid: "400001370000".into(),
+                target_name: "iqn.2025-01.lab:disk".into(),
+                max_connections: 2,
+                cmd_sn: 11,
+                exp_cmd_sn: 10,
+                max_cmd_sn: 73,
+                connections: vec![ConnectionStatus {
+                    cid: 0,
+                    health: ConnectionHealth::LoggedIn,
+                    poison_reason: None,
+                    exp_stat_sn: 5,
+                    in_flight: 1,
+                    pending_itts: vec![3],
+                    tasks: vec![TaskStatus { itt: 3, lun: 1 }],
+                }],
+            }],
+        };
+        let json = status.to_json()?;
+        assert!(json.contains(r#""max_cmd_sn":73"#), "{json}");
+        assert!(json.contains(r#""health":"LoggedIn""#), "{json}");
+        assert!(json.contains(r#""tasks":[{"itt":3,"lun":1}]"#), "{json}");
+        Ok(())
+    }
+}
@@ -0,0 +1,462 @@
+//! Tape drives (SSC), e.g. the drives of a virtual tape library.
+//!
+//! [`TapeDrive`] wraps the [`LunHandle`] of a sequential-access LUN and
+//! keeps its block mode: in [`TapeBlockMode::Fixed`] reads and writes move
+//! whole blocks of the configured length, in [`TapeBlockMode::Variable`]
+//! each call moves one record of any length. Filemarks, the end of data
+//! and records of another length than asked for are reported by
+//! [`TapeDrive::read`] in its [`TapeRead`], together with the data read
+//! before them, rather than as errors.
+//!
+//! Every command here moves the medium or depends on where it is, so none
+//! is re-issued automatically after a connection loss: the caller has to
+//! check [`TapeDrive::position`] and resume from there.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use anyhow::anyhow;
+use tracing::debug;
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::{
+        mod_sense::{PageControl, fill_mode_sense6},
+        tape::{
+            BLOCK_LIMITS_LEN, DEVICE_TYPE_SEQUENTIAL, MODE_BLOCK_DESCRIPTOR_PARAMS_LEN,
+            PositionForm, SpaceCode, TAPE_LENGTH_MAX, TapeBlockLimits, TapePosition,
+            build_load_unload, build_mode_select6, build_read_block_limits,
+            build_read_position, build_rewind, build_space6, build_tape_read6,
+            build_tape_write6, build_write_filemarks6, parse_read_block_limits,
+            parse_read_position_long, parse_read_position_short, parse_tape_mode_block,
+            tape_block_descriptor_params,
+        },
+    },
+    error::{self, IscsiError},
+    models::data::sense_data::SenseData,
+};
+
+/// Allocation length of the MODE SENSE(6) reading the block descriptor.
+const MODE_SENSE_ALLOC_LEN: u8 = 255;
+
+/// All mode pages; any page brings the header and block descriptor along.
+const ALL_MODE_PAGES: u8 = 0x3F;
+
+/// NO SENSE sense key: the flags (filemark, EOM, ILI) tell what happened.
+const SENSE_KEY_NO_SENSE: u8 = 0x00;
+
+/// BLANK CHECK sense key: reading past the end of data.
+const SENSE_KEY_BLANK_CHECK: u8 = 0x08;
+
+/// Block mode of a tape drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeBlockMode {
+    /// Records of any length, one per READ / WRITE.
+    Variable,
+    /// Blocks of this many bytes.
+    Fixed(u32),
+}
+
+impl TapeBlockMode {
+    fn block_len(self) -> u32 {
+        match self {
+            TapeBlockMode::Variable => 0,
+            TapeBlockMode::Fixed(len) => len,
+        }
+    }
+}
+
+/// What stopped a [`TapeDrive::read`] before it moved everything asked
+/// for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeMark {
+    /// A filemark was read; the medium is positioned after it.
+    Filemark,
+    /// There is no more data on the medium.
+    EndOfData,
+}
+
+/// Result of a [`TapeDrive::read`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapeRead {
+    /// The record (variable mode), or as much of it as fit, or the whole
+    /// blocks read (fixed mode).
+    pub data: Vec<u8>,
+    /// Blocks (fixed mode) or bytes (variable mode) asked for but not
+    /// read. Negative when a variable-mode record was longer than asked
+    /// for; the rest of it is skipped.
+    pub residual: i64,
+    /// The filemark or end of data the read stopped at.
+    pub mark: Option<TapeMark>,
+}
+
+/// A sequential-access LUN.
+#[derive(Debug, Clone)]
+pub struct TapeDrive {
+    lun: LunHandle,
+    mode: TapeBlockMode,
+    limits: Option<TapeBlockLimits>,
+    density: u8,
+    buffered_mode: u8,
+}
+
+impl TapeDrive {
+    /// Open a tape LUN: check its device type and learn its block limits
+    /// and current block mode.
+    pub async fn open(lun: LunHandle) -> error::Result<Self> {
+        // The first command after login usually reports a Unit Attention.
+        let _ = lun.test_unit_ready().await;
+        let inquiry = lun.inquiry().await?;
+        if inquiry.device_type != DEVICE_TYPE_SEQUENTIAL {
+            return Err(IscsiError::msg(format!(
+                "{} is a {}, not a tape drive",
+                lun.lun(),
+                inquiry.device_type_str()
+            )));
+        }
+
+        let mut cdb = [0u8; 16];
+        build_read_block_limits(&mut cdb, 0);
+        let limits = match lun.read_cdb(cdb, BLOCK_LIMITS_LEN as u32, None).await {
+            Ok(data) => Some(parse_read_block_limits(&data)?),
+            Err(e) => {
+                debug!("{}: no READ BLOCK LIMITS: {e}", lun.lun());
+                None
+            },
+        };
+
+        fill_mode_sense6(
+            &mut cdb,
+            false,
+            PageControl::Current,
+            ALL_MODE_PAGES,
+            0,
+            MODE_SENSE_ALLOC_LEN,
+            0,
+        );
+        let data = lun.read_cdb(cdb, MODE_SENSE_ALLOC_LEN as u32, None).await?;
+        let block = parse_tape_mode_block(&data)?;
+        Ok(Self {
+            lun: lun.without_retry(),
+            mode: match block.block_len {
+                0 => TapeBlockMode::Variable,
+                len => TapeBlockMode::Fixed(len),
+            },
+            limits,
+            density: block.density,
+            buffered_mode: block.buffered_mode,
+        })
+    }
+
+    /// The underlying LUN handle; like the drive's own commands, those sent
+    /// through it are never re-issued automatically.
+    #[inline]
+    pub fn lun(&self) -> &LunHandle {
+        &self.lun
+    }
+
+    /// Current block mode.
+    #[inline]
+    pub fn block_mode(&self) -> TapeBlockMode {
+        self.mode
+    }
+
+    /// READ BLOCK LIMITS data, if the drive answered it.
+    #[inline]
+    pub fn block_limits(&self) -> Option<&TapeBlockLimits> {
+        self.limits.as_ref()
+    }
+
+    /// Switch between fixed and variable block mode (MODE SELECT with a
+    /// block descriptor), keeping density and buffered mode.
+    pub async fn set_block_mode(&mut self, mode: TapeBlockMode) -> error::Result<()> {
+        if let (TapeBlockMode::Fixed(len), Some(limits)) = (mode, &self.limits)
+            && (len < limits.min_block_len as u32
+                || (limits.max_block_len != 0 && len > limits.max_block_len))
+        {
+            return Err(IscsiError::msg(format!(
+                "block length {len} is outside the drive's limits {}..={}",
+                limits.min_block_len, limits.max_block_len
+            )));
+        }
+        let params = tape_block_descriptor_params(
+            self.density,
+            self.buffered_mode,
+            mode.block_len(),
+        )?;
+        let mut cdb = [0u8; 16];
+        build_mode_select6(&mut cdb, false, MODE_BLOCK_DESCRIPTOR_PARAMS_LEN as u8, 0);
+        self.lun.write_cdb(cdb, params.to_vec(), None).await?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// Read the next record of at most `len` bytes (variable mode) or the
+    /// next `len` blocks (fixed mode). A record of another length is
+    /// returned with the difference in [`TapeRead::residual`]; a filemark
+    /// or the end of data met on the way comes with the data before it.
+    pub async fn read(&self, len: u32) -> error::Result<TapeRead> {
+        let (fixed, alloc) = match self.mode {
+            TapeBlockMode::Variable => (false, len),
+            TapeBlockMode::Fixed(bs) => (
+                true,
+                len.checked_mul(bs)
+                    .ok_or_else(|| anyhow!("read of {len} blocks exceeds 4 GiB"))?,
+            ),
+        };
+        check_length(len)?;
+        let mut cdb = [0u8; 16];
+        build_tape_read6(&mut cdb, fixed, !fixed, len, 0);
+        match self
+            .lun
+            .read_cdb(cdb, alloc, None)
+            .await
+            .map_err(IscsiError::from)
+        {
+            Ok(data) => Ok(TapeRead {
+                data,
+                residual: 0,
+                mark: None,
+            }),
+            Err(IscsiError::Scsi(mut e)) => match e.sense.as_ref() {
+                Some(sense) => match short_read(self.mode, len, e.data.len(), sense) {
+                    Some((residual, mark)) => Ok(TapeRead {
+                        data: std::mem::take(&mut e.data),
+                        residual,
+                        mark,
+                    }),
+                    None => Err(IscsiError::Scsi(e)),
+                },
+                None => Err(IscsiError::Scsi(e)),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write `data` as one record (variable mode) or as whole blocks (fixed
+    /// mode). Returns `true` when the drive reported the early warning: the
+    /// write is done but the end of the medium is near.
+    pub async fn write(&self, data: Vec<u8>) -> error::Result<bool> {
+        let (fixed, len) = match self.mode {
+            TapeBlockMode::Variable => (false, data.len()),
+            TapeBlockMode::Fixed(bs) => {
+                if !data.len().is_multiple_of(bs as usize) {
+                    return Err(IscsiError::msg(format!(
+                        "{} bytes are not a whole number of {bs}-byte blocks",
+                        data.len()
+                    )));
+                }
+                (true, data.len() / bs as usize)
+            },
+        };
+        let len =
+            u32::try_from(len).map_err(|_| anyhow!("write of {len} is too long"))?;
+        check_length(len)?;
+        let mut cdb = [0u8; 16];
+        build_tape_write6(&mut cdb, fixed, len, 0);
+        match self
+            .lun
+            .write_cdb(cdb, data, None)
+            .await
+            .map_err(IscsiError::from)
+        {
+            Ok(()) => Ok(false),
+            // NO SENSE with EOM: early warning, the data was written.
+            Err(IscsiError::Scsi(e))
+                if e.sense
+                    .as_ref()
+                    .is_some_and(|s| s.eom && s.sense_key == SENSE_KEY_NO_SENSE) =>
+            {
+                Ok(true)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write `count` filemarks at the current position; 0 flushes the drive
+    /// buffer to the medium.
+    pub async fn write_filemarks(&self, count: u32) -> error::Result<()> {
+        check_length(count)?;
+        let mut cdb = [0u8; 16];
+        build_write_filemarks6(&mut cdb, count, false, 0);
+        self.lun
+            .write_cdb(cdb, Vec::new(), None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Move over `count` blocks, filemarks or runs of filemarks, backwards
+    /// when negative, or to the end of data.
+    pub async fn space(&self, code: SpaceCode, count: i32) -> error::Result<()> {
+        let mut cdb = [0u8; 16];
+        build_space6(&mut cdb, code, count, 0)?;
+        self.lun
+            .write_cdb(cdb, Vec::new(), None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Rewind to the beginning of the partition.
+    pub async fn rewind(&self) -> error::Result<()> {
+        let mut cdb = [0u8; 16];
+        build_rewind(&mut cdb, false, 0);
+        self.lun
+            .write_cdb(cdb, Vec::new(), None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Load the medium and position it at the beginning.
+    pub async fn load(&self) -> error::Result<()> {
+        let mut cdb = [0u8; 16];
+        build_load_unload(&mut cdb, true, false, false, 0);
+        self.lun
+            .write_cdb(cdb, Vec::new(), None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Rewind and unload the medium; with `hold` it stays in the drive.
+    pub async fn unload(&self, hold: bool) -> error::Result<()> {
+        let mut cdb = [0u8; 16];
+        build_load_unload(&mut cdb, false, hold, false, 0);
+        self.lun
+            .write_cdb(cdb, Vec::new(), None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Current position, from the long form of READ POSITION or, on drives
+    /// without it, the short form.
+    pub async fn position(&self) -> error::Result<TapePosition> {
+        let mut cdb = [0u8; 16];
+        build_read_position(&mut cdb, PositionForm::Long, 0);
+        let len = PositionForm::Long.data_len() as u32;
+        match self
+            .lun
+            .read_cdb(cdb, len, None)
+            .await
+            .map_err(IscsiError::from)
+        {
+            Ok(data) => return Ok(parse_read_position_long(&data)?),
+            Err(IscsiError::Scsi(e)) => debug!("no long-form READ POSITION: {e}"),
+            Err(e) => return Err(e),
+        }
+        build_read_position(&mut cdb, PositionForm::Short, 0);
+        let len = PositionForm::Short.data_len() as u32;
+        let data = self.lun.read_cdb(cdb, len, None).await?;
+        Ok(parse_read_position_short(&data)?)
+    }
+}
+
+/// Residual and mark of a READ that ended with CHECK CONDITION after
+/// `got` bytes, or `None` when the sense reports a real error.
+fn short_read(
+    mode: TapeBlockMode,
+    len: u32,
+    got: usize,
+    sense: &SenseData,
+) -> Option<(i64, Option<TapeMark>)> {
+    let mark = if sense.filemark {
+        Some(TapeMark::Filemark)
+    } else if sense.sense_key == SENSE_KEY_BLANK_CHECK {
+        Some(TapeMark::EndOfData)
+    } else if sense.ili && sense.sense_key == SENSE_KEY_NO_SENSE {
+        None
+    } else {
+        return None;
+    };
+    let residual = if sense.valid {
+        // Signed, in the low 32 bits: a variable-mode record longer than
+        // `len` has a negative residual.
+        sense.information as i32 as i64
+    } else {
+        let got = match mode {
+            TapeBlockMode::Variable => got,
+            TapeBlockMode::Fixed(bs) => got / bs.max(1) as usize,
+        };
+        len as i64 - got as i64
+    };
+    Some((residual, mark))
+}
+
+fn check_length(len: u32) -> anyhow::Result<()> {
+    if len > TAPE_LENGTH_MAX {
+        return Err(anyhow!("transfer length {len} does not fit in 24 bits"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed-format sense with the stream flags of byte 2 and, when
+    /// `information` is set, a valid INFORMATION field.
+    fn sense(key: u8, flags: u8, information: Option<i32>) -> SenseData {
+        let mut buf = [0u8; 18];
+        buf[0] = 0x70;
+        buf[2] = flags | key;
+        buf[7] = 10;
+        if let Some(info) = information {
+            buf[0] |= 0x80;
+            buf[3..7].copy_from_slice(&info.to_be_bytes());
+        }
+        SenseData::parse(&buf).expect("sense")
+    }
+
+    const FILEMARK: u8 = 0x80;
+    const ILI: u8 = 0x20;
+
+    #[test]
+    fn filemark_keeps_the_blocks_before_it() {
+        let fm = sense(SENSE_KEY_NO_SENSE, FILEMARK, Some(6));
+        assert_eq!(
+            short_read(TapeBlockMode::Fixed(512), 10, 4 * 512, &fm),
+            Some((6, Some(TapeMark::Filemark)))
+        );
+        // Without a valid INFORMATION field the residual is what did not
+        // arrive.
+        let fm = sense(SENSE_KEY_NO_SENSE, FILEMARK, None);
+        assert_eq!(
+            short_read(TapeBlockMode::Fixed(512), 10, 4 * 512, &fm),
+            Some((6, Some(TapeMark::Filemark)))
+        );
+    }
+
+    #[test]
+    fn blank_check_is_end_of_data() {
+        let eod = sense(SENSE_KEY_BLANK_CHECK, 0, Some(100));
+        assert_eq!(
+            short_read(TapeBlockMode::Variable, 100, 0, &eod),
+            Some((100, Some(TapeMark::EndOfData)))
+        );
+    }
+
+    #[test]
+    fn ili_reports_the_record_length() {
+        // Record of 60 bytes for a read of 100.
+        let short = sense(SENSE_KEY_NO_SENSE, ILI, Some(40));
+        assert_eq!(
+            short_read(TapeBlockMode::Variable, 100, 60, &short),
+            Some((40, None))
+        );
+        // Record of 150 bytes: the first 100 arrive.
+        let long = sense(SENSE_KEY_NO_SENSE, ILI, Some(-50));
+        assert_eq!(
+            short_read(TapeBlockMode::Variable, 100, 100, &long),
+            Some((-50, None))
+        );
+    }
+
+    #[test]
+    fn other_sense_stays_an_error() {
+        let medium = sense(0x03, 0, None);
+        assert_eq!(short_read(TapeBlockMode::Variable, 100, 0, &medium), None);
+        let ili_medium = sense(0x03, ILI, Some(40));
+        assert_eq!(
+            short_read(TapeBlockMode::Fixed(512), 1, 0, &ili_medium),
+            None
+        );
+    }
+}
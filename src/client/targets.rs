@@ -0,0 +1,223 @@
+//! Sessions to several targets in one pool.
+//!
+//! A config with a `targets` section describes several targets sharing the
+//! initiator identity and runtime settings (see
+//! [`TargetConfig`](crate::cfg::config::TargetConfig)).
+//! [`Pool::login_targets_from_cfg`] logs in `MaxSessions` sessions to each;
+//! afterwards commands are addressed by target name:
+//!
+//! ```ignore
+//! pool.login_targets_from_cfg(&cfg).await?;
+//! let disk = pool.target("iqn.2025-08.example:disk1")?.lun(Lun::ZERO);
+//! let rc = pool.execute_on_target("iqn.2025-08.example:disk0", |env| {
+//!     ReadCtx::from_execute_env(env, lun, 8, cdb)
+//! }).await?;
+//! ```
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{
+    cfg::config::Config,
+    client::{
+        handles::SessionHandle,
+        pool_sessions::{ExecuteEnv, Pool},
+    },
+    error::{self, IscsiError},
+    models::identifiers::{Lun, Tsih},
+    state_machine::common::StateMachineCtx,
+};
+
+/// Some targets of [`Pool::login_targets_from_cfg`] failed to log in.
+///
+/// The sessions that did log in stay in the pool and are listed in
+/// `logged_in`, including those of partly failed targets.
+#[derive(Debug, Error)]
+#[error(
+    "{} target login(s) failed: {}",
+    failed.len(),
+    failed
+        .iter()
+        .map(|(name, e)| format!("{name}: {e}"))
+        .collect::<Vec<_>>()
+        .join("; ")
+)]
+pub struct TargetLoginError {
+    /// Sessions logged in, by target, in configuration order.
+    pub logged_in: Vec<(String, Vec<Tsih>)>,
+    /// Targets that failed, in configuration order.
+    pub failed: Vec<(String, IscsiError)>,
+}
+
+impl Pool {
+    /// Log in every target of `cfg` (see [`Config::target_configs`]), one
+    /// target after the other and each through
+    /// [`Pool::login_sessions_from_cfg`].
+    ///
+    /// Returns the TSIHs of each target in configuration order. If a target
+    /// fails, the others are still logged in and a [`TargetLoginError`]
+    /// lists every failure.
+    pub async fn login_targets_from_cfg(
+        &self,
+        cfg: &Config,
+    ) -> error::Result<Vec<(String, Vec<Tsih>)>> {
+        let mut logged_in = Vec::new();
+        let mut failed = Vec::new();
+        for cfg in cfg.target_configs() {
+            let name = cfg.login.identity.target_name.clone();
+            match self.login_sessions_from_cfg(&cfg).await {
+                Ok(tsihs) => {
+                    info!("target {name}: {} sessions logged in", tsihs.len());
+                    logged_in.push((name, tsihs));
+                },
+                Err(e) => {
+                    warn!("target {name}: login failed: {e}");
+                    if let IscsiError::SessionLogin(partial) = &e
+                        && !partial.logged_in.is_empty()
+                    {
+                        logged_in.push((name.clone(), partial.logged_in.clone()));
+                    }
+                    failed.push((name, e));
+                },
+            }
+        }
+        if !failed.is_empty() {
+            return Err(TargetLoginError { logged_in, failed }.into());
+        }
+        Ok(logged_in)
+    }
+
+    /// Names of the targets the pool holds sessions to, sorted.
+    pub fn target_names(&self) -> Vec<String> {
+        let mut names = self
+            .sessions
+            .iter()
+            .map(|sess| sess.target_name.to_string())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Sessions to `target_name`, sorted by TSIH.
+    pub fn target_sessions(&self, target_name: &str) -> Vec<Tsih> {
+        let mut tsihs = self
+            .sessions
+            .iter()
+            .filter(|sess| *sess.target_name == *target_name)
+            .map(|sess| sess.tsih)
+            .collect::<Vec<_>>();
+        tsihs.sort_unstable();
+        tsihs
+    }
+
+    /// Handle for the session to `target_name` the pool would pick (see
+    /// [`Pool::pick_session`]).
+    pub fn target(&self, target_name: &str) -> error::Result<SessionHandle> {
+        self.session(self.pick_session_of(Some(target_name), None)?)
+    }
+
+    /// Same as [`Pool::execute_any`] among the sessions to `target_name`.
+    pub async fn execute_on_target<Ctx, Res, Build>(
+        &self,
+        target_name: &str,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let tsih = self.pick_session_of(Some(target_name), None)?;
+        self.execute_balanced(tsih, None, build).await
+    }
+
+    /// Same as [`Pool::execute_any_for`] among the sessions to
+    /// `target_name`.
+    pub async fn execute_on_target_for<Ctx, Res, Build>(
+        &self,
+        target_name: &str,
+        lun: Lun,
+        lba: Option<u64>,
+        build: Build,
+    ) -> error::Result<Res>
+    where
+        Build: Fn(ExecuteEnv) -> Ctx,
+        Ctx: StateMachineCtx<Ctx, Res>,
+    {
+        let tsih = self.pick_session_of(Some(target_name), Some(lun))?;
+        self.execute_balanced(tsih, lba, build).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use tokio::net::TcpListener;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::client::client::ClientConnection;
+
+    #[tokio::test]
+    async fn picks_sessions_of_the_named_target_only() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+        cfg.login.transport.target_address = listener.local_addr()?.to_string();
+        let server = tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let pool = Pool::new(&cfg);
+        for (tsih, target) in [
+            (1, "iqn.2025-08.example:disk0"),
+            (2, "iqn.2025-08.example:disk1"),
+            (3, "iqn.2025-08.example:disk1"),
+        ] {
+            let conn =
+                ClientConnection::connect(cfg.clone(), CancellationToken::new()).await?;
+            pool.insert_session(Tsih::new(tsih), target, conn);
+        }
+
+        for _ in 0..4 {
+            assert_eq!(
+                pool.pick_session_of(Some("iqn.2025-08.example:disk0"), None)?,
+                Tsih::new(1)
+            );
+            let tsih = pool.pick_session_of(Some("iqn.2025-08.example:disk1"), None)?;
+            assert!([Tsih::new(2), Tsih::new(3)].contains(&tsih), "{tsih}");
+        }
+        assert!(
+            pool.pick_session_of(Some("iqn.2025-08.example:disk2"), None)
+                .is_err()
+        );
+        // The same LUN number is a different device on each target.
+        assert!(pool.pick_session(Some(Lun::ZERO)).is_err());
+
+        pool.drop_session_local(Tsih::new(1), "test");
+        // Once a single target is left, any session of it may be picked.
+        let tsih = pool.pick_session(None)?;
+        assert!([Tsih::new(2), Tsih::new(3)].contains(&tsih), "{tsih}");
+        server.abort();
+        Ok(())
+    }
+
+    #[test]
+    fn target_login_error_names_failed_targets() {
+        let e = TargetLoginError {
+            logged_in: vec![("iqn.2025-08.example:disk0".into(), vec![Tsih::new(1)])],
+            failed: vec![(
+                "iqn.2025-08.example:disk1".into(),
+                IscsiError::Timeout("no login response".into()),
+            )],
+        };
+        let msg = e.to_string();
+        assert!(msg.starts_with("1 target login(s) failed"), "{msg}");
+        assert!(msg.contains("disk1: no login response"), "{msg}");
+    }
+}
@@ -0,0 +1,95 @@
+//! Thin provisioning: deallocating byte ranges of a LUN with UNMAP.
+//!
+//! [`LunHandle::punch_hole`] trims the range to whole unmap granules (Block
+//! Limits VPD page) and splits it into UNMAP commands that keep to the
+//! target's MAXIMUM UNMAP LBA COUNT and descriptor count.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use anyhow::Context;
+use tracing::debug;
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::{
+        inquiry::BlockLimits,
+        unmap::{build_unmap, fill_unmap_parameters, plan_unmap, unmap_aligned},
+    },
+    error,
+};
+
+/// What [`LunHandle::punch_hole`] deallocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PunchedHole {
+    /// Byte offset of the deallocated range.
+    pub offset: u64,
+    /// Bytes deallocated; 0 when no whole unmap granule lay in the request.
+    pub len: u64,
+    /// UNMAP commands sent.
+    pub commands: usize,
+}
+
+impl LunHandle {
+    /// Deallocate `len` bytes at byte `offset` (UNMAP).
+    ///
+    /// Only whole unmap granules are released: the range is shrunk to the
+    /// OPTIMAL UNMAP GRANULARITY and UNMAP GRANULARITY ALIGNMENT of the
+    /// Block Limits page, then sent in as many UNMAP commands as its
+    /// MAXIMUM UNMAP LBA COUNT and MAXIMUM UNMAP BLOCK DESCRIPTOR COUNT
+    /// require. A LUN without the page is unmapped block by block, in
+    /// commands of one descriptor and at most
+    /// [`UNMAP_FALLBACK_LBA_COUNT`](crate::control_block::unmap::UNMAP_FALLBACK_LBA_COUNT)
+    /// blocks. The returned [`PunchedHole`] says what was released.
+    pub async fn punch_hole(&self, offset: u64, len: u64) -> error::Result<PunchedHole> {
+        let limits = match self.block_limits().await {
+            Ok(limits) => limits,
+            Err(e) => {
+                debug!(
+                    "{}: no Block Limits page ({e}), UNMAP with fallback limits",
+                    self.lun()
+                );
+                BlockLimits::default()
+            },
+        };
+        self.punch_hole_with(offset, len, &limits).await
+    }
+
+    /// [`Self::punch_hole`] with Block Limits the caller already read.
+    pub async fn punch_hole_with(
+        &self,
+        offset: u64,
+        len: u64,
+        limits: &BlockLimits,
+    ) -> error::Result<PunchedHole> {
+        let cap = self.capacity().await?;
+        let bs = cap.block_size as u64;
+        let end = offset.checked_add(len).context("byte range overflows")?;
+        if end > cap.blocks * bs {
+            return Err(error::IscsiError::msg(format!(
+                "{len} bytes at offset {offset} run past the end of the {}-byte LUN",
+                cap.blocks * bs
+            )));
+        }
+        let Some((lba, end_lba)) = unmap_aligned(offset.div_ceil(bs), end / bs, limits)
+        else {
+            return Ok(PunchedHole {
+                offset,
+                ..Default::default()
+            });
+        };
+
+        let commands = plan_unmap(lba, end_lba - lba, limits);
+        for descriptors in &commands {
+            let params = fill_unmap_parameters(descriptors);
+            let mut cdb = [0u8; 16];
+            build_unmap(&mut cdb, false, params.len() as u16, 0);
+            self.write_cdb(cdb, params, Some(descriptors[0].0)).await?;
+        }
+        Ok(PunchedHole {
+            offset: lba * bs,
+            len: (end_lba - lba) * bs,
+            commands: commands.len(),
+        })
+    }
+}
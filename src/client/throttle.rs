@@ -0,0 +1,150 @@
+//! Per-connection bandwidth limits.
+//!
+//! A token bucket caps the payload bytes of Data-Out (and immediate data)
+//! sent and of Data-In consumed on one connection, so bulk jobs such as
+//! background replication leave room on the target's link. Sending waits
+//! before the PDU is written; receiving waits after a Data-In PDU was read,
+//! which stalls the read loop and lets TCP flow control slow the target down.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Mutex, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::{Instant, sleep};
+
+/// Bandwidth caps of a connection (`runtime.Throttle`). `0` means unlimited.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ThrottlePolicy {
+    #[serde(rename = "DataOutBytesPerSec")]
+    /// Write payload rate (Data-Out and immediate data), bytes per second.
+    pub data_out_bytes_per_sec: u64,
+
+    #[serde(rename = "DataInBytesPerSec")]
+    /// Read payload rate (Data-In), bytes per second.
+    pub data_in_bytes_per_sec: u64,
+
+    #[serde(rename = "BurstBytes")]
+    /// Bytes that may pass at full speed after an idle period; `0` means one
+    /// second's worth of the rate.
+    pub burst_bytes: u64,
+}
+
+impl ThrottlePolicy {
+    /// Bucket for the Data-Out direction, if limited.
+    pub fn data_out(&self) -> Option<TokenBucket> {
+        TokenBucket::new(self.data_out_bytes_per_sec, self.burst_bytes)
+    }
+
+    /// Bucket for the Data-In direction, if limited.
+    pub fn data_in(&self) -> Option<TokenBucket> {
+        TokenBucket::new(self.data_in_bytes_per_sec, self.burst_bytes)
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Token bucket refilled at a fixed byte rate.
+///
+/// Takes larger than the burst are allowed and leave the bucket in debt, so
+/// a single PDU bigger than `burst` still goes through after waiting its
+/// share of time; later callers wait for the debt to be repaid first.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Mutex<Bucket>,
+}
+
+impl TokenBucket {
+    /// `None` when `bytes_per_sec` is zero (unlimited).
+    pub fn new(bytes_per_sec: u64, burst_bytes: u64) -> Option<Self> {
+        if bytes_per_sec == 0 {
+            return None;
+        }
+        let burst = if burst_bytes == 0 {
+            bytes_per_sec
+        } else {
+            burst_bytes
+        } as f64;
+        Some(Self {
+            rate: bytes_per_sec as f64,
+            burst,
+            state: Mutex::new(Bucket {
+                tokens: burst,
+                last: Instant::now(),
+            }),
+        })
+    }
+
+    /// Takes `bytes` tokens and returns how long the caller must wait before
+    /// using them.
+    fn take(&self, bytes: usize) -> Duration {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let now = Instant::now();
+        let refill = now.duration_since(state.last).as_secs_f64() * self.rate;
+        state.tokens = (state.tokens + refill).min(self.burst) - bytes as f64;
+        state.last = now;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        }
+    }
+
+    /// Waits until `bytes` may pass.
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = self.take(bytes);
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_passes_then_rate_applies() {
+        let bucket = TokenBucket::new(1_000, 500).expect("limited");
+        let start = Instant::now();
+        bucket.acquire(500).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        bucket.acquire(250).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+
+        // Larger than the burst: goes through after its share of time.
+        bucket.acquire(2_000).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(2_250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_time_refills_up_to_burst() {
+        let bucket = TokenBucket::new(1_000, 0).expect("limited");
+        bucket.acquire(1_000).await;
+        sleep(Duration::from_secs(10)).await;
+
+        let start = Instant::now();
+        bucket.acquire(1_000).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        bucket.acquire(100).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn zero_rate_is_unlimited() {
+        assert!(ThrottlePolicy::default().data_out().is_none());
+        assert!(ThrottlePolicy::default().data_in().is_none());
+    }
+}
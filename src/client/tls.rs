@@ -0,0 +1,295 @@
+//! TLS for the iSCSI transport.
+//!
+//! iSCSI itself has no STARTTLS; TLS is used when the target sits behind a
+//! TLS terminator (stunnel, HAProxy, a TLS-capable target). The handshake
+//! runs right after the TCP connect (and after a SOCKS5 proxy, if any), then
+//! the login proceeds over the encrypted stream. The server certificate is
+//! verified against `CaFile`; `ClientCert`/`ClientKey` enable mutual TLS.
+//!
+//! [`TlsSettings`] is always available so that configurations parse the same
+//! way regardless of features; connecting with it requires the `tls` feature.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::portal::PortalTarget;
+
+/// TLS parameters of a target (`login.transport.Tls`).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TlsSettings {
+    #[serde(rename = "CaFile")]
+    /// PEM bundle of the CAs trusted to sign the server certificate.
+    pub ca_file: PathBuf,
+
+    #[serde(
+        rename = "ClientCert",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// PEM certificate chain presented for mutual TLS.
+    pub client_cert: Option<PathBuf>,
+
+    #[serde(rename = "ClientKey", default, skip_serializing_if = "Option::is_none")]
+    /// PEM private key of `ClientCert`.
+    pub client_key: Option<PathBuf>,
+
+    #[serde(
+        rename = "ServerName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// Name sent as SNI and checked against the server certificate; defaults
+    /// to the portal host (required for Unix socket portals).
+    pub server_name: Option<String>,
+}
+
+impl TlsSettings {
+    /// Name the server certificate must match when connecting to `portal`,
+    /// if one is known.
+    pub fn server_name_for<'a>(&'a self, portal: &'a PortalTarget) -> Option<&'a str> {
+        match (&self.server_name, portal) {
+            (Some(name), _) => Some(name),
+            (None, PortalTarget::Tcp(addr)) => Some(&addr.host),
+            (None, PortalTarget::Unix(_)) => None,
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+pub(crate) use imp::{TlsClient, client_config};
+
+#[cfg(feature = "tls")]
+mod imp {
+    use std::{io, sync::Arc};
+
+    use anyhow::{Context, Result, anyhow, bail};
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio_rustls::{
+        TlsConnector,
+        client::TlsStream,
+        rustls::{
+            ClientConfig, RootCertStore,
+            crypto::ring,
+            pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject},
+        },
+    };
+
+    use super::TlsSettings;
+
+    /// Builds the rustls client configuration, reading the PEM files.
+    pub(crate) fn client_config(settings: &TlsSettings) -> Result<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_file_iter(&settings.ca_file)
+            .with_context(|| format!("CaFile {}", settings.ca_file.display()))?
+        {
+            roots.add(cert.context("CaFile")?).context("CaFile")?;
+        }
+        if roots.is_empty() {
+            bail!("CaFile {}: no certificates", settings.ca_file.display());
+        }
+
+        let builder =
+            ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots);
+        let config = match (&settings.client_cert, &settings.client_key) {
+            (Some(cert), Some(key)) => {
+                let chain = CertificateDer::pem_file_iter(cert)
+                    .and_then(Iterator::collect)
+                    .with_context(|| format!("ClientCert {}", cert.display()))?;
+                let key = PrivateKeyDer::from_pem_file(key)
+                    .with_context(|| format!("ClientKey {}", key.display()))?;
+                builder.with_client_auth_cert(chain, key)?
+            },
+            (None, None) => builder.with_no_client_auth(),
+            _ => bail!("ClientCert and ClientKey must be set together"),
+        };
+        Ok(config)
+    }
+
+    /// Client side of the handshake, ready to wrap a connected stream.
+    pub(crate) struct TlsClient {
+        connector: TlsConnector,
+        name: ServerName<'static>,
+    }
+
+    impl TlsClient {
+        /// Loads the certificates of `settings` for a server called `name`.
+        pub(crate) fn new(settings: &TlsSettings, name: &str) -> Result<Self> {
+            Ok(Self {
+                connector: TlsConnector::from(Arc::new(client_config(settings)?)),
+                name: ServerName::try_from(name.to_owned())
+                    .map_err(|e| anyhow!("TLS server name {name:?}: {e}"))?,
+            })
+        }
+
+        /// Runs the TLS handshake over an established stream.
+        pub(crate) async fn connect<S>(&self, stream: S) -> io::Result<TlsStream<S>>
+        where S: AsyncRead + AsyncWrite + Unpin {
+            self.connector.connect(self.name.clone(), stream).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use anyhow::Result;
+    use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, IsCa, KeyPair};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+    use tokio_rustls::{
+        TlsAcceptor,
+        rustls::{
+            RootCertStore, ServerConfig,
+            crypto::ring,
+            pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+            server::WebPkiClientVerifier,
+        },
+    };
+
+    use super::*;
+
+    struct Pki {
+        ca: String,
+        server_cert: String,
+        server_key: String,
+        client_cert: String,
+        client_key: String,
+    }
+
+    fn pki() -> Result<Pki> {
+        let mut ca_params = CertificateParams::new(Vec::<String>::new())?;
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = CertifiedIssuer::self_signed(ca_params, KeyPair::generate()?)?;
+
+        let server_key = KeyPair::generate()?;
+        let server = CertificateParams::new(vec!["target.lab".to_string()])?
+            .signed_by(&server_key, &ca)?;
+        let client_key = KeyPair::generate()?;
+        let client = CertificateParams::new(vec!["initiator.lab".to_string()])?
+            .signed_by(&client_key, &ca)?;
+        Ok(Pki {
+            ca: ca.pem(),
+            server_cert: server.pem(),
+            server_key: server_key.serialize_pem(),
+            client_cert: client.pem(),
+            client_key: client_key.serialize_pem(),
+        })
+    }
+
+    fn write(dir: &Path, name: &str, pem: &str) -> Result<PathBuf> {
+        let path = dir.join(name);
+        std::fs::write(&path, pem)?;
+        Ok(path)
+    }
+
+    /// Echoes one 4-byte message over TLS, requiring a client certificate
+    /// when `mutual` is set.
+    async fn echo_server(listener: TcpListener, pki: &Pki, mutual: bool) -> Result<()> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = if mutual {
+            let mut roots = RootCertStore::empty();
+            roots.add(CertificateDer::from_pem_slice(pki.ca.as_bytes())?)?;
+            builder.with_client_cert_verifier(
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                    .build()?,
+            )
+        } else {
+            builder.with_no_client_auth()
+        };
+        let config = builder.with_single_cert(
+            vec![CertificateDer::from_pem_slice(pki.server_cert.as_bytes())?],
+            PrivateKeyDer::from_pem_slice(pki.server_key.as_bytes())?,
+        )?;
+
+        let (tcp, _) = listener.accept().await?;
+        let mut tls = TlsAcceptor::from(Arc::new(config)).accept(tcp).await?;
+        let mut buf = [0u8; 4];
+        tls.read_exact(&mut buf).await?;
+        tls.write_all(&buf).await?;
+        tls.shutdown().await?;
+        Ok(())
+    }
+
+    async fn round_trip(mutual: bool) -> Result<()> {
+        let pki = pki()?;
+        let dir = std::env::temp_dir().join(format!(
+            "iscsi-tls-{}-{}",
+            std::process::id(),
+            mutual
+        ));
+        std::fs::create_dir_all(&dir)?;
+        let settings = TlsSettings {
+            ca_file: write(&dir, "ca.pem", &pki.ca)?,
+            client_cert: mutual
+                .then(|| write(&dir, "client.pem", &pki.client_cert))
+                .transpose()?,
+            client_key: mutual
+                .then(|| write(&dir, "client.key", &pki.client_key))
+                .transpose()?,
+            server_name: Some("target.lab".to_string()),
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let portal: PortalTarget = listener.local_addr()?.to_string().parse()?;
+        let name = settings.server_name_for(&portal).expect("ServerName set");
+        let server =
+            tokio::spawn(async move { echo_server(listener, &pki, mutual).await });
+
+        let tcp = TcpStream::connect(portal.to_string()).await?;
+        let mut tls = TlsClient::new(&settings, name)?.connect(tcp).await?;
+        tls.write_all(b"iscs").await?;
+        let mut buf = [0u8; 4];
+        tls.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"iscs");
+        server.await??;
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verifies_server_against_ca_file() -> Result<()> {
+        round_trip(false).await
+    }
+
+    #[tokio::test]
+    async fn presents_client_certificate() -> Result<()> {
+        round_trip(true).await
+    }
+
+    #[tokio::test]
+    async fn rejects_certificate_for_other_name() -> Result<()> {
+        let pki = pki()?;
+        let dir =
+            std::env::temp_dir().join(format!("iscsi-tls-{}-name", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let settings = TlsSettings {
+            ca_file: write(&dir, "ca.pem", &pki.ca)?,
+            client_cert: None,
+            client_key: None,
+            server_name: Some("other.lab".to_string()),
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let portal: PortalTarget = listener.local_addr()?.to_string().parse()?;
+        let name = settings.server_name_for(&portal).expect("ServerName set");
+        let server =
+            tokio::spawn(async move { echo_server(listener, &pki, false).await });
+
+        let tcp = TcpStream::connect(portal.to_string()).await?;
+        assert!(TlsClient::new(&settings, name)?.connect(tcp).await.is_err());
+        assert!(server.await?.is_err());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
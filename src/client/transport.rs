@@ -0,0 +1,73 @@
+//! Byte streams a [`ClientConnection`](crate::client::client::ClientConnection)
+//! can run over.
+//!
+//! A connection only needs an ordered, reliable, full-duplex byte stream.
+//! Plain TCP is the default, Unix domain sockets serve local targets, TCP can
+//! be driven through io_uring (feature `io-uring`) and TLS (feature `tls`)
+//! can wrap any of them. The
+//! stream is split into independently locked read and write halves, boxed so
+//! that the rest of the client does not become generic over the stream type.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::fmt::Debug;
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+
+/// Read half of a connection stream.
+pub trait ReadStream: AsyncRead + Send + Unpin + Debug {}
+impl<T: AsyncRead + Send + Unpin + Debug> ReadStream for T {}
+
+/// Write half of a connection stream.
+pub trait WriteStream: AsyncWrite + Send + Unpin + Debug {}
+impl<T: AsyncWrite + Send + Unpin + Debug> WriteStream for T {}
+
+/// Type-erased read half.
+pub type BoxedReader = Box<dyn ReadStream>;
+/// Type-erased write half.
+pub type BoxedWriter = Box<dyn WriteStream>;
+
+/// Splits a full-duplex stream into boxed halves.
+///
+/// Sockets that can be split without locking (`TcpStream::into_split`)
+/// should box their own halves instead.
+pub fn split<S>(stream: S) -> (BoxedReader, BoxedWriter)
+where S: AsyncRead + AsyncWrite + Send + Debug + 'static {
+    let (r, w) = tokio::io::split(stream);
+    (Box::new(r), Box::new(w))
+}
+
+/// A freshly connected socket, before TLS and splitting.
+#[derive(Debug)]
+pub(crate) enum Connected {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring(Box<crate::client::uring::UringStream>),
+}
+
+impl Connected {
+    /// Splits into boxed halves without the lock `tokio::io::split` needs.
+    pub(crate) fn into_split(self) -> (BoxedReader, BoxedWriter) {
+        match self {
+            Self::Tcp(stream) => {
+                let (r, w) = stream.into_split();
+                (Box::new(r), Box::new(w))
+            },
+            #[cfg(unix)]
+            Self::Unix(stream) => {
+                let (r, w) = stream.into_split();
+                (Box::new(r), Box::new(w))
+            },
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring(stream) => split(stream),
+        }
+    }
+}
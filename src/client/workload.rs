@@ -0,0 +1,341 @@
+//! Workload generator for target performance tests (a small fio).
+//!
+//! [`run_workload`] keeps [`WorkloadSpec::queue_depth`] READs / WRITEs in
+//! flight for a while, spread over the given LUN handles, which may come
+//! from different sessions; each handle's commands are balanced over the
+//! connections of its session by the pool. The [`WorkloadReport`] gives
+//! IOPS, throughput and latency percentiles. Offsets come from a seeded
+//! generator, so a run can be repeated exactly.
+//!
+//! Writes overwrite the LUN: point write workloads at scratch LUNs only.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    ops::Range,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use tokio::{task::JoinSet, time::Instant};
+use tracing::debug;
+
+use crate::{
+    client::{
+        handles::LunHandle,
+        scrub::{XorShift, fill_pattern},
+    },
+    error,
+};
+
+/// How a workload picks the LBA of the next I/O.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessPattern {
+    /// Consecutive I/Os, wrapping at the end of the range; all workers
+    /// share one cursor.
+    #[default]
+    Sequential,
+    /// Uniformly random I/O-aligned offsets.
+    Random,
+}
+
+/// What [`run_workload`] does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkloadSpec {
+    /// Offset selection.
+    pub pattern: AccessPattern,
+    /// Share of READs, 0..=100; the rest are WRITEs.
+    pub read_percent: u8,
+    /// Bytes per I/O, a whole number of LUN blocks.
+    pub io_bytes: u32,
+    /// I/Os in flight across all handles.
+    pub queue_depth: usize,
+    /// How long to run.
+    pub duration: Duration,
+    /// Stop earlier after this many I/Os.
+    pub max_ios: Option<u64>,
+    /// Blocks to use; `None` for the whole LUN (the smallest of the
+    /// handles).
+    pub lbas: Option<Range<u64>>,
+    /// Seed of the offset and read / write choices.
+    pub seed: u64,
+}
+
+impl Default for WorkloadSpec {
+    fn default() -> Self {
+        Self {
+            pattern: AccessPattern::Sequential,
+            read_percent: 100,
+            io_bytes: 4096,
+            queue_depth: 16,
+            duration: Duration::from_secs(10),
+            max_ios: None,
+            lbas: None,
+            seed: 0,
+        }
+    }
+}
+
+/// Completion latency distribution of a workload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencySummary {
+    /// Fastest completion.
+    pub min: Duration,
+    /// Average completion time.
+    pub mean: Duration,
+    /// Median.
+    pub p50: Duration,
+    /// 90th percentile.
+    pub p90: Duration,
+    /// 99th percentile.
+    pub p99: Duration,
+    /// 99.9th percentile.
+    pub p999: Duration,
+    /// Slowest completion.
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    /// Summary of `samples`, which are sorted in place.
+    pub fn from_samples(samples: &mut [Duration]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+        let total: Duration = samples.iter().sum();
+        Self {
+            min: samples[0],
+            mean: total / samples.len() as u32,
+            p50: percentile(samples, 500),
+            p90: percentile(samples, 900),
+            p99: percentile(samples, 990),
+            p999: percentile(samples, 999),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+/// Outcome of a [`run_workload`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WorkloadReport {
+    /// READs completed successfully.
+    pub reads: u64,
+    /// WRITEs completed successfully.
+    pub writes: u64,
+    /// I/Os that failed; they count in no other field.
+    pub errors: u64,
+    /// Bytes moved by the successful I/Os.
+    pub bytes: u64,
+    /// Wall time of the run.
+    pub elapsed: Duration,
+    /// Latency of the successful I/Os.
+    pub latency: LatencySummary,
+}
+
+impl WorkloadReport {
+    /// Successful I/Os per second.
+    pub fn iops(&self) -> f64 {
+        (self.reads + self.writes) as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Bytes per second.
+    pub fn throughput(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Run `spec` against `luns`; the queue depth slots are dealt to the
+/// handles round-robin. Failed I/Os are counted, not returned.
+pub async fn run_workload(
+    luns: &[LunHandle],
+    spec: &WorkloadSpec,
+) -> error::Result<WorkloadReport> {
+    let first = luns.first().context("no LUN to run the workload on")?;
+    let cap = first.capacity().await?;
+    let mut blocks = cap.blocks;
+    for lun in &luns[1..] {
+        let other = lun.capacity().await?;
+        if other.block_size != cap.block_size {
+            return Err(error::IscsiError::msg(format!(
+                "{} and {} have different block sizes",
+                first.lun(),
+                lun.lun()
+            )));
+        }
+        blocks = blocks.min(other.blocks);
+    }
+    let bs = cap.block_size;
+    if spec.io_bytes == 0 || !spec.io_bytes.is_multiple_of(bs) {
+        return Err(
+            anyhow!("I/O size {} is not whole {bs}-byte blocks", spec.io_bytes).into(),
+        );
+    }
+    let io_blocks = spec.io_bytes / bs;
+    let range = spec.lbas.clone().unwrap_or(0..blocks);
+    if range.end > blocks {
+        return Err(error::IscsiError::msg(format!(
+            "LBA range {range:?} beyond the end of the LUN"
+        )));
+    }
+    let slots = (range.end.saturating_sub(range.start)) / io_blocks as u64;
+    if slots == 0 {
+        return Err(
+            anyhow!("LBA range {range:?} holds no {} byte I/O", spec.io_bytes).into(),
+        );
+    }
+
+    let shared = Arc::new(Shared {
+        range_start: range.start,
+        slots,
+        io_blocks,
+        cursor: AtomicU64::new(0),
+        started: AtomicU64::new(0),
+        max_ios: spec.max_ios.unwrap_or(u64::MAX),
+        spec: spec.clone(),
+    });
+    let mut payload = vec![0u8; spec.io_bytes as usize];
+    fill_pattern(&mut payload, bs as usize, range.start, spec.seed);
+    let payload: Arc<[u8]> = payload.into();
+
+    let start = Instant::now();
+    let deadline = start + spec.duration;
+    let mut tasks = JoinSet::new();
+    for worker in 0..spec.queue_depth.max(1) {
+        let lun = luns[worker % luns.len()].clone();
+        let (shared, payload) = (Arc::clone(&shared), Arc::clone(&payload));
+        tasks.spawn(
+            async move { shared.worker(lun, worker as u64, deadline, payload).await },
+        );
+    }
+
+    let mut report = WorkloadReport::default();
+    let mut samples = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let done = joined.context("workload task failed")?;
+        report.reads += done.reads;
+        report.writes += done.writes;
+        report.errors += done.errors;
+        samples.extend(done.latencies);
+    }
+    report.elapsed = start.elapsed();
+    report.bytes = (report.reads + report.writes) * spec.io_bytes as u64;
+    report.latency = LatencySummary::from_samples(&mut samples);
+    Ok(report)
+}
+
+/// State the workers of one run share.
+#[derive(Debug)]
+struct Shared {
+    range_start: u64,
+    /// I/O-sized slots in the range.
+    slots: u64,
+    io_blocks: u32,
+    /// Next slot of a sequential workload.
+    cursor: AtomicU64,
+    /// I/Os started, for [`WorkloadSpec::max_ios`].
+    started: AtomicU64,
+    max_ios: u64,
+    spec: WorkloadSpec,
+}
+
+/// What one worker did.
+#[derive(Debug, Default)]
+struct WorkerTotals {
+    reads: u64,
+    writes: u64,
+    errors: u64,
+    latencies: Vec<Duration>,
+}
+
+impl Shared {
+    /// Issue one I/O at a time on `lun` until the deadline or the I/O limit.
+    async fn worker(
+        &self,
+        lun: LunHandle,
+        index: u64,
+        deadline: Instant,
+        payload: Arc<[u8]>,
+    ) -> WorkerTotals {
+        let mut rng = XorShift::new(self.spec.seed, index + 1);
+        let mut totals = WorkerTotals::default();
+        while Instant::now() < deadline
+            && self.started.fetch_add(1, Ordering::Relaxed) < self.max_ios
+        {
+            let slot = match self.spec.pattern {
+                AccessPattern::Sequential => {
+                    self.cursor.fetch_add(1, Ordering::Relaxed) % self.slots
+                },
+                AccessPattern::Random => rng.next() % self.slots,
+            };
+            let lba = self.range_start + slot * self.io_blocks as u64;
+            let read = rng.next() % 100 < self.spec.read_percent as u64;
+            let issued = Instant::now();
+            let result = if read {
+                lun.read_at(lba, self.io_blocks).await.map(drop)
+            } else {
+                lun.write_at(lba, &*payload).await
+            };
+            match result {
+                Ok(()) => {
+                    totals.latencies.push(issued.elapsed());
+                    if read {
+                        totals.reads += 1;
+                    } else {
+                        totals.writes += 1;
+                    }
+                },
+                Err(e) => {
+                    debug!("{}: workload I/O at LBA {lba} failed: {e}", lun.lun());
+                    totals.errors += 1;
+                },
+            }
+        }
+        totals
+    }
+}
+
+/// The nearest-rank quantile of `per_mille` (0..=1000) of `sorted`, which
+/// must not be empty.
+fn percentile(sorted: &[Duration], per_mille: usize) -> Duration {
+    let rank = (per_mille * sorted.len()).div_ceil(1000);
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_latencies() {
+        let mut samples: Vec<_> = (1..=1000).rev().map(Duration::from_micros).collect();
+        let summary = LatencySummary::from_samples(&mut samples);
+        assert_eq!(summary.min, Duration::from_micros(1));
+        assert_eq!(summary.p50, Duration::from_micros(500));
+        assert_eq!(summary.p99, Duration::from_micros(990));
+        assert_eq!(summary.p999, Duration::from_micros(999));
+        assert_eq!(summary.max, Duration::from_micros(1000));
+        assert_eq!(summary.mean, Duration::from_nanos(500_500));
+        assert_eq!(
+            LatencySummary::from_samples(&mut []),
+            LatencySummary::default()
+        );
+    }
+
+    #[test]
+    fn report_rates() {
+        let report = WorkloadReport {
+            reads: 300,
+            writes: 100,
+            bytes: 400 * 4096,
+            elapsed: Duration::from_secs(2),
+            ..Default::default()
+        };
+        assert_eq!(report.iops(), 200.0);
+        assert_eq!(report.throughput(), 200.0 * 4096.0);
+    }
+}
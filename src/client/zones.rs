@@ -0,0 +1,283 @@
+//! Zoned block devices (ZBC), such as host-managed SMR disks.
+//!
+//! [`LunHandle::report_zones`] lists the zones with their write pointers and
+//! [`LunHandle::zone_action`] opens, closes, finishes or resets them.
+//! [`ZoneMap`] keeps the write pointers of a LUN so writes can be checked
+//! before they are sent; [`IscsiDevice`](crate::client::device::IscsiDevice)
+//! uses it on host-managed LUNs.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use crate::{
+    client::handles::LunHandle,
+    control_block::zbc::{
+        REPORT_ZONES_HEADER_LEN, ZONE_DESCRIPTOR_LEN, ZoneAction, ZoneCondition,
+        ZoneDescriptor, ZoneReportingOptions, build_report_zones, build_zone_action,
+        parse_report_zones,
+    },
+    error,
+};
+
+/// Zone descriptors requested per REPORT ZONES.
+const REPORT_ZONES_BATCH: usize = 1024;
+
+impl LunHandle {
+    /// Zones from the one holding `start_lba` to the end of the LUN that
+    /// match `options`, fetched in batches of [`REPORT_ZONES_BATCH`].
+    pub async fn report_zones(
+        &self,
+        start_lba: u64,
+        options: ZoneReportingOptions,
+    ) -> error::Result<Vec<ZoneDescriptor>> {
+        let alloc_len =
+            (REPORT_ZONES_HEADER_LEN + REPORT_ZONES_BATCH * ZONE_DESCRIPTOR_LEN) as u32;
+        let mut zones = Vec::new();
+        let mut next = start_lba;
+        loop {
+            let mut cdb = [0u8; 16];
+            build_report_zones(&mut cdb, next, options, true, alloc_len, 0);
+            let data = self.read_cdb(cdb, alloc_len, None).await?;
+            let (header, batch) = parse_report_zones(&data)?;
+            let Some(last) = batch.last() else {
+                return Ok(zones);
+            };
+            next = last.end();
+            let done = batch.len() < REPORT_ZONES_BATCH || next > header.max_lba;
+            zones.extend(batch);
+            if done {
+                return Ok(zones);
+            }
+        }
+    }
+
+    /// The zone holding `lba`.
+    pub async fn zone_at(&self, lba: u64) -> error::Result<ZoneDescriptor> {
+        let mut cdb = [0u8; 16];
+        let alloc_len = (REPORT_ZONES_HEADER_LEN + ZONE_DESCRIPTOR_LEN) as u32;
+        build_report_zones(&mut cdb, lba, ZoneReportingOptions::All, true, alloc_len, 0);
+        let data = self.read_cdb(cdb, alloc_len, None).await?;
+        let (_, zones) = parse_report_zones(&data)?;
+        zones
+            .into_iter()
+            .find(|z| z.contains(lba))
+            .ok_or_else(|| error::IscsiError::msg(format!("no zone holds LBA {lba}")))
+    }
+
+    /// Apply `action` to the zone starting at `zone_start`, or to every zone
+    /// when `zone_start` is `None` (ALL). A [`ZoneMap`] taken before is
+    /// stale afterwards; [`IscsiDevice::zone_action`] keeps its map current.
+    ///
+    /// [`IscsiDevice::zone_action`]: crate::client::device::IscsiDevice::zone_action
+    pub async fn zone_action(
+        &self,
+        action: ZoneAction,
+        zone_start: Option<u64>,
+    ) -> error::Result<()> {
+        let mut cdb = [0u8; 16];
+        build_zone_action(
+            &mut cdb,
+            action,
+            zone_start.unwrap_or(0),
+            zone_start.is_none(),
+            0,
+        );
+        self.write_cdb(cdb, Vec::new(), zone_start)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// OPEN ZONE: keep the zone open for writes.
+    pub async fn open_zone(&self, zone_start: u64) -> error::Result<()> {
+        self.zone_action(ZoneAction::Open, Some(zone_start)).await
+    }
+
+    /// CLOSE ZONE: release the resources of an open zone.
+    pub async fn close_zone(&self, zone_start: u64) -> error::Result<()> {
+        self.zone_action(ZoneAction::Close, Some(zone_start)).await
+    }
+
+    /// FINISH ZONE: move the write pointer to the end of the zone.
+    pub async fn finish_zone(&self, zone_start: u64) -> error::Result<()> {
+        self.zone_action(ZoneAction::Finish, Some(zone_start)).await
+    }
+
+    /// RESET WRITE POINTER: empty the zone.
+    pub async fn reset_zone(&self, zone_start: u64) -> error::Result<()> {
+        self.zone_action(ZoneAction::ResetWritePointer, Some(zone_start))
+            .await
+    }
+
+    /// Every zone of the LUN as a [`ZoneMap`].
+    pub async fn zone_map(&self) -> error::Result<ZoneMap> {
+        let zones = self.report_zones(0, ZoneReportingOptions::All).await?;
+        Ok(ZoneMap::new(zones))
+    }
+}
+
+/// Zones of a LUN with their write pointers, ordered by start LBA.
+///
+/// The map only learns about writes made through [`ZoneMap::advance`];
+/// writes from other initiators make it stale until it is refreshed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ZoneMap {
+    zones: Vec<ZoneDescriptor>,
+}
+
+impl ZoneMap {
+    /// Map of `zones`, in any order.
+    pub fn new(mut zones: Vec<ZoneDescriptor>) -> Self {
+        zones.sort_by_key(|z| z.start);
+        Self { zones }
+    }
+
+    /// The zones, by start LBA.
+    #[inline]
+    pub fn zones(&self) -> &[ZoneDescriptor] {
+        &self.zones
+    }
+
+    /// The zone holding `lba`.
+    pub fn zone_of(&self, lba: u64) -> Option<&ZoneDescriptor> {
+        let i = self.zones.partition_point(|z| z.end() <= lba);
+        self.zones.get(i).filter(|z| z.contains(lba))
+    }
+
+    /// Check a write of `blocks` blocks at `lba` against the write pointers:
+    /// in sequential-write-required zones it must start at the write pointer
+    /// and stay inside the zone.
+    pub fn check_write(&self, lba: u64, blocks: u64) -> error::Result<()> {
+        let end = lba.saturating_add(blocks);
+        let mut at = lba;
+        while at < end {
+            let zone = self.zone_of(at).ok_or_else(|| {
+                error::IscsiError::msg(format!("no zone holds LBA {at}"))
+            })?;
+            if zone.is_sequential_required() {
+                if !zone.has_write_pointer() {
+                    return Err(error::IscsiError::msg(format!(
+                        "zone at LBA {} is {:?} and cannot be written",
+                        zone.start, zone.condition
+                    )));
+                }
+                if at != zone.write_pointer {
+                    return Err(error::IscsiError::msg(format!(
+                        "unaligned write at LBA {at}: the write pointer of the zone at \
+                         LBA {} is {}",
+                        zone.start, zone.write_pointer
+                    )));
+                }
+                if end > zone.end() {
+                    return Err(error::IscsiError::msg(format!(
+                        "write {lba}+{blocks} crosses the end of the zone at LBA {}",
+                        zone.start
+                    )));
+                }
+            }
+            at = zone.end();
+        }
+        Ok(())
+    }
+
+    /// Record a completed write of `blocks` blocks at `lba`: the write
+    /// pointers of the zones it covered move past it.
+    pub fn advance(&mut self, lba: u64, blocks: u64) {
+        let end = lba.saturating_add(blocks);
+        for zone in self.zones.iter_mut() {
+            if zone.end() <= lba || zone.start >= end || !zone.has_write_pointer() {
+                continue;
+            }
+            zone.write_pointer = zone.write_pointer.max(end.min(zone.end()));
+            zone.condition = if zone.write_pointer == zone.end() {
+                ZoneCondition::Full
+            } else if zone.condition == ZoneCondition::ExplicitlyOpened {
+                ZoneCondition::ExplicitlyOpened
+            } else {
+                ZoneCondition::ImplicitlyOpened
+            };
+        }
+    }
+
+    /// Replace the entry of `zone` with a fresh descriptor.
+    pub fn update(&mut self, zone: ZoneDescriptor) {
+        match self.zones.binary_search_by_key(&zone.start, |z| z.start) {
+            Ok(i) => self.zones[i] = zone,
+            Err(i) => self.zones.insert(i, zone),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_block::zbc::ZoneType;
+
+    fn zone(
+        zone_type: ZoneType,
+        condition: ZoneCondition,
+        start: u64,
+        wp: u64,
+    ) -> ZoneDescriptor {
+        ZoneDescriptor {
+            zone_type,
+            condition,
+            non_seq: false,
+            reset_recommended: false,
+            length: 100,
+            start,
+            write_pointer: wp,
+        }
+    }
+
+    #[test]
+    fn checks_and_tracks_write_pointers() {
+        let mut map = ZoneMap::new(vec![
+            zone(
+                ZoneType::SequentialWriteRequired,
+                ZoneCondition::Empty,
+                100,
+                100,
+            ),
+            zone(ZoneType::Conventional, ZoneCondition::NotWritePointer, 0, 0),
+            zone(
+                ZoneType::SequentialWriteRequired,
+                ZoneCondition::Full,
+                200,
+                300,
+            ),
+        ]);
+        assert_eq!(map.zone_of(150).map(|z| z.start), Some(100));
+        assert!(map.zone_of(300).is_none());
+
+        // conventional zone: anywhere
+        map.check_write(10, 20).expect("conventional");
+        // sequential zone: only at the write pointer, inside the zone
+        map.check_write(100, 40).expect("at write pointer");
+        assert!(map.check_write(120, 1).is_err());
+        assert!(map.check_write(100, 101).is_err());
+        // crossing from the conventional zone into an empty sequential one
+        map.check_write(90, 20)
+            .expect("conventional then write pointer");
+        assert!(map.check_write(200, 1).is_err());
+
+        map.advance(100, 40);
+        let z = map.zone_of(100).copied().expect("zone");
+        assert_eq!(z.write_pointer, 140);
+        assert_eq!(z.condition, ZoneCondition::ImplicitlyOpened);
+        assert!(map.check_write(100, 1).is_err());
+        map.check_write(140, 60).expect("rest of the zone");
+        map.advance(140, 60);
+        assert_eq!(
+            map.zone_of(100).map(|z| z.condition),
+            Some(ZoneCondition::Full)
+        );
+
+        map.update(zone(
+            ZoneType::SequentialWriteRequired,
+            ZoneCondition::Empty,
+            100,
+            100,
+        ));
+        map.check_write(100, 1).expect("after reset");
+    }
+}
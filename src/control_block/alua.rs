@@ -0,0 +1,306 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::models::error::{Result, ensure};
+
+/// MAINTENANCE IN operation code (REPORT TARGET PORT GROUPS).
+pub const MAINTENANCE_IN: u8 = 0xA3;
+/// MAINTENANCE OUT operation code (SET TARGET PORT GROUPS).
+pub const MAINTENANCE_OUT: u8 = 0xA4;
+/// SERVICE ACTION of REPORT / SET TARGET PORT GROUPS.
+pub const TARGET_PORT_GROUPS_SA: u8 = 0x0A;
+
+/// Length of the SET TARGET PORT GROUPS parameter list header.
+pub const STPG_HEADER_LEN: usize = 4;
+
+/// Asymmetric access state of a target port group (SPC-4 § 5.15.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AluaState {
+    /// Active/optimized: full performance.
+    ActiveOptimized,
+    /// Active/non-optimized: works, typically through a slower path.
+    ActiveNonOptimized,
+    /// Standby: only management commands are accepted.
+    Standby,
+    /// Unavailable: almost every command is rejected.
+    Unavailable,
+    /// Logical block dependent: the state depends on the LBA.
+    LbaDependent,
+    /// Offline: the ports cannot be reached.
+    Offline,
+    /// Transitioning between two states.
+    Transitioning,
+    /// Any other code.
+    Reserved(u8),
+}
+
+impl AluaState {
+    /// Preference for path selection; lower is better.
+    pub fn rank(self) -> u8 {
+        match self {
+            Self::ActiveOptimized => 0,
+            Self::ActiveNonOptimized | Self::LbaDependent => 1,
+            Self::Transitioning => 2,
+            Self::Standby => 3,
+            Self::Unavailable => 4,
+            Self::Offline | Self::Reserved(_) => 5,
+        }
+    }
+
+    /// Whether READ / WRITE are accepted through the group.
+    #[inline]
+    pub fn is_active(self) -> bool {
+        matches!(
+            self,
+            Self::ActiveOptimized | Self::ActiveNonOptimized | Self::LbaDependent
+        )
+    }
+}
+
+impl From<u8> for AluaState {
+    fn from(v: u8) -> Self {
+        match v & 0x0F {
+            0x0 => Self::ActiveOptimized,
+            0x1 => Self::ActiveNonOptimized,
+            0x2 => Self::Standby,
+            0x3 => Self::Unavailable,
+            0x4 => Self::LbaDependent,
+            0xE => Self::Offline,
+            0xF => Self::Transitioning,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+impl From<AluaState> for u8 {
+    fn from(state: AluaState) -> Self {
+        match state {
+            AluaState::ActiveOptimized => 0x0,
+            AluaState::ActiveNonOptimized => 0x1,
+            AluaState::Standby => 0x2,
+            AluaState::Unavailable => 0x3,
+            AluaState::LbaDependent => 0x4,
+            AluaState::Offline => 0xE,
+            AluaState::Transitioning => 0xF,
+            AluaState::Reserved(v) => v & 0x0F,
+        }
+    }
+}
+
+/// One target port group descriptor of REPORT TARGET PORT GROUPS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetPortGroup {
+    /// TARGET PORT GROUP identifier.
+    pub id: u16,
+    /// ASYMMETRIC ACCESS STATE.
+    pub state: AluaState,
+    /// PREF: the group is preferred for I/O.
+    pub preferred: bool,
+    /// Supported-state bits of byte 1 (T_SUP, O_SUP, LBD_SUP, U_SUP, S_SUP,
+    /// AN_SUP, AO_SUP).
+    pub supported_states: u8,
+    /// STATUS CODE: why the state last changed (0 = no status, 1 = by SET
+    /// TARGET PORT GROUPS, 2 = implicitly).
+    pub status_code: u8,
+    /// Relative target port identifiers in the group.
+    pub ports: Vec<u16>,
+}
+
+/// Parsed REPORT TARGET PORT GROUPS data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetPortGroups {
+    /// IMPLICIT TRANSITION TIME in seconds, from the extended header.
+    pub implicit_transition_secs: Option<u8>,
+    /// The target port groups.
+    pub groups: Vec<TargetPortGroup>,
+}
+
+impl TargetPortGroups {
+    /// The group with identifier `id`.
+    pub fn group(&self, id: u16) -> Option<&TargetPortGroup> {
+        self.groups.iter().find(|g| g.id == id)
+    }
+
+    /// The group holding relative target port `port`.
+    pub fn group_of_port(&self, port: u16) -> Option<&TargetPortGroup> {
+        self.groups.iter().find(|g| g.ports.contains(&port))
+    }
+}
+
+/// Build a padded 16-byte **SCSI REPORT TARGET PORT GROUPS** CDB (MAINTENANCE
+/// IN, SA=0x0A).
+///
+/// Layout (SPC):
+/// - byte 0      : OPERATION CODE = 0xA3
+/// - byte 1      : PARAMETER DATA FORMAT[7:5] | SERVICE ACTION[4:0]
+/// - bytes 6..9  : ALLOCATION LENGTH (big-endian, 32-bit)
+/// - byte 11     : CONTROL
+///
+/// `extended` asks for the extended header carrying the implicit transition
+/// time; targets that do not support it answer in the length-only format.
+#[inline]
+pub fn build_report_target_port_groups(
+    cdb: &mut [u8; 16],
+    alloc_len: u32,
+    extended: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = MAINTENANCE_IN;
+    cdb[1] = if extended { 0x20 } else { 0x00 } | TARGET_PORT_GROUPS_SA;
+    cdb[6..10].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[11] = control;
+}
+
+/// Build a padded 16-byte **SCSI SET TARGET PORT GROUPS** CDB (MAINTENANCE
+/// OUT, SA=0x0A).
+///
+/// Layout (SPC):
+/// - byte 0      : OPERATION CODE = 0xA4
+/// - byte 1      : SERVICE ACTION = 0x0A
+/// - bytes 6..9  : PARAMETER LIST LENGTH (big-endian, 32-bit)
+/// - byte 11     : CONTROL
+#[inline]
+pub fn build_set_target_port_groups(cdb: &mut [u8; 16], param_len: u32, control: u8) {
+    cdb.fill(0);
+    cdb[0] = MAINTENANCE_OUT;
+    cdb[1] = TARGET_PORT_GROUPS_SA;
+    cdb[6..10].copy_from_slice(&param_len.to_be_bytes());
+    cdb[11] = control;
+}
+
+/// SET TARGET PORT GROUPS parameter list asking for `states`, as
+/// `(group id, state)` pairs.
+pub fn fill_set_target_port_groups(states: &[(u16, AluaState)]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(STPG_HEADER_LEN + states.len() * 4);
+    buf.extend_from_slice(&[0; STPG_HEADER_LEN]);
+    for &(id, state) in states {
+        let [hi, lo] = id.to_be_bytes();
+        buf.extend_from_slice(&[u8::from(state), 0, hi, lo]);
+    }
+    buf
+}
+
+/// RETURN DATA LENGTH of REPORT TARGET PORT GROUPS: bytes after the first
+/// four, which may be more than the allocation length let through.
+pub fn parse_target_port_groups_length(buf: &[u8]) -> Result<u32> {
+    ensure!(
+        buf.len() >= 4,
+        "REPORT TARGET PORT GROUPS data too short: {} bytes",
+        buf.len()
+    );
+    Ok(u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]))
+}
+
+/// Decode REPORT TARGET PORT GROUPS data in either header format.
+/// Descriptors cut off by a short allocation length are not returned.
+pub fn parse_report_target_port_groups(buf: &[u8]) -> Result<TargetPortGroups> {
+    let len = parse_target_port_groups_length(buf)? as usize;
+    let end = len.saturating_add(4).min(buf.len());
+    // FORMAT TYPE 001b in byte 4 marks the 8-byte extended header.
+    let extended = buf.len() >= 8 && (buf[4] >> 4) & 0x07 == 0x01;
+    let (implicit_transition_secs, mut off) = if extended {
+        (Some(buf[5]), 8)
+    } else {
+        (None, 4)
+    };
+
+    let mut groups = Vec::new();
+    while off + 8 <= end {
+        let d = &buf[off..];
+        let port_count = d[7] as usize;
+        let ports_end = off + 8 + port_count * 4;
+        if ports_end > end {
+            break;
+        }
+        let ports = buf[off + 8..ports_end]
+            .chunks_exact(4)
+            .map(|p| u16::from_be_bytes([p[2], p[3]]))
+            .collect();
+        groups.push(TargetPortGroup {
+            id: u16::from_be_bytes([d[2], d[3]]),
+            state: AluaState::from(d[0]),
+            preferred: d[0] & 0x80 != 0,
+            supported_states: d[1],
+            status_code: d[5],
+            ports,
+        });
+        off = ports_end;
+    }
+    Ok(TargetPortGroups {
+        implicit_transition_secs,
+        groups,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(state: u8, supported: u8, id: u16, ports: &[u16]) -> Vec<u8> {
+        let [hi, lo] = id.to_be_bytes();
+        let mut d = vec![state, supported, hi, lo, 0, 0x02, 0, ports.len() as u8];
+        for port in ports {
+            let [hi, lo] = port.to_be_bytes();
+            d.extend_from_slice(&[0, 0, hi, lo]);
+        }
+        d
+    }
+
+    #[test]
+    fn rtpg_and_stpg_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_report_target_port_groups(&mut cdb, 0x1000, true, 0);
+        assert_eq!(
+            cdb,
+            [0xA3, 0x2A, 0, 0, 0, 0, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0]
+        );
+        build_set_target_port_groups(&mut cdb, 8, 0x04);
+        assert_eq!(&cdb[..12], &[0xA4, 0x0A, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0x04]);
+
+        let params = fill_set_target_port_groups(&[(0x0102, AluaState::ActiveOptimized)]);
+        assert_eq!(params, [0, 0, 0, 0, 0x00, 0, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn parses_both_header_formats() {
+        let mut body = descriptor(0x80, 0x8F, 1, &[1, 2]);
+        body.extend(descriptor(0x01, 0x8F, 2, &[3]));
+
+        let mut plain = ((body.len()) as u32).to_be_bytes().to_vec();
+        plain.extend_from_slice(&body);
+        let tpgs = parse_report_target_port_groups(&plain).expect("plain");
+        assert_eq!(tpgs.implicit_transition_secs, None);
+        assert_eq!(tpgs.groups.len(), 2);
+        let first = tpgs.group(1).expect("group 1");
+        assert_eq!(first.state, AluaState::ActiveOptimized);
+        assert!(first.preferred);
+        assert_eq!(first.ports, [1, 2]);
+        assert_eq!(
+            tpgs.group_of_port(3).map(|g| g.state),
+            Some(AluaState::ActiveNonOptimized)
+        );
+
+        let mut extended = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+        extended.extend_from_slice(&[0x10, 30, 0, 0]);
+        extended.extend_from_slice(&body);
+        let tpgs = parse_report_target_port_groups(&extended).expect("extended");
+        assert_eq!(tpgs.implicit_transition_secs, Some(30));
+        assert_eq!(tpgs.groups.len(), 2);
+
+        // Truncated in the second descriptor.
+        let tpgs = parse_report_target_port_groups(&plain[..4 + 16 + 6]).expect("short");
+        assert_eq!(tpgs.groups.len(), 1);
+    }
+
+    #[test]
+    fn ranks_states() {
+        assert!(AluaState::ActiveOptimized.rank() < AluaState::ActiveNonOptimized.rank());
+        assert!(AluaState::ActiveNonOptimized.rank() < AluaState::Standby.rank());
+        assert!(!AluaState::Standby.is_active());
+        assert_eq!(AluaState::from(0x0F), AluaState::Transitioning);
+        assert_eq!(u8::from(AluaState::Offline), 0x0E);
+    }
+}
@@ -0,0 +1,413 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::models::{
+    data::sense_data::{
+        SENSE_DESC_ATA_STATUS, find_sense_descriptor, strip_sense_length,
+    },
+    error::{Result, ensure, format_err},
+};
+
+/// ATA PASS-THROUGH(12) operation code.
+pub const ATA_PASS_THROUGH_12: u8 = 0xA1;
+/// ATA PASS-THROUGH(16) operation code.
+pub const ATA_PASS_THROUGH_16: u8 = 0x85;
+
+/// Length of an ATA Status Return sense data descriptor.
+pub const ATA_STATUS_DESCRIPTOR_LEN: usize = 14;
+
+/// ATA command codes used by the helpers.
+pub mod ata_command {
+    /// IDENTIFY DEVICE.
+    pub const IDENTIFY_DEVICE: u8 = 0xEC;
+    /// SMART (the subcommand goes in FEATURES).
+    pub const SMART: u8 = 0xB0;
+}
+
+/// SMART subcommands (FEATURES of [`ata_command::SMART`]).
+pub mod smart_feature {
+    /// SMART READ DATA.
+    pub const READ_DATA: u16 = 0xD0;
+    /// SMART READ THRESHOLDS (obsolete in ACS, still common).
+    pub const READ_THRESHOLDS: u16 = 0xD1;
+    /// SMART RETURN STATUS.
+    pub const RETURN_STATUS: u16 = 0xDA;
+}
+
+/// LBA mid/high signature SMART commands must carry (0xC24F in bits
+/// 23..8).
+pub const SMART_LBA_SIGNATURE: u64 = 0x00C2_4F00;
+
+/// PROTOCOL field of ATA PASS-THROUGH (SAT-3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AtaProtocol {
+    /// Device reset.
+    HardReset = 0x0,
+    /// Software reset.
+    SoftReset = 0x1,
+    /// Non-data command.
+    NonData = 0x3,
+    /// PIO Data-In.
+    PioIn = 0x4,
+    /// PIO Data-Out.
+    PioOut = 0x5,
+    /// DMA.
+    Dma = 0x6,
+    /// Execute Device Diagnostic.
+    DeviceDiagnostic = 0x8,
+    /// UDMA Data-In.
+    UdmaIn = 0xA,
+    /// UDMA Data-Out.
+    UdmaOut = 0xB,
+    /// NCQ (FPDMA).
+    Fpdma = 0xC,
+    /// Return the ATA registers of the last command.
+    ReturnResponse = 0xF,
+}
+
+/// Direction of the data of an ATA command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtaDirection {
+    /// No data (T_LENGTH = 0).
+    None,
+    /// From the device (T_DIR = 1).
+    In,
+    /// To the device.
+    Out,
+}
+
+/// ATA taskfile of a pass-through command.
+///
+/// With data the transfer length is COUNT 512-byte blocks (T_LENGTH = 2,
+/// BYTE_BLOCK = 1, T_TYPE = 0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtaCommand {
+    /// PROTOCOL.
+    pub protocol: AtaProtocol,
+    /// Direction of the data, if any.
+    pub direction: AtaDirection,
+    /// COMMAND.
+    pub command: u8,
+    /// FEATURES (only bits 7..0 without `extend`).
+    pub features: u16,
+    /// COUNT (only bits 7..0 without `extend`).
+    pub count: u16,
+    /// LBA (48 bits with `extend`, 28 otherwise).
+    pub lba: u64,
+    /// DEVICE.
+    pub device: u8,
+    /// EXTEND: a 48-bit command (ATA PASS-THROUGH(16) only).
+    pub extend: bool,
+    /// CK_COND: return the ATA registers in sense data even on success.
+    pub ck_cond: bool,
+}
+
+impl AtaCommand {
+    /// A 28-bit command without data.
+    pub fn non_data(command: u8) -> Self {
+        Self {
+            protocol: AtaProtocol::NonData,
+            direction: AtaDirection::None,
+            command,
+            features: 0,
+            count: 0,
+            lba: 0,
+            device: 0,
+            extend: false,
+            ck_cond: false,
+        }
+    }
+
+    /// A 28-bit PIO Data-In command reading `blocks` 512-byte blocks.
+    pub fn pio_in(command: u8, blocks: u8) -> Self {
+        Self {
+            protocol: AtaProtocol::PioIn,
+            direction: AtaDirection::In,
+            count: blocks as u16,
+            ..Self::non_data(command)
+        }
+    }
+
+    /// Bytes 1 and 2 of both CDBs: MULTIPLE_COUNT (0) | PROTOCOL | EXTEND,
+    /// then OFF_LINE (0) | CK_COND | T_TYPE (0) | T_DIR | BYTE_BLOCK |
+    /// T_LENGTH.
+    fn flags(&self, extend: bool) -> (u8, u8) {
+        let b1 = ((self.protocol as u8) << 1) | extend as u8;
+        let transfer = match self.direction {
+            AtaDirection::None => 0,
+            AtaDirection::In => 0x08 | 0x04 | 0x02,
+            AtaDirection::Out => 0x04 | 0x02,
+        };
+        (b1, ((self.ck_cond as u8) << 5) | transfer)
+    }
+}
+
+/// Build a padded 16-byte **ATA PASS-THROUGH(16)** CDB.
+///
+/// Layout (SAT-3):
+/// - byte 0      : OPERATION CODE = 0x85
+/// - byte 1      : MULTIPLE_COUNT[7:5] | PROTOCOL[4:1] | EXTEND[0]
+/// - byte 2      : OFF_LINE[7:6] | CK_COND[5] | T_TYPE[4] | T_DIR[3] |
+///   BYTE_BLOCK[2] | T_LENGTH[1:0]
+/// - bytes 3..4  : FEATURES (15:8, 7:0)
+/// - bytes 5..6  : COUNT (15:8, 7:0)
+/// - bytes 7..12 : LBA (31:24, 7:0, 39:32, 15:8, 47:40, 23:16)
+/// - byte 13     : DEVICE
+/// - byte 14     : COMMAND
+/// - byte 15     : CONTROL
+#[inline]
+pub fn build_ata_pass_through16(cdb: &mut [u8; 16], cmd: &AtaCommand, control: u8) {
+    cdb.fill(0);
+    let (b1, b2) = cmd.flags(cmd.extend);
+    let lba = cmd.lba.to_be_bytes();
+    cdb[0] = ATA_PASS_THROUGH_16;
+    cdb[1] = b1;
+    cdb[2] = b2;
+    cdb[3..5].copy_from_slice(&cmd.features.to_be_bytes());
+    cdb[5..7].copy_from_slice(&cmd.count.to_be_bytes());
+    // lba[7] = bits 7..0, lba[2] = bits 47..40
+    cdb[7] = lba[4];
+    cdb[8] = lba[7];
+    cdb[9] = lba[3];
+    cdb[10] = lba[6];
+    cdb[11] = lba[2];
+    cdb[12] = lba[5];
+    cdb[13] = cmd.device;
+    cdb[14] = cmd.command;
+    cdb[15] = control;
+}
+
+/// Build a padded 16-byte **ATA PASS-THROUGH(12)** CDB. Only 28-bit
+/// commands fit: `extend` is ignored and FEATURES, COUNT and LBA are cut to
+/// their low bits.
+///
+/// Layout (SAT-3):
+/// - byte 0     : OPERATION CODE = 0xA1
+/// - byte 1     : MULTIPLE_COUNT[7:5] | PROTOCOL[4:1]
+/// - byte 2     : as ATA PASS-THROUGH(16)
+/// - byte 3     : FEATURES (7:0)
+/// - byte 4     : COUNT (7:0)
+/// - bytes 5..7 : LBA (7:0, 15:8, 23:16)
+/// - byte 8     : DEVICE
+/// - byte 9     : COMMAND
+/// - byte 11    : CONTROL
+#[inline]
+pub fn build_ata_pass_through12(cdb: &mut [u8; 16], cmd: &AtaCommand, control: u8) {
+    cdb.fill(0);
+    let (b1, b2) = cmd.flags(false);
+    cdb[0] = ATA_PASS_THROUGH_12;
+    cdb[1] = b1;
+    cdb[2] = b2;
+    cdb[3] = cmd.features as u8;
+    cdb[4] = cmd.count as u8;
+    cdb[5] = cmd.lba as u8;
+    cdb[6] = (cmd.lba >> 8) as u8;
+    cdb[7] = (cmd.lba >> 16) as u8;
+    cdb[8] = cmd.device;
+    cdb[9] = cmd.command;
+    cdb[11] = control;
+}
+
+/// ATA registers returned in an ATA Status Return descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtaRegisters {
+    /// EXTEND: the high bytes of COUNT and LBA are valid.
+    pub extend: bool,
+    /// ERROR.
+    pub error: u8,
+    /// COUNT.
+    pub count: u16,
+    /// LBA.
+    pub lba: u64,
+    /// DEVICE.
+    pub device: u8,
+    /// STATUS.
+    pub status: u8,
+}
+
+impl AtaRegisters {
+    /// ERR bit of STATUS: the command failed and ERROR says why.
+    pub fn is_error(&self) -> bool {
+        self.status & 0x01 != 0
+    }
+}
+
+/// Decode an ATA Status Return descriptor (type 0x09).
+///
+/// Layout (SAT-3): byte 2 EXTEND[0], byte 3 ERROR, bytes 4..5 COUNT (15:8,
+/// 7:0), bytes 6..11 LBA (31:24, 7:0, 39:32, 15:8, 47:40, 23:16), byte 12
+/// DEVICE, byte 13 STATUS.
+pub fn parse_ata_status_descriptor(desc: &[u8]) -> Result<AtaRegisters> {
+    ensure!(
+        desc.len() >= ATA_STATUS_DESCRIPTOR_LEN && desc[0] == SENSE_DESC_ATA_STATUS,
+        "not an ATA Status Return descriptor: {desc:02X?}"
+    );
+    let lba = u64::from_be_bytes([
+        0, 0, desc[10], desc[8], desc[6], desc[11], desc[9], desc[7],
+    ]);
+    Ok(AtaRegisters {
+        extend: desc[2] & 0x01 != 0,
+        error: desc[3],
+        count: u16::from_be_bytes([desc[4], desc[5]]),
+        lba,
+        device: desc[12],
+        status: desc[13],
+    })
+}
+
+/// Find and decode the ATA registers in descriptor-format sense data, as
+/// returned for a command with CK_COND set or one the device failed.
+pub fn parse_ata_sense(sense: &[u8]) -> Result<AtaRegisters> {
+    let sense = strip_sense_length(sense);
+    let desc = find_sense_descriptor(sense, SENSE_DESC_ATA_STATUS)
+        .ok_or_else(|| format_err!("sense data has no ATA Status Return descriptor"))?;
+    parse_ata_status_descriptor(desc)
+}
+
+/// Size of the SMART READ DATA structure.
+pub const SMART_DATA_LEN: usize = 512;
+/// Number of attribute entries in the SMART READ DATA structure.
+pub const SMART_ATTRIBUTE_COUNT: usize = 30;
+/// Size of one SMART attribute entry.
+pub const SMART_ATTRIBUTE_LEN: usize = 12;
+
+/// One vendor attribute of the SMART READ DATA structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmartAttribute {
+    /// Attribute ID (e.g. 5 reallocated sectors, 194 temperature).
+    pub id: u8,
+    /// Status flags; bit 0 marks a pre-failure attribute.
+    pub flags: u16,
+    /// Normalized current value.
+    pub value: u8,
+    /// Worst normalized value seen.
+    pub worst: u8,
+    /// Vendor-specific raw value (48 bits, little-endian on the wire).
+    pub raw: u64,
+}
+
+/// Decode the attribute table of SMART READ DATA; empty slots (ID 0) are
+/// skipped.
+///
+/// Layout (de-facto, ATA-8): bytes 0..1 revision, then 30 entries of byte 0
+/// ID, bytes 1..2 FLAGS (LE), byte 3 VALUE, byte 4 WORST, bytes 5..10 RAW
+/// (LE).
+pub fn parse_smart_attributes(buf: &[u8]) -> Result<Vec<SmartAttribute>> {
+    ensure!(
+        buf.len() >= SMART_DATA_LEN,
+        "SMART data truncated: {} bytes",
+        buf.len()
+    );
+    Ok(buf[2..2 + SMART_ATTRIBUTE_COUNT * SMART_ATTRIBUTE_LEN]
+        .chunks_exact(SMART_ATTRIBUTE_LEN)
+        .filter(|a| a[0] != 0)
+        .map(|a| SmartAttribute {
+            id: a[0],
+            flags: u16::from_le_bytes([a[1], a[2]]),
+            value: a[3],
+            worst: a[4],
+            raw: u64::from_le_bytes([a[5], a[6], a[7], a[8], a[9], a[10], 0, 0]),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::data::sense_data::SenseData;
+
+    #[test]
+    fn pass_through16_layout() {
+        let mut cdb = [0xFFu8; 16];
+        let cmd = AtaCommand {
+            features: smart_feature::READ_DATA,
+            lba: SMART_LBA_SIGNATURE,
+            ..AtaCommand::pio_in(ata_command::SMART, 1)
+        };
+        build_ata_pass_through16(&mut cdb, &cmd, 0);
+        assert_eq!(
+            cdb,
+            [
+                0x85, 0x08, 0x0E, 0, 0xD0, 0, 0x01, 0, 0, 0, 0x4F, 0, 0xC2, 0, 0xB0, 0
+            ]
+        );
+
+        let ext = AtaCommand {
+            extend: true,
+            ck_cond: true,
+            lba: 0x0000_A1B2_C3D4_E5F6,
+            count: 0x1234,
+            ..AtaCommand::non_data(0x27)
+        };
+        build_ata_pass_through16(&mut cdb, &ext, 0);
+        assert_eq!(cdb[1], 0x07);
+        assert_eq!(cdb[2], 0x20);
+        assert_eq!(
+            &cdb[5..13],
+            &[0x12, 0x34, 0xC3, 0xF6, 0xB2, 0xE5, 0xA1, 0xD4]
+        );
+    }
+
+    #[test]
+    fn pass_through12_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_ata_pass_through12(
+            &mut cdb,
+            &AtaCommand::pio_in(ata_command::IDENTIFY_DEVICE, 1),
+            0,
+        );
+        assert_eq!(
+            &cdb[..12],
+            &[0xA1, 0x08, 0x0E, 0, 1, 0, 0, 0, 0, 0xEC, 0, 0]
+        );
+        assert!(cdb[12..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn parses_ata_status_return() {
+        // SENSE LENGTH prefix, RECOVERED ERROR / ATA PASS THROUGH INFORMATION
+        // AVAILABLE, one ATA Status Return descriptor (SMART threshold
+        // exceeded: LBA mid/high = F4/2C).
+        let mut sense = vec![0, 22, 0x72, 0x01, 0x00, 0x1D, 0, 0, 0, 14];
+        sense.extend_from_slice(&[
+            0x09, 0x0C, 0x00, 0x00, 0, 0, 0, 0, 0, 0xF4, 0, 0x2C, 0xA0, 0x50,
+        ]);
+        let regs = parse_ata_sense(&sense).expect("registers");
+        assert_eq!(regs.lba, 0x2C_F400);
+        assert_eq!(regs.device, 0xA0);
+        assert_eq!(regs.status, 0x50);
+        assert!(!regs.is_error());
+        let parsed = SenseData::parse(&sense).expect("descriptor-format sense");
+        assert_eq!(
+            (parsed.sense_key, parsed.asc, parsed.ascq),
+            (0x01, 0x00, 0x1D)
+        );
+
+        let fixed = [
+            0x70, 0, 0x05, 0, 0, 0, 0, 10, 0, 0, 0, 0, 0x24, 0, 0, 0, 0, 0,
+        ];
+        assert!(parse_ata_sense(&fixed).is_err());
+    }
+
+    #[test]
+    fn parses_smart_attributes() {
+        let mut data = [0u8; SMART_DATA_LEN];
+        data[0] = 0x10;
+        // Reallocated sectors: pre-failure, 100/100, raw 3
+        data[2..14].copy_from_slice(&[5, 0x33, 0, 100, 100, 3, 0, 0, 0, 0, 0, 0]);
+        // Temperature in the third slot, raw 0x0000_0028 (40 C)
+        data[26..38].copy_from_slice(&[194, 0x22, 0, 60, 45, 0x28, 0, 0, 0, 0, 0, 0]);
+        let attrs = parse_smart_attributes(&data).expect("attributes");
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0].id, 5);
+        assert_eq!(attrs[0].flags & 0x01, 1);
+        assert_eq!(attrs[0].raw, 3);
+        assert_eq!(attrs[1].id, 194);
+        assert_eq!(attrs[1].worst, 45);
+        assert_eq!(attrs[1].raw, 40);
+        assert!(parse_smart_attributes(&data[..100]).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use crate::models::error::{Result, ensure};
+
+/// WRITE BUFFER operation code.
+pub const WRITE_BUFFER: u8 = 0x3B;
+/// READ BUFFER(10) operation code.
+pub const READ_BUFFER: u8 = 0x3C;
+
+/// Largest BUFFER OFFSET / length the 3-byte CDB fields can carry.
+pub const BUFFER_FIELD_MAX: u32 = 0x00FF_FFFF;
+
+/// Length of the descriptor returned by the descriptor modes.
+pub const BUFFER_DESCRIPTOR_LEN: u32 = 4;
+
+/// MODE field of READ BUFFER (SPC-4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ReadBufferMode {
+    /// 4-byte header (available length) followed by the buffer data.
+    Combined = 0x00,
+    /// Buffer data only.
+    Data = 0x02,
+    /// Buffer descriptor: offset boundary and capacity.
+    Descriptor = 0x03,
+    /// Contents of the echo buffer.
+    EchoBuffer = 0x0A,
+    /// Echo buffer descriptor: its capacity.
+    EchoBufferDescriptor = 0x0B,
+}
+
+/// MODE field of WRITE BUFFER (SPC-4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WriteBufferMode {
+    /// Write the data to the buffer.
+    Data = 0x02,
+    /// Download microcode in one command and activate it.
+    DownloadActivate = 0x04,
+    /// Download microcode in one command, save and activate it.
+    DownloadSaveActivate = 0x05,
+    /// Download microcode in chunks at buffer offsets and activate it.
+    DownloadOffsetsActivate = 0x06,
+    /// Download microcode in chunks, save and activate it.
+    DownloadOffsetsSaveActivate = 0x07,
+    /// Write the data to the echo buffer.
+    EchoBuffer = 0x0A,
+    /// Download microcode in chunks and save it; activation is deferred.
+    DownloadOffsetsSaveDefer = 0x0E,
+    /// Activate microcode saved by an earlier deferred download.
+    ActivateDeferred = 0x0F,
+}
+
+/// Build a padded 16-byte **SCSI READ BUFFER(10)** CDB.
+///
+/// Layout (SPC-4):
+/// - byte 0     : OPERATION CODE = 0x3C
+/// - byte 1     : MODE[4:0]
+/// - byte 2     : BUFFER ID
+/// - bytes 3..5 : BUFFER OFFSET (BE, 24-bit)
+/// - bytes 6..8 : ALLOCATION LENGTH (BE, 24-bit)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_read_buffer(
+    cdb: &mut [u8; 16],
+    mode: ReadBufferMode,
+    buffer_id: u8,
+    offset: u32,
+    alloc_len: u32,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = READ_BUFFER;
+    cdb[1] = mode as u8;
+    cdb[2] = buffer_id;
+    cdb[3..6].copy_from_slice(&offset.to_be_bytes()[1..]);
+    cdb[6..9].copy_from_slice(&alloc_len.to_be_bytes()[1..]);
+    cdb[9] = control;
+}
+
+/// Build a padded 16-byte **SCSI WRITE BUFFER** CDB.
+///
+/// For [`WriteBufferMode::ActivateDeferred`] no data follows and
+/// `param_len` must be 0.
+///
+/// Layout (SPC-4):
+/// - byte 0     : OPERATION CODE = 0x3B
+/// - byte 1     : MODE SPECIFIC[7:5] (0) | MODE[4:0]
+/// - byte 2     : BUFFER ID
+/// - bytes 3..5 : BUFFER OFFSET (BE, 24-bit)
+/// - bytes 6..8 : PARAMETER LIST LENGTH (BE, 24-bit)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_write_buffer(
+    cdb: &mut [u8; 16],
+    mode: WriteBufferMode,
+    buffer_id: u8,
+    offset: u32,
+    param_len: u32,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = WRITE_BUFFER;
+    cdb[1] = mode as u8;
+    cdb[2] = buffer_id;
+    cdb[3..6].copy_from_slice(&offset.to_be_bytes()[1..]);
+    cdb[6..9].copy_from_slice(&param_len.to_be_bytes()[1..]);
+    cdb[9] = control;
+}
+
+/// READ BUFFER descriptor (mode 0x03).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferDescriptor {
+    /// OFFSET BOUNDARY as a power of two; BUFFER OFFSETs must be multiples
+    /// of `1 << offset_boundary`. `None` when the target reported 0xFF
+    /// (offsets must be 0).
+    pub offset_boundary: Option<u8>,
+    /// BUFFER CAPACITY in bytes.
+    pub capacity: u32,
+}
+
+impl BufferDescriptor {
+    /// Alignment BUFFER OFFSETs must keep, in bytes.
+    pub fn alignment(&self) -> Option<u32> {
+        self.offset_boundary
+            .and_then(|b| 1u32.checked_shl(b as u32))
+    }
+}
+
+/// Decode a READ BUFFER descriptor.
+///
+/// Layout (SPC-4): byte 0 OFFSET BOUNDARY, bytes 1..3 BUFFER CAPACITY (BE).
+pub fn parse_buffer_descriptor(buf: &[u8]) -> Result<BufferDescriptor> {
+    ensure!(
+        buf.len() >= BUFFER_DESCRIPTOR_LEN as usize,
+        "buffer descriptor truncated: {} bytes",
+        buf.len()
+    );
+    Ok(BufferDescriptor {
+        offset_boundary: (buf[0] != 0xFF).then_some(buf[0]),
+        capacity: u32::from_be_bytes([0, buf[1], buf[2], buf[3]]),
+    })
+}
+
+/// READ BUFFER echo buffer descriptor (mode 0x0B).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoBufferDescriptor {
+    /// EBOS: the echo buffer is shared by every initiator, so another one
+    /// may overwrite it between the WRITE BUFFER and the READ BUFFER.
+    pub shared: bool,
+    /// BUFFER CAPACITY in bytes (a multiple of 4, at most 4 KiB).
+    pub capacity: u16,
+}
+
+/// Decode an echo buffer descriptor.
+///
+/// Layout (SPC-4): byte 0 EBOS[0], bytes 2..3 BUFFER CAPACITY[12:0] (BE).
+pub fn parse_echo_buffer_descriptor(buf: &[u8]) -> Result<EchoBufferDescriptor> {
+    ensure!(
+        buf.len() >= BUFFER_DESCRIPTOR_LEN as usize,
+        "echo buffer descriptor truncated: {} bytes",
+        buf.len()
+    );
+    Ok(EchoBufferDescriptor {
+        shared: buf[0] & 0x01 != 0,
+        capacity: u16::from_be_bytes([buf[2], buf[3]]) & 0x1FFF,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_buffer_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_read_buffer(
+            &mut cdb,
+            ReadBufferMode::Data,
+            0x01,
+            0x0012_3456,
+            0x0001_0000,
+            0x04,
+        );
+        assert_eq!(
+            &cdb[..10],
+            &[0x3C, 0x02, 0x01, 0x12, 0x34, 0x56, 0x01, 0x00, 0x00, 0x04]
+        );
+        assert!(cdb[10..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn write_buffer_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_write_buffer(
+            &mut cdb,
+            WriteBufferMode::DownloadOffsetsSaveDefer,
+            0,
+            0x8000,
+            0x200,
+            0,
+        );
+        assert_eq!(&cdb[..10], &[0x3B, 0x0E, 0, 0, 0x80, 0, 0, 0x02, 0, 0]);
+        assert!(cdb[10..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn parses_descriptors() {
+        let d = parse_buffer_descriptor(&[0x09, 0x01, 0x00, 0x00]).expect("desc");
+        assert_eq!(d.capacity, 0x10000);
+        assert_eq!(d.alignment(), Some(512));
+        let d = parse_buffer_descriptor(&[0xFF, 0, 0x10, 0]).expect("desc");
+        assert_eq!(d.offset_boundary, None);
+        assert!(parse_buffer_descriptor(&[0, 0]).is_err());
+
+        let e = parse_echo_buffer_descriptor(&[0x01, 0, 0xE0, 0x40]).expect("echo");
+        assert!(e.shared);
+        assert_eq!(e.capacity, 0x40);
+    }
+}
@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// NACA bit of the CONTROL byte (bit 2).
+///
+/// When set, a CHECK CONDITION for the command establishes an ACA condition
+/// on the logical unit: the target rejects every further command with ACA
+/// ACTIVE until the initiator clears it with the CLEAR ACA task management
+/// function.
+pub const CONTROL_NACA: u8 = 0x04;
+
+/// Vendor-specific bits of the CONTROL byte (bits 7..6).
+pub const CONTROL_VENDOR_MASK: u8 = 0xC0;
+
+/// Operation code of the variable-length CDB (SPC-4 § 4.2.3).
+pub const VARIABLE_LENGTH_CDB: u8 = 0x7F;
+
+/// Build a CONTROL byte.
+#[inline]
+pub const fn control_byte(naca: bool) -> u8 {
+    if naca { CONTROL_NACA } else { 0 }
+}
+
+/// CONTROL byte settings applied to CDBs after they are built, so commands
+/// issued by higher-level helpers (which build with CONTROL = 0) can carry
+/// NACA or vendor bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CdbControl {
+    /// Establish an ACA condition on CHECK CONDITION.
+    pub naca: bool,
+    /// Vendor-specific bits 7..6 (the value is masked with
+    /// [`CONTROL_VENDOR_MASK`]).
+    pub vendor: u8,
+}
+
+impl CdbControl {
+    /// CONTROL with NACA set.
+    pub const NACA: Self = Self {
+        naca: true,
+        vendor: 0,
+    };
+
+    /// The CONTROL byte.
+    #[inline]
+    pub const fn to_byte(self) -> u8 {
+        control_byte(self.naca) | (self.vendor & CONTROL_VENDOR_MASK)
+    }
+
+    /// Settings found in a CONTROL byte.
+    #[inline]
+    pub const fn from_byte(byte: u8) -> Self {
+        Self {
+            naca: byte & CONTROL_NACA != 0,
+            vendor: byte & CONTROL_VENDOR_MASK,
+        }
+    }
+
+    /// Replace the NACA and vendor bits of an already built CDB. Returns
+    /// `false` (and leaves the CDB untouched) when the CONTROL byte cannot
+    /// be located.
+    pub fn apply(self, cdb: &mut [u8; 16]) -> bool {
+        let Some(idx) = control_index(cdb[0]) else {
+            return false;
+        };
+        cdb[idx] = (cdb[idx] & !(CONTROL_NACA | CONTROL_VENDOR_MASK)) | self.to_byte();
+        true
+    }
+}
+
+/// Length of a fixed-format CDB derived from the group code (top three bits
+/// of the operation code). Returns `None` for reserved and vendor-specific
+/// groups.
+#[inline]
+pub const fn cdb_len(opcode: u8) -> Option<usize> {
+    match opcode >> 5 {
+        0 => Some(6),
+        1 | 2 => Some(10),
+        4 => Some(16),
+        5 => Some(12),
+        _ => None,
+    }
+}
+
+/// Offset of the CONTROL byte within the CDB. Variable-length CDBs keep it
+/// in byte 1.
+#[inline]
+pub const fn control_index(opcode: u8) -> Option<usize> {
+    if opcode == VARIABLE_LENGTH_CDB {
+        return Some(1);
+    }
+    match cdb_len(opcode) {
+        Some(len) => Some(len - 1),
+        None => None,
+    }
+}
+
+/// Set or clear NACA in an already built CDB. Returns `false` (and leaves
+/// the CDB untouched) when the CONTROL byte cannot be located.
+pub fn set_naca(cdb: &mut [u8; 16], naca: bool) -> bool {
+    let Some(idx) = control_index(cdb[0]) else {
+        return false;
+    };
+    if naca {
+        cdb[idx] |= CONTROL_NACA;
+    } else {
+        cdb[idx] &= !CONTROL_NACA;
+    }
+    true
+}
+
+/// Split a 32-byte CDB into the 16 bytes carried in the BHS and the
+/// extension sent in an Extended CDB AHS.
+#[inline]
+pub fn split_cdb32(cdb: &[u8; 32]) -> ([u8; 16], [u8; 16]) {
+    let mut head = [0u8; 16];
+    let mut tail = [0u8; 16];
+    head.copy_from_slice(&cdb[..16]);
+    tail.copy_from_slice(&cdb[16..]);
+    (head, tail)
+}
+
+/// Whether the CDB requests NACA.
+pub fn has_naca(cdb: &[u8; 16]) -> bool {
+    control_index(cdb[0]).is_some_and(|idx| cdb[idx] & CONTROL_NACA != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_block::{
+        protection::PiTags,
+        read::{build_read10, build_read16, build_read32},
+        test_unit_ready::build_test_unit_ready,
+    };
+
+    #[test]
+    fn locates_control_byte_by_group_code() {
+        let mut cdb = [0u8; 16];
+
+        build_test_unit_ready(&mut cdb, control_byte(true));
+        assert_eq!(cdb[5], CONTROL_NACA);
+        assert!(has_naca(&cdb));
+
+        build_read10(&mut cdb, 0, 1, 0, 0);
+        assert!(!has_naca(&cdb));
+        assert!(set_naca(&mut cdb, true));
+        assert_eq!(cdb[9], CONTROL_NACA);
+
+        build_read16(&mut cdb, 0, 1, 0, CONTROL_NACA);
+        assert!(has_naca(&cdb));
+        assert!(set_naca(&mut cdb, false));
+        assert_eq!(cdb[15], 0);
+
+        // Vendor-specific group: unknown layout.
+        cdb[0] = 0xC0;
+        assert!(!set_naca(&mut cdb, true));
+    }
+
+    #[test]
+    fn applies_cdb_control() {
+        let control = CdbControl {
+            naca: true,
+            vendor: 0x80,
+        };
+        assert_eq!(control.to_byte(), 0x84);
+        assert_eq!(CdbControl::from_byte(0x87), control);
+
+        let mut cdb = [0u8; 16];
+        build_read10(&mut cdb, 0, 1, 0, 0x40);
+        assert!(control.apply(&mut cdb));
+        assert_eq!(cdb[9], 0x84);
+        assert!(CdbControl::default().apply(&mut cdb));
+        assert_eq!(cdb[9], 0);
+
+        let mut cdb32 = [0u8; 32];
+        build_read32(&mut cdb32, 0, 1, 0, PiTags::default(), 0);
+        let (mut head, _) = split_cdb32(&cdb32);
+        assert!(CdbControl::NACA.apply(&mut head));
+        assert_eq!(head[1], CONTROL_NACA);
+    }
+}
@@ -0,0 +1,555 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::{string::String, vec::Vec};
+
+use crate::models::error::{Result, ensure};
+
+/// RECEIVE DIAGNOSTIC RESULTS operation code.
+pub const RECEIVE_DIAGNOSTIC_RESULTS: u8 = 0x1C;
+/// SEND DIAGNOSTIC operation code.
+pub const SEND_DIAGNOSTIC: u8 = 0x1D;
+
+/// Size of the header in front of every diagnostic page.
+pub const DIAGNOSTIC_PAGE_HEADER_LEN: usize = 4;
+
+/// Diagnostic page codes (SPC-4 / SES-3).
+pub mod page {
+    /// Supported Diagnostic Pages.
+    pub const SUPPORTED: u8 = 0x00;
+    /// SES Configuration.
+    pub const SES_CONFIGURATION: u8 = 0x01;
+    /// SES Enclosure Control (send) / Enclosure Status (receive).
+    pub const SES_ENCLOSURE_STATUS: u8 = 0x02;
+    /// SES Help Text.
+    pub const SES_HELP_TEXT: u8 = 0x03;
+    /// SES String In / String Out.
+    pub const SES_STRING: u8 = 0x04;
+    /// SES Threshold In / Threshold Out.
+    pub const SES_THRESHOLD: u8 = 0x05;
+    /// SES Element Descriptor.
+    pub const SES_ELEMENT_DESCRIPTOR: u8 = 0x07;
+}
+
+/// SELF-TEST CODE of SEND DIAGNOSTIC (SPC-4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTest {
+    /// The device's default self-test (SELFTEST bit); the command
+    /// completes when the test has run.
+    Default,
+    /// Background short self-test.
+    BackgroundShort,
+    /// Background extended self-test.
+    BackgroundExtended,
+    /// Abort a running background self-test.
+    AbortBackground,
+    /// Foreground short self-test.
+    ForegroundShort,
+    /// Foreground extended self-test.
+    ForegroundExtended,
+}
+
+impl SelfTest {
+    /// Value of the 3-bit SELF-TEST CODE field (0 for [`SelfTest::Default`]).
+    pub fn code(self) -> u8 {
+        match self {
+            SelfTest::Default => 0,
+            SelfTest::BackgroundShort => 1,
+            SelfTest::BackgroundExtended => 2,
+            SelfTest::AbortBackground => 4,
+            SelfTest::ForegroundShort => 5,
+            SelfTest::ForegroundExtended => 6,
+        }
+    }
+}
+
+/// Build a padded 16-byte **SCSI SEND DIAGNOSTIC** CDB.
+///
+/// With `self_test` set the target runs that test and no parameter list is
+/// sent (`param_len` must be 0). Without it, `param_len` bytes of
+/// diagnostic pages follow as Data-Out; `pf` marks them as page-formatted
+/// (required for SES control pages).
+///
+/// Layout (SPC-4):
+/// - byte 0     : OPERATION CODE = 0x1D
+/// - byte 1     : SELF-TEST CODE[7:5] | PF[4] | SELFTEST[2] | DEVOFFL[1] |
+///   UNITOFFL[0]
+/// - bytes 3..4 : PARAMETER LIST LENGTH (BE)
+/// - byte 5     : CONTROL
+#[inline]
+pub fn build_send_diagnostic(
+    cdb: &mut [u8; 16],
+    self_test: Option<SelfTest>,
+    pf: bool,
+    param_len: u16,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = SEND_DIAGNOSTIC;
+    let test_bits = match self_test {
+        None => 0,
+        Some(SelfTest::Default) => 0x04,
+        Some(test) => test.code() << 5,
+    };
+    cdb[1] = test_bits | ((pf as u8) << 4);
+    cdb[3..5].copy_from_slice(&param_len.to_be_bytes());
+    cdb[5] = control;
+}
+
+/// Build a padded 16-byte **SCSI RECEIVE DIAGNOSTIC RESULTS** CDB.
+///
+/// `Some(page)` asks for that diagnostic page (PCV=1); `None` returns the
+/// results of the last SEND DIAGNOSTIC.
+///
+/// Layout (SPC-4):
+/// - byte 0     : OPERATION CODE = 0x1C
+/// - byte 1     : PCV[0]
+/// - byte 2     : PAGE CODE
+/// - bytes 3..4 : ALLOCATION LENGTH (BE)
+/// - byte 5     : CONTROL
+#[inline]
+pub fn build_receive_diagnostic(
+    cdb: &mut [u8; 16],
+    page_code: Option<u8>,
+    alloc_len: u16,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = RECEIVE_DIAGNOSTIC_RESULTS;
+    if let Some(code) = page_code {
+        cdb[1] = 0x01;
+        cdb[2] = code;
+    }
+    cdb[3..5].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[5] = control;
+}
+
+/// One diagnostic page as sent or received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticPage {
+    /// PAGE CODE.
+    pub page_code: u8,
+    /// Byte 1 of the header; its meaning depends on the page (e.g. the
+    /// status flags of the SES Enclosure Status page).
+    pub page_specific: u8,
+    /// Page contents after the 4-byte header.
+    pub data: Vec<u8>,
+}
+
+impl DiagnosticPage {
+    /// Serialize the page with its header, as the SEND DIAGNOSTIC
+    /// parameter list.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(DIAGNOSTIC_PAGE_HEADER_LEN + self.data.len());
+        out.push(self.page_code);
+        out.push(self.page_specific);
+        out.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.data);
+        out
+    }
+}
+
+/// Total size (header included) the target reports for the page in `buf`.
+pub fn parse_diagnostic_page_length(buf: &[u8]) -> Result<usize> {
+    ensure!(
+        buf.len() >= DIAGNOSTIC_PAGE_HEADER_LEN,
+        "diagnostic page header truncated: {} bytes",
+        buf.len()
+    );
+    Ok(DIAGNOSTIC_PAGE_HEADER_LEN + u16::from_be_bytes([buf[2], buf[3]]) as usize)
+}
+
+/// Decode a diagnostic page; the contents are clipped to the bytes
+/// received.
+pub fn parse_diagnostic_page(buf: &[u8]) -> Result<DiagnosticPage> {
+    let end = parse_diagnostic_page_length(buf)?.min(buf.len());
+    Ok(DiagnosticPage {
+        page_code: buf[0],
+        page_specific: buf[1],
+        data: buf[DIAGNOSTIC_PAGE_HEADER_LEN..end].to_vec(),
+    })
+}
+
+/// Decode the Supported Diagnostic Pages page into its list of page codes.
+pub fn parse_supported_diagnostic_pages(buf: &[u8]) -> Result<Vec<u8>> {
+    let page = parse_diagnostic_page(buf)?;
+    ensure!(
+        page.page_code == page::SUPPORTED,
+        "expected Supported Diagnostic Pages, got page {:#04x}",
+        page.page_code
+    );
+    Ok(page.data)
+}
+
+/// One type descriptor header of the SES Configuration page: a kind of
+/// element and how many the enclosure has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SesTypeDescriptor {
+    /// ELEMENT TYPE (0x01 power supply, 0x02 cooling, 0x03 temperature
+    /// sensor, 0x17 array device slot, ...).
+    pub element_type: u8,
+    /// NUMBER OF POSSIBLE ELEMENTS.
+    pub possible_elements: u8,
+    /// SUBENCLOSURE IDENTIFIER.
+    pub subenclosure_id: u8,
+    /// TYPE DESCRIPTOR TEXT, if the enclosure provides one.
+    pub text: String,
+}
+
+/// SES Configuration diagnostic page (0x01).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SesConfiguration {
+    /// GENERATION CODE; control pages must echo it.
+    pub generation: u32,
+    /// Type descriptor headers of all subenclosures, in status page order.
+    pub types: Vec<SesTypeDescriptor>,
+}
+
+/// Decode the SES Configuration diagnostic page.
+///
+/// Layout (SES-3): 4-byte header (byte 1 = secondary subenclosure count),
+/// GENERATION CODE, one enclosure descriptor per subenclosure (byte 2 =
+/// number of type descriptor headers, byte 3 = length - 4), the 4-byte type
+/// descriptor headers and finally their texts.
+pub fn parse_ses_configuration(buf: &[u8]) -> Result<SesConfiguration> {
+    let page = parse_diagnostic_page(buf)?;
+    ensure!(
+        page.page_code == page::SES_CONFIGURATION,
+        "expected SES Configuration, got page {:#04x}",
+        page.page_code
+    );
+    let d = &page.data;
+    ensure!(
+        d.len() >= 4,
+        "SES Configuration truncated: {} bytes",
+        buf.len()
+    );
+    let generation = u32::from_be_bytes([d[0], d[1], d[2], d[3]]);
+
+    let mut off = 4;
+    let mut headers = 0usize;
+    for _ in 0..=page.page_specific {
+        ensure!(
+            d.len() >= off + 4,
+            "SES enclosure descriptor truncated at offset {off}"
+        );
+        headers += d[off + 2] as usize;
+        off += 4 + d[off + 3] as usize;
+    }
+
+    ensure!(
+        d.len() >= off + headers * 4,
+        "SES type descriptor headers truncated: need {}, have {}",
+        off + headers * 4,
+        d.len()
+    );
+    let mut text_off = off + headers * 4;
+    let mut types = Vec::with_capacity(headers);
+    for h in d[off..off + headers * 4].chunks_exact(4) {
+        let text_end = (text_off + h[3] as usize).min(d.len());
+        let text = String::from_utf8_lossy(&d[text_off.min(text_end)..text_end])
+            .trim_end_matches(['\0', ' '])
+            .into();
+        text_off = text_end;
+        types.push(SesTypeDescriptor {
+            element_type: h[0],
+            possible_elements: h[1],
+            subenclosure_id: h[2],
+            text,
+        });
+    }
+    Ok(SesConfiguration { generation, types })
+}
+
+/// ELEMENT STATUS CODE of an SES status element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementStatusCode {
+    Unsupported,
+    Ok,
+    Critical,
+    Noncritical,
+    Unrecoverable,
+    NotInstalled,
+    Unknown,
+    NotAvailable,
+    NoAccess,
+    Reserved(u8),
+}
+
+impl From<u8> for ElementStatusCode {
+    fn from(v: u8) -> Self {
+        match v & 0x0F {
+            0x0 => Self::Unsupported,
+            0x1 => Self::Ok,
+            0x2 => Self::Critical,
+            0x3 => Self::Noncritical,
+            0x4 => Self::Unrecoverable,
+            0x5 => Self::NotInstalled,
+            0x6 => Self::Unknown,
+            0x7 => Self::NotAvailable,
+            0x8 => Self::NoAccess,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+/// One 4-byte SES status element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementStatus {
+    pub code: ElementStatusCode,
+    /// PRDFAIL: failure predicted.
+    pub predicted_failure: bool,
+    pub disabled: bool,
+    /// SWAP: the element was swapped since the status was last read.
+    pub swapped: bool,
+    /// The raw element, for the type-specific bytes 1..3.
+    pub raw: [u8; 4],
+}
+
+impl From<[u8; 4]> for ElementStatus {
+    fn from(raw: [u8; 4]) -> Self {
+        Self {
+            code: ElementStatusCode::from(raw[0]),
+            predicted_failure: raw[0] & 0x40 != 0,
+            disabled: raw[0] & 0x20 != 0,
+            swapped: raw[0] & 0x10 != 0,
+            raw,
+        }
+    }
+}
+
+/// Status elements of one element type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SesElementGroup {
+    pub element_type: u8,
+    /// The overall status element of the type.
+    pub overall: ElementStatus,
+    /// One status element per possible element.
+    pub elements: Vec<ElementStatus>,
+}
+
+/// SES Enclosure Status diagnostic page (0x02).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SesEnclosureStatus {
+    /// INVOP: an invalid control page was sent.
+    pub invalid_operation: bool,
+    /// INFO / NON-CRIT / CRIT / UNRECOV summary flags.
+    pub info: bool,
+    pub non_critical: bool,
+    pub critical: bool,
+    pub unrecoverable: bool,
+    pub generation: u32,
+    /// All status elements in page order; see
+    /// [`group_by_type`](Self::group_by_type).
+    pub elements: Vec<ElementStatus>,
+}
+
+impl SesEnclosureStatus {
+    /// Split the elements by the type descriptors of `config`: each type
+    /// contributes an overall element followed by its possible elements.
+    pub fn group_by_type(
+        &self,
+        config: &SesConfiguration,
+    ) -> Result<Vec<SesElementGroup>> {
+        ensure!(
+            self.generation == config.generation,
+            "SES generation changed ({} vs {}); re-read the configuration",
+            self.generation,
+            config.generation
+        );
+        let mut rest = self.elements.as_slice();
+        let mut groups = Vec::with_capacity(config.types.len());
+        for ty in &config.types {
+            let n = 1 + ty.possible_elements as usize;
+            ensure!(
+                rest.len() >= n,
+                "SES status page has too few elements for type {:#04x}",
+                ty.element_type
+            );
+            groups.push(SesElementGroup {
+                element_type: ty.element_type,
+                overall: rest[0],
+                elements: rest[1..n].to_vec(),
+            });
+            rest = &rest[n..];
+        }
+        Ok(groups)
+    }
+}
+
+/// Decode the SES Enclosure Status diagnostic page.
+///
+/// Layout (SES-3): byte 1 = INVOP[4] | INFO[3] | NON-CRIT[2] | CRIT[1] |
+/// UNRECOV[0], bytes 4..8 GENERATION CODE, then 4-byte status elements.
+pub fn parse_ses_enclosure_status(buf: &[u8]) -> Result<SesEnclosureStatus> {
+    let page = parse_diagnostic_page(buf)?;
+    ensure!(
+        page.page_code == page::SES_ENCLOSURE_STATUS,
+        "expected SES Enclosure Status, got page {:#04x}",
+        page.page_code
+    );
+    let d = &page.data;
+    ensure!(
+        d.len() >= 4,
+        "SES Enclosure Status truncated: {} bytes",
+        buf.len()
+    );
+    let flags = page.page_specific;
+    Ok(SesEnclosureStatus {
+        invalid_operation: flags & 0x10 != 0,
+        info: flags & 0x08 != 0,
+        non_critical: flags & 0x04 != 0,
+        critical: flags & 0x02 != 0,
+        unrecoverable: flags & 0x01 != 0,
+        generation: u32::from_be_bytes([d[0], d[1], d[2], d[3]]),
+        elements: d[4..]
+            .chunks_exact(4)
+            .map(|e| ElementStatus::from([e[0], e[1], e[2], e[3]]))
+            .collect(),
+    })
+}
+
+/// Build an SES Enclosure Control page: `generation` from the configuration
+/// and one 4-byte control element per status element (SELECT bit 0x80 in
+/// byte 0 marks the ones to change).
+pub fn fill_ses_enclosure_control(
+    generation: u32,
+    elements: &[[u8; 4]],
+) -> DiagnosticPage {
+    let mut data = Vec::with_capacity(4 + elements.len() * 4);
+    data.extend_from_slice(&generation.to_be_bytes());
+    for e in elements {
+        data.extend_from_slice(e);
+    }
+    DiagnosticPage {
+        page_code: page::SES_ENCLOSURE_STATUS,
+        page_specific: 0,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_diagnostic_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_send_diagnostic(&mut cdb, Some(SelfTest::Default), false, 0, 0);
+        assert_eq!(&cdb[..6], &[0x1D, 0x04, 0, 0, 0, 0]);
+        assert!(cdb[6..].iter().all(|&b| b == 0));
+
+        build_send_diagnostic(&mut cdb, Some(SelfTest::BackgroundExtended), false, 0, 0);
+        assert_eq!(cdb[1], 0x40);
+
+        build_send_diagnostic(&mut cdb, None, true, 0x0124, 0x04);
+        assert_eq!(&cdb[..6], &[0x1D, 0x10, 0, 0x01, 0x24, 0x04]);
+    }
+
+    #[test]
+    fn receive_diagnostic_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_receive_diagnostic(&mut cdb, Some(page::SES_CONFIGURATION), 0x1000, 0);
+        assert_eq!(&cdb[..6], &[0x1C, 0x01, 0x01, 0x10, 0x00, 0]);
+
+        build_receive_diagnostic(&mut cdb, None, 64, 0);
+        assert_eq!(&cdb[..6], &[0x1C, 0, 0, 0, 64, 0]);
+    }
+
+    #[test]
+    fn page_round_trip_and_clipping() {
+        let page = DiagnosticPage {
+            page_code: 0x80,
+            page_specific: 0x01,
+            data: vec![1, 2, 3],
+        };
+        let bytes = page.to_bytes();
+        assert_eq!(bytes, [0x80, 0x01, 0, 3, 1, 2, 3]);
+        assert_eq!(parse_diagnostic_page_length(&bytes).expect("len"), 7);
+        assert_eq!(parse_diagnostic_page(&bytes).expect("page"), page);
+
+        let cut = parse_diagnostic_page(&bytes[..5]).expect("clipped");
+        assert_eq!(cut.data, [1]);
+        assert!(parse_diagnostic_page(&bytes[..3]).is_err());
+    }
+
+    #[test]
+    fn parses_supported_pages() {
+        let buf = [0x00, 0, 0, 3, 0x00, 0x01, 0x02];
+        assert_eq!(
+            parse_supported_diagnostic_pages(&buf).expect("pages"),
+            [0x00, 0x01, 0x02]
+        );
+        assert!(parse_supported_diagnostic_pages(&[0x01, 0, 0, 0]).is_err());
+    }
+
+    fn ses_config() -> Vec<u8> {
+        let mut d = vec![0, 0, 0, 7]; // generation
+        // enclosure descriptor: 2 type headers, 4 extra bytes
+        d.extend_from_slice(&[0x11, 0x00, 2, 4, 0xAA, 0xBB, 0xCC, 0xDD]);
+        // type headers: 2 PSUs with text "PSU", 3 slots without text
+        d.extend_from_slice(&[0x02, 2, 0, 3]);
+        d.extend_from_slice(&[0x17, 3, 0, 0]);
+        d.extend_from_slice(b"PSU");
+        let mut buf = vec![0x01, 0, 0, d.len() as u8];
+        buf.extend_from_slice(&d);
+        buf
+    }
+
+    #[test]
+    fn parses_ses_configuration() {
+        let cfg = parse_ses_configuration(&ses_config()).expect("config");
+        assert_eq!(cfg.generation, 7);
+        assert_eq!(cfg.types.len(), 2);
+        assert_eq!(cfg.types[0].element_type, 0x02);
+        assert_eq!(cfg.types[0].possible_elements, 2);
+        assert_eq!(cfg.types[0].text, "PSU");
+        assert_eq!(cfg.types[1].element_type, 0x17);
+        assert_eq!(cfg.types[1].text, "");
+
+        let mut cut = ses_config();
+        cut.truncate(14);
+        cut[3] = 10;
+        assert!(parse_ses_configuration(&cut).is_err());
+    }
+
+    #[test]
+    fn parses_and_groups_enclosure_status() {
+        let mut d = vec![0, 0, 0, 7];
+        // PSU overall + 2 PSUs (second critical with PRDFAIL)
+        d.extend_from_slice(&[0x01, 0, 0, 0, 0x01, 0, 0, 0, 0x42, 0, 0, 0]);
+        // slot overall + 3 slots (one not installed, one swapped)
+        d.extend_from_slice(&[
+            0x01, 0, 0, 0, 0x01, 0, 0, 0, 0x05, 0, 0, 0, 0x11, 0, 0, 0,
+        ]);
+        let mut buf = vec![0x02, 0x02, 0, d.len() as u8];
+        buf.extend_from_slice(&d);
+
+        let status = parse_ses_enclosure_status(&buf).expect("status");
+        assert!(status.critical);
+        assert!(!status.invalid_operation && !status.unrecoverable);
+        assert_eq!(status.elements.len(), 7);
+
+        let cfg = parse_ses_configuration(&ses_config()).expect("config");
+        let groups = status.group_by_type(&cfg).expect("groups");
+        assert_eq!(groups.len(), 2);
+        let psu = &groups[0].elements[1];
+        assert_eq!(psu.code, ElementStatusCode::Critical);
+        assert!(psu.predicted_failure);
+        assert_eq!(groups[1].elements[1].code, ElementStatusCode::NotInstalled);
+        assert!(groups[1].elements[2].swapped);
+
+        let mut stale = status;
+        stale.generation = 8;
+        assert!(stale.group_by_type(&cfg).is_err());
+    }
+
+    #[test]
+    fn builds_enclosure_control() {
+        let page = fill_ses_enclosure_control(7, &[[0; 4], [0x80, 0, 0x02, 0]]);
+        assert_eq!(
+            page.to_bytes(),
+            [0x02, 0, 0, 12, 0, 0, 0, 7, 0, 0, 0, 0, 0x80, 0, 0x02, 0]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::models::error::{Result, ensure};
+
+/// SERVICE ACTION of GET LBA STATUS under SERVICE ACTION IN(16).
+pub const GET_LBA_STATUS_SA: u8 = 0x12;
+
+/// Bytes before the first LBA status descriptor.
+pub const GET_LBA_STATUS_HEADER_LEN: usize = 8;
+
+/// Length of one LBA status descriptor.
+pub const LBA_STATUS_DESCRIPTOR_LEN: usize = 16;
+
+/// Build a 16-byte **SCSI GET LBA STATUS** CDB via SERVICE ACTION IN(16)
+/// (opcode 0x9E, SA=0x12).
+///
+/// Parameters:
+/// - `cdb`       : output buffer (zeroed)
+/// - `lba`       : first LBA to report on
+/// - `alloc_len` : allocation length; 8 bytes of header plus 16 per descriptor
+/// - `control`   : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte 0       : OPERATION CODE = 0x9E
+/// - byte 1       : SERVICE ACTION = 0x12
+/// - bytes 2..9   : STARTING LOGICAL BLOCK ADDRESS (big-endian, 64-bit)
+/// - bytes 10..13 : ALLOCATION LENGTH (big-endian, 32-bit)
+/// - byte 14      : REPORT TYPE (0 = all extents)
+/// - byte 15      : CONTROL
+#[inline]
+pub fn build_get_lba_status(cdb: &mut [u8; 16], lba: u64, alloc_len: u32, control: u8) {
+    cdb.fill(0);
+    cdb[0] = 0x9E; // SERVICE ACTION IN(16)
+    cdb[1] = GET_LBA_STATUS_SA;
+    cdb[2..10].copy_from_slice(&lba.to_be_bytes());
+    cdb[10..14].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[15] = control;
+}
+
+/// PROVISIONING STATUS of an extent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProvisioningStatus {
+    /// Mapped, or the target does not know (0x0 / 0x3).
+    Mapped,
+    /// Deallocated: reads return the LBPRZ pattern (0x1).
+    Deallocated,
+    /// Anchored: resources are reserved but the data is not mapped (0x2).
+    Anchored,
+    /// Any other code.
+    Reserved(u8),
+}
+
+impl From<u8> for ProvisioningStatus {
+    fn from(v: u8) -> Self {
+        match v & 0x0F {
+            0x0 | 0x3 => Self::Mapped,
+            0x1 => Self::Deallocated,
+            0x2 => Self::Anchored,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+/// One LBA status descriptor: `blocks` blocks starting at `lba` share a
+/// provisioning status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LbaStatus {
+    /// LBA STATUS LOGICAL BLOCK ADDRESS: first block of the extent.
+    pub lba: u64,
+    /// NUMBER OF LOGICAL BLOCKS in the extent.
+    pub blocks: u32,
+    /// PROVISIONING STATUS (byte 12, bits 3-0).
+    pub status: ProvisioningStatus,
+    /// ADDITIONAL STATUS (byte 13).
+    pub additional_status: u8,
+}
+
+impl LbaStatus {
+    /// First LBA after the extent.
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.lba.saturating_add(self.blocks as u64)
+    }
+
+    /// Whether the extent holds data (see [`ProvisioningStatus::Mapped`]).
+    #[inline]
+    pub fn is_mapped(&self) -> bool {
+        self.status == ProvisioningStatus::Mapped
+    }
+}
+
+/// Decode GET LBA STATUS parameter data (header included). Descriptors cut
+/// off by a short allocation length are not returned.
+pub fn parse_get_lba_status(buf: &[u8]) -> Result<Vec<LbaStatus>> {
+    ensure!(
+        buf.len() >= GET_LBA_STATUS_HEADER_LEN,
+        "GET LBA STATUS data too short: {} bytes",
+        buf.len()
+    );
+    // PARAMETER DATA LENGTH counts the bytes after itself.
+    let data_len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    let end = data_len.saturating_add(4).min(buf.len());
+    let descriptors = buf.get(GET_LBA_STATUS_HEADER_LEN..end).unwrap_or_default();
+    Ok(descriptors
+        .chunks_exact(LBA_STATUS_DESCRIPTOR_LEN)
+        .map(|d| LbaStatus {
+            lba: u64::from_be_bytes([d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]]),
+            blocks: u32::from_be_bytes([d[8], d[9], d[10], d[11]]),
+            status: d[12].into(),
+            additional_status: d[13],
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_lba_status_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_get_lba_status(&mut cdb, 0x0102_0304_0506_0708, 4104, 0);
+        assert_eq!(
+            cdb,
+            [0x9E, 0x12, 1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0x10, 0x08, 0, 0]
+        );
+    }
+
+    #[test]
+    fn parses_descriptors() {
+        let mut buf = vec![0u8; 8];
+        buf[0..4].copy_from_slice(&(4u32 + 3 * 16).to_be_bytes());
+        for (lba, blocks, status) in [(0u64, 8u32, 0u8), (8, 16, 1), (24, 4, 2)] {
+            let mut d = [0u8; 16];
+            d[0..8].copy_from_slice(&lba.to_be_bytes());
+            d[8..12].copy_from_slice(&blocks.to_be_bytes());
+            d[12] = status;
+            buf.extend_from_slice(&d);
+        }
+        let extents = parse_get_lba_status(&buf).expect("parse");
+        assert_eq!(extents.len(), 3);
+        assert!(extents[0].is_mapped());
+        assert_eq!(extents[1].status, ProvisioningStatus::Deallocated);
+        assert_eq!(extents[1].end(), 24);
+        assert_eq!(extents[2].status, ProvisioningStatus::Anchored);
+
+        // Truncated by the allocation length: only whole descriptors.
+        let extents = parse_get_lba_status(&buf[..8 + 16 + 10]).expect("parse");
+        assert_eq!(extents.len(), 1);
+        assert!(parse_get_lba_status(&buf[..4]).is_err());
+    }
+}
@@ -12,7 +12,12 @@
 //!   [4] = Allocation Length (u8)
 //!   [5] = Control
 
-use anyhow::{Result, bail};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::models::error::{ProtocolError, Result, bail};
 
 pub const INQUIRY_OPCODE: u8 = 0x12;
 
@@ -49,7 +54,7 @@ impl From<VpdPage> for u8 {
 }
 
 impl TryFrom<u8> for VpdPage {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     #[inline]
     fn try_from(v: u8) -> Result<Self> {
@@ -152,6 +157,34 @@ pub struct InquiryStandard {
     pub product_id: String,
     /// Product revision level (bytes 32-35) - 4 ASCII characters
     pub product_rev: String,
+    /// LU_CONG (byte 1 bit 6) - the logical unit is a member of a conglomerate
+    pub lu_cong: bool,
+    /// NORMACA (byte 3 bit 5) - NACA=1 in the CONTROL byte is supported
+    pub norm_aca: bool,
+    /// HISUP (byte 3 bit 4) - hierarchical LUN addressing is supported
+    pub hisup: bool,
+    /// SCCS (byte 5 bit 7) - an embedded storage array controller is present
+    pub sccs: bool,
+    /// ACC (byte 5 bit 6) - an access controls coordinator is present
+    pub acc: bool,
+    /// TPGS (byte 5 bits 5-4) - asymmetric logical unit access support:
+    /// bit 0 = implicit, bit 1 = explicit (SET TARGET PORT GROUPS)
+    pub tpgs: u8,
+    /// 3PC (byte 5 bit 3) - third-party copy (EXTENDED COPY) is supported
+    pub third_party_copy: bool,
+    /// PROTECT (byte 5 bit 0) - protection information is supported
+    pub protect: bool,
+    /// ENCSERV (byte 6 bit 6) - an enclosure services component is present
+    pub enc_serv: bool,
+    /// MULTIP (byte 6 bit 4) - the device has multiple ports
+    pub multi_port: bool,
+    /// CMDQUE (byte 7 bit 1) - command queuing is supported
+    pub cmd_que: bool,
+    /// Vendor-specific bytes 36-55, when returned
+    pub vendor_specific: Vec<u8>,
+    /// Non-zero version descriptors (bytes 58-73): standards the device
+    /// claims conformance to, e.g. 0x0460 for SPC-4
+    pub version_descriptors: Vec<u16>,
 }
 
 impl InquiryStandard {
@@ -173,12 +206,31 @@ impl InquiryStandard {
             0x0F => "Optical card",
             0x11 => "Object-based storage",
             0x12 => "Automation/Drive Interface",
+            0x14 => "Host-managed zoned block",
             _ => "Unknown/Reserved",
         }
     }
+
+    /// Implicit asymmetric access (ALUA) is supported: the target changes
+    /// port group states on its own.
+    #[inline]
+    pub fn supports_implicit_alua(&self) -> bool {
+        self.tpgs & 0x01 != 0
+    }
+
+    /// Explicit asymmetric access (ALUA) is supported: SET TARGET PORT
+    /// GROUPS may be used.
+    #[inline]
+    pub fn supports_explicit_alua(&self) -> bool {
+        self.tpgs & 0x02 != 0
+    }
 }
 
 /// Parse a Standard INQUIRY (EVPD=0) response (minimum 36 bytes).
+///
+/// Fields past byte 35 are decoded from the region announced by the
+/// ADDITIONAL LENGTH that was actually returned; shorter responses leave
+/// them empty.
 pub fn parse_inquiry_standard(buf: &[u8]) -> Result<InquiryStandard> {
     if buf.len() < 36 {
         bail!("INQUIRY buffer too short: {}", buf.len());
@@ -186,6 +238,7 @@ pub fn parse_inquiry_standard(buf: &[u8]) -> Result<InquiryStandard> {
     let b0 = buf[0];
     let b1 = buf[1];
     let b3 = buf[3];
+    let (b5, b6, b7) = (buf[5], buf[6], buf[7]);
 
     let peripheral_qualifier = (b0 >> 5) & 0x07;
     let device_type = b0 & 0x1F;
@@ -194,6 +247,16 @@ pub fn parse_inquiry_standard(buf: &[u8]) -> Result<InquiryStandard> {
     let response_data_format = b3 & 0x0F;
     let additional_length = buf[4];
 
+    let data = &buf[..buf.len().min(5 + additional_length as usize)];
+    let vendor_specific = data.get(36..56.min(data.len())).unwrap_or(&[]).to_vec();
+    let version_descriptors = data
+        .get(58..74.min(data.len()))
+        .unwrap_or(&[])
+        .chunks_exact(2)
+        .map(|d| u16::from_be_bytes([d[0], d[1]]))
+        .filter(|&d| d != 0)
+        .collect();
+
     Ok(InquiryStandard {
         peripheral_qualifier,
         device_type,
@@ -204,6 +267,19 @@ pub fn parse_inquiry_standard(buf: &[u8]) -> Result<InquiryStandard> {
         vendor_id: trim_ascii(&buf[8..16]),
         product_id: trim_ascii(&buf[16..32]),
         product_rev: trim_ascii(&buf[32..36]),
+        lu_cong: (b1 & 0x40) != 0,
+        norm_aca: (b3 & 0x20) != 0,
+        hisup: (b3 & 0x10) != 0,
+        sccs: (b5 & 0x80) != 0,
+        acc: (b5 & 0x40) != 0,
+        tpgs: (b5 >> 4) & 0x03,
+        third_party_copy: (b5 & 0x08) != 0,
+        protect: (b5 & 0x01) != 0,
+        enc_serv: (b6 & 0x40) != 0,
+        multi_port: (b6 & 0x10) != 0,
+        cmd_que: (b7 & 0x02) != 0,
+        vendor_specific,
+        version_descriptors,
     })
 }
 
@@ -299,7 +375,10 @@ pub fn parse_vpd_device_id(buf: &[u8]) -> Result<Vec<DeviceIdDescriptor>> {
 
         let identifier = match code_set {
             0x02 => trim_ascii(id_bytes), // ASCII
-            0x03 => String::from_utf8_lossy(id_bytes).trim().to_string(), // UTF-8
+            // UTF-8; SCSI name strings are NUL-terminated and NUL-padded
+            0x03 => String::from_utf8_lossy(id_bytes)
+                .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+                .to_string(),
             _ => hex_bytes(id_bytes),
         };
 
@@ -316,6 +395,272 @@ pub fn parse_vpd_device_id(buf: &[u8]) -> Result<Vec<DeviceIdDescriptor>> {
     Ok(out)
 }
 
+/// VPD 0xB0 — Block Limits (SBC-4 § 6.6.4).
+///
+/// Limits of 0 mean "not reported" and are returned as `None`. Targets
+/// implementing an older SBC return a shorter page; the missing fields are
+/// `None` as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BlockLimits {
+    /// WSNZ (byte 4 bit 0) - WRITE SAME with NUMBER OF LOGICAL BLOCKS = 0 is
+    /// rejected
+    pub wsnz: bool,
+    /// MAXIMUM COMPARE AND WRITE LENGTH (byte 5), in logical blocks
+    pub max_compare_and_write_len: Option<u8>,
+    /// OPTIMAL TRANSFER LENGTH GRANULARITY (bytes 6-7), in logical blocks
+    pub optimal_transfer_granularity: Option<u16>,
+    /// MAXIMUM TRANSFER LENGTH (bytes 8-11), in logical blocks
+    pub max_transfer_len: Option<u32>,
+    /// OPTIMAL TRANSFER LENGTH (bytes 12-15), in logical blocks
+    pub optimal_transfer_len: Option<u32>,
+    /// MAXIMUM PREFETCH LENGTH (bytes 16-19), in logical blocks
+    pub max_prefetch_len: Option<u32>,
+    /// MAXIMUM UNMAP LBA COUNT (bytes 20-23); `None` also when UNMAP is not
+    /// supported
+    pub max_unmap_lba_count: Option<u32>,
+    /// MAXIMUM UNMAP BLOCK DESCRIPTOR COUNT (bytes 24-27)
+    pub max_unmap_descriptors: Option<u32>,
+    /// OPTIMAL UNMAP GRANULARITY (bytes 28-31), in logical blocks
+    pub optimal_unmap_granularity: Option<u32>,
+    /// UNMAP GRANULARITY ALIGNMENT (bytes 32-35), when UGAVALID is set
+    pub unmap_granularity_alignment: Option<u32>,
+    /// MAXIMUM WRITE SAME LENGTH (bytes 36-43), in logical blocks
+    pub max_write_same_len: Option<u64>,
+}
+
+impl BlockLimits {
+    /// Blocks to move with one READ or WRITE when `preferred` would be used
+    /// otherwise: capped by MAXIMUM and OPTIMAL TRANSFER LENGTH and rounded
+    /// down to the OPTIMAL TRANSFER LENGTH GRANULARITY. Never 0.
+    pub fn transfer_blocks(&self, preferred: u32) -> u32 {
+        let mut blocks = preferred;
+        if let Some(max) = self.max_transfer_len {
+            blocks = blocks.min(max);
+        }
+        if let Some(optimal) = self.optimal_transfer_len {
+            blocks = blocks.min(optimal);
+        }
+        if let Some(granularity) = self.optimal_transfer_granularity.map(u32::from)
+            && blocks >= granularity
+        {
+            blocks -= blocks % granularity;
+        }
+        blocks.max(1)
+    }
+}
+
+/// Parse VPD page 0xB0 (Block Limits).
+pub fn parse_vpd_block_limits(buf: &[u8]) -> Result<BlockLimits> {
+    let (pc, payload) = vpd_payload(buf)?;
+    if pc != 0xB0 {
+        bail!("expected VPD page 0xB0, got 0x{:02X}", pc);
+    }
+    // Offsets below are from the start of the page, header included.
+    let page = &buf[..4 + payload.len()];
+    let be32 = |at: usize| {
+        page.get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .filter(|&v| v != 0)
+    };
+    let max_write_same_len = page
+        .get(36..44)
+        .map(|b| u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+        .filter(|&v| v != 0);
+    let unmap_granularity_alignment = page
+        .get(32..36)
+        .filter(|b| b[0] & 0x80 != 0)
+        .map(|b| u32::from_be_bytes([b[0] & 0x7F, b[1], b[2], b[3]]));
+
+    Ok(BlockLimits {
+        wsnz: page.get(4).is_some_and(|b| b & 0x01 != 0),
+        max_compare_and_write_len: page.get(5).copied().filter(|&v| v != 0),
+        optimal_transfer_granularity: page
+            .get(6..8)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .filter(|&v| v != 0),
+        max_transfer_len: be32(8),
+        optimal_transfer_len: be32(12),
+        max_prefetch_len: be32(16),
+        max_unmap_lba_count: be32(20),
+        max_unmap_descriptors: be32(24),
+        optimal_unmap_granularity: be32(28),
+        unmap_granularity_alignment,
+        max_write_same_len,
+    })
+}
+
+/// PROVISIONING TYPE of the Logical Block Provisioning VPD page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProvisioningType {
+    /// Fully provisioned, or the type is not reported (0h).
+    FullOrUnknown,
+    /// Resource provisioned (1h).
+    Resource,
+    /// Thin provisioned (2h).
+    Thin,
+    /// Reserved value.
+    Reserved(u8),
+}
+
+impl From<u8> for ProvisioningType {
+    fn from(v: u8) -> Self {
+        match v {
+            0 => Self::FullOrUnknown,
+            1 => Self::Resource,
+            2 => Self::Thin,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+/// VPD 0xB2 — Logical Block Provisioning (SBC-4 § 6.6.6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogicalBlockProvisioning {
+    /// THRESHOLD EXPONENT (byte 4)
+    pub threshold_exponent: u8,
+    /// LBPU (byte 5 bit 7) - UNMAP is supported
+    pub lbpu: bool,
+    /// LBPWS (byte 5 bit 6) - WRITE SAME(16) with UNMAP=1 is supported
+    pub lbpws: bool,
+    /// LBPWS10 (byte 5 bit 5) - WRITE SAME(10) with UNMAP=1 is supported
+    pub lbpws10: bool,
+    /// LBPRZ (byte 5 bits 4-2) - what unmapped blocks read back as; 0 means
+    /// not specified, bit 0 set means zeros
+    pub lbprz: u8,
+    /// ANC_SUP (byte 5 bit 1) - UNMAP with ANCHOR=1 is supported
+    pub anc_sup: bool,
+    /// DP (byte 5 bit 0) - a provisioning group descriptor follows
+    pub dp: bool,
+    /// MINIMUM PERCENTAGE (byte 6 bits 7-3)
+    pub minimum_percentage: u8,
+    /// PROVISIONING TYPE (byte 6 bits 2-0)
+    pub provisioning_type: ProvisioningType,
+    /// THRESHOLD PERCENTAGE (byte 7)
+    pub threshold_percentage: u8,
+}
+
+/// Parse VPD page 0xB2 (Logical Block Provisioning).
+pub fn parse_vpd_logical_block_provisioning(
+    buf: &[u8],
+) -> Result<LogicalBlockProvisioning> {
+    let (pc, payload) = vpd_payload(buf)?;
+    if pc != 0xB2 {
+        bail!("expected VPD page 0xB2, got 0x{:02X}", pc);
+    }
+    if payload.len() < 4 {
+        bail!("VPD page 0xB2 too short: {} bytes", payload.len());
+    }
+    let (b4, b5, b6, b7) = (payload[0], payload[1], payload[2], payload[3]);
+    Ok(LogicalBlockProvisioning {
+        threshold_exponent: b4,
+        lbpu: b5 & 0x80 != 0,
+        lbpws: b5 & 0x40 != 0,
+        lbpws10: b5 & 0x20 != 0,
+        lbprz: (b5 >> 2) & 0x07,
+        anc_sup: b5 & 0x02 != 0,
+        dp: b5 & 0x01 != 0,
+        minimum_percentage: b6 >> 3,
+        provisioning_type: (b6 & 0x07).into(),
+        threshold_percentage: b7,
+    })
+}
+
+/// Designator types (byte 1, bits 3-0) of VPD 0x83 descriptors.
+pub mod designator_type {
+    pub const VENDOR_SPECIFIC: u8 = 0x0;
+    pub const T10_VENDOR_ID: u8 = 0x1;
+    pub const EUI64: u8 = 0x2;
+    pub const NAA: u8 = 0x3;
+    pub const RELATIVE_TARGET_PORT: u8 = 0x4;
+    pub const TARGET_PORT_GROUP: u8 = 0x5;
+    pub const SCSI_NAME_STRING: u8 = 0x8;
+}
+
+/// Association (byte 1, bits 5-4) of a designator with the logical unit.
+pub const ASSOCIATION_LOGICAL_UNIT: u8 = 0x0;
+
+/// Association of a designator with the target port the command came in
+/// through.
+pub const ASSOCIATION_TARGET_PORT: u8 = 0x1;
+
+/// Target port group and relative target port of the port that answered
+/// the VPD 0x83 INQUIRY, as `(group, port)`. Either is `None` when not
+/// reported.
+pub fn target_port_designators(
+    descs: &[DeviceIdDescriptor],
+) -> (Option<u16>, Option<u16>) {
+    let find = |id_type: u8| {
+        descs
+            .iter()
+            .filter(|d| d.association == ASSOCIATION_TARGET_PORT && d.id_type == id_type)
+            // 4-byte binary designator, identifier in bytes 2..3.
+            .find_map(|d| {
+                let hex = d.identifier.get(4..8)?;
+                u16::from_str_radix(hex, 16).ok()
+            })
+    };
+    (
+        find(designator_type::TARGET_PORT_GROUP),
+        find(designator_type::RELATIVE_TARGET_PORT),
+    )
+}
+
+/// Name of a logical unit that stays the same on every session and portal,
+/// taken from VPD 0x83.
+///
+/// Displays in the `naa.` / `eui.` form used by Linux and multipath tools;
+/// SCSI name strings already carry their own prefix.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeviceIdentity {
+    /// NAA designator, upper-case hex digits.
+    Naa(String),
+    /// EUI-64 based designator (8, 12 or 16 bytes), upper-case hex digits.
+    Eui64(String),
+    /// SCSI name string (e.g. `iqn.2003-01.org.example:lun0` or `naa.…`).
+    ScsiName(String),
+}
+
+impl DeviceIdentity {
+    /// The designator without a type prefix.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Naa(id) | Self::Eui64(id) | Self::ScsiName(id) => id,
+        }
+    }
+}
+
+impl core::fmt::Display for DeviceIdentity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Naa(id) => write!(f, "naa.{}", id.to_ascii_lowercase()),
+            Self::Eui64(id) => write!(f, "eui.{}", id.to_ascii_lowercase()),
+            Self::ScsiName(id) => f.write_str(id),
+        }
+    }
+}
+
+/// Pick the canonical identity among the logical-unit designators of VPD
+/// 0x83: NAA, then EUI-64, then SCSI name string. Within a type the longest
+/// designator wins (e.g. NAA IEEE Registered Extended over NAA IEEE
+/// Registered). Port and target designators are ignored.
+pub fn device_identity(descriptors: &[DeviceIdDescriptor]) -> Option<DeviceIdentity> {
+    let pick = |id_type: u8| {
+        descriptors
+            .iter()
+            .filter(|d| {
+                d.association == ASSOCIATION_LOGICAL_UNIT
+                    && d.id_type == id_type
+                    && !d.identifier.is_empty()
+            })
+            .max_by_key(|d| d.identifier.len())
+            .map(|d| d.identifier.clone())
+    };
+    pick(designator_type::NAA)
+        .map(DeviceIdentity::Naa)
+        .or_else(|| pick(designator_type::EUI64).map(DeviceIdentity::Eui64))
+        .or_else(|| pick(designator_type::SCSI_NAME_STRING).map(DeviceIdentity::ScsiName))
+}
+
 fn trim_ascii(bytes: &[u8]) -> String {
     let s: String = bytes
         .iter()
@@ -356,6 +701,107 @@ mod tests {
         assert_eq!(s.product_rev, "0020");
     }
 
+    #[test]
+    fn parse_std_inquiry_extended_fields() {
+        let mut b = [0u8; 96];
+        b[1] = 0x40; // LU_CONG
+        b[3] = 0x32; // NORMACA, HISUP, RDF=2
+        b[4] = 91;
+        b[5] = 0x39; // TPGS=3, 3PC, PROTECT
+        b[6] = 0x10; // MULTIP
+        b[7] = 0x02; // CMDQUE
+        b[8..16].copy_from_slice(b"LIO-ORG ");
+        b[36..40].copy_from_slice(b"VEND");
+        b[58..60].copy_from_slice(&0x0460u16.to_be_bytes()); // SPC-4
+        b[60..62].copy_from_slice(&0x04C0u16.to_be_bytes()); // SBC-3
+        let s = parse_inquiry_standard(&b).expect("parse");
+        assert!(s.lu_cong && s.norm_aca && s.hisup);
+        assert_eq!(s.tpgs, 3);
+        assert!(s.supports_implicit_alua() && s.supports_explicit_alua());
+        assert!(s.third_party_copy && s.protect && s.multi_port && s.cmd_que);
+        assert!(!s.sccs && !s.acc && !s.enc_serv);
+        assert_eq!(&s.vendor_specific[..4], b"VEND");
+        assert_eq!(s.vendor_specific.len(), 20);
+        assert_eq!(s.version_descriptors, vec![0x0460, 0x04C0]);
+
+        // ADDITIONAL LENGTH bounds the decoded region.
+        b[4] = 31;
+        let short = parse_inquiry_standard(&b).expect("parse");
+        assert!(short.vendor_specific.is_empty());
+        assert!(short.version_descriptors.is_empty());
+        assert!(short.protect);
+    }
+
+    #[test]
+    fn parse_block_limits_page() {
+        let mut page = vec![0u8; 64];
+        page[1] = 0xB0;
+        page[3] = 0x3C;
+        page[4] = 0x01; // WSNZ
+        page[5] = 1;
+        page[6..8].copy_from_slice(&8u16.to_be_bytes());
+        page[8..12].copy_from_slice(&4096u32.to_be_bytes());
+        page[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        page[20..24].copy_from_slice(&0x40_0000u32.to_be_bytes());
+        page[24..28].copy_from_slice(&1u32.to_be_bytes());
+        page[28..32].copy_from_slice(&8u32.to_be_bytes());
+        page[32..36].copy_from_slice(&0x8000_0002u32.to_be_bytes());
+        page[36..44].copy_from_slice(&0xFFFFu64.to_be_bytes());
+
+        let limits = parse_vpd_block_limits(&page).expect("parse");
+        assert_eq!(
+            limits,
+            BlockLimits {
+                wsnz: true,
+                max_compare_and_write_len: Some(1),
+                optimal_transfer_granularity: Some(8),
+                max_transfer_len: Some(4096),
+                optimal_transfer_len: Some(1000),
+                max_prefetch_len: None,
+                max_unmap_lba_count: Some(0x40_0000),
+                max_unmap_descriptors: Some(1),
+                optimal_unmap_granularity: Some(8),
+                unmap_granularity_alignment: Some(2),
+                max_write_same_len: Some(0xFFFF),
+            }
+        );
+        // 2048 -> OPTIMAL 1000 -> granularity 8 -> 1000; small requests stay.
+        assert_eq!(limits.transfer_blocks(2048), 1000);
+        assert_eq!(limits.transfer_blocks(5), 5);
+        assert_eq!(limits.transfer_blocks(13), 8);
+
+        // SBC-2 era page: only the transfer lengths.
+        let short = [
+            0x00, 0xB0, 0x00, 0x0C, 0, 0, 0, 0, 0, 0, 0x01, 0, 0, 0, 0, 0,
+        ];
+        let limits = parse_vpd_block_limits(&short).expect("parse");
+        assert_eq!(limits.max_transfer_len, Some(256));
+        assert_eq!(limits.max_unmap_lba_count, None);
+        assert!(parse_vpd_block_limits(&[0x00, 0x80, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn parse_logical_block_provisioning_page() {
+        let page = [0x00, 0xB2, 0x00, 0x04, 0x09, 0xC6, 0x0A, 0x32];
+        let lbp = parse_vpd_logical_block_provisioning(&page).expect("parse");
+        assert_eq!(
+            lbp,
+            LogicalBlockProvisioning {
+                threshold_exponent: 9,
+                lbpu: true,
+                lbpws: true,
+                lbpws10: false,
+                lbprz: 1,
+                anc_sup: true,
+                dp: false,
+                minimum_percentage: 1,
+                provisioning_type: ProvisioningType::Thin,
+                threshold_percentage: 0x32,
+            }
+        );
+        assert!(parse_vpd_logical_block_provisioning(&page[..6]).is_err());
+    }
+
     #[test]
     fn parse_vpd_supported() {
         // PQ/DT = disk, page=0x00, len=3, payload: 0x00,0x80,0x83
@@ -381,4 +827,94 @@ mod tests {
         assert_eq!(v[0].code_set, 0x02);
         assert_eq!(v[0].id_type, 0x00);
     }
+
+    fn descriptor(code_set: u8, association: u8, id_type: u8, id: &[u8]) -> Vec<u8> {
+        let mut d = vec![code_set, (association << 4) | id_type, 0, id.len() as u8];
+        d.extend_from_slice(id);
+        d
+    }
+
+    fn device_id_page(descriptors: &[Vec<u8>]) -> Vec<u8> {
+        let payload = descriptors.concat();
+        let mut buf = vec![0x00, 0x83, 0x00, payload.len() as u8];
+        buf.extend_from_slice(&payload);
+        buf
+    }
+
+    #[test]
+    fn identity_prefers_logical_unit_naa() {
+        let page = device_id_page(&[
+            descriptor(0x02, 0, designator_type::T10_VENDOR_ID, b"LIO-ORG disk0"),
+            descriptor(0x01, 1, designator_type::NAA, &[0x51, 2, 3, 4, 5, 6, 7, 8]),
+            descriptor(0x03, 0, designator_type::SCSI_NAME_STRING, b"iqn.x:lun0"),
+            descriptor(0x01, 0, designator_type::EUI64, &[1; 8]),
+            descriptor(0x01, 0, designator_type::NAA, &[0x50, 1, 2, 3, 4, 5, 6, 7]),
+            descriptor(
+                0x01,
+                0,
+                designator_type::NAA,
+                &[
+                    0x60, 0x01, 0x40, 0x5A, 0xBC, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01,
+                ],
+            ),
+        ]);
+        let id = device_identity(&parse_vpd_device_id(&page).expect("parse"))
+            .expect("identity");
+        assert_eq!(
+            id,
+            DeviceIdentity::Naa("6001405ABC0000000000000000000001".into())
+        );
+        assert_eq!(id.to_string(), "naa.6001405abc0000000000000000000001");
+    }
+
+    #[test]
+    fn identity_falls_back_to_eui64_then_scsi_name() {
+        let eui = device_id_page(&[
+            descriptor(0x03, 0, designator_type::SCSI_NAME_STRING, b"iqn.x:lun0"),
+            descriptor(0x01, 0, designator_type::EUI64, &[0xAB; 8]),
+        ]);
+        let id = device_identity(&parse_vpd_device_id(&eui).expect("parse"));
+        assert_eq!(
+            id.map(|id| id.to_string()).as_deref(),
+            Some("eui.abababababababab")
+        );
+
+        let name = device_id_page(&[descriptor(
+            0x03,
+            0,
+            designator_type::SCSI_NAME_STRING,
+            b"iqn.x:lun0\0\0",
+        )]);
+        let id = device_identity(&parse_vpd_device_id(&name).expect("parse"));
+        assert_eq!(id, Some(DeviceIdentity::ScsiName("iqn.x:lun0".into())));
+
+        let vendor_only =
+            device_id_page(&[descriptor(0x02, 0, designator_type::T10_VENDOR_ID, b"X")]);
+        assert_eq!(
+            device_identity(&parse_vpd_device_id(&vendor_only).expect("parse")),
+            None
+        );
+    }
+
+    #[test]
+    fn finds_target_port_designators() {
+        let page = device_id_page(&[
+            descriptor(0x01, 0, designator_type::NAA, &[0x50, 1, 2, 3, 4, 5, 6, 7]),
+            descriptor(
+                0x01,
+                1,
+                designator_type::RELATIVE_TARGET_PORT,
+                &[0, 0, 0, 2],
+            ),
+            descriptor(
+                0x01,
+                1,
+                designator_type::TARGET_PORT_GROUP,
+                &[0, 0, 0x01, 0x05],
+            ),
+        ]);
+        let descs = parse_vpd_device_id(&page).expect("parse");
+        assert_eq!(target_port_designators(&descs), (Some(0x0105), Some(2)));
+        assert_eq!(target_port_designators(&descs[..1]), (None, None));
+    }
 }
@@ -0,0 +1,459 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+//! Multimedia commands (MMC) for CD / DVD / BD devices.
+
+use alloc::vec::Vec;
+
+use crate::models::error::{Result, ensure};
+
+/// READ TOC/PMA/ATIP operation code.
+pub const READ_TOC: u8 = 0x43;
+/// GET CONFIGURATION operation code.
+pub const GET_CONFIGURATION: u8 = 0x46;
+/// READ DISC INFORMATION operation code.
+pub const READ_DISC_INFORMATION: u8 = 0x51;
+
+/// Peripheral device type of an MMC device.
+pub const DEVICE_TYPE_MMC: u8 = 0x05;
+/// User data bytes of a Mode 1 / DVD sector.
+pub const MMC_SECTOR_LEN: u32 = 2048;
+/// Track number of the lead-out in a TOC.
+pub const LEAD_OUT_TRACK: u8 = 0xAA;
+
+/// Size of the TOC header.
+pub const TOC_HEADER_LEN: usize = 4;
+/// Size of a TOC track descriptor.
+pub const TOC_DESCRIPTOR_LEN: usize = 8;
+/// Size of the GET CONFIGURATION feature header.
+pub const FEATURE_HEADER_LEN: usize = 8;
+/// Size of the standard disc information.
+pub const DISC_INFORMATION_LEN: usize = 34;
+
+/// Profile List feature code.
+pub const FEATURE_PROFILE_LIST: u16 = 0x0000;
+
+/// Build a padded 16-byte **MMC READ TOC/PMA/ATIP** CDB for the formatted
+/// TOC (format 0000b) starting at `track`.
+///
+/// Layout (MMC-6):
+/// - byte 0     : OPERATION CODE = 0x43
+/// - byte 1     : MSF[1]
+/// - byte 2     : FORMAT[3:0]
+/// - byte 6     : TRACK/SESSION NUMBER
+/// - bytes 7..8 : ALLOCATION LENGTH (BE)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_read_toc(
+    cdb: &mut [u8; 16],
+    msf: bool,
+    track: u8,
+    alloc_len: u16,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = READ_TOC;
+    cdb[1] = (msf as u8) << 1;
+    cdb[6] = track;
+    cdb[7..9].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// RT field of GET CONFIGURATION: which features are returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ConfigurationRequest {
+    /// Every feature from the starting one on.
+    All = 0b00,
+    /// Only current features from the starting one on.
+    Current = 0b01,
+    /// Only the starting feature.
+    One = 0b10,
+}
+
+/// Build a padded 16-byte **MMC GET CONFIGURATION** CDB.
+///
+/// Layout (MMC-6):
+/// - byte 0     : OPERATION CODE = 0x46
+/// - byte 1     : RT[1:0]
+/// - bytes 2..3 : STARTING FEATURE NUMBER (BE)
+/// - bytes 7..8 : ALLOCATION LENGTH (BE)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_get_configuration(
+    cdb: &mut [u8; 16],
+    rt: ConfigurationRequest,
+    start_feature: u16,
+    alloc_len: u16,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = GET_CONFIGURATION;
+    cdb[1] = rt as u8;
+    cdb[2..4].copy_from_slice(&start_feature.to_be_bytes());
+    cdb[7..9].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Build a padded 16-byte **MMC READ DISC INFORMATION** CDB for the
+/// standard disc information (data type 000b).
+///
+/// Layout (MMC-6):
+/// - byte 0     : OPERATION CODE = 0x51
+/// - byte 1     : DATA TYPE[2:0]
+/// - bytes 7..8 : ALLOCATION LENGTH (BE)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_read_disc_information(cdb: &mut [u8; 16], alloc_len: u16, control: u8) {
+    cdb.fill(0);
+    cdb[0] = READ_DISC_INFORMATION;
+    cdb[7..9].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// One track of a formatted TOC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TocTrack {
+    /// Track number; [`LEAD_OUT_TRACK`] for the lead-out.
+    pub number: u8,
+    /// ADR: what the Q sub-channel encodes (1 = current position).
+    pub adr: u8,
+    /// CONTROL: track attributes; bit 2 set marks a data track.
+    pub control: u8,
+    /// TRACK START ADDRESS as an LBA (MSF addresses are converted).
+    pub start_lba: i64,
+}
+
+impl TocTrack {
+    /// Whether the track holds data rather than audio.
+    #[inline]
+    pub fn is_data(&self) -> bool {
+        self.control & 0x04 != 0
+    }
+}
+
+/// Formatted TOC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toc {
+    /// First track number.
+    pub first_track: u8,
+    /// Last track number.
+    pub last_track: u8,
+    /// Track descriptors, lead-out included.
+    pub tracks: Vec<TocTrack>,
+}
+
+impl Toc {
+    /// Start of the lead-out, i.e. the number of addressable sectors.
+    pub fn lead_out(&self) -> Option<i64> {
+        self.tracks
+            .iter()
+            .find(|t| t.number == LEAD_OUT_TRACK)
+            .map(|t| t.start_lba)
+    }
+}
+
+/// Convert an MSF address to an LBA (MMC: LBA = (M*60 + S)*75 + F - 150).
+#[inline]
+pub fn msf_to_lba(m: u8, s: u8, f: u8) -> i64 {
+    (m as i64 * 60 + s as i64) * 75 + f as i64 - 150
+}
+
+/// Total size (header included) of a TOC, from TOC DATA LENGTH.
+pub fn parse_toc_length(buf: &[u8]) -> Result<usize> {
+    ensure!(buf.len() >= 2, "TOC header truncated: {} bytes", buf.len());
+    Ok(2 + u16::from_be_bytes([buf[0], buf[1]]) as usize)
+}
+
+/// Decode a formatted TOC; `msf` must match the request. Descriptors cut
+/// off by the allocation length are dropped.
+///
+/// Layout (MMC-6): bytes 0..1 TOC DATA LENGTH, byte 2 FIRST TRACK, byte 3
+/// LAST TRACK; per track byte 1 ADR[7:4] | CONTROL[3:0], byte 2 TRACK
+/// NUMBER, bytes 4..7 TRACK START ADDRESS (LBA, or 0/M/S/F).
+pub fn parse_toc(buf: &[u8], msf: bool) -> Result<Toc> {
+    ensure!(
+        buf.len() >= TOC_HEADER_LEN,
+        "TOC header truncated: {} bytes",
+        buf.len()
+    );
+    let end = parse_toc_length(buf)?.min(buf.len());
+    let tracks = buf[TOC_HEADER_LEN..end]
+        .chunks_exact(TOC_DESCRIPTOR_LEN)
+        .map(|d| TocTrack {
+            number: d[2],
+            adr: d[1] >> 4,
+            control: d[1] & 0x0F,
+            start_lba: if msf {
+                msf_to_lba(d[5], d[6], d[7])
+            } else {
+                i32::from_be_bytes([d[4], d[5], d[6], d[7]]) as i64
+            },
+        })
+        .collect();
+    Ok(Toc {
+        first_track: buf[2],
+        last_track: buf[3],
+        tracks,
+    })
+}
+
+/// Feature descriptor of GET CONFIGURATION.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feature {
+    /// FEATURE CODE.
+    pub code: u16,
+    /// VERSION.
+    pub version: u8,
+    /// PERSISTENT: the feature is always current.
+    pub persistent: bool,
+    /// CURRENT: the feature is active with the loaded medium.
+    pub current: bool,
+    /// Feature dependent data.
+    pub data: Vec<u8>,
+}
+
+/// Profile of the Profile List feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    /// PROFILE NUMBER (e.g. 0x0008 CD-ROM, 0x0010 DVD-ROM, 0x0040 BD-ROM).
+    pub number: u16,
+    /// CURRENTP: the loaded medium uses this profile.
+    pub current: bool,
+}
+
+/// GET CONFIGURATION data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Configuration {
+    /// CURRENT PROFILE (0 without a medium).
+    pub current_profile: u16,
+    /// Feature descriptors.
+    pub features: Vec<Feature>,
+}
+
+impl Configuration {
+    /// The feature with `code`, if reported.
+    pub fn feature(&self, code: u16) -> Option<&Feature> {
+        self.features.iter().find(|f| f.code == code)
+    }
+
+    /// Profiles of the Profile List feature.
+    pub fn profiles(&self) -> Vec<Profile> {
+        self.feature(FEATURE_PROFILE_LIST)
+            .map(|f| {
+                f.data
+                    .chunks_exact(4)
+                    .map(|p| Profile {
+                        number: u16::from_be_bytes([p[0], p[1]]),
+                        current: p[2] & 0x01 != 0,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Name of an MMC profile number.
+pub fn profile_name(number: u16) -> &'static str {
+    match number {
+        0x0000 => "No current profile",
+        0x0008 => "CD-ROM",
+        0x0009 => "CD-R",
+        0x000A => "CD-RW",
+        0x0010 => "DVD-ROM",
+        0x0011 => "DVD-R",
+        0x0012 => "DVD-RAM",
+        0x0013 | 0x0014 => "DVD-RW",
+        0x001A => "DVD+RW",
+        0x001B => "DVD+R",
+        0x0040 => "BD-ROM",
+        0x0041 | 0x0042 => "BD-R",
+        0x0043 => "BD-RE",
+        _ => "Unknown",
+    }
+}
+
+/// Total size (header included) of GET CONFIGURATION data.
+pub fn parse_configuration_length(buf: &[u8]) -> Result<usize> {
+    ensure!(
+        buf.len() >= FEATURE_HEADER_LEN,
+        "feature header truncated: {} bytes",
+        buf.len()
+    );
+    Ok(4 + u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize)
+}
+
+/// Decode GET CONFIGURATION data; descriptors cut off by the allocation
+/// length are dropped.
+///
+/// Layout (MMC-6): bytes 0..3 DATA LENGTH, bytes 6..7 CURRENT PROFILE; per
+/// feature bytes 0..1 FEATURE CODE, byte 2 VERSION[5:2] | PERSISTENT[1] |
+/// CURRENT[0], byte 3 ADDITIONAL LENGTH.
+pub fn parse_configuration(buf: &[u8]) -> Result<Configuration> {
+    let end = parse_configuration_length(buf)?.min(buf.len());
+    let mut features = Vec::new();
+    let mut off = FEATURE_HEADER_LEN;
+    while off + 4 <= end {
+        let d = &buf[off..];
+        let len = d[3] as usize;
+        if off + 4 + len > end {
+            break;
+        }
+        features.push(Feature {
+            code: u16::from_be_bytes([d[0], d[1]]),
+            version: (d[2] >> 2) & 0x0F,
+            persistent: d[2] & 0x02 != 0,
+            current: d[2] & 0x01 != 0,
+            data: d[4..4 + len].to_vec(),
+        });
+        off += 4 + len;
+    }
+    Ok(Configuration {
+        current_profile: u16::from_be_bytes([buf[6], buf[7]]),
+        features,
+    })
+}
+
+/// DISC STATUS of the disc information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscStatus {
+    /// Nothing recorded.
+    Empty,
+    /// Appendable.
+    Incomplete,
+    /// Closed; nothing can be added.
+    Finalized,
+    /// Random-access medium (e.g. DVD-RAM, BD-RE).
+    Other,
+}
+
+/// Standard disc information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscInformation {
+    /// ERASABLE: the medium is rewritable.
+    pub erasable: bool,
+    /// STATE OF LAST SESSION (0 empty, 1 incomplete, 2 damaged, 3 complete).
+    pub last_session_state: u8,
+    /// DISC STATUS.
+    pub status: DiscStatus,
+    /// FIRST TRACK NUMBER on the disc.
+    pub first_track: u8,
+    /// Number of sessions.
+    pub sessions: u16,
+    /// First track number in the last session.
+    pub first_track_last_session: u16,
+    /// Last track number in the last session.
+    pub last_track_last_session: u16,
+    /// DISC TYPE (0x00 CD-DA / CD-ROM, 0x10 CD-I, 0x20 CD-ROM XA).
+    pub disc_type: u8,
+}
+
+/// Decode the standard disc information.
+///
+/// Layout (MMC-6): byte 2 ERASABLE[4] | STATE OF LAST SESSION[3:2] | DISC
+/// STATUS[1:0], byte 3 FIRST TRACK NUMBER, bytes 4/9 NUMBER OF SESSIONS
+/// (LSB/MSB), bytes 5/10 FIRST TRACK IN LAST SESSION, bytes 6/11 LAST
+/// TRACK IN LAST SESSION, byte 8 DISC TYPE.
+pub fn parse_disc_information(buf: &[u8]) -> Result<DiscInformation> {
+    ensure!(
+        buf.len() >= 12,
+        "disc information truncated: {} bytes",
+        buf.len()
+    );
+    Ok(DiscInformation {
+        erasable: buf[2] & 0x10 != 0,
+        last_session_state: (buf[2] >> 2) & 0x03,
+        status: match buf[2] & 0x03 {
+            0 => DiscStatus::Empty,
+            1 => DiscStatus::Incomplete,
+            2 => DiscStatus::Finalized,
+            _ => DiscStatus::Other,
+        },
+        first_track: buf[3],
+        sessions: u16::from_le_bytes([buf[4], buf[9]]),
+        first_track_last_session: u16::from_le_bytes([buf[5], buf[10]]),
+        last_track_last_session: u16::from_le_bytes([buf[6], buf[11]]),
+        disc_type: buf[8],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cdb_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_read_toc(&mut cdb, true, 1, 804, 0);
+        assert_eq!(&cdb[..10], &[0x43, 0x02, 0, 0, 0, 0, 1, 0x03, 0x24, 0]);
+        assert!(cdb[10..].iter().all(|&b| b == 0));
+
+        build_get_configuration(&mut cdb, ConfigurationRequest::Current, 0, 0x400, 0);
+        assert_eq!(&cdb[..10], &[0x46, 0x01, 0, 0, 0, 0, 0, 0x04, 0, 0]);
+
+        build_read_disc_information(&mut cdb, 34, 0);
+        assert_eq!(&cdb[..10], &[0x51, 0, 0, 0, 0, 0, 0, 0, 34, 0]);
+    }
+
+    #[test]
+    fn parses_toc() {
+        // one data track at LBA 0, lead-out at 0x1_2345 (LBA form)
+        let buf = [
+            0, 18, 1, 1, //
+            0, 0x14, 1, 0, 0, 0, 0, 0, //
+            0, 0x14, 0xAA, 0, 0, 0x01, 0x23, 0x45,
+        ];
+        let toc = parse_toc(&buf, false).expect("toc");
+        assert_eq!((toc.first_track, toc.last_track), (1, 1));
+        assert_eq!(toc.tracks.len(), 2);
+        assert!(toc.tracks[0].is_data());
+        assert_eq!(toc.tracks[0].adr, 1);
+        assert_eq!(toc.lead_out(), Some(0x1_2345));
+
+        // same TOC in MSF form: 00:02:00 is LBA 0
+        let msf = [0, 10, 1, 1, 0, 0x14, 1, 0, 0, 0, 2, 0];
+        assert_eq!(parse_toc(&msf, true).expect("toc").tracks[0].start_lba, 0);
+        assert!(parse_toc(&buf[..3], false).is_err());
+    }
+
+    #[test]
+    fn parses_configuration_and_disc_information() {
+        let mut buf = vec![0, 0, 0, 0, 0, 0, 0x00, 0x10];
+        // Profile List: DVD-ROM (current), CD-ROM
+        buf.extend_from_slice(&[0, 0, 0x03, 8, 0, 0x10, 1, 0, 0, 0x08, 0, 0]);
+        // Core feature
+        buf.extend_from_slice(&[0, 1, 0x0B, 4, 0, 0, 0, 8]);
+        let len = (buf.len() - 4) as u32;
+        buf[..4].copy_from_slice(&len.to_be_bytes());
+        let cfg = parse_configuration(&buf).expect("configuration");
+        assert_eq!(profile_name(cfg.current_profile), "DVD-ROM");
+        assert_eq!(cfg.features.len(), 2);
+        assert!(cfg.feature(1).is_some_and(|f| f.current && f.version == 2));
+        assert_eq!(
+            cfg.profiles(),
+            [
+                Profile {
+                    number: 0x10,
+                    current: true
+                },
+                Profile {
+                    number: 0x08,
+                    current: false
+                },
+            ]
+        );
+
+        let mut info = [0u8; DISC_INFORMATION_LEN];
+        info[1] = 32;
+        info[2] = 0x0E;
+        info[3] = 1;
+        info[4] = 1;
+        info[5] = 1;
+        info[6] = 3;
+        let info = parse_disc_information(&info).expect("disc info");
+        assert_eq!(info.status, DiscStatus::Finalized);
+        assert_eq!(info.last_session_state, 3);
+        assert_eq!(info.last_track_last_session, 3);
+        assert!(!info.erasable);
+    }
+}
@@ -3,21 +3,72 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
+/// Implements the SCSI REPORT / SET TARGET PORT GROUPS (ALUA) commands.
+pub mod alua;
+/// Implements the SAT ATA PASS-THROUGH(12) and ATA PASS-THROUGH(16)
+/// commands.
+pub mod ata_passthrough;
+/// Implements the SCSI READ BUFFER and WRITE BUFFER commands.
+pub mod buffer;
+/// Helpers for the CDB CONTROL byte (NACA, vendor bits).
+pub mod control;
+/// Implements the SCSI SEND DIAGNOSTIC and RECEIVE DIAGNOSTIC RESULTS
+/// commands, including the SES pages.
+pub mod diagnostic;
+/// Implements the SCSI GET LBA STATUS command.
+pub mod get_lba_status;
 /// Implements the SCSI INQUIRY command.
 pub mod inquiry;
+/// Implements the MMC READ TOC, GET CONFIGURATION and READ DISC INFORMATION
+/// commands.
+pub mod mmc;
 /// Implements the SCSI MODE SENSE command.
 pub mod mod_sense;
+/// Implements the SCSI PERSISTENT RESERVE IN / OUT commands.
+pub mod persistent_reserve;
+/// Implements the SCSI PRE-FETCH command.
+pub mod prefetch;
+/// Implements T10 protection information (DIF/DIX) tuples.
+pub mod protection;
 /// Implements the SCSI READ command.
 pub mod read;
 /// Implements the SCSI READ CAPACITY command.
 pub mod read_capacity;
+/// Implements the SCSI REASSIGN BLOCKS command.
+pub mod reassign_blocks;
 /// Implements the SCSI REPORT LUNS command.
 pub mod report_luns;
 /// Implements the SCSI REQUEST SENSE command.
 pub mod request_sense;
+/// Implements the legacy SCSI RESERVE / RELEASE (6/10) commands.
+pub mod reserve_release;
+/// Classifies CDBs by whether they are safe to re-issue after an ambiguous
+/// failure.
+pub mod retry_safety;
+/// Implements the SCSI SANITIZE command.
+pub mod sanitize;
+/// Implements the SCSI START STOP UNIT command.
+pub mod start_stop_unit;
+/// Implements the SCSI REPORT SUPPORTED OPERATION CODES command.
+pub mod supported_opcodes;
+/// Implements the SCSI SYNCHRONIZE CACHE command.
+pub mod sync_cache;
+/// Implements the SSC tape commands (READ / WRITE(6), SPACE, REWIND, WRITE
+/// FILEMARKS, READ POSITION, LOAD UNLOAD, ...).
+pub mod tape;
 /// Implements the SCSI TEST UNIT READY command.
 pub mod test_unit_ready;
+/// Implements the SCSI UNMAP command.
+pub mod unmap;
+/// Implements the SCSI VERIFY and WRITE AND VERIFY commands.
+pub mod verify;
 /// Implements the SCSI WRITE command.
 pub mod write;
+/// Implements the SCSI EXTENDED COPY (LID1) and RECEIVE COPY RESULTS
+/// commands.
+pub mod xcopy;
 /// Implements the SCSI XDWRITEREAD (bidirectional) command.
 pub mod xdwrite_read;
+/// Implements the ZBC REPORT ZONES and OPEN / CLOSE / FINISH ZONE and RESET
+/// WRITE POINTER commands.
+pub mod zbc;
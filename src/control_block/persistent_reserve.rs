@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::models::error::{ProtocolError, Result, ensure, format_err};
+
+/// PERSISTENT RESERVE IN operation code.
+pub const PERSISTENT_RESERVE_IN: u8 = 0x5E;
+
+/// PERSISTENT RESERVE OUT operation code.
+pub const PERSISTENT_RESERVE_OUT: u8 = 0x5F;
+
+/// Length of the basic PERSISTENT RESERVE OUT parameter list.
+pub const PR_OUT_PARAMETER_LEN: usize = 24;
+
+/// Bytes before the keys / reservation descriptor in PR IN data.
+pub const PR_IN_HEADER_LEN: usize = 8;
+
+/// SERVICE ACTION of PERSISTENT RESERVE IN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PrInAction {
+    /// Registered reservation keys.
+    ReadKeys = 0x00,
+    /// The current persistent reservation, if any.
+    ReadReservation = 0x01,
+    /// Optional features and reservation types the LUN supports.
+    ReportCapabilities = 0x02,
+    /// Registrations with their transport IDs.
+    ReadFullStatus = 0x03,
+}
+
+/// SERVICE ACTION of PERSISTENT RESERVE OUT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PrOutAction {
+    /// Register, change or (with a zero new key) remove the I_T nexus key.
+    Register = 0x00,
+    /// Create a reservation of the given type.
+    Reserve = 0x01,
+    /// Release the reservation held by the I_T nexus.
+    Release = 0x02,
+    /// Remove every registration and the reservation.
+    Clear = 0x03,
+    /// Remove the registrations of another key and take its reservation.
+    Preempt = 0x04,
+    /// PREEMPT and abort the tasks of the preempted I_T nexuses.
+    PreemptAndAbort = 0x05,
+    /// Register without checking the current key.
+    RegisterAndIgnoreExistingKey = 0x06,
+    /// Register another I_T nexus and move the reservation to it.
+    RegisterAndMove = 0x07,
+}
+
+/// Persistent reservation TYPE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PrType {
+    /// Only the holder may write.
+    WriteExclusive = 0x1,
+    /// Only the holder may read or write.
+    ExclusiveAccess = 0x3,
+    /// Only registered I_T nexuses may write.
+    WriteExclusiveRegistrantsOnly = 0x5,
+    /// Only registered I_T nexuses may read or write.
+    ExclusiveAccessRegistrantsOnly = 0x6,
+    /// Every registrant holds the reservation and may write.
+    WriteExclusiveAllRegistrants = 0x7,
+    /// Every registrant holds the reservation and may read or write.
+    ExclusiveAccessAllRegistrants = 0x8,
+}
+
+impl TryFrom<u8> for PrType {
+    type Error = ProtocolError;
+
+    fn try_from(v: u8) -> Result<Self> {
+        Ok(match v {
+            0x1 => Self::WriteExclusive,
+            0x3 => Self::ExclusiveAccess,
+            0x5 => Self::WriteExclusiveRegistrantsOnly,
+            0x6 => Self::ExclusiveAccessRegistrantsOnly,
+            0x7 => Self::WriteExclusiveAllRegistrants,
+            0x8 => Self::ExclusiveAccessAllRegistrants,
+            other => {
+                return Err(format_err!(
+                    "unknown persistent reservation type 0x{other:X}"
+                ));
+            },
+        })
+    }
+}
+
+/// Build a padded 16-byte **SCSI PERSISTENT RESERVE IN** CDB.
+///
+/// Layout (SPC):
+/// - byte 0     : OPERATION CODE = 0x5E
+/// - byte 1     : SERVICE ACTION (low 5 bits)
+/// - bytes 7..8 : ALLOCATION LENGTH (big-endian, 16-bit)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_pr_in(cdb: &mut [u8; 16], action: PrInAction, alloc_len: u16, control: u8) {
+    cdb.fill(0);
+    cdb[0] = PERSISTENT_RESERVE_IN;
+    cdb[1] = action as u8;
+    cdb[7..9].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Build a padded 16-byte **SCSI PERSISTENT RESERVE OUT** CDB. `pr_type`
+/// is only meaningful for RESERVE, RELEASE, PREEMPT and REGISTER AND MOVE;
+/// the scope is always LU_SCOPE.
+///
+/// Layout (SPC):
+/// - byte 0     : OPERATION CODE = 0x5F
+/// - byte 1     : SERVICE ACTION (low 5 bits)
+/// - byte 2     : SCOPE[7:4] | TYPE[3:0]
+/// - bytes 5..8 : PARAMETER LIST LENGTH (big-endian, 32-bit)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_pr_out(
+    cdb: &mut [u8; 16],
+    action: PrOutAction,
+    pr_type: Option<PrType>,
+    param_len: u32,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = PERSISTENT_RESERVE_OUT;
+    cdb[1] = action as u8;
+    cdb[2] = pr_type.map_or(0, |t| t as u8);
+    cdb[5..9].copy_from_slice(&param_len.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Basic PERSISTENT RESERVE OUT parameter list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrOutParameters {
+    /// RESERVATION KEY: the key registered for this I_T nexus (0 if none).
+    pub reservation_key: u64,
+    /// SERVICE ACTION RESERVATION KEY: the new key for REGISTER, the victim
+    /// key for PREEMPT.
+    pub service_action_key: u64,
+    /// SPEC_I_PT: the list names further I_T nexuses to register.
+    pub spec_i_pt: bool,
+    /// ALL_TG_PT: register through every target port.
+    pub all_tg_pt: bool,
+    /// APTPL: keep the registrations across power loss.
+    pub aptpl: bool,
+}
+
+impl PrOutParameters {
+    /// Encode the 24-byte parameter list.
+    pub fn to_bytes(&self) -> [u8; PR_OUT_PARAMETER_LEN] {
+        let mut buf = [0u8; PR_OUT_PARAMETER_LEN];
+        buf[0..8].copy_from_slice(&self.reservation_key.to_be_bytes());
+        buf[8..16].copy_from_slice(&self.service_action_key.to_be_bytes());
+        buf[20] = (u8::from(self.spec_i_pt) << 3)
+            | (u8::from(self.all_tg_pt) << 2)
+            | u8::from(self.aptpl);
+        buf
+    }
+}
+
+/// READ KEYS parameter data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrKeys {
+    /// PRGENERATION: bumped by every registration change.
+    pub generation: u32,
+    /// Registered reservation keys, one per registered I_T nexus.
+    pub keys: Vec<u64>,
+}
+
+/// A persistent reservation held on the LUN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrReservation {
+    /// Key of the holder.
+    pub key: u64,
+    /// SCOPE (0 = logical unit).
+    pub scope: u8,
+    /// TYPE of the reservation.
+    pub pr_type: PrType,
+}
+
+/// READ RESERVATION parameter data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrReservationStatus {
+    /// PRGENERATION.
+    pub generation: u32,
+    /// The reservation, `None` when the LUN is not reserved.
+    pub reservation: Option<PrReservation>,
+}
+
+/// REPORT CAPABILITIES parameter data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrCapabilities {
+    /// RLR_C: REPLACE LOST RESERVATION is supported.
+    pub rlr_c: bool,
+    /// CRH: RESERVE/RELEASE(6/10) succeed while a persistent reservation is
+    /// held by the same I_T nexus.
+    pub crh: bool,
+    /// SIP_C: SPEC_I_PT is supported.
+    pub sip_c: bool,
+    /// ATP_C: ALL_TG_PT is supported.
+    pub atp_c: bool,
+    /// PTPL_C: APTPL is supported.
+    pub ptpl_c: bool,
+    /// TMV: `type_mask` is valid.
+    pub tmv: bool,
+    /// ALLOW COMMANDS (3 bits).
+    pub allow_commands: u8,
+    /// PTPL_A: persistence through power loss is active.
+    pub ptpl_a: bool,
+    /// PERSISTENT RESERVATION TYPE MASK (bytes 4..5).
+    pub type_mask: u16,
+}
+
+impl PrCapabilities {
+    /// Whether the LUN supports reservations of `pr_type`. Assumes support
+    /// when the type mask is not valid.
+    pub fn supports(&self, pr_type: PrType) -> bool {
+        if !self.tmv {
+            return true;
+        }
+        let bit = match pr_type {
+            PrType::WriteExclusiveAllRegistrants => 15,
+            PrType::ExclusiveAccessRegistrantsOnly => 14,
+            PrType::WriteExclusiveRegistrantsOnly => 13,
+            PrType::ExclusiveAccess => 11,
+            PrType::WriteExclusive => 9,
+            PrType::ExclusiveAccessAllRegistrants => 0,
+        };
+        self.type_mask & (1 << bit) != 0
+    }
+}
+
+/// PRGENERATION and ADDITIONAL LENGTH of PR IN data.
+fn pr_in_header(buf: &[u8], what: &str) -> Result<(u32, usize)> {
+    ensure!(
+        buf.len() >= PR_IN_HEADER_LEN,
+        "{what} data too short: {} bytes",
+        buf.len()
+    );
+    let generation = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    let len = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize;
+    Ok((generation, len))
+}
+
+/// Decode READ KEYS parameter data. Keys cut off by a short allocation
+/// length are not returned.
+pub fn parse_pr_read_keys(buf: &[u8]) -> Result<PrKeys> {
+    let (generation, len) = pr_in_header(buf, "READ KEYS")?;
+    let end = PR_IN_HEADER_LEN.saturating_add(len).min(buf.len());
+    let keys = buf[PR_IN_HEADER_LEN..end]
+        .chunks_exact(8)
+        .map(|k| u64::from_be_bytes([k[0], k[1], k[2], k[3], k[4], k[5], k[6], k[7]]))
+        .collect();
+    Ok(PrKeys { generation, keys })
+}
+
+/// Decode READ RESERVATION parameter data.
+pub fn parse_pr_read_reservation(buf: &[u8]) -> Result<PrReservationStatus> {
+    let (generation, len) = pr_in_header(buf, "READ RESERVATION")?;
+    if len == 0 {
+        return Ok(PrReservationStatus {
+            generation,
+            reservation: None,
+        });
+    }
+    ensure!(
+        buf.len() >= PR_IN_HEADER_LEN + 16,
+        "READ RESERVATION descriptor truncated: {} bytes",
+        buf.len()
+    );
+    let d = &buf[PR_IN_HEADER_LEN..];
+    Ok(PrReservationStatus {
+        generation,
+        reservation: Some(PrReservation {
+            key: u64::from_be_bytes([d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]]),
+            scope: d[13] >> 4,
+            pr_type: PrType::try_from(d[13] & 0x0F)?,
+        }),
+    })
+}
+
+/// Decode REPORT CAPABILITIES parameter data (needs 8 bytes).
+pub fn parse_pr_report_capabilities(buf: &[u8]) -> Result<PrCapabilities> {
+    ensure!(
+        buf.len() >= 8,
+        "REPORT CAPABILITIES data too short: {} bytes",
+        buf.len()
+    );
+    Ok(PrCapabilities {
+        rlr_c: buf[2] & 0x80 != 0,
+        crh: buf[2] & 0x10 != 0,
+        sip_c: buf[2] & 0x08 != 0,
+        atp_c: buf[2] & 0x04 != 0,
+        ptpl_c: buf[2] & 0x01 != 0,
+        tmv: buf[3] & 0x80 != 0,
+        allow_commands: (buf[3] >> 4) & 0x07,
+        ptpl_a: buf[3] & 0x01 != 0,
+        type_mask: u16::from_be_bytes([buf[4], buf[5]]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pr_cdb_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_pr_in(&mut cdb, PrInAction::ReadReservation, 0x0102, 0);
+        assert_eq!(&cdb[..10], &[0x5E, 0x01, 0, 0, 0, 0, 0, 0x01, 0x02, 0]);
+
+        build_pr_out(
+            &mut cdb,
+            PrOutAction::Preempt,
+            Some(PrType::ExclusiveAccessRegistrantsOnly),
+            24,
+            0,
+        );
+        assert_eq!(&cdb[..10], &[0x5F, 0x04, 0x06, 0, 0, 0, 0, 0, 24, 0]);
+        assert_eq!(&cdb[10..], &[0; 6]);
+    }
+
+    #[test]
+    fn pr_out_parameter_list() {
+        let params = PrOutParameters {
+            reservation_key: 0x1122_3344_5566_7788,
+            service_action_key: 0xAA,
+            all_tg_pt: true,
+            aptpl: true,
+            ..Default::default()
+        };
+        let buf = params.to_bytes();
+        assert_eq!(&buf[0..8], &0x1122_3344_5566_7788u64.to_be_bytes());
+        assert_eq!(&buf[8..16], &0xAAu64.to_be_bytes());
+        assert_eq!(buf[20], 0x05);
+    }
+
+    #[test]
+    fn parses_read_keys_and_reservation() {
+        let mut keys = vec![0, 0, 0, 7, 0, 0, 0, 16];
+        keys.extend_from_slice(&1u64.to_be_bytes());
+        keys.extend_from_slice(&2u64.to_be_bytes());
+        let parsed = parse_pr_read_keys(&keys).expect("keys");
+        assert_eq!(parsed.generation, 7);
+        assert_eq!(parsed.keys, [1, 2]);
+        assert_eq!(parse_pr_read_keys(&keys[..20]).expect("short").keys, [1]);
+
+        let none = parse_pr_read_reservation(&[0, 0, 0, 3, 0, 0, 0, 0]).expect("none");
+        assert_eq!(none.reservation, None);
+
+        let mut held = vec![0, 0, 0, 3, 0, 0, 0, 16];
+        held.extend_from_slice(&0xBEEFu64.to_be_bytes());
+        held.extend_from_slice(&[0, 0, 0, 0, 0, 0x05, 0, 0]);
+        let reservation = parse_pr_read_reservation(&held)
+            .expect("held")
+            .reservation
+            .expect("reserved");
+        assert_eq!(reservation.key, 0xBEEF);
+        assert_eq!(reservation.pr_type, PrType::WriteExclusiveRegistrantsOnly);
+    }
+
+    #[test]
+    fn parses_capabilities() {
+        let caps = parse_pr_report_capabilities(&[0, 8, 0x8D, 0x81, 0x6A, 0x01, 0, 0])
+            .expect("caps");
+        assert!(caps.rlr_c && caps.sip_c && caps.atp_c && caps.ptpl_c && !caps.crh);
+        assert!(caps.tmv && caps.ptpl_a);
+        assert!(caps.supports(PrType::ExclusiveAccessRegistrantsOnly));
+        assert!(caps.supports(PrType::ExclusiveAccessAllRegistrants));
+        assert!(!caps.supports(PrType::WriteExclusiveAllRegistrants));
+    }
+}
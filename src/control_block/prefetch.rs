@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// Build a padded 16-byte **SCSI PRE-FETCH(10)** CDB.
+///
+/// Parameters:
+/// - `cdb`     : output buffer (will be zeroed; only 10 bytes are used)
+/// - `lba`     : first block to stage in the cache
+/// - `blocks`  : number of blocks to stage (**0 => from `lba` to the end of the
+///   medium**)
+/// - `immed`   : IMMED bit — return status before the blocks are read
+/// - `control` : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte 0      : OPERATION CODE = 0x34
+/// - byte 1      : IMMED (bit 1)
+/// - bytes 2..5  : LBA (big-endian, 32-bit)
+/// - byte 6      : GROUP NUMBER (low 5 bits)
+/// - bytes 7..8  : PREFETCH LENGTH (big-endian, 16-bit)
+/// - byte 9      : CONTROL
+#[inline]
+pub fn build_prefetch10(
+    cdb: &mut [u8; 16],
+    lba: u32,
+    blocks: u16,
+    immed: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = 0x34; // PRE-FETCH(10)
+    cdb[1] = if immed { 0x02 } else { 0x00 };
+    cdb[2..6].copy_from_slice(&lba.to_be_bytes());
+    cdb[7..9].copy_from_slice(&blocks.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Build a **SCSI PRE-FETCH(16)** CDB.
+///
+/// Parameters:
+/// - `cdb`     : output buffer (will be zeroed)
+/// - `lba`     : first block to stage in the cache
+/// - `blocks`  : number of blocks to stage (**0 => from `lba` to the end of the
+///   medium**)
+/// - `immed`   : IMMED bit — return status before the blocks are read
+/// - `control` : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte 0       : OPERATION CODE = 0x90
+/// - byte 1       : IMMED (bit 1)
+/// - bytes 2..9   : LBA (big-endian, 64-bit)
+/// - bytes 10..13 : PREFETCH LENGTH (big-endian, 32-bit)
+/// - byte 14      : GROUP NUMBER (low 5 bits)
+/// - byte 15      : CONTROL
+#[inline]
+pub fn build_prefetch16(
+    cdb: &mut [u8; 16],
+    lba: u64,
+    blocks: u32,
+    immed: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = 0x90; // PRE-FETCH(16)
+    cdb[1] = if immed { 0x02 } else { 0x00 };
+    cdb[2..10].copy_from_slice(&lba.to_be_bytes());
+    cdb[10..14].copy_from_slice(&blocks.to_be_bytes());
+    cdb[15] = control;
+}
+
+/// Build the smallest PRE-FETCH CDB covering `blocks` blocks at `lba`: the
+/// 10-byte form when both fit, the 16-byte form otherwise. `blocks == 0`
+/// stages to the end of the medium.
+#[inline]
+pub fn build_prefetch(cdb: &mut [u8; 16], lba: u64, blocks: u32, immed: bool) {
+    match (u32::try_from(lba), u16::try_from(blocks)) {
+        (Ok(lba), Ok(blocks)) => build_prefetch10(cdb, lba, blocks, immed, 0),
+        _ => build_prefetch16(cdb, lba, blocks, immed, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefetch10_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_prefetch10(&mut cdb, 0x0102_0304, 0x0506, true, 0x04);
+        assert_eq!(
+            &cdb[..10],
+            &[0x34, 0x02, 0x01, 0x02, 0x03, 0x04, 0x00, 0x05, 0x06, 0x04]
+        );
+        assert!(cdb[10..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn picks_the_smallest_cdb() {
+        let mut cdb = [0u8; 16];
+        build_prefetch(&mut cdb, 0x10, 0x20, false);
+        assert_eq!(&cdb[..10], &[0x34, 0, 0, 0, 0, 0x10, 0, 0, 0x20, 0]);
+
+        build_prefetch(&mut cdb, 0x1_0000_0000, 8, true);
+        assert_eq!(
+            cdb,
+            [
+                0x90, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x08, 0x00, 0x00
+            ]
+        );
+    }
+}
@@ -0,0 +1,442 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+//! T10 protection information (DIF/DIX).
+//!
+//! On a LUN formatted with protection information every protection interval
+//! (the logical block, or `block_len >> P_I_EXPONENT` bytes of it) carries an
+//! 8-byte tuple: a CRC-16 GUARD of the user data, an APPLICATION TAG and a
+//! REFERENCE TAG. With RDPROTECT / WRPROTECT set in the CDB the tuples travel
+//! with the data, each one right after its interval; [`insert_pi`] builds
+//! that stream for a write and [`strip_pi`] checks and removes the tuples of
+//! a read.
+
+use alloc::vec::Vec;
+
+use thiserror::Error;
+
+use crate::{
+    control_block::read_capacity::ReadCapacity16,
+    models::error::{Result, ensure},
+};
+
+/// Length of one protection information tuple.
+pub const PI_TUPLE_LEN: usize = 8;
+
+/// APPLICATION TAG that disables checking of a tuple (types 1 and 2; type 3
+/// also needs an all-ones REFERENCE TAG).
+pub const APP_TAG_ESCAPE: u16 = 0xFFFF;
+
+/// RDPROTECT / WRPROTECT value asking the target to check the tuples and
+/// transfer them with the data.
+pub const PROTECT_CHECK_ALL: u8 = 0b001;
+
+/// RDPROTECT[7:5] / WRPROTECT[7:5] bits of byte 1 of a READ / WRITE CDB,
+/// for the `flags` argument of the `build_read*` / `build_write*` helpers.
+#[inline]
+pub fn protect_flags(protect: u8) -> u8 {
+    (protect & 0x07) << 5
+}
+
+const fn crc_t10dif_table() -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8BB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC_T10DIF: [u16; 256] = crc_t10dif_table();
+
+/// CRC-16 T10-DIF (polynomial 0x8BB7, initial value 0, not reflected): the
+/// GUARD of a protection interval.
+pub fn crc16_t10dif(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &b| {
+        (crc << 8) ^ CRC_T10DIF[((crc >> 8) as u8 ^ b) as usize]
+    })
+}
+
+/// Protection type a LUN is formatted with (READ CAPACITY(16) P_TYPE + 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionType {
+    /// REFERENCE TAG = low 32 bits of the LBA, checked.
+    Type1,
+    /// REFERENCE TAG = expected initial value from the CDB, checked.
+    Type2,
+    /// REFERENCE TAG opaque to the target, not checked.
+    Type3,
+}
+
+impl ProtectionType {
+    /// Type from the 1-based value of
+    /// [`ReadCapacity16::protection_type`].
+    pub fn from_type(t: u8) -> Option<Self> {
+        match t {
+            1 => Some(Self::Type1),
+            2 => Some(Self::Type2),
+            3 => Some(Self::Type3),
+            _ => None,
+        }
+    }
+
+    /// Whether REFERENCE TAGs increase by one per interval and are checked.
+    #[inline]
+    pub fn checks_ref_tag(self) -> bool {
+        !matches!(self, Self::Type3)
+    }
+}
+
+/// Protection information format of a LUN, from READ CAPACITY(16).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtectionFormat {
+    /// Protection type.
+    pub ptype: ProtectionType,
+    /// P_I_EXPONENT: 2^n protection intervals per logical block.
+    pub p_i_exponent: u8,
+}
+
+impl ProtectionFormat {
+    /// Format of a LUN with PROT_EN set, `None` otherwise. Fails when
+    /// P_I_EXPONENT leaves less than one byte per protection interval.
+    pub fn from_read_capacity16(rc16: &ReadCapacity16) -> Result<Option<Self>> {
+        let Some(ptype) = rc16.protection_type().and_then(ProtectionType::from_type)
+        else {
+            return Ok(None);
+        };
+        ensure!(
+            rc16.block_len
+                .checked_shr(rc16.p_i_exponent as u32)
+                .is_some_and(|interval| interval != 0),
+            "P_I_EXPONENT {} is too large for {}-byte blocks",
+            rc16.p_i_exponent,
+            rc16.block_len
+        );
+        Ok(Some(Self {
+            ptype,
+            p_i_exponent: rc16.p_i_exponent,
+        }))
+    }
+
+    /// Parameters for a transfer starting at `lba` of a LUN with
+    /// `block_len` byte blocks.
+    #[inline]
+    pub fn params(&self, block_len: u32, lba: u64) -> PiParams {
+        PiParams::for_lba(self.ptype, block_len, self.p_i_exponent, lba)
+    }
+}
+
+/// One protection information tuple.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PiTuple {
+    /// CRC-16 T10-DIF of the interval's user data.
+    pub guard: u16,
+    /// APPLICATION TAG.
+    pub app_tag: u16,
+    /// REFERENCE TAG.
+    pub ref_tag: u32,
+}
+
+impl PiTuple {
+    /// Parse the 8 bytes following an interval.
+    pub fn parse(b: &[u8; PI_TUPLE_LEN]) -> Self {
+        Self {
+            guard: u16::from_be_bytes([b[0], b[1]]),
+            app_tag: u16::from_be_bytes([b[2], b[3]]),
+            ref_tag: u32::from_be_bytes([b[4], b[5], b[6], b[7]]),
+        }
+    }
+
+    /// Wire form of the tuple.
+    pub fn to_bytes(&self) -> [u8; PI_TUPLE_LEN] {
+        let mut b = [0u8; PI_TUPLE_LEN];
+        b[0..2].copy_from_slice(&self.guard.to_be_bytes());
+        b[2..4].copy_from_slice(&self.app_tag.to_be_bytes());
+        b[4..8].copy_from_slice(&self.ref_tag.to_be_bytes());
+        b
+    }
+}
+
+/// How the tuples of one command are generated and checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PiParams {
+    /// Protection type of the LUN.
+    pub ptype: ProtectionType,
+    /// User data bytes per tuple: the logical block length shifted right by
+    /// P_I_EXPONENT.
+    pub interval_len: u32,
+    /// REFERENCE TAG of the first interval; for types 1 and 2 the low 32
+    /// bits of the first LBA (times the intervals per block).
+    pub initial_ref_tag: u32,
+    /// APPLICATION TAG written with every interval.
+    pub app_tag: u16,
+    /// Compare the APPLICATION TAG of read tuples with `app_tag`.
+    pub check_app_tag: bool,
+}
+
+impl PiParams {
+    /// Parameters for a transfer starting at `lba` of a LUN with
+    /// `block_len` byte blocks and `2^p_i_exponent` intervals per block;
+    /// application tag 0, not checked.
+    pub fn for_lba(
+        ptype: ProtectionType,
+        block_len: u32,
+        p_i_exponent: u8,
+        lba: u64,
+    ) -> Self {
+        Self {
+            ptype,
+            interval_len: block_len >> p_i_exponent,
+            initial_ref_tag: (lba << p_i_exponent) as u32,
+            app_tag: 0,
+            check_app_tag: false,
+        }
+    }
+
+    /// Length on the wire of `user_len` bytes of user data.
+    #[inline]
+    pub fn protected_len(&self, user_len: usize) -> usize {
+        user_len + user_len / self.interval_len as usize * PI_TUPLE_LEN
+    }
+
+    fn ref_tag(&self, interval: usize) -> u32 {
+        match self.ptype {
+            ProtectionType::Type3 => u32::MAX,
+            _ => self.initial_ref_tag.wrapping_add(interval as u32),
+        }
+    }
+}
+
+/// Protection fields of a 32-byte READ / WRITE CDB.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PiTags {
+    /// EXPECTED INITIAL LOGICAL BLOCK REFERENCE TAG (type 2).
+    pub initial_ref_tag: u32,
+    /// (EXPECTED) LOGICAL BLOCK APPLICATION TAG.
+    pub app_tag: u16,
+    /// LOGICAL BLOCK APPLICATION TAG MASK: bits of `app_tag` the target
+    /// checks.
+    pub app_tag_mask: u16,
+}
+
+impl PiParams {
+    /// CDB fields matching these parameters.
+    pub fn tags(&self) -> PiTags {
+        PiTags {
+            initial_ref_tag: self.initial_ref_tag,
+            app_tag: self.app_tag,
+            app_tag_mask: if self.check_app_tag { 0xFFFF } else { 0 },
+        }
+    }
+}
+
+/// Field of a tuple that did not match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiField {
+    Guard,
+    AppTag,
+    RefTag,
+}
+
+/// A read tuple does not match its interval.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error(
+    "protection information mismatch in interval {interval}: {field:?} expected \
+     {expected:#x}, got {actual:#x}"
+)]
+pub struct PiError {
+    /// Index of the interval within the transfer.
+    pub interval: usize,
+    /// The field that differs.
+    pub field: PiField,
+    /// Value computed by the initiator.
+    pub expected: u32,
+    /// Value found in the tuple.
+    pub actual: u32,
+}
+
+/// Append a tuple after every interval of `data`, which must be a whole
+/// number of intervals.
+pub fn insert_pi(data: &[u8], p: &PiParams) -> Result<Vec<u8>> {
+    let step = p.interval_len as usize;
+    ensure!(step > 0, "protection interval length is 0");
+    ensure!(
+        data.len().is_multiple_of(step),
+        "{} bytes are not a whole number of {step}-byte protection intervals",
+        data.len()
+    );
+    let mut out = Vec::with_capacity(p.protected_len(data.len()));
+    for (i, chunk) in data.chunks_exact(step).enumerate() {
+        out.extend_from_slice(chunk);
+        let tuple = PiTuple {
+            guard: crc16_t10dif(chunk),
+            app_tag: p.app_tag,
+            ref_tag: p.ref_tag(i),
+        };
+        out.extend_from_slice(&tuple.to_bytes());
+    }
+    Ok(out)
+}
+
+/// Check the tuple after every interval of `data` and return the user data
+/// alone. Tuples with the escape APPLICATION TAG (and, for type 3, the
+/// escape REFERENCE TAG) are not checked.
+pub fn strip_pi(data: &[u8], p: &PiParams) -> Result<Vec<u8>> {
+    let step = p.interval_len as usize;
+    ensure!(step > 0, "protection interval length is 0");
+    let chunk_len = step + PI_TUPLE_LEN;
+    ensure!(
+        data.len().is_multiple_of(chunk_len),
+        "{} bytes are not a whole number of protected {step}-byte intervals",
+        data.len()
+    );
+    let mut out = Vec::with_capacity(data.len() / chunk_len * step);
+    for (i, chunk) in data.chunks_exact(chunk_len).enumerate() {
+        let (user, tuple) = chunk.split_at(step);
+        let mut raw = [0u8; PI_TUPLE_LEN];
+        raw.copy_from_slice(tuple);
+        let tuple = PiTuple::parse(&raw);
+        out.extend_from_slice(user);
+
+        if tuple.app_tag == APP_TAG_ESCAPE
+            && (p.ptype.checks_ref_tag() || tuple.ref_tag == u32::MAX)
+        {
+            continue;
+        }
+        let mismatch = |field, expected: u32, actual: u32| PiError {
+            interval: i,
+            field,
+            expected,
+            actual,
+        };
+        let guard = crc16_t10dif(user);
+        if tuple.guard != guard {
+            return Err(mismatch(PiField::Guard, guard as u32, tuple.guard as u32).into());
+        }
+        if p.check_app_tag && tuple.app_tag != p.app_tag {
+            return Err(mismatch(
+                PiField::AppTag,
+                p.app_tag as u32,
+                tuple.app_tag as u32,
+            )
+            .into());
+        }
+        if p.ptype.checks_ref_tag() && tuple.ref_tag != p.ref_tag(i) {
+            return Err(mismatch(PiField::RefTag, p.ref_tag(i), tuple.ref_tag).into());
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(ptype: ProtectionType) -> PiParams {
+        PiParams::for_lba(ptype, 512, 0, 0x1_0000_0010)
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc16_t10dif(b"123456789"), 0xD0DB);
+        assert_eq!(crc16_t10dif(&[0u8; 512]), 0);
+    }
+
+    #[test]
+    fn insert_then_strip_round_trips() {
+        let data: Vec<u8> = (0..1024u32).map(|i| i as u8).collect();
+        let p = params(ProtectionType::Type1);
+        let wire = insert_pi(&data, &p).expect("insert");
+        assert_eq!(wire.len(), p.protected_len(data.len()));
+        assert_eq!(wire.len(), 1040);
+
+        let first = PiTuple::parse(wire[512..520].try_into().expect("tuple"));
+        assert_eq!(first.guard, crc16_t10dif(&data[..512]));
+        assert_eq!(first.ref_tag, 0x10);
+        let second = PiTuple::parse(wire[1032..1040].try_into().expect("tuple"));
+        assert_eq!(second.ref_tag, 0x11);
+
+        assert_eq!(strip_pi(&wire, &p).expect("strip"), data);
+    }
+
+    #[test]
+    fn strip_reports_the_bad_field() {
+        let data = [0x5Au8; 1024];
+        let p = params(ProtectionType::Type1);
+        let mut wire = insert_pi(&data, &p).expect("insert");
+
+        wire[600] ^= 1;
+        let err = strip_pi(&wire, &p).expect_err("mismatch");
+        let err = err.downcast_ref::<PiError>().expect("PiError");
+        assert_eq!((err.interval, err.field), (1, PiField::Guard));
+        wire[600] ^= 1;
+
+        wire[519] ^= 1;
+        let err = strip_pi(&wire, &p).expect_err("mismatch");
+        let err = err.downcast_ref::<PiError>().expect("PiError");
+        assert_eq!((err.interval, err.field), (0, PiField::RefTag));
+    }
+
+    #[test]
+    fn type3_and_escape_skip_checks() {
+        let data = [1u8; 512];
+        let p = params(ProtectionType::Type3);
+        let mut wire = insert_pi(&data, &p).expect("insert");
+        assert_eq!(&wire[516..520], &[0xFF; 4]);
+        wire[519] = 0;
+        assert!(strip_pi(&wire, &p).is_ok());
+
+        let p = params(ProtectionType::Type1);
+        let mut wire = insert_pi(&data, &p).expect("insert");
+        wire[512..516].copy_from_slice(&[0, 0, 0xFF, 0xFF]);
+        assert!(strip_pi(&wire, &p).is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_protection_intervals() {
+        let mut rc16 = ReadCapacity16 {
+            max_lba: 0,
+            block_len: 512,
+            rc_basis: 0,
+            p_type: 0,
+            prot_en: true,
+            p_i_exponent: 3,
+            logical_blocks_per_physical_exponent: 0,
+            lbpme: false,
+            lbprz: false,
+            lowest_aligned_lba: 0,
+        };
+        let format = ProtectionFormat::from_read_capacity16(&rc16)
+            .expect("valid exponent")
+            .expect("PROT_EN");
+        assert_eq!(format.params(512, 0).interval_len, 64);
+
+        rc16.p_i_exponent = 9;
+        assert!(ProtectionFormat::from_read_capacity16(&rc16).is_ok());
+        rc16.p_i_exponent = 10;
+        assert!(ProtectionFormat::from_read_capacity16(&rc16).is_err());
+
+        rc16.prot_en = false;
+        assert_eq!(
+            ProtectionFormat::from_read_capacity16(&rc16).expect("no PI"),
+            None
+        );
+    }
+
+    #[test]
+    fn protect_bits() {
+        assert_eq!(protect_flags(PROTECT_CHECK_ALL), 0x20);
+        assert_eq!(protect_flags(0b101), 0xA0);
+    }
+}
@@ -1,6 +1,11 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
+use crate::control_block::{control::VARIABLE_LENGTH_CDB, protection::PiTags};
+
+/// SERVICE ACTION of READ(32).
+pub const READ_32: u16 = 0x0009;
+
 /// Build a padded 16-byte **SCSI READ(10)** CDB.
 ///
 /// Parameters:
@@ -57,3 +62,48 @@ pub fn build_read16(cdb: &mut [u8; 16], lba: u64, blocks: u32, flags: u8, contro
     // cdb[14] = group number (0 unless used)
     cdb[15] = control;
 }
+
+/// Build a **SCSI READ(32)** variable-length CDB, for LUNs formatted with
+/// type 2 protection. Bytes 16..32 travel in an Extended CDB AHS.
+///
+/// Parameters:
+/// - `cdb`     : output buffer (will be zeroed)
+/// - `lba`     : 64-bit Logical Block Address
+/// - `blocks`  : number of logical blocks to transfer
+/// - `flags`   : RDPROTECT[7:5] | DPO[4] | FUA[3] (other bits must be zero)
+/// - `tags`    : expected reference / application tags and the tag mask
+/// - `control` : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte  0      : OPERATION CODE = 0x7F
+/// - byte  1      : CONTROL
+/// - byte  6      : GROUP NUMBER (low 5 bits)
+/// - byte  7      : ADDITIONAL CDB LENGTH = 0x18
+/// - bytes 8..9   : SERVICE ACTION = 0x0009
+/// - byte  10     : flags (masked to RDPROTECT/DPO/FUA)
+/// - bytes 12..19 : LBA (big-endian, 64-bit)
+/// - bytes 20..23 : EXPECTED INITIAL LOGICAL BLOCK REFERENCE TAG
+/// - bytes 24..25 : EXPECTED LOGICAL BLOCK APPLICATION TAG
+/// - bytes 26..27 : LOGICAL BLOCK APPLICATION TAG MASK
+/// - bytes 28..31 : TRANSFER LENGTH (big-endian, 32-bit)
+#[inline]
+pub fn build_read32(
+    cdb: &mut [u8; 32],
+    lba: u64,
+    blocks: u32,
+    flags: u8,
+    tags: PiTags,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = VARIABLE_LENGTH_CDB;
+    cdb[1] = control;
+    cdb[7] = 0x18; // ADDITIONAL CDB LENGTH
+    cdb[8..10].copy_from_slice(&READ_32.to_be_bytes());
+    cdb[10] = flags & 0b1111_1000; // allow RDPROTECT[7:5], DPO[4], FUA[3]
+    cdb[12..20].copy_from_slice(&lba.to_be_bytes());
+    cdb[20..24].copy_from_slice(&tags.initial_ref_tag.to_be_bytes());
+    cdb[24..26].copy_from_slice(&tags.app_tag.to_be_bytes());
+    cdb[26..28].copy_from_slice(&tags.app_tag_mask.to_be_bytes());
+    cdb[28..32].copy_from_slice(&blocks.to_be_bytes());
+}
@@ -1,12 +1,13 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, anyhow};
 use zerocopy::{
     FromBytes, Immutable, KnownLayout,
     byteorder::{BigEndian, U32, U64},
 };
 
+use crate::models::error::{Result, format_err};
+
 /// Build a padded 16-byte **SCSI READ CAPACITY(10)** CDB (opcode 0x25).
 ///
 /// Parameters:
@@ -113,18 +114,112 @@ impl Rc16Raw {
     }
 }
 
+/// Length of the READ CAPACITY(16) parameter data decoded by
+/// [`parse_read_capacity16`] (the rest of the 32 bytes is reserved).
+pub const READ_CAPACITY16_MIN_LEN: usize = 16;
+
+/// Decoded READ CAPACITY(16) parameter data (SBC-4 § 5.20.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadCapacity16 {
+    /// Returned logical block address: the last LBA of the medium.
+    pub max_lba: u64,
+    /// Logical block length in bytes.
+    pub block_len: u32,
+    /// RC BASIS (byte 12, bits 5-4): 0 = capacity may differ from the
+    /// initial one, 1 = current capacity.
+    pub rc_basis: u8,
+    /// P_TYPE (byte 12, bits 3-1): protection type minus one, when
+    /// `prot_en` is set.
+    pub p_type: u8,
+    /// PROT_EN (byte 12, bit 0): protection information is enabled.
+    pub prot_en: bool,
+    /// P_I_EXPONENT (byte 13, bits 7-4): 2^n protection information
+    /// intervals per logical block.
+    pub p_i_exponent: u8,
+    /// LOGICAL BLOCKS PER PHYSICAL BLOCK EXPONENT (byte 13, bits 3-0).
+    pub logical_blocks_per_physical_exponent: u8,
+    /// LBPME (byte 14, bit 7): logical block provisioning management (thin
+    /// provisioning) is enabled.
+    pub lbpme: bool,
+    /// LBPRZ (byte 14, bit 6): unmapped blocks read back as zeros.
+    pub lbprz: bool,
+    /// LOWEST ALIGNED LOGICAL BLOCK ADDRESS (bytes 14-15, 14 bits): first
+    /// LBA that starts a physical block.
+    pub lowest_aligned_lba: u16,
+}
+
+impl ReadCapacity16 {
+    /// Number of logical blocks (max LBA + 1).
+    #[inline]
+    pub fn blocks(&self) -> u64 {
+        self.max_lba.saturating_add(1)
+    }
+
+    /// Capacity in bytes (blocks times the logical block length); `u128` so
+    /// a max LBA of `u64::MAX` cannot overflow.
+    #[inline]
+    pub fn total_bytes(&self) -> u128 {
+        (self.max_lba as u128 + 1) * self.block_len as u128
+    }
+
+    /// Protection type 1..=3 when protection information is enabled.
+    #[inline]
+    pub fn protection_type(&self) -> Option<u8> {
+        self.prot_en.then_some(self.p_type + 1)
+    }
+
+    /// Logical blocks per physical block.
+    #[inline]
+    pub fn logical_blocks_per_physical_block(&self) -> u32 {
+        1u32 << self.logical_blocks_per_physical_exponent.min(31)
+    }
+
+    /// Physical block length in bytes.
+    #[inline]
+    pub fn physical_block_len(&self) -> u64 {
+        self.block_len as u64 * self.logical_blocks_per_physical_block() as u64
+    }
+}
+
 /// Parse READ CAPACITY(10) parameter data (needs ≥ 8 bytes).
 #[inline]
 pub fn parse_read_capacity10_zerocopy(buf: &[u8]) -> Result<&Rc10Raw> {
-    let (raw, _rest) = Rc10Raw::ref_from_prefix(buf)
-        .map_err(|_| anyhow!("READ CAPACITY(10): need ≥ 8 bytes, got {}", buf.len()))?;
+    let (raw, _rest) = Rc10Raw::ref_from_prefix(buf).map_err(|_| {
+        format_err!("READ CAPACITY(10): need ≥ 8 bytes, got {}", buf.len())
+    })?;
     Ok(raw)
 }
 
 /// Parse READ CAPACITY(16) parameter data head (needs ≥ 12 bytes).
 #[inline]
 pub fn parse_read_capacity16_zerocopy(buf: &[u8]) -> Result<&Rc16Raw> {
-    let (raw, _rest) = Rc16Raw::ref_from_prefix(buf)
-        .map_err(|_| anyhow!("READ CAPACITY(16): need ≥ 12 bytes, got {}", buf.len()))?;
+    let (raw, _rest) = Rc16Raw::ref_from_prefix(buf).map_err(|_| {
+        format_err!("READ CAPACITY(16): need ≥ 12 bytes, got {}", buf.len())
+    })?;
     Ok(raw)
 }
+
+/// Decode READ CAPACITY(16) parameter data (needs ≥ 16 bytes; issue the
+/// command with an allocation length of 32).
+pub fn parse_read_capacity16(buf: &[u8]) -> Result<ReadCapacity16> {
+    if buf.len() < READ_CAPACITY16_MIN_LEN {
+        return Err(format_err!(
+            "READ CAPACITY(16): need ≥ {READ_CAPACITY16_MIN_LEN} bytes, got {}",
+            buf.len()
+        ));
+    }
+    let head = parse_read_capacity16_zerocopy(buf)?;
+    let (b12, b13, b14, b15) = (buf[12], buf[13], buf[14], buf[15]);
+    Ok(ReadCapacity16 {
+        max_lba: head.max_lba.get(),
+        block_len: head.block_len.get(),
+        rc_basis: (b12 >> 4) & 0x03,
+        p_type: (b12 >> 1) & 0x07,
+        prot_en: b12 & 0x01 != 0,
+        p_i_exponent: b13 >> 4,
+        logical_blocks_per_physical_exponent: b13 & 0x0F,
+        lbpme: b14 & 0x80 != 0,
+        lbprz: b14 & 0x40 != 0,
+        lowest_aligned_lba: u16::from_be_bytes([b14 & 0x3F, b15]),
+    })
+}
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::models::error::{Result, ensure};
+
+/// REASSIGN BLOCKS operation code.
+pub const REASSIGN_BLOCKS: u8 = 0x07;
+/// Length of the REASSIGN BLOCKS parameter list header.
+pub const REASSIGN_HEADER_LEN: usize = 4;
+
+/// Sense key MEDIUM ERROR: the target could not read or write a block.
+pub const SENSE_KEY_MEDIUM_ERROR: u8 = 0x03;
+
+/// Build a padded 16-byte **SCSI REASSIGN BLOCKS(10)** CDB.
+///
+/// Parameters:
+/// - `cdb`       : output buffer (will be zeroed; only 6 bytes are used)
+/// - `long_lba`  : LONGLBA bit — the defect list carries 8-byte LBAs
+/// - `long_list` : LONGLIST bit — the list header has a 4-byte length
+/// - `control`   : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte 0 : OPERATION CODE = 0x07
+/// - byte 1 : LONGLBA (bit 1), LONGLIST (bit 0)
+/// - byte 5 : CONTROL
+#[inline]
+pub fn build_reassign_blocks(
+    cdb: &mut [u8; 16],
+    long_lba: bool,
+    long_list: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = REASSIGN_BLOCKS;
+    cdb[1] = ((long_lba as u8) << 1) | long_list as u8;
+    cdb[5] = control;
+}
+
+/// Build the REASSIGN BLOCKS parameter list for `lbas`, with the same
+/// `long_lba` / `long_list` choice as the CDB.
+///
+/// Layout (SBC):
+/// - bytes 0..3 : DEFECT LIST LENGTH (bytes 2..3 only unless `long_list`)
+/// - then per LBA: 4 bytes, or 8 with `long_lba` (big-endian)
+pub fn fill_reassign_blocks_parameters(
+    lbas: &[u64],
+    long_lba: bool,
+    long_list: bool,
+) -> Result<Vec<u8>> {
+    let desc = if long_lba { 8 } else { 4 };
+    let list_len = lbas.len() * desc;
+    ensure!(
+        long_lba || lbas.iter().all(|&lba| lba <= u32::MAX as u64),
+        "LBA beyond 32 bits needs LONGLBA"
+    );
+    ensure!(
+        if long_list {
+            list_len <= u32::MAX as usize
+        } else {
+            list_len <= u16::MAX as usize
+        },
+        "defect list of {} LBAs is too long",
+        lbas.len()
+    );
+
+    let mut out = Vec::with_capacity(REASSIGN_HEADER_LEN + list_len);
+    if long_list {
+        out.extend_from_slice(&(list_len as u32).to_be_bytes());
+    } else {
+        out.extend_from_slice(&[0; 2]);
+        out.extend_from_slice(&(list_len as u16).to_be_bytes());
+    }
+    for &lba in lbas {
+        if long_lba {
+            out.extend_from_slice(&lba.to_be_bytes());
+        } else {
+            out.extend_from_slice(&(lba as u32).to_be_bytes());
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reassign_blocks_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_reassign_blocks(&mut cdb, true, false, 0x04);
+        assert_eq!(&cdb[..6], &[0x07, 0x02, 0, 0, 0, 0x04]);
+        assert!(cdb[6..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn short_and_long_parameter_lists() {
+        let short = fill_reassign_blocks_parameters(&[0x10, 0x0102_0304], false, false)
+            .expect("short list");
+        assert_eq!(short, [0, 0, 0, 8, 0, 0, 0, 0x10, 1, 2, 3, 4]);
+
+        let long = fill_reassign_blocks_parameters(&[0x1_0000_0000], true, true)
+            .expect("long list");
+        assert_eq!(long, [0, 0, 0, 8, 0, 0, 0, 1, 0, 0, 0, 0]);
+
+        assert!(fill_reassign_blocks_parameters(&[0x1_0000_0000], false, false).is_err());
+    }
+}
@@ -18,11 +18,22 @@
 //!   [0..3] = LUN LIST LENGTH (big-endian u32, multiple of 8)
 //!   [4..7] = reserved
 //!   [8..]  = LUN entries (8 bytes each)
-//!
-//! This module only builds the CDB.
+
+use alloc::vec::Vec;
+
+use crate::models::{
+    error::{Result, ensure},
+    identifiers::Lun,
+};
 
 pub const REPORT_LUNS: u8 = 0xA0;
 
+/// Length of the REPORT LUNS parameter data header.
+pub const REPORT_LUNS_HEADER_LEN: usize = 8;
+
+/// Smallest allocation length SPC allows for REPORT LUNS.
+pub const REPORT_LUNS_MIN_ALLOC: u32 = 16;
+
 /// Common SELECT REPORT values (byte 2).
 pub mod select_report {
     /// All logical unit addresses.
@@ -58,3 +69,31 @@ pub fn fill_report_luns(
 pub fn fill_report_luns_simple(cdb: &mut [u8; 16], allocation_len: u32) {
     fill_report_luns(cdb, select_report::ALL, allocation_len, 0x00)
 }
+
+/// LUN LIST LENGTH from the parameter data header: bytes of LUN entries the
+/// target has, which may be more than the allocation length let through.
+pub fn parse_lun_list_length(buf: &[u8]) -> Result<u32> {
+    ensure!(
+        buf.len() >= REPORT_LUNS_HEADER_LEN,
+        "REPORT LUNS data too short: {} bytes",
+        buf.len()
+    );
+    let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    ensure!(
+        len.is_multiple_of(8),
+        "LUN LIST LENGTH {len} is not a multiple of 8"
+    );
+    Ok(len)
+}
+
+/// Decode the LUN entries of REPORT LUNS parameter data (header included).
+/// Entries cut off by a short allocation length are not returned.
+pub fn parse_report_luns(buf: &[u8]) -> Result<Vec<Lun>> {
+    let list_len = parse_lun_list_length(buf)? as usize;
+    let entries = &buf[REPORT_LUNS_HEADER_LEN..];
+    let present = &entries[..list_len.min(entries.len())];
+    Ok(present
+        .chunks_exact(8)
+        .map(|e| Lun::from_bytes([e[0], e[1], e[2], e[3], e[4], e[5], e[6], e[7]]))
+        .collect())
+}
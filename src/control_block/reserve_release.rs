@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// RESERVE(6) operation code.
+pub const RESERVE6: u8 = 0x16;
+/// RELEASE(6) operation code.
+pub const RELEASE6: u8 = 0x17;
+/// RESERVE(10) operation code.
+pub const RESERVE10: u8 = 0x56;
+/// RELEASE(10) operation code.
+pub const RELEASE10: u8 = 0x57;
+
+/// Build a padded 16-byte **SCSI RESERVE(6)** CDB reserving the whole
+/// logical unit for this initiator.
+///
+/// Layout (SPC-2):
+/// - byte 0     : OPERATION CODE = 0x16
+/// - bytes 1..4 : obsolete / reserved (0)
+/// - byte 5     : CONTROL
+#[inline]
+pub fn build_reserve6(cdb: &mut [u8; 16], control: u8) {
+    cdb.fill(0);
+    cdb[0] = RESERVE6;
+    cdb[5] = control;
+}
+
+/// Build a padded 16-byte **SCSI RELEASE(6)** CDB. Releasing a unit that
+/// is not reserved by this initiator is not an error.
+///
+/// Layout (SPC-2):
+/// - byte 0     : OPERATION CODE = 0x17
+/// - bytes 1..4 : obsolete / reserved (0)
+/// - byte 5     : CONTROL
+#[inline]
+pub fn build_release6(cdb: &mut [u8; 16], control: u8) {
+    cdb.fill(0);
+    cdb[0] = RELEASE6;
+    cdb[5] = control;
+}
+
+/// Build a padded 16-byte **SCSI RESERVE(10)** CDB. Third-party
+/// reservations are not supported, so no parameter list is sent.
+///
+/// Layout (SPC-2):
+/// - byte 0     : OPERATION CODE = 0x56
+/// - byte 1     : 3RDPTY[4] | LONGID[1] (0)
+/// - byte 3     : THIRD-PARTY DEVICE ID (0)
+/// - bytes 7..8 : PARAMETER LIST LENGTH (0)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_reserve10(cdb: &mut [u8; 16], control: u8) {
+    cdb.fill(0);
+    cdb[0] = RESERVE10;
+    cdb[9] = control;
+}
+
+/// Build a padded 16-byte **SCSI RELEASE(10)** CDB.
+///
+/// Layout (SPC-2):
+/// - byte 0     : OPERATION CODE = 0x57
+/// - byte 1     : 3RDPTY[4] | LONGID[1] (0)
+/// - byte 3     : THIRD-PARTY DEVICE ID (0)
+/// - bytes 7..8 : PARAMETER LIST LENGTH (0)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_release10(cdb: &mut [u8; 16], control: u8) {
+    cdb.fill(0);
+    cdb[0] = RELEASE10;
+    cdb[9] = control;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_release_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_reserve6(&mut cdb, 0x04);
+        assert_eq!(&cdb[..6], &[0x16, 0, 0, 0, 0, 0x04]);
+        assert_eq!(&cdb[6..], &[0; 10]);
+        build_release6(&mut cdb, 0);
+        assert_eq!(cdb[0], 0x17);
+        build_reserve10(&mut cdb, 0x04);
+        assert_eq!(&cdb[..10], &[0x56, 0, 0, 0, 0, 0, 0, 0, 0, 0x04]);
+        build_release10(&mut cdb, 0);
+        assert_eq!(cdb[0], 0x57);
+    }
+}
@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use crate::control_block::{
+    control::VARIABLE_LENGTH_CDB, read::READ_32, write::WRITE_32,
+};
+
+/// Whether a command may be re-issued after an ambiguous failure, i.e. when
+/// the connection died before the initiator learned the command's outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetrySafety {
+    /// No side effects on the medium; re-issuing is always harmless
+    /// (READ, TEST UNIT READY, INQUIRY, ...).
+    Always,
+    /// Safe only while no data has been handed to the target: once Data-Out
+    /// (immediate or solicited) left the initiator the write may already be
+    /// applied (WRITE, WRITE SAME, UNMAP, ...).
+    BeforeDataAccepted,
+    /// Never re-issue automatically: a second execution can observe the
+    /// effect of the first one (COMPARE AND WRITE, PERSISTENT RESERVE OUT)
+    /// or the opcode is unknown.
+    Never,
+}
+
+impl RetrySafety {
+    /// Returns whether a retry is allowed given whether any data has already
+    /// been sent for the failed attempt.
+    #[inline]
+    pub fn allows_retry(self, data_sent: bool) -> bool {
+        match self {
+            RetrySafety::Always => true,
+            RetrySafety::BeforeDataAccepted => !data_sent,
+            RetrySafety::Never => false,
+        }
+    }
+}
+
+/// Classify a CDB by its operation code (byte 0).
+pub fn retry_safety(opcode: u8) -> RetrySafety {
+    match opcode {
+        // TEST UNIT READY, REQUEST SENSE, INQUIRY, MODE SENSE(6/10),
+        // READ CAPACITY(10), SERVICE ACTION IN(16) (READ CAPACITY(16)),
+        // REPORT LUNS, LOG SENSE, MAINTENANCE IN, PERSISTENT RESERVE IN,
+        // RECEIVE DIAGNOSTIC RESULTS, READ BUFFER(10), RECEIVE COPY RESULTS,
+        // ZBC IN (REPORT ZONES), READ TOC, GET CONFIGURATION, READ DISC
+        // INFORMATION
+        0x00 | 0x03 | 0x12 | 0x1A | 0x5A | 0x25 | 0x9E | 0xA0 | 0x4D | 0xA3 | 0x5E
+        | 0x1C | 0x3C | 0x84 | 0x95 | 0x43 | 0x46 | 0x51 => RetrySafety::Always,
+        // READ(6/10/12/16), VERIFY(10/16), SYNCHRONIZE CACHE(10/16),
+        // PRE-FETCH(10/16) (tape READ POSITION shares 0x34); tape READ(6)
+        // shares 0x08 but moves the medium, so the tape client overrides this
+        // per command
+        0x08 | 0x28 | 0xA8 | 0x88 | 0x2F | 0x8F | 0x35 | 0x91 | 0x34 | 0x90 => {
+            RetrySafety::Always
+        },
+        // START STOP UNIT: moves the unit to a state, not by a step
+        0x1B => RetrySafety::Always,
+        // WRITE(6/10/12/16), WRITE AND VERIFY(10/16), WRITE SAME(10/16),
+        // UNMAP, MODE SELECT(6/10), XDWRITEREAD(10)
+        0x0A | 0x2A | 0xAA | 0x8A | 0x2E | 0x8E | 0x41 | 0x93 | 0x42 | 0x15 | 0x55
+        | 0x53 => RetrySafety::BeforeDataAccepted,
+        // COMPARE AND WRITE, PERSISTENT RESERVE OUT, WRITE BUFFER (a repeated
+        // microcode chunk may land after activation), ATA PASS-THROUGH(12/16)
+        // (the ATA command inside is opaque), ZBC OUT (a repeated RESET WRITE
+        // POINTER may empty a zone written since), REASSIGN BLOCKS (each
+        // execution uses up another spare block), RESERVE / RELEASE(6/10)
+        // (the connection that reserved may be gone, and a replayed RELEASE
+        // may drop a reservation taken since) and everything unknown
+        _ => RetrySafety::Never,
+    }
+}
+
+/// Classify a CDB buffer as produced by the `build_*` helpers; variable
+/// length CDBs are classified by their SERVICE ACTION.
+#[inline]
+pub fn cdb_retry_safety(cdb: &[u8; 16]) -> RetrySafety {
+    if cdb[0] == VARIABLE_LENGTH_CDB {
+        return match u16::from_be_bytes([cdb[8], cdb[9]]) {
+            READ_32 => RetrySafety::Always,
+            WRITE_32 => RetrySafety::BeforeDataAccepted,
+            _ => RetrySafety::Never,
+        };
+    }
+    retry_safety(cdb[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_block::{
+        control::split_cdb32,
+        inquiry::fill_inquiry_standard,
+        protection::PiTags,
+        read::{build_read10, build_read32},
+        reserve_release::build_reserve10,
+        start_stop_unit::{PowerCondition, build_start_stop_unit},
+        test_unit_ready::build_test_unit_ready,
+        write::{build_write10, build_write32},
+    };
+
+    #[test]
+    fn classifies_builders() {
+        let mut cdb = [0u8; 16];
+
+        build_read10(&mut cdb, 0, 1, 0, 0);
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::Always);
+
+        build_test_unit_ready(&mut cdb, 0);
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::Always);
+
+        fill_inquiry_standard(&mut cdb, 96, 0);
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::Always);
+
+        build_write10(&mut cdb, 0, 1, 0, 0);
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::BeforeDataAccepted);
+
+        build_reserve10(&mut cdb, 0);
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::Never);
+
+        build_start_stop_unit(
+            &mut cdb,
+            PowerCondition::StartValid,
+            true,
+            false,
+            false,
+            false,
+            0,
+        );
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::Always);
+
+        // COMPARE AND WRITE
+        assert_eq!(retry_safety(0x89), RetrySafety::Never);
+    }
+
+    #[test]
+    fn classifies_variable_length_cdbs_by_service_action() {
+        let mut cdb32 = [0u8; 32];
+        build_read32(&mut cdb32, 0, 1, 0, PiTags::default(), 0);
+        let (cdb, _) = split_cdb32(&cdb32);
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::Always);
+
+        build_write32(&mut cdb32, 0, 1, 0, PiTags::default(), 0);
+        let (mut cdb, _) = split_cdb32(&cdb32);
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::BeforeDataAccepted);
+
+        cdb[9] = 0x0D; // ORWRITE(32)
+        assert_eq!(cdb_retry_safety(&cdb), RetrySafety::Never);
+    }
+
+    #[test]
+    fn writes_are_retriable_only_before_data() {
+        assert!(RetrySafety::BeforeDataAccepted.allows_retry(false));
+        assert!(!RetrySafety::BeforeDataAccepted.allows_retry(true));
+        assert!(RetrySafety::Always.allows_retry(true));
+        assert!(!RetrySafety::Never.allows_retry(false));
+    }
+}
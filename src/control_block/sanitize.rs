@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::models::error::{Result, bail, ensure};
+
+/// SANITIZE operation code.
+pub const SANITIZE: u8 = 0x48;
+
+/// Size of the OVERWRITE parameter list header.
+pub const OVERWRITE_HEADER_LEN: usize = 4;
+
+/// SERVICE ACTION of SANITIZE (SBC-4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SanitizeAction {
+    /// Overwrite every block with a pattern; needs a parameter list.
+    Overwrite = 0x01,
+    /// Erase the medium (e.g. a flash block erase).
+    BlockErase = 0x02,
+    /// Change the media encryption key, making old data unreadable.
+    CryptoErase = 0x03,
+    /// Leave the failure mode a failed sanitize put the LUN in.
+    ExitFailureMode = 0x1F,
+}
+
+/// Build a padded 16-byte **SCSI SANITIZE** CDB.
+///
+/// With `immed` the command completes as soon as it is validated; poll
+/// REQUEST SENSE for the progress until the LUN is ready again. `ause`
+/// allows EXIT FAILURE MODE to clear a failed sanitize; `znr` leaves zones
+/// of a zoned LUN unreset.
+///
+/// Layout (SBC-4):
+/// - byte 0     : OPERATION CODE = 0x48
+/// - byte 1     : IMMED[7] | ZNR[6] | AUSE[5] | SERVICE ACTION[4:0]
+/// - bytes 7..8 : PARAMETER LIST LENGTH (BE)
+/// - byte 9     : CONTROL
+#[inline]
+pub fn build_sanitize(
+    cdb: &mut [u8; 16],
+    action: SanitizeAction,
+    immed: bool,
+    ause: bool,
+    znr: bool,
+    param_len: u16,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = SANITIZE;
+    cdb[1] =
+        ((immed as u8) << 7) | ((znr as u8) << 6) | ((ause as u8) << 5) | action as u8;
+    cdb[7..9].copy_from_slice(&param_len.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Parameter list of the OVERWRITE service action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverwriteParameters {
+    /// INVERT: invert the pattern between passes.
+    pub invert: bool,
+    /// TEST field (0 outside of vendor tests).
+    pub test: u8,
+    /// OVERWRITE COUNT: number of passes, 1..=31.
+    pub count: u8,
+    /// INITIALIZATION PATTERN, at most one logical block.
+    pub pattern: Vec<u8>,
+}
+
+impl OverwriteParameters {
+    /// One pass writing `pattern`.
+    pub fn single_pass(pattern: impl Into<Vec<u8>>) -> Self {
+        Self {
+            invert: false,
+            test: 0,
+            count: 1,
+            pattern: pattern.into(),
+        }
+    }
+
+    /// Serialize the parameter list.
+    ///
+    /// Layout (SBC-4): byte 0 INVERT[7] | TEST[6:5] | OVERWRITE COUNT[4:0],
+    /// bytes 2..3 INITIALIZATION PATTERN LENGTH (BE), then the pattern.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        ensure!(
+            (1..=0x1F).contains(&self.count),
+            "overwrite count {} out of range 1..=31",
+            self.count
+        );
+        ensure!(
+            self.test <= 3,
+            "overwrite TEST field {} out of range",
+            self.test
+        );
+        let len = u16::try_from(self.pattern.len())
+            .ok()
+            .filter(|&len| len > 0);
+        let Some(len) = len else {
+            bail!(
+                "initialization pattern must be 1..=65535 bytes, got {}",
+                self.pattern.len()
+            );
+        };
+        let mut out = Vec::with_capacity(OVERWRITE_HEADER_LEN + self.pattern.len());
+        out.push(((self.invert as u8) << 7) | (self.test << 5) | self.count);
+        out.push(0);
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(&self.pattern);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_sanitize(
+            &mut cdb,
+            SanitizeAction::CryptoErase,
+            true,
+            false,
+            false,
+            0,
+            0,
+        );
+        assert_eq!(&cdb[..10], &[0x48, 0x83, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(cdb[10..].iter().all(|&b| b == 0));
+
+        build_sanitize(
+            &mut cdb,
+            SanitizeAction::Overwrite,
+            false,
+            true,
+            true,
+            0x0204,
+            0x04,
+        );
+        assert_eq!(&cdb[..10], &[0x48, 0x61, 0, 0, 0, 0, 0, 0x02, 0x04, 0x04]);
+    }
+
+    #[test]
+    fn overwrite_parameters() {
+        let mut p = OverwriteParameters::single_pass([0xA5, 0x5A]);
+        assert_eq!(p.to_bytes().expect("params"), [0x01, 0, 0, 2, 0xA5, 0x5A]);
+
+        p.invert = true;
+        p.count = 3;
+        assert_eq!(p.to_bytes().expect("params")[0], 0x83);
+
+        p.count = 0;
+        assert!(p.to_bytes().is_err());
+        assert!(
+            OverwriteParameters::single_pass(Vec::new())
+                .to_bytes()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// START STOP UNIT operation code.
+pub const START_STOP_UNIT: u8 = 0x1B;
+
+/// POWER CONDITION field of START STOP UNIT (SBC-3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PowerCondition {
+    /// Process the START and LOEJ bits.
+    StartValid = 0x0,
+    /// Move to the active power condition.
+    Active = 0x1,
+    /// Move to the idle power condition.
+    Idle = 0x2,
+    /// Move to the standby power condition.
+    Standby = 0x3,
+    /// Return control of the power condition to the logical unit.
+    LuControl = 0x7,
+    /// Force the idle condition timer to zero.
+    ForceIdle0 = 0xA,
+    /// Force the standby condition timer to zero.
+    ForceStandby0 = 0xB,
+}
+
+/// Build a padded 16-byte **SCSI START STOP UNIT** CDB.
+///
+/// `start` and `loej` are only honoured with
+/// [`PowerCondition::StartValid`]; with `loej` set, a stop ejects and a
+/// start loads the medium. `no_flush` lets the target skip writing cached
+/// data before it stops.
+///
+/// Layout (SBC-3):
+/// - byte 0 : OPERATION CODE = 0x1B
+/// - byte 1 : IMMED[0]
+/// - byte 3 : POWER CONDITION MODIFIER[3:0] (0)
+/// - byte 4 : POWER CONDITION[7:4] | NO_FLUSH[2] | LOEJ[1] | START[0]
+/// - byte 5 : CONTROL
+#[inline]
+pub fn build_start_stop_unit(
+    cdb: &mut [u8; 16],
+    power: PowerCondition,
+    start: bool,
+    loej: bool,
+    no_flush: bool,
+    immed: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = START_STOP_UNIT;
+    cdb[1] = immed as u8;
+    cdb[4] = ((power as u8) << 4)
+        | ((no_flush as u8) << 2)
+        | ((loej as u8) << 1)
+        | start as u8;
+    cdb[5] = control;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_stop_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_start_stop_unit(
+            &mut cdb,
+            PowerCondition::StartValid,
+            true,
+            false,
+            false,
+            true,
+            0x04,
+        );
+        assert_eq!(&cdb[..6], &[0x1B, 0x01, 0, 0, 0x01, 0x04]);
+        assert!(cdb[6..].iter().all(|&b| b == 0));
+
+        build_start_stop_unit(
+            &mut cdb,
+            PowerCondition::StartValid,
+            false,
+            true,
+            true,
+            false,
+            0,
+        );
+        assert_eq!(cdb[1], 0);
+        assert_eq!(cdb[4], 0x06);
+    }
+
+    #[test]
+    fn power_condition_in_high_nibble() {
+        let mut cdb = [0u8; 16];
+        build_start_stop_unit(
+            &mut cdb,
+            PowerCondition::ForceStandby0,
+            false,
+            false,
+            false,
+            false,
+            0,
+        );
+        assert_eq!(cdb[4], 0xB0);
+        build_start_stop_unit(
+            &mut cdb,
+            PowerCondition::Idle,
+            false,
+            false,
+            false,
+            false,
+            0,
+        );
+        assert_eq!(cdb[4], 0x20);
+    }
+}
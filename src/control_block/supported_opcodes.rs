@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::models::error::{Result, ensure};
+
+/// MAINTENANCE IN operation code.
+pub const MAINTENANCE_IN: u8 = 0xA3;
+/// REPORT SUPPORTED OPERATION CODES service action of MAINTENANCE IN.
+pub const REPORT_SUPPORTED_OPCODES_SA: u8 = 0x0C;
+
+/// Size of the all-commands header (COMMAND DATA LENGTH).
+pub const ALL_COMMANDS_HEADER_LEN: usize = 4;
+/// Size of a command descriptor without its timeouts descriptor.
+pub const COMMAND_DESCRIPTOR_LEN: usize = 8;
+/// Size of a command timeouts descriptor.
+pub const COMMAND_TIMEOUTS_LEN: usize = 12;
+/// Size of the one-command header before the CDB usage data.
+pub const ONE_COMMAND_HEADER_LEN: usize = 4;
+
+/// REPORTING OPTIONS of REPORT SUPPORTED OPERATION CODES (SPC-4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportingOptions {
+    /// Every supported command, in the all-commands format.
+    All,
+    /// One operation code without a service action.
+    OneCommand(u8),
+    /// One operation code and service action.
+    OneServiceAction(u8, u16),
+}
+
+/// Build a padded 16-byte **SCSI REPORT SUPPORTED OPERATION CODES** CDB.
+///
+/// `rctd` asks for a command timeouts descriptor with every command.
+///
+/// Layout (SPC-4):
+/// - byte 0     : OPERATION CODE = 0xA3 (MAINTENANCE IN)
+/// - byte 1     : SERVICE ACTION = 0x0C
+/// - byte 2     : RCTD[7] | REPORTING OPTIONS[2:0]
+/// - byte 3     : REQUESTED OPERATION CODE
+/// - bytes 4..5 : REQUESTED SERVICE ACTION (BE)
+/// - bytes 6..9 : ALLOCATION LENGTH (BE)
+/// - byte 11    : CONTROL
+#[inline]
+pub fn build_report_supported_opcodes(
+    cdb: &mut [u8; 16],
+    options: ReportingOptions,
+    rctd: bool,
+    alloc_len: u32,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = MAINTENANCE_IN;
+    cdb[1] = REPORT_SUPPORTED_OPCODES_SA;
+    let (reporting, opcode, sa) = match options {
+        ReportingOptions::All => (0x00, 0, 0),
+        ReportingOptions::OneCommand(op) => (0x01, op, 0),
+        ReportingOptions::OneServiceAction(op, sa) => (0x02, op, sa),
+    };
+    cdb[2] = ((rctd as u8) << 7) | reporting;
+    cdb[3] = opcode;
+    cdb[4..6].copy_from_slice(&sa.to_be_bytes());
+    cdb[6..10].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[11] = control;
+}
+
+/// Command timeouts descriptor, in seconds; 0 means not specified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandTimeouts {
+    /// NOMINAL COMMAND PROCESSING TIMEOUT.
+    pub nominal: u32,
+    /// RECOMMENDED COMMAND TIMEOUT.
+    pub recommended: u32,
+}
+
+fn parse_command_timeouts(buf: &[u8]) -> Result<CommandTimeouts> {
+    ensure!(
+        buf.len() >= COMMAND_TIMEOUTS_LEN,
+        "command timeouts descriptor truncated: {} bytes",
+        buf.len()
+    );
+    Ok(CommandTimeouts {
+        nominal: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+        recommended: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+    })
+}
+
+/// One command of the all-commands format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandDescriptor {
+    /// OPERATION CODE.
+    pub opcode: u8,
+    /// SERVICE ACTION, when the opcode has service actions (SERVACTV).
+    pub service_action: Option<u16>,
+    /// CDB LENGTH.
+    pub cdb_len: u16,
+    /// Timeouts, when requested with RCTD and reported (CTDP).
+    pub timeouts: Option<CommandTimeouts>,
+}
+
+/// Total size (header included) the target reports for all-commands data.
+pub fn parse_supported_opcodes_length(buf: &[u8]) -> Result<usize> {
+    ensure!(
+        buf.len() >= ALL_COMMANDS_HEADER_LEN,
+        "REPORT SUPPORTED OPERATION CODES header truncated: {} bytes",
+        buf.len()
+    );
+    Ok(ALL_COMMANDS_HEADER_LEN
+        + u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize)
+}
+
+/// Decode the all-commands format; descriptors cut off by the allocation
+/// length are dropped.
+///
+/// Layout (SPC-4): bytes 0..3 COMMAND DATA LENGTH, then per command byte 0
+/// OPERATION CODE, bytes 2..3 SERVICE ACTION, byte 5 CTDP[1] | SERVACTV[0],
+/// bytes 6..7 CDB LENGTH and, with CTDP, a 12-byte timeouts descriptor.
+pub fn parse_supported_opcodes_all(buf: &[u8]) -> Result<Vec<CommandDescriptor>> {
+    let end = parse_supported_opcodes_length(buf)?.min(buf.len());
+    let mut out = Vec::new();
+    let mut off = ALL_COMMANDS_HEADER_LEN;
+    while off + COMMAND_DESCRIPTOR_LEN <= end {
+        let d = &buf[off..off + COMMAND_DESCRIPTOR_LEN];
+        let ctdp = d[5] & 0x02 != 0;
+        off += COMMAND_DESCRIPTOR_LEN;
+        let timeouts = if ctdp {
+            if off + COMMAND_TIMEOUTS_LEN > end {
+                break;
+            }
+            let t = parse_command_timeouts(&buf[off..off + COMMAND_TIMEOUTS_LEN])?;
+            off += COMMAND_TIMEOUTS_LEN;
+            Some(t)
+        } else {
+            None
+        };
+        out.push(CommandDescriptor {
+            opcode: d[0],
+            service_action: (d[5] & 0x01 != 0).then(|| u16::from_be_bytes([d[2], d[3]])),
+            cdb_len: u16::from_be_bytes([d[6], d[7]]),
+            timeouts,
+        });
+    }
+    Ok(out)
+}
+
+/// SUPPORT field of the one-command format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandSupport {
+    /// The target cannot tell (yet).
+    NotAvailable,
+    /// The command is not supported.
+    NotSupported,
+    /// Supported as the standard defines it.
+    Supported,
+    /// Supported in a vendor-specific manner.
+    VendorSpecific,
+    /// A value SPC-4 does not define.
+    Reserved(u8),
+}
+
+impl CommandSupport {
+    /// Whether the command can be issued.
+    pub fn is_supported(self) -> bool {
+        matches!(self, Self::Supported | Self::VendorSpecific)
+    }
+}
+
+impl From<u8> for CommandSupport {
+    fn from(v: u8) -> Self {
+        match v & 0x07 {
+            0b000 => Self::NotAvailable,
+            0b001 => Self::NotSupported,
+            0b011 => Self::Supported,
+            0b101 => Self::VendorSpecific,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+/// One-command format: support of a single opcode / service action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OneCommandSupport {
+    /// SUPPORT.
+    pub support: CommandSupport,
+    /// CDB USAGE DATA: the CDB with every bit the target looks at set; its
+    /// length is the CDB size.
+    pub cdb_usage: Vec<u8>,
+    /// Timeouts, when requested with RCTD and reported (CTDP).
+    pub timeouts: Option<CommandTimeouts>,
+}
+
+/// Decode the one-command format.
+///
+/// Layout (SPC-4): byte 1 CTDP[7] | SUPPORT[2:0], bytes 2..3 CDB SIZE,
+/// then the CDB USAGE DATA and, with CTDP, a 12-byte timeouts descriptor.
+pub fn parse_supported_opcode_one(buf: &[u8]) -> Result<OneCommandSupport> {
+    ensure!(
+        buf.len() >= ONE_COMMAND_HEADER_LEN,
+        "one-command data truncated: {} bytes",
+        buf.len()
+    );
+    let cdb_size = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+    let usage_end = ONE_COMMAND_HEADER_LEN + cdb_size;
+    ensure!(
+        buf.len() >= usage_end,
+        "CDB usage data truncated: need {usage_end}, have {}",
+        buf.len()
+    );
+    let timeouts = if buf[1] & 0x80 != 0 {
+        Some(parse_command_timeouts(&buf[usage_end..])?)
+    } else {
+        None
+    };
+    Ok(OneCommandSupport {
+        support: buf[1].into(),
+        cdb_usage: buf[ONE_COMMAND_HEADER_LEN..usage_end].to_vec(),
+        timeouts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cdb_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_report_supported_opcodes(&mut cdb, ReportingOptions::All, true, 0x2000, 0);
+        assert_eq!(
+            &cdb[..12],
+            &[0xA3, 0x0C, 0x80, 0, 0, 0, 0, 0, 0x20, 0, 0, 0]
+        );
+        assert!(cdb[12..].iter().all(|&b| b == 0));
+
+        build_report_supported_opcodes(
+            &mut cdb,
+            ReportingOptions::OneServiceAction(0x9E, 0x10),
+            false,
+            64,
+            0x04,
+        );
+        assert_eq!(
+            &cdb[..12],
+            &[0xA3, 0x0C, 0x02, 0x9E, 0, 0x10, 0, 0, 0, 64, 0, 0x04]
+        );
+    }
+
+    #[test]
+    fn parses_all_commands() {
+        let mut buf = vec![0, 0, 0, 0];
+        // READ(10), no timeouts
+        buf.extend_from_slice(&[0x28, 0, 0, 0, 0, 0x00, 0, 10]);
+        // READ CAPACITY(16) with a timeouts descriptor
+        buf.extend_from_slice(&[0x9E, 0, 0, 0x10, 0, 0x03, 0, 16]);
+        buf.extend_from_slice(&[0, 0x0A, 0, 0, 0, 0, 0, 5, 0, 0, 0, 30]);
+        // cut-off descriptor
+        buf.extend_from_slice(&[0x2A, 0, 0, 0]);
+        let len = (buf.len() - 4) as u32;
+        buf[..4].copy_from_slice(&len.to_be_bytes());
+
+        let cmds = parse_supported_opcodes_all(&buf).expect("commands");
+        assert_eq!(cmds.len(), 2);
+        assert_eq!(cmds[0].opcode, 0x28);
+        assert_eq!(cmds[0].service_action, None);
+        assert_eq!(cmds[0].cdb_len, 10);
+        assert_eq!(cmds[1].service_action, Some(0x10));
+        assert_eq!(
+            cmds[1].timeouts,
+            Some(CommandTimeouts {
+                nominal: 5,
+                recommended: 30
+            })
+        );
+        assert_eq!(
+            parse_supported_opcodes_length(&buf).expect("len"),
+            buf.len()
+        );
+    }
+
+    #[test]
+    fn parses_one_command() {
+        let buf = [0, 0x03, 0, 6, 0x12, 0x01, 0xFF, 0xFF, 0xFF, 0x07];
+        let one = parse_supported_opcode_one(&buf).expect("one");
+        assert!(one.support.is_supported());
+        assert_eq!(one.cdb_usage, [0x12, 0x01, 0xFF, 0xFF, 0xFF, 0x07]);
+        assert_eq!(one.timeouts, None);
+
+        let unsupported = parse_supported_opcode_one(&[0, 0x01, 0, 0]).expect("one");
+        assert_eq!(unsupported.support, CommandSupport::NotSupported);
+        assert!(parse_supported_opcode_one(&[0, 0x83, 0, 0]).is_err());
+        assert!(parse_supported_opcode_one(&[0, 0x03, 0, 6, 0x12]).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// Build a padded 16-byte **SCSI SYNCHRONIZE CACHE(10)** CDB.
+///
+/// Parameters:
+/// - `cdb`     : output buffer (will be zeroed; only 10 bytes are used)
+/// - `lba`     : first block to flush
+/// - `blocks`  : number of blocks to flush (**0 => from `lba` to the end of the
+///   medium**)
+/// - `immed`   : IMMED bit — return status before the flush completes
+/// - `control` : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte 0      : OPERATION CODE = 0x35
+/// - byte 1      : IMMED (bit 1)
+/// - bytes 2..5  : LBA (big-endian, 32-bit)
+/// - byte 6      : GROUP NUMBER (low 5 bits)
+/// - bytes 7..8  : NUMBER OF BLOCKS (big-endian, 16-bit)
+/// - byte 9      : CONTROL
+#[inline]
+pub fn build_sync_cache10(
+    cdb: &mut [u8; 16],
+    lba: u32,
+    blocks: u16,
+    immed: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = 0x35; // SYNCHRONIZE CACHE(10)
+    cdb[1] = if immed { 0x02 } else { 0x00 };
+    cdb[2..6].copy_from_slice(&lba.to_be_bytes());
+    cdb[7..9].copy_from_slice(&blocks.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Build a **SCSI SYNCHRONIZE CACHE(16)** CDB.
+///
+/// Parameters:
+/// - `cdb`     : output buffer (will be zeroed)
+/// - `lba`     : first block to flush
+/// - `blocks`  : number of blocks to flush (**0 => from `lba` to the end of the
+///   medium**)
+/// - `immed`   : IMMED bit — return status before the flush completes
+/// - `control` : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte 0       : OPERATION CODE = 0x91
+/// - byte 1       : IMMED (bit 1)
+/// - bytes 2..9   : LBA (big-endian, 64-bit)
+/// - bytes 10..13 : NUMBER OF BLOCKS (big-endian, 32-bit)
+/// - byte 14      : GROUP NUMBER (low 5 bits)
+/// - byte 15      : CONTROL
+#[inline]
+pub fn build_sync_cache16(
+    cdb: &mut [u8; 16],
+    lba: u64,
+    blocks: u32,
+    immed: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = 0x91; // SYNCHRONIZE CACHE(16)
+    cdb[1] = if immed { 0x02 } else { 0x00 };
+    cdb[2..10].copy_from_slice(&lba.to_be_bytes());
+    cdb[10..14].copy_from_slice(&blocks.to_be_bytes());
+    cdb[15] = control;
+}
+
+/// Build the smallest SYNCHRONIZE CACHE CDB covering `blocks` blocks at
+/// `lba`: the 10-byte form when both fit, the 16-byte form otherwise.
+/// `blocks == 0` flushes to the end of the medium.
+#[inline]
+pub fn build_sync_cache(cdb: &mut [u8; 16], lba: u64, blocks: u32, immed: bool) {
+    match (u32::try_from(lba), u16::try_from(blocks)) {
+        (Ok(lba), Ok(blocks)) => build_sync_cache10(cdb, lba, blocks, immed, 0),
+        _ => build_sync_cache16(cdb, lba, blocks, immed, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_cache16_layout() {
+        let mut cdb = [0xFFu8; 16];
+        build_sync_cache16(&mut cdb, 0x0102_0304_0506_0708, 0x0A0B_0C0D, true, 0x04);
+        assert_eq!(
+            cdb,
+            [
+                0x91, 0x02, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x0A, 0x0B,
+                0x0C, 0x0D, 0x00, 0x04
+            ]
+        );
+    }
+
+    #[test]
+    fn picks_the_smallest_cdb() {
+        let mut cdb = [0u8; 16];
+        build_sync_cache(&mut cdb, 0x10, 0x20, false);
+        assert_eq!(&cdb[..10], &[0x35, 0, 0, 0, 0, 0x10, 0, 0, 0x20, 0]);
+
+        build_sync_cache(&mut cdb, 0x1_0000_0000, 8, false);
+        assert_eq!(cdb[0], 0x91);
+        build_sync_cache(&mut cdb, 0, 0x1_0000, false);
+        assert_eq!(cdb[0], 0x91);
+        assert_eq!(&cdb[10..14], &[0, 1, 0, 0]);
+    }
+}
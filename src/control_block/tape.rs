@@ -0,0 +1,464 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+//! Sequential-access (SSC) commands for tape drives.
+//!
+//! READ(6) and WRITE(6) share their opcodes with the block commands of the
+//! same name but carry a FIXED bit and a transfer length instead of an LBA:
+//! in fixed-block mode the length counts blocks of the current block
+//! length, in variable-block mode it is the byte size of one record.
+
+use crate::models::error::{Result, ensure, format_err};
+
+/// REWIND operation code.
+pub const REWIND: u8 = 0x01;
+/// READ BLOCK LIMITS operation code.
+pub const READ_BLOCK_LIMITS: u8 = 0x05;
+/// READ(6) operation code.
+pub const TAPE_READ_6: u8 = 0x08;
+/// WRITE(6) operation code.
+pub const TAPE_WRITE_6: u8 = 0x0A;
+/// WRITE FILEMARKS(6) operation code.
+pub const WRITE_FILEMARKS_6: u8 = 0x10;
+/// SPACE(6) operation code.
+pub const SPACE_6: u8 = 0x11;
+/// MODE SELECT(6) operation code.
+pub const MODE_SELECT_6: u8 = 0x15;
+/// LOAD UNLOAD operation code.
+pub const LOAD_UNLOAD: u8 = 0x1B;
+/// READ POSITION operation code.
+pub const READ_POSITION: u8 = 0x34;
+
+/// Largest TRANSFER LENGTH / COUNT of the 3-byte fields.
+pub const TAPE_LENGTH_MAX: u32 = 0x00FF_FFFF;
+/// Size of the READ BLOCK LIMITS data.
+pub const BLOCK_LIMITS_LEN: usize = 6;
+/// Size of the short form of READ POSITION data.
+pub const POSITION_SHORT_LEN: usize = 20;
+/// Size of the long form of READ POSITION data.
+pub const POSITION_LONG_LEN: usize = 32;
+/// Size of the MODE SELECT(6) header plus one block descriptor.
+pub const MODE_BLOCK_DESCRIPTOR_PARAMS_LEN: usize = 12;
+
+/// Peripheral device type of a sequential-access device.
+pub const DEVICE_TYPE_SEQUENTIAL: u8 = 0x01;
+
+/// Build a padded 16-byte **SSC READ(6)** CDB.
+///
+/// `sili` suppresses the incorrect-length CHECK CONDITION when a variable
+/// record is shorter than `len`.
+///
+/// Layout (SSC-3):
+/// - byte 0     : OPERATION CODE = 0x08
+/// - byte 1     : SILI[1] | FIXED[0]
+/// - bytes 2..4 : TRANSFER LENGTH (BE, 24-bit)
+/// - byte 5     : CONTROL
+#[inline]
+pub fn build_tape_read6(
+    cdb: &mut [u8; 16],
+    fixed: bool,
+    sili: bool,
+    len: u32,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = TAPE_READ_6;
+    cdb[1] = ((sili as u8) << 1) | fixed as u8;
+    cdb[2..5].copy_from_slice(&len.to_be_bytes()[1..]);
+    cdb[5] = control;
+}
+
+/// Build a padded 16-byte **SSC WRITE(6)** CDB.
+///
+/// Layout (SSC-3):
+/// - byte 0     : OPERATION CODE = 0x0A
+/// - byte 1     : FIXED[0]
+/// - bytes 2..4 : TRANSFER LENGTH (BE, 24-bit)
+/// - byte 5     : CONTROL
+#[inline]
+pub fn build_tape_write6(cdb: &mut [u8; 16], fixed: bool, len: u32, control: u8) {
+    cdb.fill(0);
+    cdb[0] = TAPE_WRITE_6;
+    cdb[1] = fixed as u8;
+    cdb[2..5].copy_from_slice(&len.to_be_bytes()[1..]);
+    cdb[5] = control;
+}
+
+/// CODE field of SPACE(6): what is counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SpaceCode {
+    /// Logical blocks (records).
+    Blocks = 0x0,
+    /// Filemarks.
+    Filemarks = 0x1,
+    /// Runs of COUNT consecutive filemarks.
+    SequentialFilemarks = 0x2,
+    /// End of data; COUNT is ignored.
+    EndOfData = 0x3,
+}
+
+/// Build a padded 16-byte **SSC SPACE(6)** CDB. A negative `count` spaces
+/// towards the beginning of the partition.
+///
+/// Layout (SSC-3):
+/// - byte 0     : OPERATION CODE = 0x11
+/// - byte 1     : CODE[3:0]
+/// - bytes 2..4 : COUNT (BE, 24-bit two's complement)
+/// - byte 5     : CONTROL
+pub fn build_space6(
+    cdb: &mut [u8; 16],
+    code: SpaceCode,
+    count: i32,
+    control: u8,
+) -> Result<()> {
+    ensure!(
+        (-0x80_0000..0x80_0000).contains(&count),
+        "SPACE count {count} does not fit in 24 bits"
+    );
+    cdb.fill(0);
+    cdb[0] = SPACE_6;
+    cdb[1] = code as u8;
+    cdb[2..5].copy_from_slice(&count.to_be_bytes()[1..]);
+    cdb[5] = control;
+    Ok(())
+}
+
+/// Build a padded 16-byte **SSC REWIND** CDB.
+///
+/// Layout (SSC-3): byte 0 OPERATION CODE = 0x01, byte 1 IMMED[0], byte 5
+/// CONTROL.
+#[inline]
+pub fn build_rewind(cdb: &mut [u8; 16], immed: bool, control: u8) {
+    cdb.fill(0);
+    cdb[0] = REWIND;
+    cdb[1] = immed as u8;
+    cdb[5] = control;
+}
+
+/// Build a padded 16-byte **SSC WRITE FILEMARKS(6)** CDB.
+///
+/// Layout (SSC-3):
+/// - byte 0     : OPERATION CODE = 0x10
+/// - byte 1     : WSMK[1] (0) | IMMED[0]
+/// - bytes 2..4 : FILEMARK COUNT (BE, 24-bit)
+/// - byte 5     : CONTROL
+#[inline]
+pub fn build_write_filemarks6(cdb: &mut [u8; 16], count: u32, immed: bool, control: u8) {
+    cdb.fill(0);
+    cdb[0] = WRITE_FILEMARKS_6;
+    cdb[1] = immed as u8;
+    cdb[2..5].copy_from_slice(&count.to_be_bytes()[1..]);
+    cdb[5] = control;
+}
+
+/// Build a padded 16-byte **SSC LOAD UNLOAD** CDB.
+///
+/// `load` threads the medium and positions it at the beginning; without it
+/// the medium is rewound and unloaded (ejected unless `hold`).
+///
+/// Layout (SSC-3):
+/// - byte 0 : OPERATION CODE = 0x1B
+/// - byte 1 : IMMED[0]
+/// - byte 4 : HOLD[3] | EOT[2] (0) | RETEN[1] (0) | LOAD[0]
+/// - byte 5 : CONTROL
+#[inline]
+pub fn build_load_unload(
+    cdb: &mut [u8; 16],
+    load: bool,
+    hold: bool,
+    immed: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = LOAD_UNLOAD;
+    cdb[1] = immed as u8;
+    cdb[4] = ((hold as u8) << 3) | load as u8;
+    cdb[5] = control;
+}
+
+/// Build a padded 16-byte **SSC READ BLOCK LIMITS** CDB.
+///
+/// Layout (SSC-3): byte 0 OPERATION CODE = 0x05, byte 5 CONTROL.
+#[inline]
+pub fn build_read_block_limits(cdb: &mut [u8; 16], control: u8) {
+    cdb.fill(0);
+    cdb[0] = READ_BLOCK_LIMITS;
+    cdb[5] = control;
+}
+
+/// SERVICE ACTION of READ POSITION: the form of the returned data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PositionForm {
+    /// 20 bytes: partition and 32-bit block number.
+    Short = 0x00,
+    /// 32 bytes: partition, 64-bit block and file numbers.
+    Long = 0x06,
+}
+
+impl PositionForm {
+    /// Size of the data of this form.
+    #[inline]
+    pub fn data_len(self) -> usize {
+        match self {
+            PositionForm::Short => POSITION_SHORT_LEN,
+            PositionForm::Long => POSITION_LONG_LEN,
+        }
+    }
+}
+
+/// Build a padded 16-byte **SSC READ POSITION** CDB.
+///
+/// Layout (SSC-3):
+/// - byte 0 : OPERATION CODE = 0x34
+/// - byte 1 : SERVICE ACTION[4:0]
+/// - byte 9 : CONTROL
+#[inline]
+pub fn build_read_position(cdb: &mut [u8; 16], form: PositionForm, control: u8) {
+    cdb.fill(0);
+    cdb[0] = READ_POSITION;
+    cdb[1] = form as u8;
+    cdb[9] = control;
+}
+
+/// Build a padded 16-byte **MODE SELECT(6)** CDB with PF set.
+///
+/// Layout (SPC-4):
+/// - byte 0 : OPERATION CODE = 0x15
+/// - byte 1 : PF[4] | SP[0]
+/// - byte 4 : PARAMETER LIST LENGTH
+/// - byte 5 : CONTROL
+#[inline]
+pub fn build_mode_select6(cdb: &mut [u8; 16], save: bool, param_len: u8, control: u8) {
+    cdb.fill(0);
+    cdb[0] = MODE_SELECT_6;
+    cdb[1] = 0x10 | save as u8;
+    cdb[4] = param_len;
+    cdb[5] = control;
+}
+
+/// READ BLOCK LIMITS data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapeBlockLimits {
+    /// GRANULARITY: block lengths should be multiples of `1 << granularity`.
+    pub granularity: u8,
+    /// MAXIMUM BLOCK LENGTH LIMIT in bytes (0 = not specified).
+    pub max_block_len: u32,
+    /// MINIMUM BLOCK LENGTH LIMIT in bytes.
+    pub min_block_len: u16,
+}
+
+impl TapeBlockLimits {
+    /// Whether the drive only supports one block length (fixed-block only).
+    #[inline]
+    pub fn is_fixed_only(&self) -> bool {
+        self.max_block_len != 0 && self.max_block_len == self.min_block_len as u32
+    }
+}
+
+/// Decode READ BLOCK LIMITS data.
+///
+/// Layout (SSC-3): byte 0 GRANULARITY[4:0], bytes 1..3 MAXIMUM BLOCK LENGTH
+/// LIMIT (BE), bytes 4..5 MINIMUM BLOCK LENGTH LIMIT (BE).
+pub fn parse_read_block_limits(buf: &[u8]) -> Result<TapeBlockLimits> {
+    ensure!(
+        buf.len() >= BLOCK_LIMITS_LEN,
+        "READ BLOCK LIMITS data truncated: {} bytes",
+        buf.len()
+    );
+    Ok(TapeBlockLimits {
+        granularity: buf[0] & 0x1F,
+        max_block_len: u32::from_be_bytes([0, buf[1], buf[2], buf[3]]),
+        min_block_len: u16::from_be_bytes([buf[4], buf[5]]),
+    })
+}
+
+/// Position of the medium from READ POSITION.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapePosition {
+    /// BOP: at the beginning of the partition.
+    pub beginning: bool,
+    /// EOP: between early warning and the end of the partition.
+    pub end: bool,
+    /// Partition number.
+    pub partition: u32,
+    /// Logical object (block) number; `None` when the drive does not know.
+    pub block: Option<u64>,
+    /// Logical file number (long form only); `None` when unknown.
+    pub file: Option<u64>,
+}
+
+/// Decode the short form of READ POSITION data.
+///
+/// Layout (SSC-3): byte 0 BOP[7] | EOP[6] | LOCU[5] | BYCU[4] | LOLU[2] |
+/// PERR[1], byte 1 PARTITION NUMBER, bytes 4..7 FIRST LOGICAL OBJECT
+/// LOCATION (BE).
+pub fn parse_read_position_short(buf: &[u8]) -> Result<TapePosition> {
+    ensure!(
+        buf.len() >= POSITION_SHORT_LEN,
+        "READ POSITION short form truncated: {} bytes",
+        buf.len()
+    );
+    let unknown = buf[0] & 0x04 != 0;
+    Ok(TapePosition {
+        beginning: buf[0] & 0x80 != 0,
+        end: buf[0] & 0x40 != 0,
+        partition: buf[1] as u32,
+        block: (!unknown)
+            .then(|| u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as u64),
+        file: None,
+    })
+}
+
+/// Decode the long form of READ POSITION data.
+///
+/// Layout (SSC-3): byte 0 BOP[7] | EOP[6] | MPU[3] | LONU[2], bytes 4..7
+/// PARTITION NUMBER, bytes 8..15 LOGICAL OBJECT NUMBER, bytes 16..23
+/// LOGICAL FILE IDENTIFIER (all BE).
+pub fn parse_read_position_long(buf: &[u8]) -> Result<TapePosition> {
+    ensure!(
+        buf.len() >= POSITION_LONG_LEN,
+        "READ POSITION long form truncated: {} bytes",
+        buf.len()
+    );
+    let unknown = buf[0] & 0x0C != 0;
+    let be_u64 =
+        |b: &[u8]| u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
+    Ok(TapePosition {
+        beginning: buf[0] & 0x80 != 0,
+        end: buf[0] & 0x40 != 0,
+        partition: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+        block: (!unknown).then(|| be_u64(&buf[8..16])),
+        file: (!unknown).then(|| be_u64(&buf[16..24])),
+    })
+}
+
+/// Mode parameter header and block descriptor of a tape drive (MODE SENSE(6)
+/// with DBD=0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapeModeBlock {
+    /// WP: the medium is write-protected.
+    pub write_protected: bool,
+    /// BUFFERED MODE: writes complete once they reach the drive buffer.
+    pub buffered_mode: u8,
+    /// DENSITY CODE of the medium.
+    pub density: u8,
+    /// BLOCK LENGTH; 0 is variable-block mode.
+    pub block_len: u32,
+}
+
+/// Decode the MODE SENSE(6) header and the first block descriptor.
+///
+/// Layout (SPC-4 / SSC-3): header byte 2 WP[7] | BUFFERED MODE[6:4] |
+/// SPEED[3:0], byte 3 BLOCK DESCRIPTOR LENGTH; descriptor byte 0 DENSITY
+/// CODE, bytes 5..7 BLOCK LENGTH (BE).
+pub fn parse_tape_mode_block(buf: &[u8]) -> Result<TapeModeBlock> {
+    ensure!(
+        buf.len() >= 4,
+        "mode parameter header truncated: {} bytes",
+        buf.len()
+    );
+    let desc = buf
+        .get(4..4 + buf[3] as usize)
+        .filter(|d| d.len() >= 8)
+        .ok_or_else(|| format_err!("no block descriptor in mode data: {buf:02X?}"))?;
+    Ok(TapeModeBlock {
+        write_protected: buf[2] & 0x80 != 0,
+        buffered_mode: (buf[2] >> 4) & 0x07,
+        density: desc[0],
+        block_len: u32::from_be_bytes([0, desc[5], desc[6], desc[7]]),
+    })
+}
+
+/// MODE SELECT(6) parameter list setting the block length (0 = variable)
+/// and keeping `density` and `buffered_mode`.
+pub fn tape_block_descriptor_params(
+    density: u8,
+    buffered_mode: u8,
+    block_len: u32,
+) -> Result<[u8; MODE_BLOCK_DESCRIPTOR_PARAMS_LEN]> {
+    ensure!(
+        block_len <= TAPE_LENGTH_MAX,
+        "block length {block_len} does not fit in 24 bits"
+    );
+    let mut out = [0u8; MODE_BLOCK_DESCRIPTOR_PARAMS_LEN];
+    out[2] = (buffered_mode & 0x07) << 4;
+    out[3] = 8;
+    out[4] = density;
+    out[9..12].copy_from_slice(&block_len.to_be_bytes()[1..]);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cdb_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_tape_read6(&mut cdb, false, true, 0x01_0000, 0);
+        assert_eq!(&cdb[..6], &[0x08, 0x02, 0x01, 0x00, 0x00, 0]);
+        assert!(cdb[6..].iter().all(|&b| b == 0));
+
+        build_tape_write6(&mut cdb, true, 16, 0);
+        assert_eq!(&cdb[..6], &[0x0A, 0x01, 0, 0, 16, 0]);
+
+        build_space6(&mut cdb, SpaceCode::Filemarks, -2, 0).expect("space");
+        assert_eq!(&cdb[..6], &[0x11, 0x01, 0xFF, 0xFF, 0xFE, 0]);
+        assert!(build_space6(&mut cdb, SpaceCode::Blocks, 0x80_0000, 0).is_err());
+
+        build_write_filemarks6(&mut cdb, 1, true, 0);
+        assert_eq!(&cdb[..6], &[0x10, 0x01, 0, 0, 1, 0]);
+
+        build_load_unload(&mut cdb, false, true, false, 0);
+        assert_eq!(&cdb[..6], &[0x1B, 0, 0, 0, 0x08, 0]);
+
+        build_read_position(&mut cdb, PositionForm::Long, 0);
+        assert_eq!(&cdb[..10], &[0x34, 0x06, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        build_mode_select6(&mut cdb, false, 12, 0);
+        assert_eq!(&cdb[..6], &[0x15, 0x10, 0, 0, 12, 0]);
+    }
+
+    #[test]
+    fn parses_block_limits_and_position() {
+        let limits = parse_read_block_limits(&[0x02, 0x08, 0x00, 0x00, 0x00, 0x01])
+            .expect("limits");
+        assert_eq!(limits.max_block_len, 0x08_0000);
+        assert_eq!(limits.min_block_len, 1);
+        assert!(!limits.is_fixed_only());
+
+        let mut short = [0u8; POSITION_SHORT_LEN];
+        short[0] = 0x80;
+        short[4..8].copy_from_slice(&7u32.to_be_bytes());
+        let pos = parse_read_position_short(&short).expect("short");
+        assert!(pos.beginning);
+        assert_eq!(pos.block, Some(7));
+        short[0] = 0x04;
+        assert_eq!(
+            parse_read_position_short(&short).expect("short").block,
+            None
+        );
+
+        let mut long = [0u8; POSITION_LONG_LEN];
+        long[7] = 1;
+        long[8..16].copy_from_slice(&0x1_0000_0000u64.to_be_bytes());
+        long[16..24].copy_from_slice(&3u64.to_be_bytes());
+        let pos = parse_read_position_long(&long).expect("long");
+        assert_eq!(pos.partition, 1);
+        assert_eq!(pos.block, Some(0x1_0000_0000));
+        assert_eq!(pos.file, Some(3));
+    }
+
+    #[test]
+    fn mode_block_descriptor_round_trip() {
+        let params = tape_block_descriptor_params(0x58, 1, 0x1_0000).expect("params");
+        assert_eq!(params, [0, 0, 0x10, 8, 0x58, 0, 0, 0, 0, 0x01, 0x00, 0x00]);
+        let block = parse_tape_mode_block(&params).expect("block");
+        assert_eq!(block.density, 0x58);
+        assert_eq!(block.buffered_mode, 1);
+        assert_eq!(block.block_len, 0x1_0000);
+        assert!(parse_tape_mode_block(&[3, 0, 0, 0]).is_err());
+    }
+}
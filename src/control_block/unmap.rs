@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::{vec, vec::Vec};
+
+use crate::control_block::inquiry::BlockLimits;
+
+/// Length of the UNMAP parameter list header.
+pub const UNMAP_HEADER_LEN: usize = 8;
+/// Length of one UNMAP block descriptor.
+pub const UNMAP_DESCRIPTOR_LEN: usize = 16;
+/// Most descriptors that fit the 16-bit PARAMETER LIST LENGTH.
+pub const UNMAP_MAX_DESCRIPTORS: usize =
+    (u16::MAX as usize - UNMAP_HEADER_LEN) / UNMAP_DESCRIPTOR_LEN;
+/// Blocks per UNMAP command when the target reports no MAXIMUM UNMAP LBA
+/// COUNT.
+pub const UNMAP_FALLBACK_LBA_COUNT: u32 = 0xFFFF;
+
+/// Build a padded 16-byte **SCSI UNMAP** CDB.
+///
+/// Parameters:
+/// - `cdb`       : output buffer (will be zeroed; only 10 bytes are used)
+/// - `anchor`    : ANCHOR bit — anchor the blocks instead of deallocating them
+/// - `param_len` : length of the parameter list sent as Data-Out
+/// - `control`   : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte 0      : OPERATION CODE = 0x42
+/// - byte 1      : ANCHOR (bit 0)
+/// - byte 6      : GROUP NUMBER (low 5 bits)
+/// - bytes 7..8  : PARAMETER LIST LENGTH (big-endian, 16-bit)
+/// - byte 9      : CONTROL
+#[inline]
+pub fn build_unmap(cdb: &mut [u8; 16], anchor: bool, param_len: u16, control: u8) {
+    cdb.fill(0);
+    cdb[0] = 0x42; // UNMAP
+    cdb[1] = anchor as u8;
+    cdb[7..9].copy_from_slice(&param_len.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Build the UNMAP parameter list for `(lba, blocks)` descriptors.
+///
+/// Layout (SBC):
+/// - bytes 0..1 : UNMAP DATA LENGTH (list length - 2)
+/// - bytes 2..3 : UNMAP BLOCK DESCRIPTOR DATA LENGTH (16 * descriptors)
+/// - bytes 4..7 : reserved
+/// - then per descriptor: LBA (8 bytes), NUMBER OF LOGICAL BLOCKS (4 bytes), 4
+///   reserved bytes
+pub fn fill_unmap_parameters(descriptors: &[(u64, u32)]) -> Vec<u8> {
+    let desc_len = descriptors.len() * UNMAP_DESCRIPTOR_LEN;
+    let mut out = Vec::with_capacity(UNMAP_HEADER_LEN + desc_len);
+    out.extend_from_slice(&((UNMAP_HEADER_LEN + desc_len - 2) as u16).to_be_bytes());
+    out.extend_from_slice(&(desc_len as u16).to_be_bytes());
+    out.extend_from_slice(&[0; 4]);
+    for &(lba, blocks) in descriptors {
+        out.extend_from_slice(&lba.to_be_bytes());
+        out.extend_from_slice(&blocks.to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+    }
+    out
+}
+
+/// The part of the blocks `lba..end` made of whole unmap granules, per the
+/// OPTIMAL UNMAP GRANULARITY and UNMAP GRANULARITY ALIGNMENT of `limits`.
+/// `None` when no whole granule lies in the range.
+pub fn unmap_aligned(lba: u64, end: u64, limits: &BlockLimits) -> Option<(u64, u64)> {
+    let granularity = limits.optimal_unmap_granularity.unwrap_or(1).max(1) as u64;
+    let alignment = limits.unmap_granularity_alignment.unwrap_or(0) as u64 % granularity;
+    // Granules start at LBAs congruent to `alignment`.
+    let start =
+        lba.checked_add((alignment + granularity - lba % granularity) % granularity)?;
+    let end =
+        end.checked_sub((end % granularity + granularity - alignment) % granularity)?;
+    (start < end).then_some((start, end))
+}
+
+/// Split `blocks` blocks at `lba` into UNMAP commands, one descriptor list
+/// each, keeping to the MAXIMUM UNMAP LBA COUNT and MAXIMUM UNMAP BLOCK
+/// DESCRIPTOR COUNT of `limits`. Descriptor and command lengths are
+/// rounded down to the OPTIMAL UNMAP GRANULARITY when they are capped, so
+/// an aligned range stays aligned.
+///
+/// Limits the target does not report are not taken as unlimited: each
+/// command then carries one descriptor of at most
+/// [`UNMAP_FALLBACK_LBA_COUNT`] blocks.
+pub fn plan_unmap(lba: u64, blocks: u64, limits: &BlockLimits) -> Vec<Vec<(u64, u32)>> {
+    let granularity = limits.optimal_unmap_granularity.unwrap_or(1).max(1) as u64;
+    let round = |n: u64| {
+        if n >= granularity {
+            n - n % granularity
+        } else {
+            n
+        }
+    };
+    let per_descriptor = round(u32::MAX as u64);
+    // FFFF_FFFFh: no limit per command.
+    let per_command = match limits.max_unmap_lba_count {
+        Some(u32::MAX) => u64::MAX,
+        Some(n) => round(n as u64),
+        None => round(UNMAP_FALLBACK_LBA_COUNT as u64),
+    };
+    let max_descriptors = limits
+        .max_unmap_descriptors
+        .map_or(1, |n| n as usize)
+        .clamp(1, UNMAP_MAX_DESCRIPTORS);
+
+    let mut commands = Vec::new();
+    let (mut next, end) = (lba, lba + blocks);
+    while next < end {
+        let mut budget = per_command.min(end - next);
+        let mut descriptors = vec![];
+        while budget > 0 && descriptors.len() < max_descriptors {
+            let n = budget.min(per_descriptor);
+            descriptors.push((next, n as u32));
+            next += n;
+            budget -= n;
+        }
+        commands.push(descriptors);
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_to_unmap_granules() {
+        let limits = BlockLimits {
+            optimal_unmap_granularity: Some(8),
+            unmap_granularity_alignment: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(unmap_aligned(0, 100, &limits), Some((2, 98)));
+        assert_eq!(unmap_aligned(10, 26, &limits), Some((10, 26)));
+        assert_eq!(unmap_aligned(11, 17, &limits), None);
+        assert_eq!(unmap_aligned(5, 9, &BlockLimits::default()), Some((5, 9)));
+    }
+
+    #[test]
+    fn range_smaller_than_a_granule_is_empty() {
+        let limits = BlockLimits {
+            optimal_unmap_granularity: Some(8),
+            unmap_granularity_alignment: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(unmap_aligned(0, 1, &limits), None);
+        assert_eq!(unmap_aligned(0, 2, &limits), None);
+        assert_eq!(unmap_aligned(3, 9, &limits), None);
+    }
+
+    #[test]
+    fn splits_by_lba_and_descriptor_limits() {
+        let limits = BlockLimits {
+            max_unmap_lba_count: Some(100),
+            max_unmap_descriptors: Some(2),
+            optimal_unmap_granularity: Some(8),
+            ..Default::default()
+        };
+        // 100 rounds down to 96 blocks per command.
+        assert_eq!(
+            plan_unmap(0, 200, &limits),
+            [vec![(0, 96)], vec![(96, 96)], vec![(192, 8)]]
+        );
+
+        let unlimited = BlockLimits {
+            max_unmap_lba_count: Some(u32::MAX),
+            max_unmap_descriptors: Some(u32::MAX),
+            ..Default::default()
+        };
+        let unlimited = plan_unmap(0, 3 * u32::MAX as u64, &unlimited);
+        assert_eq!(unlimited.len(), 1);
+        assert_eq!(unlimited[0].len(), 3);
+
+        let one_descriptor = BlockLimits {
+            max_unmap_lba_count: Some(u32::MAX),
+            max_unmap_descriptors: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(plan_unmap(0, u32::MAX as u64 + 1, &one_descriptor).len(), 2);
+    }
+
+    #[test]
+    fn unreported_limits_split_conservatively() {
+        let fallback = UNMAP_FALLBACK_LBA_COUNT;
+        assert_eq!(
+            plan_unmap(0, 2 * fallback as u64 + 1, &BlockLimits::default()),
+            [
+                vec![(0, fallback)],
+                vec![(fallback as u64, fallback)],
+                vec![(2 * fallback as u64, 1)]
+            ]
+        );
+
+        // Granule-aligned, like the reported limits.
+        let granular = BlockLimits {
+            optimal_unmap_granularity: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(plan_unmap(0, 0x1_0000, &granular)[0], [(0, 0xFFF8)]);
+    }
+}
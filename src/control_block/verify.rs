@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// Sense key the target reports when the compared data differs.
+pub const SENSE_KEY_MISCOMPARE: u8 = 0x0E;
+
+/// BYTCHK field of VERIFY: what the target compares the medium against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum ByteCheck {
+    /// Check the medium only (e.g. ECC); no data is transferred.
+    #[default]
+    Medium = 0b00,
+    /// Compare every block against the Data-Out buffer.
+    Compare = 0b01,
+    /// Compare every block against the single block in the Data-Out buffer.
+    CompareSame = 0b11,
+}
+
+impl ByteCheck {
+    /// Bytes of Data-Out for `blocks` blocks of `block_size` bytes.
+    #[inline]
+    pub fn data_len(self, blocks: u32, block_size: u32) -> u64 {
+        match self {
+            Self::Medium => 0,
+            Self::Compare => blocks as u64 * block_size as u64,
+            Self::CompareSame => block_size as u64,
+        }
+    }
+}
+
+/// Build a padded 16-byte **SCSI VERIFY(10)** CDB.
+///
+/// Parameters:
+/// - `cdb`     : output buffer (will be zeroed; only 10 bytes are used)
+/// - `lba`     : first block to verify
+/// - `blocks`  : number of blocks to verify (0 verifies nothing)
+/// - `bytchk`  : what to compare the medium against
+/// - `flags`   : VRPROTECT[7:5] | DPO[4] (others are ignored)
+/// - `control` : CONTROL byte
+///
+/// Layout (SBC):
+/// - byte 0      : OPERATION CODE = 0x2F
+/// - byte 1      : VRPROTECT[7:5] | DPO[4] | BYTCHK[2:1]
+/// - bytes 2..5  : LBA (big-endian, 32-bit)
+/// - byte 6      : GROUP NUMBER (low 5 bits)
+/// - bytes 7..8  : VERIFICATION LENGTH (big-endian, 16-bit)
+/// - byte 9      : CONTROL
+#[inline]
+pub fn build_verify10(
+    cdb: &mut [u8; 16],
+    lba: u32,
+    blocks: u16,
+    bytchk: ByteCheck,
+    flags: u8,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = 0x2F; // VERIFY(10)
+    cdb[1] = (flags & 0b1111_0000) | ((bytchk as u8) << 1);
+    cdb[2..6].copy_from_slice(&lba.to_be_bytes());
+    cdb[7..9].copy_from_slice(&blocks.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Build a **SCSI VERIFY(16)** CDB.
+///
+/// Layout (SBC):
+/// - byte 0       : OPERATION CODE = 0x8F
+/// - byte 1       : VRPROTECT[7:5] | DPO[4] | BYTCHK[2:1]
+/// - bytes 2..9   : LBA (big-endian, 64-bit)
+/// - bytes 10..13 : VERIFICATION LENGTH (big-endian, 32-bit)
+/// - byte 14      : GROUP NUMBER (low 5 bits)
+/// - byte 15      : CONTROL
+#[inline]
+pub fn build_verify16(
+    cdb: &mut [u8; 16],
+    lba: u64,
+    blocks: u32,
+    bytchk: ByteCheck,
+    flags: u8,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = 0x8F; // VERIFY(16)
+    cdb[1] = (flags & 0b1111_0000) | ((bytchk as u8) << 1);
+    cdb[2..10].copy_from_slice(&lba.to_be_bytes());
+    cdb[10..14].copy_from_slice(&blocks.to_be_bytes());
+    cdb[15] = control;
+}
+
+/// Build a padded 16-byte **SCSI WRITE AND VERIFY(10)** CDB. The target
+/// writes the Data-Out buffer and then verifies the medium; with `bytchk`
+/// it also compares what it wrote against the buffer.
+///
+/// Layout (SBC):
+/// - byte 0      : OPERATION CODE = 0x2E
+/// - byte 1      : WRPROTECT[7:5] | DPO[4] | BYTCHK[1]
+/// - bytes 2..5  : LBA (big-endian, 32-bit)
+/// - byte 6      : GROUP NUMBER (low 5 bits)
+/// - bytes 7..8  : TRANSFER LENGTH (big-endian, 16-bit)
+/// - byte 9      : CONTROL
+#[inline]
+pub fn build_write_verify10(
+    cdb: &mut [u8; 16],
+    lba: u32,
+    blocks: u16,
+    bytchk: bool,
+    flags: u8,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = 0x2E; // WRITE AND VERIFY(10)
+    cdb[1] = (flags & 0b1111_0000) | if bytchk { 0x02 } else { 0x00 };
+    cdb[2..6].copy_from_slice(&lba.to_be_bytes());
+    cdb[7..9].copy_from_slice(&blocks.to_be_bytes());
+    cdb[9] = control;
+}
+
+/// Build a **SCSI WRITE AND VERIFY(16)** CDB.
+///
+/// Layout (SBC):
+/// - byte 0       : OPERATION CODE = 0x8E
+/// - byte 1       : WRPROTECT[7:5] | DPO[4] | BYTCHK[1]
+/// - bytes 2..9   : LBA (big-endian, 64-bit)
+/// - bytes 10..13 : TRANSFER LENGTH (big-endian, 32-bit)
+/// - byte 14      : GROUP NUMBER (low 5 bits)
+/// - byte 15      : CONTROL
+#[inline]
+pub fn build_write_verify16(
+    cdb: &mut [u8; 16],
+    lba: u64,
+    blocks: u32,
+    bytchk: bool,
+    flags: u8,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = 0x8E; // WRITE AND VERIFY(16)
+    cdb[1] = (flags & 0b1111_0000) | if bytchk { 0x02 } else { 0x00 };
+    cdb[2..10].copy_from_slice(&lba.to_be_bytes());
+    cdb[10..14].copy_from_slice(&blocks.to_be_bytes());
+    cdb[15] = control;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_verify10(&mut cdb, 0x0102_0304, 0x0506, ByteCheck::Compare, 0x10, 0);
+        assert_eq!(&cdb[..10], &[0x2F, 0x12, 1, 2, 3, 4, 0, 5, 6, 0]);
+        assert_eq!(&cdb[10..], &[0; 6]);
+
+        build_verify16(&mut cdb, 1 << 32, 8, ByteCheck::CompareSame, 0, 0x04);
+        assert_eq!(
+            cdb,
+            [0x8F, 0x06, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0x04]
+        );
+    }
+
+    #[test]
+    fn write_verify_layouts() {
+        let mut cdb = [0u8; 16];
+        build_write_verify10(&mut cdb, 7, 1, true, 0, 0);
+        assert_eq!(&cdb[..10], &[0x2E, 0x02, 0, 0, 0, 7, 0, 0, 1, 0]);
+
+        build_write_verify16(&mut cdb, 7, 1, false, 0x10, 0);
+        assert_eq!(cdb[0], 0x8E);
+        assert_eq!(cdb[1], 0x10);
+        assert_eq!(&cdb[10..14], &[0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn bytchk_data_lengths() {
+        assert_eq!(ByteCheck::Medium.data_len(8, 512), 0);
+        assert_eq!(ByteCheck::Compare.data_len(8, 512), 4096);
+        assert_eq!(ByteCheck::CompareSame.data_len(8, 512), 512);
+    }
+}
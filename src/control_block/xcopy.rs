@@ -0,0 +1,480 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::{
+    control_block::inquiry::{DeviceIdentity, designator_type},
+    models::error::{Result, bail, ensure, format_err},
+};
+
+/// EXTENDED COPY operation code.
+pub const EXTENDED_COPY: u8 = 0x83;
+/// RECEIVE COPY RESULTS operation code.
+pub const RECEIVE_COPY_RESULTS: u8 = 0x84;
+
+/// Size of the LID1 parameter list header.
+pub const XCOPY_HEADER_LEN: usize = 16;
+/// Size of a CSCD (target) descriptor.
+pub const CSCD_DESCRIPTOR_LEN: usize = 32;
+/// Size of a block-to-block segment descriptor.
+pub const BLOCK_SEGMENT_LEN: usize = 28;
+/// Size of the COPY STATUS parameter data.
+pub const COPY_STATUS_LEN: usize = 12;
+/// Size of the OPERATING PARAMETERS data without the descriptor type codes.
+pub const OPERATING_PARAMETERS_LEN: usize = 44;
+
+/// Identification descriptor CSCD type code.
+const CSCD_IDENTIFICATION: u8 = 0xE4;
+/// Block device to block device segment type code.
+const SEGMENT_BLOCK_TO_BLOCK: u8 = 0x02;
+/// Longest designator an identification descriptor has room for.
+const CSCD_DESIGNATOR_MAX: usize = 20;
+
+/// SERVICE ACTION of RECEIVE COPY RESULTS (SPC-4, LID1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CopyResultsAction {
+    /// Progress of the copy with a given LIST IDENTIFIER.
+    CopyStatus = 0x00,
+    /// Data held by the copy manager.
+    ReceiveData = 0x01,
+    /// Limits of the copy manager.
+    OperatingParameters = 0x03,
+    /// Sense data of the segment that failed.
+    FailedSegmentDetails = 0x04,
+}
+
+/// Build a padded 16-byte **SCSI EXTENDED COPY (LID1)** CDB.
+///
+/// Layout (SPC-4):
+/// - byte 0      : OPERATION CODE = 0x83
+/// - byte 1      : SERVICE ACTION = 0x00 (LID1)
+/// - bytes 10..13: PARAMETER LIST LENGTH (BE)
+/// - byte 15     : CONTROL
+#[inline]
+pub fn build_extended_copy(cdb: &mut [u8; 16], param_len: u32, control: u8) {
+    cdb.fill(0);
+    cdb[0] = EXTENDED_COPY;
+    cdb[10..14].copy_from_slice(&param_len.to_be_bytes());
+    cdb[15] = control;
+}
+
+/// Build a padded 16-byte **SCSI RECEIVE COPY RESULTS** CDB.
+///
+/// Layout (SPC-4):
+/// - byte 0      : OPERATION CODE = 0x84
+/// - byte 1      : SERVICE ACTION[4:0]
+/// - byte 2      : LIST IDENTIFIER
+/// - bytes 10..13: ALLOCATION LENGTH (BE)
+/// - byte 15     : CONTROL
+#[inline]
+pub fn build_receive_copy_results(
+    cdb: &mut [u8; 16],
+    action: CopyResultsAction,
+    list_id: u8,
+    alloc_len: u32,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = RECEIVE_COPY_RESULTS;
+    cdb[1] = action as u8;
+    cdb[2] = list_id;
+    cdb[10..14].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[15] = control;
+}
+
+/// Identification CSCD descriptor (0xE4): names a logical unit by one of
+/// its VPD 0x83 designators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentificationCscd {
+    /// Peripheral device type (0 for block devices).
+    pub device_type: u8,
+    /// CODE SET of the designator (1 = binary).
+    pub code_set: u8,
+    /// DESIGNATOR TYPE, e.g. [`designator_type::NAA`].
+    pub designator_type: u8,
+    /// The designator, at most 20 bytes.
+    pub designator: Vec<u8>,
+    /// DISK BLOCK LENGTH of the logical unit.
+    pub block_len: u32,
+}
+
+impl IdentificationCscd {
+    /// Descriptor for a block device known by `identity` (NAA or EUI-64).
+    pub fn from_identity(identity: &DeviceIdentity, block_len: u32) -> Result<Self> {
+        let designator_type = match identity {
+            DeviceIdentity::Naa(_) => designator_type::NAA,
+            DeviceIdentity::Eui64(_) => designator_type::EUI64,
+            DeviceIdentity::ScsiName(name) => {
+                bail!("XCOPY needs a binary designator, got SCSI name {name}")
+            },
+        };
+        let designator = hex::decode(identity.as_str())
+            .map_err(|e| format_err!("invalid designator {identity}: {e}"))?;
+        ensure!(
+            designator.len() <= CSCD_DESIGNATOR_MAX,
+            "designator of {} bytes does not fit a CSCD descriptor",
+            designator.len()
+        );
+        Ok(Self {
+            device_type: 0,
+            code_set: 0x01,
+            designator_type,
+            designator,
+            block_len,
+        })
+    }
+
+    /// Serialize the descriptor.
+    ///
+    /// Layout (SPC-4): byte 0 = 0xE4, byte 1 = PERIPHERAL DEVICE TYPE[4:0],
+    /// bytes 4..7 designation descriptor header (code set, association = LU,
+    /// type, length), bytes 8..27 designator, bytes 29..31 DISK BLOCK LENGTH.
+    pub fn to_bytes(&self) -> [u8; CSCD_DESCRIPTOR_LEN] {
+        let mut out = [0u8; CSCD_DESCRIPTOR_LEN];
+        let len = self.designator.len().min(CSCD_DESIGNATOR_MAX);
+        out[0] = CSCD_IDENTIFICATION;
+        out[1] = self.device_type & 0x1F;
+        out[4] = self.code_set & 0x0F;
+        out[5] = self.designator_type & 0x0F;
+        out[7] = len as u8;
+        out[8..8 + len].copy_from_slice(&self.designator[..len]);
+        out[29..32].copy_from_slice(&self.block_len.to_be_bytes()[1..]);
+        out
+    }
+}
+
+/// Block device to block device segment descriptor (0x02).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSegment {
+    /// Index of the source CSCD descriptor in the list.
+    pub src: u16,
+    /// Index of the destination CSCD descriptor in the list.
+    pub dst: u16,
+    /// Number of blocks to copy (in source blocks).
+    pub blocks: u16,
+    /// First source LBA.
+    pub src_lba: u64,
+    /// First destination LBA.
+    pub dst_lba: u64,
+}
+
+impl BlockSegment {
+    /// Serialize the descriptor.
+    ///
+    /// Layout (SPC-4): byte 0 = 0x02, bytes 2..3 DESCRIPTOR LENGTH (0x18),
+    /// bytes 4..5 / 6..7 source / destination CSCD index, bytes 10..11
+    /// BLOCK DEVICE NUMBER OF BLOCKS, bytes 12..19 / 20..27 source /
+    /// destination LBA.
+    pub fn to_bytes(&self) -> [u8; BLOCK_SEGMENT_LEN] {
+        let mut out = [0u8; BLOCK_SEGMENT_LEN];
+        out[0] = SEGMENT_BLOCK_TO_BLOCK;
+        out[2..4].copy_from_slice(&((BLOCK_SEGMENT_LEN - 4) as u16).to_be_bytes());
+        out[4..6].copy_from_slice(&self.src.to_be_bytes());
+        out[6..8].copy_from_slice(&self.dst.to_be_bytes());
+        out[10..12].copy_from_slice(&self.blocks.to_be_bytes());
+        out[12..20].copy_from_slice(&self.src_lba.to_be_bytes());
+        out[20..28].copy_from_slice(&self.dst_lba.to_be_bytes());
+        out
+    }
+}
+
+/// EXTENDED COPY (LID1) parameter list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtendedCopyParameters {
+    /// LIST IDENTIFIER to monitor the copy with RECEIVE COPY RESULTS.
+    pub list_id: u8,
+    /// NRCR: the copy manager need not keep the results for RECEIVE COPY
+    /// RESULTS.
+    pub no_receive_results: bool,
+    /// PRIORITY of the copy (0 = highest).
+    pub priority: u8,
+    /// CSCD descriptors the segments refer to by index.
+    pub targets: Vec<IdentificationCscd>,
+    /// Segments, copied in order.
+    pub segments: Vec<BlockSegment>,
+}
+
+impl ExtendedCopyParameters {
+    /// Serialize the parameter list.
+    ///
+    /// Layout (SPC-4, LID1): byte 0 LIST IDENTIFIER, byte 1 NRCR[4] |
+    /// PRIORITY[2:0], bytes 2..3 CSCD DESCRIPTOR LIST LENGTH, bytes 8..11
+    /// SEGMENT DESCRIPTOR LIST LENGTH, bytes 12..15 INLINE DATA LENGTH (0),
+    /// then the CSCD and segment descriptors.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        ensure!(!self.segments.is_empty(), "EXTENDED COPY without segments");
+        for seg in &self.segments {
+            ensure!(
+                (seg.src as usize) < self.targets.len()
+                    && (seg.dst as usize) < self.targets.len(),
+                "segment refers to CSCD {} / {} of {}",
+                seg.src,
+                seg.dst,
+                self.targets.len()
+            );
+        }
+        let cscd_len = self.targets.len() * CSCD_DESCRIPTOR_LEN;
+        let seg_len = self.segments.len() * BLOCK_SEGMENT_LEN;
+        let cscd_len = u16::try_from(cscd_len).map_err(|_| {
+            format_err!("{} CSCD descriptors do not fit", self.targets.len())
+        })?;
+
+        let mut out = Vec::with_capacity(XCOPY_HEADER_LEN + cscd_len as usize + seg_len);
+        out.push(self.list_id);
+        out.push(((self.no_receive_results as u8) << 4) | (self.priority & 0x07));
+        out.extend_from_slice(&cscd_len.to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(seg_len as u32).to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+        for target in &self.targets {
+            out.extend_from_slice(&target.to_bytes());
+        }
+        for seg in &self.segments {
+            out.extend_from_slice(&seg.to_bytes());
+        }
+        Ok(out)
+    }
+}
+
+/// COPY MANAGER STATUS of a copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyManagerStatus {
+    /// The copy is still running.
+    InProgress,
+    /// The copy completed without errors.
+    Completed,
+    /// The copy completed with errors; see the failed segment details.
+    CompletedWithErrors,
+    /// A value SPC-4 does not define.
+    Reserved(u8),
+}
+
+impl From<u8> for CopyManagerStatus {
+    fn from(v: u8) -> Self {
+        match v & 0x7F {
+            0x00 => Self::InProgress,
+            0x01 => Self::Completed,
+            0x02 => Self::CompletedWithErrors,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+/// RECEIVE COPY RESULTS / COPY STATUS parameter data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyStatus {
+    /// COPY MANAGER STATUS.
+    pub status: CopyManagerStatus,
+    /// HDD: the copy manager holds data for RECEIVE DATA.
+    pub held_data_discarded: bool,
+    /// SEGMENTS PROCESSED so far.
+    pub segments_processed: u16,
+    /// TRANSFER COUNT UNITS as a power of 1024 (0 = bytes, 1 = KiB, ...).
+    pub transfer_count_units: u8,
+    /// TRANSFER COUNT in `transfer_count_units`.
+    pub transfer_count: u32,
+}
+
+impl CopyStatus {
+    /// Bytes transferred, saturating for units too large to represent.
+    pub fn transferred_bytes(&self) -> u64 {
+        let unit = 1u64
+            .checked_shl(10 * self.transfer_count_units as u32)
+            .unwrap_or(u64::MAX);
+        (self.transfer_count as u64).saturating_mul(unit)
+    }
+}
+
+/// Decode COPY STATUS parameter data.
+///
+/// Layout (SPC-4): bytes 0..3 AVAILABLE DATA, byte 4 HDD[7] | COPY MANAGER
+/// STATUS[6:0], bytes 5..6 SEGMENTS PROCESSED, byte 7 TRANSFER COUNT UNITS,
+/// bytes 8..11 TRANSFER COUNT.
+pub fn parse_copy_status(buf: &[u8]) -> Result<CopyStatus> {
+    ensure!(
+        buf.len() >= COPY_STATUS_LEN,
+        "COPY STATUS truncated: {} bytes",
+        buf.len()
+    );
+    Ok(CopyStatus {
+        status: buf[4].into(),
+        held_data_discarded: buf[4] & 0x80 != 0,
+        segments_processed: u16::from_be_bytes([buf[5], buf[6]]),
+        transfer_count_units: buf[7],
+        transfer_count: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+    })
+}
+
+/// RECEIVE COPY RESULTS / OPERATING PARAMETERS: limits of the copy manager.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyOperatingParameters {
+    /// SNLID: the copy manager supports LID4 (no list ID) copies.
+    pub snlid: bool,
+    /// MAXIMUM CSCD DESCRIPTOR COUNT.
+    pub max_cscd_descriptors: u16,
+    /// MAXIMUM SEGMENT DESCRIPTOR COUNT.
+    pub max_segment_descriptors: u16,
+    /// MAXIMUM DESCRIPTOR LIST LENGTH in bytes.
+    pub max_descriptor_list_len: u32,
+    /// MAXIMUM SEGMENT LENGTH in bytes.
+    pub max_segment_len: u32,
+    /// MAXIMUM INLINE DATA LENGTH.
+    pub max_inline_data_len: u32,
+    /// TOTAL CONCURRENT COPIES.
+    pub total_concurrent_copies: u16,
+    /// MAXIMUM CONCURRENT COPIES.
+    pub max_concurrent_copies: u8,
+    /// DATA SEGMENT GRANULARITY as a power of two.
+    pub data_segment_granularity: u8,
+    /// Descriptor type codes the copy manager implements.
+    pub descriptor_types: Vec<u8>,
+}
+
+impl CopyOperatingParameters {
+    /// Whether the copy manager implements descriptor type `code`.
+    pub fn implements(&self, code: u8) -> bool {
+        self.descriptor_types.contains(&code)
+    }
+
+    /// Whether block-to-block segments with identification CSCDs, which
+    /// [`ExtendedCopyParameters`] produces, are supported.
+    pub fn supports_block_copy(&self) -> bool {
+        self.implements(SEGMENT_BLOCK_TO_BLOCK) && self.implements(CSCD_IDENTIFICATION)
+    }
+}
+
+/// Decode OPERATING PARAMETERS data; the descriptor type list is clipped to
+/// the bytes received.
+///
+/// Layout (SPC-4): byte 4 SNLID[0], bytes 8..9 MAXIMUM CSCD DESCRIPTOR
+/// COUNT, 10..11 MAXIMUM SEGMENT DESCRIPTOR COUNT, 12..15 MAXIMUM
+/// DESCRIPTOR LIST LENGTH, 16..19 MAXIMUM SEGMENT LENGTH, 20..23 MAXIMUM
+/// INLINE DATA LENGTH, 34..35 TOTAL CONCURRENT COPIES, 36 MAXIMUM
+/// CONCURRENT COPIES, 37 DATA SEGMENT GRANULARITY, 43 IMPLEMENTED
+/// DESCRIPTOR LIST LENGTH, then the type codes.
+pub fn parse_copy_operating_parameters(buf: &[u8]) -> Result<CopyOperatingParameters> {
+    ensure!(
+        buf.len() >= OPERATING_PARAMETERS_LEN,
+        "OPERATING PARAMETERS truncated: {} bytes",
+        buf.len()
+    );
+    let be32 =
+        |i: usize| u32::from_be_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+    let types_end = (OPERATING_PARAMETERS_LEN + buf[43] as usize).min(buf.len());
+    Ok(CopyOperatingParameters {
+        snlid: buf[4] & 0x01 != 0,
+        max_cscd_descriptors: u16::from_be_bytes([buf[8], buf[9]]),
+        max_segment_descriptors: u16::from_be_bytes([buf[10], buf[11]]),
+        max_descriptor_list_len: be32(12),
+        max_segment_len: be32(16),
+        max_inline_data_len: be32(20),
+        total_concurrent_copies: u16::from_be_bytes([buf[34], buf[35]]),
+        max_concurrent_copies: buf[36],
+        data_segment_granularity: buf[37],
+        descriptor_types: buf[OPERATING_PARAMETERS_LEN..types_end].to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cdb_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_extended_copy(&mut cdb, 0x0102_0304, 0x04);
+        assert_eq!(cdb[0], 0x83);
+        assert_eq!(cdb[1], 0);
+        assert_eq!(&cdb[10..16], &[0x01, 0x02, 0x03, 0x04, 0, 0x04]);
+
+        build_receive_copy_results(
+            &mut cdb,
+            CopyResultsAction::OperatingParameters,
+            0,
+            0x200,
+            0,
+        );
+        assert_eq!(&cdb[..3], &[0x84, 0x03, 0]);
+        assert_eq!(&cdb[10..14], &[0, 0, 0x02, 0]);
+
+        build_receive_copy_results(&mut cdb, CopyResultsAction::CopyStatus, 7, 12, 0);
+        assert_eq!(&cdb[..3], &[0x84, 0x00, 7]);
+    }
+
+    #[test]
+    fn builds_parameter_list() {
+        let identity = DeviceIdentity::Naa("600140512345678900000000000000AB".into());
+        let cscd = IdentificationCscd::from_identity(&identity, 512).expect("cscd");
+        let params = ExtendedCopyParameters {
+            list_id: 9,
+            priority: 1,
+            targets: vec![cscd.clone(), cscd],
+            segments: vec![BlockSegment {
+                src: 0,
+                dst: 1,
+                blocks: 8,
+                src_lba: 0x10,
+                dst_lba: 0x1000,
+            }],
+            ..Default::default()
+        };
+        let buf = params.to_bytes().expect("params");
+        assert_eq!(buf.len(), 16 + 2 * 32 + 28);
+        assert_eq!(&buf[..4], &[9, 0x01, 0, 64]);
+        assert_eq!(&buf[8..12], &[0, 0, 0, 28]);
+
+        let t = &buf[16..48];
+        assert_eq!(&t[..8], &[0xE4, 0, 0, 0, 0x01, 0x03, 0, 16]);
+        assert_eq!(t[8], 0x60);
+        assert_eq!(&t[29..32], &[0, 0x02, 0]);
+
+        let s = &buf[80..];
+        assert_eq!(&s[..8], &[0x02, 0, 0, 0x18, 0, 0, 0, 1]);
+        assert_eq!(&s[10..12], &[0, 8]);
+        assert_eq!(&s[12..20], &0x10u64.to_be_bytes());
+        assert_eq!(&s[20..28], &0x1000u64.to_be_bytes());
+
+        let mut bad = params;
+        bad.segments[0].dst = 2;
+        assert!(bad.to_bytes().is_err());
+        assert!(
+            IdentificationCscd::from_identity(
+                &DeviceIdentity::ScsiName("iqn.x".into()),
+                512
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_copy_status() {
+        let buf = [0, 0, 0, 8, 0x01, 0, 3, 1, 0, 0, 0, 4];
+        let st = parse_copy_status(&buf).expect("status");
+        assert_eq!(st.status, CopyManagerStatus::Completed);
+        assert_eq!(st.segments_processed, 3);
+        assert_eq!(st.transferred_bytes(), 4096);
+        assert!(parse_copy_status(&buf[..8]).is_err());
+    }
+
+    #[test]
+    fn parses_operating_parameters() {
+        let mut buf = vec![0u8; 44];
+        buf[8..10].copy_from_slice(&2u16.to_be_bytes());
+        buf[10..12].copy_from_slice(&1u16.to_be_bytes());
+        buf[16..20].copy_from_slice(&(4u32 << 20).to_be_bytes());
+        buf[36] = 1;
+        buf[43] = 2;
+        buf.extend_from_slice(&[0x02, 0xE4]);
+        let op = parse_copy_operating_parameters(&buf).expect("params");
+        assert_eq!(op.max_cscd_descriptors, 2);
+        assert_eq!(op.max_segment_descriptors, 1);
+        assert_eq!(op.max_segment_len, 4 << 20);
+        assert!(op.supports_block_copy());
+
+        buf.truncate(45);
+        let op = parse_copy_operating_parameters(&buf).expect("clipped");
+        assert!(!op.supports_block_copy());
+    }
+}
@@ -0,0 +1,387 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::vec::Vec;
+
+use crate::models::error::{Result, ensure};
+
+/// ZBC OUT operation code (zone actions).
+pub const ZBC_OUT: u8 = 0x94;
+/// ZBC IN operation code (REPORT ZONES).
+pub const ZBC_IN: u8 = 0x95;
+/// REPORT ZONES service action of ZBC IN.
+pub const REPORT_ZONES_SA: u8 = 0x00;
+
+/// Peripheral device type of a host-managed zoned block device.
+pub const DEVICE_TYPE_HOST_MANAGED_ZONED: u8 = 0x14;
+
+/// Size of the REPORT ZONES header.
+pub const REPORT_ZONES_HEADER_LEN: usize = 64;
+/// Size of a zone descriptor.
+pub const ZONE_DESCRIPTOR_LEN: usize = 64;
+
+/// Service actions of ZBC OUT (ZBC).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ZoneAction {
+    /// CLOSE ZONE: release the open resources of the zone.
+    Close = 0x01,
+    /// FINISH ZONE: move the write pointer to the end of the zone.
+    Finish = 0x02,
+    /// OPEN ZONE: explicitly open the zone.
+    Open = 0x03,
+    /// RESET WRITE POINTER: empty the zone.
+    ResetWritePointer = 0x04,
+}
+
+/// REPORTING OPTIONS of REPORT ZONES: which zones are listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ZoneReportingOptions {
+    /// Every zone.
+    All = 0x00,
+    /// Zones in the EMPTY condition.
+    Empty = 0x01,
+    /// Zones in the IMPLICITLY OPENED condition.
+    ImplicitlyOpened = 0x02,
+    /// Zones in the EXPLICITLY OPENED condition.
+    ExplicitlyOpened = 0x03,
+    /// Zones in the CLOSED condition.
+    Closed = 0x04,
+    /// Zones in the FULL condition.
+    Full = 0x05,
+    /// Zones in the READ ONLY condition.
+    ReadOnly = 0x06,
+    /// Zones in the OFFLINE condition.
+    Offline = 0x07,
+    /// Zones with RESET WRITE POINTER RECOMMENDED set.
+    ResetRecommended = 0x10,
+    /// Zones with NON-SEQUENTIAL WRITE RESOURCES ACTIVE set.
+    NonSequential = 0x11,
+    /// Zones in the NOT WRITE POINTER condition.
+    NotWritePointer = 0x3F,
+}
+
+/// Build a padded 16-byte **ZBC REPORT ZONES** CDB.
+///
+/// `partial` limits ZONE LIST LENGTH to the descriptors that fit in
+/// `alloc_len`.
+///
+/// Layout (ZBC):
+/// - byte 0       : OPERATION CODE = 0x95 (ZBC IN)
+/// - byte 1       : SERVICE ACTION = 0x00
+/// - bytes 2..9   : ZONE START LBA (BE)
+/// - bytes 10..13 : ALLOCATION LENGTH (BE)
+/// - byte 14      : PARTIAL[7] | REPORTING OPTIONS[5:0]
+/// - byte 15      : CONTROL
+#[inline]
+pub fn build_report_zones(
+    cdb: &mut [u8; 16],
+    zone_start_lba: u64,
+    options: ZoneReportingOptions,
+    partial: bool,
+    alloc_len: u32,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = ZBC_IN;
+    cdb[1] = REPORT_ZONES_SA;
+    cdb[2..10].copy_from_slice(&zone_start_lba.to_be_bytes());
+    cdb[10..14].copy_from_slice(&alloc_len.to_be_bytes());
+    cdb[14] = ((partial as u8) << 7) | options as u8;
+    cdb[15] = control;
+}
+
+/// Build a padded 16-byte **ZBC OUT** CDB (OPEN / CLOSE / FINISH ZONE,
+/// RESET WRITE POINTER).
+///
+/// `zone_id` is the start LBA of the zone; with `all` it is ignored and the
+/// action applies to every zone it can.
+///
+/// Layout (ZBC):
+/// - byte 0     : OPERATION CODE = 0x94 (ZBC OUT)
+/// - byte 1     : SERVICE ACTION[4:0]
+/// - bytes 2..9 : ZONE ID (BE)
+/// - byte 14    : ALL[0]
+/// - byte 15    : CONTROL
+#[inline]
+pub fn build_zone_action(
+    cdb: &mut [u8; 16],
+    action: ZoneAction,
+    zone_id: u64,
+    all: bool,
+    control: u8,
+) {
+    cdb.fill(0);
+    cdb[0] = ZBC_OUT;
+    cdb[1] = action as u8;
+    if !all {
+        cdb[2..10].copy_from_slice(&zone_id.to_be_bytes());
+    }
+    cdb[14] = all as u8;
+    cdb[15] = control;
+}
+
+/// ZONE TYPE of a zone descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoneType {
+    /// Written like an ordinary LBA range; no write pointer.
+    Conventional,
+    /// Writes must start at the write pointer.
+    SequentialWriteRequired,
+    /// Writes should start at the write pointer (host-aware devices).
+    SequentialWritePreferred,
+    /// A type ZBC does not define.
+    Reserved(u8),
+}
+
+impl From<u8> for ZoneType {
+    fn from(v: u8) -> Self {
+        match v & 0x0F {
+            0x1 => Self::Conventional,
+            0x2 => Self::SequentialWriteRequired,
+            0x3 => Self::SequentialWritePreferred,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+/// ZONE CONDITION of a zone descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoneCondition {
+    /// Conventional zone: there is no write pointer.
+    NotWritePointer,
+    /// Write pointer at the zone start.
+    Empty,
+    /// Opened by a write.
+    ImplicitlyOpened,
+    /// Opened by OPEN ZONE.
+    ExplicitlyOpened,
+    /// Partly written and not open.
+    Closed,
+    /// Only readable.
+    ReadOnly,
+    /// Written to the end (or finished).
+    Full,
+    /// Neither readable nor writable.
+    Offline,
+    /// A condition ZBC does not define.
+    Reserved(u8),
+}
+
+impl From<u8> for ZoneCondition {
+    fn from(v: u8) -> Self {
+        match v & 0x0F {
+            0x0 => Self::NotWritePointer,
+            0x1 => Self::Empty,
+            0x2 => Self::ImplicitlyOpened,
+            0x3 => Self::ExplicitlyOpened,
+            0x4 => Self::Closed,
+            0xD => Self::ReadOnly,
+            0xE => Self::Full,
+            0xF => Self::Offline,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+/// One zone of a REPORT ZONES list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZoneDescriptor {
+    /// ZONE TYPE.
+    pub zone_type: ZoneType,
+    /// ZONE CONDITION.
+    pub condition: ZoneCondition,
+    /// NON_SEQ: non-sequential write resources are active.
+    pub non_seq: bool,
+    /// RESET: a RESET WRITE POINTER is recommended.
+    pub reset_recommended: bool,
+    /// ZONE LENGTH in logical blocks.
+    pub length: u64,
+    /// ZONE START LBA.
+    pub start: u64,
+    /// WRITE POINTER LBA (meaningless without a write pointer).
+    pub write_pointer: u64,
+}
+
+impl ZoneDescriptor {
+    /// First LBA after the zone.
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.start.saturating_add(self.length)
+    }
+
+    /// Whether `lba` lies in the zone.
+    #[inline]
+    pub fn contains(&self, lba: u64) -> bool {
+        (self.start..self.end()).contains(&lba)
+    }
+
+    /// Whether writes must land exactly on the write pointer.
+    #[inline]
+    pub fn is_sequential_required(&self) -> bool {
+        self.zone_type == ZoneType::SequentialWriteRequired
+    }
+
+    /// Whether the zone has a write pointer the condition makes valid.
+    pub fn has_write_pointer(&self) -> bool {
+        !matches!(
+            self.condition,
+            ZoneCondition::NotWritePointer
+                | ZoneCondition::Full
+                | ZoneCondition::ReadOnly
+                | ZoneCondition::Offline
+        )
+    }
+
+    /// Blocks left between the write pointer and the end of the zone.
+    pub fn remaining(&self) -> u64 {
+        if self.has_write_pointer() {
+            self.end().saturating_sub(self.write_pointer)
+        } else {
+            0
+        }
+    }
+}
+
+/// REPORT ZONES header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZoneListHeader {
+    /// ZONE LIST LENGTH: bytes of descriptors the target has for the
+    /// request (or fit, with PARTIAL).
+    pub list_len: u32,
+    /// SAME: how alike the listed zones are (0 = all different).
+    pub same: u8,
+    /// MAXIMUM LBA of the device.
+    pub max_lba: u64,
+}
+
+impl ZoneListHeader {
+    /// Number of descriptors the whole list holds.
+    #[inline]
+    pub fn zones(&self) -> usize {
+        self.list_len as usize / ZONE_DESCRIPTOR_LEN
+    }
+}
+
+#[inline]
+fn be_u64(b: &[u8]) -> u64 {
+    u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+}
+
+/// Decode the REPORT ZONES header.
+///
+/// Layout (ZBC): bytes 0..3 ZONE LIST LENGTH, byte 4 SAME[3:0], bytes 8..15
+/// MAXIMUM LBA.
+pub fn parse_zone_list_header(buf: &[u8]) -> Result<ZoneListHeader> {
+    ensure!(
+        buf.len() >= REPORT_ZONES_HEADER_LEN,
+        "REPORT ZONES header truncated: {} bytes",
+        buf.len()
+    );
+    Ok(ZoneListHeader {
+        list_len: u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]),
+        same: buf[4] & 0x0F,
+        max_lba: be_u64(&buf[8..16]),
+    })
+}
+
+/// Decode one zone descriptor.
+///
+/// Layout (ZBC): byte 0 ZONE TYPE[3:0], byte 1 ZONE CONDITION[7:4] |
+/// NON_SEQ[1] | RESET[0], bytes 8..15 ZONE LENGTH, bytes 16..23 ZONE START
+/// LBA, bytes 24..31 WRITE POINTER LBA.
+pub fn parse_zone_descriptor(d: &[u8]) -> Result<ZoneDescriptor> {
+    ensure!(
+        d.len() >= ZONE_DESCRIPTOR_LEN,
+        "zone descriptor truncated: {} bytes",
+        d.len()
+    );
+    Ok(ZoneDescriptor {
+        zone_type: d[0].into(),
+        condition: (d[1] >> 4).into(),
+        non_seq: d[1] & 0x02 != 0,
+        reset_recommended: d[1] & 0x01 != 0,
+        length: be_u64(&d[8..16]),
+        start: be_u64(&d[16..24]),
+        write_pointer: be_u64(&d[24..32]),
+    })
+}
+
+/// Decode REPORT ZONES parameter data; descriptors cut off by the allocation
+/// length are dropped.
+pub fn parse_report_zones(buf: &[u8]) -> Result<(ZoneListHeader, Vec<ZoneDescriptor>)> {
+    let header = parse_zone_list_header(buf)?;
+    let end = (REPORT_ZONES_HEADER_LEN + header.list_len as usize).min(buf.len());
+    let zones = buf[REPORT_ZONES_HEADER_LEN..end]
+        .chunks_exact(ZONE_DESCRIPTOR_LEN)
+        .map(parse_zone_descriptor)
+        .collect::<Result<_>>()?;
+    Ok((header, zones))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(zone_type: u8, cond: u8, len: u64, start: u64, wp: u64) -> [u8; 64] {
+        let mut d = [0u8; 64];
+        d[0] = zone_type;
+        d[1] = cond << 4;
+        d[8..16].copy_from_slice(&len.to_be_bytes());
+        d[16..24].copy_from_slice(&start.to_be_bytes());
+        d[24..32].copy_from_slice(&wp.to_be_bytes());
+        d
+    }
+
+    #[test]
+    fn cdb_layouts() {
+        let mut cdb = [0xFFu8; 16];
+        build_report_zones(
+            &mut cdb,
+            0x8_0000,
+            ZoneReportingOptions::Closed,
+            true,
+            4096,
+            0,
+        );
+        assert_eq!(
+            cdb,
+            [0x95, 0, 0, 0, 0, 0, 0, 0x08, 0, 0, 0, 0, 0x10, 0, 0x84, 0]
+        );
+
+        build_zone_action(&mut cdb, ZoneAction::ResetWritePointer, 0x8_0000, false, 0);
+        assert_eq!(
+            cdb,
+            [0x94, 0x04, 0, 0, 0, 0, 0, 0x08, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        build_zone_action(&mut cdb, ZoneAction::Close, 0x8_0000, true, 0);
+        assert_eq!(cdb[1], 0x01);
+        assert!(cdb[2..10].iter().all(|&b| b == 0));
+        assert_eq!(cdb[14], 0x01);
+    }
+
+    #[test]
+    fn parses_report_zones() {
+        let mut buf = vec![0u8; REPORT_ZONES_HEADER_LEN];
+        buf[0..4].copy_from_slice(&(3 * ZONE_DESCRIPTOR_LEN as u32).to_be_bytes());
+        buf[8..16].copy_from_slice(&0x2_FFFFu64.to_be_bytes());
+        buf.extend_from_slice(&descriptor(0x1, 0x0, 0x1_0000, 0, 0));
+        buf.extend_from_slice(&descriptor(0x2, 0x2, 0x1_0000, 0x1_0000, 0x1_0100));
+        // third descriptor cut off by the allocation length
+        buf.extend_from_slice(&[0u8; 16]);
+
+        let (header, zones) = parse_report_zones(&buf).expect("zones");
+        assert_eq!(header.zones(), 3);
+        assert_eq!(header.max_lba, 0x2_FFFF);
+        assert_eq!(zones.len(), 2);
+        assert_eq!(zones[0].zone_type, ZoneType::Conventional);
+        assert!(!zones[0].has_write_pointer());
+        assert!(zones[1].is_sequential_required());
+        assert_eq!(zones[1].condition, ZoneCondition::ImplicitlyOpened);
+        assert!(zones[1].contains(0x1_FFFF));
+        assert!(!zones[1].contains(0x2_0000));
+        assert_eq!(zones[1].remaining(), 0xFF00);
+        assert!(parse_zone_list_header(&buf[..32]).is_err());
+    }
+}
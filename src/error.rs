@@ -0,0 +1,496 @@
+//! Typed errors of the public API.
+//!
+//! [`Pool`](crate::client::pool_sessions::Pool),
+//! [`ClientConnection`](crate::client::client::ClientConnection), the
+//! session/LUN handles, [`IscsiDevice`](crate::client::device::IscsiDevice),
+//! the multipath session and the public helpers of the state machines
+//! return [`IscsiError`], so callers can match on the kind of failure
+//! instead of inspecting `anyhow` chains:
+//!
+//! ```ignore
+//! match lun.read_at(lba, 8).await {
+//!     Ok(data) => consume(data),
+//!     Err(IscsiError::Scsi(e)) if e.sense_codes() == Some((0x06, 0x29, 0x00)) => retry(),
+//!     Err(IscsiError::Timeout(msg)) => warn!("slow target: {msg}"),
+//!     Err(e) => return Err(e.into()),
+//! }
+//! ```
+//!
+//! Internally the crate still builds errors with `anyhow`; the conversion
+//! from [`anyhow::Error`] classifies the chain by the typed errors it
+//! carries and keeps the rest as [`IscsiError::Other`]. The PDU models and
+//! CDB builders return [`ProtocolError`], which converts the same way.
+//!
+//! I/O-shaped consumers (exporters, `AsyncRead`/`AsyncWrite` adapters) convert
+//! into [`std::io::Error`]; [`IscsiError::io_kind`] picks the
+//! [`ErrorKind`](io::ErrorKind) and the `IscsiError` stays available through
+//! [`io::Error::get_ref`].
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{error::Error as StdError, fmt, io};
+
+use thiserror::Error;
+
+use crate::{
+    client::{
+        client::{DisconnectError, ShutdownError},
+        common::ClientIoError,
+        pool_sessions::{
+            CommandTimeoutError, DeadlineExceededError, SessionLoginError,
+            TaskTerminatedError,
+        },
+        reservations::ReservationConflictError,
+        retry::AmbiguousOutcomeError,
+        targets::TargetLoginError,
+    },
+    models::{
+        command::common::{ScsiStatus, UnknownResponseCode, UnknownScsiStatus},
+        data_fromat::DigestMismatchError,
+        error::ProtocolError,
+        login::status::{InitiatorErrorDetail, StatusClass},
+        opcode::UnknownOpcode,
+        snapshot::PduSnapshot,
+    },
+    state_machine::{common::ScsiStatusError, login::common::LoginStatusError},
+};
+
+/// `Result` of the public API.
+pub type Result<T, E = IscsiError> = std::result::Result<T, E>;
+
+/// Why an initiator operation failed.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum IscsiError {
+    /// The target sent a PDU that breaks RFC 7143 (unknown opcode, SCSI
+    /// status or response code, malformed or unroutable PDU).
+    #[error("protocol violation: {message}")]
+    ProtocolViolation {
+        /// Text of the whole error chain.
+        message: String,
+        /// Header of the offending PDU, when the read path captured it.
+        pdu: Option<PduSnapshot>,
+    },
+    /// A received PDU failed its HeaderDigest or DataDigest check.
+    #[error(transparent)]
+    DigestMismatch(DigestMismatchError),
+    /// A SCSI command completed with a status other than GOOD; the status
+    /// and sense data are in the wrapped error.
+    #[error(transparent)]
+    Scsi(ScsiStatusError),
+    /// The target rejected a login; Status-Class and Status-Detail are in
+    /// the wrapped error.
+    #[error(transparent)]
+    LoginRejected(LoginStatusError),
+    /// Some sessions of a multi-session login failed; the wrapped error
+    /// lists the sessions that did log in.
+    #[error(transparent)]
+    SessionLogin(#[from] SessionLoginError),
+    /// Some targets of a multi-target login failed; the sessions that did
+    /// log in are listed in the wrapped error.
+    #[error(transparent)]
+    TargetLogin(#[from] TargetLoginError),
+    /// A command, an I/O step or the caller's deadline timed out.
+    #[error("{0}")]
+    Timeout(String),
+    /// The connection failed while the request was outstanding.
+    #[error("{0}")]
+    Disconnected(String),
+    /// The request was cancelled or the connection shut down on purpose.
+    #[error("{0}")]
+    Cancelled(String),
+    /// The connection failed after a non-idempotent command may have been
+    /// applied; it was not re-issued.
+    #[error(transparent)]
+    AmbiguousOutcome(AmbiguousOutcomeError),
+    /// A persistent reservation request of
+    /// [`PrFence`](crate::client::reservations::PrFence) conflicted with
+    /// another initiator's reservation.
+    #[error(transparent)]
+    ReservationConflict(ReservationConflictError),
+    /// A task management function terminated the task.
+    #[error(transparent)]
+    TaskTerminated(TaskTerminatedError),
+    /// Any other failure (configuration, invalid arguments, local I/O, ...).
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl IscsiError {
+    /// An [`IscsiError::Other`] with only a message, for failures the crate
+    /// detects itself (invalid arguments, unexpected replies).
+    pub(crate) fn msg<M>(message: M) -> Self
+    where M: fmt::Display + fmt::Debug + Send + Sync + 'static {
+        Self::Other(anyhow::Error::msg(message))
+    }
+
+    /// Classifies one link of an error chain; `message` is the text of the
+    /// whole chain, kept for the string-carrying variants.
+    fn classify(cause: &(dyn StdError + 'static), message: &str) -> Option<Self> {
+        // A model error passed up with `?` hides its own chain.
+        if let Some(e) = cause.downcast_ref::<ProtocolError>() {
+            return e.chain().find_map(|link| Self::classify(link, message));
+        }
+        if let Some(e) = cause.downcast_ref::<ScsiStatusError>() {
+            return Some(Self::Scsi(e.clone()));
+        }
+        if let Some(e) = cause.downcast_ref::<LoginStatusError>() {
+            return Some(Self::LoginRejected(e.clone()));
+        }
+        if let Some(e) = cause.downcast_ref::<DigestMismatchError>() {
+            return Some(Self::DigestMismatch(e.clone()));
+        }
+        if let Some(e) = cause.downcast_ref::<AmbiguousOutcomeError>() {
+            return Some(Self::AmbiguousOutcome(AmbiguousOutcomeError {
+                tsih: e.tsih,
+                cid: e.cid,
+                reason: e.reason.clone(),
+            }));
+        }
+        if let Some(e) = cause.downcast_ref::<ReservationConflictError>() {
+            return Some(Self::ReservationConflict(e.clone()));
+        }
+        if let Some(e) = cause.downcast_ref::<TaskTerminatedError>() {
+            return Some(Self::TaskTerminated(TaskTerminatedError { itt: e.itt }));
+        }
+        if cause.is::<CommandTimeoutError>() || cause.is::<DeadlineExceededError>() {
+            return Some(Self::Timeout(message.to_string()));
+        }
+        if let Some(e) = cause.downcast_ref::<ClientIoError>() {
+            return Some(match e {
+                ClientIoError::Timeout { .. } => Self::Timeout(message.to_string()),
+                ClientIoError::Cancelled { .. } => Self::Cancelled(message.to_string()),
+            });
+        }
+        if cause.is::<DisconnectError>() {
+            return Some(Self::Disconnected(message.to_string()));
+        }
+        if cause.is::<ShutdownError>() {
+            return Some(Self::Cancelled(message.to_string()));
+        }
+        if cause.is::<UnknownOpcode>()
+            || cause.is::<UnknownScsiStatus>()
+            || cause.is::<UnknownResponseCode>()
+        {
+            return Some(Self::ProtocolViolation {
+                message: message.to_string(),
+                pdu: None,
+            });
+        }
+        None
+    }
+
+    /// Header of the received PDU the error is about, if one was captured.
+    pub fn pdu(&self) -> Option<&PduSnapshot> {
+        match self {
+            Self::ProtocolViolation { pdu, .. } => pdu.as_ref(),
+            Self::DigestMismatch(e) => Some(&e.bhs),
+            _ => None,
+        }
+    }
+
+    /// The [`io::ErrorKind`] this error maps to when surfaced as an
+    /// [`io::Error`].
+    pub fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            Self::ProtocolViolation { .. } | Self::DigestMismatch(_) => {
+                io::ErrorKind::InvalidData
+            },
+            Self::Scsi(e) => scsi_io_kind(e),
+            Self::LoginRejected(e) => {
+                match (e.class, InitiatorErrorDetail::try_from(e.detail)) {
+                    (
+                        StatusClass::InitiatorError,
+                        Ok(
+                            InitiatorErrorDetail::AuthFailed
+                            | InitiatorErrorDetail::AuthzFailed,
+                        ),
+                    ) => io::ErrorKind::PermissionDenied,
+                    (
+                        StatusClass::InitiatorError,
+                        Ok(
+                            InitiatorErrorDetail::NotFound
+                            | InitiatorErrorDetail::TargetRemoved,
+                        ),
+                    ) => io::ErrorKind::NotFound,
+                    _ => io::ErrorKind::ConnectionRefused,
+                }
+            },
+            Self::SessionLogin(_) | Self::TargetLogin(_) => {
+                io::ErrorKind::ConnectionRefused
+            },
+            Self::Timeout(_) => io::ErrorKind::TimedOut,
+            Self::Disconnected(_) | Self::AmbiguousOutcome(_) => {
+                io::ErrorKind::BrokenPipe
+            },
+            Self::Cancelled(_) => io::ErrorKind::ConnectionAborted,
+            Self::ReservationConflict(_) => io::ErrorKind::PermissionDenied,
+            Self::TaskTerminated(_) => io::ErrorKind::Other,
+            Self::Other(e) => e
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<io::Error>())
+                .map_or(io::ErrorKind::Other, io::Error::kind),
+        }
+    }
+}
+
+/// Sense keys (SPC-4 § 4.5.6) with a specific [`io::ErrorKind`].
+const SENSE_KEY_ILLEGAL_REQUEST: u8 = 0x05;
+const SENSE_KEY_DATA_PROTECT: u8 = 0x07;
+const SENSE_KEY_VOLUME_OVERFLOW: u8 = 0x0d;
+
+/// ASCs refining ILLEGAL REQUEST / DATA PROTECT.
+const ASC_LBA_OUT_OF_RANGE: u8 = 0x21;
+const ASC_INVALID_FIELD_IN_CDB: u8 = 0x24;
+const ASC_LUN_NOT_SUPPORTED: u8 = 0x25;
+const ASC_WRITE_PROTECTED: u8 = 0x27;
+
+fn scsi_io_kind(e: &ScsiStatusError) -> io::ErrorKind {
+    match e.status {
+        ScsiStatus::Busy | ScsiStatus::TaskSetFull => return io::ErrorKind::ResourceBusy,
+        ScsiStatus::ReservationConflict => return io::ErrorKind::PermissionDenied,
+        _ => {},
+    }
+    match e.sense_codes() {
+        Some((SENSE_KEY_ILLEGAL_REQUEST, ASC_LUN_NOT_SUPPORTED, _)) => {
+            io::ErrorKind::NotFound
+        },
+        Some((
+            SENSE_KEY_ILLEGAL_REQUEST,
+            ASC_LBA_OUT_OF_RANGE | ASC_INVALID_FIELD_IN_CDB,
+            _,
+        )) => io::ErrorKind::InvalidInput,
+        Some((SENSE_KEY_ILLEGAL_REQUEST, ..)) => io::ErrorKind::Unsupported,
+        Some((SENSE_KEY_DATA_PROTECT, ASC_WRITE_PROTECTED, _)) => {
+            io::ErrorKind::ReadOnlyFilesystem
+        },
+        Some((SENSE_KEY_DATA_PROTECT, ..)) => io::ErrorKind::PermissionDenied,
+        Some((SENSE_KEY_VOLUME_OVERFLOW, ..)) => io::ErrorKind::StorageFull,
+        _ => io::ErrorKind::Other,
+    }
+}
+
+impl From<anyhow::Error> for IscsiError {
+    fn from(error: anyhow::Error) -> Self {
+        // Already classified by an inner call and passed up with `?`.
+        let error = match error.downcast::<IscsiError>() {
+            Ok(classified) => return classified,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<SessionLoginError>() {
+            Ok(partial) => return Self::SessionLogin(partial),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<TargetLoginError>() {
+            Ok(partial) => return Self::TargetLogin(partial),
+            Err(error) => error,
+        };
+        let message = format!("{error:#}");
+        let classified = error
+            .chain()
+            .find_map(|cause| Self::classify(cause, &message));
+        // The read path attaches a snapshot only to PDUs it rejected.
+        let pdu = error.downcast_ref::<PduSnapshot>().cloned();
+        match (classified, pdu) {
+            (Some(Self::ProtocolViolation { message, .. }), pdu) => {
+                Self::ProtocolViolation { message, pdu }
+            },
+            (Some(classified), _) => classified,
+            (None, Some(pdu)) => Self::ProtocolViolation {
+                message,
+                pdu: Some(pdu),
+            },
+            (None, None) => Self::Other(error),
+        }
+    }
+}
+
+impl From<ProtocolError> for IscsiError {
+    fn from(error: ProtocolError) -> Self {
+        Self::from(error.into_anyhow())
+    }
+}
+
+impl From<io::Error> for IscsiError {
+    fn from(error: io::Error) -> Self {
+        // Unwrap an `IscsiError` that was converted into an `io::Error`.
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<IscsiError>())
+        {
+            let inner = error.into_inner().expect("checked above");
+            return *inner.downcast::<IscsiError>().expect("checked above");
+        }
+        Self::Other(error.into())
+    }
+}
+
+impl From<IscsiError> for io::Error {
+    fn from(error: IscsiError) -> Self {
+        io::Error::new(error.io_kind(), error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Context, anyhow};
+
+    use super::*;
+    use crate::models::identifiers::{Cid, Tsih};
+
+    /// Fixed-format sense data carrying `key`/`asc`/`ascq`.
+    fn check_condition(key: u8, asc: u8, ascq: u8) -> IscsiError {
+        let mut sense = [0u8; 18];
+        sense[0] = 0x70;
+        sense[2] = key;
+        sense[7] = 10;
+        sense[12] = asc;
+        sense[13] = ascq;
+        IscsiError::Scsi(ScsiStatusError::new(
+            0x28,
+            ScsiStatus::CheckCondition,
+            &sense,
+        ))
+    }
+
+    #[test]
+    fn classifies_typed_causes_through_context() {
+        let scsi: anyhow::Error =
+            ScsiStatusError::new(0x28, ScsiStatus::Busy, &[]).into();
+        let err = IscsiError::from(scsi.context("READ(10) at LBA 0"));
+        assert!(matches!(
+            err,
+            IscsiError::Scsi(ScsiStatusError {
+                status: ScsiStatus::Busy,
+                ..
+            })
+        ));
+
+        let timeout = Err::<(), _>(DeadlineExceededError {
+            tsih: Tsih::new(1),
+            cid: Cid::ZERO,
+        })
+        .context("flush")
+        .expect_err("error");
+        match IscsiError::from(timeout) {
+            IscsiError::Timeout(message) => {
+                assert!(message.starts_with("flush: deadline"))
+            },
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let other = IscsiError::from(anyhow!("no target portal configured"));
+        assert!(matches!(other, IscsiError::Other(_)));
+        assert_eq!(other.to_string(), "no target portal configured");
+    }
+
+    #[test]
+    fn classified_errors_survive_a_round_trip_through_anyhow() {
+        let err = IscsiError::Disconnected("connection lost: EOF".into());
+        let again = IscsiError::from(anyhow::Error::from(err));
+        assert!(
+            matches!(again, IscsiError::Disconnected(m) if m == "connection lost: EOF")
+        );
+    }
+
+    #[test]
+    fn maps_to_io_error_kinds() {
+        let cases = [
+            (check_condition(0x05, 0x25, 0x00), io::ErrorKind::NotFound),
+            (
+                check_condition(0x05, 0x20, 0x00),
+                io::ErrorKind::Unsupported,
+            ),
+            (
+                check_condition(0x05, 0x21, 0x00),
+                io::ErrorKind::InvalidInput,
+            ),
+            (
+                check_condition(0x07, 0x27, 0x00),
+                io::ErrorKind::ReadOnlyFilesystem,
+            ),
+            (check_condition(0x03, 0x11, 0x00), io::ErrorKind::Other),
+            (
+                IscsiError::Scsi(ScsiStatusError::new(0x2a, ScsiStatus::Busy, &[])),
+                io::ErrorKind::ResourceBusy,
+            ),
+            (
+                IscsiError::Timeout("deadline".into()),
+                io::ErrorKind::TimedOut,
+            ),
+            (
+                IscsiError::Disconnected("EOF".into()),
+                io::ErrorKind::BrokenPipe,
+            ),
+            (
+                IscsiError::LoginRejected(LoginStatusError {
+                    class: StatusClass::InitiatorError,
+                    detail: 0x01,
+                }),
+                io::ErrorKind::PermissionDenied,
+            ),
+            (
+                IscsiError::from(
+                    anyhow::Error::from(io::Error::from(io::ErrorKind::ConnectionReset))
+                        .context("connect"),
+                ),
+                io::ErrorKind::ConnectionReset,
+            ),
+        ];
+        for (err, kind) in cases {
+            assert_eq!(io::Error::from(err).kind(), kind);
+        }
+    }
+
+    #[test]
+    fn io_error_keeps_the_iscsi_error() {
+        let io_err = io::Error::from(check_condition(0x05, 0x25, 0x00));
+        assert!(
+            io_err
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<IscsiError>())
+                .is_some_and(|inner| matches!(inner, IscsiError::Scsi(_)))
+        );
+        assert!(matches!(IscsiError::from(io_err), IscsiError::Scsi(_)));
+    }
+
+    #[test]
+    fn rejected_pdus_become_protocol_violations_with_their_bhs() {
+        let mut bhs = [0u8; 48];
+        bhs[0] = 0x1f;
+        bhs[19] = 9;
+        let err = IscsiError::from(
+            anyhow!("invalid opcode: 0x1f").context(PduSnapshot::new(&bhs)),
+        );
+        match &err {
+            IscsiError::ProtocolViolation { message, pdu } => {
+                assert!(message.ends_with(": invalid opcode: 0x1f"), "{message}");
+                assert_eq!(pdu.as_ref().map(PduSnapshot::itt), Some(9));
+            },
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(err.pdu().map(PduSnapshot::raw_opcode), Some(0x1f));
+    }
+
+    #[test]
+    fn reservation_conflicts_name_the_holder() {
+        use crate::control_block::persistent_reserve::{
+            PrOutAction, PrReservation, PrType,
+        };
+
+        let conflict = ReservationConflictError {
+            action: PrOutAction::Reserve,
+            key: 0xA,
+            holder: Some(PrReservation {
+                key: 0xB,
+                scope: 0,
+                pr_type: PrType::WriteExclusiveRegistrantsOnly,
+            }),
+        };
+        let err = IscsiError::from(anyhow::Error::from(conflict).context("fence"));
+        assert!(err.to_string().contains("held by key 0x000000000000000B"));
+        assert!(matches!(err, IscsiError::ReservationConflict(_)));
+        assert_eq!(err.io_kind(), io::ErrorKind::PermissionDenied);
+    }
+}
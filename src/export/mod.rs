@@ -0,0 +1,141 @@
+//! Exporters presenting a LUN to local consumers.
+//!
+//! An exporter serves a [`BlockBackend`] — normally an
+//! [`IscsiDevice`] — through an interface other tools already speak (NBD, or
+//! a local `/dev/ublkb*` block device), so the LUN can be used by `qemu`,
+//! `mkfs` or `mount` without a kernel initiator.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::future::Future;
+
+use crate::{client::device::IscsiDevice, error::Result};
+
+/// NBD server bridge (`nbd` feature).
+#[cfg(feature = "nbd")]
+pub mod nbd;
+/// ublk block device exporter (Linux, `ublk` feature).
+#[cfg(all(target_os = "linux", feature = "ublk"))]
+pub mod ublk;
+
+/// Byte-addressed storage an exporter can serve.
+pub trait BlockBackend: Send + Sync + 'static {
+    /// Size in bytes.
+    fn size(&self) -> u64;
+
+    /// Preferred I/O granularity in bytes.
+    fn block_size(&self) -> u32;
+
+    /// Read `len` bytes at `offset`.
+    fn read(
+        &self,
+        offset: u64,
+        len: usize,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// Write `data` at `offset`.
+    fn write(&self, offset: u64, data: &[u8]) -> impl Future<Output = Result<()>> + Send;
+
+    /// Make completed writes durable.
+    fn flush(&self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Make completed writes to `len` bytes at `offset` durable. Flushes
+    /// everything unless the backend can do better.
+    fn flush_range(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = (offset, len);
+        self.flush()
+    }
+
+    /// Hint that `len` bytes at `offset` are no longer needed.
+    fn discard(&self, offset: u64, len: u64) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl BlockBackend for IscsiDevice {
+    fn size(&self) -> u64 {
+        IscsiDevice::size(self)
+    }
+
+    fn block_size(&self) -> u32 {
+        IscsiDevice::block_size(self)
+    }
+
+    async fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        IscsiDevice::read(self, offset, len).await
+    }
+
+    async fn write(&self, offset: u64, data: &[u8]) -> Result<()> {
+        IscsiDevice::write(self, offset, data).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        IscsiDevice::flush(self).await
+    }
+
+    async fn flush_range(&self, offset: u64, len: u64) -> Result<()> {
+        IscsiDevice::flush_range(self, offset, len).await
+    }
+
+    async fn discard(&self, offset: u64, len: u64) -> Result<()> {
+        IscsiDevice::discard(self, offset, len).await
+    }
+}
+
+#[cfg(all(test, any(feature = "nbd", feature = "ublk")))]
+pub(crate) mod test_backend {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// In-memory backend recording flushes and discards.
+    #[derive(Default)]
+    pub(crate) struct MemBackend {
+        pub(crate) data: Mutex<Vec<u8>>,
+        pub(crate) flushes: Mutex<u32>,
+        pub(crate) discarded: Mutex<Vec<(u64, u64)>>,
+    }
+
+    impl MemBackend {
+        pub(crate) fn new(data: Vec<u8>) -> Self {
+            Self {
+                data: Mutex::new(data),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl BlockBackend for MemBackend {
+        fn size(&self) -> u64 {
+            self.data.lock().expect("lock").len() as u64
+        }
+
+        fn block_size(&self) -> u32 {
+            512
+        }
+
+        async fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+            let data = self.data.lock().expect("lock");
+            Ok(data[offset as usize..offset as usize + len].to_vec())
+        }
+
+        async fn write(&self, offset: u64, buf: &[u8]) -> Result<()> {
+            let mut data = self.data.lock().expect("lock");
+            data[offset as usize..offset as usize + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+
+        async fn flush(&self) -> Result<()> {
+            *self.flushes.lock().expect("lock") += 1;
+            Ok(())
+        }
+
+        async fn discard(&self, offset: u64, len: u64) -> Result<()> {
+            self.discarded.lock().expect("lock").push((offset, len));
+            Ok(())
+        }
+    }
+}
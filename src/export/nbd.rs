@@ -0,0 +1,586 @@
+//! NBD server bridge.
+//!
+//! [`NbdServer`] speaks the fixed-newstyle Network Block Device protocol and
+//! maps NBD READ / WRITE / FLUSH / TRIM onto the backend, which for an
+//! [`IscsiDevice`](crate::client::device::IscsiDevice) become READ, WRITE,
+//! SYNCHRONIZE CACHE and UNMAP. It serves one export; `nbd-client`,
+//! `qemu-img` and `qemu -drive nbd:...` can connect to it directly.
+//!
+//! Every client connection is served by its own task, so clients (and the
+//! several connections of `nbd-client -C`) reach the backend concurrently;
+//! only the requests of one connection are handled in order.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{io::ErrorKind, sync::Arc};
+
+use anyhow::{Context, Result, bail, ensure};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream},
+    net::TcpListener,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::{
+    error::{self, IscsiError},
+    export::BlockBackend,
+};
+
+/// Port registered for NBD.
+pub const NBD_DEFAULT_PORT: u16 = 10809;
+
+/// Largest READ or WRITE accepted in one request.
+pub const NBD_MAX_REQUEST_LEN: u32 = 32 << 20;
+
+/// Largest option payload accepted during the handshake.
+const MAX_OPTION_LEN: u32 = 64 << 10;
+
+const NBDMAGIC: u64 = 0x4e42_444d_4147_4943;
+const IHAVEOPT: u64 = 0x4948_4156_454F_5054;
+const OPTION_REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+const REQUEST_MAGIC: u32 = 0x2560_9513;
+const SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+// Handshake flags.
+const FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const FLAG_NO_ZEROES: u16 = 1 << 1;
+const FLAG_C_FIXED_NEWSTYLE: u32 = 1 << 0;
+const FLAG_C_NO_ZEROES: u32 = 1 << 1;
+
+// Transmission flags.
+const FLAG_HAS_FLAGS: u16 = 1 << 0;
+const FLAG_READ_ONLY: u16 = 1 << 1;
+const FLAG_SEND_FLUSH: u16 = 1 << 2;
+const FLAG_SEND_FUA: u16 = 1 << 3;
+const FLAG_SEND_TRIM: u16 = 1 << 5;
+
+// Options.
+const OPT_EXPORT_NAME: u32 = 1;
+const OPT_ABORT: u32 = 2;
+const OPT_LIST: u32 = 3;
+const OPT_INFO: u32 = 6;
+const OPT_GO: u32 = 7;
+
+// Option replies.
+const REP_ACK: u32 = 1;
+const REP_SERVER: u32 = 2;
+const REP_INFO: u32 = 3;
+const REP_ERR_UNSUP: u32 = (1 << 31) + 1;
+const REP_ERR_INVALID: u32 = (1 << 31) + 3;
+const REP_ERR_UNKNOWN: u32 = (1 << 31) + 6;
+
+// NBD_REP_INFO types.
+const INFO_EXPORT: u16 = 0;
+const INFO_BLOCK_SIZE: u16 = 3;
+
+// Commands.
+const CMD_READ: u16 = 0;
+const CMD_WRITE: u16 = 1;
+const CMD_DISC: u16 = 2;
+const CMD_FLUSH: u16 = 3;
+const CMD_TRIM: u16 = 4;
+const CMD_FLAG_FUA: u16 = 1 << 0;
+
+// Errors (errno values).
+const EPERM: u32 = 1;
+const EIO: u32 = 5;
+const EINVAL: u32 = 22;
+const ENOSPC: u32 = 28;
+const ENOTSUP: u32 = 95;
+const ESHUTDOWN: u32 = 108;
+
+/// Serves one [`BlockBackend`] as an NBD export.
+#[derive(Debug)]
+pub struct NbdServer<B> {
+    backend: B,
+    export_name: String,
+    read_only: bool,
+}
+
+impl<B: BlockBackend> NbdServer<B> {
+    /// Export `backend` read-write under the empty (default) name.
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            export_name: String::new(),
+            read_only: false,
+        }
+    }
+
+    /// Name clients must ask for. The empty name is always accepted too.
+    pub fn with_export_name(mut self, name: impl Into<String>) -> Self {
+        self.export_name = name.into();
+        self
+    }
+
+    /// Refuse WRITE and TRIM.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// The exported backend.
+    #[inline]
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Accept clients on `listener` until `cancel` fires. Each client is
+    /// served by its own task.
+    pub async fn serve(
+        self: Arc<Self>,
+        listener: TcpListener,
+        cancel: CancellationToken,
+    ) -> Result<()> {
+        loop {
+            let (stream, peer) = tokio::select! {
+                _ = cancel.cancelled() => return Ok(()),
+                r = listener.accept() => r.context("NBD accept failed")?,
+            };
+            let _ = stream.set_nodelay(true);
+            let this = Arc::clone(&self);
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = cancel.cancelled() => {},
+                    r = this.serve_client(stream) => match r {
+                        Ok(()) => debug!(%peer, "NBD client disconnected"),
+                        Err(e) => warn!(%peer, "NBD client failed: {e:#}"),
+                    },
+                }
+            });
+        }
+    }
+
+    /// Run the handshake and transmission phases over one client stream.
+    pub async fn serve_client<S>(&self, stream: S) -> Result<()>
+    where S: AsyncRead + AsyncWrite + Unpin {
+        let mut s = BufStream::new(stream);
+        if self.handshake(&mut s).await? {
+            self.transmission(&mut s).await?;
+        }
+        Ok(())
+    }
+
+    fn transmission_flags(&self) -> u16 {
+        let mut flags = FLAG_HAS_FLAGS | FLAG_SEND_FLUSH | FLAG_SEND_FUA;
+        if self.read_only {
+            flags |= FLAG_READ_ONLY;
+        } else {
+            flags |= FLAG_SEND_TRIM;
+        }
+        flags
+    }
+
+    fn serves(&self, name: &[u8]) -> bool {
+        name.is_empty() || name == self.export_name.as_bytes()
+    }
+
+    /// Option haggling. Returns `false` when the client aborted.
+    async fn handshake<S>(&self, s: &mut BufStream<S>) -> Result<bool>
+    where S: AsyncRead + AsyncWrite + Unpin {
+        s.write_u64(NBDMAGIC).await?;
+        s.write_u64(IHAVEOPT).await?;
+        s.write_u16(FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES).await?;
+        s.flush().await?;
+
+        let client_flags = s.read_u32().await?;
+        ensure!(
+            client_flags & FLAG_C_FIXED_NEWSTYLE != 0,
+            "NBD client does not support fixed newstyle negotiation"
+        );
+        let no_zeroes = client_flags & FLAG_C_NO_ZEROES != 0;
+
+        loop {
+            let magic = s.read_u64().await?;
+            ensure!(magic == IHAVEOPT, "bad NBD option magic {magic:#x}");
+            let opt = s.read_u32().await?;
+            let len = s.read_u32().await?;
+            ensure!(
+                len <= MAX_OPTION_LEN,
+                "NBD option {opt} too long: {len} bytes"
+            );
+            let mut data = vec![0u8; len as usize];
+            s.read_exact(&mut data).await?;
+
+            match opt {
+                OPT_EXPORT_NAME => {
+                    if !self.serves(&data) {
+                        bail!("unknown NBD export {:?}", String::from_utf8_lossy(&data));
+                    }
+                    s.write_u64(self.backend.size()).await?;
+                    s.write_u16(self.transmission_flags()).await?;
+                    if !no_zeroes {
+                        s.write_all(&[0u8; 124]).await?;
+                    }
+                    s.flush().await?;
+                    return Ok(true);
+                },
+                OPT_ABORT => {
+                    reply_option(s, opt, REP_ACK, &[]).await?;
+                    return Ok(false);
+                },
+                OPT_LIST => {
+                    let name = self.export_name.as_bytes();
+                    let mut payload = Vec::with_capacity(4 + name.len());
+                    payload.extend_from_slice(&(name.len() as u32).to_be_bytes());
+                    payload.extend_from_slice(name);
+                    reply_option(s, opt, REP_SERVER, &payload).await?;
+                    reply_option(s, opt, REP_ACK, &[]).await?;
+                },
+                OPT_INFO | OPT_GO => {
+                    let Some((name, infos)) = parse_info_request(&data) else {
+                        reply_option(s, opt, REP_ERR_INVALID, &[]).await?;
+                        continue;
+                    };
+                    if !self.serves(name) {
+                        reply_option(s, opt, REP_ERR_UNKNOWN, &[]).await?;
+                        continue;
+                    }
+
+                    let mut export = Vec::with_capacity(12);
+                    export.extend_from_slice(&INFO_EXPORT.to_be_bytes());
+                    export.extend_from_slice(&self.backend.size().to_be_bytes());
+                    export.extend_from_slice(&self.transmission_flags().to_be_bytes());
+                    reply_option(s, opt, REP_INFO, &export).await?;
+
+                    if infos.contains(&INFO_BLOCK_SIZE) {
+                        let mut sizes = Vec::with_capacity(14);
+                        sizes.extend_from_slice(&INFO_BLOCK_SIZE.to_be_bytes());
+                        sizes.extend_from_slice(&self.backend.block_size().to_be_bytes());
+                        sizes.extend_from_slice(&self.backend.block_size().to_be_bytes());
+                        sizes.extend_from_slice(&NBD_MAX_REQUEST_LEN.to_be_bytes());
+                        reply_option(s, opt, REP_INFO, &sizes).await?;
+                    }
+                    reply_option(s, opt, REP_ACK, &[]).await?;
+                    if opt == OPT_GO {
+                        return Ok(true);
+                    }
+                },
+                _ => reply_option(s, opt, REP_ERR_UNSUP, &[]).await?,
+            }
+        }
+    }
+
+    async fn transmission<S>(&self, s: &mut BufStream<S>) -> Result<()>
+    where S: AsyncRead + AsyncWrite + Unpin {
+        loop {
+            let magic = match s.read_u32().await {
+                Ok(m) => m,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            ensure!(magic == REQUEST_MAGIC, "bad NBD request magic {magic:#x}");
+            let flags = s.read_u16().await?;
+            let cmd = s.read_u16().await?;
+            let cookie = s.read_u64().await?;
+            let offset = s.read_u64().await?;
+            let len = s.read_u32().await?;
+            let in_range = offset
+                .checked_add(len as u64)
+                .is_some_and(|end| end <= self.backend.size());
+
+            match cmd {
+                CMD_READ => {
+                    if !in_range || len > NBD_MAX_REQUEST_LEN {
+                        reply(s, cookie, EINVAL, &[]).await?;
+                        continue;
+                    }
+                    match self.backend.read(offset, len as usize).await {
+                        Ok(data) => reply(s, cookie, 0, &data).await?,
+                        Err(e) => {
+                            warn!("NBD read {offset}+{len} failed: {e:#}");
+                            reply(s, cookie, error_errno(e), &[]).await?;
+                        },
+                    }
+                },
+                CMD_WRITE => {
+                    if len > NBD_MAX_REQUEST_LEN {
+                        // Skip the payload to stay in step with the client.
+                        let mut payload = (&mut *s).take(len as u64);
+                        tokio::io::copy(&mut payload, &mut tokio::io::sink()).await?;
+                        ensure!(payload.limit() == 0, "NBD write payload ended early");
+                        reply(s, cookie, EINVAL, &[]).await?;
+                        continue;
+                    }
+                    let mut data = vec![0u8; len as usize];
+                    s.read_exact(&mut data).await?;
+                    let error = if self.read_only {
+                        EPERM
+                    } else if !in_range {
+                        ENOSPC
+                    } else {
+                        let mut r = self.backend.write(offset, &data).await;
+                        if r.is_ok() && flags & CMD_FLAG_FUA != 0 {
+                            r = self.backend.flush_range(offset, len as u64).await;
+                        }
+                        errno(r, "write", offset, len)
+                    };
+                    reply(s, cookie, error, &[]).await?;
+                },
+                CMD_DISC => return Ok(()),
+                CMD_FLUSH => {
+                    let error = errno(self.backend.flush().await, "flush", offset, len);
+                    reply(s, cookie, error, &[]).await?;
+                },
+                CMD_TRIM => {
+                    let error = if self.read_only {
+                        EPERM
+                    } else if !in_range {
+                        EINVAL
+                    } else {
+                        let r = self.backend.discard(offset, len as u64).await;
+                        errno(r, "trim", offset, len)
+                    };
+                    reply(s, cookie, error, &[]).await?;
+                },
+                _ => reply(s, cookie, EINVAL, &[]).await?,
+            }
+        }
+    }
+}
+
+/// Split an NBD_OPT_INFO / NBD_OPT_GO payload into the export name and the
+/// requested information types.
+fn parse_info_request(data: &[u8]) -> Option<(&[u8], Vec<u16>)> {
+    let name_len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let name = data.get(4..4 + name_len)?;
+    let rest = &data[4 + name_len..];
+    let count = u16::from_be_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+    let list = rest.get(2..)?;
+    if list.len() != count * 2 {
+        return None;
+    }
+    let infos = list
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect();
+    Some((name, infos))
+}
+
+fn errno(result: error::Result<()>, what: &str, offset: u64, len: u32) -> u32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            warn!("NBD {what} {offset}+{len} failed: {e:#}");
+            error_errno(e)
+        },
+    }
+}
+
+/// The NBD error for a failed backend call, by the [`std::io::ErrorKind`] of
+/// its [`IscsiError`]. NBD only defines a few errno values; the rest are EIO.
+fn error_errno(error: IscsiError) -> u32 {
+    match error.io_kind() {
+        ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => EPERM,
+        ErrorKind::InvalidInput => EINVAL,
+        ErrorKind::StorageFull => ENOSPC,
+        ErrorKind::Unsupported => ENOTSUP,
+        ErrorKind::ConnectionAborted => ESHUTDOWN,
+        _ => EIO,
+    }
+}
+
+async fn reply_option<S>(
+    s: &mut BufStream<S>,
+    opt: u32,
+    kind: u32,
+    data: &[u8],
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    s.write_u64(OPTION_REPLY_MAGIC).await?;
+    s.write_u32(opt).await?;
+    s.write_u32(kind).await?;
+    s.write_u32(data.len() as u32).await?;
+    s.write_all(data).await?;
+    s.flush().await?;
+    Ok(())
+}
+
+async fn reply<S>(
+    s: &mut BufStream<S>,
+    cookie: u64,
+    error: u32,
+    data: &[u8],
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    s.write_u32(SIMPLE_REPLY_MAGIC).await?;
+    s.write_u32(error).await?;
+    s.write_u64(cookie).await?;
+    if error == 0 {
+        s.write_all(data).await?;
+    }
+    s.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{DuplexStream, duplex};
+
+    use super::*;
+    use crate::export::test_backend::MemBackend;
+
+    async fn send_option(c: &mut DuplexStream, opt: u32, data: &[u8]) -> Result<()> {
+        c.write_u64(IHAVEOPT).await?;
+        c.write_u32(opt).await?;
+        c.write_u32(data.len() as u32).await?;
+        c.write_all(data).await?;
+        Ok(())
+    }
+
+    /// Read one option reply: (option, type, payload).
+    async fn read_option_reply(c: &mut DuplexStream) -> Result<(u32, u32, Vec<u8>)> {
+        assert_eq!(c.read_u64().await?, OPTION_REPLY_MAGIC);
+        let opt = c.read_u32().await?;
+        let kind = c.read_u32().await?;
+        let mut data = vec![0u8; c.read_u32().await? as usize];
+        c.read_exact(&mut data).await?;
+        Ok((opt, kind, data))
+    }
+
+    async fn request(
+        c: &mut DuplexStream,
+        cmd: u16,
+        flags: u16,
+        cookie: u64,
+        offset: u64,
+        len: u32,
+    ) -> Result<()> {
+        c.write_u32(REQUEST_MAGIC).await?;
+        c.write_u16(flags).await?;
+        c.write_u16(cmd).await?;
+        c.write_u64(cookie).await?;
+        c.write_u64(offset).await?;
+        c.write_u32(len).await?;
+        Ok(())
+    }
+
+    /// Read a simple reply header: (error, cookie).
+    async fn read_reply(c: &mut DuplexStream) -> Result<(u32, u64)> {
+        assert_eq!(c.read_u32().await?, SIMPLE_REPLY_MAGIC);
+        Ok((c.read_u32().await?, c.read_u64().await?))
+    }
+
+    fn start(
+        server: NbdServer<MemBackend>,
+    ) -> (DuplexStream, tokio::task::JoinHandle<Result<()>>) {
+        let (client, srv) = duplex(1 << 20);
+        let task = tokio::spawn(async move { server.serve_client(srv).await });
+        (client, task)
+    }
+
+    #[tokio::test]
+    async fn go_then_read_write_flush_trim() -> Result<()> {
+        let backend = MemBackend::new(vec![0u8; 4096]);
+        let (mut c, task) = start(NbdServer::new(backend).with_export_name("lun0"));
+
+        assert_eq!(c.read_u64().await?, NBDMAGIC);
+        assert_eq!(c.read_u64().await?, IHAVEOPT);
+        assert_eq!(c.read_u16().await?, FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES);
+        c.write_u32(FLAG_C_FIXED_NEWSTYLE | FLAG_C_NO_ZEROES)
+            .await?;
+
+        // Unknown export names are refused without ending the handshake.
+        let mut go = Vec::new();
+        go.extend_from_slice(&3u32.to_be_bytes());
+        go.extend_from_slice(b"bad");
+        go.extend_from_slice(&0u16.to_be_bytes());
+        send_option(&mut c, OPT_GO, &go).await?;
+        assert_eq!(read_option_reply(&mut c).await?.1, REP_ERR_UNKNOWN);
+
+        send_option(&mut c, 42, &[]).await?;
+        assert_eq!(read_option_reply(&mut c).await?.1, REP_ERR_UNSUP);
+
+        let mut go = Vec::new();
+        go.extend_from_slice(&4u32.to_be_bytes());
+        go.extend_from_slice(b"lun0");
+        go.extend_from_slice(&1u16.to_be_bytes());
+        go.extend_from_slice(&INFO_BLOCK_SIZE.to_be_bytes());
+        send_option(&mut c, OPT_GO, &go).await?;
+        let (opt, kind, export) = read_option_reply(&mut c).await?;
+        assert_eq!((opt, kind), (OPT_GO, REP_INFO));
+        assert_eq!(&export[2..10], &4096u64.to_be_bytes());
+        let flags = u16::from_be_bytes([export[10], export[11]]);
+        assert_eq!(flags & FLAG_SEND_TRIM, FLAG_SEND_TRIM);
+        let (_, kind, sizes) = read_option_reply(&mut c).await?;
+        assert_eq!(kind, REP_INFO);
+        assert_eq!(&sizes[2..6], &512u32.to_be_bytes());
+        assert_eq!(&sizes[6..10], &512u32.to_be_bytes());
+        assert_eq!(read_option_reply(&mut c).await?.1, REP_ACK);
+
+        request(&mut c, CMD_WRITE, CMD_FLAG_FUA, 1, 700, 5).await?;
+        c.write_all(b"hello").await?;
+        assert_eq!(read_reply(&mut c).await?, (0, 1));
+
+        request(&mut c, CMD_READ, 0, 2, 698, 9).await?;
+        assert_eq!(read_reply(&mut c).await?, (0, 2));
+        let mut buf = [0u8; 9];
+        c.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"\0\0hello\0\0");
+
+        request(&mut c, CMD_READ, 0, 3, 4000, 200).await?;
+        assert_eq!(read_reply(&mut c).await?, (EINVAL, 3));
+
+        // An oversized write is refused, and its payload skipped.
+        let len = NBD_MAX_REQUEST_LEN + 1;
+        request(&mut c, CMD_WRITE, 0, 7, 0, len).await?;
+        c.write_all(&vec![1u8; len as usize]).await?;
+        assert_eq!(read_reply(&mut c).await?, (EINVAL, 7));
+
+        request(&mut c, CMD_FLUSH, 0, 4, 0, 0).await?;
+        assert_eq!(read_reply(&mut c).await?, (0, 4));
+        request(&mut c, CMD_TRIM, 0, 5, 1024, 2048).await?;
+        assert_eq!(read_reply(&mut c).await?, (0, 5));
+
+        request(&mut c, CMD_DISC, 0, 6, 0, 0).await?;
+        task.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn export_name_and_read_only() -> Result<()> {
+        let backend = MemBackend::new(vec![7u8; 1024]);
+        let (mut c, task) = start(NbdServer::new(backend).read_only(true));
+
+        c.read_u64().await?;
+        c.read_u64().await?;
+        c.read_u16().await?;
+        c.write_u32(FLAG_C_FIXED_NEWSTYLE).await?;
+        send_option(&mut c, OPT_EXPORT_NAME, &[]).await?;
+        assert_eq!(c.read_u64().await?, 1024);
+        let flags = c.read_u16().await?;
+        assert_eq!(flags & FLAG_READ_ONLY, FLAG_READ_ONLY);
+        assert_eq!(flags & FLAG_SEND_TRIM, 0);
+        let mut zeroes = [1u8; 124];
+        c.read_exact(&mut zeroes).await?;
+        assert_eq!(zeroes, [0u8; 124]);
+
+        request(&mut c, CMD_WRITE, 0, 9, 0, 4).await?;
+        c.write_all(b"nope").await?;
+        assert_eq!(read_reply(&mut c).await?, (EPERM, 9));
+
+        drop(c);
+        task.await??;
+        Ok(())
+    }
+
+    #[test]
+    fn backend_errors_map_to_nbd_errno() {
+        assert_eq!(error_errno(IscsiError::Timeout("deadline".into())), EIO);
+        assert_eq!(
+            error_errno(IscsiError::Cancelled("shutdown".into())),
+            ESHUTDOWN
+        );
+        let unsupported =
+            anyhow::Error::from(std::io::Error::from(ErrorKind::Unsupported));
+        let unsupported = IscsiError::from(unsupported.context("WRITE SAME"));
+        assert_eq!(error_errno(unsupported), ENOTSUP);
+    }
+}
@@ -0,0 +1,778 @@
+//! ublk exporter (Linux, feature `ublk`).
+//!
+//! [`UblkServer`] registers a userspace block device with the kernel's ublk
+//! driver and serves its requests from a [`BlockBackend`], so a LUN shows up
+//! as `/dev/ublkb<N>` and can be partitioned, formatted and mounted.
+//!
+//! Device management goes through `/dev/ublk-control` (ADD_DEV, SET_PARAMS,
+//! START_DEV, STOP_DEV, DEL_DEV). Each hardware queue is served by its own
+//! thread and io_uring: the thread keeps one FETCH_REQ outstanding per tag,
+//! hands every request to a tokio task that runs it against the backend,
+//! and commits the result (COMMIT_AND_FETCH_REQ) when the task signals the
+//! queue's eventfd. Requests of one queue therefore run concurrently, up to
+//! the queue depth.
+//!
+//! Needs the `ublk_drv` module and `CAP_SYS_ADMIN`.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    path::PathBuf,
+    ptr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use io_uring::{IoUring, cqueue, opcode, squeue, types};
+use tokio::runtime::Handle;
+use tracing::{info, warn};
+
+use crate::{error::IscsiError, export::BlockBackend};
+
+const CONTROL_PATH: &str = "/dev/ublk-control";
+
+/// `_IOWR('u', nr, size)`, the encoding of ublk commands with
+/// `UBLK_F_CMD_IOCTL_ENCODE`.
+const fn iowr(nr: u32, size: u32) -> u32 {
+    (3 << 30) | (size << 16) | ((b'u' as u32) << 8) | nr
+}
+
+/// `sizeof(struct ublksrv_ctrl_cmd)`.
+const CTRL_CMD_LEN: u32 = 32;
+/// `sizeof(struct ublksrv_io_cmd)`.
+const IO_CMD_LEN: u32 = 16;
+
+const U_CMD_ADD_DEV: u32 = iowr(0x04, CTRL_CMD_LEN);
+const U_CMD_DEL_DEV: u32 = iowr(0x05, CTRL_CMD_LEN);
+const U_CMD_START_DEV: u32 = iowr(0x06, CTRL_CMD_LEN);
+const U_CMD_STOP_DEV: u32 = iowr(0x07, CTRL_CMD_LEN);
+const U_CMD_SET_PARAMS: u32 = iowr(0x08, CTRL_CMD_LEN);
+const U_IO_FETCH_REQ: u32 = iowr(0x20, IO_CMD_LEN);
+const U_IO_COMMIT_AND_FETCH_REQ: u32 = iowr(0x21, IO_CMD_LEN);
+
+const F_CMD_IOCTL_ENCODE: u64 = 1 << 6;
+
+const ATTR_READ_ONLY: u32 = 1 << 0;
+const ATTR_VOLATILE_CACHE: u32 = 1 << 2;
+const ATTR_FUA: u32 = 1 << 3;
+const PARAM_TYPE_BASIC: u32 = 1 << 0;
+const PARAM_TYPE_DISCARD: u32 = 1 << 1;
+/// `ublk_params` up to and including the discard parameters.
+const PARAMS_LEN: u16 = 60;
+
+const OP_READ: u8 = 0;
+const OP_WRITE: u8 = 1;
+const OP_FLUSH: u8 = 2;
+const OP_DISCARD: u8 = 3;
+const IO_F_FUA: u32 = 1 << 13;
+
+/// Completion result of a FETCH_REQ when the device is going away.
+const IO_RES_ABORT: i32 = -libc::ENODEV;
+const MAX_QUEUE_DEPTH: usize = 4096;
+const SECTOR_SHIFT: u32 = 9;
+/// `user_data` of the queue eventfd read; tags are below 4096.
+const EVENT_TAG: u64 = u64::MAX;
+
+/// `struct ublksrv_ctrl_dev_info`.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct DevInfo {
+    nr_hw_queues: u16,
+    queue_depth: u16,
+    state: u16,
+    pad0: u16,
+    max_io_buf_bytes: u32,
+    dev_id: u32,
+    ublksrv_pid: i32,
+    pad1: u32,
+    flags: u64,
+    ublksrv_flags: u64,
+    owner_uid: u32,
+    owner_gid: u32,
+    reserved1: u64,
+    reserved2: u64,
+}
+
+/// `struct ublk_param_basic`.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct ParamBasic {
+    attrs: u32,
+    logical_bs_shift: u8,
+    physical_bs_shift: u8,
+    io_opt_shift: u8,
+    io_min_shift: u8,
+    max_sectors: u32,
+    chunk_sectors: u32,
+    dev_sectors: u64,
+    virt_boundary_mask: u64,
+}
+
+/// `struct ublk_param_discard`.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct ParamDiscard {
+    discard_alignment: u32,
+    discard_granularity: u32,
+    max_discard_sectors: u32,
+    max_write_zeroes_sectors: u32,
+    max_discard_segments: u16,
+    reserved0: u16,
+}
+
+/// The leading part of `struct ublk_params`.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct Params {
+    len: u32,
+    types: u32,
+    basic: ParamBasic,
+    discard: ParamDiscard,
+}
+
+/// `struct ublksrv_io_desc`, as mapped from the char device.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct IoDesc {
+    op_flags: u32,
+    nr_sectors: u32,
+    start_sector: u64,
+    addr: u64,
+}
+
+const _: () = assert!(size_of::<DevInfo>() == 64);
+const _: () = assert!(size_of::<ParamBasic>() == 32);
+const _: () = assert!(size_of::<ParamDiscard>() == 20);
+const _: () = assert!(size_of::<IoDesc>() == 24);
+
+impl IoDesc {
+    fn op(&self) -> u8 {
+        self.op_flags as u8
+    }
+}
+
+/// Builds a ublk device for one [`BlockBackend`].
+#[derive(Debug)]
+pub struct UblkServer<B> {
+    backend: Arc<B>,
+    queues: u16,
+    queue_depth: u16,
+    max_io_bytes: u32,
+    read_only: bool,
+}
+
+impl<B: BlockBackend> UblkServer<B> {
+    /// One queue of depth 64, 512 KiB per request, read-write.
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            queues: 1,
+            queue_depth: 64,
+            max_io_bytes: 512 << 10,
+            read_only: false,
+        }
+    }
+
+    /// Number of hardware queues (one thread each).
+    pub fn with_queues(mut self, queues: u16) -> Self {
+        self.queues = queues.max(1);
+        self
+    }
+
+    /// Requests in flight per queue.
+    pub fn with_queue_depth(mut self, depth: u16) -> Self {
+        self.queue_depth = depth.clamp(1, MAX_QUEUE_DEPTH as u16);
+        self
+    }
+
+    /// Largest request the kernel sends, in bytes (rounded down to whole
+    /// sectors).
+    pub fn with_max_io_bytes(mut self, bytes: u32) -> Self {
+        self.max_io_bytes =
+            (bytes >> SECTOR_SHIFT << SECTOR_SHIFT).max(1 << SECTOR_SHIFT);
+        self
+    }
+
+    /// Expose the device read-only.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Create and start the device. Must be called inside a tokio runtime,
+    /// which then runs the backend I/O.
+    pub async fn start(self) -> Result<UblkDevice> {
+        let handle = Handle::current();
+        tokio::task::spawn_blocking(move || self.start_blocking(handle)).await?
+    }
+
+    fn start_blocking(self, handle: Handle) -> Result<UblkDevice> {
+        let block_size = self.backend.block_size();
+        ensure!(
+            block_size.is_power_of_two() && block_size >= 1 << SECTOR_SHIFT,
+            "block size {block_size} is not supported by ublk"
+        );
+
+        let mut control = Control::open()?;
+        let mut info = DevInfo {
+            nr_hw_queues: self.queues,
+            queue_depth: self.queue_depth,
+            max_io_buf_bytes: self.max_io_bytes,
+            dev_id: u32::MAX,
+            flags: F_CMD_IOCTL_ENCODE,
+            ..Default::default()
+        };
+        control
+            .command(
+                U_CMD_ADD_DEV,
+                u32::MAX,
+                ptr::from_mut(&mut info) as u64,
+                size_of::<DevInfo>() as u16,
+                0,
+            )
+            .context("ublk ADD_DEV failed")?;
+
+        // From here on, dropping `device` removes the kernel device again.
+        let mut device = UblkDevice {
+            control,
+            dev_id: info.dev_id,
+            stop: Arc::new(AtomicBool::new(false)),
+            queues: Vec::new(),
+            removed: false,
+        };
+
+        let logical_shift = block_size.trailing_zeros() as u8;
+        let mut attrs = ATTR_VOLATILE_CACHE | ATTR_FUA;
+        if self.read_only {
+            attrs |= ATTR_READ_ONLY;
+        }
+        let mut params = Params {
+            len: PARAMS_LEN as u32,
+            types: PARAM_TYPE_BASIC | PARAM_TYPE_DISCARD,
+            basic: ParamBasic {
+                attrs,
+                logical_bs_shift: logical_shift,
+                physical_bs_shift: logical_shift,
+                io_min_shift: logical_shift,
+                io_opt_shift: logical_shift,
+                max_sectors: self.max_io_bytes >> SECTOR_SHIFT,
+                dev_sectors: self.backend.size() >> SECTOR_SHIFT,
+                ..Default::default()
+            },
+            discard: ParamDiscard {
+                discard_granularity: block_size,
+                max_discard_sectors: u32::MAX >> SECTOR_SHIFT,
+                max_discard_segments: 1,
+                ..Default::default()
+            },
+        };
+        if self.read_only {
+            params.types &= !PARAM_TYPE_DISCARD;
+        }
+        device
+            .control
+            .command(
+                U_CMD_SET_PARAMS,
+                device.dev_id,
+                ptr::from_mut(&mut params) as u64,
+                PARAMS_LEN,
+                0,
+            )
+            .context("ublk SET_PARAMS failed")?;
+
+        let char_dev = Arc::new(open_char_dev(device.dev_id)?);
+        for q_id in 0..self.queues {
+            let queue = Queue {
+                q_id,
+                depth: self.queue_depth,
+                buf_len: self.max_io_bytes as usize,
+                descs: DescMap::new(&char_dev, q_id, self.queue_depth)?,
+                char_dev: Arc::clone(&char_dev),
+                wake: Arc::new(eventfd()?),
+                stop: Arc::clone(&device.stop),
+                backend: Arc::clone(&self.backend),
+                handle: handle.clone(),
+            };
+            let wake = Arc::clone(&queue.wake);
+            let thread = thread::Builder::new()
+                .name(format!("ublk{}-q{q_id}", device.dev_id))
+                .spawn(move || queue.run())?;
+            device.queues.push((thread, wake));
+        }
+
+        // Returns once every queue has its FETCH_REQs outstanding.
+        device
+            .control
+            .command(
+                U_CMD_START_DEV,
+                device.dev_id,
+                0,
+                0,
+                std::process::id() as u64,
+            )
+            .context("ublk START_DEV failed")?;
+        info!(
+            "ublk device {} started ({} bytes)",
+            device.path().display(),
+            self.backend.size()
+        );
+        Ok(device)
+    }
+}
+
+/// A started ublk device. Dropping it stops and removes the device; prefer
+/// [`UblkDevice::stop`] from async code.
+#[derive(Debug)]
+pub struct UblkDevice {
+    control: Control,
+    dev_id: u32,
+    stop: Arc<AtomicBool>,
+    queues: Vec<(thread::JoinHandle<io::Result<()>>, Arc<OwnedFd>)>,
+    removed: bool,
+}
+
+impl UblkDevice {
+    /// Kernel device id (`N` in `/dev/ublkbN`).
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.dev_id
+    }
+
+    /// Path of the block device.
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(format!("/dev/ublkb{}", self.dev_id))
+    }
+
+    /// Stop the device, wait for the queues to drain and remove it.
+    pub async fn stop(mut self) -> Result<()> {
+        tokio::task::spawn_blocking(move || self.remove()).await?
+    }
+
+    fn remove(&mut self) -> Result<()> {
+        if self.removed {
+            return Ok(());
+        }
+        self.removed = true;
+
+        // Aborts the outstanding FETCH_REQs; fails harmlessly when the
+        // device never started.
+        let stopped = self.control.command(U_CMD_STOP_DEV, self.dev_id, 0, 0, 0);
+        self.stop.store(true, Ordering::Release);
+        let mut first_error = None;
+        for (thread, wake) in self.queues.drain(..) {
+            signal(wake.as_raw_fd());
+            let res = match thread.join() {
+                Ok(res) => res.context("ublk queue failed"),
+                Err(_) => Err(anyhow::anyhow!("ublk queue thread panicked")),
+            };
+            if let Err(e) = res {
+                first_error.get_or_insert(e);
+            }
+        }
+        self.control
+            .command(U_CMD_DEL_DEV, self.dev_id, 0, 0, 0)
+            .context("ublk DEL_DEV failed")?;
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+        stopped.context("ublk STOP_DEV failed")?;
+        Ok(())
+    }
+}
+
+impl Drop for UblkDevice {
+    fn drop(&mut self) {
+        if let Err(e) = self.remove() {
+            warn!("removing ublk device {} failed: {e:#}", self.dev_id);
+        }
+    }
+}
+
+/// `/dev/ublk-control` and the ring its commands are submitted on.
+struct Control {
+    ring: IoUring<squeue::Entry128, cqueue::Entry>,
+    file: File,
+}
+
+impl std::fmt::Debug for Control {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Control").finish_non_exhaustive()
+    }
+}
+
+impl Control {
+    fn open() -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(CONTROL_PATH)
+            .with_context(|| {
+                format!("cannot open {CONTROL_PATH} (is ublk_drv loaded?)")
+            })?;
+        Ok(Self {
+            ring: IoUring::builder().build(4)?,
+            file,
+        })
+    }
+
+    /// Submit one control command and wait for it.
+    fn command(
+        &mut self,
+        op: u32,
+        dev_id: u32,
+        addr: u64,
+        len: u16,
+        data: u64,
+    ) -> io::Result<i32> {
+        let mut cmd = [0u8; 80];
+        cmd[0..4].copy_from_slice(&dev_id.to_ne_bytes());
+        cmd[4..6].copy_from_slice(&u16::MAX.to_ne_bytes()); // queue_id: none
+        cmd[6..8].copy_from_slice(&len.to_ne_bytes());
+        cmd[8..16].copy_from_slice(&addr.to_ne_bytes());
+        cmd[16..24].copy_from_slice(&data.to_ne_bytes());
+        let entry = opcode::UringCmd80::new(types::Fd(self.file.as_raw_fd()), op)
+            .cmd(cmd)
+            .build();
+        // SAFETY: `addr` points at a buffer of the caller that outlives the
+        // command, since we wait for its completion below.
+        unsafe { self.ring.submission().push(&entry) }
+            .map_err(|_| io::Error::other("io_uring submission queue full"))?;
+        submit_and_wait(&self.ring.submitter())?;
+        let cqe = self
+            .ring
+            .completion()
+            .next()
+            .ok_or_else(|| io::Error::other("io_uring completion missing"))?;
+        match cqe.result() {
+            res if res < 0 => Err(io::Error::from_raw_os_error(-res)),
+            res => Ok(res),
+        }
+    }
+}
+
+/// Read-only mapping of one queue's I/O descriptors.
+struct DescMap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// SAFETY: the mapping is only read, through volatile reads of descriptors
+// the kernel has finished writing.
+unsafe impl Send for DescMap {}
+
+impl DescMap {
+    fn new(char_dev: &File, q_id: u16, depth: u16) -> io::Result<Self> {
+        // SAFETY: sysconf has no memory-safety preconditions.
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let stride = (MAX_QUEUE_DEPTH * size_of::<IoDesc>()).next_multiple_of(page);
+        let len = (depth as usize * size_of::<IoDesc>()).next_multiple_of(page);
+        // SAFETY: maps a fresh region; the kernel validates fd and offset.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                char_dev.as_raw_fd(),
+                (q_id as usize * stride) as libc::off_t,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    fn get(&self, tag: u16) -> IoDesc {
+        // SAFETY: `tag < depth`, so the descriptor lies inside the mapping.
+        unsafe { ptr::read_volatile(self.ptr.cast::<IoDesc>().add(tag as usize)) }
+    }
+}
+
+impl Drop for DescMap {
+    fn drop(&mut self) {
+        // SAFETY: `ptr`/`len` come from a successful mmap.
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+/// One hardware queue, run on its own thread.
+struct Queue<B> {
+    q_id: u16,
+    depth: u16,
+    buf_len: usize,
+    descs: DescMap,
+    char_dev: Arc<File>,
+    wake: Arc<OwnedFd>,
+    stop: Arc<AtomicBool>,
+    backend: Arc<B>,
+    handle: Handle,
+}
+
+/// A finished request handed back to the queue thread.
+type Completion = (u16, i32, Box<[u8]>);
+
+impl<B: BlockBackend> Queue<B> {
+    fn run(self) -> io::Result<()> {
+        let mut ring = IoUring::new(self.depth as u32 * 2 + 2)?;
+        let (done_tx, done_rx) = mpsc::channel::<Completion>();
+        let mut bufs: Vec<Option<Box<[u8]>>> = (0..self.depth)
+            .map(|_| Some(vec![0u8; self.buf_len].into_boxed_slice()))
+            .collect();
+        let mut counter = Box::new(0u64);
+        let mut live = self.depth as usize;
+        let mut in_flight = 0usize;
+
+        for tag in 0..self.depth {
+            let addr = buf_addr(&bufs[tag as usize]);
+            self.push(&mut ring, U_IO_FETCH_REQ, tag, 0, addr)?;
+        }
+        self.arm_wake(&mut ring, &mut counter)?;
+
+        while live > 0 {
+            submit_and_wait(&ring.submitter())?;
+            let cqes: Vec<(u64, i32)> = ring
+                .completion()
+                .map(|c| (c.user_data(), c.result()))
+                .collect();
+            for (user_data, res) in cqes {
+                if user_data == EVENT_TAG {
+                    for (tag, result, buf) in done_rx.try_iter() {
+                        in_flight -= 1;
+                        let addr = buf.as_ptr() as u64;
+                        bufs[tag as usize] = Some(buf);
+                        self.push(
+                            &mut ring,
+                            U_IO_COMMIT_AND_FETCH_REQ,
+                            tag,
+                            result,
+                            addr,
+                        )?;
+                    }
+                    if self.stop.load(Ordering::Acquire) && in_flight == 0 {
+                        // Not started or already stopped: whatever is still
+                        // outstanding is cancelled when the ring is dropped.
+                        return Ok(());
+                    }
+                    self.arm_wake(&mut ring, &mut counter)?;
+                    continue;
+                }
+
+                let tag = user_data as u16;
+                if res == IO_RES_ABORT {
+                    live -= 1;
+                    continue;
+                }
+                if res < 0 {
+                    return Err(io::Error::from_raw_os_error(-res));
+                }
+
+                let desc = self.descs.get(tag);
+                let buf = bufs[tag as usize]
+                    .take()
+                    .ok_or_else(|| io::Error::other(format!("ublk tag {tag} reused")))?;
+                in_flight += 1;
+                let backend = Arc::clone(&self.backend);
+                let done_tx = done_tx.clone();
+                let wake = Arc::clone(&self.wake);
+                self.handle.spawn(async move {
+                    let (result, buf) = handle_io(backend.as_ref(), desc, buf).await;
+                    let _ = done_tx.send((tag, result, buf));
+                    signal(wake.as_raw_fd());
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn push(
+        &self,
+        ring: &mut IoUring,
+        op: u32,
+        tag: u16,
+        result: i32,
+        addr: u64,
+    ) -> io::Result<()> {
+        let mut cmd = [0u8; 16];
+        cmd[0..2].copy_from_slice(&self.q_id.to_ne_bytes());
+        cmd[2..4].copy_from_slice(&tag.to_ne_bytes());
+        cmd[4..8].copy_from_slice(&result.to_ne_bytes());
+        cmd[8..16].copy_from_slice(&addr.to_ne_bytes());
+        let entry = opcode::UringCmd16::new(types::Fd(self.char_dev.as_raw_fd()), op)
+            .cmd(cmd)
+            .build()
+            .user_data(tag as u64);
+        // SAFETY: `addr` is the tag's buffer, which stays in `bufs` (and is
+        // never reallocated) until the kernel completes the command.
+        unsafe { ring.submission().push(&entry) }
+            .map_err(|_| io::Error::other("io_uring submission queue full"))
+    }
+
+    fn arm_wake(&self, ring: &mut IoUring, counter: &mut u64) -> io::Result<()> {
+        let entry = opcode::Read::new(
+            types::Fd(self.wake.as_raw_fd()),
+            ptr::from_mut(counter).cast(),
+            8,
+        )
+        .build()
+        .user_data(EVENT_TAG);
+        // SAFETY: `counter` is boxed and outlives the ring.
+        unsafe { ring.submission().push(&entry) }
+            .map_err(|_| io::Error::other("io_uring submission queue full"))
+    }
+}
+
+/// Run one ublk request against the backend. Returns the ublk result (bytes
+/// transferred or `-errno`) and the buffer.
+async fn handle_io<B: BlockBackend>(
+    backend: &B,
+    desc: IoDesc,
+    mut buf: Box<[u8]>,
+) -> (i32, Box<[u8]>) {
+    let offset = desc.start_sector << SECTOR_SHIFT;
+    let len = (desc.nr_sectors as usize) << SECTOR_SHIFT;
+    let transfers = matches!(desc.op(), OP_READ | OP_WRITE);
+    if transfers && len > buf.len() {
+        return (-libc::EINVAL, buf);
+    }
+
+    let result = match desc.op() {
+        OP_READ => match backend.read(offset, len).await {
+            Ok(data) if data.len() == len => {
+                buf[..len].copy_from_slice(&data);
+                Ok(())
+            },
+            Ok(data) => Err(IscsiError::msg(format!(
+                "short read: {} of {len} bytes",
+                data.len()
+            ))),
+            Err(e) => Err(e),
+        },
+        OP_WRITE => {
+            let mut r = backend.write(offset, &buf[..len]).await;
+            if r.is_ok() && desc.op_flags & IO_F_FUA != 0 {
+                r = backend.flush_range(offset, len as u64).await;
+            }
+            r
+        },
+        OP_FLUSH => backend.flush().await,
+        OP_DISCARD => backend.discard(offset, len as u64).await,
+        _ => return (-libc::EOPNOTSUPP, buf),
+    };
+    match result {
+        Ok(()) if transfers => (len as i32, buf),
+        Ok(()) => (0, buf),
+        Err(e) => {
+            warn!("ublk op {} at {offset}+{len} failed: {e:#}", desc.op());
+            (-libc::EIO, buf)
+        },
+    }
+}
+
+fn buf_addr(buf: &Option<Box<[u8]>>) -> u64 {
+    buf.as_ref().map_or(0, |b| b.as_ptr() as u64)
+}
+
+fn submit_and_wait(submitter: &io_uring::Submitter<'_>) -> io::Result<()> {
+    loop {
+        match submitter.submit_and_wait(1) {
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn eventfd() -> io::Result<OwnedFd> {
+    // SAFETY: eventfd has no memory-safety preconditions.
+    let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` is a freshly created descriptor we own.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn signal(fd: RawFd) {
+    let one = 1u64;
+    // SAFETY: writes 8 bytes from a live u64 to an eventfd.
+    unsafe { libc::write(fd, ptr::from_ref(&one).cast(), 8) };
+}
+
+/// `/dev/ublkc<N>` shows up asynchronously after ADD_DEV (udev).
+fn open_char_dev(dev_id: u32) -> Result<File> {
+    let path = format!("/dev/ublkc{dev_id}");
+    for _ in 0..100 {
+        match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(f) => return Ok(f),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                thread::sleep(Duration::from_millis(10));
+            },
+            Err(e) => return Err(e).with_context(|| format!("cannot open {path}")),
+        }
+    }
+    bail!("{path} did not appear")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_backend::MemBackend;
+
+    #[test]
+    fn command_encoding_matches_uapi() {
+        assert_eq!(U_CMD_ADD_DEV, 0xC020_7504);
+        assert_eq!(U_CMD_SET_PARAMS, 0xC020_7508);
+        assert_eq!(U_IO_FETCH_REQ, 0xC010_7520);
+        assert_eq!(U_IO_COMMIT_AND_FETCH_REQ, 0xC010_7521);
+        assert_eq!(
+            std::mem::offset_of!(Params, discard) + 20,
+            PARAMS_LEN as usize
+        );
+    }
+
+    fn desc(op: u8, flags: u32, sector: u64, sectors: u32) -> IoDesc {
+        IoDesc {
+            op_flags: op as u32 | flags,
+            nr_sectors: sectors,
+            start_sector: sector,
+            addr: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_map_onto_the_backend() {
+        let backend = MemBackend::new(vec![0u8; 8192]);
+        let buf = vec![0u8; 4096].into_boxed_slice();
+
+        let mut data = buf;
+        data[..512].fill(0xAB);
+        let (res, buf) = handle_io(&backend, desc(OP_WRITE, IO_F_FUA, 2, 1), data).await;
+        assert_eq!(res, 512);
+        assert_eq!(*backend.flushes.lock().expect("lock"), 1);
+
+        let (res, buf) = handle_io(&backend, desc(OP_READ, 0, 1, 2), buf).await;
+        assert_eq!(res, 1024);
+        assert!(buf[..512].iter().all(|&b| b == 0));
+        assert!(buf[512..1024].iter().all(|&b| b == 0xAB));
+
+        let (res, buf) = handle_io(&backend, desc(OP_DISCARD, 0, 4, 4), buf).await;
+        assert_eq!(res, 0);
+        assert_eq!(*backend.discarded.lock().expect("lock"), vec![(2048, 2048)]);
+
+        let (res, buf) = handle_io(&backend, desc(OP_READ, 0, 0, 16), buf).await;
+        assert_eq!(res, -libc::EINVAL, "larger than the buffer");
+        let (res, _) = handle_io(&backend, desc(5, 0, 0, 1), buf).await;
+        assert_eq!(res, -libc::EOPNOTSUPP);
+    }
+}
@@ -0,0 +1,457 @@
+//! C ABI for embedding the initiator (feature `ffi`).
+//!
+//! The functions declared in `include/iscsi_client.h` wrap the pool and
+//! handle API behind opaque pointers. Calls are blocking: they run on a
+//! shared tokio runtime created on first use. Every function returns
+//! `ISCSI_OK` (0) or a negative `ISCSI_ERR_*` code, and the message of the
+//! last failure on the calling thread is available from
+//! [`iscsi_last_error`].
+//!
+//! Build a shared library with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib`.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail, ensure};
+
+use crate::{
+    bindings::{ErrorClass, runtime},
+    cfg::config::Config,
+    client::{
+        handles::{LunHandle, SessionHandle},
+        pool_sessions::Pool,
+    },
+    error::IscsiError,
+    models::identifiers::Lun,
+};
+
+/// Success.
+pub const ISCSI_OK: c_int = 0;
+/// A pointer argument was NULL or a length did not match.
+pub const ISCSI_ERR_INVALID_ARG: c_int = -1;
+/// Any failure not covered by a more specific code.
+pub const ISCSI_ERR_IO: c_int = -2;
+/// The target rejected the login.
+pub const ISCSI_ERR_LOGIN: c_int = -3;
+/// The command did not complete in time.
+pub const ISCSI_ERR_TIMEOUT: c_int = -4;
+/// The connection was lost or shut down.
+pub const ISCSI_ERR_DISCONNECTED: c_int = -5;
+/// The command completed with a SCSI status other than GOOD.
+pub const ISCSI_ERR_SCSI_STATUS: c_int = -6;
+/// A panic was caught at the ABI boundary.
+pub const ISCSI_ERR_PANIC: c_int = -7;
+
+/// Opaque logged-in session.
+pub struct IscsiSession {
+    pool: Arc<Pool>,
+    session: SessionHandle,
+}
+
+/// Opaque logical unit of a session.
+pub struct IscsiLun {
+    lun: LunHandle,
+}
+
+/// Standard INQUIRY data with NUL-terminated strings.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct IscsiInquiry {
+    pub peripheral_qualifier: u8,
+    pub device_type: u8,
+    /// 1 when the medium is removable.
+    pub removable: u8,
+    pub version: u8,
+    pub vendor: [c_char; 9],
+    pub product: [c_char; 17],
+    pub revision: [c_char; 5],
+}
+
+/// A bad argument, reported as [`ISCSI_ERR_INVALID_ARG`].
+#[derive(Debug, thiserror::Error)]
+#[error("invalid argument: {0}")]
+struct InvalidArgError(&'static str);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn error_code(error: anyhow::Error) -> c_int {
+    if error.chain().any(|cause| cause.is::<InvalidArgError>()) {
+        return ISCSI_ERR_INVALID_ARG;
+    }
+    match ErrorClass::of(&IscsiError::from(error)) {
+        ErrorClass::Login => ISCSI_ERR_LOGIN,
+        ErrorClass::Timeout => ISCSI_ERR_TIMEOUT,
+        ErrorClass::Disconnected => ISCSI_ERR_DISCONNECTED,
+        ErrorClass::Scsi => ISCSI_ERR_SCSI_STATUS,
+        ErrorClass::Other => ISCSI_ERR_IO,
+    }
+}
+
+/// Run `f`, translating errors and panics into return codes.
+fn ffi_call(f: impl FnOnce() -> Result<()>) -> c_int {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => ISCSI_OK,
+        Ok(Err(e)) => {
+            set_last_error(format!("{e:#}"));
+            error_code(e)
+        },
+        Err(_) => {
+            set_last_error("panic inside iscsi-client-rs".to_string());
+            ISCSI_ERR_PANIC
+        },
+    }
+}
+
+/// Borrow `*ptr`, failing with [`ISCSI_ERR_INVALID_ARG`] on NULL.
+///
+/// # Safety
+///
+/// `ptr` must be NULL or valid for the returned lifetime.
+unsafe fn arg<'a, T>(ptr: *const T, name: &'static str) -> Result<&'a T> {
+    // SAFETY: guaranteed by the caller.
+    unsafe { ptr.as_ref() }.ok_or_else(|| InvalidArgError(name).into())
+}
+
+fn copy_c_string<const N: usize>(dst: &mut [c_char; N], src: &str) {
+    for (d, s) in dst.iter_mut().zip(src.bytes().take(N - 1)) {
+        *d = s as c_char;
+    }
+}
+
+/// Message of the last failed call on this thread, or NULL.
+///
+/// The string stays valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn iscsi_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Connect to the target described by the YAML file at `config_path`, log
+/// in and store the session in `*out`.
+///
+/// # Safety
+///
+/// `config_path` must be a NUL-terminated string and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_session_open(
+    config_path: *const c_char,
+    out: *mut *mut IscsiSession,
+) -> c_int {
+    ffi_call(|| {
+        ensure!(!out.is_null(), InvalidArgError("out"));
+        ensure!(!config_path.is_null(), InvalidArgError("config_path"));
+        // SAFETY: non-NULL and NUL-terminated per the contract.
+        let path = unsafe { CStr::from_ptr(config_path) }
+            .to_str()
+            .map_err(|_| InvalidArgError("config_path is not UTF-8"))?;
+        let cfg = Config::load_from_file(path)?;
+        let session = runtime()?.block_on(async {
+            let pool = Pool::new(&cfg);
+            let tsih = *pool
+                .login_sessions_from_cfg(&cfg)
+                .await?
+                .first()
+                .context("no session logged in")?;
+            let session = pool.session(tsih)?;
+            anyhow::Ok(IscsiSession { pool, session })
+        })?;
+        // SAFETY: checked non-NULL above.
+        unsafe { *out = Box::into_raw(Box::new(session)) };
+        Ok(())
+    })
+}
+
+/// Log out every connection of `session`, waiting up to `timeout_ms` per
+/// connection, and free it. LUNs opened on the session fail afterwards and
+/// must still be freed with [`iscsi_lun_free`].
+///
+/// # Safety
+///
+/// `session` must be NULL or come from [`iscsi_session_open`] and not have
+/// been closed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_session_close(
+    session: *mut IscsiSession,
+    timeout_ms: u32,
+) -> c_int {
+    ffi_call(|| {
+        if session.is_null() {
+            return Ok(());
+        }
+        // SAFETY: owned pointer from `iscsi_session_open`.
+        let session = unsafe { Box::from_raw(session) };
+        Ok(runtime()?.block_on(
+            session
+                .pool
+                .shutdown_gracefully(Duration::from_millis(timeout_ms as u64)),
+        )?)
+    })
+}
+
+/// Open logical unit `lun` (the 64-bit LUN field) of `session`.
+///
+/// # Safety
+///
+/// `session` must be a live session and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_lun_open(
+    session: *const IscsiSession,
+    lun: u64,
+    out: *mut *mut IscsiLun,
+) -> c_int {
+    ffi_call(|| {
+        // SAFETY: per the contract.
+        let session = unsafe { arg(session, "session") }?;
+        ensure!(!out.is_null(), InvalidArgError("out"));
+        let lun = IscsiLun {
+            lun: session.session.lun(Lun::new(lun)),
+        };
+        // SAFETY: checked non-NULL above.
+        unsafe { *out = Box::into_raw(Box::new(lun)) };
+        Ok(())
+    })
+}
+
+/// Free a LUN opened with [`iscsi_lun_open`].
+///
+/// # Safety
+///
+/// `lun` must be NULL or come from [`iscsi_lun_open`] and not have been
+/// freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_lun_free(lun: *mut IscsiLun) {
+    if !lun.is_null() {
+        // SAFETY: owned pointer from `iscsi_lun_open`.
+        drop(unsafe { Box::from_raw(lun) });
+    }
+}
+
+/// TEST UNIT READY.
+///
+/// # Safety
+///
+/// `lun` must be a live LUN.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_lun_test_unit_ready(lun: *const IscsiLun) -> c_int {
+    ffi_call(|| {
+        // SAFETY: per the contract.
+        let lun = unsafe { arg(lun, "lun") }?;
+        Ok(runtime()?.block_on(lun.lun.test_unit_ready())?)
+    })
+}
+
+/// Number of blocks and block size of the LUN.
+///
+/// # Safety
+///
+/// `lun` must be a live LUN; `blocks` and `block_size` valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_lun_capacity(
+    lun: *const IscsiLun,
+    blocks: *mut u64,
+    block_size: *mut u32,
+) -> c_int {
+    ffi_call(|| {
+        // SAFETY: per the contract.
+        let lun = unsafe { arg(lun, "lun") }?;
+        ensure!(
+            !blocks.is_null() && !block_size.is_null(),
+            InvalidArgError("blocks/block_size")
+        );
+        let cap = runtime()?.block_on(lun.lun.capacity())?;
+        // SAFETY: checked non-NULL above.
+        unsafe {
+            *blocks = cap.blocks;
+            *block_size = cap.block_size;
+        }
+        Ok(())
+    })
+}
+
+/// Standard INQUIRY.
+///
+/// # Safety
+///
+/// `lun` must be a live LUN and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_lun_inquiry(
+    lun: *const IscsiLun,
+    out: *mut IscsiInquiry,
+) -> c_int {
+    ffi_call(|| {
+        // SAFETY: per the contract.
+        let lun = unsafe { arg(lun, "lun") }?;
+        ensure!(!out.is_null(), InvalidArgError("out"));
+        let inq = runtime()?.block_on(lun.lun.inquiry())?;
+        let mut c = IscsiInquiry {
+            peripheral_qualifier: inq.peripheral_qualifier,
+            device_type: inq.device_type,
+            removable: inq.rmb as u8,
+            version: inq.version,
+            ..Default::default()
+        };
+        copy_c_string(&mut c.vendor, &inq.vendor_id);
+        copy_c_string(&mut c.product, &inq.product_id);
+        copy_c_string(&mut c.revision, &inq.product_rev);
+        // SAFETY: checked non-NULL above.
+        unsafe { *out = c };
+        Ok(())
+    })
+}
+
+/// Read `blocks` blocks at `lba` into `buf`, which must hold exactly
+/// `blocks * block_size` bytes.
+///
+/// # Safety
+///
+/// `lun` must be a live LUN and `buf` valid for `buf_len` bytes of writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_lun_read(
+    lun: *const IscsiLun,
+    lba: u64,
+    blocks: u32,
+    buf: *mut u8,
+    buf_len: usize,
+) -> c_int {
+    ffi_call(|| {
+        // SAFETY: per the contract.
+        let lun = unsafe { arg(lun, "lun") }?;
+        let rt = runtime()?;
+        let block_size = rt.block_on(lun.lun.capacity())?.block_size;
+        if blocks as u64 * block_size as u64 != buf_len as u64 {
+            bail!(InvalidArgError(
+                "buf_len does not match blocks * block_size"
+            ));
+        }
+        if buf_len == 0 {
+            return Ok(());
+        }
+        ensure!(!buf.is_null(), InvalidArgError("buf"));
+        let data = rt.block_on(lun.lun.read_at(lba, blocks))?;
+        ensure!(
+            data.len() == buf_len,
+            "target returned {} bytes instead of {buf_len}",
+            data.len()
+        );
+        // SAFETY: `buf` is non-NULL and valid for `buf_len == data.len()`
+        // bytes.
+        unsafe { ptr::copy_nonoverlapping(data.as_ptr(), buf, buf_len) };
+        Ok(())
+    })
+}
+
+/// Write `len` bytes (a whole number of blocks) from `buf` at `lba`.
+///
+/// # Safety
+///
+/// `lun` must be a live LUN and `buf` valid for `len` bytes of reads.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_lun_write(
+    lun: *const IscsiLun,
+    lba: u64,
+    buf: *const u8,
+    len: usize,
+) -> c_int {
+    ffi_call(|| {
+        // SAFETY: per the contract.
+        let lun = unsafe { arg(lun, "lun") }?;
+        if len == 0 {
+            return Ok(());
+        }
+        ensure!(!buf.is_null(), InvalidArgError("buf"));
+        // SAFETY: `buf` is valid for `len` bytes per the contract.
+        let data = unsafe { std::slice::from_raw_parts(buf, len) }.to_vec();
+        Ok(runtime()?.block_on(lun.lun.write_at(lba, data))?)
+    })
+}
+
+/// SYNCHRONIZE CACHE for the whole LUN.
+///
+/// # Safety
+///
+/// `lun` must be a live LUN.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iscsi_lun_flush(lun: *const IscsiLun) -> c_int {
+    ffi_call(|| {
+        // SAFETY: per the contract.
+        let lun = unsafe { arg(lun, "lun") }?;
+        Ok(runtime()?.block_on(lun.lun.flush(..))?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = include_str!("../include/iscsi_client.h");
+    const SOURCE: &str = include_str!("ffi.rs");
+
+    #[test]
+    fn header_declares_every_export() {
+        let exports: Vec<&str> = SOURCE
+            .lines()
+            .filter_map(|l| l.split_once("extern \"C\" fn ").map(|(_, rest)| rest))
+            .filter_map(|rest| rest.split('(').next())
+            .collect();
+        assert!(exports.len() >= 10);
+        for name in exports {
+            assert!(
+                HEADER.contains(&format!("{name}(")),
+                "{name} missing from header"
+            );
+        }
+        for (name, value) in [
+            ("ISCSI_OK", ISCSI_OK),
+            ("ISCSI_ERR_INVALID_ARG", ISCSI_ERR_INVALID_ARG),
+            ("ISCSI_ERR_IO", ISCSI_ERR_IO),
+            ("ISCSI_ERR_LOGIN", ISCSI_ERR_LOGIN),
+            ("ISCSI_ERR_TIMEOUT", ISCSI_ERR_TIMEOUT),
+            ("ISCSI_ERR_DISCONNECTED", ISCSI_ERR_DISCONNECTED),
+            ("ISCSI_ERR_SCSI_STATUS", ISCSI_ERR_SCSI_STATUS),
+            ("ISCSI_ERR_PANIC", ISCSI_ERR_PANIC),
+        ] {
+            assert!(
+                HEADER.contains(&format!("{name} = {value}")),
+                "{name} = {value} missing from header"
+            );
+        }
+    }
+
+    #[test]
+    fn bad_arguments_set_code_and_message() {
+        let mut lun: *mut IscsiLun = ptr::null_mut();
+        // SAFETY: NULL session is rejected before any dereference.
+        let rc = unsafe { iscsi_lun_open(ptr::null(), 0, &mut lun) };
+        assert_eq!(rc, ISCSI_ERR_INVALID_ARG);
+        assert!(lun.is_null());
+        // SAFETY: non-NULL results of iscsi_last_error are NUL-terminated.
+        let msg = unsafe { CStr::from_ptr(iscsi_last_error()) };
+        assert!(msg.to_string_lossy().contains("session"));
+
+        let mut session: *mut IscsiSession = ptr::null_mut();
+        let path = CString::new("/nonexistent/config.yaml").expect("CString");
+        // SAFETY: valid string and out pointer.
+        let rc = unsafe { iscsi_session_open(path.as_ptr(), &mut session) };
+        assert_eq!(rc, ISCSI_ERR_IO);
+        assert!(session.is_null());
+        // SAFETY: NULL is accepted.
+        assert_eq!(unsafe { iscsi_session_close(ptr::null_mut(), 0) }, ISCSI_OK);
+    }
+}
@@ -1,20 +1,46 @@
 //! This crate provides a client-side implementation of the iSCSI protocol.
+//!
+//! Without the default `std` feature the crate is `no_std + alloc` and only
+//! offers [`models`] (PDU builders and zero-copy parsers) and
+//! [`control_block`] (CDB builders and response parsers), for embedded
+//! initiators and other tooling that brings its own transport.
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 #[cfg(test)]
 extern crate self as iscsi_client_rs;
 
+/// Runtime and error classes shared by the `ffi` and `python` bindings.
+#[cfg(any(feature = "ffi", feature = "python"))]
+mod bindings;
 /// Handles configuration, command-line parsing, and logging.
+#[cfg(feature = "std")]
 pub mod cfg;
 /// Manages client connections, sessions, and the session pool.
+#[cfg(feature = "std")]
 pub mod client;
 /// Implements various SCSI commands (control blocks).
 pub mod control_block;
-#[cfg(test)]
+/// Typed errors of the public API.
+#[cfg(feature = "std")]
+pub mod error;
+/// Serves a LUN to local consumers (NBD, ...).
+#[cfg(feature = "std")]
+pub mod export;
+/// C ABI for embedding the initiator (`ffi` feature).
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(all(test, feature = "std"))]
 mod internal_tests;
 /// Defines the data structures for iSCSI PDUs and SCSI commands.
 pub mod models;
+/// Python bindings (`python` feature).
+#[cfg(feature = "python")]
+pub mod python;
 /// Contains state machines for handling iSCSI operations like Login, Logout,
 /// Read, and Write.
+#[cfg(feature = "std")]
 pub mod state_machine;
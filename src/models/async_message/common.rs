@@ -0,0 +1,43 @@
+//! This module defines the AsyncEvent codes of iSCSI Asynchronous Messages
+//! (RFC 7143 § 11.9.1).
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// Event reported by an Asynchronous Message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncEvent {
+    /// A SCSI asynchronous event; the sense data is in the data segment.
+    ScsiEvent,
+    /// The target asks for a Logout of this connection (or of the session
+    /// when it is the last one) within Parameter3 seconds.
+    LogoutRequested,
+    /// The target will drop connection Parameter1; Parameter2 is Time2Wait
+    /// and Parameter3 Time2Retain.
+    ConnectionDropped,
+    /// The target will drop every connection of the session.
+    SessionDropped,
+    /// The target asks for a new operational parameter negotiation.
+    NegotiationRequested,
+    /// All active tasks for a LU with a matching LUN will be terminated.
+    TaskTermination,
+    /// Vendor-specific event (`AsyncVCode` carries the detail).
+    VendorSpecific,
+    /// Reserved code.
+    Reserved(u8),
+}
+
+impl From<u8> for AsyncEvent {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Self::ScsiEvent,
+            1 => Self::LogoutRequested,
+            2 => Self::ConnectionDropped,
+            3 => Self::SessionDropped,
+            4 => Self::NegotiationRequested,
+            5 => Self::TaskTermination,
+            255 => Self::VendorSpecific,
+            other => Self::Reserved(other),
+        }
+    }
+}
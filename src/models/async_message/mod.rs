@@ -0,0 +1,10 @@
+//! This module defines the structures for iSCSI Asynchronous Message PDUs.
+//! It includes the event codes and the message header sent by the target.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// Defines the AsyncEvent codes.
+pub mod common;
+/// Defines the structure of the Asynchronous Message PDU.
+pub mod response;
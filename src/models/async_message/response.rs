@@ -0,0 +1,142 @@
+//! This module defines the structures for iSCSI Asynchronous Message PDUs.
+//! It includes the `AsyncMessage` header the target sends unsolicited to
+//! report events.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use tracing::warn;
+use zerocopy::{
+    BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U16, U32, U64,
+};
+
+use crate::models::{
+    async_message::common::AsyncEvent,
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::Itt,
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
+};
+
+/// Represents the Basic Header Segment (BHS) for an Asynchronous Message PDU.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq, ZFromBytes, IntoBytes, KnownLayout, Immutable)]
+pub struct AsyncMessage {
+    pub opcode: RawBhsOpcode,         // Byte 0: `Opcode::AsyncMsg`
+    pub flags: u8,                    // Byte 1: Final bit, always set
+    reserved1: [u8; 2],               // Bytes 2..4: reserved
+    pub total_ahs_length: u8,         // Byte 4: AHS length in 4-byte words
+    pub data_segment_length: [u8; 3], // Bytes 5..8: sense data + iSCSI event data
+    pub lun: U64<BigEndian>,          // Bytes 8..16: LUN (SCSI events)
+    pub initiator_task_tag: U32<BigEndian>, // Bytes 16..20: always 0xffffffff
+    reserved2: [u8; 4],               // Bytes 20..24: reserved (0xffffffff)
+    pub stat_sn: U32<BigEndian>,      // Bytes 24..28: StatSN
+    pub exp_cmd_sn: U32<BigEndian>,   // Bytes 28..32: ExpCmdSN
+    pub max_cmd_sn: U32<BigEndian>,   // Bytes 32..36: MaxCmdSN
+    pub async_event: u8,              // Byte 36: AsyncEvent
+    pub async_vcode: u8,              // Byte 37: AsyncVCode
+    pub parameter1: U16<BigEndian>,   // Bytes 38..40: Parameter1
+    pub parameter2: U16<BigEndian>,   // Bytes 40..42: Parameter2
+    pub parameter3: U16<BigEndian>,   // Bytes 42..44: Parameter3
+    reserved3: [u8; 4],               // Bytes 44..48: reserved
+}
+
+impl AsyncMessage {
+    /// Serializes the BHS into a byte buffer.
+    pub fn to_bhs_bytes(&self, buf: &mut [u8]) -> Result<()> {
+        if buf.len() != HEADER_LEN {
+            bail!("buffer length must be {HEADER_LEN}, got {}", buf.len());
+        }
+        buf.copy_from_slice(self.as_bytes());
+        Ok(())
+    }
+
+    /// Deserializes the BHS from a byte buffer.
+    pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
+        let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
+            .map_err(|e| format_err!("failed convert buffer AsyncMessage: {e}"))?;
+        if hdr.opcode.opcode_known() != Some(Opcode::AsyncMsg) {
+            bail!(
+                "AsyncMessage: invalid opcode 0x{:02x}",
+                hdr.opcode.opcode_raw()
+            );
+        }
+        Ok(hdr)
+    }
+
+    /// Decoded AsyncEvent.
+    #[inline]
+    pub fn event(&self) -> AsyncEvent {
+        AsyncEvent::from(self.async_event)
+    }
+}
+
+impl SendingData for AsyncMessage {
+    fn get_final_bit(&self) -> bool {
+        true
+    }
+
+    fn set_final_bit(&mut self) {
+        warn!("AsyncMessage cannot be marked as Final");
+    }
+
+    fn get_continue_bit(&self) -> bool {
+        false
+    }
+
+    fn set_continue_bit(&mut self) {
+        warn!("AsyncMessage cannot be marked as Contine");
+    }
+}
+
+impl FromBytes for AsyncMessage {
+    fn from_bhs_bytes(bytes: &mut [u8]) -> Result<&mut Self> {
+        AsyncMessage::from_bhs_bytes(bytes)
+    }
+}
+
+impl BasicHeaderSegment for AsyncMessage {
+    #[inline]
+    fn to_bhs_bytes(&self, buf: &mut [u8]) -> Result<()> {
+        self.to_bhs_bytes(buf)
+    }
+
+    #[inline]
+    fn get_opcode(&self) -> Result<BhsOpcode> {
+        BhsOpcode::try_from(self.opcode.raw())
+    }
+
+    #[inline]
+    fn get_initiator_task_tag(&self) -> Itt {
+        self.initiator_task_tag.get().into()
+    }
+
+    #[inline]
+    fn get_ahs_length_bytes(&self) -> usize {
+        (self.total_ahs_length as usize) * 4
+    }
+
+    #[inline]
+    fn set_ahs_length_bytes(&mut self, len: u8) {
+        self.total_ahs_length = len >> 2;
+    }
+
+    #[inline]
+    fn get_data_length_bytes(&self) -> usize {
+        u32::from_be_bytes([
+            0,
+            self.data_segment_length[0],
+            self.data_segment_length[1],
+            self.data_segment_length[2],
+        ]) as usize
+    }
+
+    #[inline]
+    fn set_data_length_bytes(&mut self, len: u32) {
+        let be = len.to_be_bytes();
+        self.data_segment_length = [be[1], be[2], be[3]];
+    }
+}
+
+impl ZeroCopyType for AsyncMessage {}
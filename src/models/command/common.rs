@@ -4,11 +4,12 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::{fmt, ptr};
+use core::{fmt, ptr};
 
-use anyhow::bail;
 use thiserror::Error;
 
+use crate::models::error::{ProtocolError, Result, bail, format_err};
+
 bitflags::bitflags! {
     #[derive(Default, Clone, PartialEq)]
     /// iSCSI SCSI Command PDU flags
@@ -22,11 +23,11 @@ bitflags::bitflags! {
 }
 
 impl TryFrom<u8> for ScsiCommandRequestFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         ScsiCommandRequestFlags::from_bits(value)
-            .ok_or_else(|| anyhow::anyhow!("invalid ScsiCommandFlags: {:#08b}", value))
+            .ok_or_else(|| format_err!("invalid ScsiCommandFlags: {:#08b}", value))
     }
 }
 
@@ -63,7 +64,7 @@ impl fmt::Debug for ScsiCommandRequestFlags {
 /// Defines how SCSI commands should be queued and executed relative to other
 /// commands. These attributes control the ordering behavior of commands in the
 /// target's command queue.
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TaskAttribute {
     /// Untagged command (0) - legacy simple queuing
     Untagged,
@@ -136,11 +137,11 @@ bitflags::bitflags! {
 }
 
 impl TryFrom<u8> for ScsiCommandResponseFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         let tmp = ScsiCommandResponseFlags::from_bits(value)
-            .ok_or_else(|| anyhow::anyhow!("invalid ScsiCommandFlags: {:#08b}", value))?;
+            .ok_or_else(|| format_err!("invalid ScsiCommandFlags: {:#08b}", value))?;
 
         if (tmp.contains(ScsiCommandResponseFlags::U_BIG)
             && tmp.contains(ScsiCommandResponseFlags::O_BIG))
@@ -208,6 +209,8 @@ pub enum ScsiStatus {
     Good = 0x00,
     /// Check condition - sense data available (0x02)
     CheckCondition = 0x02,
+    /// Condition met - PRE-FETCH blocks fit in the cache (0x04)
+    ConditionMet = 0x04,
     /// Target busy - retry later (0x08)
     Busy = 0x08,
     /// Reservation conflict (0x18)
@@ -243,6 +246,7 @@ impl TryFrom<u8> for ScsiStatus {
         let s = match b {
             0x00 => ScsiStatus::Good,
             0x02 => ScsiStatus::CheckCondition,
+            0x04 => ScsiStatus::ConditionMet,
             0x08 => ScsiStatus::Busy,
             0x18 => ScsiStatus::ReservationConflict,
             0x28 => ScsiStatus::TaskSetFull,
@@ -5,20 +5,19 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, anyhow, bail};
+use alloc::vec::Vec;
+
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32, U64,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        command::{common::TaskAttribute, zero_copy::RawScsiCmdReqFlags},
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data_fromat::ZeroCopyType,
-        identifiers::{CmdSn, Itt, Lun, StatSn},
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-    },
+use crate::models::{
+    command::{common::TaskAttribute, zero_copy::RawScsiCmdReqFlags},
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, ensure, format_err},
+    identifiers::{CmdSn, Itt, Lun, StatSn},
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
 };
 
 /// Basic Header Segment for iSCSI SCSI Command Request PDU
@@ -60,9 +59,9 @@ impl ScsiCommandRequest {
     /// Deserializes the BHS from a byte buffer.
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
         let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
-            .map_err(|e| anyhow!("failed convert buffer ScsiCommandRequest: {e}"))?;
+            .map_err(|e| format_err!("failed convert buffer ScsiCommandRequest: {e}"))?;
         if hdr.opcode.opcode_known() != Some(Opcode::ScsiCommandReq) {
-            anyhow::bail!(
+            bail!(
                 "ScsiCommandRequest: invalid opcode 0x{:02x}",
                 hdr.opcode.opcode_raw()
             );
@@ -179,6 +178,35 @@ impl ScsiCommandRequestBuilder {
     }
 }
 
+/// AHSType of the Extended CDB AHS (RFC 7143 § 11.2.1.3).
+pub const AHS_TYPE_EXTENDED_CDB: u8 = 0x01;
+
+/// Longest CDB an Extended CDB AHS can complete (SPC-4 variable-length CDBs).
+pub const CDB_MAX_LEN: usize = 260;
+
+/// Build the Extended CDB AHS carrying `extension`, the bytes of a CDB past
+/// the 16 that fit in the BHS.
+///
+/// Layout (RFC 7143 § 11.2.1.3):
+/// - bytes 0..1 : AHSLength (extension length + 1 for the reserved byte)
+/// - byte 2     : AHSType = 0x01
+/// - byte 3     : reserved
+/// - then       : ExtendedCDB, zero-padded to a multiple of 4 bytes
+pub fn extended_cdb_ahs(extension: &[u8]) -> Result<Vec<u8>> {
+    ensure!(
+        !extension.is_empty() && extension.len() <= CDB_MAX_LEN - 16,
+        "CDB extension of {} bytes is out of range",
+        extension.len()
+    );
+    let mut ahs = Vec::with_capacity(4 + extension.len().next_multiple_of(4));
+    ahs.extend_from_slice(&(extension.len() as u16 + 1).to_be_bytes());
+    ahs.push(AHS_TYPE_EXTENDED_CDB);
+    ahs.push(0);
+    ahs.extend_from_slice(extension);
+    ahs.resize(ahs.len().next_multiple_of(4), 0);
+    Ok(ahs)
+}
+
 impl SendingData for ScsiCommandRequest {
     fn get_final_bit(&self) -> bool {
         self.flags.fin()
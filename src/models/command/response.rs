@@ -4,21 +4,18 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, bail};
 use tracing::warn;
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        command::zero_copy::{RawResponseCode, RawScsiCmdRespFlags, RawScsiStatus},
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data_fromat::ZeroCopyType,
-        identifiers::Itt,
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-    },
+use crate::models::{
+    command::zero_copy::{RawResponseCode, RawScsiCmdRespFlags, RawScsiStatus},
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::Itt,
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
 };
 
 /// Basic Header Segment for iSCSI SCSI Command Response PDU
@@ -61,11 +58,10 @@ impl ScsiCommandResponse {
 
     /// Deserializes the BHS from a byte buffer.
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
-        let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf).map_err(|e| {
-            anyhow::anyhow!("failed convert buffer ScsiCommandResponse: {e}")
-        })?;
+        let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
+            .map_err(|e| format_err!("failed convert buffer ScsiCommandResponse: {e}"))?;
         if hdr.opcode.opcode_known() != Some(Opcode::ScsiCommandResp) {
-            anyhow::bail!(
+            bail!(
                 "ScsiCommandResponse: invalid opcode 0x{:02x}",
                 hdr.opcode.opcode_raw()
             );
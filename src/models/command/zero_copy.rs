@@ -5,14 +5,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
+use alloc::format;
 use core::fmt;
 
-use anyhow::{Result, bail};
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
-use crate::models::command::common::{
-    ResponseCode, ScsiCommandRequestFlags, ScsiCommandResponseFlags, ScsiStatus,
-    TaskAttribute, UnknownResponseCode, UnknownScsiStatus,
+use crate::models::{
+    command::common::{
+        ResponseCode, ScsiCommandRequestFlags, ScsiCommandResponseFlags, ScsiStatus,
+        TaskAttribute, UnknownResponseCode, UnknownScsiStatus,
+    },
+    error::{ProtocolError, Result, bail, format_err},
 };
 
 /// Represents the 3-bit SCSI Task Attribute, which is part of the flags in a
@@ -175,12 +178,12 @@ impl From<ScsiCommandRequestFlags> for RawScsiCmdReqFlags {
     }
 }
 impl TryFrom<RawScsiCmdReqFlags> for ScsiCommandRequestFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     #[inline]
     fn try_from(r: RawScsiCmdReqFlags) -> Result<Self> {
         ScsiCommandRequestFlags::from_bits(r.raw()).ok_or_else(|| {
-            anyhow::anyhow!("invalid ScsiCommandRequestFlags: {:#010b}", r.raw())
+            format_err!("invalid ScsiCommandRequestFlags: {:#010b}", r.raw())
         })
     }
 }
@@ -312,12 +315,12 @@ impl From<ScsiCommandResponseFlags> for RawScsiCmdRespFlags {
     }
 }
 impl TryFrom<RawScsiCmdRespFlags> for ScsiCommandResponseFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     #[inline]
     fn try_from(r: RawScsiCmdRespFlags) -> Result<Self> {
         let f = ScsiCommandResponseFlags::from_bits(r.raw()).ok_or_else(|| {
-            anyhow::anyhow!("invalid ScsiCommandResponseFlags: {:#010b}", r.raw())
+            format_err!("invalid ScsiCommandResponseFlags: {:#010b}", r.raw())
         })?;
         // keep the same validation semantics as before
         if (f.contains(ScsiCommandResponseFlags::U_BIG)
@@ -415,6 +418,7 @@ impl RawScsiStatus {
         Ok(match self.0 {
             0x00 => ScsiStatus::Good,
             0x02 => ScsiStatus::CheckCondition,
+            0x04 => ScsiStatus::ConditionMet,
             0x08 => ScsiStatus::Busy,
             0x18 => ScsiStatus::ReservationConflict,
             0x28 => ScsiStatus::TaskSetFull,
@@ -430,6 +434,7 @@ impl RawScsiStatus {
         self.0 = match st {
             ScsiStatus::Good => 0x00,
             ScsiStatus::CheckCondition => 0x02,
+            ScsiStatus::ConditionMet => 0x04,
             ScsiStatus::Busy => 0x08,
             ScsiStatus::ReservationConflict => 0x18,
             ScsiStatus::TaskSetFull => 0x28,
@@ -5,10 +5,12 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::Result;
 use enum_dispatch::enum_dispatch;
 
-use crate::models::opcode::BhsOpcode;
+use crate::models::{
+    error::{Result, format_err},
+    opcode::BhsOpcode,
+};
 
 /// The fixed length of the Basic Header Segment (BHS) in bytes.
 pub const HEADER_LEN: usize = 48;
@@ -130,12 +132,12 @@ impl<T: SendingData> SendingData for &mut T {
 // Forward BasicHeaderSegment to &mut T
 impl<T: BasicHeaderSegment> BasicHeaderSegment for &mut T {
     #[inline]
-    fn to_bhs_bytes(&self, buf: &mut [u8]) -> anyhow::Result<()> {
+    fn to_bhs_bytes(&self, buf: &mut [u8]) -> Result<()> {
         (**self).to_bhs_bytes(buf)
     }
 
     #[inline]
-    fn get_opcode(&self) -> anyhow::Result<BhsOpcode> {
+    fn get_opcode(&self) -> Result<BhsOpcode> {
         (**self).get_opcode()
     }
 
@@ -180,6 +182,23 @@ impl<T: BasicHeaderSegment> BasicHeaderSegment for &mut T {
     }
 }
 
+/// A trait for deserializing a Protocol Data Unit (PDU) from a raw byte stream.
+///
+/// This trait provides functionality to parse incoming binary data into
+/// structured PDU objects. It requires the implementing type to also implement
+/// BasicHeaderSegment for header access.
+pub trait FromBytes: Sized + BasicHeaderSegment {
+    /// Parse the full PDU from a contiguous byte buffer.
+    ///
+    /// The parsed `Response` (often a tuple of header struct, payload bytes,
+    /// and digest), or an error if parsing fails.
+    fn from_bhs_bytes(bytes: &mut [u8]) -> Result<&mut Self> {
+        let _ = BhsOpcode::try_from(bytes[0])
+            .map_err(|e| format_err!("invalid opcode: {}", e))?;
+        Self::from_bhs_bytes(bytes)
+    }
+}
+
 /// A helper-trait for **builder objects** that construct a complete
 /// iSCSI PDU: a 48-byte Basic-Header-Segment (BHS) plus the optional
 /// **Data-Segment** and digests.
@@ -204,7 +223,7 @@ pub trait Builder: Sized {
 
     /// Append raw bytes to the **Data-Segment** and update the
     /// `DataSegmentLength` field inside the owned header.
-    fn append_data(&mut self, more: &[u8]) -> anyhow::Result<()>;
+    fn append_data(&mut self, more: &[u8]) -> Result<()>;
 
     /// Finish the builder and produce one or more ready-to-send
     /// `(header_bytes, data_bytes)` frames.
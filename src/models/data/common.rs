@@ -7,9 +7,10 @@
 
 use core::fmt;
 
-use anyhow::{Result, bail};
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
+use crate::models::error::{ProtocolError, Result, bail, format_err};
+
 bitflags::bitflags! {
     #[derive(Default, Debug, PartialEq)]
     /// Flags for iSCSI SCSI Data-Out PDU
@@ -23,11 +24,11 @@ bitflags::bitflags! {
 }
 
 impl TryFrom<u8> for DataOutFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         DataOutFlags::from_bits(value)
-            .ok_or_else(|| anyhow::anyhow!("invalid DataOutFlags: {:#08b}", value))
+            .ok_or_else(|| format_err!("invalid DataOutFlags: {:#08b}", value))
     }
 }
 
@@ -81,12 +82,12 @@ impl From<DataOutFlags> for RawDataOutFlags {
 }
 
 impl TryFrom<RawDataOutFlags> for DataOutFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     #[inline]
     fn try_from(r: RawDataOutFlags) -> Result<Self> {
         DataOutFlags::from_bits(r.raw())
-            .ok_or_else(|| anyhow::anyhow!("invalid DataOutFlags: {:#010b}", r.raw()))
+            .ok_or_else(|| format_err!("invalid DataOutFlags: {:#010b}", r.raw()))
     }
 }
 
@@ -112,11 +113,11 @@ bitflags::bitflags! {
 }
 
 impl TryFrom<u8> for DataInFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         let tmp = DataInFlags::from_bits(value)
-            .ok_or_else(|| anyhow::anyhow!("invalid DataOutFlags: {:#08b}", value))?;
+            .ok_or_else(|| format_err!("invalid DataOutFlags: {:#08b}", value))?;
 
         if tmp.contains(DataInFlags::U) && tmp.contains(DataInFlags::O) {
             bail!("Protocol error cause U && O both presented")
@@ -271,13 +272,13 @@ impl From<DataInFlags> for RawDataInFlags {
 }
 
 impl TryFrom<RawDataInFlags> for DataInFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     #[inline]
     fn try_from(r: RawDataInFlags) -> Result<Self> {
         r.validate()?;
         DataInFlags::from_bits(r.raw())
-            .ok_or_else(|| anyhow::anyhow!("invalid DataInFlags: {:#010b}", r.raw()))
+            .ok_or_else(|| format_err!("invalid DataInFlags: {:#010b}", r.raw()))
     }
 }
 
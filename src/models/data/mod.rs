@@ -5,10 +5,6 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::collections::HashMap;
-
-use once_cell::sync::Lazy;
-
 use crate::models::data::asc_ascq_gen::ASC_ASCQ;
 
 mod asc_ascq_gen;
@@ -29,27 +25,18 @@ pub struct Entry {
 
 impl Entry {
     /// Looks up the description for a given ASC/ASCQ code.
+    ///
+    /// When the table lists a code several times (one line per device
+    /// type), the shortest description wins.
     #[inline]
     pub fn lookup(asc: u8, ascq: u8) -> Option<&'static str> {
-        let k = ((asc as u16) << 8) | (ascq as u16);
-        ASC_ASCQ_MAP.get(&k).copied()
+        let k = ((asc as usize) << 8) | (ascq as usize);
+        // The generated table is sorted by code.
+        let start = ASC_ASCQ.partition_point(|e| e.code < k);
+        ASC_ASCQ[start..]
+            .iter()
+            .take_while(|e| e.code == k)
+            .map(|e| e.desc)
+            .min_by_key(|d| d.len())
     }
 }
-
-static ASC_ASCQ_MAP: Lazy<HashMap<u16, &'static str>> = Lazy::new(|| {
-    let mut m: HashMap<u16, &'static str> = HashMap::with_capacity(ASC_ASCQ.len());
-    for e in ASC_ASCQ {
-        let code = e.code as u16;
-        match m.get(&code) {
-            Some(cur) => {
-                if e.desc.len() < cur.len() {
-                    m.insert(code, e.desc);
-                }
-            },
-            None => {
-                m.insert(code, e.desc);
-            },
-        }
-    }
-    m
-});
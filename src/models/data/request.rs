@@ -4,20 +4,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, bail};
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32, U64,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data::common::RawDataOutFlags,
-        data_fromat::ZeroCopyType,
-        identifiers::{Itt, Lun, StatSn, Ttt},
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-    },
+use crate::models::{
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data::common::RawDataOutFlags,
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::{Itt, Lun, StatSn, Ttt},
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
 };
 
 /// BHS for SCSI Data-Out (opcode 0x26)
@@ -53,9 +50,9 @@ impl ScsiDataOut {
     /// Deserializes the BHS from a byte buffer.
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
         let hdr = Self::mut_from_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed convert buffer ScsiDataOut: {e}"))?;
+            .map_err(|e| format_err!("failed convert buffer ScsiDataOut: {e}"))?;
         if hdr.opcode.opcode_known() != Some(Opcode::ScsiDataOut) {
-            anyhow::bail!(
+            bail!(
                 "ScsiDataOut: invalid opcode 0x{:02x}",
                 hdr.opcode.opcode_raw()
             );
@@ -5,22 +5,19 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, anyhow, bail};
 use tracing::debug;
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32, U64,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        command::{common::ScsiStatus, zero_copy::RawScsiStatus},
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data::common::RawDataInFlags,
-        data_fromat::ZeroCopyType,
-        identifiers::Itt,
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-    },
+use crate::models::{
+    command::{common::ScsiStatus, zero_copy::RawScsiStatus},
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data::common::RawDataInFlags,
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::Itt,
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
 };
 
 /// Represents the Basic Header Segment (BHS) for a SCSI Data-In PDU (opcode
@@ -109,13 +106,13 @@ impl ScsiDataIn {
     #[inline]
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
         if buf.len() < HEADER_LEN {
-            return Err(anyhow!(
+            return Err(format_err!(
                 "buffer too small for SCSI Data-In BHS: {}",
                 buf.len()
             ));
         }
         let hdr = Self::mut_from_bytes(buf)
-            .map_err(|_| anyhow!("SCSI Data-In: zerocopy prefix error"))?;
+            .map_err(|_| format_err!("SCSI Data-In: zerocopy prefix error"))?;
         // opcode check
         if hdr.opcode.opcode_known() != Some(Opcode::ScsiDataIn) {
             bail!(
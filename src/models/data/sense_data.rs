@@ -7,16 +7,29 @@
 
 use core::fmt;
 
-use anyhow::{Context, Result, anyhow};
-
-use crate::models::data::Entry;
+use crate::models::{
+    data::Entry,
+    error::{Context, Result, format_err},
+};
 
 /// The minimum length of a fixed-format sense data structure.
 pub const FIXED_MIN_LEN: usize = 18;
 
+/// The length of the descriptor-format sense header.
+pub const DESCRIPTOR_MIN_LEN: usize = 8;
+
+/// Information sense data descriptor type.
+pub const SENSE_DESC_INFORMATION: u8 = 0x00;
+/// Sense key specific sense data descriptor type.
+pub const SENSE_DESC_KEY_SPECIFIC: u8 = 0x02;
+/// Stream commands sense data descriptor (FILEMARK, EOM, ILI).
+pub const SENSE_DESC_STREAM: u8 = 0x04;
+/// ATA Status Return sense data descriptor type (SAT).
+pub const SENSE_DESC_ATA_STATUS: u8 = 0x09;
+
 /// Represents SCSI Sense Data, providing detailed error information.
 #[repr(C)]
-#[derive(Default, PartialEq)]
+#[derive(Default, Clone, PartialEq)]
 pub struct SenseData {
     /// Indicates if the information field is valid.
     pub valid: bool,
@@ -30,8 +43,9 @@ pub struct SenseData {
     pub eom: bool,
     /// Filemark indicator.
     pub filemark: bool,
-    /// Command-specific information.
-    pub information: u32,
+    /// INFORMATION field, e.g. the failing LBA of a MEDIUM ERROR. Fixed
+    /// format carries 4 bytes, descriptor format 8.
+    pub information: u64,
     /// The length of the additional sense data.
     pub additional_len: u8,
     /// Command-specific information.
@@ -40,49 +54,67 @@ pub struct SenseData {
     pub asc: u8,
     /// Additional Sense Code Qualifier.
     pub ascq: u8,
+    /// SENSE KEY SPECIFIC bytes (15..17); byte 0 bit 7 is SKSV.
+    pub sense_key_specific: [u8; 3],
 }
 
 impl SenseData {
-    /// Parses a byte buffer into a `SenseData` structure.
+    /// Parses a byte buffer into a `SenseData` structure. Fixed and
+    /// descriptor format are accepted, with or without the 2-byte SENSE
+    /// LENGTH prefix of the iSCSI sense segment.
     pub fn parse(buf: &[u8]) -> Result<Self> {
-        if buf.len() < FIXED_MIN_LEN {
-            return Err(anyhow!("sense buffer too small: {}", buf.len()));
-        }
-
-        let sense = if buf.len() >= 3 {
-            let maybe_len = u16::from_be_bytes([buf[0], buf[1]]) as usize;
-            let rc = buf[2] & 0x7F;
-            if maybe_len + 2 == buf.len() && matches!(rc, 0x70..=0x73) {
-                &buf[2..]
-            } else {
-                buf
-            }
-        } else {
-            buf
+        let sense = strip_sense_length(buf);
+        let Some(&first) = sense.first() else {
+            return Err(format_err!("empty sense buffer"));
         };
 
-        if sense.len() < FIXED_MIN_LEN {
-            return Err(anyhow!(
-                "sense payload too small after prefix stripping: {}",
-                sense.len()
-            ));
-        }
-
-        let response_code = sense[0] & 0x7F;
+        let response_code = first & 0x7F;
 
         match response_code {
             0x70 | 0x71 => Self::parse_fixed(sense),
-            0x72 | 0x73 => Err(anyhow!(
-                "descriptor-format sense (0x{:02x}) is not supported yet",
-                response_code
-            )),
-            other => Err(anyhow!("unknown sense response code 0x{:02x}", other)),
+            0x72 | 0x73 => Self::parse_descriptor(sense),
+            other => Err(format_err!("unknown sense response code 0x{:02x}", other)),
+        }
+    }
+
+    /// Descriptor-format sense: the header carries the codes, the
+    /// INFORMATION and sense-key specific fields come from their
+    /// descriptors when present.
+    fn parse_descriptor(sense: &[u8]) -> Result<Self> {
+        if sense.len() < DESCRIPTOR_MIN_LEN {
+            return Err(format_err!("descriptor sense too small: {}", sense.len()));
         }
+
+        let information = find_sense_descriptor(sense, SENSE_DESC_INFORMATION)
+            .filter(|d| d.len() >= 12);
+        let sense_key_specific = find_sense_descriptor(sense, SENSE_DESC_KEY_SPECIFIC)
+            .filter(|d| d.len() >= 7)
+            .map_or([0; 3], |d| [d[4], d[5], d[6]]);
+        let stream = find_sense_descriptor(sense, SENSE_DESC_STREAM)
+            .filter(|d| d.len() >= 4)
+            .map_or(0, |d| d[3]);
+
+        Ok(SenseData {
+            valid: information.is_some_and(|d| d[2] & 0x80 != 0),
+            response_code: sense[0] & 0x7F,
+            sense_key: sense[1] & 0x0F,
+            ili: stream & 0x20 != 0,
+            eom: stream & 0x40 != 0,
+            filemark: stream & 0x80 != 0,
+            information: information.map_or(0, |d| {
+                u64::from_be_bytes([d[4], d[5], d[6], d[7], d[8], d[9], d[10], d[11]])
+            }),
+            additional_len: sense[7],
+            cmd_specific: 0,
+            asc: sense[2],
+            ascq: sense[3],
+            sense_key_specific,
+        })
     }
 
     fn parse_fixed(sense: &[u8]) -> Result<Self> {
         if sense.len() < FIXED_MIN_LEN {
-            return Err(anyhow!("fixed sense too small: {}", sense.len()));
+            return Err(format_err!("fixed sense too small: {}", sense.len()));
         }
 
         let valid = sense[0] & 0x80 != 0;
@@ -97,13 +129,13 @@ impl SenseData {
             sense[3..7]
                 .try_into()
                 .context("failed to read Information (3..6)")?,
-        );
+        ) as u64;
 
         let additional_len = sense[7];
 
         let needed = 8usize + (additional_len as usize);
         if sense.len() < needed {
-            return Err(anyhow!(
+            return Err(format_err!(
                 "sense length mismatch: have {}, need at least {} (additional_len={})",
                 sense.len(),
                 needed,
@@ -119,6 +151,7 @@ impl SenseData {
 
         let asc = sense[12];
         let ascq = sense[13];
+        let sense_key_specific = [sense[15], sense[16], sense[17]];
 
         Ok(SenseData {
             valid,
@@ -132,8 +165,18 @@ impl SenseData {
             cmd_specific,
             asc,
             ascq,
+            sense_key_specific,
         })
     }
+
+    /// PROGRESS INDICATION of a long-running operation (FORMAT UNIT,
+    /// SANITIZE, a background self-test, ...) as a fraction of 65536, when
+    /// the target set SKSV on NO SENSE or NOT READY sense.
+    pub fn progress(&self) -> Option<u16> {
+        let sks = self.sense_key_specific;
+        (matches!(self.sense_key, 0x00 | 0x02) && sks[0] & 0x80 != 0)
+            .then(|| u16::from_be_bytes([sks[1], sks[2]]))
+    }
 }
 
 impl fmt::Debug for SenseData {
@@ -153,11 +196,46 @@ impl fmt::Debug for SenseData {
             .field("cmd_specific", &self.cmd_specific)
             .field("asc", &format_args!("{:#04x}", self.asc))
             .field("ascq", &format_args!("{:#04x}", self.ascq))
+            .field(
+                "sense_key_specific",
+                &format_args!("{:02x?}", self.sense_key_specific),
+            )
             .field("description", &asc_ascq_to_str(self.asc, self.ascq))
             .finish()
     }
 }
 
+/// Drops the 2-byte SENSE LENGTH an iSCSI sense segment starts with, when
+/// `buf` has one.
+pub fn strip_sense_length(buf: &[u8]) -> &[u8] {
+    if buf.len() >= 3 {
+        let maybe_len = u16::from_be_bytes([buf[0], buf[1]]) as usize;
+        let rc = buf[2] & 0x7F;
+        if maybe_len + 2 == buf.len() && matches!(rc, 0x70..=0x73) {
+            return &buf[2..];
+        }
+    }
+    buf
+}
+
+/// The first sense data descriptor of type `desc_type` in descriptor-format
+/// `sense` (without the SENSE LENGTH prefix), header included.
+pub fn find_sense_descriptor(sense: &[u8], desc_type: u8) -> Option<&[u8]> {
+    if sense.len() < DESCRIPTOR_MIN_LEN || !matches!(sense[0] & 0x7F, 0x72 | 0x73) {
+        return None;
+    }
+    let end = (DESCRIPTOR_MIN_LEN + sense[7] as usize).min(sense.len());
+    let mut off = DESCRIPTOR_MIN_LEN;
+    while off + 2 <= end {
+        let next = (off + 2 + sense[off + 1] as usize).min(end);
+        if sense[off] == desc_type {
+            return Some(&sense[off..next]);
+        }
+        off = next;
+    }
+    None
+}
+
 /// Converts an ASC/ASCQ code pair to a human-readable string.
 #[inline]
 pub fn asc_ascq_to_str(asc: u8, ascq: u8) -> &'static str {
@@ -1,24 +1,55 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::{any::type_name, fmt, marker::PhantomData, ops::Deref};
+use core::{any::type_name, fmt, marker::PhantomData, ops::Deref};
 
-use anyhow::{Context, Result, bail};
 use bytes::{Bytes, BytesMut};
+#[cfg(feature = "std")]
 use crc32c::crc32c_append;
+use thiserror::Error;
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32,
 };
 
-use crate::{
-    cfg::{config::Config, enums::Digest},
-    client::pdu_connection::FromBytes,
-    models::{
-        common::{BasicHeaderSegment, Builder, HEADER_LEN, SendingData},
-        opcode::Opcode,
-    },
+#[cfg(feature = "std")]
+use crate::cfg::{config::Config, enums::Digest};
+use crate::models::{
+    common::{BasicHeaderSegment, Builder, FromBytes, HEADER_LEN, SendingData},
+    error::{Context, Result, bail, ensure, format_err},
+    opcode::Opcode,
+    snapshot::PduSnapshot,
 };
 
+/// A received PDU failed its HeaderDigest or DataDigest check.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{pdu}: {}Digest mismatch ({bhs})", if *.header { "Header" } else { "Data" })]
+pub struct DigestMismatchError {
+    /// Type of the PDU that failed the check.
+    pub pdu: &'static str,
+    /// `true` for the HeaderDigest, `false` for the DataDigest.
+    pub header: bool,
+    /// Header of the PDU that failed the check.
+    pub bhs: PduSnapshot,
+}
+
+impl DigestMismatchError {
+    fn header(pdu: &'static str, bhs: &[u8]) -> Self {
+        Self {
+            pdu,
+            header: true,
+            bhs: PduSnapshot::new(bhs),
+        }
+    }
+
+    fn data(pdu: &'static str, bhs: &[u8]) -> Self {
+        Self {
+            pdu,
+            header: false,
+            bhs: PduSnapshot::new(bhs),
+        }
+    }
+}
+
 /// A marker trait for types that can be used with zerocopy and are suitable for
 /// iSCSI PDUs.
 pub trait ZeroCopyType: KnownLayout + Immutable + IntoBytes + ZFromBytes {}
@@ -28,6 +59,24 @@ fn pad_len(n: usize) -> usize {
     (4 - (n % 4)) % 4
 }
 
+/// Bitwise CRC32C (Castagnoli), continuing from a previous `crc` like
+/// [`crc32c::crc32c_append`]; used when the hardware-accelerated crate is
+/// not available.
+#[cfg(any(not(feature = "std"), test))]
+fn crc32c_append_sw(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for &b in data {
+        c ^= b as u32;
+        for _ in 0..8 {
+            c = (c >> 1) ^ (0x82F6_3B78 & (c & 1).wrapping_neg());
+        }
+    }
+    !c
+}
+
+#[cfg(not(feature = "std"))]
+use crc32c_append_sw as crc32c_append;
+
 #[inline]
 fn crc32c_of_parts(parts: &[&[u8]]) -> u32 {
     let mut acc = 0u32;
@@ -122,7 +171,7 @@ where T: BasicHeaderSegment + SendingData + FromBytes + ZeroCopyType
     /// On the first call, if HeaderDigest is enabled, reserve exactly one
     /// 4-byte slot for it right before DATA; then append DATA and update
     /// DataSegmentLength.
-    fn append_data(&mut self, more: &[u8]) -> anyhow::Result<()> {
+    fn append_data(&mut self, more: &[u8]) -> Result<()> {
         // Once we start writing data, AHS cannot be appended any more.
         self.phase = BuilderPhase::Data;
 
@@ -228,14 +277,29 @@ where T: BasicHeaderSegment + SendingData + FromBytes + ZeroCopyType
 impl<T> PDUWithData<T, Bytes> {
     /// Creates a new `PDUWithData` instance from a header slice and
     /// configuration.
+    #[cfg(feature = "std")]
     pub fn from_header_slice(header_buf: [u8; HEADER_LEN], cfg: &Config) -> Self {
+        Self::from_header_slice_with_digests(
+            header_buf,
+            cfg.login.integrity.header_digest == Digest::CRC32C,
+            cfg.login.integrity.data_digest == Digest::CRC32C,
+        )
+    }
+
+    /// Creates a new `PDUWithData` instance from a header slice, with the
+    /// negotiated HeaderDigest/DataDigest given explicitly.
+    pub fn from_header_slice_with_digests(
+        header_buf: [u8; HEADER_LEN],
+        header_digest: bool,
+        data_digest: bool,
+    ) -> Self {
         Self {
             header_buf,
             payload: Bytes::new(),
-            enable_header_digest: cfg.login.integrity.header_digest == Digest::CRC32C,
+            enable_header_digest: header_digest,
             header_digest: None,
             allocated_header_diggest: false,
-            enable_data_digest: cfg.login.integrity.data_digest == Digest::CRC32C,
+            enable_data_digest: data_digest,
             data_digest: None,
             phase: BuilderPhase::Data,
             _marker: PhantomData,
@@ -245,14 +309,29 @@ impl<T> PDUWithData<T, Bytes> {
 
 impl<T> PDUWithData<T, BytesMut> {
     /// Creates a new `PDUWithData` request instance with a mutable body.
+    #[cfg(feature = "std")]
     pub fn new_request(header_buf: [u8; HEADER_LEN], cfg: &Config) -> Self {
+        Self::new_request_with_digests(
+            header_buf,
+            cfg.login.integrity.header_digest == Digest::CRC32C,
+            cfg.login.integrity.data_digest == Digest::CRC32C,
+        )
+    }
+
+    /// Creates a new `PDUWithData` request instance with a mutable body,
+    /// with the negotiated HeaderDigest/DataDigest given explicitly.
+    pub fn new_request_with_digests(
+        header_buf: [u8; HEADER_LEN],
+        header_digest: bool,
+        data_digest: bool,
+    ) -> Self {
         Self {
             header_buf,
             payload: BytesMut::new(),
-            enable_header_digest: cfg.login.integrity.header_digest == Digest::CRC32C,
+            enable_header_digest: header_digest,
             header_digest: None,
             allocated_header_diggest: false,
-            enable_data_digest: cfg.login.integrity.data_digest == Digest::CRC32C,
+            enable_data_digest: data_digest,
             data_digest: None,
             phase: BuilderPhase::Ahs,
             _marker: PhantomData,
@@ -269,9 +348,9 @@ impl<T> PDUWithData<T, BytesMut> {
     /// # Errors
     ///
     /// Returns an error if the header view cannot be obtained.
-    pub fn append_ahs(&mut self, ahs: &[u8]) -> anyhow::Result<()>
+    pub fn append_ahs(&mut self, ahs: &[u8]) -> Result<()>
     where T: BasicHeaderSegment + FromBytes + ZeroCopyType {
-        anyhow::ensure!(
+        ensure!(
             self.phase == BuilderPhase::Ahs,
             "cannot append AHS after data has been written"
         );
@@ -290,7 +369,7 @@ impl<T> PDUWithData<T, BytesMut> {
         // Insert AHS bytes at the front, before any HD placeholder + data.
         let mut new_payload = BytesMut::with_capacity(ahs.len() + self.payload.len());
         new_payload.extend_from_slice(ahs);
-        new_payload.unsplit(std::mem::replace(&mut self.payload, BytesMut::new()));
+        new_payload.unsplit(core::mem::replace(&mut self.payload, BytesMut::new()));
         self.payload = new_payload;
         Ok(())
     }
@@ -346,14 +425,14 @@ impl<T> PDUWithData<T, BytesMut> {
         if self.enable_header_digest {
             let want = compute_header_digest(&self.header_buf, self.additional_header()?);
             if self.header_digest.map(|x| x.get()) != Some(want) {
-                bail!("{tn}: HeaderDigest mismatch");
+                return Err(DigestMismatchError::header(tn, &self.header_buf).into());
             }
         }
         if self.enable_data_digest {
             let data = self.data()?;
             let want = compute_data_digest(data);
             if !data.is_empty() && self.data_digest.map(|x| x.get()) != Some(want) {
-                bail!("{tn}: DataDigest mismatch");
+                return Err(DigestMismatchError::data(tn, &self.header_buf).into());
             }
         }
 
@@ -376,8 +455,7 @@ where
     #[inline]
     pub fn header_view(&self) -> Result<&T>
     where T: FromBytes + ZeroCopyType {
-        T::ref_from_bytes(self.header_buf.as_slice())
-            .map_err(|e| anyhow::anyhow!("{}", e))
+        T::ref_from_bytes(self.header_buf.as_slice()).map_err(|e| format_err!("{}", e))
     }
 
     /// Returns a mutable view of the PDU's header.
@@ -385,7 +463,7 @@ where
     pub fn header_view_mut(&mut self) -> Result<&mut T>
     where T: FromBytes + ZeroCopyType {
         T::mut_from_bytes(self.header_buf.as_mut_slice())
-            .map_err(|e| anyhow::anyhow!("{}", e))
+            .map_err(|e| format_err!("{}", e))
     }
 
     /// Returns a slice of the Additional Header Segment (AHS).
@@ -409,7 +487,7 @@ where
     }
 
     /// Rebinds the PDU to a different header type.
-    pub fn rebind_pdu<U>(self) -> anyhow::Result<PDUWithData<U, B>>
+    pub fn rebind_pdu<U>(self) -> Result<PDUWithData<U, B>>
     where U: BasicHeaderSegment {
         Ok(PDUWithData::<U, B> {
             header_buf: self.header_buf,
@@ -472,14 +550,14 @@ where T: BasicHeaderSegment + FromBytes + ZeroCopyType
         if hd_len != 0 {
             let want = compute_header_digest(&self.header_buf, self.additional_header()?);
             if self.header_digest.map(|x| x.get()) != Some(want) {
-                bail!("{tn}: HeaderDigest mismatch");
+                return Err(DigestMismatchError::header(tn, &self.header_buf).into());
             }
         }
         if dd_len != 0 {
             let data = self.data()?;
             let want = compute_data_digest(data);
             if !data.is_empty() && self.data_digest.map(|x| x.get()) != Some(want) {
-                bail!("{tn}: DataDigest mismatch");
+                return Err(DigestMismatchError::data(tn, &self.header_buf).into());
             }
         }
         Ok(())
@@ -533,3 +611,22 @@ impl<
         s.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn software_crc32c_matches_rfc_vectors() {
+        // RFC 3720, appendix B.4.
+        let inc: Vec<u8> = (0..32).collect();
+        let dec: Vec<u8> = (0..32).rev().collect();
+        assert_eq!(crc32c_append_sw(0, &[0u8; 32]), 0x8A91_36AA);
+        assert_eq!(crc32c_append_sw(0, &[0xFFu8; 32]), 0x62A8_AB43);
+        assert_eq!(crc32c_append_sw(0, &inc), 0x46DD_794E);
+        assert_eq!(crc32c_append_sw(0, &dec), 0x113F_DB5C);
+
+        let (a, b) = inc.split_at(11);
+        assert_eq!(crc32c_append_sw(crc32c_append_sw(0, a), b), 0x46DD_794E);
+    }
+}
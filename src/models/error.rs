@@ -0,0 +1,168 @@
+//! Error type of the PDU models and CDB builders.
+//!
+//! Everything that parses or builds wire data returns [`ProtocolError`],
+//! available with or without `std`. It keeps the whole cause chain, so typed
+//! causes such as [`UnknownOpcode`](crate::models::opcode::UnknownOpcode)
+//! stay reachable through [`ProtocolError::downcast_ref`] and
+//! [`core::error::Error::source`].
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use alloc::string::ToString;
+use core::fmt::{self, Debug, Display};
+
+/// `Result` of the PDU models and CDB builders.
+pub type Result<T, E = ProtocolError> = core::result::Result<T, E>;
+
+/// Malformed wire data, an invalid argument of a builder, or a field the
+/// target filled with a value this crate does not know.
+pub struct ProtocolError(anyhow::Error);
+
+impl ProtocolError {
+    /// An error with only a message.
+    pub fn msg<M>(message: M) -> Self
+    where M: Display + Debug + Send + Sync + 'static {
+        Self(anyhow::Error::msg(message))
+    }
+
+    /// An error caused by `error`, which stays reachable through
+    /// [`ProtocolError::downcast_ref`].
+    pub fn new<E>(error: E) -> Self
+    where E: core::error::Error + Send + Sync + 'static {
+        Self(anyhow::Error::new(error))
+    }
+
+    /// Wrap the error with `context`.
+    pub fn context<C>(self, context: C) -> Self
+    where C: Display + Send + Sync + 'static {
+        Self(self.0.context(context))
+    }
+
+    /// The cause of type `E` anywhere in the chain.
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+    where E: Display + Debug + Send + Sync + 'static {
+        self.0.downcast_ref()
+    }
+
+    /// Whether the chain contains a cause of type `E`.
+    pub fn is<E>(&self) -> bool
+    where E: Display + Debug + Send + Sync + 'static {
+        self.0.is::<E>()
+    }
+
+    /// This error and its causes, outermost first.
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn core::error::Error + 'static)> {
+        self.0.chain()
+    }
+
+    /// The chain as an `anyhow` error, for the std side of the crate.
+    #[cfg(feature = "std")]
+    pub(crate) fn into_anyhow(self) -> anyhow::Error {
+        self.0
+    }
+}
+
+impl Debug for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+/// Shows the outermost message; `{:#}` shows the whole chain.
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl core::error::Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+impl From<anyhow::Error> for ProtocolError {
+    fn from(error: anyhow::Error) -> Self {
+        Self(error)
+    }
+}
+
+macro_rules! impl_from_cause {
+    ($($ty:ty),* $(,)?) => {
+        $(impl From<$ty> for ProtocolError {
+            fn from(error: $ty) -> Self {
+                Self::new(error)
+            }
+        })*
+    };
+}
+
+impl_from_cause!(
+    core::array::TryFromSliceError,
+    core::num::TryFromIntError,
+    core::num::ParseIntError,
+    core::str::Utf8Error,
+    alloc::string::FromUtf8Error,
+    crate::models::opcode::UnknownOpcode,
+    crate::models::command::common::UnknownResponseCode,
+    crate::models::command::common::UnknownScsiStatus,
+    crate::models::data_fromat::DigestMismatchError,
+    crate::control_block::protection::PiError,
+);
+
+/// Adds a message to the error of a `Result` or to a `None`, like
+/// `anyhow::Context`.
+pub(crate) trait Context<T> {
+    fn context<C>(self, context: C) -> Result<T>
+    where C: Display + Send + Sync + 'static;
+}
+
+impl<T, E> Context<T> for core::result::Result<T, E>
+where E: Into<ProtocolError>
+{
+    fn context<C>(self, context: C) -> Result<T>
+    where C: Display + Send + Sync + 'static {
+        self.map_err(|e| e.into().context(context))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<C>(self, context: C) -> Result<T>
+    where C: Display + Send + Sync + 'static {
+        self.ok_or_else(|| ProtocolError(anyhow::Error::msg(context.to_string())))
+    }
+}
+
+/// Builds a [`ProtocolError`] from a format string, like `anyhow!`.
+macro_rules! format_err {
+    ($($arg:tt)*) => {
+        $crate::models::error::ProtocolError::from(::anyhow::anyhow!($($arg)*))
+    };
+}
+
+/// Returns early with a [`ProtocolError`], like `anyhow::bail!`.
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return ::core::result::Result::Err($crate::models::error::format_err!($($arg)*))
+    };
+}
+
+/// Returns early with a [`ProtocolError`] unless the condition holds, like
+/// `anyhow::ensure!`.
+macro_rules! ensure {
+    ($cond:expr $(,)?) => {
+        if !$cond {
+            $crate::models::error::bail!(concat!("Condition failed: `", stringify!($cond), "`"));
+        }
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::models::error::bail!($($arg)+);
+        }
+    };
+}
+
+pub(crate) use bail;
+pub(crate) use ensure;
+pub(crate) use format_err;
@@ -8,14 +8,21 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::{
-    fmt::{self, Write},
+#[cfg(feature = "std")]
+use alloc::string::String;
+#[cfg(feature = "std")]
+use core::fmt::Write;
+use core::{
+    fmt,
+    str::FromStr,
     sync::atomic::{AtomicU32, Ordering},
 };
 
-use anyhow::bail;
+#[cfg(feature = "std")]
 use rand::RngExt;
 
+use crate::models::error::{ProtocolError, Result, bail, format_err};
+
 // ── Initiator Session Identifier (ISID) ─────────────────────────────────────
 
 /// Six-byte identifier chosen by the initiator to identify an iSCSI session.
@@ -25,6 +32,7 @@ pub struct Isid([u8; 6]);
 impl Isid {
     /// Generates a random RFC-compatible ISID and its lowercase hexadecimal
     /// representation.
+    #[cfg(feature = "std")]
     pub fn generate() -> (Self, String) {
         let mut raw = [0u8; 6];
         rand::rng().fill(&mut raw);
@@ -55,6 +63,33 @@ impl Isid {
     pub const fn as_bytes(&self) -> &[u8; 6] {
         &self.0
     }
+
+    /// Returns this ISID with its 16-bit Qualifier (bytes 4..6) advanced by
+    /// `offset`, e.g. to give each session of one initiator its own ISID.
+    pub const fn with_qualifier_offset(self, offset: u16) -> Self {
+        let mut raw = self.0;
+        let [hi, lo] = u16::from_be_bytes([raw[4], raw[5]])
+            .wrapping_add(offset)
+            .to_be_bytes();
+        raw[4] = hi;
+        raw[5] = lo;
+        Self(raw)
+    }
+}
+
+impl FromStr for Isid {
+    type Err = ProtocolError;
+
+    /// Parses 12 hexadecimal digits, optionally prefixed with `0x`.
+    fn from_str(s: &str) -> Result<Self> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        let bytes =
+            hex::decode(digits).map_err(|e| format_err!("invalid ISID '{s}': {e}"))?;
+        let raw: [u8; 6] = bytes
+            .try_into()
+            .map_err(|_| format_err!("ISID '{s}' must be exactly 6 bytes"))?;
+        Ok(Self(raw))
+    }
 }
 
 impl From<[u8; 6]> for Isid {
@@ -163,7 +198,7 @@ impl Itt {
 
     /// Creates an ITT, rejecting the reserved `0xFFFF_FFFF` value.
     #[inline]
-    pub fn new(raw: u32) -> anyhow::Result<Self> {
+    pub fn new(raw: u32) -> Result<Self> {
         if raw == Self::RESERVED {
             bail!("ITT 0xFFFFFFFF is reserved");
         }
@@ -193,10 +228,49 @@ impl fmt::Display for Itt {
 // ── Logical Unit Number (LUN) ───────────────────────────────────────────────
 
 /// Encoded iSCSI Logical Unit Number used to address a target logical unit.
+///
+/// The value is the 8-byte LUN field of SAM-5 § 4.7, the same bytes that
+/// travel in PDU headers and REPORT LUNS entries. Most targets use a
+/// single-level LUN in the first two bytes; [`Lun::from_number`] picks
+/// the usual encoding for a plain LUN number, and [`Lun::addressing`] goes
+/// back from the wire form.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct Lun(u64);
 
+/// First-level address of a [`Lun`], by addressing method (SAM-5 § 4.7).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LunAddressing {
+    /// Peripheral device addressing (method 00b). Bus 0 addresses a logical
+    /// unit of the target itself.
+    Peripheral {
+        /// Bus identifier (6 bits).
+        bus: u8,
+        /// Target or LUN (8 bits).
+        lun: u8,
+    },
+    /// Flat space addressing (method 01b), LUNs 0..16384.
+    Flat(u16),
+    /// Logical unit addressing (method 10b).
+    LogicalUnit {
+        /// Target (6 bits).
+        target: u8,
+        /// Bus number (3 bits).
+        bus: u8,
+        /// LUN (5 bits).
+        lun: u8,
+    },
+    /// Extended flat space addressing (method 11b, length 01b, extended
+    /// method 2h), 24-bit LUNs.
+    ExtendedFlat(u32),
+    /// Any other encoding, including hierarchical (multi-level) LUNs.
+    Other(u64),
+}
+
 impl Lun {
+    /// Largest LUN expressible with extended flat space addressing.
+    pub const MAX_EXTENDED_FLAT: u32 = 0x00FF_FFFF;
+    /// Largest LUN expressible with flat space addressing.
+    pub const MAX_FLAT: u16 = 0x3FFF;
     /// Logical unit zero.
     pub const ZERO: Self = Self(0);
 
@@ -211,6 +285,116 @@ impl Lun {
     pub const fn get(self) -> u64 {
         self.0
     }
+
+    /// Creates a LUN from the 8 bytes of a LUN field (e.g. a REPORT LUNS
+    /// entry).
+    #[inline]
+    pub const fn from_bytes(bytes: [u8; 8]) -> Self {
+        Self(u64::from_be_bytes(bytes))
+    }
+
+    /// Returns the 8 bytes of the LUN field.
+    #[inline]
+    pub const fn to_bytes(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+
+    /// LUN number `n` as Linux and most targets encode it: peripheral device
+    /// addressing below 256, flat space addressing up to 16383 and extended
+    /// flat space addressing above.
+    pub fn from_number(n: u16) -> Self {
+        let [hi, lo] = n.to_be_bytes();
+        if n <= 0xFF {
+            Self::first_level([0, lo])
+        } else if n <= Self::MAX_FLAT {
+            Self::first_level([0x40 | hi, lo])
+        } else {
+            Self::from_bytes([0xD2, 0, hi, lo, 0, 0, 0, 0])
+        }
+    }
+
+    /// Peripheral device addressing of `lun` on `bus` (bus 0 is the target's
+    /// own logical units).
+    pub fn peripheral(bus: u8, lun: u8) -> Result<Self> {
+        if bus > 0x3F {
+            bail!("peripheral LUN bus {bus} does not fit in 6 bits");
+        }
+        Ok(Self::first_level([bus, lun]))
+    }
+
+    /// Flat space addressing of `lun`.
+    pub fn flat(lun: u16) -> Result<Self> {
+        if lun > Self::MAX_FLAT {
+            bail!("flat space LUN {lun} exceeds {}", Self::MAX_FLAT);
+        }
+        let [hi, lo] = lun.to_be_bytes();
+        Ok(Self::first_level([0x40 | hi, lo]))
+    }
+
+    /// Logical unit addressing of `lun` behind `target` on `bus`.
+    pub fn logical_unit(target: u8, bus: u8, lun: u8) -> Result<Self> {
+        if target > 0x3F || bus > 0x07 || lun > 0x1F {
+            bail!(
+                "logical unit address target={target} bus={bus} lun={lun} out of range"
+            );
+        }
+        Ok(Self::first_level([0x80 | target, (bus << 5) | lun]))
+    }
+
+    /// Extended flat space addressing of a 24-bit `lun`.
+    pub fn extended_flat(lun: u32) -> Result<Self> {
+        if lun > Self::MAX_EXTENDED_FLAT {
+            bail!(
+                "extended flat space LUN {lun} exceeds {}",
+                Self::MAX_EXTENDED_FLAT
+            );
+        }
+        let [_, b1, b2, b3] = lun.to_be_bytes();
+        Ok(Self::from_bytes([0xD2, b1, b2, b3, 0, 0, 0, 0]))
+    }
+
+    /// Decodes the addressing method of a single-level LUN.
+    pub fn addressing(self) -> LunAddressing {
+        let b = self.to_bytes();
+        if b[0] == 0xD2 && b[4..] == [0; 4] {
+            return LunAddressing::ExtendedFlat(u32::from_be_bytes([
+                0, b[1], b[2], b[3],
+            ]));
+        }
+        if b[2..] != [0; 6] {
+            return LunAddressing::Other(self.0);
+        }
+        match b[0] >> 6 {
+            0b00 => LunAddressing::Peripheral {
+                bus: b[0] & 0x3F,
+                lun: b[1],
+            },
+            0b01 => LunAddressing::Flat(u16::from_be_bytes([b[0] & 0x3F, b[1]])),
+            0b10 => LunAddressing::LogicalUnit {
+                target: b[0] & 0x3F,
+                bus: b[1] >> 5,
+                lun: b[1] & 0x1F,
+            },
+            _ => LunAddressing::Other(self.0),
+        }
+    }
+
+    /// The plain LUN number of a single-level LUN addressed on the target
+    /// itself (peripheral bus 0, flat or extended flat space), as shown by
+    /// most tools.
+    pub fn number(self) -> Option<u32> {
+        match self.addressing() {
+            LunAddressing::Peripheral { bus: 0, lun } => Some(lun as u32),
+            LunAddressing::Flat(lun) => Some(lun as u32),
+            LunAddressing::ExtendedFlat(lun) => Some(lun),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    const fn first_level([b0, b1]: [u8; 2]) -> Self {
+        Self::from_bytes([b0, b1, 0, 0, 0, 0, 0, 0])
+    }
 }
 
 impl From<u64> for Lun {
@@ -220,6 +404,31 @@ impl From<u64> for Lun {
     }
 }
 
+impl From<[u8; 8]> for Lun {
+    #[inline]
+    fn from(bytes: [u8; 8]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl FromStr for Lun {
+    type Err = ProtocolError;
+
+    /// Parses a decimal LUN number (encoded as by [`Lun::from_number`]) or the
+    /// raw 64-bit LUN field written as `0x...`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16)
+                .map(Self)
+                .map_err(|e| format_err!("invalid LUN field '{s}': {e}")),
+            None => s
+                .parse::<u16>()
+                .map(Self::from_number)
+                .map_err(|e| format_err!("invalid LUN number '{s}': {e}")),
+        }
+    }
+}
+
 impl fmt::Display for Lun {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "LUN(0x{:016X})", self.0)
@@ -237,7 +446,7 @@ impl Ttt {
     pub const NONE: u32 = u32::MAX;
 
     /// Creates a TTT, rejecting the reserved `0xFFFF_FFFF` value.
-    pub fn new(raw: u32) -> anyhow::Result<Self> {
+    pub fn new(raw: u32) -> Result<Self> {
         if raw == Self::NONE {
             bail!("TTT 0xFFFFFFFF is reserved");
         }
@@ -366,8 +575,22 @@ impl IttGen {
 
 #[cfg(test)]
 mod tests {
-    use super::Isid;
+    use super::{Isid, Lun, LunAddressing};
 
+    #[test]
+    fn isid_parses_hex_and_offsets_qualifier() {
+        let isid: Isid = "0x400001370000".parse().expect("valid ISID");
+        assert_eq!(isid.get(), [0x40, 0x00, 0x01, 0x37, 0x00, 0x00]);
+        assert_eq!(isid.to_string(), "400001370000");
+        assert_eq!(
+            isid.with_qualifier_offset(0x0102).get(),
+            [0x40, 0x00, 0x01, 0x37, 0x01, 0x02]
+        );
+        assert!("4000013700".parse::<Isid>().is_err());
+        assert!("zz0001370000".parse::<Isid>().is_err());
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn generated_isid_matches_hex_representation() {
         let (isid, hex) = Isid::generate();
@@ -378,4 +601,79 @@ mod tests {
             isid.as_bytes()
         );
     }
+
+    #[test]
+    fn lun_numbers_use_the_common_encodings() {
+        assert_eq!(Lun::from_number(1).get(), 1 << 48);
+        assert_eq!(
+            Lun::from_number(300).to_bytes(),
+            [0x41, 0x2C, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            Lun::from_number(20_000).to_bytes(),
+            [0xD2, 0, 0x4E, 0x20, 0, 0, 0, 0]
+        );
+        for n in [
+            0u16,
+            1,
+            255,
+            256,
+            Lun::MAX_FLAT,
+            Lun::MAX_FLAT + 1,
+            u16::MAX,
+        ] {
+            assert_eq!(Lun::from_number(n).number(), Some(n as u32), "n={n}");
+        }
+    }
+
+    #[test]
+    fn lun_addressing_round_trips() {
+        let cases = [
+            (
+                Lun::peripheral(2, 7).expect("peripheral"),
+                LunAddressing::Peripheral { bus: 2, lun: 7 },
+            ),
+            (
+                Lun::flat(0x1234).expect("flat"),
+                LunAddressing::Flat(0x1234),
+            ),
+            (
+                Lun::logical_unit(5, 3, 17).expect("logical unit"),
+                LunAddressing::LogicalUnit {
+                    target: 5,
+                    bus: 3,
+                    lun: 17,
+                },
+            ),
+            (
+                Lun::extended_flat(0xABCDEF).expect("extended flat"),
+                LunAddressing::ExtendedFlat(0xABCDEF),
+            ),
+        ];
+        for (lun, addressing) in cases {
+            assert_eq!(lun.addressing(), addressing);
+            assert_eq!(Lun::from_bytes(lun.to_bytes()), lun);
+        }
+        assert_eq!(Lun::peripheral(2, 7).expect("peripheral").number(), None);
+        assert!(Lun::peripheral(64, 0).is_err());
+        assert!(Lun::flat(Lun::MAX_FLAT + 1).is_err());
+        assert!(Lun::logical_unit(0, 8, 0).is_err());
+
+        let two_level = Lun::new(0x4001_0002_0000_0000);
+        assert_eq!(
+            two_level.addressing(),
+            LunAddressing::Other(two_level.get())
+        );
+    }
+
+    #[test]
+    fn lun_parses_numbers_and_raw_fields() {
+        assert_eq!("1".parse::<Lun>().expect("number"), Lun::from_number(1));
+        assert_eq!(
+            "0x0001000000000000".parse::<Lun>().expect("raw"),
+            Lun::from_number(1)
+        );
+        assert!("65536".parse::<Lun>().is_err());
+        assert!("0xzz".parse::<Lun>().is_err());
+    }
 }
@@ -4,11 +4,13 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::fmt;
+use alloc::vec::Vec;
+use core::fmt;
 
-use anyhow::Result;
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
+use crate::models::error::{ProtocolError, Result, format_err};
+
 bitflags::bitflags! {
     #[derive(Default, PartialEq)]
     pub struct LoginFlags: u8 {
@@ -24,11 +26,11 @@ bitflags::bitflags! {
 }
 
 impl TryFrom<u8> for LoginFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         LoginFlags::from_bits(value)
-            .ok_or_else(|| anyhow::anyhow!("invalid LoginFlags: {:#08b}", value))
+            .ok_or_else(|| format_err!("invalid LoginFlags: {:#08b}", value))
     }
 }
 
@@ -187,7 +189,7 @@ impl RawLoginFlags {
 }
 
 impl TryFrom<RawLoginFlags> for LoginFlags {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     #[inline]
     fn try_from(r: RawLoginFlags) -> Result<Self> {
@@ -4,20 +4,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, bail};
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U16, U32,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data_fromat::ZeroCopyType,
-        identifiers::{Cid, CmdSn, Isid, Itt, StatSn, Tsih},
-        login::common::{RawLoginFlags, Stage},
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-    },
+use crate::models::{
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::{Cid, CmdSn, Isid, Itt, StatSn, Tsih},
+    login::common::{RawLoginFlags, Stage},
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
 };
 
 /// Basic Header Segment for iSCSI Login Request PDU
@@ -62,9 +59,9 @@ impl LoginRequest {
     /// Deserializes the BHS from a byte buffer.
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
         let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed convert buffer LoginRequest: {e}"))?;
+            .map_err(|e| format_err!("failed convert buffer LoginRequest: {e}"))?;
         if hdr.opcode.opcode_known() != Some(Opcode::LoginReq) {
-            anyhow::bail!(
+            bail!(
                 "LoginRequest: invalid opcode 0x{:02x}",
                 hdr.opcode.opcode_raw()
             );
@@ -6,9 +6,10 @@
 
 use core::fmt;
 
-use anyhow::{Result, anyhow, bail};
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
+use crate::models::error::{ProtocolError, Result, bail, format_err};
+
 /// The status classes as per RFC 3720 §11.11.1
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -69,12 +70,12 @@ pub enum SuccessDetail {
 }
 
 impl TryFrom<u8> for SuccessDetail {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(raw: u8) -> Result<Self> {
         match raw {
             0x00 => Ok(SuccessDetail::CmdCompletedNormally),
-            other => Err(anyhow!("unknown Success detail code: {:#04x}", other)),
+            other => Err(format_err!("unknown Success detail code: {:#04x}", other)),
         }
     }
 }
@@ -88,12 +89,15 @@ pub enum RedirectionDetail {
 }
 
 impl TryFrom<u8> for RedirectionDetail {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(raw: u8) -> Result<Self> {
         match raw {
             0x01 => Ok(RedirectionDetail::TargetRedirected),
-            other => Err(anyhow!("unknown Redirection detail code: {:#04x}", other)),
+            other => Err(format_err!(
+                "unknown Redirection detail code: {:#04x}",
+                other
+            )),
         }
     }
 }
@@ -133,7 +137,7 @@ pub enum InitiatorErrorDetail {
 }
 
 impl TryFrom<u8> for InitiatorErrorDetail {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(byte: u8) -> Result<Self> {
         match byte {
@@ -149,7 +153,7 @@ impl TryFrom<u8> for InitiatorErrorDetail {
             0x09 => Ok(InitiatorErrorDetail::SessionTypeNotSupported),
             0x0a => Ok(InitiatorErrorDetail::SessionDoesNotExist),
             0x0b => Ok(InitiatorErrorDetail::InvalidDuringLogin),
-            other => Err(anyhow!("unknown InitiatorErrorDetail: 0x{:02x}", other)),
+            other => Err(format_err!("unknown InitiatorErrorDetail: 0x{:02x}", other)),
         }
     }
 }
@@ -173,7 +177,7 @@ pub enum TargetErrorDetail {
 }
 
 impl TryFrom<u8> for TargetErrorDetail {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(raw: u8) -> Result<Self, Self::Error> {
         let detail = match raw {
@@ -189,7 +193,7 @@ impl TryFrom<u8> for TargetErrorDetail {
 }
 
 impl TryFrom<(StatusClass, u8)> for StatusDetail {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from((class, raw): (StatusClass, u8)) -> Result<Self> {
         Ok(match class {
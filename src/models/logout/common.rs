@@ -4,11 +4,13 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::fmt;
+use alloc::format;
+use core::fmt;
 
-use anyhow::{Result, bail};
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
+use crate::models::error::{ProtocolError, Result, bail};
+
 /// iSCSI Logout Reason Code.
 #[derive(Debug, Default, PartialEq, Clone)]
 #[repr(u8)]
@@ -35,7 +37,7 @@ impl LogoutReason {
 }
 
 impl TryFrom<u8> for LogoutReason {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(value: u8) -> Result<Self> {
         Ok(match value {
@@ -105,7 +107,7 @@ impl RawLogoutReason {
 /* Convenience conversions */
 
 impl TryFrom<RawLogoutReason> for LogoutReason {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     #[inline]
     fn try_from(w: RawLogoutReason) -> Result<Self> {
@@ -156,7 +158,7 @@ impl LogoutResponseCode {
 }
 
 impl TryFrom<u8> for LogoutResponseCode {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(v: u8) -> Result<Self> {
         Ok(match v {
@@ -224,7 +226,7 @@ impl RawLogoutResponseCode {
 }
 
 impl TryFrom<RawLogoutResponseCode> for LogoutResponseCode {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     #[inline]
     fn try_from(w: RawLogoutResponseCode) -> Result<Self> {
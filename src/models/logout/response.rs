@@ -5,21 +5,18 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, bail};
 use tracing::{error, warn};
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U16, U32,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data_fromat::ZeroCopyType,
-        identifiers::Itt,
-        logout::common::RawLogoutResponseCode,
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-    },
+use crate::models::{
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::Itt,
+    logout::common::RawLogoutResponseCode,
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
 };
 
 /// Represents the Basic Header Segment (BHS) for a Logout Response PDU.
@@ -57,9 +54,9 @@ impl LogoutResponse {
     /// Deserializes the BHS from a byte buffer.
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
         let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed convert buffer LogoutResponse: {e}"))?;
+            .map_err(|e| format_err!("failed convert buffer LogoutResponse: {e}"))?;
         if hdr.opcode.opcode_known() != Some(Opcode::LogoutResp) {
-            anyhow::bail!(
+            bail!(
                 "LogoutResponse: invalid opcode 0x{:02x}",
                 hdr.opcode.opcode_raw()
             );
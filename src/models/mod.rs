@@ -3,6 +3,8 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
+/// Defines the structures for Asynchronous Message PDUs.
+pub mod async_message;
 /// Defines the structures for SCSI Command PDUs.
 pub mod command;
 /// Defines common structures and traits for iSCSI models.
@@ -11,6 +13,8 @@ pub mod common;
 pub mod data;
 /// Defines the generic PDU container and related traits.
 pub mod data_fromat;
+/// Error type of the PDU models and CDB builders.
+pub mod error;
 /// Typed wrappers for iSCSI identifiers (ITT, LUN, TTT).
 pub mod identifiers;
 /// Defines the structures for Login PDUs.
@@ -27,5 +31,9 @@ pub mod parse;
 pub mod ready_2_transfer;
 /// Defines the structure for Reject PDUs.
 pub mod reject;
+/// Copies of offending PDU headers carried inside errors.
+pub mod snapshot;
+/// Defines the structures for Task Management Function PDUs.
+pub mod task_mgmt;
 /// Defines the structures for Text PDUs.
 pub mod text;
@@ -5,20 +5,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, anyhow, bail};
 use tracing::warn;
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32, U64,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data_fromat::ZeroCopyType,
-        identifiers::Itt,
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-    },
+use crate::models::{
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::Itt,
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
 };
 
 /// Represents the Basic Header Segment (BHS) for a NOP-In PDU.
@@ -51,7 +48,7 @@ impl NopInResponse {
     /// Deserializes the BHS from a byte buffer.
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
         let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
-            .map_err(|e| anyhow!("failed convert buffer NopInResponse: {e}"))?;
+            .map_err(|e| format_err!("failed convert buffer NopInResponse: {e}"))?;
         if hdr.opcode.opcode_known() != Some(Opcode::NopIn) {
             bail!(
                 "NopInResponse: invalid opcode 0x{:02x}",
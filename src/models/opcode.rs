@@ -23,11 +23,12 @@
 //! * merge a pair back into the raw byte (`From<&BhsOpcode> for u8`).
 
 use core::fmt;
-use std::convert::TryFrom;
 
 use thiserror::Error;
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
+use crate::models::error::{ProtocolError, Result};
+
 /// Mask that selects the lower 6 bits (**OPCODE**) from the first BHS byte.
 const OPCODE_MASK: u8 = 0b0011_1111;
 /// Mask that selects the upper 1 bits (**I**) from the first BHS byte.
@@ -54,6 +55,7 @@ pub enum Opcode {
     ScsiDataIn = 0x25,
     LogoutResp = 0x26,
     ReadyToTransfer = 0x31,
+    AsyncMsg = 0x32,
     /* 0x27–0x3E reserved */
     Reject = 0x3F,
 }
@@ -77,6 +79,7 @@ impl Opcode {
             0x25 => Self::ScsiDataIn,
             0x26 => Self::LogoutResp,
             0x31 => Self::ReadyToTransfer,
+            0x32 => Self::AsyncMsg,
             0x3F => Self::Reject,
             _ => return None,
         })
@@ -100,7 +103,7 @@ pub struct BhsOpcode {
 }
 
 impl TryFrom<u8> for BhsOpcode {
-    type Error = anyhow::Error;
+    type Error = ProtocolError;
 
     fn try_from(byte: u8) -> Result<Self, Self::Error> {
         let flags = (byte & I_MASK) != 0;
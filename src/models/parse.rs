@@ -5,13 +5,14 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, bail};
 use enum_dispatch::enum_dispatch;
 
 use crate::models::{
+    async_message::response::AsyncMessage,
     command::{request::ScsiCommandRequest, response::ScsiCommandResponse},
     common::{BasicHeaderSegment, SendingData},
     data::{request::ScsiDataOut, response::ScsiDataIn},
+    error::{Result, format_err},
     identifiers::Itt,
     login::{request::LoginRequest, response::LoginResponse},
     logout::{request::LogoutRequest, response::LogoutResponse},
@@ -19,6 +20,7 @@ use crate::models::{
     opcode::{BhsOpcode, Opcode},
     ready_2_transfer::response::ReadyToTransfer,
     reject::response::RejectPdu,
+    task_mgmt::{request::TaskMgmtRequest, response::TaskMgmtResponse},
     text::{request::TextRequest, response::TextResponse},
 };
 
@@ -41,13 +43,16 @@ pub enum Pdu<'a> {
     ReadyToTransfer(&'a mut ReadyToTransfer),
     LogoutRequest(&'a mut LogoutRequest),
     LogoutResponse(&'a mut LogoutResponse),
+    TaskMgmtRequest(&'a mut TaskMgmtRequest),
+    TaskMgmtResponse(&'a mut TaskMgmtResponse),
+    AsyncMessage(&'a mut AsyncMessage),
 }
 
 impl<'a> Pdu<'a> {
     /// Parses a PDU from its Basic Header Segment (BHS) bytes.
     pub fn from_bhs_bytes(bytes: &'a mut [u8]) -> Result<Self> {
         let bhs = BhsOpcode::try_from(bytes[0])
-            .map_err(|e| anyhow::anyhow!("invalid opcode: {}", e))?;
+            .map_err(|e| format_err!("invalid opcode: {}", e))?;
         match bhs.opcode {
             Opcode::NopOut => {
                 let req = NopOutRequest::from_bhs_bytes(bytes)?;
@@ -105,7 +110,18 @@ impl<'a> Pdu<'a> {
                 let req = LogoutResponse::from_bhs_bytes(bytes)?;
                 Ok(Pdu::LogoutResponse(req))
             },
-            other => bail!("unsupported opcode: {:?}", other),
+            Opcode::ScsiTaskMgmtReq => {
+                let req = TaskMgmtRequest::from_bhs_bytes(bytes)?;
+                Ok(Pdu::TaskMgmtRequest(req))
+            },
+            Opcode::ScsiTaskMgmtResp => {
+                let rsp = TaskMgmtResponse::from_bhs_bytes(bytes)?;
+                Ok(Pdu::TaskMgmtResponse(rsp))
+            },
+            Opcode::AsyncMsg => {
+                let rsp = AsyncMessage::from_bhs_bytes(bytes)?;
+                Ok(Pdu::AsyncMessage(rsp))
+            },
         }
     }
 }
@@ -5,21 +5,18 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, bail};
 use tracing::warn;
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32, U64,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data_fromat::ZeroCopyType,
-        identifiers::Itt,
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-        reject::reject_description::RawRejectReason,
-    },
+use crate::models::{
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::Itt,
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
+    reject::reject_description::RawRejectReason,
 };
 
 /// Represents the Basic Header Segment (BHS) for a Reject PDU.
@@ -56,9 +53,9 @@ impl RejectPdu {
     /// Deserializes the BHS from a byte buffer.
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
         let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed convert buffer RejectPdu: {e}"))?;
+            .map_err(|e| format_err!("failed convert buffer RejectPdu: {e}"))?;
         if hdr.opcode.opcode_known() != Some(Opcode::Reject) {
-            anyhow::bail!(
+            bail!(
                 "RejectPdu: invalid opcode 0x{:02x}",
                 hdr.opcode.opcode_raw()
             );
@@ -0,0 +1,143 @@
+//! Copies of offending PDU headers carried inside errors.
+//!
+//! When a received PDU fails to parse or validate, the read path attaches a
+//! [`PduSnapshot`] to the error, so a report from the field shows the raw
+//! BHS and its decoded opcode, ITT and sequence numbers without a packet
+//! capture.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use core::fmt;
+
+use crate::models::{common::HEADER_LEN, opcode::Opcode};
+
+/// The Basic Header Segment of a PDU, as received.
+///
+/// The sequence-number accessors follow the layout of target PDUs
+/// (RFC 7143 § 11): StatSN at byte 24, ExpCmdSN at 28 and MaxCmdSN at 32.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PduSnapshot {
+    /// Raw BHS bytes.
+    pub bhs: [u8; HEADER_LEN],
+}
+
+impl PduSnapshot {
+    /// Copies the first [`HEADER_LEN`] bytes of `header`, zero-padding a
+    /// shorter slice.
+    pub fn new(header: &[u8]) -> Self {
+        let mut bhs = [0u8; HEADER_LEN];
+        let len = header.len().min(HEADER_LEN);
+        bhs[..len].copy_from_slice(&header[..len]);
+        Self { bhs }
+    }
+
+    /// Raw 6-bit opcode.
+    #[inline]
+    pub fn raw_opcode(&self) -> u8 {
+        self.bhs[0] & 0x3f
+    }
+
+    /// Decoded opcode, `None` if it is not defined by RFC 7143.
+    #[inline]
+    pub fn opcode(&self) -> Option<Opcode> {
+        Opcode::from_u6(self.raw_opcode())
+    }
+
+    /// Initiator Task Tag.
+    #[inline]
+    pub fn itt(&self) -> u32 {
+        self.word(16)
+    }
+
+    /// StatSN.
+    #[inline]
+    pub fn stat_sn(&self) -> u32 {
+        self.word(24)
+    }
+
+    /// ExpCmdSN.
+    #[inline]
+    pub fn exp_cmd_sn(&self) -> u32 {
+        self.word(28)
+    }
+
+    /// MaxCmdSN.
+    #[inline]
+    pub fn max_cmd_sn(&self) -> u32 {
+        self.word(32)
+    }
+
+    fn word(&self, at: usize) -> u32 {
+        u32::from_be_bytes([
+            self.bhs[at],
+            self.bhs[at + 1],
+            self.bhs[at + 2],
+            self.bhs[at + 3],
+        ])
+    }
+}
+
+impl fmt::Display for PduSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PDU opcode=0x{:02x}", self.raw_opcode())?;
+        if let Some(opcode) = self.opcode() {
+            write!(f, " ({opcode:?})")?;
+        }
+        write!(
+            f,
+            " ITT=0x{:08x} StatSN={} ExpCmdSN={} MaxCmdSN={} BHS=",
+            self.itt(),
+            self.stat_sn(),
+            self.exp_cmd_sn(),
+            self.max_cmd_sn()
+        )?;
+        for byte in &self.bhs {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PduSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn decodes_fields_and_prints_hex() {
+        let mut bhs = [0u8; HEADER_LEN];
+        bhs[0] = 0x21;
+        bhs[16..20].copy_from_slice(&7u32.to_be_bytes());
+        bhs[24..28].copy_from_slice(&100u32.to_be_bytes());
+        bhs[28..32].copy_from_slice(&5u32.to_be_bytes());
+        bhs[32..36].copy_from_slice(&36u32.to_be_bytes());
+        let snap = PduSnapshot::new(&bhs);
+
+        assert_eq!(snap.opcode(), Some(Opcode::ScsiCommandResp));
+        assert_eq!((snap.itt(), snap.stat_sn()), (7, 100));
+        let text = format!("{snap}");
+        assert!(text.starts_with(
+            "PDU opcode=0x21 (ScsiCommandResp) ITT=0x00000007 StatSN=100 ExpCmdSN=5 \
+             MaxCmdSN=36 BHS=21000000"
+        ));
+        assert_eq!(
+            text.len() - text.find("BHS=").expect("BHS") - 4,
+            2 * HEADER_LEN
+        );
+    }
+
+    #[test]
+    fn pads_short_headers() {
+        let snap = PduSnapshot::new(&[0x3f, 0x80]);
+        assert_eq!(snap.opcode(), Some(Opcode::Reject));
+        assert_eq!(&snap.bhs[..3], &[0x3f, 0x80, 0]);
+    }
+}
@@ -0,0 +1,166 @@
+//! This module defines common enums for iSCSI Task Management Function PDUs
+//! (RFC 7143 § 11.5 / § 11.6).
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use core::fmt;
+
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::models::error::{ProtocolError, Result, bail};
+
+/// Task Management Function code (lower 7 bits of byte 1).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TaskManagementFunction {
+    /// Abort the task identified by the Referenced Task Tag.
+    #[default]
+    AbortTask = 0x01,
+    /// Abort all tasks issued by this initiator on the LUN.
+    AbortTaskSet = 0x02,
+    /// Clear the ACA condition on the LUN.
+    ClearAca = 0x03,
+    /// Abort all tasks on the LUN from all initiators.
+    ClearTaskSet = 0x04,
+    /// Reset the logical unit.
+    LogicalUnitReset = 0x05,
+    /// Warm reset of the target.
+    TargetWarmReset = 0x06,
+    /// Cold reset of the target (drops all connections).
+    TargetColdReset = 0x07,
+    /// Reassign a task to this connection (connection recovery).
+    TaskReassign = 0x08,
+}
+
+impl TaskManagementFunction {
+    /// Returns the function code as a `u8`.
+    #[inline]
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl TryFrom<u8> for TaskManagementFunction {
+    type Error = ProtocolError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Ok(match value & 0x7F {
+            0x01 => Self::AbortTask,
+            0x02 => Self::AbortTaskSet,
+            0x03 => Self::ClearAca,
+            0x04 => Self::ClearTaskSet,
+            0x05 => Self::LogicalUnitReset,
+            0x06 => Self::TargetWarmReset,
+            0x07 => Self::TargetColdReset,
+            0x08 => Self::TaskReassign,
+            other => bail!("invalid TaskManagementFunction: {other:#04x}"),
+        })
+    }
+}
+
+impl fmt::Display for TaskManagementFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Task Management Function response code (byte 2 of the response).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TmfResponseCode {
+    /// Function complete.
+    #[default]
+    FunctionComplete,
+    /// Referenced task does not exist (it may have already completed).
+    TaskDoesNotExist,
+    /// LUN does not exist.
+    LunDoesNotExist,
+    /// Task still allegiant.
+    TaskStillAllegiant,
+    /// Task allegiance reassignment not supported.
+    TaskReassignNotSupported,
+    /// Task management function not supported.
+    FunctionNotSupported,
+    /// Function authorization failed.
+    AuthorizationFailed,
+    /// Function rejected.
+    FunctionRejected,
+    /// Any other (reserved) code.
+    Other(u8),
+}
+
+impl TmfResponseCode {
+    /// Returns the response code as a `u8`.
+    #[inline]
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Self::FunctionComplete => 0x00,
+            Self::TaskDoesNotExist => 0x01,
+            Self::LunDoesNotExist => 0x02,
+            Self::TaskStillAllegiant => 0x03,
+            Self::TaskReassignNotSupported => 0x04,
+            Self::FunctionNotSupported => 0x05,
+            Self::AuthorizationFailed => 0x06,
+            Self::FunctionRejected => 0xFF,
+            Self::Other(v) => *v,
+        }
+    }
+}
+
+impl From<u8> for TmfResponseCode {
+    fn from(v: u8) -> Self {
+        match v {
+            0x00 => Self::FunctionComplete,
+            0x01 => Self::TaskDoesNotExist,
+            0x02 => Self::LunDoesNotExist,
+            0x03 => Self::TaskStillAllegiant,
+            0x04 => Self::TaskReassignNotSupported,
+            0x05 => Self::FunctionNotSupported,
+            0x06 => Self::AuthorizationFailed,
+            0xFF => Self::FunctionRejected,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl fmt::Display for TmfResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Wire-safe, zero-copy wrapper for the TMF response code (1 byte).
+#[repr(transparent)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    FromBytes,
+    IntoBytes,
+    KnownLayout,
+    Immutable,
+)]
+pub struct RawTmfResponseCode(u8);
+
+impl RawTmfResponseCode {
+    /// Returns the raw 8-bit value of the response code.
+    #[inline]
+    pub const fn raw(self) -> u8 {
+        self.0
+    }
+
+    /// Creates a new `RawTmfResponseCode` from a raw 8-bit value.
+    #[inline]
+    pub const fn from_raw(v: u8) -> Self {
+        Self(v)
+    }
+
+    /// Decodes the raw value into a `TmfResponseCode`.
+    #[inline]
+    pub fn decode(self) -> TmfResponseCode {
+        TmfResponseCode::from(self.0)
+    }
+}
@@ -0,0 +1,12 @@
+//! This module defines the structures for iSCSI Task Management Function
+//! PDUs. It includes submodules for requests, responses and shared codes.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+/// Defines TMF function and response codes.
+pub mod common;
+/// Defines the structures for iSCSI Task Management Function Request PDUs.
+pub mod request;
+/// Defines the structures for iSCSI Task Management Function Response PDUs.
+pub mod response;
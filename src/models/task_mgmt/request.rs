@@ -0,0 +1,212 @@
+//! This module defines the structures for iSCSI Task Management Function
+//! Request PDUs. It includes the `TaskMgmtRequest` header and a builder for
+//! constructing it.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use tracing::{debug, error, warn};
+use zerocopy::{
+    BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32, U64,
+};
+
+use crate::models::{
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::{CmdSn, Itt, Lun, StatSn},
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
+    task_mgmt::common::TaskManagementFunction,
+};
+
+/// Referenced Task Tag value used when the function does not address a
+/// single task.
+pub const NO_REFERENCED_TASK: u32 = u32::MAX;
+
+/// BHS structure for **Task Management Function Request** (opcode
+/// `ScsiTaskMgmtReq`, RFC 7143 § 11.5).
+///
+/// Data Segment length must always be zero.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq, ZFromBytes, IntoBytes, KnownLayout, Immutable)]
+pub struct TaskMgmtRequest {
+    pub opcode: RawBhsOpcode, // Byte 0: I flag + `Opcode::ScsiTaskMgmtReq`
+    pub function: u8,         // Byte 1: F bit (always 1) + function code
+    reserved0: [u8; 2],       // Bytes 2..4: reserved
+    pub total_ahs_length: u8, // Byte 4: normally zero
+    pub data_segment_length: [u8; 3], // Bytes 5..8: must be zero
+    pub lun: U64<BigEndian>,  // Bytes 8..16: LUN
+    pub initiator_task_tag: U32<BigEndian>, // Bytes 16..20: ITT of this TMF
+    pub referenced_task_tag: U32<BigEndian>, // Bytes 20..24: ITT of the task
+    pub cmd_sn: U32<BigEndian>, // Bytes 24..28: CmdSN
+    pub exp_stat_sn: U32<BigEndian>, // Bytes 28..32: ExpStatSN
+    pub ref_cmd_sn: U32<BigEndian>, // Bytes 32..36: RefCmdSN
+    pub exp_data_sn: U32<BigEndian>, // Bytes 36..40: ExpDataSN
+    reserved1: [u8; 8],       // Bytes 40..48: reserved
+}
+
+impl TaskMgmtRequest {
+    /// Serializes the BHS into a byte buffer.
+    pub fn to_bhs_bytes(&self, buf: &mut [u8]) -> Result<()> {
+        buf.fill(0);
+        if buf.len() != HEADER_LEN {
+            bail!("buffer length must be {HEADER_LEN}, got {}", buf.len());
+        }
+        buf.copy_from_slice(self.as_bytes());
+        Ok(())
+    }
+
+    /// Deserializes the BHS from a byte buffer.
+    pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
+        let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
+            .map_err(|e| format_err!("failed convert buffer TaskMgmtRequest: {e}"))?;
+        if hdr.opcode.opcode_known() != Some(Opcode::ScsiTaskMgmtReq) {
+            bail!(
+                "TaskMgmtRequest: invalid opcode 0x{:02x}",
+                hdr.opcode.opcode_raw()
+            );
+        }
+        Ok(hdr)
+    }
+
+    /// Decodes the function code.
+    #[inline]
+    pub fn function(&self) -> Result<TaskManagementFunction> {
+        TaskManagementFunction::try_from(self.function)
+    }
+}
+
+/// Builder for **Task Management Function Request**
+///
+/// Defaults to an Immediate request (`I` bit) with the Final bit set, empty
+/// AHS, zero Data Segment length and no referenced task.
+#[derive(Debug, Default)]
+pub struct TaskMgmtRequestBuilder {
+    pub header: TaskMgmtRequest,
+}
+
+impl TaskMgmtRequestBuilder {
+    /// Creates a new builder for `function` carried under `itt`.
+    pub fn new(function: TaskManagementFunction, itt: Itt) -> Self {
+        Self {
+            header: TaskMgmtRequest {
+                opcode: {
+                    let mut tmp = RawBhsOpcode::default();
+                    tmp.set_opcode_known(Opcode::ScsiTaskMgmtReq);
+                    tmp.set_i();
+                    tmp
+                },
+                function: 0x80 | function.as_u8(),
+                initiator_task_tag: itt.get().into(),
+                referenced_task_tag: NO_REFERENCED_TASK.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Sets the logical unit the function applies to.
+    pub fn lun(mut self, lun: Lun) -> Self {
+        self.header.lun.set(lun.get());
+        self
+    }
+
+    /// Sets the ITT of the task referenced by ABORT TASK / TASK REASSIGN.
+    pub fn referenced_task_tag(mut self, itt: Itt) -> Self {
+        self.header.referenced_task_tag.set(itt.get());
+        self
+    }
+
+    /// Sets the command sequence number (CmdSN) for this request.
+    pub fn cmd_sn(mut self, cmd_sn: impl Into<CmdSn>) -> Self {
+        self.header.cmd_sn.set(cmd_sn.into().get());
+        self
+    }
+
+    /// Sets the expected status sequence number (ExpStatSN) from the target.
+    pub fn exp_stat_sn(mut self, exp_stat_sn: impl Into<StatSn>) -> Self {
+        self.header.exp_stat_sn.set(exp_stat_sn.into().get());
+        self
+    }
+
+    /// Sets the CmdSN of the referenced task (RefCmdSN).
+    pub fn ref_cmd_sn(mut self, ref_cmd_sn: impl Into<CmdSn>) -> Self {
+        self.header.ref_cmd_sn.set(ref_cmd_sn.into().get());
+        self
+    }
+
+    /// Sets the expected DataSN (used by TASK REASSIGN).
+    pub fn exp_data_sn(mut self, exp_data_sn: u32) -> Self {
+        self.header.exp_data_sn.set(exp_data_sn);
+        self
+    }
+}
+
+impl SendingData for TaskMgmtRequest {
+    fn get_final_bit(&self) -> bool {
+        true
+    }
+
+    fn set_final_bit(&mut self) {
+        debug!("Task Management Request is always Final");
+    }
+
+    fn get_continue_bit(&self) -> bool {
+        false
+    }
+
+    fn set_continue_bit(&mut self) {
+        warn!("Task Management Request cannot be marked as Contine");
+    }
+}
+
+impl FromBytes for TaskMgmtRequest {
+    fn from_bhs_bytes(bytes: &mut [u8]) -> Result<&mut Self> {
+        TaskMgmtRequest::from_bhs_bytes(bytes)
+    }
+}
+
+impl BasicHeaderSegment for TaskMgmtRequest {
+    #[inline]
+    fn to_bhs_bytes(&self, buf: &mut [u8]) -> Result<()> {
+        self.to_bhs_bytes(buf)
+    }
+
+    #[inline]
+    fn get_opcode(&self) -> Result<BhsOpcode> {
+        BhsOpcode::try_from(self.opcode.raw())
+    }
+
+    #[inline]
+    fn get_initiator_task_tag(&self) -> Itt {
+        self.initiator_task_tag.get().into()
+    }
+
+    #[inline]
+    fn get_ahs_length_bytes(&self) -> usize {
+        (self.total_ahs_length as usize) * 4
+    }
+
+    #[inline]
+    fn set_ahs_length_bytes(&mut self, len: u8) {
+        self.total_ahs_length = len >> 2;
+    }
+
+    #[inline]
+    fn get_data_length_bytes(&self) -> usize {
+        u32::from_be_bytes([
+            0,
+            self.data_segment_length[0],
+            self.data_segment_length[1],
+            self.data_segment_length[2],
+        ]) as usize
+    }
+
+    #[inline]
+    fn set_data_length_bytes(&mut self, len: u32) {
+        error!("TaskMgmtReq must have zero DataSegmentLength");
+        let be = len.to_be_bytes();
+        self.data_segment_length = [be[1], be[2], be[3]];
+    }
+}
+
+impl ZeroCopyType for TaskMgmtRequest {}
@@ -0,0 +1,145 @@
+//! This module defines the structures for iSCSI Task Management Function
+//! Response PDUs.
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use tracing::{error, warn};
+use zerocopy::{
+    BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32,
+};
+
+use crate::models::{
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::Itt,
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
+    task_mgmt::common::RawTmfResponseCode,
+};
+
+/// Represents the Basic Header Segment (BHS) for a Task Management Function
+/// Response PDU (RFC 7143 § 11.6).
+#[repr(C)]
+#[derive(Debug, Default, PartialEq, ZFromBytes, IntoBytes, KnownLayout, Immutable)]
+pub struct TaskMgmtResponse {
+    pub opcode: RawBhsOpcode, // Byte 0: `Opcode::ScsiTaskMgmtResp`
+    pub flags: u8,            // Byte 1: Final bit in bit 7, rest reserved
+    pub response: RawTmfResponseCode, // Byte 2: TMF response code
+    reserved0: u8,            // Byte 3: reserved
+    pub total_ahs_length: u8, // Byte 4: must be zero
+    pub data_segment_length: [u8; 3], // Bytes 5..8: must be zero
+    reserved1: [u8; 8],       // Bytes 8..16: reserved
+    pub initiator_task_tag: U32<BigEndian>, // Bytes 16..20: ITT
+    reserved2: [u8; 4],       // Bytes 20..24: reserved
+    pub stat_sn: U32<BigEndian>, // Bytes 24..28: StatSN
+    pub exp_cmd_sn: U32<BigEndian>, // Bytes 28..32: ExpCmdSN
+    pub max_cmd_sn: U32<BigEndian>, // Bytes 32..36: MaxCmdSN
+    reserved3: [u8; 12],      // Bytes 36..48: reserved
+}
+
+impl TaskMgmtResponse {
+    /// Serializes the BHS into a byte buffer.
+    pub fn to_bhs_bytes(&self, buf: &mut [u8]) -> Result<()> {
+        if buf.len() != HEADER_LEN {
+            bail!("buffer length must be {HEADER_LEN}, got {}", buf.len());
+        }
+        buf.copy_from_slice(self.as_bytes());
+        Ok(())
+    }
+
+    /// Deserializes the BHS from a byte buffer.
+    pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
+        let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
+            .map_err(|e| format_err!("failed convert buffer TaskMgmtResponse: {e}"))?;
+        if hdr.opcode.opcode_known() != Some(Opcode::ScsiTaskMgmtResp) {
+            bail!(
+                "TaskMgmtResponse: invalid opcode 0x{:02x}",
+                hdr.opcode.opcode_raw()
+            );
+        }
+        Ok(hdr)
+    }
+
+    /// Checks if the Final (F) bit is set.
+    #[inline]
+    pub fn is_final(&self) -> bool {
+        (self.flags & 0b1000_0000) != 0
+    }
+
+    /// Sets the Final (F) bit.
+    #[inline]
+    pub fn set_final(&mut self) {
+        self.flags |= 0b1000_0000;
+    }
+}
+
+impl SendingData for TaskMgmtResponse {
+    fn get_final_bit(&self) -> bool {
+        self.is_final()
+    }
+
+    fn set_final_bit(&mut self) {
+        self.set_final();
+    }
+
+    fn get_continue_bit(&self) -> bool {
+        false
+    }
+
+    fn set_continue_bit(&mut self) {
+        warn!("Task Management Response cannot be marked as Contine");
+    }
+}
+
+impl FromBytes for TaskMgmtResponse {
+    fn from_bhs_bytes(bytes: &mut [u8]) -> Result<&mut Self> {
+        TaskMgmtResponse::from_bhs_bytes(bytes)
+    }
+}
+
+impl BasicHeaderSegment for TaskMgmtResponse {
+    #[inline]
+    fn to_bhs_bytes(&self, buf: &mut [u8]) -> Result<()> {
+        self.to_bhs_bytes(buf)
+    }
+
+    #[inline]
+    fn get_opcode(&self) -> Result<BhsOpcode> {
+        BhsOpcode::try_from(self.opcode.raw())
+    }
+
+    #[inline]
+    fn get_initiator_task_tag(&self) -> Itt {
+        self.initiator_task_tag.get().into()
+    }
+
+    #[inline]
+    fn get_ahs_length_bytes(&self) -> usize {
+        (self.total_ahs_length as usize) * 4
+    }
+
+    #[inline]
+    fn set_ahs_length_bytes(&mut self, len: u8) {
+        self.total_ahs_length = len >> 2;
+    }
+
+    #[inline]
+    fn get_data_length_bytes(&self) -> usize {
+        u32::from_be_bytes([
+            0,
+            self.data_segment_length[0],
+            self.data_segment_length[1],
+            self.data_segment_length[2],
+        ]) as usize
+    }
+
+    #[inline]
+    fn set_data_length_bytes(&mut self, len: u32) {
+        error!("TaskMgmtResp must have zero DataSegmentLength");
+        let be = len.to_be_bytes();
+        self.data_segment_length = [be[1], be[2], be[3]];
+    }
+}
+
+impl ZeroCopyType for TaskMgmtResponse {}
@@ -4,20 +4,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use anyhow::{Result, bail};
 use zerocopy::{
     BigEndian, FromBytes as ZFromBytes, Immutable, IntoBytes, KnownLayout, U32, U64,
 };
 
-use crate::{
-    client::pdu_connection::FromBytes,
-    models::{
-        common::{BasicHeaderSegment, HEADER_LEN, SendingData},
-        data_fromat::ZeroCopyType,
-        identifiers::{CmdSn, Itt, Lun, StatSn, Ttt},
-        opcode::{BhsOpcode, Opcode, RawBhsOpcode},
-        text::common::RawStageFlags,
-    },
+use crate::models::{
+    common::{BasicHeaderSegment, FromBytes, HEADER_LEN, SendingData},
+    data_fromat::ZeroCopyType,
+    error::{Result, bail, format_err},
+    identifiers::{CmdSn, Itt, Lun, StatSn, Ttt},
+    opcode::{BhsOpcode, Opcode, RawBhsOpcode},
+    text::common::RawStageFlags,
 };
 
 /// Represents the Basic Header Segment (BHS) for a Text Request PDU.
@@ -54,9 +51,9 @@ impl TextRequest {
     /// Deserializes the BHS from a byte buffer.
     pub fn from_bhs_bytes(buf: &mut [u8]) -> Result<&mut Self> {
         let hdr = <Self as zerocopy::FromBytes>::mut_from_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed convert buffer TextRequest: {e}"))?;
+            .map_err(|e| format_err!("failed convert buffer TextRequest: {e}"))?;
         if hdr.opcode.opcode_known() != Some(Opcode::TextReq) {
-            anyhow::bail!(
+            bail!(
                 "TextRequest: invalid opcode 0x{:02x}",
                 hdr.opcode.opcode_raw()
             );
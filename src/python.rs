@@ -0,0 +1,571 @@
+//! Python bindings (feature `python`).
+//!
+//! Builds the `iscsi_client` extension module: `Pool`, `Session` and `Lun`
+//! wrap the pool and handle API behind a blocking facade (each call runs on
+//! a shared tokio runtime with the GIL released), and the `cdb` submodule
+//! exposes the [`control_block`](crate::control_block) builders so tests can
+//! craft arbitrary commands and send them with `Lun.execute_in` /
+//! `Lun.execute_out`.
+//!
+//! ```python
+//! import iscsi_client
+//!
+//! with iscsi_client.Pool("config.yaml") as pool:
+//!     lun = pool.session(pool.login()[0]).lun(1 << 48)
+//!     blocks, block_size = lun.capacity()
+//!     lun.write(0, b"\xAA" * block_size)
+//!     assert lun.read(0, 1) == b"\xAA" * block_size
+//! ```
+
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Result, ensure};
+use pyo3::{
+    create_exception,
+    exceptions::PyException,
+    prelude::*,
+    types::{PyBytes, PyDict},
+};
+
+use crate::{
+    bindings::{ErrorClass, runtime},
+    cfg::config::Config,
+    client::{
+        handles::{LunHandle, SessionHandle},
+        pool_sessions::Pool,
+    },
+    control_block::{
+        inquiry::{VpdPage, fill_inquiry_standard, fill_inquiry_vpd},
+        mod_sense::{fill_mode_sense6_simple, fill_mode_sense10_simple},
+        read::{build_read10, build_read16},
+        read_capacity::{build_read_capacity10, build_read_capacity16},
+        report_luns::fill_report_luns,
+        request_sense::fill_request_sense,
+        sync_cache::{build_sync_cache10, build_sync_cache16},
+        test_unit_ready::build_test_unit_ready,
+        unmap::{build_unmap, fill_unmap_parameters},
+        write::{build_write10, build_write16},
+    },
+    error,
+    models::identifiers::{Lun, Tsih},
+    state_machine::{read_states::ReadCtx, write_states::WriteCtx},
+};
+
+create_exception!(
+    iscsi_client,
+    IscsiError,
+    PyException,
+    "Any initiator failure."
+);
+create_exception!(
+    iscsi_client,
+    LoginError,
+    IscsiError,
+    "The target rejected the login."
+);
+create_exception!(
+    iscsi_client,
+    CommandTimeout,
+    IscsiError,
+    "A command timed out."
+);
+create_exception!(
+    iscsi_client,
+    Disconnected,
+    IscsiError,
+    "The connection was lost or shut down."
+);
+create_exception!(
+    iscsi_client,
+    ScsiError,
+    IscsiError,
+    "A command completed with a SCSI status other than GOOD."
+);
+
+fn to_py_err(error: impl Into<anyhow::Error>) -> PyErr {
+    let error = error.into();
+    let message = format!("{error:#}");
+    match ErrorClass::of(&error::IscsiError::from(error)) {
+        ErrorClass::Login => LoginError::new_err(message),
+        ErrorClass::Timeout => CommandTimeout::new_err(message),
+        ErrorClass::Disconnected => Disconnected::new_err(message),
+        ErrorClass::Scsi => ScsiError::new_err(message),
+        ErrorClass::Other => IscsiError::new_err(message),
+    }
+}
+
+/// Run `fut` to completion on the shared runtime with the GIL released.
+fn block_on<T: Send, E: Into<anyhow::Error>>(
+    py: Python<'_>,
+    fut: impl Future<Output = Result<T, E>> + Send,
+) -> PyResult<T> {
+    py.detach(|| {
+        runtime()
+            .map_err(to_py_err)?
+            .block_on(fut)
+            .map_err(to_py_err)
+    })
+}
+
+fn cdb_from(bytes: &[u8]) -> Result<[u8; 16]> {
+    ensure!(
+        !bytes.is_empty() && bytes.len() <= 16,
+        "CDB must be 1..=16 bytes, got {}",
+        bytes.len()
+    );
+    let mut cdb = [0u8; 16];
+    cdb[..bytes.len()].copy_from_slice(bytes);
+    Ok(cdb)
+}
+
+/// `Pool(config_path)`: a session pool configured from a YAML file.
+#[pyclass(name = "Pool", module = "iscsi_client", frozen)]
+pub struct PyPool {
+    pool: Arc<Pool>,
+    cfg: Config,
+}
+
+#[pymethods]
+impl PyPool {
+    #[new]
+    fn new(config_path: &str) -> PyResult<Self> {
+        let cfg = Config::load_from_file(config_path).map_err(to_py_err)?;
+        Ok(Self {
+            pool: Pool::new(&cfg),
+            cfg,
+        })
+    }
+
+    /// Connect and log in the sessions of the config; returns their TSIHs.
+    fn login(&self, py: Python<'_>) -> PyResult<Vec<u16>> {
+        let tsihs = block_on(py, self.pool.login_sessions_from_cfg(&self.cfg))?;
+        Ok(tsihs.into_iter().map(Tsih::get).collect())
+    }
+
+    /// Handle of a logged-in session.
+    fn session(&self, tsih: u16) -> PyResult<PySession> {
+        let session = self.pool.session(Tsih::new(tsih)).map_err(to_py_err)?;
+        Ok(PySession { session })
+    }
+
+    /// Log out every session, waiting up to `timeout` seconds per
+    /// connection.
+    #[pyo3(signature = (timeout = 10.0))]
+    fn shutdown(&self, py: Python<'_>, timeout: f64) -> PyResult<()> {
+        let wait = Duration::from_secs_f64(timeout.max(0.0));
+        block_on(py, self.pool.shutdown_gracefully(wait))
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_exc))]
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc: &Bound<'_, pyo3::types::PyTuple>,
+    ) -> PyResult<bool> {
+        self.shutdown(py, 10.0)?;
+        Ok(false)
+    }
+}
+
+/// A logged-in session.
+#[pyclass(name = "Session", module = "iscsi_client", frozen)]
+pub struct PySession {
+    session: SessionHandle,
+}
+
+#[pymethods]
+impl PySession {
+    #[getter]
+    fn tsih(&self) -> u16 {
+        self.session.tsih().get()
+    }
+
+    /// Logical unit `lun` (the 64-bit LUN field, e.g. `1 << 48` for LUN 1).
+    fn lun(&self, lun: u64) -> PyLun {
+        PyLun {
+            lun: self.session.lun(Lun::new(lun)),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Session(tsih={})", self.session.tsih())
+    }
+}
+
+/// A logical unit of a session.
+#[pyclass(name = "Lun", module = "iscsi_client", frozen)]
+pub struct PyLun {
+    lun: LunHandle,
+}
+
+#[pymethods]
+impl PyLun {
+    /// `(blocks, block_size)`, read once and cached.
+    fn capacity(&self, py: Python<'_>) -> PyResult<(u64, u32)> {
+        let cap = block_on(py, self.lun.capacity())?;
+        Ok((cap.blocks, cap.block_size))
+    }
+
+    fn test_unit_ready(&self, py: Python<'_>) -> PyResult<()> {
+        block_on(py, self.lun.test_unit_ready())
+    }
+
+    /// Standard INQUIRY data as a dict.
+    fn inquiry<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let inq = block_on(py, self.lun.inquiry())?;
+        let dict = PyDict::new(py);
+        dict.set_item("peripheral_qualifier", inq.peripheral_qualifier)?;
+        dict.set_item("device_type", inq.device_type)?;
+        dict.set_item("removable", inq.rmb)?;
+        dict.set_item("version", inq.version)?;
+        dict.set_item("vendor", inq.vendor_id)?;
+        dict.set_item("product", inq.product_id)?;
+        dict.set_item("revision", inq.product_rev)?;
+        dict.set_item("tpgs", inq.tpgs)?;
+        dict.set_item("protect", inq.protect)?;
+        dict.set_item("third_party_copy", inq.third_party_copy)?;
+        dict.set_item("cmd_que", inq.cmd_que)?;
+        dict.set_item("version_descriptors", inq.version_descriptors)?;
+        Ok(dict)
+    }
+
+    /// Read `blocks` blocks at `lba`.
+    fn read<'py>(
+        &self,
+        py: Python<'py>,
+        lba: u64,
+        blocks: u32,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let data = block_on(py, self.lun.read_at(lba, blocks))?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Write `data` (a whole number of blocks) at `lba`.
+    fn write(&self, py: Python<'_>, lba: u64, data: &[u8]) -> PyResult<()> {
+        block_on(py, self.lun.write_at(lba, data))
+    }
+
+    fn flush(&self, py: Python<'_>) -> PyResult<()> {
+        block_on(py, self.lun.flush(..))
+    }
+
+    fn unmap(&self, py: Python<'_>, lba: u64, blocks: u32) -> PyResult<()> {
+        block_on(py, self.lun.unmap(lba, blocks))
+    }
+
+    /// Send a data-in (or no-data) CDB and return up to `length` bytes.
+    #[pyo3(signature = (cdb, length = 0))]
+    fn execute_in<'py>(
+        &self,
+        py: Python<'py>,
+        cdb: &[u8],
+        length: u32,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let cdb = cdb_from(cdb).map_err(to_py_err)?;
+        let session = self.lun.session();
+        let lun = self.lun.lun();
+        let outcome = block_on(
+            py,
+            session
+                .pool()
+                .execute_balanced(session.tsih(), None, |env| {
+                    ReadCtx::from_execute_env(env, lun, length, cdb)
+                }),
+        )?;
+        Ok(PyBytes::new(py, &outcome.data))
+    }
+
+    /// Send a data-out CDB with `data`.
+    fn execute_out(&self, py: Python<'_>, cdb: &[u8], data: &[u8]) -> PyResult<()> {
+        let cdb = cdb_from(cdb).map_err(to_py_err)?;
+        let data = data.to_vec();
+        let session = self.lun.session();
+        let lun = self.lun.lun();
+        block_on(
+            py,
+            session
+                .pool()
+                .execute_balanced(session.tsih(), None, |env| {
+                    WriteCtx::from_execute_env(env, lun, cdb, data.clone())
+                }),
+        )?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Lun({}, tsih={})",
+            self.lun.lun(),
+            self.lun.session().tsih()
+        )
+    }
+}
+
+/// CDB builders; each returns the 16-byte (zero padded) CDB.
+mod cdb {
+    use super::*;
+
+    fn build(py: Python<'_>, f: impl FnOnce(&mut [u8; 16])) -> Bound<'_, PyBytes> {
+        let mut cdb = [0u8; 16];
+        f(&mut cdb);
+        PyBytes::new(py, &cdb)
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (lba, blocks, flags = 0, control = 0))]
+    pub fn read10(
+        py: Python<'_>,
+        lba: u32,
+        blocks: u16,
+        flags: u8,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| build_read10(c, lba, blocks, flags, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (lba, blocks, flags = 0, control = 0))]
+    pub fn read16(
+        py: Python<'_>,
+        lba: u64,
+        blocks: u32,
+        flags: u8,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| build_read16(c, lba, blocks, flags, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (lba, blocks, flags = 0, control = 0))]
+    pub fn write10(
+        py: Python<'_>,
+        lba: u32,
+        blocks: u16,
+        flags: u8,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| build_write10(c, lba, blocks, flags, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (lba, blocks, flags = 0, control = 0))]
+    pub fn write16(
+        py: Python<'_>,
+        lba: u64,
+        blocks: u32,
+        flags: u8,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| build_write16(c, lba, blocks, flags, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (lba = 0, pmi = false, control = 0))]
+    pub fn read_capacity10(
+        py: Python<'_>,
+        lba: u32,
+        pmi: bool,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| build_read_capacity10(c, lba, pmi, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (alloc_len = 32, lba = 0, pmi = false, control = 0))]
+    pub fn read_capacity16(
+        py: Python<'_>,
+        alloc_len: u32,
+        lba: u64,
+        pmi: bool,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| {
+            build_read_capacity16(c, lba, pmi, alloc_len, control)
+        })
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (lba = 0, blocks = 0, immed = false, control = 0))]
+    pub fn sync_cache10(
+        py: Python<'_>,
+        lba: u32,
+        blocks: u16,
+        immed: bool,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| build_sync_cache10(c, lba, blocks, immed, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (lba = 0, blocks = 0, immed = false, control = 0))]
+    pub fn sync_cache16(
+        py: Python<'_>,
+        lba: u64,
+        blocks: u32,
+        immed: bool,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| build_sync_cache16(c, lba, blocks, immed, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (param_len, anchor = false, control = 0))]
+    pub fn unmap(
+        py: Python<'_>,
+        param_len: u16,
+        anchor: bool,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| build_unmap(c, anchor, param_len, control))
+    }
+
+    /// UNMAP parameter list for `[(lba, blocks), ...]`.
+    #[pyfunction]
+    pub fn unmap_parameters(
+        py: Python<'_>,
+        descriptors: Vec<(u64, u32)>,
+    ) -> Bound<'_, PyBytes> {
+        PyBytes::new(py, &fill_unmap_parameters(&descriptors))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (control = 0))]
+    pub fn test_unit_ready(py: Python<'_>, control: u8) -> Bound<'_, PyBytes> {
+        build(py, |c| build_test_unit_ready(c, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (alloc_len = 96, control = 0))]
+    pub fn inquiry(py: Python<'_>, alloc_len: u8, control: u8) -> Bound<'_, PyBytes> {
+        build(py, |c| fill_inquiry_standard(c, alloc_len, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (page, alloc_len = 255, control = 0))]
+    pub fn inquiry_vpd(
+        py: Python<'_>,
+        page: u8,
+        alloc_len: u8,
+        control: u8,
+    ) -> PyResult<Bound<'_, PyBytes>> {
+        let page = VpdPage::try_from(page).map_err(to_py_err)?;
+        Ok(build(py, |c| fill_inquiry_vpd(c, page, alloc_len, control)))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (alloc_len = 4096, select = 0, control = 0))]
+    pub fn report_luns(
+        py: Python<'_>,
+        alloc_len: u32,
+        select: u8,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| fill_report_luns(c, select, alloc_len, control))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (page_code, alloc_len = 255))]
+    pub fn mode_sense6(
+        py: Python<'_>,
+        page_code: u8,
+        alloc_len: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| fill_mode_sense6_simple(c, page_code, alloc_len))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (page_code, alloc_len = 4096))]
+    pub fn mode_sense10(
+        py: Python<'_>,
+        page_code: u8,
+        alloc_len: u16,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| fill_mode_sense10_simple(c, page_code, alloc_len))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (alloc_len = 252, desc = false, control = 0))]
+    pub fn request_sense(
+        py: Python<'_>,
+        alloc_len: u8,
+        desc: bool,
+        control: u8,
+    ) -> Bound<'_, PyBytes> {
+        build(py, |c| fill_request_sense(c, desc, alloc_len, control))
+    }
+
+    pub fn register(parent: &Bound<'_, PyModule>) -> PyResult<()> {
+        let m = PyModule::new(parent.py(), "cdb")?;
+        m.add_function(wrap_pyfunction!(read10, &m)?)?;
+        m.add_function(wrap_pyfunction!(read16, &m)?)?;
+        m.add_function(wrap_pyfunction!(write10, &m)?)?;
+        m.add_function(wrap_pyfunction!(write16, &m)?)?;
+        m.add_function(wrap_pyfunction!(read_capacity10, &m)?)?;
+        m.add_function(wrap_pyfunction!(read_capacity16, &m)?)?;
+        m.add_function(wrap_pyfunction!(sync_cache10, &m)?)?;
+        m.add_function(wrap_pyfunction!(sync_cache16, &m)?)?;
+        m.add_function(wrap_pyfunction!(unmap, &m)?)?;
+        m.add_function(wrap_pyfunction!(unmap_parameters, &m)?)?;
+        m.add_function(wrap_pyfunction!(test_unit_ready, &m)?)?;
+        m.add_function(wrap_pyfunction!(inquiry, &m)?)?;
+        m.add_function(wrap_pyfunction!(inquiry_vpd, &m)?)?;
+        m.add_function(wrap_pyfunction!(report_luns, &m)?)?;
+        m.add_function(wrap_pyfunction!(mode_sense6, &m)?)?;
+        m.add_function(wrap_pyfunction!(mode_sense10, &m)?)?;
+        m.add_function(wrap_pyfunction!(request_sense, &m)?)?;
+        parent.add_submodule(&m)
+    }
+}
+
+/// The `iscsi_client` extension module.
+#[pymodule]
+#[pyo3(name = "iscsi_client")]
+pub fn iscsi_client(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<PyPool>()?;
+    m.add_class::<PySession>()?;
+    m.add_class::<PyLun>()?;
+    m.add("IscsiError", py.get_type::<IscsiError>())?;
+    m.add("LoginError", py.get_type::<LoginError>())?;
+    m.add("CommandTimeout", py.get_type::<CommandTimeout>())?;
+    m.add("Disconnected", py.get_type::<Disconnected>())?;
+    m.add("ScsiError", py.get_type::<ScsiError>())?;
+    cdb::register(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &std::ffi::CStr = cr#"
+cdb = m.cdb.read16(0x1122, 8)
+assert len(cdb) == 16 and cdb[:2] == b"\x88\x00"
+assert cdb[2:10] == (0x1122).to_bytes(8, "big")
+assert m.cdb.test_unit_ready() == bytes(16)
+assert issubclass(m.ScsiError, m.IscsiError)
+try:
+    m.Pool("/nonexistent.yaml")
+    raise AssertionError("Pool() accepted a missing config")
+except m.IscsiError:
+    pass
+"#;
+
+    #[test]
+    fn module_exposes_cdb_builders() {
+        Python::initialize();
+        Python::attach(|py| {
+            let m = PyModule::new(py, "iscsi_client").expect("module");
+            iscsi_client(&m).expect("init");
+            let locals = PyDict::new(py);
+            locals.set_item("m", m).expect("set");
+            py.run(SCRIPT, None, Some(&locals)).expect("script");
+        });
+    }
+}
@@ -5,11 +5,21 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::future::Future;
+use std::{fmt, future::Future};
 
 use anyhow::Result;
+use thiserror::Error;
 use tokio_util::sync::CancellationToken;
 
+use crate::{
+    control_block::verify::SENSE_KEY_MISCOMPARE,
+    models::{
+        command::common::{ScsiStatus, TaskAttribute},
+        data::sense_data::SenseData,
+        identifiers::{Itt, Lun},
+    },
+};
+
 /// Represents the outcome of a state transition.
 pub enum Transition<S, R> {
     /// Move to the next state.
@@ -40,4 +50,145 @@ pub trait StateMachineCtx<Ctx, Out = ()>: Sized {
         &mut self,
         cancel: &CancellationToken,
     ) -> impl Future<Output = Result<Out>>;
+
+    /// Whether the command may be re-issued after an ambiguous failure (the
+    /// connection died before its outcome was known) without risking a
+    /// double-applied side effect. Non-SCSI machines are safe by default.
+    fn is_retry_safe(&self) -> bool {
+        true
+    }
+
+    /// Logical unit addressed by the command, used by the pool's per-LUN
+    /// queue depth scheduler. Non-SCSI machines are not queued.
+    fn target_lun(&self) -> Option<Lun> {
+        None
+    }
+
+    /// Task attribute of a SCSI command. Commands with the ACA attribute are
+    /// not held by the pool while an ACA condition is established.
+    fn task_attribute(&self) -> TaskAttribute {
+        TaskAttribute::Simple
+    }
+
+    /// CDB carried by the command, used by the pool to notice when a CHECK
+    /// CONDITION established an ACA condition (NACA=1).
+    fn cdb(&self) -> Option<&[u8; 16]> {
+        None
+    }
+
+    /// Identifies the in-flight SCSI task so the pool can abort it (ABORT
+    /// TASK) when the command times out.
+    fn task_ref(&self) -> Option<TaskRef> {
+        None
+    }
+}
+
+/// Identity of a SCSI task as seen by the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskRef {
+    /// Logical unit the task was issued to.
+    pub lun: Lun,
+    /// Initiator Task Tag of the task.
+    pub itt: Itt,
+    /// CmdSN the command was sent with; `None` if it never left the
+    /// initiator.
+    pub cmd_sn: Option<u32>,
+}
+
+/// A SCSI command completed at the target with a status other than GOOD.
+///
+/// SCSI state machines return this inside `anyhow::Error` so callers (and the
+/// retry engine) can inspect the status and sense data with `downcast_ref`
+/// instead of matching on the message text.
+#[derive(Debug, Clone, Error)]
+pub struct ScsiStatusError {
+    /// Operation code of the failed CDB (byte 0).
+    pub opcode: u8,
+    /// SCSI status reported by the target.
+    pub status: ScsiStatus,
+    /// Parsed sense data (fixed or descriptor format), when the target
+    /// returned any.
+    pub sense: Option<SenseData>,
+    /// Raw sense bytes as received (may be empty).
+    pub raw_sense: Vec<u8>,
+    /// Data-In received before the status. A read can end with CHECK
+    /// CONDITION after moving part of its data, e.g. a tape READ that met a
+    /// filemark.
+    pub data: Vec<u8>,
+}
+
+impl ScsiStatusError {
+    /// Builds the error from the status and the (possibly empty) sense
+    /// segment of a SCSI Response or final Data-In PDU.
+    pub fn new(opcode: u8, status: ScsiStatus, raw_sense: &[u8]) -> Self {
+        let sense = if raw_sense.is_empty() {
+            None
+        } else {
+            SenseData::parse(raw_sense).ok()
+        };
+        Self {
+            opcode,
+            status,
+            sense,
+            raw_sense: raw_sense.to_vec(),
+            data: Vec::new(),
+        }
+    }
+
+    /// The error with the Data-In that arrived before the status.
+    pub fn with_data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Whether the target reported ACA ACTIVE, i.e. the command was rejected
+    /// because an ACA condition is established on the logical unit.
+    #[inline]
+    pub fn is_aca_active(&self) -> bool {
+        self.status == ScsiStatus::AcaActive
+    }
+
+    /// Whether a VERIFY or WRITE AND VERIFY found data that differs from
+    /// the Data-Out buffer (sense key MISCOMPARE).
+    pub fn is_miscompare(&self) -> bool {
+        self.sense
+            .as_ref()
+            .is_some_and(|sense| sense.sense_key == SENSE_KEY_MISCOMPARE)
+    }
+
+    /// Offset of the first byte that did not match in a MISCOMPARE, taken
+    /// from the INFORMATION field when the target marked it valid.
+    pub fn miscompare_offset(&self) -> Option<u64> {
+        self.sense
+            .as_ref()
+            .filter(|sense| sense.sense_key == SENSE_KEY_MISCOMPARE && sense.valid)
+            .map(|sense| sense.information)
+    }
+
+    /// Returns `(sense_key, asc, ascq)` when sense data was parsed.
+    pub fn sense_codes(&self) -> Option<(u8, u8, u8)> {
+        self.sense
+            .as_ref()
+            .map(|sense| (sense.sense_key, sense.asc, sense.ascq))
+    }
+}
+
+impl fmt::Display for ScsiStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SCSI command 0x{:02X} failed: status={:?}",
+            self.opcode, self.status
+        )?;
+        match (&self.sense, self.raw_sense.is_empty()) {
+            (Some(sense), _) => write!(f, ", sense={sense:?}"),
+            (None, false) => write!(
+                f,
+                ", sense ({} bytes)={:02X?}",
+                self.raw_sense.len(),
+                self.raw_sense
+            ),
+            (None, true) => Ok(()),
+        }
+    }
 }
@@ -11,8 +11,9 @@ use tokio_util::sync::CancellationToken;
 use tracing::debug;
 
 use crate::{
-    cfg::config::{AuthConfig, Config},
+    cfg::{config::Config, secrets::SecretProvider},
     client::client::ClientConnection,
+    error,
     models::{
         common::{Builder, HEADER_LEN},
         data_fromat::PduRequest,
@@ -57,6 +58,9 @@ pub struct DiscoveryCtx<'a> {
     pub buf: [u8; HEADER_LEN],
     /// Initiator-selected identifier for the discovery session.
     pub isid: Isid,
+    /// Source of the CHAP credentials of the discovery login, asked for the
+    /// configured target name.
+    pub secret_provider: Option<Arc<dyn SecretProvider>>,
 
     state: Option<DiscoveryStates>,
 }
@@ -81,6 +85,7 @@ impl<'a> DiscoveryCtx<'a> {
             exp_stat_sn: 0,
             buf: [0u8; HEADER_LEN],
             isid: Isid::new(isid),
+            secret_provider: None,
             state: Some(DiscoveryStates::Connect(Connect)),
             _lt: PhantomData,
         }
@@ -89,9 +94,11 @@ impl<'a> DiscoveryCtx<'a> {
     pub async fn discover(
         cfg: Config,
         cancel: CancellationToken,
-    ) -> Result<Vec<DiscoveredTarget>> {
+    ) -> error::Result<Vec<DiscoveredTarget>> {
         let mut ctx = DiscoveryCtx::new(cfg, cancel);
-        ctx.execute(&CancellationToken::new()).await
+        ctx.execute(&CancellationToken::new())
+            .await
+            .map_err(Into::into)
     }
 
     pub fn parse_send_targets_response(data: &[u8]) -> Vec<DiscoveredTarget> {
@@ -220,9 +227,10 @@ impl<'ctx> StateMachine<DiscoveryCtx<'ctx>, DiscoveryStep> for Login {
 
             let mut login_ctx =
                 LoginCtx::new(Arc::clone(&conn), ctx.isid, Cid::ZERO, Tsih::NONE);
-            match &conn.cfg.login.auth {
-                AuthConfig::Chap(_) => login_ctx.set_chap_login(),
-                AuthConfig::None => login_ctx.set_plain_login(),
+            let provider = ctx.secret_provider.as_deref();
+            let target_name = &conn.cfg.login.identity.target_name;
+            if let Err(e) = login_ctx.set_auth_login(provider, target_name).await {
+                return Transition::Done(Err(e.context("discovery login failed")));
             }
 
             let login_pdu = match login_ctx.execute(&ctx.cancel).await {
@@ -234,7 +242,7 @@ impl<'ctx> StateMachine<DiscoveryCtx<'ctx>, DiscoveryStep> for Login {
 
             let hdr = match login_pdu.header_view() {
                 Ok(h) => h,
-                Err(e) => return Transition::Done(Err(e)),
+                Err(e) => return Transition::Done(Err(e.into())),
             };
 
             debug!("Discovery login complete — TSIH={}", hdr.tsih.get());
@@ -273,12 +281,12 @@ impl<'ctx> StateMachine<DiscoveryCtx<'ctx>, DiscoveryStep> for SendTargets {
                 .exp_stat_sn(ctx.exp_stat_sn);
 
             if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             let mut builder = PduRequest::<TextRequest>::new_request(ctx.buf, &conn.cfg);
             if let Err(e) = builder.append_data(b"SendTargets=All\0".as_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             let itt = ctx.itt;
@@ -348,7 +356,7 @@ impl<'ctx> StateMachine<DiscoveryCtx<'ctx>, DiscoveryStep> for Collect {
             let next = ctx.itt.get().wrapping_add(1);
             ctx.itt = match Itt::new(next) {
                 Ok(itt) => itt,
-                Err(e) => return Transition::Stay(Err(e)),
+                Err(e) => return Transition::Stay(Err(e.into())),
             };
 
             Transition::Next(DiscoveryStates::Logout(Logout), Ok(()))
@@ -378,7 +386,7 @@ impl<'ctx> StateMachine<DiscoveryCtx<'ctx>, DiscoveryStep> for Logout {
                     .exp_stat_sn(ctx.exp_stat_sn);
 
             if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             let builder = PduRequest::<LogoutRequest>::new_request(ctx.buf, &conn.cfg);
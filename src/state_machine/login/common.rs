@@ -5,27 +5,85 @@
 use std::{collections::HashMap, marker::PhantomData, sync::Arc};
 
 use anyhow::{Context, Result, anyhow, bail};
+use thiserror::Error;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, warn};
 
 use crate::{
-    cfg::config::{Config, login_keys_operational},
+    cfg::{
+        config::{AuthConfig, ChapConfig, Config, login_keys_operational},
+        secrets::{SecretProvider, check_provided_chap},
+    },
     client::client::ClientConnection,
+    error::{self, IscsiError},
     models::{
         common::HEADER_LEN,
         data_fromat::PduResponse,
-        identifiers::{Cid, Isid, Tsih},
-        login::{common::Stage, response::LoginResponse},
+        identifiers::{Cid, Isid, Itt, Tsih},
+        login::{
+            common::Stage,
+            response::LoginResponse,
+            status::{
+                InitiatorErrorDetail, RawStatusDetail, StatusClass, TargetErrorDetail,
+            },
+        },
     },
     state_machine::{
         common::{StateMachine, StateMachineCtx, Transition},
         login::{
             login_chap::{ChapA, ChapAnswer, ChapOpToFull, ChapSecurity},
             login_plain::{PlainOpToFull, PlainStart},
+            login_srp::{SrpClient, SrpEvidence, SrpExchange, SrpUser},
         },
     },
 };
 
+/// The target answered a Login Request with a Status-Class other than
+/// Success.
+///
+/// Login state machines return this inside `anyhow::Error` so the pool can
+/// tell transient target conditions (see [`LoginStatusError::is_retryable`])
+/// from permanent failures with `downcast_ref`.
+#[derive(Debug, Clone, Error)]
+#[error("login rejected by target: status={class:?}/{}", self.detail_name())]
+pub struct LoginStatusError {
+    /// Status-Class of the Login Response.
+    pub class: StatusClass,
+    /// Raw Status-Detail of the Login Response.
+    pub detail: u8,
+}
+
+impl LoginStatusError {
+    /// Decoded Status-Detail, falling back to the raw value.
+    fn detail_name(&self) -> String {
+        RawStatusDetail::from_raw(self.detail)
+            .decode_with_class(self.class)
+            .map_or_else(|_| format!("0x{:02x}", self.detail), |d| format!("{d:?}"))
+    }
+
+    /// Whether the target no longer knows the session the login tried to
+    /// join, e.g. after it timed out a session left behind by a crash. A
+    /// fresh TSIH=0 login (session reinstatement) is needed instead.
+    pub fn is_session_gone(&self) -> bool {
+        self.class == StatusClass::InitiatorError
+            && matches!(
+                InitiatorErrorDetail::try_from(self.detail),
+                Ok(InitiatorErrorDetail::SessionDoesNotExist)
+            )
+    }
+
+    /// Whether the target is busy or temporarily out of resources, so the
+    /// login may be repeated after Time2Wait (RFC 7143 § 11.13.5).
+    pub fn is_retryable(&self) -> bool {
+        self.class == StatusClass::TargetError
+            && matches!(
+                TargetErrorDetail::try_from(self.detail),
+                Ok(TargetErrorDetail::TargetBusy
+                    | TargetErrorDetail::TargetResourceUnavailable)
+            )
+    }
+}
+
 /// This structure represents the context for a Login command.
 #[derive(Debug)]
 pub struct LoginCtx<'a> {
@@ -44,6 +102,14 @@ pub struct LoginCtx<'a> {
 
     /// The last received login response.
     pub last_response: Option<PduResponse<LoginResponse>>,
+    /// CHAP credentials from a secret provider, used instead of the
+    /// configured ones.
+    pub chap: Option<ChapConfig>,
+    /// CHAP_I / CHAP_C sent to the target in mutual CHAP, to check its
+    /// CHAP_R against.
+    pub chap_challenge: Option<(u8, Vec<u8>)>,
+    /// The SRP exchange in progress, once SRP_A was sent.
+    pub srp: Option<SrpClient>,
 
     state: Option<LoginStates>,
 }
@@ -58,6 +124,9 @@ impl<'a> LoginCtx<'a> {
             tsih,
             buf: [0u8; HEADER_LEN],
             last_response: None,
+            chap: None,
+            chap_challenge: None,
+            srp: None,
             state: None,
             _lt: PhantomData,
         }
@@ -73,22 +142,93 @@ impl<'a> LoginCtx<'a> {
         self.state = Some(LoginStates::ChapSecurity(ChapSecurity));
     }
 
+    /// Sets the login state to use SRP authentication. The first Security
+    /// request is the one CHAP sends.
+    pub fn set_srp_login(&mut self) {
+        self.state = Some(LoginStates::ChapSecurity(ChapSecurity));
+    }
+
+    /// Sets the login state for the configured AuthMethod. For CHAP,
+    /// `provider` is asked for the credentials of `target_name` first; its
+    /// answer is checked like configured credentials and kept in
+    /// [`LoginCtx::chap`].
+    pub async fn set_auth_login(
+        &mut self,
+        provider: Option<&dyn SecretProvider>,
+        target_name: &str,
+    ) -> Result<()> {
+        match &self.conn.cfg.login.auth {
+            AuthConfig::Chap(chap) => {
+                if let Some(provider) = provider {
+                    self.chap = provider
+                        .chap_credentials(target_name)
+                        .await
+                        .with_context(|| {
+                            format!("secret provider failed for {target_name}")
+                        })?
+                        .map(|creds| creds.apply_to(chap));
+                }
+                if let Some(chap) = &self.chap {
+                    check_provided_chap(chap).with_context(|| {
+                        format!(
+                            "secret provider gave invalid credentials for {target_name}"
+                        )
+                    })?;
+                }
+                self.set_chap_login();
+            },
+            AuthConfig::Srp(_) => self.set_srp_login(),
+            AuthConfig::None => self.set_plain_login(),
+        }
+        Ok(())
+    }
+
+    /// CHAP credentials of this login: [`LoginCtx::chap`] if set, else the
+    /// configured ones.
+    pub fn chap_config(&self) -> Option<&ChapConfig> {
+        match (&self.chap, &self.conn.cfg.login.auth) {
+            (Some(chap), _) | (None, AuthConfig::Chap(chap)) => Some(chap),
+            (None, _) => None,
+        }
+    }
+
     /// Validates and returns the header of the last login response.
-    pub fn validate_last_response_header(&self) -> Result<&LoginResponse> {
+    pub fn validate_last_response_header(&self) -> error::Result<&LoginResponse> {
         match &self.last_response {
             Some(l) => match l.header_view() {
                 Ok(last) => Ok(last),
-                Err(e) => Err(e),
+                Err(e) => Err(e.into()),
             },
-            None => Err(anyhow!("no last response in ctx")),
+            None => Err(IscsiError::msg("no last response in ctx")),
+        }
+    }
+
+    /// Reads the Login Response for `itt`, failing with
+    /// [`IscsiError::LoginRejected`] when the target did not accept the
+    /// request.
+    pub async fn read_login_response(
+        &self,
+        itt: Itt,
+    ) -> error::Result<PduResponse<LoginResponse>> {
+        let rsp = self.conn.read_response::<LoginResponse>(itt).await?;
+        let header = rsp.header_view()?;
+        let class = header.status_class.decode();
+        if class != StatusClass::Success {
+            return Err(IscsiError::LoginRejected(LoginStatusError {
+                class,
+                detail: header.status_detail.raw(),
+            }));
         }
+        Ok(rsp)
     }
 
     /// Validates and returns the last login response PDU.
-    pub fn validate_last_response_pdu(&self) -> Result<&PduResponse<LoginResponse>> {
+    pub fn validate_last_response_pdu(
+        &self,
+    ) -> error::Result<&PduResponse<LoginResponse>> {
         match &self.last_response {
             Some(l) => Ok(l),
-            None => Err(anyhow!("no last response in ctx")),
+            None => Err(IscsiError::msg("no last response in ctx")),
         }
     }
 }
@@ -112,15 +252,38 @@ pub enum LoginStates {
     ChapAnswer(ChapAnswer),
     /// The state for transitioning from operational to full feature phase.
     ChapOpToFull(ChapOpToFull),
+    /// The state for sending the SRP username.
+    SrpUser(SrpUser),
+    /// The state for sending the SRP public value.
+    SrpExchange(SrpExchange),
+    /// The state for sending the SRP evidence.
+    SrpEvidence(SrpEvidence),
 }
 
 impl<'ctx> StateMachineCtx<LoginCtx<'ctx>, PduResponse<LoginResponse>>
     for LoginCtx<'ctx>
 {
+    /// Runs the login exchange bounded by `runtime.login_timeout()`. A login
+    /// that does not finish in time leaves the connection in an unknown
+    /// stage, so it is poisoned.
     async fn execute(
         &mut self,
         _cancel: &CancellationToken,
     ) -> Result<PduResponse<LoginResponse>> {
+        let limit = self.conn.cfg.runtime.login_timeout();
+        match tokio::time::timeout(limit, self.run()).await {
+            Ok(res) => res,
+            Err(_) => {
+                self.conn
+                    .poison(format!("login did not complete within {limit:?}"));
+                bail!("login timeout after {limit:?}")
+            },
+        }
+    }
+}
+
+impl<'ctx> LoginCtx<'ctx> {
+    async fn run(&mut self) -> Result<PduResponse<LoginResponse>> {
         debug!("Loop login");
         loop {
             let state = self.state.take().context("state must be set LoginCtx")?;
@@ -131,6 +294,9 @@ impl<'ctx> StateMachineCtx<LoginCtx<'ctx>, PduResponse<LoginResponse>>
                 LoginStates::ChapA(s) => s.step(self).await,
                 LoginStates::ChapAnswer(s) => s.step(self).await,
                 LoginStates::ChapOpToFull(s) => s.step(self).await,
+                LoginStates::SrpUser(s) => s.step(self).await,
+                LoginStates::SrpExchange(s) => s.step(self).await,
+                LoginStates::SrpEvidence(s) => s.step(self).await,
             };
 
             match tr {
@@ -175,6 +341,18 @@ fn parse_login_text_map(data: &[u8]) -> Result<HashMap<String, Vec<String>>> {
     Ok(map)
 }
 
+/// Value the target returned for `key` in a login response, if any.
+pub(crate) fn response_key(
+    rsp: &PduResponse<LoginResponse>,
+    key: &str,
+) -> Option<String> {
+    let data = rsp.data().ok()?;
+    parse_login_text_map(data)
+        .ok()?
+        .remove(key)
+        .and_then(|values| values.into_iter().next())
+}
+
 /// Ensures that the target accepted every operational key/value requested in
 /// the configuration.
 pub(crate) fn verify_operational_negotiation(
@@ -2,14 +2,22 @@
 //! process. It includes the states and transitions for handling the CHAP
 //! exchange.
 
-use std::pin::Pin;
+use std::{collections::VecDeque, pin::Pin, sync::Mutex};
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail, ensure};
 use md5::{Digest, Md5};
+use rand::RngExt;
+use sha2::Sha256;
+use sha3::Sha3_256;
 
 use crate::{
-    cfg::config::{
-        AuthConfig, login_keys_chap_response, login_keys_operational, login_keys_security,
+    cfg::{
+        config::{
+            AuthConfig, ChapConfig, login_keys_chap_algorithms,
+            login_keys_chap_mutual_response, login_keys_chap_response,
+            login_keys_operational, login_keys_security,
+        },
+        enums::ChapAlgorithm,
     },
     models::{
         common::{BasicHeaderSegment, Builder},
@@ -18,46 +26,193 @@ use crate::{
         login::{
             common::Stage,
             request::{LoginRequest, LoginRequestBuilder},
-            response::LoginResponse,
         },
     },
     state_machine::{
         common::{StateMachine, Transition},
-        login::common::{
-            LoginCtx, LoginStates, LoginStepOut, verify_operational_negotiation,
+        login::{
+            common::{
+                LoginCtx, LoginStates, LoginStepOut, verify_operational_negotiation,
+            },
+            login_srp::SrpUser,
         },
     },
 };
 
 /* -------------------- helpers (CHAP) -------------------- */
 
-/// CHAP_R = MD5( one-octet CHAP_ID || secret || challenge ), HEX uppercase with
-/// prefix 0x
-fn calc_chap_r_hex(id: u8, secret: &[u8], challenge: &[u8]) -> String {
-    let mut h = Md5::new();
-    h.update([id]);
-    h.update(secret);
-    h.update(challenge);
-    let d = h.finalize();
+/// Challenges of each direction remembered for reflection / reuse checks.
+const CHALLENGE_HISTORY_LEN: usize = 1024;
+
+/// Mutual CHAP challenges of this process, over all connections.
+static CHALLENGE_HISTORY: Mutex<ChallengeHistory> = Mutex::new(ChallengeHistory::new());
+
+/// Recent CHAP_C values sent to and received from targets.
+#[derive(Debug, Default)]
+struct ChallengeHistory {
+    sent: VecDeque<Vec<u8>>,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl ChallengeHistory {
+    const fn new() -> Self {
+        Self {
+            sent: VecDeque::new(),
+            received: VecDeque::new(),
+        }
+    }
+
+    /// Accept the target's challenge unless it is shorter than
+    /// `min_challenge_len`, echoes a challenge the initiator sent (a
+    /// reflection, to have the initiator compute its own expected answer) or
+    /// repeats one the target sent before.
+    fn check_received(&mut self, challenge: &[u8], chap: &ChapConfig) -> Result<()> {
+        ensure!(
+            challenge.len() >= chap.min_challenge_len,
+            "target CHAP_C has {} bytes, at least {} required",
+            challenge.len(),
+            chap.min_challenge_len
+        );
+        ensure!(
+            !self.sent.iter().any(|c| c == challenge),
+            "target reflected a CHAP challenge of the initiator"
+        );
+        ensure!(
+            !self.received.iter().any(|c| c == challenge),
+            "target reused a CHAP challenge"
+        );
+        Self::remember(&mut self.received, challenge.to_vec());
+        Ok(())
+    }
+
+    fn record_sent(&mut self, challenge: Vec<u8>) {
+        Self::remember(&mut self.sent, challenge);
+    }
+
+    fn remember(list: &mut VecDeque<Vec<u8>>, challenge: Vec<u8>) {
+        if list.len() == CHALLENGE_HISTORY_LEN {
+            list.pop_front();
+        }
+        list.push_back(challenge);
+    }
+}
+
+/// CHAP_R = H( one-octet CHAP_ID || secret || challenge ), HEX uppercase with
+/// prefix 0x, H being the negotiated algorithm
+fn calc_chap_r_hex(
+    algorithm: ChapAlgorithm,
+    id: u8,
+    secret: &[u8],
+    challenge: &[u8],
+) -> String {
+    fn hash<D: Digest>(id: u8, secret: &[u8], challenge: &[u8]) -> Vec<u8> {
+        let mut h = D::new();
+        h.update([id]);
+        h.update(secret);
+        h.update(challenge);
+        h.finalize().to_vec()
+    }
+    to_chap_hex(&match algorithm {
+        ChapAlgorithm::Md5 => hash::<Md5>(id, secret, challenge),
+        ChapAlgorithm::Sha256 => hash::<Sha256>(id, secret, challenge),
+        ChapAlgorithm::Sha3_256 => hash::<Sha3_256>(id, secret, challenge),
+    })
+}
+
+/// Algorithm the target picked from the CHAP_A offer of `chap`. A target
+/// that answers without CHAP_A is taken to use MD5, if offered.
+fn negotiated_algorithm(answer: Option<u8>, chap: &ChapConfig) -> Result<ChapAlgorithm> {
+    let offered = chap.algorithms();
+    let algorithm = match answer {
+        Some(code) => ChapAlgorithm::from_code(code)
+            .with_context(|| format!("target chose unknown CHAP_A={code}"))?,
+        None => ChapAlgorithm::Md5,
+    };
+    ensure!(
+        offered.contains(&algorithm),
+        "target chose CHAP algorithm {algorithm}, which was not offered"
+    );
+    Ok(algorithm)
+}
 
-    let mut s = String::with_capacity(2 + d.len() * 2);
+/// Fresh CHAP_I / CHAP_C for challenging the target, as long as the
+/// algorithm's digest.
+fn new_chap_challenge(algorithm: ChapAlgorithm) -> (u8, Vec<u8>) {
+    let mut rng = rand::rng();
+    let mut challenge = vec![0u8; algorithm.digest_len()];
+    rng.fill(challenge.as_mut_slice());
+    (rng.random(), challenge)
+}
+
+/// `bytes` as uppercase hex with prefix 0x
+fn to_chap_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(2 + bytes.len() * 2);
     s.push_str("0x");
-    for b in d {
+    for b in bytes {
         use core::fmt::Write;
-        write!(&mut s, "{b:02X}").expect("WTF");
+        write!(&mut s, "{b:02X}").expect("writing to String cannot fail");
     }
     s
 }
 
-/// split CHAP_I/CHAP_C
-fn parse_chap_challenge(txt_bytes: &[u8]) -> Result<(u8, Vec<u8>)> {
+/// Check the target's CHAP_N / CHAP_R in `txt_bytes` against the challenge
+/// `(id, challenge)` the initiator sent and the target credentials of
+/// `chap` (mutual CHAP), hashed with `algorithm`.
+fn verify_target_response(
+    txt_bytes: &[u8],
+    (id, challenge): &(u8, Vec<u8>),
+    chap: &ChapConfig,
+    algorithm: ChapAlgorithm,
+) -> Result<()> {
+    let secret = chap
+        .target_secret
+        .as_deref()
+        .context("mutual CHAP without a target secret")?;
+    let txt = core::str::from_utf8(txt_bytes)?;
+    let mut name = None;
+    let mut response = None;
+    for kv in txt.split_terminator('\x00') {
+        match kv.split_once('=') {
+            Some(("CHAP_N", v)) => name = Some(v.trim()),
+            Some(("CHAP_R", v)) => response = Some(v.trim()),
+            _ => {},
+        }
+    }
+    let name = name.context("target sent no CHAP_N")?;
+    let response = response.context("target sent no CHAP_R")?;
+    if let Some(expected) = chap.target_username.as_deref() {
+        ensure!(
+            name == expected,
+            "target authenticated as {name:?}, expected {expected:?}"
+        );
+    }
+    let Some(hex) = response
+        .strip_prefix("0x")
+        .or_else(|| response.strip_prefix("0X"))
+    else {
+        bail!("unsupported CHAP_R encoding: {response}");
+    };
+    let got =
+        hex::decode(hex).with_context(|| format!("failed to decode CHAP_R: {hex}"))?;
+    let expected = calc_chap_r_hex(algorithm, *id, secret.as_bytes(), challenge);
+    ensure!(
+        to_chap_hex(&got) == expected,
+        "target CHAP_R does not match the target secret"
+    );
+    Ok(())
+}
+
+/// split CHAP_A/CHAP_I/CHAP_C; CHAP_A is optional
+fn parse_chap_challenge(txt_bytes: &[u8]) -> Result<(Option<u8>, u8, Vec<u8>)> {
     let txt = String::from_utf8(txt_bytes.to_vec())?;
+    let mut chap_a: Option<u8> = None;
     let mut chap_i: Option<u8> = None;
     let mut chap_c_hex: Option<String> = None;
 
     for kv in txt.split_terminator('\x00') {
         let mut parts = kv.splitn(2, '=');
         match (parts.next(), parts.next()) {
+            (Some("CHAP_A"), Some(v)) => chap_a = Some(v.trim().parse()?),
             (Some("CHAP_I"), Some(v)) => chap_i = Some(v.trim().parse()?),
             (Some("CHAP_C"), Some(s)) => {
                 let s = s.trim();
@@ -78,11 +233,11 @@ fn parse_chap_challenge(txt_bytes: &[u8]) -> Result<(u8, Vec<u8>)> {
     }
     let chal =
         hex::decode(&hex).with_context(|| format!("failed to decode CHAP_C: {hex}"))?;
-    Ok((id, chal))
+    Ok((chap_a, id, chal))
 }
 
 /// Represents the initial state for a CHAP (Challenge-Handshake Authentication
-/// Protocol) login.
+/// Protocol) login; SRP logins start here too.
 #[derive(Debug)]
 pub struct ChapSecurity;
 
@@ -105,27 +260,27 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for ChapSecurity {
                 .exp_stat_sn(0);
 
             if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
             if let Err(e) = pdu.append_data(login_keys_security(&ctx.conn.cfg).as_slice())
             {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             match ctx.conn.send_request(Itt::default(), pdu).await {
                 Err(e) => Transition::Done(Err(e)),
-                Ok(()) => match ctx
-                    .conn
-                    .read_response::<LoginResponse>(Itt::default())
-                    .await
-                {
+                Ok(()) => match ctx.read_login_response(Itt::default()).await {
                     Ok(rsp) => {
                         ctx.last_response = Some(rsp);
-                        Transition::Next(LoginStates::ChapA(ChapA), Ok(()))
+                        let next = match ctx.conn.cfg.login.auth {
+                            AuthConfig::Srp(_) => LoginStates::SrpUser(SrpUser),
+                            _ => LoginStates::ChapA(ChapA),
+                        };
+                        Transition::Next(next, Ok(()))
                     },
-                    Err(e) => Transition::Done(Err(e)),
+                    Err(e) => Transition::Done(Err(e.into())),
                 },
             }
         })
@@ -149,7 +304,7 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for ChapA {
             let (header, itt) = {
                 let last = match ctx.validate_last_response_header() {
                     Ok(last) => last,
-                    Err(e) => return Transition::Done(Err(e)),
+                    Err(e) => return Transition::Done(Err(e.into())),
                 };
 
                 let header = LoginRequestBuilder::new(ctx.isid, last.tsih.get().into())
@@ -164,22 +319,31 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for ChapA {
             };
 
             if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
+            let offer = match ctx.chap_config() {
+                Some(c) => login_keys_chap_algorithms(c),
+                None => {
+                    return Transition::Done(Err(anyhow!(
+                        "Target requires CHAP but config has no credentials"
+                    )));
+                },
+            };
+
             let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
-            if let Err(e) = pdu.append_data(b"CHAP_A=5\x00".as_slice()) {
-                return Transition::Done(Err(e));
+            if let Err(e) = pdu.append_data(offer.as_slice()) {
+                return Transition::Done(Err(e.into()));
             }
 
             match ctx.conn.send_request(itt, pdu).await {
                 Err(e) => Transition::Done(Err(e)),
-                Ok(()) => match ctx.conn.read_response::<LoginResponse>(itt).await {
+                Ok(()) => match ctx.read_login_response(itt).await {
                     Ok(rsp) => {
                         ctx.last_response = Some(rsp);
                         Transition::Next(LoginStates::ChapAnswer(ChapAnswer), Ok(()))
                     },
-                    Err(e) => Transition::Done(Err(e)),
+                    Err(e) => Transition::Done(Err(e.into())),
                 },
             }
         })
@@ -200,37 +364,72 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for ChapAnswer {
 
     fn step<'a>(&'a self, ctx: &'a mut LoginCtx<'ctx>) -> Self::StepResult<'a> {
         Box::pin(async move {
-            let (header, itt, user, chap_r) = {
+            let (header, itt, keys, challenge, algorithm) = {
                 let last = match ctx.validate_last_response_pdu() {
                     Ok(last) => last,
-                    Err(e) => return Transition::Done(Err(e)),
+                    Err(e) => return Transition::Done(Err(e.into())),
                 };
 
                 let last_header = match ctx.validate_last_response_header() {
                     Ok(last) => last,
-                    Err(e) => return Transition::Done(Err(e)),
+                    Err(e) => return Transition::Done(Err(e.into())),
                 };
 
                 let data = match last.data() {
                     Ok(data) => data,
-                    Err(e) => return Transition::Done(Err(e)),
+                    Err(e) => return Transition::Done(Err(e.into())),
                 };
 
-                let (id, chal) = match parse_chap_challenge(data) {
+                let (chap_a, id, chal) = match parse_chap_challenge(data) {
                     Ok(v) => v,
                     Err(e) => return Transition::Done(Err(e)),
                 };
 
-                let (user, secret) = match &ctx.conn.cfg.login.auth {
-                    AuthConfig::Chap(c) => (c.username.as_str(), c.secret.as_bytes()),
-                    AuthConfig::None => {
+                let chap = match ctx.chap_config() {
+                    Some(c) if !c.secret.is_empty() => c,
+                    Some(_) => {
+                        return Transition::Done(Err(anyhow!(
+                            "no CHAP secret: set secret, secret_file or secret_env, or \
+                             install a secret provider"
+                        )));
+                    },
+                    None => {
                         return Transition::Done(Err(anyhow!(
                             "Target requires CHAP but config has no credentials"
                         )));
                     },
                 };
 
-                let chap_r = calc_chap_r_hex(id, secret, &chal);
+                let algorithm = match negotiated_algorithm(chap_a, chap) {
+                    Ok(algorithm) => algorithm,
+                    Err(e) => return Transition::Done(Err(e)),
+                };
+
+                let chap_r =
+                    calc_chap_r_hex(algorithm, id, chap.secret.as_bytes(), &chal);
+                let (keys, challenge) = if chap.is_mutual() {
+                    let (chap_i, chap_c) = new_chap_challenge(algorithm);
+                    let checked = CHALLENGE_HISTORY
+                        .lock()
+                        .map_err(|_| anyhow!("CHAP challenge history poisoned"))
+                        .and_then(|mut history| {
+                            history.check_received(&chal, chap)?;
+                            history.record_sent(chap_c.clone());
+                            Ok(())
+                        });
+                    if let Err(e) = checked {
+                        return Transition::Done(Err(e));
+                    }
+                    let keys = login_keys_chap_mutual_response(
+                        &chap.username,
+                        &chap_r,
+                        chap_i,
+                        &to_chap_hex(&chap_c),
+                    );
+                    (keys, Some((chap_i, chap_c)))
+                } else {
+                    (login_keys_chap_response(&chap.username, &chap_r), None)
+                };
 
                 let header =
                     LoginRequestBuilder::new(ctx.isid, last_header.tsih.get().into())
@@ -242,30 +441,47 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for ChapAnswer {
                         .cmd_sn(last_header.exp_cmd_sn.get())
                         .exp_stat_sn(last_header.stat_sn.get().wrapping_add(1));
 
-                (header, last_header.get_initiator_task_tag(), user, chap_r)
+                (
+                    header,
+                    last_header.get_initiator_task_tag(),
+                    keys,
+                    challenge,
+                    algorithm,
+                )
             };
+            ctx.chap_challenge = challenge;
 
             if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
-            if let Err(e) =
-                pdu.append_data(login_keys_chap_response(user, &chap_r).as_slice())
-            {
-                return Transition::Done(Err(e));
+            if let Err(e) = pdu.append_data(keys.as_slice()) {
+                return Transition::Done(Err(e.into()));
             }
 
             if let Err(e) = ctx.conn.send_request(itt, pdu).await {
                 return Transition::Done(Err(e));
             }
 
-            match ctx.conn.read_response::<LoginResponse>(itt).await {
+            match ctx.read_login_response(itt).await {
                 Ok(rsp) => {
+                    if let (Some(challenge), Some(chap)) =
+                        (&ctx.chap_challenge, ctx.chap_config())
+                    {
+                        let verified = rsp.data().map_err(Into::into).and_then(|data| {
+                            verify_target_response(data, challenge, chap, algorithm)
+                        });
+                        if let Err(e) = verified {
+                            return Transition::Done(Err(
+                                e.context("target failed mutual CHAP authentication")
+                            ));
+                        }
+                    }
                     ctx.last_response = Some(rsp);
                     Transition::Next(LoginStates::ChapOpToFull(ChapOpToFull), Ok(()))
                 },
-                Err(e) => Transition::Done(Err(e)),
+                Err(e) => Transition::Done(Err(e.into())),
             }
         })
     }
@@ -287,7 +503,7 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for ChapOpToFull {
         Box::pin(async move {
             let last = match ctx.validate_last_response_header() {
                 Ok(last) => last,
-                Err(e) => return Transition::Done(Err(e)),
+                Err(e) => return Transition::Done(Err(e.into())),
             };
 
             let itt = last.get_initiator_task_tag();
@@ -302,19 +518,19 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for ChapOpToFull {
                 .exp_stat_sn(last.stat_sn.get().wrapping_add(1));
 
             if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
             if let Err(e) =
                 pdu.append_data(login_keys_operational(&ctx.conn.cfg).as_slice())
             {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             match ctx.conn.send_request(itt, pdu).await {
                 Err(e) => Transition::Done(Err(e)),
-                Ok(()) => match ctx.conn.read_response::<LoginResponse>(itt).await {
+                Ok(()) => match ctx.read_login_response(itt).await {
                     Ok(rsp) => {
                         if let Err(e) =
                             verify_operational_negotiation(&ctx.conn.cfg, &rsp)
@@ -324,9 +540,83 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for ChapOpToFull {
                         ctx.last_response = Some(rsp);
                         Transition::Done(Ok(()))
                     },
-                    Err(e) => Transition::Done(Err(e)),
+                    Err(e) => Transition::Done(Err(e.into())),
                 },
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target_answer(name: &str, response: &str) -> Vec<u8> {
+        format!("CHAP_N={name}\0CHAP_R={response}\0").into_bytes()
+    }
+
+    #[test]
+    fn verifies_the_target_chap_response() {
+        let chap = ChapConfig::new("initiator", "initiator-secret")
+            .with_target("target", "target-secret");
+        let sha = ChapAlgorithm::Sha256;
+        let challenge = (7, vec![0xA5; sha.digest_len()]);
+        let verify =
+            |answer: &[u8]| verify_target_response(answer, &challenge, &chap, sha);
+
+        let good = calc_chap_r_hex(sha, 7, b"target-secret", &challenge.1);
+        assert!(verify(&target_answer("target", &good)).is_ok());
+        let wrong_secret = calc_chap_r_hex(sha, 7, b"initiator-secret", &challenge.1);
+        assert!(verify(&target_answer("target", &wrong_secret)).is_err());
+        let wrong_hash =
+            calc_chap_r_hex(ChapAlgorithm::Md5, 7, b"target-secret", &challenge.1);
+        assert!(verify(&target_answer("target", &wrong_hash)).is_err());
+        assert!(verify(&target_answer("intruder", &good)).is_err());
+        assert!(verify(b"CHAP_N=target\0").is_err());
+    }
+
+    #[test]
+    fn rejects_short_reflected_and_reused_challenges() {
+        let chap = ChapConfig::new("initiator", "initiator-secret")
+            .with_target("target", "target-secret");
+        let mut history = ChallengeHistory::new();
+        let ours = vec![0x11; 16];
+        history.record_sent(ours.clone());
+
+        assert!(history.check_received(&[0x22; 8], &chap).is_err());
+        assert!(history.check_received(&ours, &chap).is_err());
+        assert!(history.check_received(&[0x33; 16], &chap).is_ok());
+        assert!(history.check_received(&[0x33; 16], &chap).is_err());
+
+        for i in 0..CHALLENGE_HISTORY_LEN {
+            history.record_sent((i as u32).to_be_bytes().repeat(4));
+        }
+        assert!(history.check_received(&ours, &chap).is_ok());
+    }
+
+    #[test]
+    fn negotiates_only_offered_algorithms() -> Result<()> {
+        let mut chap = ChapConfig::new("initiator", "secret");
+        assert_eq!(login_keys_chap_algorithms(&chap), b"CHAP_A=8,7,5\0");
+        assert_eq!(
+            negotiated_algorithm(Some(8), &chap)?,
+            ChapAlgorithm::Sha3_256
+        );
+        assert_eq!(negotiated_algorithm(None, &chap)?, ChapAlgorithm::Md5);
+        assert!(negotiated_algorithm(Some(6), &chap).is_err());
+
+        chap.md5_fallback = false;
+        assert_eq!(login_keys_chap_algorithms(&chap), b"CHAP_A=8,7\0");
+        assert_eq!(negotiated_algorithm(Some(7), &chap)?, ChapAlgorithm::Sha256);
+        assert!(negotiated_algorithm(Some(5), &chap).is_err());
+        assert!(negotiated_algorithm(None, &chap).is_err());
+
+        let (_, challenge) = new_chap_challenge(ChapAlgorithm::Sha3_256);
+        assert_eq!(challenge.len(), 32);
+        assert_eq!(
+            calc_chap_r_hex(ChapAlgorithm::Sha3_256, 1, b"s", &challenge).len(),
+            66
+        );
+        Ok(())
+    }
+}
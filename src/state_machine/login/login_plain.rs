@@ -14,7 +14,6 @@ use crate::{
         login::{
             common::Stage,
             request::{LoginRequest, LoginRequestBuilder},
-            response::LoginResponse,
         },
     },
     state_machine::{
@@ -47,27 +46,23 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for PlainStart {
                 .connection_id(ctx.cid);
 
             if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
             let mut sec_bytes = login_keys_security(&ctx.conn.cfg);
             sec_bytes.extend_from_slice(&login_keys_operational(&ctx.conn.cfg));
             if let Err(e) = pdu.append_data(&sec_bytes) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             match ctx.conn.send_request(Itt::default(), pdu).await {
                 Err(e) => Transition::Done(Err(e)),
-                Ok(()) => match ctx
-                    .conn
-                    .read_response::<LoginResponse>(Itt::default())
-                    .await
-                {
+                Ok(()) => match ctx.read_login_response(Itt::default()).await {
                     Ok(rsp) => {
                         let nsg = match rsp.header_view() {
                             Ok(header) => header.flags.nsg(),
-                            Err(e) => return Transition::Done(Err(e)),
+                            Err(e) => return Transition::Done(Err(e.into())),
                         };
 
                         match nsg {
@@ -92,10 +87,9 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for PlainStart {
                             ))),
                         }
                     },
-                    Err(other) => Transition::Done(Err(anyhow::anyhow!(
-                        "got unexpected PDU: {}",
-                        other
-                    ))),
+                    Err(other) => Transition::Done(Err(
+                        anyhow::Error::from(other).context("got unexpected PDU")
+                    )),
                 },
             }
         })
@@ -119,7 +113,7 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for PlainOpToFull {
             let (header, itt) = {
                 let last = match ctx.validate_last_response_header() {
                     Ok(last) => last,
-                    Err(e) => return Transition::Done(Err(e)),
+                    Err(e) => return Transition::Done(Err(e.into())),
                 };
 
                 let header = LoginRequestBuilder::new(ctx.isid, last.tsih.get().into())
@@ -136,21 +130,21 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for PlainOpToFull {
             };
 
             if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
             if let Err(e) =
                 pdu.append_data(login_keys_operational(&ctx.conn.cfg).as_slice())
             {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             if let Err(e) = ctx.conn.send_request(itt, pdu).await {
                 return Transition::Done(Err(e));
             }
 
-            match ctx.conn.read_response::<LoginResponse>(itt).await {
+            match ctx.read_login_response(itt).await {
                 Ok(rsp) => {
                     if let Err(e) = verify_operational_negotiation(&ctx.conn.cfg, &rsp) {
                         return Transition::Done(Err(e));
@@ -158,7 +152,7 @@ impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for PlainOpToFull {
                     ctx.last_response = Some(rsp);
                     Transition::Done(Ok(()))
                 },
-                Err(e) => Transition::Done(Err(e)),
+                Err(e) => Transition::Done(Err(e.into())),
             }
         })
     }
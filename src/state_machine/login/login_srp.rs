@@ -0,0 +1,630 @@
+//! This module defines the state machine for the iSCSI SRP authentication
+//! process (RFC 7143 § 12.1.2, RFC 2945 with SHA-1). It starts from the
+//! Security request shared with CHAP and ends in the same Operational step.
+
+use std::{fmt, pin::Pin, sync::LazyLock};
+
+use anyhow::{Context, Result, anyhow, bail};
+use num_bigint::BigUint;
+use rand::RngExt;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    cfg::config::{
+        AuthConfig, SrpConfig, login_keys_srp_evidence, login_keys_srp_public,
+        login_keys_srp_user,
+    },
+    error::{self, IscsiError},
+    models::{
+        common::{BasicHeaderSegment, Builder},
+        data_fromat::PduRequest,
+        login::{
+            common::Stage,
+            request::{LoginRequest, LoginRequestBuilder},
+        },
+    },
+    state_machine::{
+        common::{StateMachine, Transition},
+        login::{
+            common::{LoginCtx, LoginStates, LoginStepOut},
+            login_chap::ChapOpToFull,
+        },
+    },
+};
+
+/// SRP-1024 modulus (RFC 3723 § 4), generator 2.
+const SRP_1024_N: &str = "EEAF0AB9ADB38DD69C33F80AFA8FC5E86072618775FF3C0B9EA2314C\
+    9C256576D674DF7496EA81D3383B4813D692C6E0E0D5D8E250B98BE48E495C1D6089DAD15DC7D7B4\
+    6154D6B6CE8EF4AD69B15D4982559B297BCF1885C529F566660E57EC68EDBC3C05726CC02FD4CBF4\
+    976EAA9AFD5138FE8376435B9FC61D2FC0EB06E3";
+
+/// SRP-1536 modulus (RFC 3723 § 4), generator 2.
+const SRP_1536_N: &str = "9DEF3CAFB939277AB1F12A8617A47BBBDBA51DF499AC4C80BEEEA961\
+    4B19CC4D5F4F5F556E27CBDE51C6A94BE4607A291558903BA0D0F84380B655BB9A22E8DCDF028A7C\
+    EC67F0D08134B1C8B97989149B609E0BE3BAB63D47548381DBC5B1FC764E3F4B53DD9DA1158BFD3E\
+    2B9C8CF56EDF019539349627DB2FD53D24B7C48665772E437D6C7F8CE442734AF7CCB7AE837C264A\
+    E3A9BEB87F8A2FE9B8B5292E5A021FFF5E91479E8CE7A28C2442C6F315180F93499A234DCF76E3FE\
+    D135F9BB";
+
+/// SRP-2048 modulus (RFC 3723 § 4), generator 2.
+const SRP_2048_N: &str = "AC6BDB41324A9A9BF166DE5E1389582FAF72B6651987EE07FC319294\
+    3DB56050A37329CBB4A099ED8193E0757767A13DD52312AB4B03310DCD7F48A9DA04FD50E8083969\
+    EDB767B0CF6095179A163AB3661A05FBD5FAAAE82918A9962F0B93B855F97993EC975EEAA80D740A\
+    DBF4FF747359D041D5C33EA71D281E446B14773BCA97B43A23FB801676BD207A436C6481F1D2B907\
+    8717461A5B9D32E688F87748544523B524B0D57D5EA77A2775D2ECFA032CFBDBF52FB3786160279\
+    004E57AE6AF874E7303CE53299CCC041C7BC308D82A5698F3A8D0C38271AE35F8E9DBFBB694B5C80\
+    3D89F7AE435DE236D525F54759B65E372FCD68EF20FA7111F9E4AFF73";
+
+/// Bytes of the initiator's ephemeral secret `a`.
+const SRP_SECRET_LEN: usize = 32;
+
+/// SRP group (modulus and generator) named in SRP_GROUP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrpGroup {
+    Srp1024,
+    Srp1536,
+    Srp2048,
+}
+
+impl SrpGroup {
+    /// The supported groups, the one preferred first.
+    pub const BY_STRENGTH: [Self; 3] = [Self::Srp2048, Self::Srp1536, Self::Srp1024];
+
+    /// Value used in SRP_GROUP.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Srp1024 => "SRP-1024",
+            Self::Srp1536 => "SRP-1536",
+            Self::Srp2048 => "SRP-2048",
+        }
+    }
+
+    /// Group of an SRP_GROUP value, if supported.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::BY_STRENGTH
+            .into_iter()
+            .find(|g| g.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    fn modulus(self) -> &'static BigUint {
+        static SRP_1024: LazyLock<BigUint> = LazyLock::new(|| parse_modulus(SRP_1024_N));
+        static SRP_1536: LazyLock<BigUint> = LazyLock::new(|| parse_modulus(SRP_1536_N));
+        static SRP_2048: LazyLock<BigUint> = LazyLock::new(|| parse_modulus(SRP_2048_N));
+        match self {
+            Self::Srp1024 => &SRP_1024,
+            Self::Srp1536 => &SRP_1536,
+            Self::Srp2048 => &SRP_2048,
+        }
+    }
+
+    fn generator(self) -> BigUint {
+        BigUint::from(2u8)
+    }
+}
+
+impl fmt::Display for SrpGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/* -------------------- helpers (SRP) -------------------- */
+
+/// Modulus given as whitespace-separated hex digits.
+fn parse_modulus(hex: &str) -> BigUint {
+    let digits = hex.split_whitespace().collect::<String>();
+    BigUint::parse_bytes(digits.as_bytes(), 16).expect("SRP modulus is valid hex")
+}
+
+fn sha1(parts: &[&[u8]]) -> [u8; 20] {
+    let mut h = Sha1::new();
+    for part in parts {
+        h.update(part);
+    }
+    h.finalize().into()
+}
+
+/// x = H(s | H(U | ":" | P))
+fn private_key(username: &str, password: &str, salt: &[u8]) -> BigUint {
+    let inner = sha1(&[username.as_bytes(), b":", password.as_bytes()]);
+    BigUint::from_bytes_be(&sha1(&[salt, &inner]))
+}
+
+/// SHA_Interleave of the shared secret S (RFC 2945 § 3.1): 40 bytes of
+/// session key.
+fn interleave(s: &BigUint) -> [u8; 40] {
+    let bytes = s.to_bytes_be();
+    // Leading zero bytes are already gone; an odd length drops one more.
+    let t = &bytes[bytes.len() % 2..];
+    let even = t.iter().step_by(2).copied().collect::<Vec<_>>();
+    let odd = t.iter().skip(1).step_by(2).copied().collect::<Vec<_>>();
+    let (g, h) = (sha1(&[&even]), sha1(&[&odd]));
+    let mut key = [0u8; 40];
+    for i in 0..20 {
+        key[2 * i] = g[i];
+        key[2 * i + 1] = h[i];
+    }
+    key
+}
+
+/// u = the first 32 bits of H(B).
+fn scrambler(b: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(&sha1(&[b])[..4])
+}
+
+/// M = H(H(N) xor H(g) | H(U) | s | A | B | K)
+fn client_evidence(
+    group: SrpGroup,
+    username: &str,
+    salt: &[u8],
+    a: &[u8],
+    b: &[u8],
+    key: &[u8; 40],
+) -> [u8; 20] {
+    let hn = sha1(&[&group.modulus().to_bytes_be()]);
+    let hg = sha1(&[&group.generator().to_bytes_be()]);
+    let mut ng = [0u8; 20];
+    for (i, byte) in ng.iter_mut().enumerate() {
+        *byte = hn[i] ^ hg[i];
+    }
+    let hu = sha1(&[username.as_bytes()]);
+    sha1(&[&ng, &hu, salt, a, b, key])
+}
+
+/// `bytes` as uppercase hex with prefix 0x
+fn to_srp_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode_upper(bytes))
+}
+
+/// Value of `key` in the key=value text of a Login Response.
+fn response_value<'a>(txt: &'a str, key: &str) -> Option<&'a str> {
+    txt.split_terminator('\x00')
+        .filter_map(|kv| kv.split_once('='))
+        .find_map(|(k, v)| (k == key).then(|| v.trim()))
+}
+
+/// Hex value of `key`, with or without the 0x prefix.
+fn response_bytes(txt: &str, key: &str) -> Result<Vec<u8>> {
+    let v = response_value(txt, key).with_context(|| format!("target sent no {key}"))?;
+    let hex = v
+        .strip_prefix("0x")
+        .or_else(|| v.strip_prefix("0X"))
+        .unwrap_or(v);
+    hex::decode(hex).with_context(|| format!("failed to decode {key}: {v}"))
+}
+
+/// Strongest supported group of an SRP_GROUP list.
+fn choose_group(list: &str) -> Result<SrpGroup> {
+    let offered = list.split(',').filter_map(SrpGroup::from_name);
+    offered
+        .min_by_key(|g| SrpGroup::BY_STRENGTH.iter().position(|s| s == g))
+        .with_context(|| format!("target offers no supported SRP group: {list}"))
+}
+
+/// Verifier `v = g^x mod N` a target stores for `username` / `password`
+/// with `salt`, big-endian.
+pub fn srp_verifier(
+    group: SrpGroup,
+    username: &str,
+    password: &str,
+    salt: &[u8],
+) -> Vec<u8> {
+    let x = private_key(username, password, salt);
+    group.generator().modpow(&x, group.modulus()).to_bytes_be()
+}
+
+/// Initiator side of one SRP exchange: the ephemeral secret and, once SRP_B
+/// arrived, the evidence sent and the session key.
+pub struct SrpClient {
+    group: SrpGroup,
+    salt: Vec<u8>,
+    secret: BigUint,
+    public: Vec<u8>,
+    proof: Option<([u8; 20], [u8; 40])>,
+}
+
+impl fmt::Debug for SrpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SrpClient")
+            .field("group", &self.group)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SrpClient {
+    /// Start an exchange in `group` for the target's `salt`, with a fresh
+    /// ephemeral secret.
+    pub fn start(group: SrpGroup, salt: Vec<u8>) -> Self {
+        let mut a = [0u8; SRP_SECRET_LEN];
+        rand::rng().fill(&mut a);
+        Self::with_secret(group, salt, BigUint::from_bytes_be(&a))
+    }
+
+    fn with_secret(group: SrpGroup, salt: Vec<u8>, secret: BigUint) -> Self {
+        let public = group
+            .generator()
+            .modpow(&secret, group.modulus())
+            .to_bytes_be();
+        Self {
+            group,
+            salt,
+            secret,
+            public,
+            proof: None,
+        }
+    }
+
+    /// SRP_A, the initiator's public value.
+    pub fn public_hex(&self) -> String {
+        to_srp_hex(&self.public)
+    }
+
+    /// Compute SRP_M from the target's SRP_B and the credentials of `srp`.
+    pub fn evidence(&mut self, srp: &SrpConfig, b: &[u8]) -> error::Result<[u8; 20]> {
+        let n = self.group.modulus();
+        let g = self.group.generator();
+        let big_b = BigUint::from_bytes_be(b);
+        if &big_b % n == BigUint::ZERO {
+            return Err(IscsiError::msg("target sent an invalid SRP_B"));
+        }
+        let u = scrambler(b);
+        if u == BigUint::ZERO {
+            return Err(IscsiError::msg("SRP scrambling parameter is zero"));
+        }
+
+        let x = private_key(&srp.username, &srp.password, &self.salt);
+        // S = (B - g^x) ^ (a + u * x) mod N
+        let gx = g.modpow(&x, n);
+        let base = (&big_b % n + n - gx) % n;
+        let s = base.modpow(&(&self.secret + u * x), n);
+        let key = interleave(&s);
+        let m =
+            client_evidence(self.group, &srp.username, &self.salt, &self.public, b, &key);
+        self.proof = Some((m, key));
+        Ok(m)
+    }
+
+    /// Check the target's SRP_HM = H(A | M | K).
+    pub fn verify_target(&self, hm: &[u8]) -> error::Result<()> {
+        let (m, key) = self.proof.as_ref().context("SRP_M was not computed")?;
+        if hm != sha1(&[&self.public, m, key]) {
+            return Err(IscsiError::msg("target SRP_HM does not match the verifier"));
+        }
+        Ok(())
+    }
+}
+
+/* -------------------- states (SRP) -------------------- */
+
+/// Represents the state where the initiator sends its SRP username (SRP_U)
+/// and whether the target must authenticate (TargetAuth).
+#[derive(Debug)]
+pub struct SrpUser;
+
+impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for SrpUser {
+    type StepResult<'a>
+        = Pin<Box<dyn Future<Output = LoginStepOut> + Send + 'a>>
+    where
+        Self: 'a,
+        LoginCtx<'ctx>: 'a;
+
+    fn step<'a>(&'a self, ctx: &'a mut LoginCtx<'ctx>) -> Self::StepResult<'a> {
+        Box::pin(async move {
+            let (header, itt) = {
+                let last = match ctx.validate_last_response_header() {
+                    Ok(last) => last,
+                    Err(e) => return Transition::Done(Err(e.into())),
+                };
+
+                let header = LoginRequestBuilder::new(ctx.isid, last.tsih.get().into())
+                    .csg(Stage::Security)
+                    .nsg(Stage::Security)
+                    .initiator_task_tag(last.get_initiator_task_tag())
+                    .connection_id(ctx.cid)
+                    .cmd_sn(last.exp_cmd_sn.get())
+                    .exp_stat_sn(last.stat_sn.get().wrapping_add(1));
+
+                (header, last.get_initiator_task_tag())
+            };
+
+            let keys = match &ctx.conn.cfg.login.auth {
+                AuthConfig::Srp(srp) => login_keys_srp_user(srp),
+                AuthConfig::None | AuthConfig::Chap(_) => {
+                    return Transition::Done(Err(anyhow!(
+                        "SRP login without SRP credentials"
+                    )));
+                },
+            };
+
+            if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
+                return Transition::Done(Err(e.into()));
+            }
+
+            let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
+            if let Err(e) = pdu.append_data(keys.as_slice()) {
+                return Transition::Done(Err(e.into()));
+            }
+
+            match ctx.conn.send_request(itt, pdu).await {
+                Err(e) => Transition::Done(Err(e)),
+                Ok(()) => match ctx.read_login_response(itt).await {
+                    Ok(rsp) => {
+                        ctx.last_response = Some(rsp);
+                        Transition::Next(LoginStates::SrpExchange(SrpExchange), Ok(()))
+                    },
+                    Err(e) => Transition::Done(Err(e.into())),
+                },
+            }
+        })
+    }
+}
+
+/// Represents the state where the initiator picks a group from SRP_GROUP,
+/// takes the salt (SRP_s) and sends its public value (SRP_A).
+#[derive(Debug)]
+pub struct SrpExchange;
+
+impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for SrpExchange {
+    type StepResult<'a>
+        = Pin<Box<dyn Future<Output = LoginStepOut> + Send + 'a>>
+    where
+        Self: 'a,
+        LoginCtx<'ctx>: 'a;
+
+    fn step<'a>(&'a self, ctx: &'a mut LoginCtx<'ctx>) -> Self::StepResult<'a> {
+        Box::pin(async move {
+            let (header, itt, client) = {
+                let last = match ctx.validate_last_response_pdu() {
+                    Ok(last) => last,
+                    Err(e) => return Transition::Done(Err(e.into())),
+                };
+
+                let last_header = match ctx.validate_last_response_header() {
+                    Ok(last) => last,
+                    Err(e) => return Transition::Done(Err(e.into())),
+                };
+
+                let client = last
+                    .data()
+                    .map_err(Into::into)
+                    .and_then(|data| Ok(core::str::from_utf8(data)?))
+                    .and_then(|txt| {
+                        let groups = response_value(txt, "SRP_GROUP")
+                            .context("target sent no SRP_GROUP")?;
+                        let group = choose_group(groups)?;
+                        let salt = response_bytes(txt, "SRP_s")?;
+                        Ok(SrpClient::start(group, salt))
+                    });
+                let client = match client {
+                    Ok(client) => client,
+                    Err(e) => return Transition::Done(Err(e)),
+                };
+
+                let header =
+                    LoginRequestBuilder::new(ctx.isid, last_header.tsih.get().into())
+                        .csg(Stage::Security)
+                        .nsg(Stage::Security)
+                        .initiator_task_tag(last_header.get_initiator_task_tag())
+                        .connection_id(ctx.cid)
+                        .cmd_sn(last_header.exp_cmd_sn.get())
+                        .exp_stat_sn(last_header.stat_sn.get().wrapping_add(1));
+
+                (header, last_header.get_initiator_task_tag(), client)
+            };
+
+            if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
+                return Transition::Done(Err(e.into()));
+            }
+
+            let keys = login_keys_srp_public(client.group.name(), &client.public_hex());
+            ctx.srp = Some(client);
+
+            let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
+            if let Err(e) = pdu.append_data(keys.as_slice()) {
+                return Transition::Done(Err(e.into()));
+            }
+
+            match ctx.conn.send_request(itt, pdu).await {
+                Err(e) => Transition::Done(Err(e)),
+                Ok(()) => match ctx.read_login_response(itt).await {
+                    Ok(rsp) => {
+                        ctx.last_response = Some(rsp);
+                        Transition::Next(LoginStates::SrpEvidence(SrpEvidence), Ok(()))
+                    },
+                    Err(e) => Transition::Done(Err(e.into())),
+                },
+            }
+        })
+    }
+}
+
+/// Represents the state where the initiator answers SRP_B with its evidence
+/// (SRP_M), leaving the Security stage, and checks the target's SRP_HM when
+/// TargetAuth was requested.
+#[derive(Debug)]
+pub struct SrpEvidence;
+
+impl<'ctx> StateMachine<LoginCtx<'ctx>, LoginStepOut> for SrpEvidence {
+    type StepResult<'a>
+        = Pin<Box<dyn Future<Output = LoginStepOut> + Send + 'a>>
+    where
+        Self: 'a,
+        LoginCtx<'ctx>: 'a;
+
+    fn step<'a>(&'a self, ctx: &'a mut LoginCtx<'ctx>) -> Self::StepResult<'a> {
+        Box::pin(async move {
+            let srp = match &ctx.conn.cfg.login.auth {
+                AuthConfig::Srp(srp) => srp.clone(),
+                AuthConfig::None | AuthConfig::Chap(_) => {
+                    return Transition::Done(Err(anyhow!(
+                        "SRP login without SRP credentials"
+                    )));
+                },
+            };
+
+            let (header, itt, b) = {
+                let last = match ctx.validate_last_response_pdu() {
+                    Ok(last) => last,
+                    Err(e) => return Transition::Done(Err(e.into())),
+                };
+
+                let last_header = match ctx.validate_last_response_header() {
+                    Ok(last) => last,
+                    Err(e) => return Transition::Done(Err(e.into())),
+                };
+
+                let b = last
+                    .data()
+                    .map_err(Into::into)
+                    .and_then(|data| Ok(core::str::from_utf8(data)?))
+                    .and_then(|txt| response_bytes(txt, "SRP_B"));
+                let b = match b {
+                    Ok(b) => b,
+                    Err(e) => return Transition::Done(Err(e)),
+                };
+
+                let header =
+                    LoginRequestBuilder::new(ctx.isid, last_header.tsih.get().into())
+                        .transit()
+                        .csg(Stage::Security)
+                        .nsg(Stage::Operational)
+                        .initiator_task_tag(last_header.get_initiator_task_tag())
+                        .connection_id(ctx.cid)
+                        .cmd_sn(last_header.exp_cmd_sn.get())
+                        .exp_stat_sn(last_header.stat_sn.get().wrapping_add(1));
+
+                (header, last_header.get_initiator_task_tag(), b)
+            };
+
+            let m = match ctx.srp.as_mut() {
+                Some(client) => client.evidence(&srp, &b),
+                None => Err(IscsiError::msg("SRP_B received before SRP_A was sent")),
+            };
+            let m = match m {
+                Ok(m) => m,
+                Err(e) => return Transition::Done(Err(e.into())),
+            };
+
+            if let Err(e) = header.header.to_bhs_bytes(ctx.buf.as_mut_slice()) {
+                return Transition::Done(Err(e.into()));
+            }
+
+            let mut pdu = PduRequest::<LoginRequest>::new_request(ctx.buf, &ctx.conn.cfg);
+            if let Err(e) =
+                pdu.append_data(login_keys_srp_evidence(&to_srp_hex(&m)).as_slice())
+            {
+                return Transition::Done(Err(e.into()));
+            }
+
+            if let Err(e) = ctx.conn.send_request(itt, pdu).await {
+                return Transition::Done(Err(e));
+            }
+
+            match ctx.read_login_response(itt).await {
+                Ok(rsp) => {
+                    if srp.target_auth {
+                        let verified = rsp
+                            .data()
+                            .map_err(Into::into)
+                            .and_then(|data| Ok(core::str::from_utf8(data)?))
+                            .and_then(|txt| response_bytes(txt, "SRP_HM"))
+                            .and_then(|hm| match &ctx.srp {
+                                Some(client) => {
+                                    client.verify_target(&hm).map_err(Into::into)
+                                },
+                                None => bail!("no SRP exchange in ctx"),
+                            });
+                        if let Err(e) = verified {
+                            return Transition::Done(Err(
+                                e.context("target failed SRP authentication")
+                            ));
+                        }
+                    }
+                    ctx.last_response = Some(rsp);
+                    Transition::Next(LoginStates::ChapOpToFull(ChapOpToFull), Ok(()))
+                },
+                Err(e) => Transition::Done(Err(e.into())),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Target side of RFC 2945: B = v + g^b, S = (A * v^u)^b.
+    fn target_key(
+        group: SrpGroup,
+        verifier: &[u8],
+        b: &BigUint,
+        client_public: &[u8],
+    ) -> (Vec<u8>, [u8; 40]) {
+        let n = group.modulus();
+        let v = BigUint::from_bytes_be(verifier);
+        let big_b = ((&v + group.generator().modpow(b, n)) % n).to_bytes_be();
+        let u = scrambler(&big_b);
+        let s = (BigUint::from_bytes_be(client_public) * v.modpow(&u, n)) % n;
+        (big_b, interleave(&s.modpow(b, n)))
+    }
+
+    #[test]
+    fn initiator_and_target_agree_on_the_session_key() -> Result<()> {
+        let group = SrpGroup::Srp1536;
+        let srp = SrpConfig::new("initiator", "password");
+        let salt = vec![0x5A; 10];
+        let verifier = srp_verifier(group, "initiator", "password", &salt);
+
+        let mut client =
+            SrpClient::with_secret(group, salt.clone(), BigUint::from(0x1234_5678u32));
+        let b = BigUint::from(0x0BAD_CAFEu32);
+        let (big_b, key) = target_key(group, &verifier, &b, &client.public);
+
+        let m = client.evidence(&srp, &big_b)?;
+        let expected =
+            client_evidence(group, "initiator", &salt, &client.public, &big_b, &key);
+        assert_eq!(m, expected);
+        assert!(
+            client
+                .verify_target(&sha1(&[&client.public, &m, &key]))
+                .is_ok()
+        );
+        assert!(client.verify_target(&[0u8; 20]).is_err());
+
+        let mut wrong =
+            SrpClient::with_secret(group, salt, BigUint::from(0x1234_5678u32));
+        let m = wrong.evidence(&SrpConfig::new("initiator", "guess"), &big_b)?;
+        assert_ne!(m, expected);
+        assert!(
+            wrong
+                .evidence(&srp, &group.modulus().to_bytes_be())
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn picks_the_strongest_offered_group() -> Result<()> {
+        assert_eq!(choose_group("SRP-1024,SRP-2048")?, SrpGroup::Srp2048);
+        assert_eq!(choose_group("SRP-768,SRP-1536")?, SrpGroup::Srp1536);
+        assert!(choose_group("SRP-768").is_err());
+        for group in SrpGroup::BY_STRENGTH {
+            let bits = group.name()[4..].parse::<u64>()?;
+            assert_eq!(group.modulus().bits(), bits);
+        }
+
+        let txt = "SRP_GROUP=SRP-1024\0SRP_s=0xA1B2\0";
+        assert_eq!(response_bytes(txt, "SRP_s")?, [0xA1, 0xB2]);
+        assert!(response_bytes(txt, "SRP_B").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn session_key_interleaves_even_and_odd_bytes() {
+        let s = BigUint::from_bytes_be(&[0x01, 0x02, 0x03, 0x04, 0x05]);
+        let key = interleave(&s);
+        let (g, h) = (sha1(&[&[0x02, 0x04]]), sha1(&[&[0x03, 0x05]]));
+        assert_eq!(key[..4], [g[0], h[0], g[1], h[1]]);
+        assert_eq!(key[38..], [g[19], h[19]]);
+    }
+}
@@ -102,9 +102,6 @@ impl<'a> LogoutCtx<'a> {
         let rsp = self.conn.read_response::<LogoutResponse>(self.itt).await?;
         let hv = rsp.header_view()?;
 
-        self.exp_stat_sn
-            .store(hv.stat_sn.get().wrapping_add(1), Ordering::SeqCst);
-
         if hv.response.decode()? != LogoutResponseCode::Success {
             bail!("LogoutResp: target returned {:?}", hv.response);
         }
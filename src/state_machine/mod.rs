@@ -15,6 +15,8 @@ pub mod logout_states;
 pub mod nop_states;
 /// State machine for the SCSI Read command.
 pub mod read_states;
+/// State machine for Task Management Function requests.
+pub mod tmf_states;
 /// State machine for the SCSI Test Unit Ready command.
 pub mod tur_states;
 /// State machine for the SCSI Write command.
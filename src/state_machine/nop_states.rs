@@ -10,6 +10,7 @@ use std::{
         Arc,
         atomic::{AtomicU32, Ordering},
     },
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result, anyhow, bail};
@@ -18,8 +19,9 @@ use tracing::debug;
 
 use crate::{
     client::{client::ClientConnection, pool_sessions::ExecuteEnv},
+    error::{self, IscsiError},
     models::{
-        common::HEADER_LEN,
+        common::{Builder, HEADER_LEN},
         data_fromat::{PduRequest, PduResponse},
         identifiers::{Itt, IttGen, Lun},
         nop::{
@@ -41,7 +43,13 @@ pub struct NopCtx<'a> {
     pub exp_stat_sn: Arc<AtomicU32>,
     pub ttt: u32,
     pub buf: [u8; HEADER_LEN],
+    /// Ping data sent with the NOP-Out; the target must echo it back.
+    pub payload: Vec<u8>,
+    /// Time from sending the NOP-Out to receiving its NOP-In, once the
+    /// exchange completed.
+    pub rtt: Option<Duration>,
 
+    sent_at: Option<Instant>,
     last_response: Option<PduResponse<NopInResponse>>,
     state: Option<NopStates>,
 }
@@ -75,29 +83,32 @@ impl<'a> NopCtx<'a> {
             exp_stat_sn,
             ttt,
             buf: [0u8; HEADER_LEN],
+            payload: Vec::new(),
+            rtt: None,
+            sent_at: None,
             state: Some(NopStates::Start(Start)),
             last_response: None,
             _lt: PhantomData,
         }
     }
 
+    /// Attach ping data to the NOP-Out. The exchange fails unless the NOP-In
+    /// echoes it back unchanged.
+    pub fn with_payload(mut self, payload: impl Into<Vec<u8>>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
     /// Resets the state of the context to the initial state.
     pub fn set_default_state(&mut self) {
         self.state = Some(NopStates::Start(Start));
     }
 
     /// Validates the header of the last received NOP-In response.
-    pub fn validate_last_response_header(&mut self) -> Result<&NopInResponse> {
+    pub fn validate_last_response_header(&mut self) -> error::Result<&NopInResponse> {
         match &self.last_response {
-            Some(l) => match l.header_view() {
-                Ok(last) => {
-                    self.exp_stat_sn
-                        .store(last.stat_sn.get().wrapping_add(1), Ordering::SeqCst);
-                    Ok(last)
-                },
-                Err(e) => Err(e),
-            },
-            None => Err(anyhow!("no last response in ctx")),
+            Some(l) => l.header_view().map_err(Into::into),
+            None => Err(IscsiError::msg("no last response in ctx")),
         }
     }
 
@@ -108,7 +119,7 @@ impl<'a> NopCtx<'a> {
         cmd_sn: Arc<AtomicU32>,
         exp_stat_sn: Arc<AtomicU32>,
         response: PduResponse<NopInResponse>,
-    ) -> Result<Self> {
+    ) -> error::Result<Self> {
         let header = response.header_view()?;
         Ok(Self {
             conn,
@@ -118,6 +129,9 @@ impl<'a> NopCtx<'a> {
             exp_stat_sn,
             ttt: header.target_task_tag.get(),
             buf: [0u8; HEADER_LEN],
+            payload: Vec::new(),
+            rtt: None,
+            sent_at: None,
             last_response: Some(response),
             state: Some(NopStates::Reply(Reply)),
             _lt: PhantomData,
@@ -137,19 +151,58 @@ impl<'a> NopCtx<'a> {
 
         header.header.to_bhs_bytes(self.buf.as_mut_slice())?;
 
-        let builder = PduRequest::<NopOutRequest>::new_request(self.buf, &self.conn.cfg);
+        let mut builder =
+            PduRequest::<NopOutRequest>::new_request(self.buf, &self.conn.cfg);
+        if !self.payload.is_empty() {
+            builder.append_data(&self.payload)?;
+        }
         self.conn.send_request(self.itt, builder).await?;
+        self.sent_at = Some(Instant::now());
         Ok(())
     }
 
+    /// Send an immediate NOP-Out with ITT=0xFFFF_FFFF that expects no NOP-In
+    /// (a reply to a target ping, or a bare ExpStatSN acknowledgment).
+    async fn send_unsolicited_nop_out(&mut self) -> Result<()> {
+        let hdr = NopOutRequestBuilder::new()
+            .immediate()
+            .lun(0_u64)
+            .initiator_task_tag(Itt::RESERVED)
+            .target_task_tag(self.ttt)
+            .cmd_sn(self.cmd_sn)
+            .exp_stat_sn(self.exp_stat_sn.load(Ordering::SeqCst))
+            .header;
+
+        hdr.to_bhs_bytes(self.buf.as_mut_slice())?;
+        let pdu = PduRequest::<NopOutRequest>::new_request(self.buf, &self.conn.cfg);
+
+        // Fire-and-forget (ITT = RESERVED)
+        self.conn.send_request(Itt::RESERVED.into(), pdu).await
+    }
+
+    /// Acknowledge every StatSN received so far by sending the current
+    /// ExpStatSN in an immediate NOP-Out that needs no answer.
+    pub async fn acknowledge_stat_sn(&mut self) -> error::Result<()> {
+        self.ttt = NopOutRequest::DEFAULT_TAG;
+        self.send_unsolicited_nop_out().await.map_err(Into::into)
+    }
+
     async fn recieve_nop_in(&mut self) -> Result<()> {
-        match self.conn.read_response::<NopInResponse>(self.itt).await {
-            Ok(rsp) => {
-                self.last_response = Some(rsp);
-                Ok(())
-            },
+        let rsp = match self.conn.read_response::<NopInResponse>(self.itt).await {
+            Ok(rsp) => rsp,
             Err(other) => bail!("got unexpected PDU: {:?}", other.to_string()),
+        };
+        self.rtt = self.sent_at.map(|sent| sent.elapsed());
+        let echoed = rsp.data()?;
+        if echoed != self.payload.as_slice() {
+            bail!(
+                "NOP-In did not echo the ping payload: sent {} bytes, got {} bytes",
+                self.payload.len(),
+                echoed.len()
+            );
         }
+        self.last_response = Some(rsp);
+        Ok(())
     }
 }
 
@@ -219,26 +272,11 @@ impl<'ctx> StateMachine<NopCtx<'ctx>, NopStepOut> for Reply {
     fn step<'a>(&'a self, ctx: &'a mut NopCtx<'ctx>) -> Self::StepResult<'a> {
         Box::pin(async move {
             if let Err(e) = ctx.validate_last_response_header() {
-                return Transition::Done(Err(e));
+                return Transition::Done(Err(e.into()));
             }
 
             // ITT for response NOP-In = 0xFFFF_FFFF (RESERVED)
-            let hdr = NopOutRequestBuilder::new()
-                .immediate()
-                .lun(0_u64)
-                .initiator_task_tag(Itt::RESERVED)
-                .target_task_tag(ctx.ttt)
-                .cmd_sn(ctx.cmd_sn)
-                .exp_stat_sn(ctx.exp_stat_sn.load(Ordering::SeqCst))
-                .header;
-
-            if let Err(e) = hdr.to_bhs_bytes(ctx.buf.as_mut_slice()) {
-                return Transition::Done(Err(e));
-            }
-            let pdu = PduRequest::<NopOutRequest>::new_request(ctx.buf, &ctx.conn.cfg);
-
-            // Response — fire-and-forget (ITT = RESERVED)
-            if let Err(e) = ctx.conn.send_request(Itt::RESERVED.into(), pdu).await {
+            if let Err(e) = ctx.send_unsolicited_nop_out().await {
                 return Transition::Done(Err(e));
             }
 
@@ -247,6 +285,20 @@ impl<'ctx> StateMachine<NopCtx<'ctx>, NopStepOut> for Reply {
     }
 }
 
+/// A NOP-Out/NOP-In exchange that yields the round-trip time [`NopCtx`]
+/// measured instead of the NOP-In.
+#[derive(Debug)]
+pub struct PingCtx<'a>(pub NopCtx<'a>);
+
+impl<'s> StateMachineCtx<PingCtx<'s>, Duration> for PingCtx<'s> {
+    async fn execute(&mut self, cancel: &CancellationToken) -> Result<Duration> {
+        self.0.execute(cancel).await?;
+        self.0
+            .rtt
+            .context("NOP-In received without a matching NOP-Out")
+    }
+}
+
 impl<'s> StateMachineCtx<NopCtx<'s>, PduResponse<NopInResponse>> for NopCtx<'s> {
     async fn execute(
         &mut self,
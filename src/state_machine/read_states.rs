@@ -20,20 +20,28 @@ use tracing::debug;
 
 use crate::{
     client::{client::ClientConnection, pool_sessions::ExecuteEnv},
+    control_block::{
+        control::CdbControl,
+        protection::{PiParams, strip_pi},
+        retry_safety::{RetrySafety, cdb_retry_safety},
+    },
+    error::{self, IscsiError},
     models::{
         command::{
             common::{ScsiStatus, TaskAttribute},
-            request::{ScsiCommandRequest, ScsiCommandRequestBuilder},
+            request::{ScsiCommandRequest, ScsiCommandRequestBuilder, extended_cdb_ahs},
             response::ScsiCommandResponse,
         },
         common::HEADER_LEN,
-        data::{response::ScsiDataIn, sense_data::SenseData},
+        data::response::ScsiDataIn,
         data_fromat::{PduRequest, PduResponse},
         identifiers::{Itt, IttGen, Lun},
         opcode::{BhsOpcode, Opcode},
         parse::Pdu,
     },
-    state_machine::common::{StateMachine, StateMachineCtx, Transition},
+    state_machine::common::{
+        ScsiStatusError, StateMachine, StateMachineCtx, TaskRef, Transition,
+    },
 };
 
 /// Represents the types of PDUs that can be received during a SCSI Read
@@ -65,6 +73,18 @@ pub struct ReadCtx<'a> {
     pub read_len: u32,
     pub cdb: [u8; 16],
     pub buf: [u8; HEADER_LEN],
+    /// Task attribute the command is queued with (SIMPLE by default).
+    pub task_attribute: TaskAttribute,
+    /// Whether the command may be re-issued after an ambiguous failure;
+    /// classified by opcode unless overridden.
+    pub retry_safety: RetrySafety,
+    /// Bytes 16.. of a CDB longer than 16 bytes (e.g. READ(32) /
+    /// WRITE(32)), sent in an Extended CDB AHS.
+    pub cdb_extension: Option<Vec<u8>>,
+    /// Protection information expected after every interval of the data
+    /// (RDPROTECT set in the CDB); checked and stripped when the read
+    /// completes.
+    pub protection: Option<PiParams>,
 
     pub last_response: Option<PduResponse<ScsiCommandResponse>>,
     pub rt: ReadRuntime,
@@ -107,6 +127,10 @@ impl<'a> ReadCtx<'a> {
             read_len,
             cdb,
             buf: [0u8; HEADER_LEN],
+            task_attribute: TaskAttribute::Simple,
+            retry_safety: cdb_retry_safety(&cdb),
+            cdb_extension: None,
+            protection: None,
             last_response: None,
             rt: ReadRuntime {
                 acc: Vec::with_capacity(read_len as usize),
@@ -119,8 +143,43 @@ impl<'a> ReadCtx<'a> {
         }
     }
 
+    /// Queue the command with `attr` instead of SIMPLE (e.g. ORDERED for a
+    /// barrier, HEAD OF QUEUE for an urgent command, ACA to run while an ACA
+    /// condition is established).
+    pub fn with_task_attribute(mut self, attr: TaskAttribute) -> Self {
+        self.task_attribute = attr;
+        self
+    }
+
+    /// Replace the NACA and vendor bits of the CDB's CONTROL byte.
+    pub fn with_control(mut self, control: CdbControl) -> Self {
+        control.apply(&mut self.cdb);
+        self
+    }
+
+    /// Override the opcode-based retry classification, for commands whose
+    /// effect depends on more than the CDB (e.g. tape READ(6), which moves
+    /// the medium position).
+    pub fn with_retry_safety(mut self, safety: RetrySafety) -> Self {
+        self.retry_safety = safety;
+        self
+    }
+
+    /// Send `extension` as bytes 16.. of the CDB in an Extended CDB AHS.
+    pub fn with_cdb_extension(mut self, extension: impl Into<Vec<u8>>) -> Self {
+        self.cdb_extension = Some(extension.into());
+        self
+    }
+
+    /// Check and strip the protection information interleaved in the data
+    /// (`read_len` must count the tuples).
+    pub fn with_protection(mut self, protection: PiParams) -> Self {
+        self.protection = Some(protection);
+        self
+    }
+
     /// Receives any PDU related to the read operation.
-    pub async fn recv_any(&self, itt: Itt) -> anyhow::Result<ReadPdu> {
+    pub async fn recv_any(&self, itt: Itt) -> error::Result<ReadPdu> {
         let (p_any, data): (PduResponse<Pdu>, Bytes) =
             self.conn.read_response_raw(itt).await?;
         let op = BhsOpcode::try_from(p_any.header_buf[0])?.opcode;
@@ -136,7 +195,9 @@ impl<'a> ReadCtx<'a> {
                 pdu.parse_with_buff(&data)?;
                 pdu
             })),
-            other => anyhow::bail!("unexpected PDU opcode for read path: {other:?}"),
+            other => Err(IscsiError::msg(format!(
+                "unexpected PDU opcode for read path: {other:?}"
+            ))),
         };
         debug!("READ {pdu_local:?}");
         pdu_local
@@ -154,11 +215,14 @@ impl<'a> ReadCtx<'a> {
             .expected_data_transfer_length(self.read_len)
             .scsi_descriptor_block(&self.cdb)
             .read()
-            .task_attribute(TaskAttribute::Simple);
+            .task_attribute(self.task_attribute);
 
         header.header.to_bhs_bytes(self.buf.as_mut_slice())?;
-        let builder =
+        let mut builder =
             PduRequest::<ScsiCommandRequest>::new_request(self.buf, &self.conn.cfg);
+        if let Some(ext) = &self.cdb_extension {
+            builder.append_ahs(&extended_cdb_ahs(ext)?)?;
+        }
         self.conn.send_request(self.itt, builder).await?;
 
         self.rt.cur_cmd_sn = Some(sn);
@@ -166,21 +230,24 @@ impl<'a> ReadCtx<'a> {
     }
 
     /// Receives a Data-In PDU.
-    pub async fn recv_datain(&self, itt: Itt) -> Result<PduResponse<ScsiDataIn>> {
-        self.conn.read_response(itt).await
+    pub async fn recv_datain(&self, itt: Itt) -> error::Result<PduResponse<ScsiDataIn>> {
+        self.conn.read_response(itt).await.map_err(Into::into)
     }
 
     /// Appends the data from a Data-In PDU to the accumulator.
-    pub fn apply_datain_append(&mut self, pdu: &PduResponse<ScsiDataIn>) -> Result<bool> {
+    pub fn apply_datain_append(
+        &mut self,
+        pdu: &PduResponse<ScsiDataIn>,
+    ) -> error::Result<bool> {
         let h = pdu.header_view()?;
 
         let off = h.buffer_offset.get() as usize;
         if off != self.rt.acc.len() {
-            return Err(anyhow!(
+            return Err(IscsiError::msg(format!(
                 "unexpected buffer_offset: got {}, expected {}",
                 off,
                 self.rt.acc.len()
-            ));
+            )));
         }
 
         let data = pdu.data()?;
@@ -189,10 +256,6 @@ impl<'a> ReadCtx<'a> {
             self.rt.acc.extend_from_slice(data);
         }
 
-        if h.stat_sn_or_rsvd.get() != 0 {
-            self.exp_stat_sn
-                .store(h.stat_sn_or_rsvd.get().wrapping_add(1), Ordering::SeqCst);
-        }
         if h.get_status_bit() {
             self.rt.status_in_datain = h.scsi_status();
             self.rt.residual_in_datain = Some(h.residual_effective());
@@ -206,7 +269,7 @@ impl<'a> ReadCtx<'a> {
     pub async fn finalize_status_after_datain(
         &mut self,
         itt: Itt,
-    ) -> Result<(ScsiStatus, u32, Option<Vec<u8>>)> {
+    ) -> error::Result<(ScsiStatus, u32, Option<Vec<u8>>)> {
         if let Some(ScsiStatus::Good) = self.rt.status_in_datain {
             return Ok((
                 ScsiStatus::Good,
@@ -228,8 +291,6 @@ impl<'a> ReadCtx<'a> {
             .status
             .decode()
             .map_err(|e| anyhow!("SCSI status decode: {e}"))?;
-        self.exp_stat_sn
-            .store(h.stat_sn.get().wrapping_add(1), Ordering::SeqCst);
 
         let data = lr.data()?;
 
@@ -298,7 +359,7 @@ impl<'ctx> StateMachine<ReadCtx<'ctx>, ReadStepOut> for ReadWait {
                     Ok(ReadPdu::DataIn(pdu)) => {
                         let is_final = match ctx.apply_datain_append(&pdu) {
                             Ok(f) => f,
-                            Err(e) => return Transition::Done(Err(e)),
+                            Err(e) => return Transition::Done(Err(e.into())),
                         };
                         if is_final {
                             break;
@@ -332,27 +393,17 @@ impl<'ctx> StateMachine<ReadCtx<'ctx>, ReadStepOut> for Finish {
             let (status, residual, sense_opt) =
                 match ctx.finalize_status_after_datain(ctx.itt).await {
                     Ok(v) => v,
-                    Err(e) => return Transition::Done(Err(e)),
+                    Err(e) => return Transition::Done(Err(e.into())),
                 };
 
             if status != ScsiStatus::Good {
-                if let Some(sb) = sense_opt {
-                    if let Ok(sense) = SenseData::parse(&sb) {
-                        return Transition::Done(Err(anyhow!(
-                            "SCSI CheckCondition: {:?}",
-                            sense
-                        )));
-                    }
-                    return Transition::Done(Err(anyhow!(
-                        "SCSI CheckCondition (sense {} bytes): {:02X?}",
-                        sb.len(),
-                        sb
-                    )));
-                }
-                return Transition::Done(Err(anyhow!(
-                    "SCSI status != GOOD ({:?}) and no sense provided",
-                    status
-                )));
+                let raw_sense = sense_opt.unwrap_or_default();
+                let data = std::mem::take(&mut ctx.rt.acc);
+                return Transition::Done(Err(ScsiStatusError::new(
+                    ctx.cdb[0], status, &raw_sense,
+                )
+                .with_data(data)
+                .into()));
             }
 
             let requested = ctx.read_len as usize;
@@ -370,6 +421,13 @@ impl<'ctx> StateMachine<ReadCtx<'ctx>, ReadStepOut> for Finish {
                 )));
             }
 
+            if let Some(p) = &ctx.protection {
+                match strip_pi(&ctx.rt.acc, p) {
+                    Ok(data) => ctx.rt.acc = data,
+                    Err(e) => return Transition::Done(Err(e.into())),
+                }
+            }
+
             Transition::Done(Ok(()))
         })
     }
@@ -413,4 +471,28 @@ impl<'ctx> StateMachineCtx<ReadCtx<'ctx>, ReadOutcome> for ReadCtx<'ctx> {
             }
         }
     }
+
+    fn is_retry_safe(&self) -> bool {
+        self.retry_safety.allows_retry(false)
+    }
+
+    fn target_lun(&self) -> Option<Lun> {
+        Some(self.lun)
+    }
+
+    fn task_attribute(&self) -> TaskAttribute {
+        self.task_attribute
+    }
+
+    fn cdb(&self) -> Option<&[u8; 16]> {
+        Some(&self.cdb)
+    }
+
+    fn task_ref(&self) -> Option<TaskRef> {
+        Some(TaskRef {
+            lun: self.lun,
+            itt: self.itt,
+            cmd_sn: self.rt.cur_cmd_sn,
+        })
+    }
 }
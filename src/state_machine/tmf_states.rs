@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+//! This module defines the state machine for iSCSI Task Management Function
+//! requests (ABORT TASK, LOGICAL UNIT RESET, ...).
+
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::{
+    client::{client::ClientConnection, pool_sessions::ExecuteEnv},
+    models::{
+        common::HEADER_LEN,
+        data_fromat::{PduRequest, PduResponse},
+        identifiers::{Itt, IttGen, Lun},
+        task_mgmt::{
+            common::{TaskManagementFunction, TmfResponseCode},
+            request::{TaskMgmtRequest, TaskMgmtRequestBuilder},
+            response::TaskMgmtResponse,
+        },
+    },
+    state_machine::common::{StateMachine, StateMachineCtx, Transition},
+};
+
+/// Context for a single Task Management Function exchange.
+///
+/// TMF requests are sent as immediate PDUs: they carry the current CmdSN but
+/// do not advance it.
+#[derive(Debug)]
+pub struct TmfCtx<'a> {
+    _lt: PhantomData<&'a ()>,
+
+    pub conn: Arc<ClientConnection>,
+    pub lun: Lun,
+    pub itt: Itt,
+    pub cmd_sn: Arc<AtomicU32>,
+    pub exp_stat_sn: Arc<AtomicU32>,
+    pub function: TaskManagementFunction,
+    /// ITT of the task addressed by ABORT TASK.
+    pub referenced_task: Option<Itt>,
+    /// CmdSN of the task addressed by ABORT TASK.
+    pub ref_cmd_sn: Option<u32>,
+    pub buf: [u8; HEADER_LEN],
+
+    last_response: Option<PduResponse<TaskMgmtResponse>>,
+    state: Option<TmfStates>,
+}
+
+impl<'a> TmfCtx<'a> {
+    pub fn from_execute_env(
+        env: ExecuteEnv,
+        lun: Lun,
+        function: TaskManagementFunction,
+    ) -> Self {
+        Self::new(
+            env.conn,
+            lun,
+            env.itt_gen.as_ref(),
+            env.cmd_sn,
+            env.exp_stat_sn,
+            function,
+        )
+    }
+
+    /// Builds an ABORT TASK for the task `referenced` issued with `ref_cmd_sn`.
+    pub fn abort_task(
+        env: ExecuteEnv,
+        lun: Lun,
+        referenced: Itt,
+        ref_cmd_sn: u32,
+    ) -> Self {
+        let mut ctx = Self::from_execute_env(env, lun, TaskManagementFunction::AbortTask);
+        ctx.referenced_task = Some(referenced);
+        ctx.ref_cmd_sn = Some(ref_cmd_sn);
+        ctx
+    }
+
+    /// Creates a new `TmfCtx` for the given function.
+    pub fn new(
+        conn: Arc<ClientConnection>,
+        lun: Lun,
+        itt_gen: &IttGen,
+        cmd_sn: Arc<AtomicU32>,
+        exp_stat_sn: Arc<AtomicU32>,
+        function: TaskManagementFunction,
+    ) -> Self {
+        Self {
+            conn,
+            lun,
+            itt: itt_gen.fetch_inc(),
+            cmd_sn,
+            exp_stat_sn,
+            function,
+            referenced_task: None,
+            ref_cmd_sn: None,
+            buf: [0u8; HEADER_LEN],
+            last_response: None,
+            state: Some(TmfStates::Idle(Idle)),
+            _lt: PhantomData,
+        }
+    }
+
+    async fn send_tmf(&mut self) -> Result<()> {
+        let cmd_sn = self.cmd_sn.load(Ordering::SeqCst);
+        let exp_stat_sn = self.exp_stat_sn.load(Ordering::SeqCst);
+
+        let mut header = TaskMgmtRequestBuilder::new(self.function, self.itt)
+            .lun(self.lun)
+            .cmd_sn(cmd_sn)
+            .exp_stat_sn(exp_stat_sn)
+            .ref_cmd_sn(self.ref_cmd_sn.unwrap_or(cmd_sn));
+        if let Some(task) = self.referenced_task {
+            header = header.referenced_task_tag(task);
+        }
+
+        header.header.to_bhs_bytes(self.buf.as_mut_slice())?;
+
+        let pdu = PduRequest::<TaskMgmtRequest>::new_request(self.buf, &self.conn.cfg);
+        self.conn.send_request(self.itt, pdu).await?;
+
+        Ok(())
+    }
+
+    async fn receive_tmf_resp(&mut self) -> Result<()> {
+        let rsp = self
+            .conn
+            .read_response::<TaskMgmtResponse>(self.itt)
+            .await?;
+        let hv = rsp.header_view()?;
+
+        match hv.response.decode() {
+            TmfResponseCode::FunctionComplete => {},
+            // The task finished before the abort reached the target.
+            TmfResponseCode::TaskDoesNotExist
+                if self.function == TaskManagementFunction::AbortTask => {},
+            other => bail!("TMF {} rejected by target: {}", self.function, other),
+        }
+
+        self.last_response = Some(rsp);
+        Ok(())
+    }
+}
+
+/// Represents the initial state of a TMF exchange.
+#[derive(Debug)]
+pub struct Idle;
+
+/// Represents the state of waiting for the TMF response.
+#[derive(Debug)]
+pub struct Wait;
+
+/// Defines the possible states for a TMF state machine.
+#[derive(Debug)]
+pub enum TmfStates {
+    Idle(Idle),
+    Wait(Wait),
+}
+
+type TmfStepOut = Transition<TmfStates, Result<()>>;
+
+impl<'ctx> StateMachine<TmfCtx<'ctx>, TmfStepOut> for Idle {
+    type StepResult<'a>
+        = Pin<Box<dyn std::future::Future<Output = TmfStepOut> + Send + 'a>>
+    where
+        Self: 'a,
+        TmfCtx<'ctx>: 'a;
+
+    fn step<'a>(&'a self, ctx: &'a mut TmfCtx<'ctx>) -> Self::StepResult<'a> {
+        Box::pin(async move {
+            match ctx.send_tmf().await {
+                Ok(()) => Transition::Next(TmfStates::Wait(Wait), Ok(())),
+                Err(e) => Transition::Done(Err(e)),
+            }
+        })
+    }
+}
+
+impl<'ctx> StateMachine<TmfCtx<'ctx>, TmfStepOut> for Wait {
+    type StepResult<'a>
+        = Pin<Box<dyn std::future::Future<Output = TmfStepOut> + Send + 'a>>
+    where
+        Self: 'a,
+        TmfCtx<'ctx>: 'a;
+
+    fn step<'a>(&'a self, ctx: &'a mut TmfCtx<'ctx>) -> Self::StepResult<'a> {
+        Box::pin(async move { Transition::Done(ctx.receive_tmf_resp().await) })
+    }
+}
+
+impl<'ctx> StateMachineCtx<TmfCtx<'ctx>, PduResponse<TaskMgmtResponse>> for TmfCtx<'ctx> {
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+    ) -> Result<PduResponse<TaskMgmtResponse>> {
+        debug!("Loop TMF {}", self.function);
+        loop {
+            let state = self.state.take().context("state must be set TmfCtx")?;
+            let trans = match state {
+                TmfStates::Idle(s) => s.step(self).await,
+                TmfStates::Wait(s) => s.step(self).await,
+            };
+
+            match trans {
+                Transition::Next(next_state, r) => {
+                    r?;
+                    self.state = Some(next_state);
+                },
+                Transition::Stay(Ok(_)) => {},
+                Transition::Stay(Err(e)) => return Err(e),
+                Transition::Done(r) => {
+                    r?;
+                    return self
+                        .last_response
+                        .take()
+                        .ok_or_else(|| anyhow!("no last response in ctx"));
+                },
+            }
+        }
+    }
+}
@@ -13,13 +13,13 @@ use std::{
     },
 };
 
-use anyhow::{Context, Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow};
 use tokio_util::sync::CancellationToken;
 use tracing::debug;
 
 use crate::{
     client::{client::ClientConnection, pool_sessions::ExecuteEnv},
-    control_block::test_unit_ready::build_test_unit_ready,
+    control_block::{control::CdbControl, test_unit_ready::build_test_unit_ready},
     models::{
         command::{
             common::{ScsiStatus, TaskAttribute},
@@ -30,7 +30,9 @@ use crate::{
         data_fromat::{PduRequest, PduResponse},
         identifiers::{Itt, IttGen, Lun},
     },
-    state_machine::common::{StateMachine, StateMachineCtx, Transition},
+    state_machine::common::{
+        ScsiStatusError, StateMachine, StateMachineCtx, TaskRef, Transition,
+    },
 };
 
 /// This structure represents the context for a SCSI Test Unit Ready (TUR)
@@ -49,6 +51,12 @@ pub struct TurCtx<'a> {
     pub buf: [u8; HEADER_LEN],
     /// The SCSI Command Descriptor Block.
     pub cbd: [u8; 16],
+    /// CmdSN the command was sent with, once it left the initiator.
+    pub cur_cmd_sn: Option<u32>,
+    /// Task attribute the command is queued with (SIMPLE by default).
+    pub task_attribute: TaskAttribute,
+    /// CONTROL byte settings of the CDB.
+    pub control: CdbControl,
 
     /// The last received command response.
     pub last_response: Option<PduResponse<ScsiCommandResponse>>,
@@ -82,16 +90,34 @@ impl<'a> TurCtx<'a> {
             lun,
             buf: [0u8; HEADER_LEN],
             cbd: [0u8; 16],
+            cur_cmd_sn: None,
+            task_attribute: TaskAttribute::Simple,
+            control: CdbControl::default(),
             last_response: None,
             state: Some(TurStates::Idle(Idle)),
             _lt: PhantomData,
         }
     }
 
+    /// Queue the command with `attr` instead of SIMPLE (e.g. ORDERED for a
+    /// barrier, HEAD OF QUEUE for an urgent command, ACA to run while an ACA
+    /// condition is established).
+    pub fn with_task_attribute(mut self, attr: TaskAttribute) -> Self {
+        self.task_attribute = attr;
+        self
+    }
+
+    /// Send the CDB with the NACA and vendor bits of `control`.
+    pub fn with_control(mut self, control: CdbControl) -> Self {
+        self.control = control;
+        self
+    }
+
     async fn send_tur(&mut self) -> Result<()> {
-        build_test_unit_ready(&mut self.cbd, 0);
+        build_test_unit_ready(&mut self.cbd, self.control.to_byte());
 
         let cmd_sn = self.cmd_sn.fetch_add(1, Ordering::SeqCst);
+        self.cur_cmd_sn = Some(cmd_sn);
         let esn = self.exp_stat_sn.load(Ordering::SeqCst);
 
         let header = ScsiCommandRequestBuilder::new()
@@ -99,7 +125,7 @@ impl<'a> TurCtx<'a> {
             .lun(self.lun.get())
             .cmd_sn(cmd_sn)
             .exp_stat_sn(esn)
-            .task_attribute(TaskAttribute::Simple)
+            .task_attribute(self.task_attribute)
             .expected_data_transfer_length(0)
             .scsi_descriptor_block(&self.cbd);
 
@@ -120,21 +146,9 @@ impl<'a> TurCtx<'a> {
         let lr = self.last_response.as_ref().expect("saved above");
         let hv = lr.header_view()?;
 
-        self.exp_stat_sn
-            .store(hv.stat_sn.get().wrapping_add(1), Ordering::SeqCst);
-
         let scsi_status = hv.status.decode()?;
         if scsi_status != ScsiStatus::Good {
-            let data = lr.data()?;
-            if !data.is_empty() {
-                bail!(
-                    "TEST UNIT READY failed: status={:?}, sense ({} bytes)={:02X?}",
-                    scsi_status,
-                    data.len(),
-                    data
-                );
-            }
-            bail!("TEST UNIT READY failed: status={:?}", scsi_status);
+            return Err(ScsiStatusError::new(self.cbd[0], scsi_status, lr.data()?).into());
         }
         Ok(())
     }
@@ -225,4 +239,24 @@ impl<'ctx> StateMachineCtx<TurCtx<'ctx>, PduResponse<ScsiCommandResponse>>
             }
         }
     }
+
+    fn target_lun(&self) -> Option<Lun> {
+        Some(self.lun)
+    }
+
+    fn task_attribute(&self) -> TaskAttribute {
+        self.task_attribute
+    }
+
+    fn cdb(&self) -> Option<&[u8; 16]> {
+        Some(&self.cbd)
+    }
+
+    fn task_ref(&self) -> Option<TaskRef> {
+        Some(TaskRef {
+            lun: self.lun,
+            itt: self.itt,
+            cmd_sn: self.cur_cmd_sn,
+        })
+    }
 }
@@ -13,30 +13,46 @@ use std::{
 };
 
 use anyhow::{Context, Result, anyhow, bail};
+use bytes::Bytes;
 use tokio_util::sync::CancellationToken;
 use tracing::debug;
 
 use crate::{
     cfg::enums::YesNo,
     client::{client::ClientConnection, pool_sessions::ExecuteEnv},
+    control_block::{
+        control::CdbControl,
+        protection::{PiParams, insert_pi},
+        retry_safety::{RetrySafety, cdb_retry_safety},
+    },
     models::{
         command::{
             common::{ResponseCode, ScsiStatus, TaskAttribute},
-            request::{ScsiCommandRequest, ScsiCommandRequestBuilder},
+            request::{ScsiCommandRequest, ScsiCommandRequestBuilder, extended_cdb_ahs},
             response::ScsiCommandResponse,
         },
         common::{BasicHeaderSegment, Builder, HEADER_LEN, SendingData},
-        data::{
-            request::{ScsiDataOut, ScsiDataOutBuilder},
-            sense_data::SenseData,
-        },
+        data::request::{ScsiDataOut, ScsiDataOutBuilder},
         data_fromat::{PduRequest, PduResponse},
         identifiers::{Itt, IttGen, Lun, Ttt},
+        opcode::{BhsOpcode, Opcode},
+        parse::Pdu,
         ready_2_transfer::response::ReadyToTransfer,
     },
-    state_machine::common::{StateMachine, StateMachineCtx, Transition},
+    state_machine::common::{
+        ScsiStatusError, StateMachine, StateMachineCtx, TaskRef, Transition,
+    },
 };
 
+/// PDUs the target may send while the initiator waits for an R2T.
+#[derive(Debug)]
+pub enum WritePdu {
+    R2T(PduResponse<ReadyToTransfer>),
+    /// The target ended the task before taking all the data, e.g. a VERIFY
+    /// or WRITE AND VERIFY that hit a MISCOMPARE.
+    CmdResp(PduResponse<ScsiCommandResponse>),
+}
+
 /// This structure represents the context for a SCSI Write operation.
 #[derive(Debug)]
 pub struct WriteCtx<'a> {
@@ -51,9 +67,22 @@ pub struct WriteCtx<'a> {
     pub cdb: [u8; 16],
     pub payload: Vec<u8>,
     pub buf: [u8; HEADER_LEN],
+    /// Task attribute the command is queued with (SIMPLE by default).
+    pub task_attribute: TaskAttribute,
+    /// Whether the command may be re-issued after an ambiguous failure;
+    /// classified by opcode unless overridden.
+    pub retry_safety: RetrySafety,
+    /// Bytes 16.. of a CDB longer than 16 bytes (e.g. READ(32) /
+    /// WRITE(32)), sent in an Extended CDB AHS.
+    pub cdb_extension: Option<Vec<u8>>,
+    /// Protection information to interleave into `payload` before it is
+    /// sent (WRPROTECT set in the CDB).
+    pub protection: Option<PiParams>,
 
     pub sent_bytes: usize,
     pub total_bytes: usize,
+    /// CmdSN the command was sent with, once it left the initiator.
+    pub cur_cmd_sn: Option<u32>,
 
     pub last_response: Option<PduResponse<ScsiCommandResponse>>,
     state: Option<WriteStates>,
@@ -96,44 +125,107 @@ impl<'a> WriteCtx<'a> {
             cdb,
             payload: payload.into(),
             buf: [0u8; HEADER_LEN],
+            task_attribute: TaskAttribute::Simple,
+            retry_safety: cdb_retry_safety(&cdb),
+            cdb_extension: None,
+            protection: None,
             sent_bytes: 0,
             total_bytes: 0,
+            cur_cmd_sn: None,
             last_response: None,
             state: Some(WriteStates::Start(Start)),
             _lt: PhantomData,
         }
     }
 
+    /// Queue the command with `attr` instead of SIMPLE (e.g. ORDERED for a
+    /// barrier, HEAD OF QUEUE for an urgent command, ACA to run while an ACA
+    /// condition is established).
+    pub fn with_task_attribute(mut self, attr: TaskAttribute) -> Self {
+        self.task_attribute = attr;
+        self
+    }
+
+    /// Replace the NACA and vendor bits of the CDB's CONTROL byte.
+    pub fn with_control(mut self, control: CdbControl) -> Self {
+        control.apply(&mut self.cdb);
+        self
+    }
+
+    /// Override the opcode-based retry classification, for commands whose
+    /// effect depends on more than the CDB (e.g. tape READ(6), which moves
+    /// the medium position).
+    pub fn with_retry_safety(mut self, safety: RetrySafety) -> Self {
+        self.retry_safety = safety;
+        self
+    }
+
+    /// Send `extension` as bytes 16.. of the CDB in an Extended CDB AHS.
+    pub fn with_cdb_extension(mut self, extension: impl Into<Vec<u8>>) -> Self {
+        self.cdb_extension = Some(extension.into());
+        self
+    }
+
+    /// Generate a protection information tuple for every interval of the
+    /// payload and send it after the interval.
+    pub fn with_protection(mut self, protection: PiParams) -> Self {
+        self.protection = Some(protection);
+        self
+    }
+
     /// Sends the SCSI Write command.
     async fn send_write_command(&mut self) -> Result<()> {
         let cmd_sn = self.cmd_sn.fetch_add(1, Ordering::SeqCst);
+        self.cur_cmd_sn = Some(cmd_sn);
         let esn = self.exp_stat_sn.load(Ordering::SeqCst);
 
         self.total_bytes = self.payload.len();
 
-        let header = ScsiCommandRequestBuilder::new()
+        let mut header = ScsiCommandRequestBuilder::new()
             .lun(self.lun.get())
             .initiator_task_tag(self.itt)
             .cmd_sn(cmd_sn)
             .exp_stat_sn(esn)
             .expected_data_transfer_length(self.total_bytes as u32)
             .scsi_descriptor_block(&self.cdb)
-            .write()
-            .task_attribute(TaskAttribute::Simple);
+            .task_attribute(self.task_attribute);
+        // W=1 with no data (e.g. VERIFY with BYTCHK=0) violates RFC 7143.
+        if self.total_bytes > 0 {
+            header = header.write();
+        }
 
         header.header.to_bhs_bytes(&mut self.buf)?;
-        let pdu = PduRequest::<ScsiCommandRequest>::new_request(self.buf, &self.conn.cfg);
+        let mut pdu =
+            PduRequest::<ScsiCommandRequest>::new_request(self.buf, &self.conn.cfg);
+        if let Some(ext) = &self.cdb_extension {
+            pdu.append_ahs(&extended_cdb_ahs(ext)?)?;
+        }
         self.conn.send_request(self.itt, pdu).await?;
 
         Ok(())
     }
 
-    async fn recv_r2t(&self, itt: Itt) -> Result<PduResponse<ReadyToTransfer>> {
-        let r2t: PduResponse<ReadyToTransfer> = self.conn.read_response(itt).await?;
-        let header = r2t.header_view()?;
-        self.exp_stat_sn
-            .store(header.stat_sn.get().wrapping_add(1), Ordering::SeqCst);
-        Ok(r2t)
+    /// Receives the next R2T, or the SCSI Response of a task the target
+    /// ended early.
+    async fn recv_r2t(&self, itt: Itt) -> Result<WritePdu> {
+        let (p_any, data): (PduResponse<Pdu>, Bytes) =
+            self.conn.read_response_raw(itt).await?;
+        let op = BhsOpcode::try_from(p_any.header_buf[0])?.opcode;
+
+        match op {
+            Opcode::ReadyToTransfer => {
+                let mut r2t = p_any.rebind_pdu::<ReadyToTransfer>()?;
+                r2t.parse_with_buff(&data)?;
+                r2t.header_view()?;
+                Ok(WritePdu::R2T(r2t))
+            },
+            Opcode::ScsiCommandResp => {
+                let mut rsp = p_any.rebind_pdu::<ScsiCommandResponse>()?;
+                rsp.parse_with_buff(&data)?;
+                Ok(WritePdu::CmdResp(rsp))
+            },
+            other => bail!("unexpected PDU opcode while waiting for R2T: {other:?}"),
+        }
     }
 
     /// Sends a window of data to the target.
@@ -205,16 +297,18 @@ impl<'a> WriteCtx<'a> {
 
     async fn wait_scsi_response(&mut self, itt: Itt) -> Result<()> {
         let rsp: PduResponse<ScsiCommandResponse> = self.conn.read_response(itt).await?;
-        let header = rsp.header_view()?;
-        self.exp_stat_sn
-            .store(header.stat_sn.get().wrapping_add(1), Ordering::SeqCst);
+        self.finish_with(rsp)
+    }
 
+    /// Checks the final SCSI Response and keeps it as `last_response`.
+    fn finish_with(&mut self, rsp: PduResponse<ScsiCommandResponse>) -> Result<()> {
+        let header = rsp.header_view()?;
         if header.response.decode()? != ResponseCode::CommandCompleted {
             bail!("WRITE failed: response={:?}", header.response);
         }
-        if header.status.decode()? != ScsiStatus::Good {
-            let sense = SenseData::parse(rsp.data()?)?;
-            bail!("WRITE failed: {:?}", sense);
+        let status = header.status.decode()?;
+        if status != ScsiStatus::Good {
+            return Err(ScsiStatusError::new(self.cdb[0], status, rsp.data()?).into());
         }
 
         self.last_response = Some(rsp);
@@ -255,22 +349,29 @@ impl<'a> WriteCtx<'a> {
     /// Sends the SCSI Write command with immediate data.
     async fn send_write_cmd_with_immediate(&mut self, imm_len: usize) -> Result<()> {
         let cmd_sn = self.cmd_sn.fetch_add(1, Ordering::SeqCst);
+        self.cur_cmd_sn = Some(cmd_sn);
         let esn = self.exp_stat_sn.load(Ordering::SeqCst);
         self.total_bytes = self.payload.len();
 
-        let header = ScsiCommandRequestBuilder::new()
+        let mut header = ScsiCommandRequestBuilder::new()
             .lun(self.lun.get())
             .initiator_task_tag(self.itt)
             .cmd_sn(cmd_sn)
             .exp_stat_sn(esn)
             .expected_data_transfer_length(self.total_bytes as u32)
             .scsi_descriptor_block(&self.cdb)
-            .write()
-            .task_attribute(TaskAttribute::Simple);
+            .task_attribute(self.task_attribute);
+        // W=1 with no data (e.g. VERIFY with BYTCHK=0) violates RFC 7143.
+        if self.total_bytes > 0 {
+            header = header.write();
+        }
 
         header.header.to_bhs_bytes(&mut self.buf)?;
         let mut pdu =
             PduRequest::<ScsiCommandRequest>::new_request(self.buf, &self.conn.cfg);
+        if let Some(ext) = &self.cdb_extension {
+            pdu.append_ahs(&extended_cdb_ahs(ext)?)?;
+        }
 
         if imm_len > 0 {
             pdu.append_data(&self.payload[0..imm_len])?;
@@ -376,6 +477,12 @@ impl<'ctx> StateMachine<WriteCtx<'ctx>, WriteStep> for Start {
 
     fn step<'a>(&'a self, ctx: &'a mut WriteCtx<'ctx>) -> Self::StepResult<'a> {
         Box::pin(async move {
+            if let Some(p) = ctx.protection.take() {
+                match insert_pi(&ctx.payload, &p) {
+                    Ok(payload) => ctx.payload = payload,
+                    Err(e) => return Transition::Done(Err(e.into())),
+                }
+            }
             ctx.total_bytes = ctx.payload.len();
 
             let use_immediate = !ctx.peer_initial_r2t() && ctx.peer_immediate_data();
@@ -432,7 +539,10 @@ impl<'ctx> StateMachine<WriteCtx<'ctx>, WriteStep> for WaitR2T {
         Box::pin(async move {
             let itt = ctx.itt;
             let r2t = match ctx.recv_r2t(itt).await {
-                Ok(v) => v,
+                Ok(WritePdu::R2T(v)) => v,
+                Ok(WritePdu::CmdResp(rsp)) => {
+                    return Transition::Done(ctx.finish_with(rsp));
+                },
                 Err(e) => return Transition::Done(Err(e)),
             };
             let h = match r2t.header_view() {
@@ -557,4 +667,28 @@ impl<'ctx> StateMachineCtx<WriteCtx<'ctx>, WriteOutcome> for WriteCtx<'ctx> {
             }
         }
     }
+
+    fn is_retry_safe(&self) -> bool {
+        self.retry_safety.allows_retry(self.sent_bytes > 0)
+    }
+
+    fn target_lun(&self) -> Option<Lun> {
+        Some(self.lun)
+    }
+
+    fn task_attribute(&self) -> TaskAttribute {
+        self.task_attribute
+    }
+
+    fn cdb(&self) -> Option<&[u8; 16]> {
+        Some(&self.cdb)
+    }
+
+    fn task_ref(&self) -> Option<TaskRef> {
+        Some(TaskRef {
+            lun: self.lun,
+            itt: self.itt,
+            cmd_sn: self.cur_cmd_sn,
+        })
+    }
 }
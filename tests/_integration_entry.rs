@@ -9,16 +9,20 @@ mod integration_tests {
 
     pub mod check_tur;
     pub mod concurrent_io;
+    pub mod device;
     pub mod io_boundaries;
     pub mod login_chap_ok;
     pub mod login_negative;
     pub mod login_plain_ok;
     pub mod logout_ok;
+    pub mod lun_handle;
     pub mod mod_sense;
+    pub mod persistent_reserve;
     pub mod read_sense;
     pub mod recovery;
     pub mod report_luns;
     pub mod send_targets_discovery;
+    pub mod start_stop_unit;
 
     //inquiry
     pub mod inquiry;
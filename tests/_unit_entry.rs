@@ -56,6 +56,8 @@ mod unit_tests {
     pub mod test_read_capacity;
     pub mod test_ready_to_transfer;
     pub mod test_reject;
+    pub mod test_report_luns;
+    pub mod test_task_mgmt;
     pub mod test_text;
     pub mod test_write;
 }
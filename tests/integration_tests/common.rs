@@ -24,7 +24,9 @@ pub fn load_config() -> Result<Config> {
 }
 
 pub async fn connect_cfg(cfg: &Config) -> Result<Arc<ClientConnection>> {
-    ClientConnection::connect(cfg.clone(), CancellationToken::new()).await
+    ClientConnection::connect(cfg.clone(), CancellationToken::new())
+        .await
+        .map_err(Into::into)
 }
 
 pub fn test_isid() -> Isid {
@@ -33,8 +35,8 @@ pub fn test_isid() -> Isid {
 
 pub fn get_lun() -> Lun {
     if test_path().contains("/truenas/") {
-        0.into()
+        Lun::ZERO
     } else {
-        (1u64 << 48).into()
+        Lun::from_number(1)
     }
 }
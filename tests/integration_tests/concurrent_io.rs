@@ -96,5 +96,5 @@ async fn concurrent_writes_and_reads_share_one_connection() -> Result<()> {
         read.await.context("read task panicked")??;
     }
 
-    pool.shutdown_gracefully(Duration::from_secs(10)).await
+    Ok(pool.shutdown_gracefully(Duration::from_secs(10)).await?)
 }
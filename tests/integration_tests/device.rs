@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::time::Duration;
+
+use anyhow::{Result, ensure};
+use iscsi_client_rs::{
+    cfg::{config::Config, logger::init_logger},
+    client::device::IscsiDevice,
+};
+use serial_test::serial;
+
+use crate::integration_tests::common::{get_lun, load_config, test_path};
+
+#[tokio::test]
+#[serial]
+async fn device_unaligned_and_split_io() -> Result<()> {
+    let _ = init_logger(&test_path());
+
+    let cfg: Config = load_config()?;
+    let dev = IscsiDevice::open(&cfg, get_lun())
+        .await?
+        .with_max_transfer_bytes(8 * 1024);
+    let bs = dev.block_size() as u64;
+    ensure!(dev.size() >= 64 * bs, "LUN too small: {} bytes", dev.size());
+
+    // Spans several blocks and several commands, starting and ending
+    // mid-block.
+    let offset = dev.size() - 40 * bs + 17;
+    let payload: Vec<u8> = (0..(32 * bs as usize + 100))
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let before = dev.read(offset - 17, 17).await?;
+
+    dev.write(offset, &payload).await?;
+    dev.flush().await?;
+    assert_eq!(dev.read(offset, payload.len()).await?, payload);
+    assert_eq!(
+        dev.read(offset - 17, 17).await?,
+        before,
+        "head bytes clobbered"
+    );
+    assert!(
+        dev.read(dev.size() - 1, 2).await.is_err(),
+        "reads past the end must be refused"
+    );
+
+    dev.close(Duration::from_secs(10)).await?;
+    Ok(())
+}
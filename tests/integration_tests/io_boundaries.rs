@@ -95,5 +95,5 @@ async fn io_around_negotiated_segment_boundaries() -> Result<()> {
         lba += blocks as u32 + 8;
     }
 
-    pool.shutdown_gracefully(Duration::from_secs(10)).await
+    Ok(pool.shutdown_gracefully(Duration::from_secs(10)).await?)
 }
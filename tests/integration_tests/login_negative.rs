@@ -6,7 +6,7 @@ use std::sync::Arc;
 use anyhow::Result;
 use iscsi_client_rs::{
     cfg::{
-        config::{AuthConfig, ChapConfig, Config},
+        config::{AuthConfig, ChapConfig, Config, SrpConfig},
         logger::init_logger,
     },
     client::pool_sessions::Pool,
@@ -41,29 +41,33 @@ async fn invalid_authentication_is_rejected() -> Result<()> {
     match &cfg.login.auth {
         AuthConfig::Chap(chap) => {
             let mut wrong_secret = cfg.clone();
-            wrong_secret.login.auth = AuthConfig::Chap(ChapConfig {
-                username: chap.username.clone(),
-                secret: "definitely-wrong-secret".into(),
-            });
+            wrong_secret.login.auth = AuthConfig::Chap(ChapConfig::new(
+                chap.username.clone(),
+                "definitely-wrong-secret",
+            ));
             assert_login_rejected(wrong_secret, 31).await?;
 
             let mut wrong_user = cfg.clone();
-            wrong_user.login.auth = AuthConfig::Chap(ChapConfig {
-                username: "unknown-user".into(),
-                secret: chap.secret.clone(),
-            });
+            wrong_user.login.auth =
+                AuthConfig::Chap(ChapConfig::new("unknown-user", chap.secret.clone()));
             assert_login_rejected(wrong_user, 32).await?;
 
             let mut missing_chap = cfg;
             missing_chap.login.auth = AuthConfig::None;
             assert_login_rejected(missing_chap, 33).await?;
         },
+        AuthConfig::Srp(srp) => {
+            let mut wrong_password = cfg.clone();
+            wrong_password.login.auth = AuthConfig::Srp(SrpConfig::new(
+                srp.username.clone(),
+                "definitely-wrong-password",
+            ));
+            assert_login_rejected(wrong_password, 35).await?;
+        },
         AuthConfig::None => {
             let mut unexpected_chap = cfg;
-            unexpected_chap.login.auth = AuthConfig::Chap(ChapConfig {
-                username: "testuser".into(),
-                secret: "secretpass".into(),
-            });
+            unexpected_chap.login.auth =
+                AuthConfig::Chap(ChapConfig::new("testuser", "secretpass"));
             assert_login_rejected(unexpected_chap, 34).await?;
         },
     }
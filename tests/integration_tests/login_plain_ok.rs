@@ -41,6 +41,13 @@ async fn login_and_nop() -> Result<()> {
         .await
         .context("NOP failed")?;
 
+    // Ping with payload echo
+    let rtt = pool
+        .ping(tsih, cid, b"iscsi-client-rs ping".to_vec())
+        .await
+        .context("ping failed")?;
+    assert!(rtt < Duration::from_secs(10));
+
     pool.shutdown_gracefully(Duration::from_secs(10)).await?;
 
     Ok(())
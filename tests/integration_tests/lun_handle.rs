@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, ensure};
+use iscsi_client_rs::{
+    cfg::{config::Config, logger::init_logger},
+    client::pool_sessions::Pool,
+    models::command::common::TaskAttribute,
+};
+use serial_test::serial;
+
+use crate::integration_tests::common::{
+    connect_cfg, get_lun, load_config, test_isid, test_path,
+};
+
+#[tokio::test]
+#[serial]
+async fn lun_handle_write_read_flush() -> Result<()> {
+    let _ = init_logger(&test_path());
+
+    let cfg: Config = load_config()?;
+    let conn = connect_cfg(&cfg).await?;
+    let pool = Pool::new(&cfg);
+    let target_name: Arc<str> = Arc::from(cfg.login.identity.target_name.clone());
+    let tsih = pool
+        .login_and_insert(target_name, test_isid(), 1u16.into(), conn)
+        .await
+        .context("pool login failed")?;
+
+    let lun = pool.session(tsih)?.lun(get_lun());
+    // Clear a pending Unit Attention left by the login.
+    let _ = lun.test_unit_ready().await;
+    lun.test_unit_ready().await.context("TUR failed")?;
+
+    let inquiry = lun.inquiry().await?;
+    ensure!(inquiry.device_type == 0x00, "LUN is not a disk");
+    let identity = lun.identity().await?.context("LUN reports no identity")?;
+    assert_eq!(
+        pool.session(tsih)?.lun(get_lun()).identity().await?,
+        Some(identity)
+    );
+
+    let cap = lun.capacity().await?;
+    // Every handle of the LUN shares the capacity cached on the session.
+    assert_eq!(
+        pool.session(tsih)?.lun(get_lun()).cached_capacity(),
+        Some(cap)
+    );
+    ensure!(cap.blocks >= 16, "LUN too small: {cap:?}");
+    let lba = cap.blocks - 8;
+    let payload: Vec<u8> = (0..8 * cap.block_size).map(|i| (i % 251) as u8).collect();
+
+    lun.write_at(lba, &payload).await?;
+    lun.flush(..).await?;
+    assert_eq!(lun.read_at(lba, 8).await?, payload);
+    assert!(
+        lun.read_at(cap.blocks - 1, 2).await.is_err(),
+        "reads past the end must be refused"
+    );
+
+    pool.shutdown_gracefully(Duration::from_secs(10)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn session_handle_flush_all() -> Result<()> {
+    let _ = init_logger(&test_path());
+
+    let cfg: Config = load_config()?;
+    let conn = connect_cfg(&cfg).await?;
+    let pool = Pool::new(&cfg);
+    let target_name: Arc<str> = Arc::from(cfg.login.identity.target_name.clone());
+    let tsih = pool
+        .login_and_insert(target_name, test_isid(), 1u16.into(), conn)
+        .await
+        .context("pool login failed")?;
+
+    let session = pool.session(tsih)?;
+    // Clear a pending Unit Attention left by the login.
+    let _ = session.lun(get_lun()).test_unit_ready().await;
+
+    let flushed = session
+        .flush_all(&[get_lun()], TaskAttribute::Ordered)
+        .await?;
+    assert_eq!(flushed, [get_lun()]);
+    // An empty list flushes every reported LUN.
+    let flushed = session.flush_all(&[], TaskAttribute::Simple).await?;
+    ensure!(flushed.contains(&get_lun()), "LUN not flushed: {flushed:?}");
+
+    pool.shutdown_gracefully(Duration::from_secs(10)).await?;
+    Ok(())
+}
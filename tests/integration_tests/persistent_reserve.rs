@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, ensure};
+use iscsi_client_rs::{
+    cfg::{config::Config, logger::init_logger},
+    client::pool_sessions::Pool,
+    control_block::persistent_reserve::{PrOutAction, PrOutParameters, PrType},
+};
+use serial_test::serial;
+
+use crate::integration_tests::common::{
+    connect_cfg, get_lun, load_config, test_isid, test_path,
+};
+
+#[tokio::test]
+#[serial]
+async fn register_reserve_release_unregister() -> Result<()> {
+    let _ = init_logger(&test_path());
+
+    let cfg: Config = load_config()?;
+    let conn = connect_cfg(&cfg).await?;
+    let pool = Pool::new(&cfg);
+    let target_name: Arc<str> = Arc::from(cfg.login.identity.target_name.clone());
+    let tsih = pool
+        .login_and_insert(target_name, test_isid(), 1u16.into(), conn)
+        .await
+        .context("pool login failed")?;
+
+    let lun = pool.session(tsih)?.lun(get_lun());
+    let _ = lun.test_unit_ready().await;
+
+    let key = 0x1C5C_0000_0000_0001;
+    let caps = lun.pr_capabilities().await?;
+    ensure!(
+        caps.supports(PrType::WriteExclusiveRegistrantsOnly),
+        "target lacks WE-RO reservations: {caps:?}"
+    );
+
+    lun.pr_out(
+        PrOutAction::RegisterAndIgnoreExistingKey,
+        None,
+        &PrOutParameters {
+            service_action_key: key,
+            ..Default::default()
+        },
+    )
+    .await?;
+    ensure!(lun.pr_read_keys().await?.keys.contains(&key));
+
+    let params = PrOutParameters {
+        reservation_key: key,
+        ..Default::default()
+    };
+    lun.pr_out(
+        PrOutAction::Reserve,
+        Some(PrType::WriteExclusiveRegistrantsOnly),
+        &params,
+    )
+    .await?;
+    let held = lun
+        .pr_read_reservation()
+        .await?
+        .reservation
+        .context("LUN is not reserved")?;
+    assert_eq!(held.key, key);
+    assert_eq!(held.pr_type, PrType::WriteExclusiveRegistrantsOnly);
+
+    lun.pr_out(
+        PrOutAction::Release,
+        Some(PrType::WriteExclusiveRegistrantsOnly),
+        &params,
+    )
+    .await?;
+    assert_eq!(lun.pr_read_reservation().await?.reservation, None);
+
+    // A zero new key removes the registration.
+    lun.pr_out(PrOutAction::Register, None, &params).await?;
+    ensure!(!lun.pr_read_keys().await?.keys.contains(&key));
+
+    let fence = lun
+        .register_and_reserve(key, PrType::WriteExclusiveRegistrantsOnly)
+        .await?;
+    assert_eq!(
+        lun.pr_read_reservation().await?.reservation.map(|r| r.key),
+        Some(key)
+    );
+    fence.release().await?;
+    fence.unregister().await?;
+    ensure!(!lun.pr_read_keys().await?.keys.contains(&key));
+
+    pool.shutdown_gracefully(Duration::from_secs(10)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn legacy_reserve_release() -> Result<()> {
+    let _ = init_logger(&test_path());
+
+    let cfg: Config = load_config()?;
+    let conn = connect_cfg(&cfg).await?;
+    let pool = Pool::new(&cfg);
+    let target_name: Arc<str> = Arc::from(cfg.login.identity.target_name.clone());
+    let tsih = pool
+        .login_and_insert(target_name, test_isid(), 1u16.into(), conn)
+        .await
+        .context("pool login failed")?;
+
+    let lun = pool.session(tsih)?.lun(get_lun());
+    let _ = lun.test_unit_ready().await;
+
+    lun.reserve_unit().await?;
+    // The holder keeps access and may reserve again.
+    lun.test_unit_ready().await?;
+    lun.reserve_unit().await?;
+    lun.release_unit().await?;
+    // Releasing an unreserved LUN is not an error.
+    lun.release_unit().await?;
+
+    pool.shutdown_gracefully(Duration::from_secs(10)).await?;
+    Ok(())
+}
@@ -7,14 +7,7 @@ use anyhow::{Context, Result};
 use iscsi_client_rs::{
     cfg::{config::AuthConfig, logger::init_logger},
     client::pool_sessions::Pool,
-    control_block::{
-        read::build_read10,
-        read_capacity::{
-            Rc10Raw, Rc16Raw, build_read_capacity10, build_read_capacity16,
-            parse_read_capacity10_zerocopy, parse_read_capacity16_zerocopy,
-        },
-        write::build_write10,
-    },
+    control_block::{read::build_read10, write::build_write10},
     state_machine::{read_states::ReadCtx, write_states::WriteCtx},
 };
 use serial_test::serial;
@@ -59,33 +52,13 @@ async fn read10_write10_read10_plain_pool() -> Result<()> {
     let lba: u32 = pick_lba_from_isid(isid);
 
     // --- READ CAPACITY: узнаём реальный размер логического блока ---
-    let blk_len: usize = match pool
-        .execute_with_ctx(tsih, cid, |env| {
-            let mut cdb = [0u8; 16];
-            build_read_capacity16(&mut cdb, 0, false, 32, 0);
-            ReadCtx::from_execute_env(env, lun, 32, cdb)
-        })
+    let handle = pool.session(tsih)?.lun(lun);
+    let _ = handle.test_unit_ready().await;
+    let blk_len = handle
+        .read_capacity()
         .await
-    {
-        Ok(rc16) => {
-            let rc16_raw: &Rc16Raw =
-                parse_read_capacity16_zerocopy(&rc16.data).context("parse RC16")?;
-            rc16_raw.block_len.get() as usize
-        },
-        Err(_) => {
-            let rc10 = pool
-                .execute_with_ctx(tsih, cid, |env| {
-                    let mut cdb = [0u8; 16];
-                    build_read_capacity10(&mut cdb, 0, false, 0);
-                    ReadCtx::from_execute_env(env, lun, 8, cdb)
-                })
-                .await
-                .context("READ CAPACITY(10) failed")?;
-            let rc10_raw: &Rc10Raw =
-                parse_read_capacity10_zerocopy(&rc10.data).context("parse RC10")?;
-            rc10_raw.block_len.get() as usize
-        },
-    };
+        .context("READ CAPACITY failed")?
+        .block_size as usize;
 
     let expected_len: u32 = (blk_len as u32) * (blocks as u32);
 
@@ -9,7 +9,7 @@ use iscsi_client_rs::{
         config::{Config, login_keys_operational},
         logger::init_logger,
     },
-    client::{client::ClientConnection, pool_sessions::Pool},
+    client::{client::ClientConnection, multipath::Multipath, pool_sessions::Pool},
     models::{
         common::{BasicHeaderSegment, HEADER_LEN},
         identifiers::{Cid, Lun, Tsih},
@@ -176,6 +176,67 @@ async fn serve_recovery_target(listener: TcpListener, cfg: Config) -> Result<()>
     Ok(())
 }
 
+/// Answers every login on `listener` with `tsih` and keeps the connections
+/// open.
+async fn serve_logins(listener: TcpListener, cfg: Config, tsih: Tsih) -> Result<()> {
+    let mut open = Vec::new();
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let (login, _) = read_frame(&mut stream).await?;
+        write_login_response(&mut stream, &cfg, login, tsih).await?;
+        open.push(stream);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn multipath_fails_over_to_the_next_portal() -> Result<()> {
+    let _ = init_logger(&test_path());
+
+    let dead = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+    let first = TcpListener::bind("127.0.0.1:0").await?;
+    let second = TcpListener::bind("127.0.0.1:0").await?;
+    let (first_addr, second_addr) = (first.local_addr()?, second.local_addr()?);
+
+    let mut cfg = load_plain_cfg(dead.to_string())?;
+    cfg.login.transport.portals = vec![first_addr.to_string(), second_addr.to_string()];
+    let first_server = tokio::spawn(serve_logins(first, cfg.clone(), Tsih::new(0x0301)));
+    let second_server =
+        tokio::spawn(serve_logins(second, cfg.clone(), Tsih::new(0x0302)));
+
+    let pool = Pool::new(&cfg);
+    let multipath = Multipath::connect(pool.clone(), &cfg).await?;
+    assert_eq!(
+        multipath.active_portal().await,
+        Some(first_addr.to_string()),
+        "connect must skip the dead portal"
+    );
+    assert_eq!(multipath.active_tsih().await, Some(Tsih::new(0x0301)));
+
+    let tsih = multipath.failover().await?;
+    assert_eq!(tsih, Tsih::new(0x0302));
+    assert_eq!(
+        multipath.active_portal().await,
+        Some(second_addr.to_string())
+    );
+    assert!(pool.sessions.get(&Tsih::new(0x0301)).is_none());
+    assert!(pool.sessions.get(&tsih).is_some());
+
+    let portals = multipath.portals().await;
+    let health: Vec<_> = portals
+        .iter()
+        .map(|p| (p.healthy, p.failures, p.active))
+        .collect();
+    assert_eq!(
+        health,
+        [(false, 1, false), (false, 1, false), (true, 0, true)]
+    );
+
+    first_server.abort();
+    second_server.abort();
+    Ok(())
+}
+
 #[tokio::test]
 #[serial]
 async fn poisoned_connection_is_recreated_after_timeout() -> Result<()> {
@@ -207,7 +268,7 @@ async fn poisoned_connection_is_recreated_after_timeout() -> Result<()> {
         .clone();
 
     pool.execute_with_ctx(tsih, Cid::ZERO, |env| {
-        NopCtx::from_execute_env(env, Lun::new(1u64 << 48), ttt)
+        NopCtx::from_execute_env(env, Lun::from_number(1), ttt)
     })
     .await
     .context("NOP after recovery failed")?;
@@ -7,15 +7,14 @@ use anyhow::{Context, Result};
 use iscsi_client_rs::{
     cfg::{config::Config, logger::init_logger},
     client::pool_sessions::Pool,
-    control_block::report_luns::{fill_report_luns, select_report},
-    state_machine::{read_states::ReadCtx, tur_states::TurCtx},
+    state_machine::tur_states::TurCtx,
 };
 
 use crate::integration_tests::common::{
     connect_cfg, get_lun, load_config, test_isid, test_path,
 };
 
-/// Integration: login -> TUR -> REPORT LUNS (header) -> REPORT LUNS (full)
+/// Integration: login -> TUR -> REPORT LUNS -> LUN enumeration
 #[tokio::test]
 async fn login_tur_report_luns_pool() -> Result<()> {
     let _ = init_logger(&test_path());
@@ -34,11 +33,8 @@ async fn login_tur_report_luns_pool() -> Result<()> {
         .await
         .context("pool login failed")?;
 
-    // --- LUNs we use in this test ---
-    let lun_for_tur = get_lun();
-    let lun_report = 0u64; // REPORT LUNS always sends to LUN=0
-
     // --- TEST UNIT READY ---
+    let lun_for_tur = get_lun();
     let _ = pool
         .execute_with_ctx(tsih, cid, |env| TurCtx::from_execute_env(env, lun_for_tur))
         .await;
@@ -46,66 +42,31 @@ async fn login_tur_report_luns_pool() -> Result<()> {
         .await
         .context("TUR failed")?;
 
-    // --- REPORT LUNS (step 1): only header ---
-    let hdr = pool
-        .execute_with_ctx(tsih, cid, |env| {
-            let mut cdb = [0u8; 16];
-            fill_report_luns(
-                &mut cdb,
-                select_report::ALL_MAPPED,
-                /* allocation_len */ 16,
-                /* control */ 0x00,
-            );
-            ReadCtx::from_execute_env(
-                env,
-                iscsi_client_rs::models::identifiers::Lun::new(lun_report),
-                16,
-                cdb,
-            )
-        })
-        .await
-        .context("REPORT LUNS header read failed")?;
-    assert_eq!(hdr.data.len(), 16, "REPORT LUNS header must be 16 bytes");
-
-    let lun_list_len =
-        u32::from_be_bytes([hdr.data[0], hdr.data[1], hdr.data[2], hdr.data[3]]) as usize;
-    assert_eq!(lun_list_len % 8, 0, "LUN LIST LENGTH must be multiple of 8");
-
-    // --- REPORT LUNS (step 2): full list (8 bytes header + list) ---
-    let total_needed = 8 + lun_list_len;
-    let full = pool
-        .execute_with_ctx(tsih, cid, |env| {
-            let mut cdb = [0u8; 16];
-            fill_report_luns(
-                &mut cdb,
-                select_report::ALL_MAPPED,
-                total_needed as u32,
-                0x00,
-            );
-            ReadCtx::from_execute_env(
-                env,
-                iscsi_client_rs::models::identifiers::Lun::new(lun_report),
-                total_needed as u32,
-                cdb,
-            )
-        })
-        .await
-        .context("REPORT LUNS full read failed")?;
-
-    assert_eq!(
-        full.data.len(),
-        total_needed,
-        "unexpected REPORT LUNS length"
-    );
-    assert_eq!(
-        &full.data[4..8],
-        &[0, 0, 0, 0],
-        "reserved bytes must be zero"
-    );
-
-    let entries = lun_list_len / 8;
+    // --- REPORT LUNS (header, then the full list when needed) ---
+    let luns = pool.report_luns(tsih).await.context("REPORT LUNS failed")?;
     // Для lio обычно 1, для tgt — 2 (в зависимости от конфигурации окружения)
-    assert!(entries == 1 || entries == 2, "entries={entries}");
+    assert!(luns.len() == 1 || luns.len() == 2, "luns={luns:?}");
+    assert!(luns.contains(&lun_for_tur), "{lun_for_tur} not reported");
+    assert_eq!(pool.cached_luns(tsih).as_ref(), Some(&luns));
+
+    // --- INQUIRY + READ CAPACITY for every LUN ---
+    let infos = pool.enumerate_luns(tsih).await.context("enumerate LUNs")?;
+    assert_eq!(infos.iter().map(|info| info.lun).collect::<Vec<_>>(), luns);
+    let info = infos
+        .iter()
+        .find(|info| info.lun == lun_for_tur)
+        .context("test LUN not enumerated")?;
+    assert!(info.is_block_device(), "{info:?}");
+    assert!(info.block_size().is_some_and(|size| size > 0), "{info:?}");
+    // READ CAPACITY(16) is optional; when supported it must agree with the
+    // block size reported through READ CAPACITY(10).
+    if let Ok(rc16) = pool.session(tsih)?.lun(lun_for_tur).read_capacity16().await {
+        assert_eq!(
+            Some(rc16.block_len),
+            info.block_size(),
+            "RC10/RC16 block size mismatch"
+        );
+    }
 
     // --- Logout + ensure cleanup ---
     pool.shutdown_gracefully(Duration::from_secs(10)).await?;
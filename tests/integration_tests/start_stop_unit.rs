@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use iscsi_client_rs::{
+    cfg::{config::Config, logger::init_logger},
+    client::pool_sessions::Pool,
+    error::IscsiError,
+};
+use serial_test::serial;
+
+use crate::integration_tests::common::{
+    connect_cfg, get_lun, load_config, test_isid, test_path,
+};
+
+/// NOT READY sense key.
+const NOT_READY: u8 = 0x02;
+
+#[tokio::test]
+#[serial]
+async fn stop_then_start_unit() -> Result<()> {
+    let _ = init_logger(&test_path());
+
+    let cfg: Config = load_config()?;
+    let conn = connect_cfg(&cfg).await?;
+    let pool = Pool::new(&cfg);
+    let target_name: Arc<str> = Arc::from(cfg.login.identity.target_name.clone());
+    let tsih = pool
+        .login_and_insert(target_name, test_isid(), 1u16.into(), conn)
+        .await
+        .context("pool login failed")?;
+
+    let lun = pool.session(tsih)?.lun(get_lun());
+    let _ = lun.test_unit_ready().await;
+
+    lun.start_unit().await.context("START UNIT failed")?;
+    lun.test_unit_ready().await?;
+
+    // Not every target can stop (LIO refuses it); one that does must report
+    // NOT READY until it is started again.
+    if lun.stop_unit().await.is_ok() {
+        match lun.test_unit_ready().await {
+            Ok(()) => {},
+            Err(IscsiError::Scsi(e))
+                if e.sense_codes().is_some_and(|(key, ..)| key == NOT_READY) => {},
+            Err(e) => bail!("unexpected TUR failure on a stopped LUN: {e}"),
+        }
+        lun.start_unit()
+            .await
+            .context("START UNIT after stop failed")?;
+    }
+    lun.test_unit_ready().await?;
+
+    pool.shutdown_gracefully(Duration::from_secs(10)).await?;
+    Ok(())
+}
@@ -3,28 +3,18 @@ use std::{sync::Arc, time::Duration};
 use anyhow::{Context, Result, bail};
 use iscsi_client_rs::{
     cfg::{config::Config, logger::init_logger},
-    client::{client::ClientConnection, pool_sessions::Pool},
+    client::{pool_sessions::Pool, scrub::fill_pattern},
     control_block::{
-        read::build_read10,
-        read_capacity::{
-            Rc10Raw, Rc16Raw, build_read_capacity10, build_read_capacity16,
-            parse_read_capacity10_zerocopy, parse_read_capacity16_zerocopy,
-        },
-        write::build_write10,
+        read::build_read10, read_capacity::build_read_capacity10, write::build_write10,
     },
     state_machine::{read_states::ReadCtx, write_states::WriteCtx},
 };
 use serial_test::serial;
 
-use crate::integration_tests::common::{connect_cfg, get_lun, load_config, test_path};
+use crate::integration_tests::common::{get_lun, load_config, test_path};
 
-fn fill_pattern(buf: &mut [u8], blk_sz: usize, lba_start: u64) {
-    assert!(blk_sz > 0 && buf.len() % blk_sz == 0);
-    for (i, chunk) in buf.chunks_exact(blk_sz).enumerate() {
-        let v = (((lba_start as usize) + i) as u8) ^ 0xA5;
-        unsafe { std::ptr::write_bytes(chunk.as_ptr() as *mut u8, v, blk_sz) }
-    }
-}
+/// Seed of the pattern written and then read back.
+const PATTERN_SEED: u64 = 0xA5;
 
 fn choose_lba_safely(max_lba: u64, need_blocks: u64) -> Result<u32> {
     // Берём «середину» устройства, чтобы сдвинуться от нуля и оставить запас.
@@ -65,14 +55,12 @@ async fn write10_read10_1_gib_plain_pool_multi_tsih_mcs() -> Result<()> {
     // --- Для каждой сессии добавим дополнительные CIDs (MC/S) ---
     let max_conns = cfg.login.limits.max_connections.max(1);
     for &tsih in &tsihs {
-        for cid in 1..max_conns {
-            // отдельный TCP для каждого CID
-            let conn_i: Arc<ClientConnection> = connect_cfg(&cfg).await?;
-            pool.add_connection_to_session(tsih, cid.into(), conn_i)
-                .await
-                .with_context(|| {
-                    format!("add_connection_to_session(tsih={tsih}, cid={cid})")
-                })?;
+        let report = pool
+            .scale_connections(tsih, max_conns)
+            .await
+            .with_context(|| format!("scale_connections(tsih={tsih})"))?;
+        if let Some((cid, e)) = report.failed.first() {
+            bail!("adding CID={cid} to TSIH={tsih} failed: {e:#}");
         }
     }
 
@@ -97,44 +85,13 @@ async fn write10_read10_1_gib_plain_pool_multi_tsih_mcs() -> Result<()> {
             })
             .await;
     }
-    let (tsih0, cid0) = workers[0];
-
-    let rc10 = pool
-        .execute_with_ctx(tsih0, cid0, |env| {
-            let mut cdb = [0u8; 16];
-            build_read_capacity10(&mut cdb, 0, false, 0);
-            ReadCtx::from_execute_env(env, lun, 8, cdb)
-        })
+    let capacity = pool
+        .session(tsihs[0])?
+        .lun(lun)
+        .read_capacity()
         .await
-        .context("READ CAPACITY(10) failed")?;
-    let rc10_raw: &Rc10Raw =
-        parse_read_capacity10_zerocopy(&rc10.data).context("parse RC10")?;
-    let blk_len_10 = rc10_raw.block_len.get();
-    let max_lba_10 = rc10_raw.max_lba.get() as u64;
-
-    let (blk_len, max_lba_u64) = {
-        let try16 = pool
-            .execute_with_ctx(tsih0, cid0, |env| {
-                let mut cdb = [0u8; 16];
-                build_read_capacity16(&mut cdb, 0, false, 32, 0);
-                ReadCtx::from_execute_env(env, lun, 32, cdb)
-            })
-            .await;
-
-        match try16 {
-            Ok(rc16) => {
-                let rc16_raw: &Rc16Raw =
-                    parse_read_capacity16_zerocopy(&rc16.data).context("parse RC16")?;
-                let bl = rc16_raw.block_len.get();
-                let ml = rc16_raw.max_lba.get();
-                if max_lba_10 != u32::MAX as u64 {
-                    assert_eq!(bl, blk_len_10, "RC10/RC16 block size mismatch");
-                }
-                (bl, ml)
-            },
-            Err(_) => (blk_len_10, max_lba_10),
-        }
-    };
+        .context("READ CAPACITY failed")?;
+    let (blk_len, max_lba_u64) = (capacity.block_size, capacity.blocks.saturating_sub(1));
 
     let blk_sz = blk_len as usize;
     assert!(blk_sz.is_power_of_two() && blk_sz > 0);
@@ -189,7 +146,7 @@ async fn write10_read10_1_gib_plain_pool_multi_tsih_mcs() -> Result<()> {
                 let len_bytes = blk_this * blk_sz;
 
                 let mut payload = vec![0u8; len_bytes];
-                fill_pattern(&mut payload, blk_sz, start_lba_u32 as u64);
+                fill_pattern(&mut payload, blk_sz, start_lba_u32 as u64, PATTERN_SEED);
 
                 pool_cl
                     .execute_with_ctx(tsih, cid, |env| {
@@ -250,7 +207,7 @@ async fn write10_read10_1_gib_plain_pool_multi_tsih_mcs() -> Result<()> {
                     })?;
 
                 let mut expected = vec![0u8; len_bytes];
-                fill_pattern(&mut expected, blk_sz, start_lba_u32 as u64);
+                fill_pattern(&mut expected, blk_sz, start_lba_u32 as u64, PATTERN_SEED);
                 if chunk.data != expected {
                     bail!(
                         "data mismatch tsih={} cid={} lba={} blocks={}",
@@ -5,6 +5,7 @@ use anyhow::Result;
 use iscsi_client_rs::{
     cfg::{cli::resolve_config_path, config::Config},
     models::{
+        command::request::{AHS_TYPE_EXTENDED_CDB, extended_cdb_ahs},
         common::{BasicHeaderSegment, Builder, HEADER_LEN},
         data_fromat::PduRequest,
         nop::request::{NopOutRequest, NopOutRequestBuilder},
@@ -125,3 +126,23 @@ fn ahs_can_be_written_after_data_if_data_was_empty() -> Result<()> {
     assert!(err.to_string().contains("cannot append AHS"));
     Ok(())
 }
+
+#[test]
+fn extended_cdb_ahs_layout() -> Result<()> {
+    let cfg =
+        resolve_config_path("tests/config.yaml").and_then(Config::load_from_file)?;
+    let mut pdu = make_request(&cfg);
+
+    let extension: Vec<u8> = (16..32).collect();
+    let ahs = extended_cdb_ahs(&extension)?;
+    assert_eq!(&ahs[..4], &[0x00, 0x11, AHS_TYPE_EXTENDED_CDB, 0x00]);
+    assert_eq!(&ahs[4..], extension.as_slice());
+
+    pdu.append_ahs(&ahs)?;
+    assert_eq!(pdu.header_view()?.get_ahs_length_bytes(), 20);
+
+    // A 2-byte extension is padded to the next 4-byte boundary.
+    assert_eq!(extended_cdb_ahs(&[1, 2])?, [0, 3, 1, 0, 1, 2, 0, 0]);
+    assert!(extended_cdb_ahs(&[]).is_err());
+    Ok(())
+}
@@ -1,8 +1,18 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
+use std::time::Duration;
+
 use anyhow::Result;
-use iscsi_client_rs::cfg::config::{AuthConfig, Config};
+use iscsi_client_rs::{
+    cfg::{
+        cli::write_default_config,
+        config::{AuthConfig, ChapConfig, Config, ConfigFormat, TargetConfig},
+        enums::Digest,
+        validate::ConfigValidationError,
+    },
+    client::load_balance::{BalanceStrategy, LoadBalancePolicy},
+};
 
 #[test]
 fn integration_configs_are_valid() -> Result<()> {
@@ -35,3 +45,378 @@ fn integration_configs_are_valid() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn phase_timeouts_fall_back_to_timeout_connection() -> Result<()> {
+    let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    let base = cfg.runtime.timeout_connection;
+    assert_eq!(cfg.runtime.connect_timeout(), base);
+    assert_eq!(cfg.runtime.login_timeout(), base);
+    assert_eq!(cfg.runtime.io_timeout(), base);
+    assert_eq!(cfg.runtime.tmf_timeout(), base);
+
+    cfg.runtime.timeouts = serde_yaml::from_str("Login: 10\nTmf: 7\n")?;
+    assert_eq!(cfg.runtime.connect_timeout(), base);
+    assert_eq!(cfg.runtime.login_timeout(), Duration::from_secs(10));
+    assert_eq!(cfg.runtime.io_timeout(), base);
+    assert_eq!(cfg.runtime.tmf_timeout(), Duration::from_secs(7));
+    cfg.validate_and_normalize()?;
+
+    cfg.runtime.timeouts.io = Some(Duration::ZERO);
+    assert!(cfg.validate_and_normalize().is_err());
+    Ok(())
+}
+
+#[test]
+fn fixed_isid_gives_each_session_its_own_qualifier() -> Result<()> {
+    let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    cfg.login.identity.isid = Some("400001370000".to_string());
+    cfg.validate_and_normalize()?;
+    assert_eq!(
+        cfg.login.identity.session_isid(0)?.get(),
+        [0x40, 0x00, 0x01, 0x37, 0x00, 0x00]
+    );
+    assert_eq!(
+        cfg.login.identity.session_isid(2)?.get(),
+        [0x40, 0x00, 0x01, 0x37, 0x00, 0x02]
+    );
+
+    cfg.login.identity.isid = Some("not-hex".to_string());
+    assert!(cfg.validate_and_normalize().is_err());
+    Ok(())
+}
+
+#[test]
+fn all_portals_lists_target_address_first_without_duplicates() -> Result<()> {
+    let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    cfg.login.transport.target_address = "10.0.0.1:3260".to_string();
+    cfg.login.transport.portals = vec![
+        "10.0.1.1:3260".to_string(),
+        "10.0.0.1:3260".to_string(),
+        String::new(),
+        "[fd00::1]:3260".to_string(),
+    ];
+    assert_eq!(
+        cfg.login.transport.all_portals(),
+        ["10.0.0.1:3260", "10.0.1.1:3260", "[fd00::1]:3260"]
+    );
+    Ok(())
+}
+
+#[test]
+fn tcp_tuning_defaults_and_dscp_range() -> Result<()> {
+    let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    assert!(cfg.runtime.tcp.nodelay);
+    assert_eq!(cfg.runtime.tcp.dscp, None);
+
+    cfg.runtime.tcp = serde_yaml::from_str(
+        "NoDelay: false\nRecvBufferSize: 4194304\nKeepaliveIdle: 30\nDscp: 46\n",
+    )?;
+    assert!(!cfg.runtime.tcp.nodelay);
+    assert_eq!(cfg.runtime.tcp.recv_buffer_size, 4 << 20);
+    assert_eq!(cfg.runtime.tcp.keepalive_idle, Duration::from_secs(30));
+    assert_eq!(cfg.runtime.tcp.keepalive_retries, 0);
+    cfg.validate_and_normalize()?;
+
+    cfg.runtime.tcp.dscp = Some(64);
+    assert!(cfg.validate_and_normalize().is_err());
+    Ok(())
+}
+
+#[test]
+fn load_balance_defaults_to_round_robin() -> Result<()> {
+    let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    assert_eq!(cfg.runtime.load_balance, LoadBalancePolicy::default());
+    assert_eq!(
+        cfg.runtime.load_balance.strategy,
+        BalanceStrategy::RoundRobin
+    );
+
+    cfg.runtime.load_balance =
+        serde_yaml::from_str("Strategy: LbaAffinity\nAffinityStripeBlocks: 256\n")?;
+    assert_eq!(
+        cfg.runtime.load_balance.strategy,
+        BalanceStrategy::LbaAffinity
+    );
+    assert_eq!(cfg.runtime.load_balance.affinity_stripe_blocks, 256);
+    cfg.validate_and_normalize()?;
+
+    cfg.runtime.load_balance.affinity_stripe_blocks = 0;
+    assert!(cfg.validate_and_normalize().is_err());
+    Ok(())
+}
+
+#[test]
+fn login_parallelism_defaults_and_must_be_positive() -> Result<()> {
+    let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    assert_eq!(cfg.runtime.login_parallelism, 8);
+
+    cfg.runtime.login_parallelism = 0;
+    assert!(cfg.validate_and_normalize().is_err());
+    Ok(())
+}
+
+#[test]
+fn env_style_overrides_change_single_parameters() -> Result<()> {
+    let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    let max_sessions = cfg.runtime.max_sessions;
+    cfg.apply_overrides([
+        (
+            "ISCSI__LOGIN__FLOW__MAX_BURST_LENGTH".to_string(),
+            "1048576".to_string(),
+        ),
+        (
+            "ISCSI__LOGIN__INTEGRITY__HEADER_DIGEST".to_string(),
+            "CRC32C".to_string(),
+        ),
+        ("ISCSI__RUNTIME__TIMEOUTS__IO".to_string(), "45".to_string()),
+        ("HOME".to_string(), "/root".to_string()),
+    ])?;
+    assert_eq!(cfg.login.flow.max_burst_length, 1 << 20);
+    assert_eq!(cfg.login.integrity.header_digest.to_string(), "CRC32C");
+    assert_eq!(cfg.runtime.io_timeout(), Duration::from_secs(45));
+    assert_eq!(cfg.runtime.max_sessions, max_sessions);
+    cfg.validate_and_normalize()?;
+
+    let bad = [(
+        "ISCSI__LOGIN__FLOW__MAX_BURST_LENGTH".to_string(),
+        "lots".to_string(),
+    )];
+    assert!(cfg.apply_overrides(bad).is_err());
+    Ok(())
+}
+
+#[test]
+fn toml_and_json_configs_match_yaml() -> Result<()> {
+    let yaml = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    for (path, format) in [
+        ("tests/configs/tgt/plain.toml", ConfigFormat::Toml),
+        ("tests/configs/tgt/plain.json", ConfigFormat::Json),
+    ] {
+        assert_eq!(ConfigFormat::from_path(path.as_ref()), format);
+        let cfg = Config::load_from_file(path)?;
+        assert_eq!(
+            serde_yaml::to_value(&cfg)?,
+            serde_yaml::to_value(&yaml)?,
+            "{path}"
+        );
+    }
+    assert_eq!(
+        ConfigFormat::from_path("config.yml".as_ref()),
+        ConfigFormat::Yaml
+    );
+    assert!(
+        Config::load_from_file_as("tests/configs/tgt/plain.yaml", ConfigFormat::Json)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn targets_inherit_the_login_section() -> Result<()> {
+    let plain = std::fs::read_to_string("tests/configs/tgt/plain.yaml")?;
+    let yaml = format!(
+        "{plain}targets:
+  - TargetName: iqn.2025-08.example:disk0
+  - TargetName: iqn.2025-08.example:disk1
+    TargetAddress: 127.0.0.2:3260
+    auth:
+      AuthMethod: CHAP
+      username: user
+      secret: secretpass
+    integrity:
+      HeaderDigest: CRC32C
+      DataDigest: None
+"
+    );
+    let mut cfg: Config = ConfigFormat::Yaml.parse(&yaml)?;
+    cfg.validate_and_normalize()?;
+
+    let targets = cfg.target_configs();
+    assert_eq!(targets.len(), 2);
+    let disk0 = &targets[0];
+    assert_eq!(
+        disk0.login.identity.target_name,
+        "iqn.2025-08.example:disk0"
+    );
+    assert_eq!(disk0.login.transport.target_address, "127.0.0.1:3260");
+    assert!(matches!(disk0.login.auth, AuthConfig::None));
+    assert!(disk0.targets.is_empty());
+
+    let disk1 = cfg
+        .target_config("iqn.2025-08.example:disk1")
+        .expect("disk1 is configured");
+    assert_eq!(disk1.login.transport.target_address, "127.0.0.2:3260");
+    assert!(matches!(disk1.login.auth, AuthConfig::Chap(_)));
+    assert_eq!(disk1.login.integrity.header_digest.to_string(), "CRC32C");
+    assert_eq!(disk1.login.flow.max_burst_length, 262_144);
+    assert!(cfg.target_config("iqn.2025-08.example:disk2").is_none());
+
+    let mut dup = cfg.clone();
+    dup.targets[1].name = dup.targets[0].name.clone();
+    assert!(dup.validate_and_normalize().is_err());
+    let mut unnamed = cfg;
+    unnamed.targets[1].name.clear();
+    assert!(unnamed.validate_and_normalize().is_err());
+    Ok(())
+}
+
+#[test]
+fn default_template_is_a_valid_config() -> Result<()> {
+    let mut cfg: Config = ConfigFormat::Yaml.parse(Config::default_template())?;
+    cfg.validate_and_normalize()?;
+    assert!(matches!(cfg.login.auth, AuthConfig::None));
+    assert_eq!(cfg.runtime.max_sessions, 1);
+
+    let dir = std::env::temp_dir().join(format!("iscsi-init-{}", std::process::id()));
+    let path = dir.join("config.yaml");
+    write_default_config(&path, false)?;
+    let loaded = Config::load_from_file(&path)?;
+    assert_eq!(serde_yaml::to_value(&loaded)?, serde_yaml::to_value(&cfg)?);
+    assert!(std::fs::read_to_string(&path)?.contains("\nlogger:"));
+    assert!(write_default_config(&path, false).is_err());
+    write_default_config(&path, true)?;
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn validation_reports_every_violation_with_its_path() -> Result<()> {
+    let mut cfg = Config::load_from_file("tests/configs/tgt/plain.yaml")?;
+    cfg.login.identity.initiator_name.clear();
+    cfg.login.flow.max_recv_data_segment_length = 1000;
+    cfg.login.flow.first_burst_length = cfg.login.flow.max_burst_length * 2;
+    // DataDigest without HeaderDigest is allowed.
+    cfg.login.integrity.data_digest = Digest::CRC32C;
+    cfg.login.recovery.error_recovery_level = 2;
+    cfg.runtime.max_sessions = 0;
+
+    let paths = cfg
+        .violations()
+        .into_iter()
+        .map(|v| v.path)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            "login.identity.InitiatorName",
+            "login.flow.MaxRecvDataSegmentLength",
+            "login.flow.FirstBurstLength",
+            "login.timers.DefaultTime2Retain",
+            "runtime.MaxSessions",
+        ]
+    );
+
+    let err = cfg.validate_and_normalize().expect_err("config is invalid");
+    let err = err
+        .downcast_ref::<ConfigValidationError>()
+        .expect("typed validation error");
+    assert_eq!(err.violations.len(), 5);
+    assert!(
+        err.to_string()
+            .contains("login.flow.MaxRecvDataSegmentLength: must be a multiple of 512"),
+        "{err}"
+    );
+
+    // Inherited keys keep their own path and are reported once; keys a
+    // target sets are reported under it.
+    cfg.targets = vec![TargetConfig::new("iqn.2025-08.example:disk0")];
+    cfg.targets.push(cfg.targets[0].clone());
+    let mut flow = cfg.login.flow.clone();
+    flow.max_recv_data_segment_length = 8192;
+    cfg.targets.push(TargetConfig {
+        flow: Some(flow),
+        ..TargetConfig::new("iqn.2025-08.example:disk2")
+    });
+    let paths = cfg
+        .violations()
+        .into_iter()
+        .map(|v| v.path)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            "targets[1].TargetName",
+            "login.identity.InitiatorName",
+            "login.flow.MaxRecvDataSegmentLength",
+            "login.flow.FirstBurstLength",
+            "login.timers.DefaultTime2Retain",
+            "targets[2].flow.FirstBurstLength",
+            "runtime.MaxSessions",
+        ]
+    );
+    Ok(())
+}
+
+/// Paths of every mapping key below `node`.
+fn key_paths(node: &serde_yaml::Value, prefix: &[String], out: &mut Vec<Vec<String>>) {
+    let Some(map) = node.as_mapping() else {
+        return;
+    };
+    for (key, value) in map {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        let path = [prefix, &[key.to_string()]].concat();
+        key_paths(value, &path, out);
+        out.push(path);
+    }
+}
+
+#[test]
+fn default_template_spells_out_the_serde_defaults() -> Result<()> {
+    let template: serde_yaml::Value = serde_yaml::from_str(Config::default_template())?;
+    let full = serde_yaml::to_value(serde_yaml::from_value::<Config>(template.clone())?)?;
+    // Values naming the local setup rather than a default.
+    let placeholders = [
+        "login.identity.InitiatorAlias",
+        "login.identity.TargetName",
+        "login.transport.TargetAddress",
+        "login.transport.TargetPortalGroupTag",
+    ];
+    let mut paths = Vec::new();
+    key_paths(&template, &[], &mut paths);
+    paths.retain(|path| !placeholders.contains(&path.join(".").as_str()));
+    for path in paths {
+        let mut pruned = template.clone();
+        let (last, parents) = path.split_last().expect("paths are not empty");
+        let parent = parents
+            .iter()
+            .try_fold(&mut pruned, |node, key| node.get_mut(key.as_str()))
+            .and_then(serde_yaml::Value::as_mapping_mut)
+            .expect("parent is a mapping");
+        parent.remove(last.as_str());
+        // Only keys serde can fill in are compared.
+        if let Ok(cfg) = serde_yaml::from_value::<Config>(pruned) {
+            assert_eq!(
+                serde_yaml::to_value(cfg)?,
+                full,
+                "{} differs from its default",
+                path.join(".")
+            );
+        }
+    }
+
+    // The commented-out CHAP settings show their defaults too.
+    let chap = Config::default_template()
+        .lines()
+        .skip_while(|line| !line.contains("# CHAP:"))
+        .skip(1)
+        .take_while(|line| !line.contains("# SRP:"))
+        .filter_map(|line| line.trim().strip_prefix("# "))
+        .filter(|line| {
+            ["md5_fallback:", "min_challenge_len:", "distinct_secrets:"]
+                .iter()
+                .any(|key| line.starts_with(key))
+        })
+        .map(|line| line.split(" #").next().unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let documented: serde_yaml::Mapping = serde_yaml::from_str(&chap)?;
+    assert_eq!(documented.len(), 3, "{chap}");
+    let defaults = serde_yaml::to_value(serde_yaml::from_str::<ChapConfig>("{}")?)?;
+    for (key, value) in &documented {
+        assert_eq!(defaults.get(key), Some(value), "CHAP {key:?}");
+    }
+    Ok(())
+}
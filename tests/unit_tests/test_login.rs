@@ -1,15 +1,16 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2012-2025 Andrei Maltsev
 
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, fs, path::Path};
 
 use anyhow::{Context, Result, bail};
 use iscsi_client_rs::{
     cfg::{
         cli::resolve_config_path,
         config::{
-            AuthConfig, Config, login_keys_chap_response, login_keys_operational,
-            login_keys_security,
+            AuthConfig, ChapConfig, Config, SrpConfig, login_keys_chap_mutual_response,
+            login_keys_chap_response, login_keys_operational, login_keys_security,
+            login_keys_srp_user,
         },
     },
     models::{
@@ -20,8 +21,10 @@ use iscsi_client_rs::{
             common::Stage,
             request::{LoginRequest, LoginRequestBuilder},
             response::LoginResponse,
+            status::StatusClass,
         },
     },
+    state_machine::login::common::LoginStatusError,
 };
 
 use crate::unit_tests::{load_fixture, parse_imm, parse_mut};
@@ -80,6 +83,13 @@ fn split_zeroes(buf: &[u8]) -> BTreeSet<String> {
         .collect()
 }
 
+/// Value of the `AuthMethod` key among the NUL-separated `buf` keys.
+fn auth_method(buf: &[u8]) -> Option<String> {
+    split_zeroes(buf)
+        .into_iter()
+        .find_map(|kv| kv.strip_prefix("AuthMethod=").map(str::to_owned))
+}
+
 #[test]
 fn test_login_request() -> Result<()> {
     let cfg = resolve_config_path("tests/config.yaml")
@@ -327,3 +337,144 @@ fn chap_step4_oper_to_ff_with_ops() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn mutual_chap_offers_only_chap_and_challenges_the_target() -> Result<()> {
+    let mut cfg = resolve_config_path("tests/config_chap.yaml")
+        .and_then(Config::load_from_file)
+        .context("failed to load tests/config_chap.yaml")?;
+    let AuthConfig::Chap(chap) = &cfg.login.auth else {
+        bail!("tests/config_chap.yaml has no CHAP credentials");
+    };
+    assert_eq!(
+        auth_method(&login_keys_security(&cfg)).as_deref(),
+        Some("CHAP,None")
+    );
+
+    cfg.login.auth =
+        AuthConfig::Chap(chap.clone().with_target("target", "target-secret"));
+    cfg.validate_and_normalize()?;
+    assert_eq!(
+        auth_method(&login_keys_security(&cfg)).as_deref(),
+        Some("CHAP")
+    );
+
+    let keys = login_keys_chap_mutual_response("user", "0x01", 9, "0xAB");
+    assert_eq!(
+        split_zeroes(&keys),
+        ["CHAP_C=0xAB", "CHAP_I=9", "CHAP_N=user", "CHAP_R=0x01"]
+            .map(String::from)
+            .into()
+    );
+
+    let mut no_secret = ChapConfig::new("user", "secret");
+    no_secret.target_username = Some("target".into());
+    cfg.login.auth = AuthConfig::Chap(no_secret);
+    assert!(cfg.validate_and_normalize().is_err());
+
+    let mut same_secret =
+        ChapConfig::new("user", "secret").with_target("target", "secret");
+    cfg.login.auth = AuthConfig::Chap(same_secret.clone());
+    cfg.validate_and_normalize()?;
+    same_secret.distinct_secrets = true;
+    cfg.login.auth = AuthConfig::Chap(same_secret);
+    assert!(cfg.validate_and_normalize().is_err());
+    Ok(())
+}
+
+#[test]
+fn srp_offers_srp_and_names_the_user() -> Result<()> {
+    let mut cfg = resolve_config_path("tests/config_chap.yaml")
+        .and_then(Config::load_from_file)
+        .context("failed to load tests/config_chap.yaml")?;
+    let mut srp = SrpConfig::new("user", "password");
+    cfg.login.auth = AuthConfig::Srp(srp.clone());
+    cfg.validate_and_normalize()?;
+    assert_eq!(
+        auth_method(&login_keys_security(&cfg)).as_deref(),
+        Some("SRP,None")
+    );
+    assert_eq!(
+        split_zeroes(&login_keys_srp_user(&srp)),
+        ["SRP_U=user", "TargetAuth=No"].map(String::from).into()
+    );
+
+    srp.target_auth = true;
+    cfg.login.auth = AuthConfig::Srp(srp.clone());
+    assert_eq!(
+        auth_method(&login_keys_security(&cfg)).as_deref(),
+        Some("SRP")
+    );
+    assert_eq!(
+        split_zeroes(&login_keys_srp_user(&srp)),
+        ["SRP_U=user", "TargetAuth=Yes"].map(String::from).into()
+    );
+
+    cfg.login.auth = AuthConfig::Srp(SrpConfig::new("", "password"));
+    assert!(cfg.validate_and_normalize().is_err());
+    cfg.login.auth = AuthConfig::Srp(SrpConfig::new("user", ""));
+    assert!(cfg.validate_and_normalize().is_err());
+    Ok(())
+}
+
+#[test]
+fn srp_password_is_read_from_a_file() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("iscsi-srp-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("srp"), "s3cret\n")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir.join("srp"), fs::Permissions::from_mode(0o600))?;
+    }
+
+    let mut srp = SrpConfig::new("user", "");
+    srp.password_file = Some(Path::new("srp").into());
+    let mut auth = AuthConfig::Srp(srp.clone());
+    auth.resolve_secrets(&dir)?;
+    let AuthConfig::Srp(resolved) = auth else {
+        bail!("auth method changed");
+    };
+    assert_eq!(resolved.password, "s3cret");
+
+    srp.password = "inline".into();
+    assert!(AuthConfig::Srp(srp).resolve_secrets(&dir).is_err());
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn login_status_error_retries_only_transient_target_errors() {
+    let busy = LoginStatusError {
+        class: StatusClass::TargetError,
+        detail: 0x00,
+    };
+    let out_of_resources = LoginStatusError {
+        class: StatusClass::TargetError,
+        detail: 0x02,
+    };
+    let internal = LoginStatusError {
+        class: StatusClass::TargetError,
+        detail: 0x03,
+    };
+    let auth = LoginStatusError {
+        class: StatusClass::InitiatorError,
+        detail: 0x01,
+    };
+
+    assert!(busy.is_retryable());
+    assert!(out_of_resources.is_retryable());
+    assert!(!internal.is_retryable());
+    assert!(!auth.is_retryable());
+}
+
+#[test]
+fn login_status_error_names_detail_and_detects_lost_session() {
+    let gone = LoginStatusError {
+        class: StatusClass::InitiatorError,
+        detail: 0x0a,
+    };
+    assert!(gone.is_session_gone());
+    assert!(!gone.is_retryable());
+    assert!(gone.to_string().contains("SessionDoesNotExist"));
+}
@@ -6,8 +6,9 @@ use bytes::Bytes;
 use iscsi_client_rs::{
     cfg::{cli::resolve_config_path, config::Config},
     control_block::read_capacity::{
-        Rc10Raw, Rc16Raw, build_read_capacity10, build_read_capacity16,
-        parse_read_capacity10_zerocopy, parse_read_capacity16_zerocopy,
+        Rc10Raw, Rc16Raw, ReadCapacity16, build_read_capacity10, build_read_capacity16,
+        parse_read_capacity10_zerocopy, parse_read_capacity16,
+        parse_read_capacity16_zerocopy,
     },
     models::{
         command::{
@@ -242,5 +243,47 @@ fn test_rc16_response_parse() -> Result<()> {
     let total = rc16.total_bytes();
     assert!(total > 0, "total capacity must be > 0");
 
+    let full = parse_read_capacity16(&pdu.data()?)?;
+    assert_eq!(
+        (full.max_lba, full.block_len),
+        (rc16.max_lba.get(), rc16.block_len.get())
+    );
+    assert_eq!(full.physical_block_len(), 4096);
+    assert_eq!(full.protection_type(), None);
+    assert!(!full.lbpme);
+
+    Ok(())
+}
+
+#[test]
+fn test_rc16_decodes_protection_and_provisioning() -> Result<()> {
+    let mut data = [0u8; 32];
+    data[..8].copy_from_slice(&0x1_0000_0000u64.to_be_bytes());
+    data[8..12].copy_from_slice(&4096u32.to_be_bytes());
+    data[12] = 0x10 | (1 << 1) | 0x01; // RC BASIS=1, P_TYPE=1, PROT_EN
+    data[13] = 0x20 | 0x01; // P_I_EXPONENT=2, 2 logical blocks per physical
+    data[14] = 0x80 | 0x40 | 0x01; // LBPME, LBPRZ, lowest aligned LBA high bits
+    data[15] = 0x07;
+
+    let rc16 = parse_read_capacity16(&data)?;
+    assert_eq!(
+        rc16,
+        ReadCapacity16 {
+            max_lba: 0x1_0000_0000,
+            block_len: 4096,
+            rc_basis: 1,
+            p_type: 1,
+            prot_en: true,
+            p_i_exponent: 2,
+            logical_blocks_per_physical_exponent: 1,
+            lbpme: true,
+            lbprz: true,
+            lowest_aligned_lba: 0x107,
+        }
+    );
+    assert_eq!(rc16.protection_type(), Some(2));
+    assert_eq!(rc16.blocks(), 0x1_0000_0001);
+    assert_eq!(rc16.physical_block_len(), 8192);
+    assert!(parse_read_capacity16(&data[..12]).is_err());
     Ok(())
 }
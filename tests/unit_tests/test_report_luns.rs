@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use anyhow::Result;
+use iscsi_client_rs::{
+    control_block::report_luns::{
+        fill_report_luns_simple, parse_lun_list_length, parse_report_luns,
+    },
+    models::identifiers::Lun,
+};
+
+fn report_luns_data(list_len: u32, entries: &[u64]) -> Vec<u8> {
+    let mut data = list_len.to_be_bytes().to_vec();
+    data.extend_from_slice(&[0; 4]);
+    for lun in entries {
+        data.extend_from_slice(&lun.to_be_bytes());
+    }
+    data
+}
+
+#[test]
+fn test_report_luns_cdb() {
+    let mut cdb = [0xffu8; 16];
+    fill_report_luns_simple(&mut cdb, 2056);
+    assert_eq!(cdb[0], 0xA0);
+    assert_eq!(&cdb[6..10], &2056u32.to_be_bytes());
+    assert_eq!(&cdb[12..], &[0; 4]);
+}
+
+#[test]
+fn test_parse_report_luns() -> Result<()> {
+    let data = report_luns_data(16, &[0, 1 << 48]);
+    assert_eq!(parse_lun_list_length(&data)?, 16);
+    assert_eq!(
+        parse_report_luns(&data)?,
+        vec![Lun::ZERO, Lun::from_number(1)]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_parse_report_luns_truncated_by_allocation_length() -> Result<()> {
+    // The target has three LUNs, the allocation length let one through.
+    let data = report_luns_data(24, &[0]);
+    assert_eq!(parse_lun_list_length(&data)?, 24);
+    assert_eq!(parse_report_luns(&data)?, vec![Lun::ZERO]);
+    Ok(())
+}
+
+#[test]
+fn test_parse_report_luns_rejects_bad_headers() {
+    assert!(parse_report_luns(&[0, 0, 0]).is_err());
+    assert!(parse_report_luns(&report_luns_data(12, &[0])).is_err());
+}
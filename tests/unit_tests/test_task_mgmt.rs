@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2012-2025 Andrei Maltsev
+
+use anyhow::Result;
+use iscsi_client_rs::{
+    cfg::{cli::resolve_config_path, config::Config},
+    models::{
+        common::{BasicHeaderSegment, HEADER_LEN},
+        data_fromat::PduResponse,
+        identifiers::{Itt, Lun},
+        opcode::{BhsOpcode, Opcode},
+        task_mgmt::{
+            common::{TaskManagementFunction, TmfResponseCode},
+            request::{TaskMgmtRequest, TaskMgmtRequestBuilder},
+            response::TaskMgmtResponse,
+        },
+    },
+};
+
+use crate::unit_tests::parse_imm;
+
+#[test]
+fn test_abort_task_request_layout() -> Result<()> {
+    let header =
+        TaskMgmtRequestBuilder::new(TaskManagementFunction::AbortTask, Itt::new(7)?)
+            .lun(Lun::new(0x0001_0000_0000_0000))
+            .referenced_task_tag(Itt::new(3)?)
+            .cmd_sn(10)
+            .exp_stat_sn(20)
+            .ref_cmd_sn(9);
+
+    let mut buf = [0u8; HEADER_LEN];
+    header.header.to_bhs_bytes(&mut buf)?;
+
+    assert_eq!(buf[0], 0x40 | 0x02, "immediate TMF request opcode");
+    assert_eq!(buf[1], 0x80 | 0x01, "F bit + ABORT TASK");
+    assert_eq!(&buf[8..16], &[0, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(&buf[16..20], &7u32.to_be_bytes());
+    assert_eq!(&buf[20..24], &3u32.to_be_bytes());
+    assert_eq!(&buf[24..28], &10u32.to_be_bytes());
+    assert_eq!(&buf[28..32], &20u32.to_be_bytes());
+    assert_eq!(&buf[32..36], &9u32.to_be_bytes());
+
+    let parsed = TaskMgmtRequest::from_bhs_bytes(&mut buf)?;
+    assert_eq!(parsed.function()?, TaskManagementFunction::AbortTask);
+    assert_eq!(parsed.get_initiator_task_tag(), Itt::new(7)?);
+    Ok(())
+}
+
+#[test]
+fn test_task_mgmt_response_parse() -> Result<()> {
+    let cfg =
+        resolve_config_path("tests/config.yaml").and_then(Config::load_from_file)?;
+
+    let mut bytes = [0u8; HEADER_LEN];
+    bytes[0] = 0x22;
+    bytes[1] = 0x80;
+    bytes[2] = 0x01; // Task does not exist
+    bytes[16..20].copy_from_slice(&7u32.to_be_bytes());
+    bytes[24..28].copy_from_slice(&5u32.to_be_bytes());
+
+    let parsed: PduResponse<TaskMgmtResponse> = parse_imm(&bytes, &cfg)?;
+    let hdr = parsed.header_view()?;
+    assert_eq!(
+        BhsOpcode::try_from(hdr.opcode.raw())?.opcode,
+        Opcode::ScsiTaskMgmtResp
+    );
+    assert!(hdr.is_final());
+    assert_eq!(hdr.response.decode(), TmfResponseCode::TaskDoesNotExist);
+    assert_eq!(hdr.stat_sn.get(), 5);
+    assert_eq!(hdr.get_initiator_task_tag(), Itt::new(7)?);
+    Ok(())
+}
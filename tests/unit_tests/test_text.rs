@@ -39,7 +39,7 @@ fn test_text_request() -> Result<()> {
     let exp_sn = 1939077135;
 
     let header_builder = TextRequestBuilder::new()
-        .lun(0) // builder takes u64
+        .lun(0) // raw 64-bit LUN field
         .initiator_task_tag(itt)
         .target_task_tag(ttt)
         .cmd_sn(cmd_sn)